@@ -0,0 +1,150 @@
+//! Recorded GraphQL interactions, for testing without a live server
+//!
+//! A cassette is a JSON file of request/response pairs. A client built
+//! with `Client::with_recording` appends every exchange to one; a client
+//! from `Client::from_cassette` answers from it without touching the
+//! network. `state-cli serve http --record-cassette` writes the same
+//! format from the server side, capturing whatever clients talk to it.
+//!
+//! Requests match on their query, whitespace-insensitively, and their
+//! variables. Each recording is played once, in the order recorded, so a
+//! poll repeated five times replays five recorded answers.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Format version written to new cassettes
+pub const CASSETTE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Value,
+    /// The whole response body, `data` and `errors` alike
+    pub response: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub version: u32,
+    pub interactions: Vec<Interaction>,
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self { version: CASSETTE_VERSION, interactions: Vec::new() }
+    }
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let cassette: Cassette = serde_json::from_slice(&std::fs::read(path)?)?;
+        if cassette.version > CASSETTE_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("cassette version {} is newer than this client supports", cassette.version),
+            ));
+        }
+        Ok(cassette)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Collapse runs of whitespace so reformatted queries still match
+fn normalize(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Appends interactions to a cassette file
+///
+/// The file is rewritten after every interaction, so a test that dies
+/// midway still leaves a usable cassette.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl Recorder {
+    /// Start a new cassette at `path`, replacing any file there on the
+    /// first recording
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), cassette: Mutex::new(Cassette::default()) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, query: &str, variables: &Value, response: &Value) -> std::io::Result<()> {
+        let mut cassette = self.cassette.lock().unwrap_or_else(|e| e.into_inner());
+        cassette.interactions.push(Interaction {
+            query: normalize(query),
+            variables: variables.clone(),
+            response: response.clone(),
+        });
+        cassette.save(&self.path)
+    }
+}
+
+/// Answers requests from a loaded cassette
+#[derive(Debug)]
+pub(crate) struct Player {
+    interactions: Vec<Interaction>,
+    played: Vec<bool>,
+}
+
+impl Player {
+    pub(crate) fn new(cassette: Cassette) -> Self {
+        let played = vec![false; cassette.interactions.len()];
+        Self { interactions: cassette.interactions, played }
+    }
+
+    /// The response to the first unplayed recording of this request
+    pub(crate) fn play(&mut self, query: &str, variables: &Value) -> Option<Value> {
+        let query = normalize(query);
+        let index = self
+            .interactions
+            .iter()
+            .zip(&self.played)
+            .position(|(i, played)| !played && normalize(&i.query) == query && i.variables == *variables)?;
+        self.played[index] = true;
+        Some(self.interactions[index].response.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, ClientError};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.json", std::process::id()));
+        let recorder = Recorder::new(&path);
+        let query = "query($id: ID!) {\n  node(id: $id) { id }\n}";
+        recorder.record(query, &json!({"id": "01N"}), &json!({"data": {"node": null}})).unwrap();
+        recorder.record(query, &json!({"id": "01N"}), &json!({"data": {"node": {"id": "01N"}}})).unwrap();
+        recorder.record("{ apiVersion }", &json!({}), &json!({"errors": [{"message": "boom"}]})).unwrap();
+
+        let mut player = Player::new(Cassette::load(&path).unwrap());
+        let reformatted = "query($id: ID!) { node(id: $id) { id } }";
+        assert!(player.play(reformatted, &json!({"id": "01X"})).is_none());
+        assert_eq!(player.play(reformatted, &json!({"id": "01N"})).unwrap()["data"]["node"], Value::Null);
+        assert_eq!(player.play(reformatted, &json!({"id": "01N"})).unwrap()["data"]["node"]["id"], "01N");
+        assert!(player.play(reformatted, &json!({"id": "01N"})).is_none());
+
+        let replay = Client::from_cassette(&path).unwrap();
+        assert_eq!(replay.execute(query, json!({"id": "01N"})).await.unwrap()["node"], Value::Null);
+        assert!(matches!(replay.execute("{ apiVersion }", json!({})).await, Err(ClientError::Graphql(_))));
+        assert!(matches!(replay.execute("{ apiVersion }", json!({})).await, Err(ClientError::Cassette(_))));
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,58 @@
+//! JSON POST over HTTP or HTTPS
+//!
+//! The one transport behind this crate and the server crate's CLI,
+//! connectors and webhooks: ureq, with rustls and the Mozilla root
+//! certificates for `https://` URLs. It blocks, so the async client runs it
+//! on tokio's blocking pool.
+
+use serde_json::Value;
+use std::io::Read;
+use std::time::Duration;
+
+use crate::ClientError;
+
+/// POST `body` and read the JSON reply; an empty reply reads as `null`
+pub fn post_json<N: AsRef<str>, V: AsRef<str>>(
+    url: &str,
+    body: &Value,
+    headers: &[(N, V)],
+    timeout: Duration,
+) -> Result<Value, ClientError> {
+    let mut request = ureq::post(url)
+        .timeout(timeout)
+        .set("Content-Type", "application/json")
+        .set("Accept", "application/json");
+    for (name, value) in headers {
+        request = request.set(name.as_ref(), value.as_ref());
+    }
+    let response = match request.send_string(&body.to_string()) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(ClientError::Http(format!("{} {}", code, response.status_text())))
+        }
+        Err(e) => return Err(ClientError::Transport(format!("{}: {}", url, e))),
+    };
+
+    let mut body = String::new();
+    response
+        .into_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| ClientError::Transport(format!("{}: {}", url, e)))?;
+    if body.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&body).map_err(|e| ClientError::Decode(e.to_string()))
+}
+
+/// `post_json` without blocking the runtime
+pub(crate) async fn post_json_async(
+    url: &str,
+    body: &Value,
+    headers: &[(String, String)],
+    timeout: Duration,
+) -> Result<Value, ClientError> {
+    let (url, body, headers) = (url.to_string(), body.clone(), headers.to_vec());
+    tokio::task::spawn_blocking(move || post_json(&url, &body, &headers, timeout))
+        .await
+        .map_err(|e| ClientError::Transport(e.to_string()))?
+}
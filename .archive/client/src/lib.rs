@@ -0,0 +1,379 @@
+//! Typed async client for elegant-state servers
+//!
+//! Wraps the server's GraphQL API in methods returning plain Rust types, so
+//! agents written in Rust don't hand-roll query strings. The crate doesn't
+//! depend on the server crate (or sled), only on tokio, serde and ureq.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), elegant_state_client::ClientError> {
+//! use elegant_state_client::{Agent, Client, NewNode, NodeKind};
+//!
+//! let client = Client::new("http://127.0.0.1:4000/graphql").with_bearer_token("s3cret");
+//! let node = client.create_node(NewNode::new(NodeKind::Insight, serde_json::json!({"text": "hi"})), Agent::Claude).await?;
+//! let hits = client.search("hi", &[NodeKind::Insight]).await?;
+//! let mut events = client.subscribe_events(std::time::Duration::from_secs(2));
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(()) }
+//! ```
+
+pub mod cassette;
+pub mod http;
+mod types;
+
+pub use types::{Agent, ClockEntry, Edge, EdgeKind, Event, NewNode, NewProposal, Node, NodeKind, Proposal};
+
+use cassette::{Cassette, Player, Recorder};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Header selecting a namespace on the server
+pub const NAMESPACE_HEADER: &str = "x-state-namespace";
+
+/// Events fetched per poll by `subscribe_events`; more arriving between
+/// polls are missed
+pub const POLL_LIMIT: usize = 200;
+
+const NODE_FIELDS: &str = "id kind content metadata createdAt updatedAt expiresAt version";
+const EDGE_FIELDS: &str = "id from to kind weight metadata createdAt";
+const EVENT_FIELDS: &str = "id timestamp agent operation capture before after group origin clock { instance count }";
+const PROPOSAL_FIELDS: &str = "id proposer operation target payload rationale status createdAt";
+
+#[derive(Error, Debug, Clone)]
+pub enum ClientError {
+    #[error("Transport error: {0}")]
+    Transport(String),
+
+    #[error("HTTP error: {0}")]
+    Http(String),
+
+    #[error("GraphQL error: {0}")]
+    Graphql(String),
+
+    #[error("Unexpected response: {0}")]
+    Decode(String),
+
+    #[error("Cassette error: {0}")]
+    Cassette(String),
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+/// A server's GraphQL endpoint plus the headers sent with every request
+#[derive(Debug, Clone)]
+pub struct Client {
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+    cassette: Option<CassetteMode>,
+}
+
+#[derive(Debug, Clone)]
+enum CassetteMode {
+    Record(Arc<Recorder>),
+    Replay(Arc<Mutex<Player>>),
+}
+
+impl Client {
+    /// `url` is the full endpoint, e.g. `http://host:4000/graphql/v1`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into(), headers: Vec::new(), timeout: Duration::from_secs(30), cassette: None }
+    }
+
+    /// A client answering from a recorded cassette instead of a server
+    ///
+    /// Requests without an unplayed recording fail with
+    /// `ClientError::Cassette`.
+    pub fn from_cassette(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let cassette = Cassette::load(path).map_err(|e| ClientError::Cassette(format!("{}: {}", path.display(), e)))?;
+        let mut client = Self::new(format!("cassette:{}", path.display()));
+        client.cassette = Some(CassetteMode::Replay(Arc::new(Mutex::new(Player::new(cassette)))));
+        Ok(client)
+    }
+
+    /// Record every exchange with the server to a cassette at `path`
+    pub fn with_recording(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cassette = Some(CassetteMode::Record(Arc::new(Recorder::new(path))));
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send `Authorization: Bearer <token>`
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        let value = format!("Bearer {}", token.as_ref());
+        self.with_header("Authorization", value)
+    }
+
+    /// Select a namespace on the server
+    pub fn with_namespace(self, namespace: impl Into<String>) -> Self {
+        self.with_header(NAMESPACE_HEADER, namespace)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Run any operation and return its `data`; GraphQL errors are errors
+    pub async fn execute(&self, query: &str, variables: Value) -> Result<Value> {
+        let mut response = match &self.cassette {
+            Some(CassetteMode::Replay(player)) => replay(player, query, &variables)?,
+            _ => {
+                let body = json!({ "query": query, "variables": &variables });
+                let response = http::post_json_async(&self.url, &body, &self.headers, self.timeout).await?;
+                if let Some(CassetteMode::Record(recorder)) = &self.cassette {
+                    recorder.record(query, &variables, &response).map_err(|e| {
+                        ClientError::Cassette(format!("{}: {}", recorder.path().display(), e))
+                    })?;
+                }
+                response
+            }
+        };
+        match response.get("errors") {
+            Some(errors) if !errors.is_null() => Err(ClientError::Graphql(errors.to_string())),
+            _ => Ok(response.get_mut("data").map(Value::take).unwrap_or_default()),
+        }
+    }
+
+    /// Run an operation and decode `data.<field>`
+    async fn field<T: DeserializeOwned>(&self, field: &str, query: &str, variables: Value) -> Result<T> {
+        let mut data = self.execute(query, variables).await?;
+        let value = data.get_mut(field).map(Value::take).unwrap_or_default();
+        serde_json::from_value(value).map_err(|e| ClientError::Decode(format!("{}: {}", field, e)))
+    }
+
+    pub async fn node(&self, id: &str) -> Result<Option<Node>> {
+        let query = format!("query($id: ID!) {{ node(id: $id) {{ {} }} }}", NODE_FIELDS);
+        self.field("node", &query, json!({ "id": id })).await
+    }
+
+    pub async fn create_node(&self, node: NewNode, agent: Agent) -> Result<Node> {
+        let query = format!(
+            "mutation($input: CreateNodeInput!, $agent: AgentKind!) {{ createNode(input: $input, agent: $agent) {{ {} }} }}",
+            NODE_FIELDS
+        );
+        self.field("createNode", &query, json!({ "input": node, "agent": agent })).await
+    }
+
+    /// Replace a node's content; with `expected_version`, fail if another
+    /// writer got in first
+    pub async fn update_node(&self, id: &str, content: Value, expected_version: Option<u64>, agent: Agent) -> Result<Node> {
+        let query = format!(
+            "mutation($input: UpdateNodeInput!, $agent: AgentKind!) {{ updateNode(input: $input, agent: $agent) {{ {} }} }}",
+            NODE_FIELDS
+        );
+        let input = json!({ "id": id, "content": content, "expectedVersion": expected_version });
+        self.field("updateNode", &query, json!({ "input": input, "agent": agent })).await
+    }
+
+    /// Nodes whose content contains `query`; empty `kinds` means any kind
+    pub async fn search(&self, query: &str, kinds: &[NodeKind]) -> Result<Vec<Node>> {
+        let operation = format!(
+            "query($query: String!, $kinds: [NodeKind!]) {{ search(query: $query, kinds: $kinds) {{ {} }} }}",
+            NODE_FIELDS
+        );
+        let kinds = if kinds.is_empty() { Value::Null } else { json!(kinds) };
+        self.field("search", &operation, json!({ "query": query, "kinds": kinds })).await
+    }
+
+    pub async fn create_edge(&self, from: &str, to: &str, kind: EdgeKind, agent: Agent) -> Result<Edge> {
+        let query = format!(
+            "mutation($input: CreateEdgeInput!, $agent: AgentKind!) {{ createEdge(input: $input, agent: $agent) {{ {} }} }}",
+            EDGE_FIELDS
+        );
+        let input = json!({ "from": from, "to": to, "kind": kind });
+        self.field("createEdge", &query, json!({ "input": input, "agent": agent })).await
+    }
+
+    pub async fn submit_proposal(&self, proposal: NewProposal, agent: Agent) -> Result<Proposal> {
+        let query = format!(
+            "mutation($input: SubmitProposalInput!, $agent: AgentKind!) {{ submitProposal(input: $input, agent: $agent) {{ {} }} }}",
+            PROPOSAL_FIELDS
+        );
+        self.field("submitProposal", &query, json!({ "input": proposal, "agent": agent })).await
+    }
+
+    /// The most recent events, newest first
+    pub async fn events(&self, limit: usize) -> Result<Vec<Event>> {
+        let query = format!("query($limit: Int!) {{ events(limit: $limit) {{ {} }} }}", EVENT_FIELDS);
+        self.field("events", &query, json!({ "limit": limit })).await
+    }
+
+    /// Events logged from now on, oldest first
+    ///
+    /// Polls the `events` query every `interval` from a background task, so
+    /// it needs a tokio runtime; the server's websocket subscriptions would
+    /// need a websocket client this crate doesn't carry. Dropping the
+    /// subscription stops the polling. A failed poll is delivered as an
+    /// error and polling carries on.
+    pub fn subscribe_events(&self, interval: Duration) -> EventSubscription {
+        let (sender, receiver) = mpsc::channel(POLL_LIMIT);
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut cursor = EventCursor::default();
+            loop {
+                let batch = match client.events(POLL_LIMIT).await {
+                    Ok(events) => cursor.advance(events).into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                for item in batch {
+                    if sender.send(item).await.is_err() {
+                        return;
+                    }
+                }
+                if sender.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        EventSubscription { receiver }
+    }
+}
+
+/// Kept out of `execute` so the lock guard never lives across an await
+fn replay(player: &Mutex<Player>, query: &str, variables: &Value) -> Result<Value> {
+    player
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .play(query, variables)
+        .ok_or_else(|| ClientError::Cassette(format!("no recording left for {} with {}", query, variables)))
+}
+
+/// Events from `Client::subscribe_events`
+pub struct EventSubscription {
+    receiver: mpsc::Receiver<Result<Event>>,
+}
+
+impl EventSubscription {
+    /// The next event; `None` once the polling task has stopped
+    pub async fn next(&mut self) -> Option<Result<Event>> {
+        self.receiver.recv().await
+    }
+}
+
+/// Where a subscriber has read up to in the event log
+#[derive(Debug, Default)]
+struct EventCursor {
+    /// Newest event already seen; `None` until the first poll
+    last_seen: Option<String>,
+}
+
+impl EventCursor {
+    /// The events after `last_seen`, oldest first, given newest first; the
+    /// first batch only marks where the log ends
+    fn advance(&mut self, events: Vec<Event>) -> Vec<Event> {
+        let Some(newest) = events.first().map(|e| e.id.clone()) else {
+            return Vec::new();
+        };
+        let fresh: Vec<Event> = match self.last_seen.replace(newest) {
+            // Event IDs are ULIDs, so they sort by time as strings
+            Some(last) => events.into_iter().take_while(|e| e.id > last).collect(),
+            None => Vec::new(),
+        };
+        fresh.into_iter().rev().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_typed_calls() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for reply in [
+                json!({"data": {"createNode": {
+                    "id": "01N", "kind": "INSIGHT", "content": {"text": "hi"}, "metadata": {},
+                    "createdAt": "2024-05-01T00:00:00+00:00", "updatedAt": "2024-05-01T00:00:00+00:00",
+                    "expiresAt": null, "version": 1
+                }}}),
+                json!({"data": null, "errors": [{"message": "Unknown operation: merge"}]}),
+            ] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                // The request is small enough to arrive whole once the body's length is in
+                loop {
+                    let n = stream.read(&mut buffer).await.unwrap();
+                    assert!(n > 0, "client hung up mid-request");
+                    request.extend_from_slice(&buffer[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length: usize = head
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                            .unwrap()
+                            .parse()
+                            .unwrap();
+                        if body.len() >= length {
+                            requests.push(text);
+                            break;
+                        }
+                    }
+                }
+                let body = reply.to_string();
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        let client = Client::new(url).with_namespace("team");
+        let node = client
+            .create_node(NewNode::new(NodeKind::Insight, json!({"text": "hi"})), Agent::Claude)
+            .await
+            .unwrap();
+        assert_eq!((node.id.as_str(), node.kind, node.version), ("01N", NodeKind::Insight, 1));
+        let error = client.submit_proposal(NewProposal::new("merge", "node:01N", json!({})), Agent::User).await;
+        assert!(matches!(error, Err(ClientError::Graphql(message)) if message.contains("Unknown operation")));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].contains("x-state-namespace: team"));
+        assert!(requests[0].contains(r#""agent":"CLAUDE""#) && requests[0].contains(r#""kind":"INSIGHT""#));
+        assert!(requests[1].contains("submitProposal"));
+
+        assert!(matches!(Client::new("https://example.org").events(1).await, Err(ClientError::Transport(_))));
+    }
+
+    #[test]
+    fn test_event_cursor() {
+        let event = |id: &str| Event {
+            id: id.into(),
+            timestamp: String::new(),
+            agent: "user".into(),
+            operation: "Create".into(),
+            capture: "full".into(),
+            before: None,
+            after: None,
+            group: None,
+            origin: None,
+            clock: Vec::new(),
+        };
+        let mut cursor = EventCursor::default();
+        assert!(cursor.advance(vec![event("01B"), event("01A")]).is_empty());
+        let fresh = cursor.advance(vec![event("01D"), event("01C"), event("01B")]);
+        assert_eq!(fresh.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["01C", "01D"]);
+        assert!(cursor.advance(vec![event("01D")]).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Records as the server's GraphQL API returns them
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NodeKind {
+    Conversation,
+    Project,
+    Insight,
+    Task,
+    Context,
+    Module,
+    Agent,
+    /// A registered custom kind, written `custom:<name>`
+    #[serde(untagged)]
+    Custom(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EdgeKind {
+    References,
+    DerivedFrom,
+    RelatedTo,
+    PartOf,
+    Blocks,
+    Enables,
+    Supersedes,
+    /// A registered custom kind, written `custom:<name>`
+    #[serde(untagged)]
+    Custom(String),
+}
+
+/// Who a write is recorded as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Agent {
+    #[default]
+    User,
+    Claude,
+    Llama,
+    System,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    pub id: String,
+    pub kind: NodeKind,
+    pub content: Value,
+    pub metadata: Value,
+    pub created_at: String,
+    pub updated_at: String,
+    pub expires_at: Option<String>,
+    pub version: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Edge {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+    pub weight: f32,
+    pub metadata: Value,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub id: String,
+    pub timestamp: String,
+    pub agent: String,
+    pub operation: String,
+    pub capture: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub group: Option<String>,
+    /// Instance that first recorded the event
+    #[serde(default)]
+    pub origin: Option<String>,
+    #[serde(default)]
+    pub clock: Vec<ClockEntry>,
+}
+
+/// One instance's count in an event's vector clock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockEntry {
+    pub instance: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal {
+    pub id: String,
+    pub proposer: String,
+    pub operation: String,
+    pub target: Value,
+    pub payload: Value,
+    pub rationale: Option<String>,
+    pub status: String,
+    pub created_at: String,
+}
+
+/// A node to create
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewNode {
+    pub kind: NodeKind,
+    pub content: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    /// Delete the node this many seconds after creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<i64>,
+}
+
+impl NewNode {
+    pub fn new(kind: NodeKind, content: Value) -> Self {
+        Self { kind, content, metadata: None, ttl_seconds: None }
+    }
+
+    pub fn with_metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_ttl_seconds(mut self, ttl_seconds: i64) -> Self {
+        self.ttl_seconds = Some(ttl_seconds);
+        self
+    }
+}
+
+/// A mutation to put to a vote
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewProposal {
+    /// create, update, delete, link or unlink
+    pub operation: String,
+    /// `node:ID`, `edge:ID` or `new:kind`
+    pub target: String,
+    pub payload: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rationale: Option<String>,
+}
+
+impl NewProposal {
+    pub fn new(operation: impl Into<String>, target: impl Into<String>, payload: Value) -> Self {
+        Self { operation: operation.into(), target: target.into(), payload, rationale: None }
+    }
+
+    pub fn with_rationale(mut self, rationale: impl Into<String>) -> Self {
+        self.rationale = Some(rationale.into());
+        self
+    }
+}
@@ -0,0 +1,289 @@
+//! Citation-traceable answers over the state graph
+//!
+//! A question is answered from nodes retrieved out of the graph. The answer
+//! is stored as an `Insight` node with a `DerivedFrom` edge to every node it
+//! cites, so each answer can be traced back to its sources.
+//!
+//! The language model is pluggable through [`AnswerModel`]. Models cite a
+//! source by writing its node ID in square brackets, e.g. `[01J...]`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+use crate::schema::{AgentId, EdgeKind, NodeId, NodeKind, StateEdge, StateNode};
+use crate::store::{node_text, Store, StoreError};
+
+/// Number of nodes retrieved for a question by default
+pub const DEFAULT_TOP_K: usize = 5;
+
+/// Characters of a source's content shown to the model
+const SOURCE_CHARS: usize = 1200;
+
+#[derive(Error, Debug)]
+pub enum AskError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    #[error("Model error: {0}")]
+    Model(String),
+
+    #[error("No nodes match the question")]
+    NoSources,
+}
+
+pub type Result<T> = std::result::Result<T, AskError>;
+
+/// A retrieved node as presented to the model
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub id: NodeId,
+    pub kind: NodeKind,
+    pub text: String,
+    pub score: usize,
+}
+
+/// Generates an answer from retrieved sources
+pub trait AnswerModel {
+    /// Answer `question` using only `sources`, citing them as `[<node id>]`
+    fn answer(&self, question: &str, sources: &[Source]) -> Result<String>;
+}
+
+/// Model that runs an external command (e.g. `ollama run llama3`), writing
+/// the prompt to its stdin and reading the answer from stdout
+pub struct CommandModel {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandModel {
+    /// Build from a shell-style command line split on whitespace
+    pub fn from_command_line(command: &str) -> Result<Self> {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        let program = parts
+            .next()
+            .ok_or_else(|| AskError::Model("empty model command".into()))?;
+        Ok(Self {
+            program,
+            args: parts.collect(),
+        })
+    }
+}
+
+impl AnswerModel for CommandModel {
+    fn answer(&self, question: &str, sources: &[Source]) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AskError::Model(format!("failed to run {}: {}", self.program, e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(build_prompt(question, sources).as_bytes())
+                .map_err(|e| AskError::Model(format!("failed to write prompt: {}", e)))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| AskError::Model(format!("{} failed: {}", self.program, e)))?;
+        if !output.status.success() {
+            return Err(AskError::Model(String::from_utf8_lossy(&output.stderr).into_owned()));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Offline model that answers by quoting the best-matching sources
+#[derive(Default)]
+pub struct ExtractiveModel;
+
+impl AnswerModel for ExtractiveModel {
+    fn answer(&self, _question: &str, sources: &[Source]) -> Result<String> {
+        Ok(sources
+            .iter()
+            .take(3)
+            .map(|s| format!("{} [{}]", first_sentence(&s.text), s.id))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+/// An answer stored in the graph
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub text: String,
+    pub citations: Vec<NodeId>,
+    /// The `Insight` node holding the answer
+    pub node: StateNode,
+}
+
+/// Retrieves sources, asks the model and records the answer
+pub struct Asker<'a, S: Store> {
+    store: &'a S,
+    model: &'a dyn AnswerModel,
+    top_k: usize,
+}
+
+impl<'a, S: Store> Asker<'a, S> {
+    pub fn new(store: &'a S, model: &'a dyn AnswerModel) -> Self {
+        Self {
+            store,
+            model,
+            top_k: DEFAULT_TOP_K,
+        }
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k.max(1);
+        self
+    }
+
+    /// Nodes sharing the most terms with the question, best first
+    pub fn retrieve(&self, question: &str) -> Result<Vec<Source>> {
+        let terms = terms(question);
+        let mut sources: Vec<Source> = self
+            .store
+            .list_nodes(None, usize::MAX)?
+            .into_iter()
+            .filter_map(|node| {
+                let text = node_text(&node);
+                let haystack = text.to_lowercase();
+                let score = terms.iter().filter(|t| haystack.contains(t.as_str())).count();
+                (score > 0).then(|| Source {
+                    id: node.id,
+                    kind: node.kind,
+                    text,
+                    score,
+                })
+            })
+            .collect();
+
+        sources.sort_by(|a, b| b.score.cmp(&a.score).then(b.id.cmp(&a.id)));
+        sources.truncate(self.top_k);
+        Ok(sources)
+    }
+
+    /// Answer a question and store it as an `Insight` derived from its citations
+    ///
+    /// Only IDs of retrieved sources count as citations. If the model cites
+    /// none of them, every source it was shown is recorded instead.
+    pub fn ask(&self, question: &str, agent: AgentId) -> Result<Answer> {
+        let sources = self.retrieve(question)?;
+        if sources.is_empty() {
+            return Err(AskError::NoSources);
+        }
+
+        let text = self.model.answer(question, &sources)?;
+        let mut citations: Vec<NodeId> = sources
+            .iter()
+            .map(|s| s.id)
+            .filter(|id| text.contains(&format!("[{}]", id)))
+            .collect();
+        if citations.is_empty() {
+            citations = sources.iter().map(|s| s.id).collect();
+        }
+
+        let node = StateNode::new(
+            NodeKind::Insight,
+            serde_json::json!({
+                "question": question,
+                "answer": text,
+                "citations": citations.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            }),
+        );
+        let node = self.store.create_node(node, agent.clone())?;
+        for cited in &citations {
+            self.store
+                .create_edge(StateEdge::new(node.id, *cited, EdgeKind::DerivedFrom), agent.clone())?;
+        }
+
+        Ok(Answer {
+            text,
+            citations,
+            node,
+        })
+    }
+}
+
+fn build_prompt(question: &str, sources: &[Source]) -> String {
+    let mut prompt = String::from(
+        "Answer the question using only the sources below. \
+         Cite every source you use by writing its ID in square brackets, e.g. [ID].\n\n",
+    );
+    for source in sources {
+        let excerpt: String = source.text.chars().take(SOURCE_CHARS).collect();
+        prompt.push_str(&format!("Source [{}] ({}):\n{}\n\n", source.id, source.kind, excerpt));
+    }
+    prompt.push_str(&format!("Question: {}\nAnswer:", question));
+    prompt
+}
+
+/// Lowercased question words worth matching on
+fn terms(question: &str) -> Vec<String> {
+    let mut terms: Vec<String> = question
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn first_sentence(text: &str) -> &str {
+    let end = text.find(['.', '\n']).map(|i| i + 1).unwrap_or(text.len());
+    text[..end].trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_answer_is_linked_to_citations() {
+        let store = SledStore::open_temporary().unwrap();
+        let sled = store
+            .create_node(
+                StateNode::new(
+                    NodeKind::Insight,
+                    serde_json::json!({"text": "sled is an embedded database. It is fast."}),
+                ),
+                AgentId::User,
+            )
+            .unwrap();
+        store
+            .create_node(
+                StateNode::new(NodeKind::Task, serde_json::json!({"text": "water the plants"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let model = ExtractiveModel;
+        let answer = Asker::new(&store, &model)
+            .ask("Which embedded database is used?", AgentId::Claude)
+            .unwrap();
+
+        assert_eq!(answer.citations, [sled.id]);
+        assert!(answer.text.contains(&sled.id.to_string()));
+
+        let edges = store.edges_from(answer.node.id).unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].to, sled.id);
+        assert_eq!(edges[0].kind, EdgeKind::DerivedFrom);
+    }
+
+    #[test]
+    fn test_no_sources() {
+        let store = SledStore::open_temporary().unwrap();
+        let model = ExtractiveModel;
+        assert!(matches!(
+            Asker::new(&store, &model).ask("anything here?", AgentId::User),
+            Err(AskError::NoSources)
+        ));
+    }
+}
@@ -1,27 +1,19 @@
 use clap::Subcommand;
-use super::CapabilityModeArg;
+use elegant_state::CapabilityMode;
 
 #[derive(Subcommand)]
 pub enum AgentCommands {
-    /// List all configured agents
+    /// List agents with capability overrides or watchdog demotions
     List {
-        /// Show detailed information
-        #[arg(short, long)]
-        verbose: bool,
-
-        /// Include reputation scores
+        /// Output as JSON
         #[arg(long)]
-        reputation: bool,
+        json: bool,
     },
 
     /// Show agent configuration
     Show {
         /// Agent name (user, claude, llama, system, or module:*)
         agent: String,
-
-        /// Show reputation history
-        #[arg(long)]
-        history: bool,
     },
 
     /// Set agent capabilities
@@ -29,9 +21,9 @@ pub enum AgentCommands {
         /// Agent name
         agent: String,
 
-        /// Capability mode
-        #[arg(short, long, value_enum)]
-        mode: Option<CapabilityModeArg>,
+        /// Capability mode (direct, proposal, observer)
+        #[arg(short, long)]
+        mode: Option<CapabilityMode>,
 
         /// Can vote on proposals
         #[arg(long)]
@@ -42,64 +34,9 @@ pub enum AgentCommands {
         vote_weight: Option<f32>,
     },
 
-    /// Register a new module agent
-    Register {
-        /// Module name
-        name: String,
-
-        /// Initial capability mode
-        #[arg(short, long, value_enum, default_value = "proposal")]
-        mode: CapabilityModeArg,
-
-        /// Module description
-        #[arg(short, long)]
-        description: Option<String>,
-    },
-
-    /// Unregister a module agent
-    Unregister {
-        /// Module name
-        name: String,
-
-        /// Skip confirmation
-        #[arg(long)]
-        force: bool,
-    },
-
-    /// Show reputation leaderboard
-    Leaderboard {
-        /// Number of entries to show
-        #[arg(short, long, default_value = "10")]
-        limit: usize,
-
-        /// Sort by (score, accuracy, votes)
-        #[arg(long, default_value = "score")]
-        sort: String,
-    },
-
-    /// Reset agent reputation
-    ResetReputation {
-        /// Agent name (or "all" for all agents)
-        agent: String,
-
-        /// Skip confirmation
-        #[arg(long)]
-        force: bool,
-    },
-
-    /// Apply reputation decay
-    Decay {
-        /// Decay factor (0.0 - 1.0)
-        #[arg(long, default_value = "0.99")]
-        factor: f32,
-    },
-
-    /// Switch current agent identity
-    Switch {
-        /// Agent to switch to
+    /// Lift a watchdog demotion, restoring the agent's configured mode
+    Reinstate {
+        /// Agent name
         agent: String,
     },
-
-    /// Show current agent identity
-    Whoami,
 }
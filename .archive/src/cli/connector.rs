@@ -0,0 +1,36 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum ConnectorCommands {
+    /// List registered connectors
+    List,
+
+    /// Register an external source consulted by `search fulltext --federated`
+    Add {
+        /// Connector name, recorded as the provenance of cached nodes
+        name: String,
+
+        /// Command run with the query as its last argument, printing
+        /// records as JSON ({"external_id", "content", "kind"?, "url"?})
+        #[arg(long, conflicts_with = "url", required_unless_present = "url")]
+        command: Option<String>,
+
+        /// GraphQL endpoint of another elegant-state server
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Namespace to search on the remote server
+        #[arg(long, requires = "url")]
+        namespace: Option<String>,
+
+        /// How long cached nodes live (e.g., "1h", "7d")
+        #[arg(long, default_value = "1d")]
+        ttl: String,
+    },
+
+    /// Remove a connector
+    Remove {
+        /// Connector name
+        name: String,
+    },
+}
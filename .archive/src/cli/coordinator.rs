@@ -0,0 +1,44 @@
+use clap::Subcommand;
+use super::VotingStrategyArg;
+
+#[derive(Subcommand)]
+pub enum CoordinatorCommands {
+    /// Simulate the proposal/voting/reputation loop with synthetic agents
+    Simulate {
+        /// Number of synthetic agents
+        #[arg(long, default_value = "5")]
+        agents: usize,
+
+        /// Number of proposals to run
+        #[arg(long, default_value = "100")]
+        proposals: usize,
+
+        /// Voting strategy under test
+        #[arg(long, value_enum, default_value = "simple-majority")]
+        strategy: VotingStrategyArg,
+
+        /// Approval threshold for the weighted strategy (default: half the agents)
+        #[arg(long)]
+        threshold: Option<f32>,
+
+        /// Behavior mix as honest,random,adversarial counts (e.g. "3,1,1")
+        #[arg(long)]
+        mix: Option<String>,
+
+        /// Probability that an honest agent votes correctly (0.0 - 1.0)
+        #[arg(long, default_value = "0.9")]
+        honest_accuracy: f64,
+
+        /// Disable reputation-weighted votes
+        #[arg(long)]
+        no_reputation: bool,
+
+        /// RNG seed for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
@@ -3,6 +3,9 @@
 #[derive(Subcommand)]
 pub enum DbCommands {
     /// Show database statistics
+    ///
+    /// Store operation timings are kept per process; a running server
+    /// serves its own at `/metrics`.
     Stats {
         /// Show detailed statistics
         #[arg(short, long)]
@@ -11,58 +14,20 @@ pub enum DbCommands {
         /// Include index statistics
         #[arg(long)]
         index: bool,
-    },
-
-    /// Initialize a new database
-    Init {
-        /// Database path (uses default if not specified)
-        path: Option<String>,
 
-        /// Force initialization even if database exists
+        /// Show compression ratio achieved on node values
         #[arg(long)]
-        force: bool,
-    },
+        compression: bool,
 
-    /// Backup database
-    Backup {
-        /// Backup destination (directory or file)
-        output: Option<String>,
-
-        /// Backup name (default: timestamp)
-        #[arg(short, long)]
-        name: Option<String>,
-
-        /// Compress backup
+        /// Recount the cached kind, agent and day counters from scratch
         #[arg(long)]
-        compress: bool,
-
-        /// Include full-text index
-        #[arg(long)]
-        include_index: bool,
+        recount: bool,
     },
 
-    /// Restore database from backup
-    Restore {
-        /// Backup file or directory
-        backup: String,
-
-        /// Skip confirmation
-        #[arg(long)]
-        force: bool,
-
-        /// Verify backup integrity before restore
-        #[arg(long)]
-        verify: bool,
-    },
-
-    /// List available backups
-    Backups {
-        /// Backup directory
-        #[arg(short, long)]
-        dir: Option<String>,
-    },
-
-    /// Compact database
+    /// Compact database in place, reporting bytes reclaimed
+    ///
+    /// A running server can be compacted without downtime through the
+    /// `compactDatabase` GraphQL mutation.
     Compact {
         /// Compaction threshold in MB
         #[arg(long)]
@@ -71,11 +36,20 @@ pub enum DbCommands {
         /// Force compaction regardless of threshold
         #[arg(long)]
         force: bool,
+
+        /// Report reclaimable space without compacting
+        #[arg(long)]
+        dry_run: bool,
     },
 
-    /// Verify database integrity
-    Verify {
-        /// Fix issues if possible
+    /// Verify database integrity (fsck)
+    ///
+    /// Checks that edges point at existing nodes, events resolve, indexes
+    /// match the records and stored JSON parses. `--fix` repairs indexes
+    /// and moves broken records to the quarantine tree.
+    #[command(alias = "verify")]
+    Check {
+        /// Repair indexes and quarantine broken records
         #[arg(long)]
         fix: bool,
 
@@ -84,17 +58,6 @@ pub enum DbCommands {
         index: bool,
     },
 
-    /// Repair database
-    Repair {
-        /// Skip confirmation
-        #[arg(long)]
-        force: bool,
-
-        /// Create backup before repair
-        #[arg(long, default_value = "true")]
-        backup: bool,
-    },
-
     /// Migrate database schema
     Migrate {
         /// Target schema version
@@ -110,24 +73,232 @@ pub enum DbCommands {
         dry_run: bool,
     },
 
+    /// Write every namespace to a portable, checksummed dump file
+    ///
+    /// Dumps do not depend on the on-disk format, so they move data
+    /// between storage backends and crate versions.
+    Dump {
+        /// Output file
+        output: String,
+    },
+
+    /// Load a dump into an empty database
+    Load {
+        /// Dump file
+        input: String,
+
+        /// Only check the file's framing and checksum
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Rebuild the nodes and edges, and their indexes, from the event log
+    ///
+    /// For recovering a damaged node or edge tree. The archive tier,
+    /// annotations, attachments and settings are kept as they are.
+    Rebuild {
+        /// Replay the event log (the only source for now)
+        #[arg(long)]
+        from_events: bool,
+
+        /// Replay and compare without touching the database
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Rebuild even if some events can't be replayed
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Read the graph as it stood at a past time, rebuilt from the event
+    /// log into a read-only snapshot
+    At {
+        /// RFC 3339 time, or an age like "2d"
+        time: String,
+
+        #[command(subcommand)]
+        command: AtCommands,
+    },
+
     /// Show database path
     Path,
 
-    /// Reset database (destructive!)
-    Reset {
+    /// List namespaces in the database
+    Namespaces,
+
+    /// Named snapshots of the whole database
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// Delete expired nodes and their edges, and archive nodes their
+    /// retention class says to
+    Gc {
+        /// Only list the nodes that would be deleted or archived
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show or set retention classes and what each one means
+    Retention {
+        #[command(subcommand)]
+        command: RetentionCommands,
+    },
+
+    /// Show or set how events capture node payloads
+    Capture {
+        /// full, diff (RFC 6902 patches on update) or hash (digests only);
+        /// omit to show the current policy
+        mode: Option<String>,
+
+        /// Apply to one node kind instead of the default
+        #[arg(short, long)]
+        kind: Option<String>,
+
+        /// Drop the override for --kind
+        #[arg(long, requires = "kind", conflicts_with = "mode")]
+        clear: bool,
+    },
+
+    /// Show or set metadata stamped on every node an agent creates
+    Stamp {
+        /// user, claude, llama, system or module:<name>; omit to list all
+        agent: Option<String>,
+
+        /// Fields to stamp as key=value; values parse as JSON, else as text
+        #[arg(requires = "agent")]
+        fields: Vec<String>,
+
+        /// Stop stamping a field
+        #[arg(long, requires = "agent")]
+        remove: Vec<String>,
+
+        /// Drop all of the agent's defaults
+        #[arg(long, requires = "agent", conflicts_with_all = ["fields", "remove"])]
+        clear: bool,
+    },
+
+    /// Show how another instance's imported node IDs map to local ones
+    Remaps {
+        /// Source instance ID; omit to show this instance's own
+        source: Option<String>,
+
+        /// Forget the mapping, so the next import creates fresh nodes
+        #[arg(long, requires = "source")]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AtCommands {
+    /// Export the graph as it stood then, as `export` does
+    Export {
+        /// Output format (json, dot, mermaid, graphml)
+        #[arg(short, long, default_value = "json")]
+        format: String,
+
+        /// Only export this node and its surroundings, with their edges
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Hops from --root to include
+        #[arg(long, default_value = "1", requires = "root")]
+        depth: usize,
+
+        /// Only follow and export edges of this kind (repeatable)
+        #[arg(short, long = "kind", requires = "root")]
+        kinds: Vec<elegant_state::schema::EdgeKind>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Checkpoint the current graph under a name
+    Create {
+        /// Snapshot name
+        name: String,
+
+        /// Snapshot directory (default: <db-path>.snapshots)
+        #[arg(long)]
+        dir: Option<String>,
+    },
+
+    /// List snapshots
+    List {
+        /// Snapshot directory (default: <db-path>.snapshots)
+        #[arg(long)]
+        dir: Option<String>,
+    },
+
+    /// Roll the database back to a snapshot, saving the current one as `pre-restore-<time>`
+    Restore {
+        /// Snapshot name
+        name: String,
+
+        /// Snapshot directory (default: <db-path>.snapshots)
+        #[arg(long)]
+        dir: Option<String>,
+
         /// Skip confirmation
         #[arg(long)]
         force: bool,
+    },
+}
 
-        /// Create backup before reset
+#[derive(Subcommand)]
+pub enum RetentionCommands {
+    /// Show the class of each kind and the rules of each class
+    Show {
+        /// Output as JSON
         #[arg(long)]
-        backup: bool,
+        json: bool,
+    },
+
+    /// Put a kind in a class, or set the default class without --kind
+    Assign {
+        /// ephemeral, standard or permanent
+        class: String,
+
+        /// Node kind
+        #[arg(short, long)]
+        kind: Option<String>,
     },
 
-    /// Vacuum database (reclaim space)
-    Vacuum {
-        /// Show progress
+    /// Let a kind fall back to the default class
+    Clear {
+        /// Node kind
+        kind: String,
+    },
+
+    /// Change what a class means
+    Rules {
+        /// ephemeral, standard or permanent
+        class: String,
+
+        /// Expire nodes this long after creation (e.g., "7d")
+        #[arg(long, conflicts_with = "no_ttl")]
+        ttl: Option<String>,
+
+        /// Stop expiring the class's nodes
+        #[arg(long)]
+        no_ttl: bool,
+
+        /// Archive nodes on `db gc` after this long without an update
+        #[arg(long, conflicts_with = "no_archive")]
+        archive_after: Option<String>,
+
+        /// Stop archiving the class's nodes
+        #[arg(long)]
+        no_archive: bool,
+
+        /// Capture mode for the class's events: full, diff, hash, or policy
+        /// to follow `db capture`
+        #[arg(long)]
+        capture: Option<String>,
+
+        /// Include the class's nodes in dumps
         #[arg(long)]
-        progress: bool,
+        backup: Option<bool>,
     },
 }
@@ -0,0 +1,36 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum DevCommands {
+    /// Create fabricated nodes of one kind, linked to earlier nodes, for
+    /// demos and stress tests
+    Generate {
+        /// Node kind (conversation, project, insight, task, context, module, agent, or custom:*)
+        #[arg(short, long)]
+        kind: String,
+
+        /// Nodes to create
+        #[arg(short, long, default_value = "10")]
+        count: usize,
+
+        /// Build content from the kind's JSON Schema
+        #[arg(long)]
+        respect_schema: bool,
+
+        /// Average edges from each new node to earlier ones
+        #[arg(long, default_value = "1.0")]
+        edges: f64,
+
+        /// RNG seed for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Creating agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "system")]
+        agent: String,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
@@ -19,6 +19,28 @@ pub enum EdgeCommands {
         /// Edge weight (0.0 - 1.0)
         #[arg(short, long)]
         weight: Option<f32>,
+
+        /// Metadata as key=value, e.g. reason="same root cause"; values parse as JSON, else as text
+        #[arg(long = "meta")]
+        metadata: Vec<String>,
+    },
+
+    /// Change an edge's weight or metadata
+    Update {
+        /// Edge ID
+        id: String,
+
+        /// New weight (0.0 - 1.0)
+        #[arg(short, long)]
+        weight: Option<f32>,
+
+        /// Metadata to set as key=value; values parse as JSON, else as text
+        #[arg(long = "meta")]
+        metadata: Vec<String>,
+
+        /// Metadata fields to remove
+        #[arg(long)]
+        unset: Vec<String>,
     },
 
     /// List edges from a node
@@ -38,4 +60,11 @@ pub enum EdgeCommands {
         /// Edge ID
         id: String,
     },
+
+    /// Delete edges whose source or target node no longer exists
+    PruneOrphans {
+        /// List orphaned edges without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
@@ -0,0 +1,36 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum EmbeddingCommands {
+    /// Embed nodes through an external command, reusing cached vectors
+    Embed {
+        /// Node IDs (default: every node, or every node of --kind)
+        ids: Vec<String>,
+
+        /// Only embed nodes of this kind
+        #[arg(short, long, conflicts_with = "ids")]
+        kind: Option<String>,
+
+        /// Model name the vectors are cached under
+        #[arg(short, long)]
+        model: String,
+
+        /// Command that reads text on stdin and writes a JSON array of numbers
+        #[arg(short, long)]
+        command: String,
+    },
+
+    /// Show cached vectors and the hit rate per model
+    Stats {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Drop cached vectors, e.g. after a model changes behind the same name
+    Purge {
+        /// Only this model's vectors (default: all)
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+}
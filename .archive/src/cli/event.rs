@@ -0,0 +1,16 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum EventCommands {
+    /// Rebuild a fresh database purely from the event log and check that
+    /// it matches this one
+    Replay {
+        /// Path of the database to build (must be empty)
+        #[arg(long)]
+        into: String,
+
+        /// List every event that could not be applied
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
@@ -0,0 +1,298 @@
+use clap::Subcommand;
+use elegant_state::schema::EdgeKind;
+use elegant_state::store::rank::Centrality;
+use elegant_state::store::PathMode;
+use elegant_state::viz::DiagramFormat;
+use elegant_state::NodeKind;
+
+#[derive(Subcommand)]
+pub enum GraphCommands {
+    /// Show the cheapest or strongest path between two nodes, following
+    /// edges either way
+    Path {
+        /// Start node ID
+        from: String,
+
+        /// End node ID
+        to: String,
+
+        /// Only follow edges of this kind (repeatable)
+        #[arg(short, long = "kind")]
+        kinds: Vec<EdgeKind>,
+
+        /// What to optimize: cost (lowest weight sum) or strongest (highest
+        /// weight product)
+        #[arg(short, long, default_value = "cost")]
+        mode: PathMode,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Score nodes by PageRank or betweenness centrality and store the
+    /// scores in node metadata
+    Rank {
+        /// Centrality measure (pagerank, betweenness)
+        #[arg(short, long, default_value = "pagerank")]
+        algorithm: Centrality,
+
+        /// Only follow edges of this kind (repeatable)
+        #[arg(short, long = "kind")]
+        kinds: Vec<EdgeKind>,
+
+        /// Only rank nodes of these kinds
+        #[arg(long, value_delimiter = ',')]
+        node_kinds: Option<Vec<NodeKind>>,
+
+        /// PageRank damping factor
+        #[arg(long, default_value = "0.85")]
+        damping: f64,
+
+        /// Metadata field to write scores to [default: the algorithm name]
+        #[arg(long)]
+        field: Option<String>,
+
+        /// Number of top nodes to print
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+
+        /// Print scores without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Group densely linked nodes into topic clusters by label propagation
+    /// and store each node's cluster number in its metadata
+    Clusters {
+        /// Edge kind that links a topic (repeatable) [default: related_to, references]
+        #[arg(short, long = "kind")]
+        kinds: Vec<EdgeKind>,
+
+        /// Only cluster nodes of these kinds
+        #[arg(long, value_delimiter = ',')]
+        node_kinds: Option<Vec<NodeKind>>,
+
+        /// Metadata field to write cluster numbers to
+        #[arg(long, default_value = "cluster")]
+        field: String,
+
+        /// Smallest cluster to print
+        #[arg(long, default_value = "2")]
+        min_size: usize,
+
+        /// Print clusters without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List loops among edges of the given kinds
+    Cycles {
+        /// Edge kind to check (repeatable) [default: blocks, part_of]
+        #[arg(short, long = "kind")]
+        kinds: Vec<EdgeKind>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show or set the edge kinds new edges may not close a cycle in
+    Acyclic {
+        /// Edge kinds to enforce; omit to show the current setting
+        kinds: Vec<EdgeKind>,
+
+        /// Stop enforcing any kind
+        #[arg(long, conflicts_with = "kinds")]
+        clear: bool,
+    },
+
+    /// List, add and remove rules new nodes and edges must satisfy
+    Constraint {
+        #[command(subcommand)]
+        command: ConstraintCommands,
+    },
+
+    /// Show or set the edge kinds followed in both directions
+    Symmetric {
+        /// Edge kinds to treat as undirected; omit to show the current setting
+        kinds: Vec<EdgeKind>,
+
+        /// Treat every kind as directed
+        #[arg(long, conflicts_with = "kinds")]
+        clear: bool,
+    },
+
+    /// Show or set edge kinds created in reciprocal pairs: an edge of either
+    /// kind gets a reversed twin of the other, updated and deleted with it
+    Reciprocal {
+        /// Pairs as kind=reciprocal (e.g. blocks=custom:blocked_by), or one
+        /// kind twinned with itself; omit to show the current setting
+        pairs: Vec<String>,
+
+        /// Stop creating twins; existing pairs stay linked
+        #[arg(long, conflicts_with = "pairs")]
+        clear: bool,
+
+        /// Give existing edges of the paired kinds their twins
+        #[arg(long)]
+        backfill: bool,
+
+        /// Agent recorded for backfilled edges (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// List nodes in dependency order (blocks/enables edges), flagging
+    /// those stuck behind a cycle
+    Toposort {
+        /// Only order nodes of this kind (repeatable) [default: all]
+        #[arg(short, long = "kind")]
+        kinds: Vec<NodeKind>,
+
+        /// Edge kind that orders nodes (repeatable) [default: blocks, enables]
+        #[arg(short, long = "edge-kind")]
+        edge_kinds: Vec<EdgeKind>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the part_of hierarchy under a node as an indented tree
+    Tree {
+        /// Root node ID or alias
+        root: String,
+
+        /// Levels below the root to show [default: all]
+        #[arg(long)]
+        depth: Option<usize>,
+
+        /// Also list the nodes the root is part of, nearest first
+        #[arg(long)]
+        ancestors: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Report the graph's shape: counts per kind, degree distribution,
+    /// connected components, isolated nodes and sampled path lengths
+    Stats {
+        /// Nodes to sample path lengths from
+        #[arg(long, default_value_t = elegant_state::store::structure::DEFAULT_PATH_SAMPLES)]
+        samples: usize,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Draw the graph, or the neighbourhood of one node, as a diagram
+    Viz {
+        /// Only draw this node and its surroundings
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Hops from --root to include
+        #[arg(long, default_value = "1", requires = "root")]
+        depth: usize,
+
+        /// Only follow and draw edges of this kind (repeatable)
+        #[arg(short, long = "kind")]
+        kinds: Vec<EdgeKind>,
+
+        /// Diagram format (dot, mermaid, graphml)
+        #[arg(short, long, default_value = "dot")]
+        format: DiagramFormat,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Compare two states of the graph and print the added, removed and
+    /// changed nodes and edges as JSON
+    Diff {
+        /// Earlier side: a `state-cli export` file, or a time (RFC 3339 or
+        /// an age like 2h) rebuilt from the event log
+        before: String,
+
+        /// Later side, likewise [default: now]
+        after: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConstraintCommands {
+    /// List the rules
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Limit how many edges of a kind leave (or enter) a node
+    MaxDegree {
+        /// Rule name
+        name: String,
+
+        /// Edge kind counted
+        #[arg(short, long)]
+        edge: EdgeKind,
+
+        /// Most edges allowed
+        #[arg(short, long)]
+        max: usize,
+
+        /// Count edges arriving at the node instead of leaving it
+        #[arg(long)]
+        incoming: bool,
+
+        /// Only limit nodes of this kind
+        #[arg(short, long)]
+        node: Option<NodeKind>,
+    },
+
+    /// Restrict which node kinds an edge kind may join
+    Pairs {
+        /// Rule name
+        name: String,
+
+        /// Edge kind restricted
+        #[arg(short, long)]
+        edge: EdgeKind,
+
+        /// Allowed pairs as FROM->TO, e.g. insight->conversation
+        #[arg(required = true)]
+        pairs: Vec<String>,
+    },
+
+    /// Require a content field to be unique among nodes of a kind
+    Unique {
+        /// Rule name
+        name: String,
+
+        /// Node kind
+        #[arg(short, long)]
+        kind: NodeKind,
+
+        /// JSON Pointer to the field, e.g. /title
+        #[arg(short, long)]
+        field: String,
+    },
+
+    /// Remove a rule
+    Remove {
+        /// Rule name
+        name: String,
+    },
+}
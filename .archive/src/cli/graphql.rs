@@ -3,16 +3,38 @@
 #[derive(Subcommand)]
 pub enum GraphqlCommands {
     /// Export GraphQL schema
+    ///
+    /// As SDL, the full introspection result (json), or client type
+    /// definitions (typescript, go).
     Schema {
         /// Output file (- for stdout)
         #[arg(short, long, default_value = "-")]
         output: String,
 
-        /// Include descriptions
-        #[arg(long, default_value = "true")]
+        /// sdl, json, typescript or go
+        #[arg(short, long, default_value = "sdl")]
+        format: elegant_state::graphql::codegen::SchemaFormat,
+
+        /// Package name for Go output
+        #[arg(long, default_value = "state")]
+        package: String,
+
+        /// Include descriptions (--descriptions false to omit)
+        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
         descriptions: bool,
     },
 
+    /// Report changes between a saved schema and the current one
+    Diff {
+        /// Previously exported SDL file
+        #[arg(long)]
+        against: String,
+
+        /// Only list breaking changes
+        #[arg(long)]
+        breaking_only: bool,
+    },
+
     /// Execute a GraphQL query
     Query {
         /// Query string or @file
@@ -29,80 +51,38 @@ pub enum GraphqlCommands {
         /// Pretty print output
         #[arg(short, long)]
         pretty: bool,
-    },
-
-    /// Execute a GraphQL mutation
-    Mutate {
-        /// Mutation string or @file
-        mutation: String,
 
-        /// Variables as JSON
-        #[arg(short, long)]
-        variables: Option<String>,
+        /// Send to a remote server instead of the local database
+        #[arg(long)]
+        url: Option<String>,
 
-        /// Operation name
-        #[arg(short, long)]
-        operation: Option<String>,
+        /// Extra request header as "Name: value" (repeatable)
+        #[arg(short = 'H', long = "header", requires = "url")]
+        headers: Vec<String>,
 
-        /// Dry run - validate but don't execute
-        #[arg(long)]
-        dry_run: bool,
+        /// Bearer token for the remote server
+        #[arg(long, requires = "url")]
+        token: Option<String>,
     },
 
     /// Introspect the GraphQL API
+    ///
+    /// Without --url the local schema is introspected.
     Introspect {
-        /// Server URL (default: http://127.0.0.1:4000/graphql)
+        /// Remote server endpoint, e.g. http://127.0.0.1:4000/graphql/v1
         #[arg(short, long)]
         url: Option<String>,
 
         /// Output format (json, sdl)
         #[arg(short, long, default_value = "sdl")]
         format: String,
-    },
 
-    /// Validate a GraphQL query/mutation
-    Validate {
-        /// Query/mutation string or @file
-        query: String,
-    },
-
-    /// Start GraphQL playground
-    Playground {
-        /// Server URL
-        #[arg(short, long, default_value = "http://127.0.0.1:4000/graphql")]
-        url: String,
-
-        /// Open in browser
-        #[arg(long)]
-        open: bool,
-    },
-
-    /// Generate TypeScript types from schema
-    Codegen {
-        /// Output file
-        #[arg(short, long, default_value = "types.ts")]
-        output: String,
+        /// Extra request header as "Name: value" (repeatable)
+        #[arg(short = 'H', long = "header", requires = "url")]
+        headers: Vec<String>,
 
-        /// Include resolvers
-        #[arg(long)]
-        resolvers: bool,
-    },
-
-    /// Monitor GraphQL subscriptions
-    Subscribe {
-        /// Subscription query
-        query: String,
-
-        /// Variables as JSON
-        #[arg(short, long)]
-        variables: Option<String>,
-
-        /// Server URL
-        #[arg(short, long, default_value = "ws://127.0.0.1:4000/graphql")]
-        url: String,
-
-        /// Output each event on a new line (NDJSON)
-        #[arg(long)]
-        ndjson: bool,
+        /// Bearer token for the remote server
+        #[arg(long, requires = "url")]
+        token: Option<String>,
     },
 }
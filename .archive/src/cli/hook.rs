@@ -0,0 +1,15 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum HookCommands {
+    /// Install a post-commit hook in the current git repository that runs
+    /// `ingest commit` after every commit
+    Install {
+        /// Replace an existing post-commit hook
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Remove the post-commit hook installed by `hook install`
+    Uninstall,
+}
@@ -0,0 +1,19 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum IndexCommands {
+    /// Index a metadata field so `search meta` on it avoids a full scan
+    Create {
+        /// Metadata field (e.g., "metadata.project" or "project"; dots nest)
+        field: String,
+    },
+
+    /// Remove a metadata index
+    Drop {
+        /// Metadata field
+        field: String,
+    },
+
+    /// List indexed metadata fields
+    List,
+}
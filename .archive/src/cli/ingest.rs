@@ -0,0 +1,16 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum IngestCommands {
+    /// Store a commit of the current git repository as a Context node
+    /// linked to the repository's Project node
+    Commit {
+        /// Commit to ingest
+        #[arg(default_value = "HEAD")]
+        rev: String,
+
+        /// Print nothing on success
+        #[arg(short, long)]
+        quiet: bool,
+    },
+}
@@ -0,0 +1,67 @@
+use clap::Subcommand;
+use elegant_state::NodeKind;
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum KindCommands {
+    /// List built-in and registered kinds
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Register a custom kind, or update its description and colour
+    Register {
+        /// Lowercase name; use it as custom:<name> wherever kinds are taken
+        name: String,
+
+        /// Register an edge kind rather than a node kind
+        #[arg(long)]
+        edge: bool,
+
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// Fill colour in diagrams, as #rrggbb
+        #[arg(short, long)]
+        color: Option<String>,
+    },
+
+    /// Forget a custom kind; nodes and edges of the kind keep it
+    Remove {
+        name: String,
+
+        /// Remove an edge kind rather than a node kind
+        #[arg(long)]
+        edge: bool,
+    },
+
+    /// Show, set or clear the JSON Schema a node kind's content must match
+    Schema {
+        kind: NodeKind,
+
+        /// JSON Schema file to require from now on
+        file: Option<PathBuf>,
+
+        /// Stop checking the kind's content
+        #[arg(long, conflicts_with = "file")]
+        clear: bool,
+    },
+    /// Show, set or clear which parts of a node kind's content search reads
+    Text {
+        kind: NodeKind,
+
+        /// JSON Pointer to search under (repeatable; default all content)
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// JSON Pointer to leave out, such as an embedded blob (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Search the kind's whole content again
+        #[arg(long, conflicts_with_all = ["include", "exclude"])]
+        clear: bool,
+    },
+}
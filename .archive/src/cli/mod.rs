@@ -1,12 +1,48 @@
 mod node;
 mod edge;
+mod graph;
 mod serve;
+mod coordinator;
+mod db;
+mod report;
+mod search;
+mod graphql;
+mod share;
+mod connector;
+mod index;
+mod event;
+mod proposal;
+mod vote;
+mod hook;
+mod ingest;
+mod kind;
+mod embeddings;
+mod agent;
+mod dev;
 
-pub use node::NodeCommands;
+pub use node::{AliasCommands, NodeCommands, TagCommands, TemplateCommands};
 pub use edge::EdgeCommands;
-pub use serve::ServeCommands;
+pub use graph::{ConstraintCommands, GraphCommands};
+pub use serve::{ServeCommands, ServeLogsCommands};
+pub use coordinator::CoordinatorCommands;
+pub use db::{AtCommands, DbCommands, RetentionCommands, SnapshotCommands};
+pub use report::ReportCommands;
+pub use search::{SearchCommands, SearchIndexCommands};
+pub use graphql::GraphqlCommands;
+pub use share::ShareCommands;
+pub use connector::ConnectorCommands;
+pub use index::IndexCommands;
+pub use event::EventCommands;
+pub use proposal::{AutoApproveCommands, EscalationCommands, ProposalCommands};
+pub use vote::VoteCommands;
+pub use hook::HookCommands;
+pub use ingest::IngestCommands;
+pub use kind::KindCommands;
+pub use embeddings::EmbeddingCommands;
+pub use agent::AgentCommands;
+pub use dev::DevCommands;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "state-cli")]
@@ -18,6 +54,32 @@ pub struct Cli {
     #[arg(short, long, default_value = "~/.local/share/elegant-state/db")]
     pub db_path: String,
 
+    /// Compress stored values at least this many bytes (0 disables compression)
+    #[arg(long, global = true, default_value = "4096")]
+    pub compression_threshold: usize,
+
+    /// Namespace to operate in (isolated graph within the same database)
+    #[arg(short = 'n', long, global = true)]
+    pub namespace: Option<String>,
+
+    /// Open the database read-only; every write fails
+    #[arg(long, global = true)]
+    pub read_only: bool,
+
+    /// Fail instead of silently skipping undecodable records, dangling
+    /// index entries or unindexed metadata lookups
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Wait for another process to release the database instead of failing
+    #[arg(long, global = true)]
+    pub wait: bool,
+
+    /// Don't scope node, search and ingest commands to the project of the
+    /// enclosing `.elegant-state` marker or git repository
+    #[arg(long, global = true)]
+    pub no_workspace: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -36,17 +98,80 @@ pub enum Commands {
         command: EdgeCommands,
     },
 
-    /// Search the state graph
+    /// Graph queries across nodes and edges
+    Graph {
+        #[command(subcommand)]
+        command: GraphCommands,
+    },
+
+    /// Custom node and edge kinds
+    Kind {
+        #[command(subcommand)]
+        command: KindCommands,
+    },
+
+    /// Cached text embeddings
+    Embeddings {
+        #[command(subcommand)]
+        command: EmbeddingCommands,
+    },
+
+    /// Agent capabilities and watchdog demotions
+    Agent {
+        #[command(subcommand)]
+        command: AgentCommands,
+    },
+
+    /// Search the state graph; `search <query>` is `search fulltext <query>`
+    #[command(args_conflicts_with_subcommands = true)]
     Search {
+        #[command(subcommand)]
+        command: Option<SearchCommands>,
+
         /// Search query
-        query: String,
+        query: Option<String>,
+
+        /// Filter by node kinds
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<elegant_state::NodeKind>>,
+    },
+
+    /// Answer a question from the graph, citing source nodes
+    #[cfg(feature = "ask")]
+    Ask {
+        /// The question
+        question: String,
+
+        /// Number of nodes to retrieve as sources
+        #[arg(long, default_value = "5")]
+        top_k: usize,
+
+        /// Command that reads a prompt on stdin and writes the answer
+        /// (e.g. "ollama run llama3"); quotes the sources when omitted
+        #[arg(long)]
+        model_command: Option<String>,
 
-        /// Filter by node kinds (comma-separated)
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Render a node's content according to its content type
+    Render {
+        /// Node ID
+        id: String,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "ansi")]
+        format: RenderFormatArg,
+
+        /// Write to a file instead of stdout
         #[arg(short, long)]
-        kinds: Option<String>,
+        output: Option<String>,
     },
 
     /// Show recent events
+    #[command(alias = "event")]
     Events {
         /// Number of events to show
         #[arg(short, long, default_value = "20")]
@@ -55,19 +180,87 @@ pub enum Commands {
         /// Filter by agent
         #[arg(short, long)]
         agent: Option<String>,
+
+        /// Sort by created_at (default: newest first)
+        #[arg(long)]
+        sort: Option<String>,
+
+        /// Sort in descending order
+        #[arg(long, requires = "sort")]
+        desc: bool,
+
+        #[command(subcommand)]
+        command: Option<EventCommands>,
+    },
+
+    /// Undo an agent's most recent operation, logging compensating events
+    Undo {
+        /// Agent whose operation to undo (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+
+        /// Print the compensating events as JSON
+        #[arg(long)]
+        json: bool,
     },
 
-    /// Export state to JSON
+    /// Redo the operation an agent most recently undid
+    Redo {
+        /// Agent whose undo to redo (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+
+        /// Print the compensating events as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export state to JSON, a diagram (Graphviz DOT, Mermaid) or GraphML
     Export {
-        /// Output format
+        /// Output format (json, dot, mermaid, graphml)
         #[arg(short, long, default_value = "json")]
         format: String,
+
+        /// Only export this node and its surroundings, with their edges
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Hops from --root to include
+        #[arg(long, default_value = "1", requires = "root")]
+        depth: usize,
+
+        /// Only follow and export edges of this kind (repeatable)
+        #[arg(short, long = "kind", requires = "root")]
+        kinds: Vec<elegant_state::schema::EdgeKind>,
     },
 
     /// Import state from JSON
+    ///
+    /// Reads NDJSON (one node per line), a JSON array of nodes or an
+    /// `export` document, streaming it in batches. Another instance's
+    /// export is remapped onto local IDs.
     Import {
-        /// Input file
+        /// Input file (- for stdin)
         file: String,
+
+        /// Skip (reuse) or link (DerivedFrom) nodes whose kind and content
+        /// match an existing node
+        #[arg(long, num_args = 0..=1, default_missing_value = "reuse")]
+        dedupe: Option<elegant_state::DedupeMode>,
+
+        /// Nodes written per batch
+        #[arg(long, default_value_t = elegant_state::store::DEFAULT_IMPORT_BATCH)]
+        batch_size: usize,
+
+        /// Instance the input came from, for input other than an export
+        /// document; nodes get local IDs, and re-importing from the same
+        /// source updates them
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Don't report progress on stderr
+        #[arg(short, long)]
+        quiet: bool,
     },
 
     /// Start GraphQL server
@@ -75,4 +268,132 @@ pub enum Commands {
         #[command(subcommand)]
         command: ServeCommands,
     },
+
+    /// GraphQL schema tools
+    Graphql {
+        #[command(subcommand)]
+        command: GraphqlCommands,
+    },
+
+    /// Secondary indexes on metadata fields
+    Index {
+        #[command(subcommand)]
+        command: IndexCommands,
+    },
+
+    /// External sources the graph reads through to
+    Connector {
+        #[command(subcommand)]
+        command: ConnectorCommands,
+    },
+
+    /// Time-boxed read-only share links
+    Share {
+        #[command(subcommand)]
+        command: ShareCommands,
+    },
+
+    /// Follow a running server's events, alerting on those matching a filter
+    Watch {
+        /// Server endpoint
+        #[arg(short, long, default_value = "http://127.0.0.1:4000/graphql")]
+        url: String,
+
+        /// Filter as `[name:]op=create|update,agent=claude,text=proposal,alert=desktop+bell`
+        /// (repeatable); alerts default to the terminal bell
+        #[arg(long = "on")]
+        filters: Vec<elegant_state::watch::WatchFilter>,
+
+        /// JSON file with an array of filters
+        #[arg(long)]
+        filter_file: Option<String>,
+
+        /// Time between polls (e.g. "2s", "1m")
+        #[arg(long, default_value = "2s")]
+        interval: String,
+
+        /// Extra request header, "Name: value" (repeatable)
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+
+        /// Bearer token for the Authorization header
+        #[arg(long)]
+        token: Option<String>,
+    },
+
+    /// Git hooks that feed commits into the graph
+    Hook {
+        #[command(subcommand)]
+        command: HookCommands,
+    },
+
+    /// Record external activity as nodes
+    Ingest {
+        #[command(subcommand)]
+        command: IngestCommands,
+    },
+
+    /// Database operations
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+
+    /// Reports over recorded telemetry
+    Report {
+        #[command(subcommand)]
+        command: ReportCommands,
+    },
+
+    /// Proposed mutations awaiting votes
+    Proposal {
+        #[command(subcommand)]
+        command: ProposalCommands,
+    },
+
+    /// Vote on proposals
+    Vote {
+        #[command(subcommand)]
+        command: VoteCommands,
+    },
+
+    /// Multi-agent coordination tools
+    Coordinator {
+        #[command(subcommand)]
+        command: CoordinatorCommands,
+    },
+
+    /// Development helpers
+    Dev {
+        #[command(subcommand)]
+        command: DevCommands,
+    },
+}
+
+/// Voting strategy selector for CLI arguments
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum VotingStrategyArg {
+    Unanimous,
+    SimpleMajority,
+    Supermajority,
+    Weighted,
+    FirstVote,
+    SingleApprover,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum RenderFormatArg {
+    Html,
+    Ansi,
+    Plain,
+}
+
+impl From<RenderFormatArg> for elegant_state::render::RenderTarget {
+    fn from(f: RenderFormatArg) -> Self {
+        match f {
+            RenderFormatArg::Html => Self::Html,
+            RenderFormatArg::Ansi => Self::Ansi,
+            RenderFormatArg::Plain => Self::Plain,
+        }
+    }
 }
@@ -15,12 +15,64 @@ pub enum NodeCommands {
         /// Optional metadata as JSON
         #[arg(short, long)]
         metadata: Option<String>,
+
+        /// Expire the node after this long (e.g., "30m", "2h", "7d")
+        #[arg(long)]
+        ttl: Option<String>,
+
+        /// Tag the node (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Pin the node for every agent to load
+        #[arg(long)]
+        pin: bool,
+
+        /// Retention class, overriding the kind's (ephemeral, standard, permanent)
+        #[arg(long)]
+        retention: Option<String>,
     },
 
     /// Get a node by ID
     Get {
         /// Node ID (ULID)
         id: String,
+
+        /// Also look in the archive tier
+        #[arg(long)]
+        include_archived: bool,
+
+        /// Show the node as it stood at this time (RFC 3339, or an age like "2d")
+        #[arg(long, conflicts_with = "include_archived")]
+        as_of: Option<String>,
+
+        /// With --as-of, also show the node's edges at that time
+        #[arg(long, requires = "as_of")]
+        edges: bool,
+
+        /// Show the newest node superseding this one, if any
+        #[arg(long, conflicts_with = "as_of")]
+        resolve_superseded: bool,
+    },
+
+    /// List every version of a node, rebuilt from the event log
+    Versions {
+        /// Node ID
+        id: String,
+
+        /// Print the full versions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show one version of a node
+    At {
+        /// Node ID
+        id: String,
+
+        /// Version number, as listed by `node versions`
+        #[arg(long)]
+        version: u64,
     },
 
     /// List nodes
@@ -32,6 +84,57 @@ pub enum NodeCommands {
         /// Maximum number of nodes to return
         #[arg(short, long, default_value = "20")]
         limit: usize,
+
+        /// Only nodes with this tag, read from the tag index
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Only pinned nodes
+        #[arg(long)]
+        pinned: bool,
+
+        /// Sort by created_at, updated_at, kind or degree (default: ID order)
+        #[arg(long)]
+        sort: Option<String>,
+
+        /// Sort in descending order
+        #[arg(long, requires = "sort")]
+        desc: bool,
+
+        /// Also list archived nodes
+        #[arg(long)]
+        include_archived: bool,
+    },
+
+    /// Move old nodes into the compressed archive tier
+    ///
+    /// Archived nodes are hidden from list and search unless
+    /// `--include-archived` is given.
+    Archive {
+        /// Node IDs to archive (instead of selecting by age)
+        ids: Vec<String>,
+
+        /// Archive nodes not updated for this long (e.g., "90d", "12w")
+        #[arg(long, conflicts_with = "ids")]
+        older_than: Option<String>,
+
+        /// Only archive nodes of this kind
+        #[arg(short, long)]
+        kind: Option<String>,
+
+        /// Only archive nodes whose content matches this query
+        #[arg(short, long)]
+        query: Option<String>,
+
+        /// List the nodes that would be archived
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Move an archived node back into the hot store
+    Unarchive {
+        /// Node ID
+        id: String,
     },
 
     /// Update a node
@@ -42,6 +145,65 @@ pub enum NodeCommands {
         /// New content as JSON
         #[arg(short, long)]
         content: String,
+
+        /// Fail unless the node is still at this version
+        #[arg(long)]
+        expected_version: Option<u64>,
+    },
+
+    /// Move every edge of one node onto another in a single change
+    Replace {
+        /// Node whose edges move
+        old: String,
+
+        /// Node that takes them over
+        new: String,
+    },
+
+    /// Report where a node came from and how far to trust it: its creation
+    /// and changes, DerivedFrom sources, proposals and votes, annotations
+    /// and reactions
+    Explain {
+        /// Node ID
+        id: String,
+
+        /// Output as JSON instead of markdown
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Fold duplicate nodes into one: merge their content and metadata,
+    /// move their edges onto the winner and mark them superseded by it
+    Merge {
+        /// Node that stays
+        winner: String,
+
+        /// Duplicates merged into it
+        #[arg(required = true)]
+        losers: Vec<String>,
+
+        /// How content and metadata combine (keep, union, latest)
+        #[arg(short, long, default_value = "keep")]
+        strategy: elegant_state::store::MergeStrategy,
+    },
+
+    /// Make a node part of a different parent, dropping its other PartOf edges
+    Reparent {
+        /// Node ID
+        id: String,
+
+        /// New parent node ID
+        parent: String,
+    },
+
+    /// Split a node into parts, each linked PartOf it
+    Split {
+        /// Node ID
+        id: String,
+
+        /// Content of each part as JSON, in order (repeatable)
+        #[arg(short, long = "part", required = true)]
+        parts: Vec<String>,
     },
 
     /// Delete a node
@@ -52,5 +214,292 @@ pub enum NodeCommands {
         /// Skip confirmation
         #[arg(short, long)]
         force: bool,
+
+        /// Refuse to delete while edges exist instead of deleting them too
+        #[arg(long)]
+        restrict: bool,
+    },
+
+    /// Comment on a node without changing its content
+    Comment {
+        /// Node ID
+        id: String,
+
+        /// Comment text
+        text: String,
+
+        /// Anchor as a JSON path ("$.field") or text range ("10..42")
+        #[arg(long)]
+        anchor: Option<String>,
+
+        /// Comment author (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        author: String,
+    },
+
+    /// List comments on a node
+    Comments {
+        /// Node ID
+        id: String,
+    },
+
+    /// React to a node (useful, outdated, disputed)
+    React {
+        /// Node ID
+        id: String,
+
+        /// Reaction kind
+        kind: String,
+
+        /// Reacting agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+
+        /// Withdraw the reaction instead
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Show reaction counts and scores for a node
+    Reactions {
+        /// Node ID
+        id: String,
+
+        /// List individual reactions
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Attach a file (PDF, image, audio, ...) to a node
+    Attach {
+        /// Node ID
+        id: String,
+
+        /// File to attach
+        file: String,
+
+        /// MIME type (guessed from the extension if omitted)
+        #[arg(long)]
+        mime: Option<String>,
+    },
+
+    /// List a node's attachments, or save one to a file
+    Attachments {
+        /// Node ID
+        id: String,
+
+        /// Hash of the attachment to save
+        #[arg(long, requires = "output")]
+        hash: Option<String>,
+
+        /// Where to write the attachment
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Remove an attachment from a node
+    Detach {
+        /// Node ID
+        id: String,
+
+        /// Attachment hash
+        hash: String,
+    },
+
+    /// Ingest a document, linking it to the nodes it references
+    Ingest {
+        /// Document path (non-markdown formats are converted with pandoc)
+        file: String,
+
+        /// Node kind
+        #[arg(short, long, default_value = "context")]
+        kind: String,
+
+        /// Also store one chunk node per heading section
+        #[arg(long)]
+        chunk: bool,
+
+        /// Skip (reuse) or link (DerivedFrom) when a node of the same kind
+        /// already has identical content
+        #[arg(long, num_args = 0..=1, default_missing_value = "reuse")]
+        dedupe: Option<elegant_state::DedupeMode>,
+    },
+
+    /// Print one section of an ingested document, or its outline
+    Section {
+        /// Node ID
+        id: String,
+
+        /// Heading anchor (omit to list the outline)
+        anchor: Option<String>,
+    },
+
+    /// Saved node skeletons with {{variable}} placeholders
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
+
+    /// Add, remove and list tags
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands,
+    },
+
+    /// Name nodes with aliases usable wherever a node ID is
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommands,
+    },
+
+    /// Pin a node as context every agent should load
+    Pin {
+        /// Node ID
+        id: String,
+
+        /// Pinning agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// Unpin a node
+    Unpin {
+        /// Node ID
+        id: String,
+
+        /// Unpinning agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// Override a node's retention class
+    Retention {
+        /// Node ID
+        id: String,
+
+        /// ephemeral, standard, permanent, or inherit to follow the kind
+        class: String,
+
+        /// Changing agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TagCommands {
+    /// Tag a node
+    Add {
+        /// Node ID
+        id: String,
+
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        /// Tagging agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// Remove tags from a node
+    Remove {
+        /// Node ID
+        id: String,
+
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        /// Tagging agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// Every tag in use and how many nodes carry it
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum AliasCommands {
+    /// Give a node an alias, replacing any it had
+    Set {
+        /// Node ID or alias
+        id: String,
+
+        /// Lowercase slug, e.g. "project-roadmap"
+        alias: String,
+    },
+
+    /// Remove a node's alias
+    Remove {
+        /// Node ID or alias
+        id: String,
+    },
+
+    /// List every alias and its node
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommands {
+    /// Save a template, replacing any of the same name
+    Save {
+        /// Template name
+        name: String,
+
+        /// Node kind
+        #[arg(short, long)]
+        kind: String,
+
+        /// Node content as JSON; strings may hold {{variable}} placeholders
+        #[arg(short, long)]
+        content: String,
+
+        /// Metadata as key=value; values parse as JSON, else as text
+        #[arg(long = "meta")]
+        metadata: Vec<String>,
+
+        /// Edge from the new node as kind=node, e.g. part_of={{project}} (repeatable)
+        #[arg(long = "edge")]
+        edges: Vec<String>,
+
+        /// Edge to the new node as kind=node (repeatable)
+        #[arg(long = "incoming")]
+        incoming: Vec<String>,
+
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+
+    /// Create a node and its edges from a template
+    Apply {
+        /// Template name
+        name: String,
+
+        /// Variables as key=value; values parse as JSON, else as text
+        #[arg(long = "var")]
+        variables: Vec<String>,
+
+        /// Creating agent (user, claude, llama, system, or module:*)
+        #[arg(long, default_value = "user")]
+        agent: String,
+    },
+
+    /// List templates and the variables they take
+    List,
+
+    /// Print a template as JSON
+    Show {
+        /// Template name
+        name: String,
+    },
+
+    /// Delete a template
+    Delete {
+        /// Template name
+        name: String,
     },
 }
@@ -20,6 +20,14 @@ pub enum ProposalCommands {
         #[arg(short, long, default_value = "20")]
         limit: usize,
 
+        /// Sort by created_at, updated_at or kind (default: newest first)
+        #[arg(long)]
+        sort: Option<String>,
+
+        /// Sort in descending order
+        #[arg(long, requires = "sort")]
+        desc: bool,
+
         /// Show detailed information
         #[arg(short, long)]
         verbose: bool,
@@ -96,34 +104,110 @@ pub enum ProposalCommands {
         verbose: bool,
     },
 
-    /// Execute an approved proposal
-    Execute {
-        /// Proposal ID
-        id: String,
+    /// Show which proposals touch which nodes
+    Impact {
+        /// Only proposals touching this node
+        #[arg(long)]
+        node: Option<String>,
+
+        /// Include resolved proposals, not just pending ones
+        #[arg(long)]
+        all: bool,
 
-        /// Skip execution confirmation
+        /// Print the proposal/node graph as Graphviz DOT
         #[arg(long)]
-        force: bool,
+        dot: bool,
     },
 
-    /// Expire old pending proposals
-    Expire {
-        /// Only expire proposals older than (e.g., "1h", "2d")
+    /// Rules that approve low-risk proposals after a grace period
+    AutoApprove {
+        #[command(subcommand)]
+        command: AutoApproveCommands,
+    },
+
+    /// Hand stalled proposals to an arbiter
+    Escalation {
+        #[command(subcommand)]
+        command: EscalationCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EscalationCommands {
+    /// Add or replace the rule for a voting strategy
+    Add {
+        /// Strategy the rule applies to (e.g., unanimous, weighted); all
+        /// strategies without their own rule when omitted
         #[arg(long)]
-        older_than: Option<String>,
+        strategy: Option<String>,
 
-        /// Dry run - show what would be expired
+        /// Time a proposal may stay pending (e.g., "1h", "2d")
         #[arg(long)]
-        dry_run: bool,
+        after: String,
+
+        /// Agent whose vote decides escalated proposals
+        #[arg(long, default_value = "user")]
+        arbiter: String,
+    },
+
+    /// Remove the rule for a voting strategy
+    Remove {
+        /// Strategy name; the catch-all rule when omitted
+        #[arg(long)]
+        strategy: Option<String>,
+    },
+
+    /// List rules
+    Rules,
+
+    /// Escalate every proposal past its deadline
+    Run,
+
+    /// Show escalated proposals awaiting an arbiter's vote
+    Inbox {
+        /// Only escalations to this agent
+        #[arg(long)]
+        arbiter: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AutoApproveCommands {
+    /// Add or replace a rule
+    Add {
+        /// Rule name
+        name: String,
+
+        /// Filter such as "operation=create AND kind=context"
+        /// (fields: proposer, kind, operation, status)
+        #[arg(long = "where")]
+        filter: String,
+
+        /// Proposer reputation must be above this (0.0 to 1.0)
+        #[arg(long)]
+        min_reputation: Option<f32>,
+
+        /// Payload must be at most this many bytes of JSON
+        #[arg(long)]
+        max_payload: Option<usize>,
+
+        /// Time without objection before approval (e.g., "5m", "1h")
+        #[arg(long, default_value = "5m")]
+        grace: String,
+    },
+
+    /// Remove a rule
+    Remove {
+        /// Rule name
+        name: String,
     },
 
-    /// Clean up resolved proposals
-    Cleanup {
-        /// Keep proposals newer than (e.g., "7d", "30d")
-        #[arg(long, default_value = "7d")]
-        keep: String,
+    /// List rules
+    List,
 
-        /// Dry run - show what would be removed
+    /// Approve every pending proposal a rule allows
+    Run {
+        /// Only list the proposals that would be approved
         #[arg(long)]
         dry_run: bool,
     },
@@ -0,0 +1,53 @@
+use clap::Subcommand;
+use elegant_state::store::maintenance::MaintenanceKind;
+
+#[derive(Subcommand)]
+pub enum ReportCommands {
+    /// Voting strategy effectiveness and per-agent influence
+    Governance {
+        /// Only include decisions resolved within this window (e.g., "7d", "30d")
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Durations and sizes of compactions, repairs, backups, reindexes,
+    /// migrations and expiry sweeps, with trends
+    Maintenance {
+        /// Only include runs started within this window (e.g., "7d", "30d")
+        #[arg(long)]
+        since: Option<String>,
+
+        /// List every run of this operation (compaction, repair, backup,
+        /// reindex, migration, gc)
+        #[arg(short, long)]
+        kind: Option<MaintenanceKind>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Per-agent request counts, mutation/read ratios, error rates and
+    /// latency from the server's request journal
+    Usage {
+        /// Only include requests within this window (e.g., "1h", "7d")
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Also break usage down into windows of this length (e.g., "1h")
+        #[arg(short, long)]
+        window: Option<String>,
+
+        /// Request journal [default: requests.log beside the database]
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
@@ -1,103 +1,107 @@
 use clap::Subcommand;
-use super::{NodeKindArg, SearchAlgorithm};
+use elegant_state::NodeKind;
+use elegant_state::schema::EdgeKind;
+use elegant_state::store::Direction;
+use elegant_state::store::bench::SearchBackend;
 
 #[derive(Subcommand)]
 pub enum SearchCommands {
-    /// Full-text search using tantivy
+    /// Full-text search over node content
     Fulltext {
         /// Search query
         query: String,
 
         /// Filter by node kinds
-        #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<NodeKind>>,
 
         /// Maximum results
         #[arg(short, long, default_value = "20")]
         limit: usize,
 
-        /// Minimum score threshold (0.0 - 1.0)
+        /// Return this many sibling chunks either side of each chunk hit,
+        /// plus the parent document summary
+        #[arg(long, default_value = "0")]
+        expand_context: usize,
+
+        /// Also search archived nodes
         #[arg(long)]
-        min_score: Option<f32>,
+        include_archived: bool,
 
-        /// Show relevance scores
+        /// On a local miss, consult registered connectors and cache what
+        /// they return
         #[arg(long)]
-        scores: bool,
+        federated: bool,
 
-        /// Highlight matches in output
+        /// Replace superseded hits with the newest node superseding them
         #[arg(long)]
-        highlight: bool,
+        resolve_superseded: bool,
     },
 
-    /// Fuzzy search (skim/fzf-like)
-    Fuzzy {
-        /// Search pattern
-        pattern: String,
-
-        /// Filter by node kinds
-        #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
-
-        /// Maximum results
-        #[arg(short, long, default_value = "20")]
-        limit: usize,
+    /// Search by metadata field
+    Meta {
+        /// Field name
+        field: String,
 
-        /// Use nucleo algorithm instead of skim
-        #[arg(long)]
-        nucleo: bool,
+        /// Field value (supports wildcards with *)
+        value: String,
 
-        /// Case sensitive matching
-        #[arg(long)]
-        case_sensitive: bool,
+        /// Filter by node kinds
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<NodeKind>>,
     },
 
-    /// Approximate matching (agrep-like)
-    Agrep {
-        /// Search pattern
-        pattern: String,
-
-        /// Maximum edit distance (errors allowed)
-        #[arg(short, long, default_value = "2")]
-        max_errors: usize,
+    /// Search several reformulations of a query and fuse the results
+    Expand {
+        /// Search query
+        query: String,
 
         /// Filter by node kinds
-        #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<NodeKind>>,
 
         /// Maximum results
         #[arg(short, long, default_value = "20")]
         limit: usize,
-    },
 
-    /// Exact substring search
-    Exact {
-        /// Search string
-        query: String,
+        /// Maximum number of reformulations (including the original)
+        #[arg(long, default_value = "8")]
+        max_queries: usize,
 
-        /// Filter by node kinds
+        /// Show the reformulations and which of them found each hit
         #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
+        verbose: bool,
+    },
 
-        /// Case insensitive
-        #[arg(short, long)]
-        ignore_case: bool,
+    /// Time each query across the search backends and compare their results
+    Bench {
+        /// File with one query per line, optionally `class<TAB>query`
+        #[arg(long)]
+        queries: String,
 
-        /// Maximum results
-        #[arg(short, long, default_value = "20")]
-        limit: usize,
-    },
+        /// Backend to run (repeatable) [default: all]
+        #[arg(short, long = "backend")]
+        backends: Vec<SearchBackend>,
 
-    /// Search by metadata field
-    Meta {
-        /// Field name
-        field: String,
+        /// Backend whose results the others are compared with
+        #[arg(long, default_value = "fulltext")]
+        baseline: SearchBackend,
 
-        /// Field value (supports wildcards with *)
-        value: String,
+        /// Timed runs per query and backend
+        #[arg(long, default_value = "3")]
+        runs: usize,
 
-        /// Filter by node kinds
+        /// Edits agrep allows
+        #[arg(short, long, default_value = "1")]
+        max_errors: usize,
+
+        /// Also show every query's results, not just the per-class summary
         #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
+        verbose: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Find nodes by edge relationships
@@ -107,25 +111,62 @@ pub enum SearchCommands {
 
         /// Edge direction (in, out, both)
         #[arg(short, long, default_value = "both")]
-        direction: String,
+        direction: Direction,
 
         /// Edge kinds to follow
-        #[arg(short, long)]
-        edge_kinds: Option<Vec<String>>,
+        #[arg(short, long, value_delimiter = ',')]
+        edge_kinds: Option<Vec<EdgeKind>>,
 
         /// Traversal depth
         #[arg(long, default_value = "1")]
         depth: usize,
+
+        /// Output as JSON, with the path to each node
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export or import the full-text index
+    Index {
+        #[command(subcommand)]
+        command: SearchIndexCommands,
     },
 
     /// Rebuild search index
     Reindex {
         /// Only index nodes of specific kinds
-        #[arg(short, long)]
-        kinds: Option<Vec<NodeKindArg>>,
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<NodeKind>>,
 
         /// Show progress
         #[arg(long)]
         progress: bool,
     },
 }
+
+#[derive(Subcommand)]
+pub enum SearchIndexCommands {
+    /// Write the index to an archive stamped with the store's revision
+    Export {
+        /// Archive file to write
+        output: String,
+
+        /// Index directory [default: `fulltext` beside the database]
+        #[arg(long)]
+        dir: Option<String>,
+    },
+
+    /// Replace the index with an archive exported at the store's revision
+    Import {
+        /// Archive file to read
+        archive: String,
+
+        /// Index directory [default: `fulltext` beside the database]
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// Import even if the archive was exported at another revision
+        #[arg(long)]
+        force: bool,
+    },
+}
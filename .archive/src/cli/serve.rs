@@ -11,6 +11,82 @@ pub enum ServeCommands {
         /// Host to bind to
         #[arg(short = 'H', long, default_value = "127.0.0.1")]
         host: String,
+
+        /// Seconds between expired-node sweeps (0 disables the sweeper)
+        #[arg(long, default_value = "60")]
+        gc_interval: u64,
+
+        /// Seconds between watchdog checks of module agents (0 disables the
+        /// watchdog; thresholds come from --config)
+        #[arg(long, default_value = "10")]
+        watchdog_interval: u64,
+
+        /// Log store operations slower than this many milliseconds (0 disables)
+        #[arg(long, default_value = "250")]
+        slow_op_ms: u64,
+
+        /// Origin allowed to make cross-origin requests (repeatable; * for any).
+        /// Without one, CORS is disabled and browsers only allow same-origin use.
+        #[arg(long = "cors-origin")]
+        cors_origins: Vec<String>,
+
+        /// Methods allowed cross-origin
+        #[arg(long, value_delimiter = ',', default_value = "GET,POST,OPTIONS")]
+        cors_methods: Vec<String>,
+
+        /// Request headers allowed cross-origin
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "content-type,authorization,x-state-namespace"
+        )]
+        cors_headers: Vec<String>,
+
+        /// Largest accepted request body, e.g. 512KiB or 4MiB
+        #[arg(long, default_value = "2MiB")]
+        max_body: String,
+
+        /// Write requests slower than this many milliseconds to the
+        /// slow-query log (0 disables it)
+        #[arg(long, default_value = "1000")]
+        slow_query_ms: u64,
+
+        /// Slow-query log file [default: slow-queries.log beside the database]
+        #[arg(long)]
+        slow_query_log: Option<String>,
+
+        /// Rotate the slow-query log once it reaches this size
+        #[arg(long, default_value = "10MiB")]
+        slow_query_log_size: String,
+
+        /// Rotated slow-query logs to keep
+        #[arg(long, default_value = "5")]
+        slow_query_log_files: usize,
+
+        /// Journal every request here for `report usage` [default:
+        /// requests.log beside the database]
+        #[arg(long)]
+        request_journal: Option<String>,
+
+        /// Don't journal requests
+        #[arg(long, conflicts_with = "request_journal")]
+        no_request_journal: bool,
+
+        /// JSON file of capability policies, voting strategy, rate limits
+        /// and webhooks; re-read on SIGHUP or POST /admin/reload
+        #[arg(long)]
+        config: Option<String>,
+
+        /// Record every GraphQL request and response to this cassette file,
+        /// for replay with the client crate's `Client::from_cassette`
+        #[arg(long)]
+        record_cassette: Option<String>,
+    },
+
+    /// Inspect server logs
+    Logs {
+        #[command(subcommand)]
+        command: ServeLogsCommands,
     },
 
     // Future: Unix socket support
@@ -19,3 +95,25 @@ pub enum ServeCommands {
     //     path: String,
     // },
 }
+
+#[derive(Subcommand)]
+pub enum ServeLogsCommands {
+    /// Show the most recent slow queries
+    Tail {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        lines: usize,
+
+        /// Slow-query log file [default: slow-queries.log beside the database]
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Keep printing entries as they are written
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Output raw JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+}
@@ -0,0 +1,42 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum ShareCommands {
+    /// Create a read-only share link for the subgraph around a node
+    Create {
+        /// Root node ID
+        #[arg(short, long)]
+        root: String,
+
+        /// How long the link stays valid (e.g. 30m, 48h, 7d)
+        #[arg(long, default_value = "48h")]
+        ttl: String,
+
+        /// Edge hops from the root to include
+        #[arg(long, default_value = "2")]
+        depth: usize,
+
+        /// Server base URL used to print the full link
+        #[arg(long, default_value = "http://127.0.0.1:4000")]
+        base_url: String,
+    },
+
+    /// List share links
+    List {
+        /// Include expired and revoked links
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    /// Revoke a share link
+    Revoke {
+        /// Share ID
+        id: String,
+    },
+
+    /// Show the audit log
+    Audit {
+        /// Only entries for this share ID
+        id: Option<String>,
+    },
+}
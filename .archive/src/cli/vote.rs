@@ -0,0 +1,53 @@
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum VoteCommands {
+    /// Vote on one proposal
+    Cast {
+        /// Proposal ID
+        id: String,
+
+        /// approve, reject or abstain
+        #[arg(short, long)]
+        decision: String,
+
+        /// Agent casting the vote
+        #[arg(long = "as", default_value = "user")]
+        agent: String,
+
+        /// Reason for the vote
+        #[arg(short, long)]
+        reason: Option<String>,
+    },
+
+    /// Vote on every pending proposal matching a filter in one audited action
+    Batch {
+        /// Filter such as "proposer=module:scraper AND kind=context"
+        /// (fields: proposer, kind, operation, status)
+        #[arg(long = "where")]
+        filter: String,
+
+        /// approve, reject or abstain
+        #[arg(short, long)]
+        decision: String,
+
+        /// Agent casting the votes (must be the user or trusted to batch vote)
+        #[arg(long = "as", default_value = "user")]
+        agent: String,
+
+        /// Reason recorded on every vote
+        #[arg(short, long)]
+        reason: Option<String>,
+
+        /// Only list the proposals that would be voted on
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show the batch vote audit log
+    Batches {
+        /// Number of batches to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+}
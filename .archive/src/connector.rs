@@ -0,0 +1,386 @@
+//! Read-through external knowledge connectors
+//!
+//! A search that finds too little locally consults registered external
+//! sources and materializes what they return as cached nodes. Cached nodes
+//! carry their provenance in the `provenance` metadata key and expire after
+//! the connector's TTL, so the expiry sweeper keeps the cache fresh.
+//!
+//! A cached node's ID is derived from the connector name and the record's
+//! external ID, so fetching the same record twice refreshes one node rather
+//! than creating duplicates.
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::process::Command;
+use thiserror::Error;
+
+use crate::graphql::client::post_json;
+use crate::schema::{AgentId, NodeId, NodeKind, StateNode};
+use crate::store::{Store, StoreError};
+
+/// Store metadata key holding the registered connector specs
+pub const CONNECTORS_META_KEY: &str = "connectors";
+
+/// Node metadata key recording where a cached node came from
+pub const PROVENANCE_KEY: &str = "provenance";
+
+/// Cache lifetime when a connector doesn't set one
+pub const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Error, Debug)]
+pub enum ConnectorError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    #[error("Connector {0} failed: {1}")]
+    Source(String, String),
+
+    #[error("Invalid connector config: {0}")]
+    Config(String),
+}
+
+pub type Result<T> = std::result::Result<T, ConnectorError>;
+
+/// A record returned by an external source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalRecord {
+    /// Stable ID within the source
+    pub external_id: String,
+    #[serde(default)]
+    pub kind: Option<NodeKind>,
+    pub content: serde_json::Value,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// An external source of nodes
+pub trait Connector: Send + Sync {
+    fn name(&self) -> &str;
+    fn fetch(&self, query: &str, limit: usize) -> Result<Vec<ExternalRecord>>;
+}
+
+/// Runs a command with the query as its last argument; the command prints
+/// records as a JSON array or one JSON object per line
+///
+/// Wraps anything scriptable: a wiki API via `curl`, a vector DB client.
+pub struct CommandConnector {
+    name: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandConnector {
+    pub fn from_command_line(name: impl Into<String>, command: &str) -> Result<Self> {
+        let name = name.into();
+        let mut parts = command.split_whitespace().map(str::to_string);
+        let program = parts
+            .next()
+            .ok_or_else(|| ConnectorError::Config(format!("{}: empty command", name)))?;
+        Ok(Self {
+            name,
+            program,
+            args: parts.collect(),
+        })
+    }
+}
+
+impl Connector for CommandConnector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch(&self, query: &str, limit: usize) -> Result<Vec<ExternalRecord>> {
+        let fail = |message: String| ConnectorError::Source(self.name.clone(), message);
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(query)
+            .output()
+            .map_err(|e| fail(format!("failed to run {}: {}", self.program, e)))?;
+        if !output.status.success() {
+            return Err(fail(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let records: Vec<ExternalRecord> = if stdout.trim_start().starts_with('[') {
+            serde_json::from_str(&stdout).map_err(|e| fail(e.to_string()))?
+        } else {
+            stdout
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|e| fail(e.to_string()))?
+        };
+        Ok(records.into_iter().take(limit).collect())
+    }
+}
+
+/// Searches another elegant-state server over GraphQL (plain HTTP only)
+pub struct StateConnector {
+    name: String,
+    url: String,
+    namespace: Option<String>,
+}
+
+impl StateConnector {
+    /// `url` is the server's GraphQL endpoint, e.g. `http://host:4000/graphql/v1`
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            namespace: None,
+        }
+    }
+
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+}
+
+impl Connector for StateConnector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn fetch(&self, query: &str, limit: usize) -> Result<Vec<ExternalRecord>> {
+        let fail = |message: String| ConnectorError::Source(self.name.clone(), message);
+        let body = serde_json::json!({
+            "query": "query($q: String!) { search(query: $q) { id kind content } }",
+            "variables": { "q": query },
+        });
+        let namespace: Vec<_> = self
+            .namespace
+            .as_deref()
+            .map(|ns| (crate::graphql::NAMESPACE_HEADER, ns))
+            .into_iter()
+            .collect();
+        let response = post_json(&self.url, &body, &namespace).map_err(fail)?;
+
+        if let Some(errors) = response.get("errors") {
+            return Err(fail(errors.to_string()));
+        }
+        let hits = response["data"]["search"]
+            .as_array()
+            .ok_or_else(|| fail("response has no search results".into()))?;
+
+        Ok(hits
+            .iter()
+            .take(limit)
+            .filter_map(|hit| {
+                Some(ExternalRecord {
+                    external_id: hit["id"].as_str()?.to_string(),
+                    kind: hit["kind"].as_str().and_then(|k| k.to_lowercase().parse().ok()),
+                    content: hit["content"].clone(),
+                    url: Some(self.url.clone()),
+                })
+            })
+            .collect())
+    }
+}
+
+/// How a connector is registered, as stored under [`CONNECTORS_META_KEY`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectorSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: SourceSpec,
+    /// Seconds cached nodes live for
+    #[serde(default)]
+    pub ttl_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceSpec {
+    Command { command: String },
+    State { url: String, namespace: Option<String> },
+}
+
+impl ConnectorSpec {
+    pub fn build(&self) -> Result<Box<dyn Connector>> {
+        Ok(match &self.source {
+            SourceSpec::Command { command } => {
+                Box::new(CommandConnector::from_command_line(&self.name, command)?)
+            }
+            SourceSpec::State { url, namespace } => {
+                let connector = StateConnector::new(&self.name, url);
+                Box::new(match namespace {
+                    Some(ns) => connector.with_namespace(ns),
+                    None => connector,
+                })
+            }
+        })
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::seconds(self.ttl_secs.unwrap_or(DEFAULT_TTL_SECS))
+    }
+}
+
+/// Outcome of a federated search
+#[derive(Debug, Default)]
+pub struct FederatedResults {
+    /// Hits already in the store (including previously cached nodes)
+    pub local: Vec<StateNode>,
+    /// Nodes materialized from connectors by this search
+    pub fetched: Vec<StateNode>,
+    /// Connectors that failed, with their error; a failing source never
+    /// fails the search
+    pub errors: Vec<(String, String)>,
+}
+
+/// Local search that reads through to external connectors on a miss
+pub struct Federation {
+    connectors: Vec<(Box<dyn Connector>, Duration)>,
+    min_local: usize,
+}
+
+impl Default for Federation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Federation {
+    pub fn new() -> Self {
+        Self {
+            connectors: Vec::new(),
+            min_local: 1,
+        }
+    }
+
+    pub fn from_specs(specs: &[ConnectorSpec]) -> Result<Self> {
+        specs
+            .iter()
+            .try_fold(Self::new(), |federation, spec| Ok(federation.with_connector(spec.build()?, spec.ttl())))
+    }
+
+    pub fn with_connector(mut self, connector: Box<dyn Connector>, ttl: Duration) -> Self {
+        self.connectors.push((connector, ttl));
+        self
+    }
+
+    /// Consult connectors only when fewer than this many local hits exist
+    pub fn with_min_local(mut self, min_local: usize) -> Self {
+        self.min_local = min_local;
+        self
+    }
+
+    pub fn search<S: Store>(
+        &self,
+        store: &S,
+        query: &str,
+        kinds: Option<Vec<NodeKind>>,
+        limit: usize,
+    ) -> Result<FederatedResults> {
+        let mut results = FederatedResults {
+            local: store.search(query, kinds.clone())?,
+            ..Default::default()
+        };
+        if results.local.len() >= self.min_local {
+            return Ok(results);
+        }
+
+        for (connector, ttl) in &self.connectors {
+            let records = match connector.fetch(query, limit) {
+                Ok(records) => records,
+                Err(e) => {
+                    results.errors.push((connector.name().to_string(), e.to_string()));
+                    continue;
+                }
+            };
+            for record in records {
+                let node = materialize(store, connector.name(), query, record, *ttl)?;
+                if kinds.as_ref().map_or(true, |ks| ks.contains(&node.kind)) {
+                    results.fetched.push(node);
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Deterministic node ID for a record from a connector
+pub fn cache_id(connector: &str, external_id: &str) -> NodeId {
+    let digest = Sha256::new()
+        .chain_update(connector.as_bytes())
+        .chain_update([0u8])
+        .chain_update(external_id.as_bytes())
+        .finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    NodeId::from_bytes(bytes)
+}
+
+/// Store a record as a cached node, replacing any earlier copy
+fn materialize<S: Store>(
+    store: &S,
+    connector: &str,
+    query: &str,
+    record: ExternalRecord,
+    ttl: Duration,
+) -> Result<StateNode> {
+    let id = cache_id(connector, &record.external_id);
+    if store.get_node(id)?.is_some() {
+        store.delete_node(id, AgentId::System)?;
+    }
+
+    let mut node = StateNode::new(record.kind.unwrap_or(NodeKind::Context), record.content)
+        .with_id(id)
+        .with_ttl(ttl);
+    node.metadata.insert(
+        PROVENANCE_KEY.to_string(),
+        serde_json::json!({
+            "connector": connector,
+            "external_id": record.external_id,
+            "url": record.url,
+            "query": query,
+            "fetched_at": Utc::now().to_rfc3339(),
+        }),
+    );
+    Ok(store.create_node(node, AgentId::System)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+
+    struct Wiki;
+
+    impl Connector for Wiki {
+        fn name(&self) -> &str {
+            "wiki"
+        }
+
+        fn fetch(&self, query: &str, _limit: usize) -> Result<Vec<ExternalRecord>> {
+            Ok(vec![ExternalRecord {
+                external_id: "Sled_(database)".into(),
+                kind: None,
+                content: serde_json::json!({ "text": format!("{} is an embedded database", query) }),
+                url: Some("https://example.org/wiki/Sled".into()),
+            }])
+        }
+    }
+
+    #[test]
+    fn test_read_through_caches_with_provenance() {
+        let store = SledStore::open_temporary().unwrap();
+        let federation = Federation::new().with_connector(Box::new(Wiki), Duration::hours(1));
+
+        let first = federation.search(&store, "sled", None, 10).unwrap();
+        assert!(first.local.is_empty());
+        assert_eq!(first.fetched.len(), 1);
+        let cached = &first.fetched[0];
+        assert_eq!(cached.id, cache_id("wiki", "Sled_(database)"));
+        assert_eq!(cached.metadata[PROVENANCE_KEY]["connector"], "wiki");
+        assert!(cached.expires_at.is_some());
+
+        // The cached node now answers locally
+        let second = federation.search(&store, "sled", None, 10).unwrap();
+        assert_eq!(second.local.len(), 1);
+        assert!(second.fetched.is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+//! Auto-approval of low-risk proposals
+//!
+//! A rule selects proposals by filter, proposer reputation and payload size.
+//! A selected proposal is approved once it has been pending for the rule's
+//! grace period with no reject vote. The resolution reason names the rule,
+//! so auto-approvals stay distinguishable in the audit trail.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::proposal::{Proposal, ProposalFilter, ProposalId, ProposalManager};
+use super::reputation::ReputationTracker;
+use super::voting::{Vote, VoteDecision, VotingCoordinator};
+
+/// Score assumed for agents without a reputation record
+const NEUTRAL_REPUTATION: f32 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoApprovalRule {
+    pub name: String,
+    pub filter: ProposalFilter,
+    /// Proposer reputation must be above this
+    pub min_reputation: Option<f32>,
+    /// Payload, serialized as JSON, must be at most this many bytes
+    pub max_payload_bytes: Option<usize>,
+    /// How long a proposal must sit unopposed before approval
+    pub grace_seconds: i64,
+}
+
+impl AutoApprovalRule {
+    pub fn new(name: impl Into<String>, filter: ProposalFilter) -> Self {
+        Self {
+            name: name.into(),
+            filter,
+            min_reputation: None,
+            max_payload_bytes: None,
+            grace_seconds: 300, // 5 minutes default
+        }
+    }
+
+    pub fn with_min_reputation(mut self, score: f32) -> Self {
+        self.min_reputation = Some(score);
+        self
+    }
+
+    pub fn with_max_payload_bytes(mut self, bytes: usize) -> Self {
+        self.max_payload_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_grace_period(mut self, seconds: i64) -> Self {
+        self.grace_seconds = seconds;
+        self
+    }
+
+    /// Whether the rule approves `proposal` at `now`
+    pub fn allows(
+        &self,
+        proposal: &Proposal,
+        votes: &[Vote],
+        reputation: &ReputationTracker,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if !proposal.is_pending() || !self.filter.matches(proposal) {
+            return false;
+        }
+        if now - proposal.created_at < chrono::Duration::seconds(self.grace_seconds) {
+            return false;
+        }
+        if votes.iter().any(|v| v.decision == VoteDecision::Reject) {
+            return false;
+        }
+        if let Some(min) = self.min_reputation {
+            let score = reputation
+                .get(&proposal.proposer)
+                .map(|r| r.score)
+                .unwrap_or(NEUTRAL_REPUTATION);
+            if score <= min {
+                return false;
+            }
+        }
+        if let Some(max) = self.max_payload_bytes {
+            let size = serde_json::to_vec(&proposal.payload).map(|b| b.len()).unwrap_or(usize::MAX);
+            if size > max {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl std::fmt::Display for AutoApprovalRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: where {}", self.name, self.filter)?;
+        if let Some(min) = self.min_reputation {
+            write!(f, ", reputation > {:.2}", min)?;
+        }
+        if let Some(max) = self.max_payload_bytes {
+            write!(f, ", payload <= {} bytes", max)?;
+        }
+        write!(f, ", after {}s", self.grace_seconds)
+    }
+}
+
+/// Ordered set of auto-approval rules; the first rule that allows wins
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AutoApprovalPolicy {
+    rules: Vec<AutoApprovalRule>,
+}
+
+impl AutoApprovalPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule, replacing any rule with the same name
+    pub fn add(&mut self, rule: AutoApprovalRule) {
+        match self.rules.iter_mut().find(|r| r.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// Remove a rule by name; false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.name != name);
+        self.rules.len() != before
+    }
+
+    pub fn rules(&self) -> &[AutoApprovalRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule that would approve `proposal` at `now`
+    pub fn rule_for(
+        &self,
+        proposal: &Proposal,
+        votes: &[Vote],
+        reputation: &ReputationTracker,
+        now: DateTime<Utc>,
+    ) -> Option<&AutoApprovalRule> {
+        self.rules.iter().find(|r| r.allows(proposal, votes, reputation, now))
+    }
+
+    /// Pending proposals that would be approved at `now`, with the rule name
+    pub fn eligible(
+        &self,
+        proposals: &ProposalManager,
+        voting: &VotingCoordinator,
+        reputation: &ReputationTracker,
+        now: DateTime<Utc>,
+    ) -> Vec<(ProposalId, String)> {
+        let mut pending = proposals.pending();
+        pending.sort_by_key(|p| p.created_at);
+        pending
+            .into_iter()
+            .filter_map(|p| {
+                self.rule_for(p, voting.get_votes(p.id), reputation, now)
+                    .map(|rule| (p.id, rule.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Approve every eligible proposal, returning the approved IDs and rules
+    pub fn apply(
+        &self,
+        proposals: &mut ProposalManager,
+        voting: &VotingCoordinator,
+        reputation: &ReputationTracker,
+        now: DateTime<Utc>,
+    ) -> Vec<(ProposalId, String)> {
+        let approved = self.eligible(proposals, voting, reputation, now);
+        for (id, rule) in &approved {
+            if let Some(proposal) = proposals.get_mut(*id) {
+                let grace = self.rules.iter().find(|r| &r.name == rule).map_or(0, |r| r.grace_seconds);
+                proposal.approve(Some(format!(
+                    "Auto-approved by rule {} after {}s without objection",
+                    rule, grace
+                )));
+            }
+        }
+        approved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::{CapabilityConfig, ProposalStatus, ProposalTarget};
+    use crate::schema::{AgentId, Operation};
+    use serde_json::json;
+
+    fn context_proposal(proposer: AgentId, payload: serde_json::Value, age: i64) -> Proposal {
+        let mut proposal = Proposal::new(
+            proposer,
+            Operation::Create,
+            ProposalTarget::Node { id: None, kind: Some("context".into()) },
+            payload,
+        );
+        proposal.created_at = Utc::now() - chrono::Duration::seconds(age);
+        proposal
+    }
+
+    #[test]
+    fn test_auto_approval() {
+        let mut reputation = ReputationTracker::new();
+        for _ in 0..30 {
+            reputation.get_or_create(&AgentId::Claude).record_vote_outcome(true);
+        }
+
+        let mut policy = AutoApprovalPolicy::new();
+        policy.add(
+            AutoApprovalRule::new("small-context", "operation=create AND kind=context".parse().unwrap())
+                .with_min_reputation(0.8)
+                .with_max_payload_bytes(64)
+                .with_grace_period(600),
+        );
+
+        let mut proposals = ProposalManager::new();
+        let trusted = proposals.submit(context_proposal(AgentId::Claude, json!({"k": "v"}), 900));
+        let too_new = proposals.submit(context_proposal(AgentId::Claude, json!({"k": "v"}), 60));
+        let unknown = proposals.submit(context_proposal(AgentId::Llama, json!({"k": "v"}), 900));
+        let too_big = proposals.submit(context_proposal(AgentId::Claude, json!({"k": "v".repeat(100)}), 900));
+        let objected = proposals.submit(context_proposal(AgentId::Claude, json!({"k": "v"}), 900));
+
+        let mut voting = VotingCoordinator::default();
+        voting
+            .cast_vote(Vote::new(objected, AgentId::User, VoteDecision::Reject), &CapabilityConfig::default())
+            .unwrap();
+
+        let approved = policy.apply(&mut proposals, &voting, &reputation, Utc::now());
+        assert_eq!(approved, vec![(trusted, "small-context".to_string())]);
+
+        let proposal = proposals.get(trusted).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Approved);
+        assert!(proposal.resolution_reason.as_deref().unwrap().contains("small-context"));
+        for id in [too_new, unknown, too_big, objected] {
+            assert!(proposals.get(id).unwrap().is_pending());
+        }
+
+        // Applying again approves nothing new
+        assert!(policy.apply(&mut proposals, &voting, &reputation, Utc::now()).is_empty());
+
+        // Same-named rules replace each other
+        policy.add(AutoApprovalRule::new("small-context", ProposalFilter::default()).with_grace_period(0));
+        assert_eq!(policy.rules().len(), 1);
+        assert_eq!(policy.apply(&mut proposals, &voting, &reputation, Utc::now()).len(), 3);
+        assert!(policy.remove("small-context"));
+        assert!(policy.is_empty());
+    }
+}
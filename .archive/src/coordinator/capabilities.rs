@@ -49,6 +49,9 @@ pub struct AgentCapabilities {
     pub mode: CapabilityMode,
     pub can_vote: bool,
     pub vote_weight: f32,
+    /// May vote on many proposals at once with a filter
+    #[serde(default)]
+    pub can_batch_vote: bool,
 }
 
 impl AgentCapabilities {
@@ -58,6 +61,7 @@ pub fn new(agent: AgentId) -> Self {
             mode: CapabilityMode::default(),
             can_vote: true,
             vote_weight: 1.0,
+            can_batch_vote: false,
         }
     }
 
@@ -71,6 +75,11 @@ pub fn with_vote_weight(mut self, weight: f32) -> Self {
         self
     }
 
+    pub fn with_batch_vote(mut self) -> Self {
+        self.can_batch_vote = true;
+        self
+    }
+
     pub fn as_observer(mut self) -> Self {
         self.mode = CapabilityMode::Observer;
         self.can_vote = false;
@@ -149,6 +158,11 @@ pub fn can_write_directly(&self, agent: &AgentId) -> bool {
     pub fn can_vote(&self, agent: &AgentId) -> bool {
         self.get_capabilities(agent).can_vote
     }
+
+    /// The user can always batch vote; agents need to be trusted with it
+    pub fn can_batch_vote(&self, agent: &AgentId) -> bool {
+        *agent == AgentId::User || self.get_capabilities(agent).can_batch_vote
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,214 @@
+//! Escalation of stalled proposals
+//!
+//! A rule per voting strategy says how long a proposal may stay pending
+//! before it is handed to an arbiter. An escalated proposal is settled by
+//! the arbiter's vote, is exempt from expiry, and records the escalation
+//! in its history.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::AgentId;
+use super::proposal::{ProposalId, ProposalManager};
+use super::voting::{VotingCoordinator, VotingResult};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EscalationRule {
+    /// Strategy name (see `VotingStrategy::name`); `None` applies to any
+    /// strategy without a rule of its own
+    pub strategy: Option<String>,
+    /// How long a proposal may stay pending before escalation
+    pub deadline_seconds: i64,
+    pub arbiter: AgentId,
+}
+
+impl EscalationRule {
+    pub fn new(deadline_seconds: i64, arbiter: AgentId) -> Self {
+        Self {
+            strategy: None,
+            deadline_seconds,
+            arbiter,
+        }
+    }
+
+    /// Restrict the rule to one voting strategy
+    pub fn for_strategy(mut self, strategy: impl Into<String>) -> Self {
+        self.strategy = Some(strategy.into());
+        self
+    }
+}
+
+impl std::fmt::Display for EscalationRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: escalate to {} after {}s",
+            self.strategy.as_deref().unwrap_or("any"),
+            self.arbiter,
+            self.deadline_seconds
+        )
+    }
+}
+
+/// Notice that a proposal now waits on an arbiter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Escalation {
+    pub proposal_id: ProposalId,
+    pub arbiter: AgentId,
+    pub strategy: String,
+    pub escalated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EscalationPolicy {
+    rules: Vec<EscalationRule>,
+}
+
+impl EscalationPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule, replacing any rule for the same strategy
+    pub fn add(&mut self, rule: EscalationRule) {
+        match self.rules.iter_mut().find(|r| r.strategy == rule.strategy) {
+            Some(existing) => *existing = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// Remove the rule for a strategy (`None` for the catch-all rule)
+    pub fn remove(&mut self, strategy: Option<&str>) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.strategy.as_deref() != strategy);
+        self.rules.len() != before
+    }
+
+    pub fn rules(&self) -> &[EscalationRule] {
+        &self.rules
+    }
+
+    /// The rule for a strategy, falling back to the catch-all rule
+    pub fn rule_for(&self, strategy: &str) -> Option<&EscalationRule> {
+        self.rules
+            .iter()
+            .find(|r| r.strategy.as_deref() == Some(strategy))
+            .or_else(|| self.rules.iter().find(|r| r.strategy.is_none()))
+    }
+
+    /// Escalate every pending proposal whose deadline has passed without
+    /// the strategy reaching a decision
+    ///
+    /// Each proposal is escalated at most once. The returned notices are
+    /// what the arbiters should be told about.
+    pub fn escalate_stalled(
+        &self,
+        proposals: &mut ProposalManager,
+        voting: &VotingCoordinator,
+        now: DateTime<Utc>,
+    ) -> Vec<Escalation> {
+        let strategy = voting.strategy().name();
+        let Some(rule) = self.rule_for(strategy) else {
+            return Vec::new();
+        };
+        let deadline = chrono::Duration::seconds(rule.deadline_seconds);
+
+        let mut stalled: Vec<(ProposalId, DateTime<Utc>)> = proposals
+            .pending()
+            .into_iter()
+            .filter(|p| p.escalated_to.is_none() && now - p.created_at >= deadline)
+            .map(|p| (p.id, p.created_at))
+            .collect();
+        stalled.sort_by_key(|(_, created_at)| *created_at);
+
+        let mut escalations = Vec::new();
+        for (id, _) in stalled {
+            let tally = match voting.evaluate(id) {
+                VotingResult::Pending { votes_for, votes_against, .. } => {
+                    format!("{:.1} for, {:.1} against", votes_for, votes_against)
+                }
+                // Decided but not yet processed; leave it to the strategy
+                _ => continue,
+            };
+            if let Some(proposal) = proposals.get_mut(id) {
+                proposal.escalate(
+                    rule.arbiter.clone(),
+                    format!(
+                        "No {} decision after {}s ({}); escalated to {}",
+                        strategy, rule.deadline_seconds, tally, rule.arbiter
+                    ),
+                );
+                escalations.push(Escalation {
+                    proposal_id: id,
+                    arbiter: rule.arbiter.clone(),
+                    strategy: strategy.to_string(),
+                    escalated_at: now,
+                });
+            }
+        }
+        escalations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::{
+        CapabilityConfig, Proposal, ProposalStatus, ProposalTarget, Vote, VoteDecision,
+        VotingStrategy,
+    };
+    use crate::schema::Operation;
+    use serde_json::json;
+
+    #[test]
+    fn test_escalation_to_arbiter() {
+        let mut proposals = ProposalManager::new();
+        let mut old = Proposal::new(
+            AgentId::Claude,
+            Operation::Create,
+            ProposalTarget::Node { id: None, kind: Some("insight".into()) },
+            json!({}),
+        );
+        old.created_at = Utc::now() - chrono::Duration::hours(2);
+        let old = proposals.submit(old);
+        let fresh = proposals.submit(Proposal::new(
+            AgentId::Claude,
+            Operation::Create,
+            ProposalTarget::Node { id: None, kind: Some("insight".into()) },
+            json!({}),
+        ));
+
+        let mut policy = EscalationPolicy::new();
+        policy.add(EscalationRule::new(600, AgentId::Llama).for_strategy("supermajority"));
+        policy.add(EscalationRule::new(3600, AgentId::User));
+        assert_eq!(policy.rule_for("unanimous").unwrap().arbiter, AgentId::User);
+
+        let config = CapabilityConfig::default();
+        let mut voting = VotingCoordinator::new(VotingStrategy::Unanimous).with_min_voters(3);
+        voting.cast_vote(Vote::new(old, AgentId::Claude, VoteDecision::Approve), &config).unwrap();
+
+        let escalations = policy.escalate_stalled(&mut proposals, &voting, Utc::now());
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(escalations[0].proposal_id, old);
+        assert_eq!(escalations[0].arbiter, AgentId::User);
+        assert!(proposals.get(fresh).unwrap().escalated_to.is_none());
+        // Already escalated proposals are left alone
+        assert!(policy.escalate_stalled(&mut proposals, &voting, Utc::now()).is_empty());
+
+        // The arbiter's vote settles it despite the missing quorum
+        voting.cast_vote(Vote::new(old, AgentId::User, VoteDecision::Reject), &config).unwrap();
+        let result = voting.process_proposal(old, &mut proposals);
+        assert!(matches!(result, VotingResult::Rejected { .. }));
+
+        let proposal = proposals.get(old).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Rejected);
+        let steps: Vec<_> = proposal.history.iter().map(|h| (h.status, h.escalated_to.clone())).collect();
+        assert_eq!(
+            steps,
+            vec![
+                (ProposalStatus::Pending, Some(AgentId::User)),
+                (ProposalStatus::Rejected, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Which proposals touch which nodes
+//!
+//! The impact graph is bipartite: proposals on one side, the existing nodes
+//! they would change on the other. Nodes touched by more than one proposal
+//! are where approval order matters.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::schema::{EdgeId, NodeId};
+use super::proposal::{Proposal, ProposalId, ProposalTarget};
+
+/// Existing nodes a proposal would change
+///
+/// Node proposals touch their target; edge proposals touch both endpoints,
+/// looked up with `endpoints` when only the edge ID is known. Proposals to
+/// create a node touch nothing that exists yet.
+pub fn touched_nodes(
+    proposal: &Proposal,
+    endpoints: impl Fn(EdgeId) -> Option<(NodeId, NodeId)>,
+) -> Vec<NodeId> {
+    let mut nodes = Vec::new();
+    match &proposal.target {
+        ProposalTarget::Node { id: Some(id), .. } => nodes.push(*id),
+        ProposalTarget::Node { id: None, .. } => {}
+        ProposalTarget::Edge { id, from, to } => {
+            let known = id.and_then(&endpoints);
+            for node in [from.or(known.map(|k| k.0)), to.or(known.map(|k| k.1))].into_iter().flatten() {
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+            }
+        }
+    }
+    nodes
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImpactGraph {
+    by_node: BTreeMap<NodeId, BTreeSet<ProposalId>>,
+    by_proposal: BTreeMap<ProposalId, BTreeSet<NodeId>>,
+}
+
+impl ImpactGraph {
+    pub fn build<'a>(
+        proposals: impl IntoIterator<Item = &'a Proposal>,
+        endpoints: impl Fn(EdgeId) -> Option<(NodeId, NodeId)>,
+    ) -> Self {
+        let mut graph = Self::default();
+        for proposal in proposals {
+            for node in touched_nodes(proposal, &endpoints) {
+                graph.by_node.entry(node).or_default().insert(proposal.id);
+                graph.by_proposal.entry(proposal.id).or_default().insert(node);
+            }
+        }
+        graph
+    }
+
+    /// Proposals touching a node
+    pub fn proposals_for(&self, node: NodeId) -> Vec<ProposalId> {
+        self.by_node.get(&node).map(|p| p.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Nodes a proposal touches
+    pub fn nodes_for(&self, proposal: ProposalId) -> Vec<NodeId> {
+        self.by_proposal.get(&proposal).map(|n| n.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Nodes touched by more than one proposal, with those proposals
+    pub fn contended(&self) -> Vec<(NodeId, Vec<ProposalId>)> {
+        self.by_node
+            .iter()
+            .filter(|(_, proposals)| proposals.len() > 1)
+            .map(|(node, proposals)| (*node, proposals.iter().copied().collect()))
+            .collect()
+    }
+
+    /// Proposals that share at least one node with `proposal`
+    pub fn overlapping(&self, proposal: ProposalId) -> Vec<ProposalId> {
+        let mut overlapping = BTreeSet::new();
+        for node in self.nodes_for(proposal) {
+            overlapping.extend(self.proposals_for(node));
+        }
+        overlapping.remove(&proposal);
+        overlapping.into_iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_node.is_empty()
+    }
+
+    /// Graphviz DOT of the bipartite graph
+    ///
+    /// `label` names each vertex; contended nodes are drawn in red.
+    pub fn to_dot(&self, label: impl Fn(Vertex) -> String) -> String {
+        let escape = |s: String| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph impact {\n    rankdir=LR;\n");
+        for proposal in self.by_proposal.keys() {
+            dot.push_str(&format!(
+                "    \"p{}\" [shape=box, label=\"{}\"];\n",
+                proposal,
+                escape(label(Vertex::Proposal(*proposal)))
+            ));
+        }
+        for (node, proposals) in &self.by_node {
+            let color = if proposals.len() > 1 { ", color=red" } else { "" };
+            dot.push_str(&format!(
+                "    \"n{}\" [shape=ellipse, label=\"{}\"{}];\n",
+                node,
+                escape(label(Vertex::Node(*node))),
+                color
+            ));
+        }
+        for (proposal, nodes) in &self.by_proposal {
+            for node in nodes {
+                dot.push_str(&format!("    \"p{}\" -> \"n{}\";\n", proposal, node));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// A vertex of the impact graph, for labelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vertex {
+    Proposal(ProposalId),
+    Node(NodeId),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, Operation};
+    use serde_json::json;
+    use ulid::Ulid;
+
+    fn proposal(target: ProposalTarget) -> Proposal {
+        Proposal::new(AgentId::Claude, Operation::Update, target, json!({}))
+    }
+
+    #[test]
+    fn test_impact_graph() {
+        let (a, b, c) = (Ulid::new(), Ulid::new(), Ulid::new());
+        let edge = Ulid::new();
+        let update_a = proposal(ProposalTarget::Node { id: Some(a), kind: None });
+        let unlink = proposal(ProposalTarget::Edge { id: Some(edge), from: None, to: None });
+        let link = proposal(ProposalTarget::Edge { id: None, from: Some(b), to: Some(c) });
+        let create = proposal(ProposalTarget::Node { id: None, kind: Some("task".into()) });
+
+        let graph = ImpactGraph::build(
+            [&update_a, &unlink, &link, &create],
+            |id| (id == edge).then_some((a, b)),
+        );
+
+        assert_eq!(graph.nodes_for(unlink.id).len(), 2);
+        assert!(graph.nodes_for(create.id).is_empty());
+        assert_eq!(graph.proposals_for(c), vec![link.id]);
+
+        let contended: Vec<NodeId> = graph.contended().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(contended.len(), 2);
+        assert!(contended.contains(&a) && contended.contains(&b));
+        let mut overlapping = graph.overlapping(unlink.id);
+        overlapping.sort();
+        let mut expected = vec![update_a.id, link.id];
+        expected.sort();
+        assert_eq!(overlapping, expected);
+
+        let dot = graph.to_dot(|v| match v {
+            Vertex::Proposal(id) => format!("proposal {}", id),
+            Vertex::Node(id) => format!("node \"{}\"", id),
+        });
+        assert!(dot.starts_with("digraph impact {"));
+        assert_eq!(dot.matches(" -> ").count(), 5);
+        assert_eq!(dot.matches("color=red").count(), 2);
+        assert!(dot.contains("\\\""));
+    }
+}
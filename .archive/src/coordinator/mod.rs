@@ -4,13 +4,37 @@
 //! - Proposal mode (Direct vs Proposal capabilities)
 //! - Voting system for proposal approval
 //! - Agent reputation tracking
+//! - In-memory governance simulation
+//! - Governance telemetry for strategy tuning
+//! - Auto-approval of low-risk proposals
+//! - Escalation of stalled proposals to an arbiter
+//! - Impact graph of pending proposals over the nodes they touch
 
 mod capabilities;
 mod proposal;
 mod voting;
 mod reputation;
+mod simulation;
+mod telemetry;
+mod auto_approval;
+mod escalation;
+mod impact;
 
 pub use capabilities::{CapabilityMode, AgentCapabilities, CapabilityConfig};
-pub use proposal::{Proposal, ProposalStatus, ProposalManager};
-pub use voting::{Vote, VoteDecision, VotingStrategy, VotingCoordinator};
+pub use proposal::{
+    Proposal, ProposalFilter, ProposalHistoryEntry, ProposalId, ProposalStatus, ProposalTarget,
+    ProposalManager, PROPOSALS_KEY,
+};
+pub use voting::{BatchVote, Vote, VoteDecision, VotingStrategy, VotingCoordinator, VotingResult};
 pub use reputation::{Reputation, ReputationTracker};
+pub use simulation::{
+    AgentBehavior, Simulation, SimulationConfig, SimulationReport, SimulationCheckpoint,
+    SimulatedAgentReport,
+};
+pub use telemetry::{
+    DecisionRecord, VoteRecord, StrategyStats, AgentInfluence, GovernanceReport,
+    GovernanceTelemetry,
+};
+pub use auto_approval::{AutoApprovalPolicy, AutoApprovalRule};
+pub use escalation::{Escalation, EscalationPolicy, EscalationRule};
+pub use impact::{touched_nodes, ImpactGraph, Vertex};
@@ -13,6 +13,9 @@
 
 pub type ProposalId = Ulid;
 
+/// Metadata key holding the persisted `ProposalManager`
+pub const PROPOSALS_KEY: &str = "proposals";
+
 /// A proposed mutation to the state graph
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proposal {
@@ -26,6 +29,23 @@ pub struct Proposal {
     pub created_at: DateTime<Utc>,
     pub resolved_at: Option<DateTime<Utc>>,
     pub resolution_reason: Option<String>,
+    /// Agent whose vote now decides the proposal, once voting has stalled
+    #[serde(default)]
+    pub escalated_to: Option<AgentId>,
+    /// Escalations and resolutions, oldest first
+    #[serde(default)]
+    pub history: Vec<ProposalHistoryEntry>,
+}
+
+/// One step in a proposal's life after submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposalHistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub status: ProposalStatus,
+    /// Set when the proposal was escalated at this step
+    #[serde(default)]
+    pub escalated_to: Option<AgentId>,
+    pub note: Option<String>,
 }
 
 /// Target of a proposed mutation
@@ -53,6 +73,116 @@ pub enum ProposalStatus {
     Withdrawn,
 }
 
+impl std::fmt::Display for ProposalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Approved => "approved",
+            ProposalStatus::Rejected => "rejected",
+            ProposalStatus::Expired => "expired",
+            ProposalStatus::Withdrawn => "withdrawn",
+        })
+    }
+}
+
+impl std::str::FromStr for ProposalStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(ProposalStatus::Pending),
+            "approved" => Ok(ProposalStatus::Approved),
+            "rejected" => Ok(ProposalStatus::Rejected),
+            "expired" => Ok(ProposalStatus::Expired),
+            "withdrawn" => Ok(ProposalStatus::Withdrawn),
+            _ => Err(format!("Unknown proposal status: {}", s)),
+        }
+    }
+}
+
+impl ProposalTarget {
+    /// Node kind of the target, when known
+    pub fn kind(&self) -> Option<&str> {
+        match self {
+            ProposalTarget::Node { kind, .. } => kind.as_deref(),
+            ProposalTarget::Edge { .. } => None,
+        }
+    }
+}
+
+impl std::str::FromStr for ProposalTarget {
+    type Err = String;
+
+    /// `node:ID`, `edge:ID` or `new:kind`; an existing node's kind is left
+    /// for the caller to look up
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("node", id)) => {
+                let id = id.parse().map_err(|e| format!("Invalid ID: {}", e))?;
+                Ok(ProposalTarget::Node { id: Some(id), kind: None })
+            }
+            Some(("edge", id)) => {
+                let id = id.parse().map_err(|e| format!("Invalid ID: {}", e))?;
+                Ok(ProposalTarget::Edge { id: Some(id), from: None, to: None })
+            }
+            Some(("new", kind)) => Ok(ProposalTarget::Node { id: None, kind: Some(kind.to_string()) }),
+            _ => Err("Target must be node:ID, edge:ID or new:kind".to_string()),
+        }
+    }
+}
+
+/// Selects proposals by field, e.g. `proposer=module:scraper AND kind=context`
+///
+/// Clauses are `field=value` joined by `AND`. Fields: `proposer`, `kind`,
+/// `operation` and `status`. Values compare case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalFilter {
+    clauses: Vec<(String, String)>,
+}
+
+impl std::str::FromStr for ProposalFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let mut clauses = Vec::new();
+        for clause in words.split(|w| w.eq_ignore_ascii_case("and")) {
+            let clause = clause.join(" ");
+            let (field, value) = clause
+                .split_once('=')
+                .ok_or_else(|| format!("Expected field=value, got {:?}", clause))?;
+            let field = field.trim().to_lowercase();
+            if !matches!(field.as_str(), "proposer" | "kind" | "operation" | "status") {
+                return Err(format!("Unknown proposal field: {}", field));
+            }
+            clauses.push((field, value.trim().to_lowercase()));
+        }
+        Ok(Self { clauses })
+    }
+}
+
+impl std::fmt::Display for ProposalFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let clauses: Vec<String> = self.clauses.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        write!(f, "{}", clauses.join(" AND "))
+    }
+}
+
+impl ProposalFilter {
+    pub fn matches(&self, proposal: &Proposal) -> bool {
+        self.clauses.iter().all(|(field, value)| {
+            let actual = match field.as_str() {
+                "proposer" => proposal.proposer.to_string(),
+                "kind" => proposal.target.kind().unwrap_or_default().to_lowercase(),
+                "operation" => format!("{:?}", proposal.operation).to_lowercase(),
+                "status" => proposal.status.to_string(),
+                _ => return false,
+            };
+            &actual == value
+        })
+    }
+}
+
 impl Proposal {
     /// Create a new proposal
     pub fn new(proposer: AgentId, operation: Operation, target: ProposalTarget, payload: Value) -> Self {
@@ -67,6 +197,8 @@ pub fn new(proposer: AgentId, operation: Operation, target: ProposalTarget, payl
             created_at: Utc::now(),
             resolved_at: None,
             resolution_reason: None,
+            escalated_to: None,
+            history: Vec::new(),
         }
     }
 
@@ -81,28 +213,64 @@ pub fn is_pending(&self) -> bool {
         self.status == ProposalStatus::Pending
     }
 
+    /// Whether this approved proposal undoes approved `earlier`: it deletes
+    /// or unlinks the same node or edge that `earlier` updated or linked
+    pub fn reverses(&self, earlier: &Proposal) -> bool {
+        let same_target = match (&self.target, &earlier.target) {
+            (ProposalTarget::Node { id: Some(a), .. }, ProposalTarget::Node { id: Some(b), .. }) => a == b,
+            (ProposalTarget::Edge { id: Some(a), .. }, ProposalTarget::Edge { id: Some(b), .. }) => a == b,
+            _ => false,
+        };
+        same_target
+            && self.id != earlier.id
+            && self.status == ProposalStatus::Approved
+            && earlier.status == ProposalStatus::Approved
+            && matches!(self.operation, Operation::Delete | Operation::Unlink)
+            && matches!(earlier.operation, Operation::Update | Operation::Link)
+    }
+
     /// Approve the proposal
     pub fn approve(&mut self, reason: Option<String>) {
-        self.status = ProposalStatus::Approved;
-        self.resolved_at = Some(Utc::now());
-        self.resolution_reason = reason;
+        self.resolve(ProposalStatus::Approved, reason);
     }
 
     /// Reject the proposal
     pub fn reject(&mut self, reason: Option<String>) {
-        self.status = ProposalStatus::Rejected;
-        self.resolved_at = Some(Utc::now());
-        self.resolution_reason = reason;
+        self.resolve(ProposalStatus::Rejected, reason);
     }
 
     /// Withdraw the proposal
     pub fn withdraw(&mut self) {
-        self.status = ProposalStatus::Withdrawn;
-        self.resolved_at = Some(Utc::now());
+        self.resolve(ProposalStatus::Withdrawn, None);
+    }
+
+    fn resolve(&mut self, status: ProposalStatus, reason: Option<String>) {
+        let now = Utc::now();
+        self.status = status;
+        self.resolved_at = Some(now);
+        self.resolution_reason = reason.clone();
+        self.history.push(ProposalHistoryEntry {
+            timestamp: now,
+            status,
+            escalated_to: None,
+            note: reason,
+        });
+    }
+
+    /// Hand the decision to `arbiter`; their vote now settles the proposal
+    pub fn escalate(&mut self, arbiter: AgentId, note: impl Into<String>) {
+        self.history.push(ProposalHistoryEntry {
+            timestamp: Utc::now(),
+            status: self.status,
+            escalated_to: Some(arbiter.clone()),
+            note: Some(note.into()),
+        });
+        self.escalated_to = Some(arbiter);
     }
 }
 
 /// Manages pending proposals
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProposalManager {
     proposals: HashMap<ProposalId, Proposal>,
     /// Maximum time a proposal can be pending (in seconds)
@@ -160,6 +328,17 @@ pub fn all(&self) -> Vec<&Proposal> {
         self.proposals.values().collect()
     }
 
+    /// Pending proposals matching a filter, oldest first
+    pub fn matching(&self, filter: &ProposalFilter) -> Vec<&Proposal> {
+        let mut matching: Vec<&Proposal> = self
+            .proposals
+            .values()
+            .filter(|p| p.is_pending() && filter.matches(p))
+            .collect();
+        matching.sort_by_key(|p| p.created_at);
+        matching
+    }
+
     /// List proposals by proposer
     pub fn by_proposer(&self, agent: &AgentId) -> Vec<&Proposal> {
         self.proposals
@@ -169,15 +348,18 @@ pub fn by_proposer(&self, agent: &AgentId) -> Vec<&Proposal> {
     }
 
     /// Expire old pending proposals
+    ///
+    /// Escalated proposals wait for their arbiter and never expire.
     pub fn expire_old(&mut self) {
         let now = Utc::now();
         let expiry = chrono::Duration::seconds(self.expiry_seconds);
 
         for proposal in self.proposals.values_mut() {
-            if proposal.is_pending() && now - proposal.created_at > expiry {
-                proposal.status = ProposalStatus::Expired;
-                proposal.resolved_at = Some(now);
-                proposal.resolution_reason = Some("Expired".into());
+            if proposal.is_pending()
+                && proposal.escalated_to.is_none()
+                && now - proposal.created_at > expiry
+            {
+                proposal.resolve(ProposalStatus::Expired, Some("Expired".into()));
             }
         }
     }
@@ -234,4 +416,21 @@ fn test_proposal_with_rationale() {
 
         assert!(proposal.rationale.is_some());
     }
+
+    #[test]
+    fn test_deleting_reverses_an_update() {
+        let node = Ulid::new();
+        let proposal = |operation, id| {
+            let mut p = Proposal::new(AgentId::Llama, operation, ProposalTarget::Node { id, kind: None }, json!({}));
+            p.approve(None);
+            p
+        };
+        let update = proposal(Operation::Update, Some(node));
+        let delete = proposal(Operation::Delete, Some(node));
+
+        assert!(delete.reverses(&update));
+        assert!(!update.reverses(&delete));
+        assert!(!delete.reverses(&proposal(Operation::Update, Some(Ulid::new()))));
+        assert!(!delete.reverses(&proposal(Operation::Update, None)));
+    }
 }
@@ -8,7 +8,6 @@
 
 use crate::schema::AgentId;
 use super::voting::{VoteDecision, VotingResult};
-use super::proposal::ProposalId;
 
 /// Reputation score for an agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,393 @@
+//! Governance simulation with synthetic agents
+//!
+//! Runs the proposal/voting/reputation loop in-memory so voting strategies
+//! and reputation settings can be evaluated before they govern real state.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::schema::{AgentId, Operation};
+use super::capabilities::{AgentCapabilities, CapabilityConfig, CapabilityMode};
+use super::proposal::{Proposal, ProposalManager, ProposalTarget};
+use super::reputation::ReputationTracker;
+use super::voting::{Vote, VoteDecision, VotingCoordinator, VotingResult, VotingStrategy};
+
+/// How a synthetic agent decides its votes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentBehavior {
+    /// Votes for the correct outcome (subject to `honest_accuracy`)
+    Honest,
+    /// Votes approve or reject with equal probability
+    Random,
+    /// Always votes against the correct outcome
+    Adversarial,
+}
+
+impl std::fmt::Display for AgentBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentBehavior::Honest => write!(f, "honest"),
+            AgentBehavior::Random => write!(f, "random"),
+            AgentBehavior::Adversarial => write!(f, "adversarial"),
+        }
+    }
+}
+
+impl std::str::FromStr for AgentBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "honest" => Ok(AgentBehavior::Honest),
+            "random" => Ok(AgentBehavior::Random),
+            "adversarial" => Ok(AgentBehavior::Adversarial),
+            _ => Err(format!("Unknown agent behavior: {}", s)),
+        }
+    }
+}
+
+/// Parameters for a simulation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// One behavior per synthetic agent
+    pub behaviors: Vec<AgentBehavior>,
+    /// Number of proposals to run through the loop
+    pub proposals: usize,
+    /// Voting strategy under test
+    pub strategy: VotingStrategy,
+    /// Feed reputation into vote weights
+    pub reputation_weighting: bool,
+    /// Probability that an honest agent votes correctly
+    pub honest_accuracy: f64,
+    /// Probability that a proposal is beneficial (should be approved)
+    pub beneficial_rate: f64,
+    /// Number of convergence checkpoints to record
+    pub checkpoints: usize,
+    /// RNG seed for reproducible runs
+    pub seed: Option<u64>,
+}
+
+impl SimulationConfig {
+    /// Create a config with the default behavior mix
+    ///
+    /// Roughly a fifth of the agents are random and a fifth adversarial;
+    /// the rest are honest.
+    pub fn new(agents: usize, proposals: usize) -> Self {
+        let adversarial = agents / 5;
+        let random = agents / 5;
+        let honest = agents - adversarial - random;
+        Self::with_mix(honest, random, adversarial, proposals)
+    }
+
+    /// Create a config with an explicit behavior mix
+    pub fn with_mix(honest: usize, random: usize, adversarial: usize, proposals: usize) -> Self {
+        let mut behaviors = Vec::with_capacity(honest + random + adversarial);
+        behaviors.extend(std::iter::repeat(AgentBehavior::Honest).take(honest));
+        behaviors.extend(std::iter::repeat(AgentBehavior::Random).take(random));
+        behaviors.extend(std::iter::repeat(AgentBehavior::Adversarial).take(adversarial));
+
+        Self {
+            behaviors,
+            proposals,
+            strategy: VotingStrategy::default(),
+            reputation_weighting: true,
+            honest_accuracy: 0.9,
+            beneficial_rate: 0.7,
+            checkpoints: 10,
+            seed: None,
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: VotingStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_reputation_weighting(mut self, enabled: bool) -> Self {
+        self.reputation_weighting = enabled;
+        self
+    }
+
+    /// Agent identity used for the synthetic agent at `index`
+    pub fn agent_id(index: usize) -> AgentId {
+        AgentId::Module(format!("sim-{}", index))
+    }
+}
+
+/// Final state of one synthetic agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedAgentReport {
+    pub agent: AgentId,
+    pub behavior: AgentBehavior,
+    pub reputation: f32,
+    pub accuracy: f32,
+    pub vote_weight: f32,
+}
+
+/// Snapshot of the simulation at a point in the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationCheckpoint {
+    pub proposals_processed: usize,
+    /// Fraction of resolved proposals whose outcome matched ground truth
+    pub decision_accuracy: f32,
+    /// Mean reputation per behavior
+    pub mean_reputation: BTreeMap<String, f32>,
+}
+
+/// Outcome of a simulation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub strategy: String,
+    pub proposals: usize,
+    pub approved: usize,
+    pub rejected: usize,
+    pub unresolved: usize,
+    /// Resolved proposals whose outcome matched ground truth
+    pub correct_decisions: usize,
+    pub false_approvals: usize,
+    pub false_rejections: usize,
+    pub agents: Vec<SimulatedAgentReport>,
+    pub checkpoints: Vec<SimulationCheckpoint>,
+}
+
+impl SimulationReport {
+    /// Fraction of resolved proposals decided correctly
+    pub fn decision_accuracy(&self) -> f32 {
+        let resolved = self.approved + self.rejected;
+        if resolved == 0 {
+            0.0
+        } else {
+            self.correct_decisions as f32 / resolved as f32
+        }
+    }
+}
+
+/// In-memory governance simulation
+pub struct Simulation {
+    config: SimulationConfig,
+    rng: StdRng,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng }
+    }
+
+    fn decide(&mut self, behavior: AgentBehavior, beneficial: bool) -> VoteDecision {
+        let correct = if beneficial { VoteDecision::Approve } else { VoteDecision::Reject };
+        let wrong = if beneficial { VoteDecision::Reject } else { VoteDecision::Approve };
+
+        match behavior {
+            AgentBehavior::Honest => {
+                if self.rng.gen_bool(self.config.honest_accuracy.clamp(0.0, 1.0)) {
+                    correct
+                } else {
+                    wrong
+                }
+            }
+            AgentBehavior::Random => {
+                if self.rng.gen_bool(0.5) {
+                    VoteDecision::Approve
+                } else {
+                    VoteDecision::Reject
+                }
+            }
+            AgentBehavior::Adversarial => wrong,
+        }
+    }
+
+    fn mean_reputation(&self, tracker: &ReputationTracker) -> BTreeMap<String, f32> {
+        let mut sums: BTreeMap<String, (f32, usize)> = BTreeMap::new();
+        for (i, behavior) in self.config.behaviors.iter().enumerate() {
+            let score = tracker
+                .get(&SimulationConfig::agent_id(i))
+                .map(|r| r.score)
+                .unwrap_or(0.5);
+            let entry = sums.entry(behavior.to_string()).or_insert((0.0, 0));
+            entry.0 += score;
+            entry.1 += 1;
+        }
+        sums.into_iter()
+            .map(|(k, (sum, n))| (k, sum / n as f32))
+            .collect()
+    }
+
+    /// Run the simulation to completion
+    pub fn run(mut self) -> SimulationReport {
+        let agents: Vec<AgentId> = (0..self.config.behaviors.len())
+            .map(SimulationConfig::agent_id)
+            .collect();
+
+        let mut capabilities = CapabilityConfig::default();
+        let mut reputation = ReputationTracker::new();
+        let mut proposals = ProposalManager::new();
+
+        let mut report = SimulationReport {
+            strategy: self.config.strategy.to_string(),
+            proposals: self.config.proposals,
+            approved: 0,
+            rejected: 0,
+            unresolved: 0,
+            correct_decisions: 0,
+            false_approvals: 0,
+            false_rejections: 0,
+            agents: Vec::new(),
+            checkpoints: Vec::new(),
+        };
+
+        let checkpoint_every = (self.config.proposals / self.config.checkpoints.max(1)).max(1);
+
+        for round in 0..self.config.proposals {
+            let proposer = agents
+                .get(round % agents.len().max(1))
+                .cloned()
+                .unwrap_or(AgentId::System);
+            let beneficial = self.rng.gen_bool(self.config.beneficial_rate.clamp(0.0, 1.0));
+
+            let proposal = Proposal::new(
+                proposer,
+                Operation::Create,
+                ProposalTarget::Node { id: None, kind: Some("context".into()) },
+                serde_json::json!({ "round": round, "beneficial": beneficial }),
+            );
+            let proposal_id = proposals.submit(proposal);
+
+            // Fresh coordinator per proposal keeps vote bookkeeping bounded
+            let mut voting = VotingCoordinator::new(self.config.strategy.clone());
+            let mut cast = Vec::with_capacity(agents.len());
+
+            for (i, agent) in agents.iter().enumerate() {
+                let weight = if self.config.reputation_weighting {
+                    reputation.calculate_vote_weight(agent)
+                } else {
+                    1.0
+                };
+                let caps = AgentCapabilities::new(agent.clone())
+                    .with_mode(CapabilityMode::Proposal)
+                    .with_vote_weight(weight);
+                // Default config allows runtime changes
+                let _ = capabilities.set_capabilities(caps);
+
+                let decision = self.decide(self.config.behaviors[i], beneficial);
+                if voting
+                    .cast_vote(Vote::new(proposal_id, agent.clone(), decision), &capabilities)
+                    .is_ok()
+                {
+                    cast.push((agent.clone(), decision));
+                }
+            }
+
+            let result = voting.process_proposal(proposal_id, &mut proposals);
+
+            match &result {
+                VotingResult::Approved { .. } => {
+                    report.approved += 1;
+                    if beneficial {
+                        report.correct_decisions += 1;
+                    } else {
+                        report.false_approvals += 1;
+                    }
+                }
+                VotingResult::Rejected { .. } => {
+                    report.rejected += 1;
+                    if beneficial {
+                        report.false_rejections += 1;
+                    } else {
+                        report.correct_decisions += 1;
+                    }
+                }
+                VotingResult::Pending { .. } => report.unresolved += 1,
+            }
+
+            for (agent, decision) in &cast {
+                reputation.record_outcome(agent, *decision, &result);
+            }
+
+            if (round + 1) % checkpoint_every == 0 || round + 1 == self.config.proposals {
+                let resolved = report.approved + report.rejected;
+                report.checkpoints.push(SimulationCheckpoint {
+                    proposals_processed: round + 1,
+                    decision_accuracy: if resolved == 0 {
+                        0.0
+                    } else {
+                        report.correct_decisions as f32 / resolved as f32
+                    },
+                    mean_reputation: self.mean_reputation(&reputation),
+                });
+            }
+        }
+
+        report.agents = agents
+            .iter()
+            .zip(self.config.behaviors.iter())
+            .map(|(agent, behavior)| {
+                let rep = reputation.get(agent);
+                SimulatedAgentReport {
+                    agent: agent.clone(),
+                    behavior: *behavior,
+                    reputation: rep.map(|r| r.score).unwrap_or(0.5),
+                    accuracy: rep.map(|r| r.accuracy()).unwrap_or(0.5),
+                    vote_weight: reputation.calculate_vote_weight(agent),
+                }
+            })
+            .collect();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulation_is_reproducible() {
+        let config = SimulationConfig::new(5, 50).with_seed(42);
+        let a = Simulation::new(config.clone()).run();
+        let b = Simulation::new(config).run();
+
+        assert_eq!(a.approved, b.approved);
+        assert_eq!(a.rejected, b.rejected);
+        assert_eq!(a.correct_decisions, b.correct_decisions);
+    }
+
+    #[test]
+    fn test_honest_agents_gain_reputation() {
+        let config = SimulationConfig::with_mix(4, 0, 1, 200).with_seed(7);
+        let report = Simulation::new(config).run();
+
+        let honest = report
+            .agents
+            .iter()
+            .find(|a| a.behavior == AgentBehavior::Honest)
+            .unwrap();
+        let adversarial = report
+            .agents
+            .iter()
+            .find(|a| a.behavior == AgentBehavior::Adversarial)
+            .unwrap();
+
+        assert!(honest.reputation > adversarial.reputation);
+        assert_eq!(report.approved + report.rejected + report.unresolved, 200);
+    }
+
+    #[test]
+    fn test_checkpoints_recorded() {
+        let config = SimulationConfig::new(5, 100).with_seed(1);
+        let report = Simulation::new(config).run();
+
+        assert_eq!(report.checkpoints.len(), 10);
+        assert_eq!(report.checkpoints.last().unwrap().proposals_processed, 100);
+    }
+}
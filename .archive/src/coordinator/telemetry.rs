@@ -0,0 +1,316 @@
+//! Governance telemetry
+//!
+//! Records how each voting strategy resolved proposals and whether those
+//! decisions held up, so the coordinator can be tuned over time.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::schema::AgentId;
+use super::proposal::{Proposal, ProposalId, ProposalStatus};
+use super::voting::{Vote, VoteDecision, VotingStrategy};
+
+/// A single resolved proposal as seen by telemetry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub proposal_id: ProposalId,
+    pub proposer: AgentId,
+    pub strategy: String,
+    pub status: ProposalStatus,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: DateTime<Utc>,
+    pub votes: Vec<VoteRecord>,
+    /// Set when the realized outcome contradicted the decision
+    pub overturned: bool,
+    pub overturn_reason: Option<String>,
+}
+
+/// A vote as stored in telemetry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRecord {
+    pub voter: AgentId,
+    pub decision: VoteDecision,
+    pub weight: f32,
+}
+
+impl DecisionRecord {
+    /// Seconds between submission and resolution
+    pub fn resolution_seconds(&self) -> i64 {
+        (self.resolved_at - self.created_at).num_seconds()
+    }
+
+    /// The vote decision that matched the outcome, if the outcome was a vote
+    fn winning_decision(&self) -> Option<VoteDecision> {
+        match self.status {
+            ProposalStatus::Approved => Some(VoteDecision::Approve),
+            ProposalStatus::Rejected => Some(VoteDecision::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Aggregated figures for one voting strategy
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StrategyStats {
+    pub decisions: usize,
+    pub approved: usize,
+    pub rejected: usize,
+    pub expired: usize,
+    pub overturned: usize,
+    pub approval_rate: f32,
+    pub avg_resolution_seconds: f64,
+}
+
+/// How much a single agent shaped outcomes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentInfluence {
+    pub votes: usize,
+    /// Votes that matched the outcome
+    pub aligned: usize,
+    /// Share of all winning vote weight contributed by this agent
+    pub influence: f32,
+}
+
+/// Governance report across all recorded decisions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GovernanceReport {
+    pub decisions: usize,
+    pub by_strategy: BTreeMap<String, StrategyStats>,
+    pub by_agent: BTreeMap<String, AgentInfluence>,
+}
+
+/// Collects decision records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GovernanceTelemetry {
+    records: Vec<DecisionRecord>,
+}
+
+impl GovernanceTelemetry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a resolved proposal; pending and already recorded proposals
+    /// are ignored
+    pub fn record(&mut self, proposal: &Proposal, strategy: &VotingStrategy, votes: &[Vote]) {
+        let Some(resolved_at) = proposal.resolved_at else {
+            return;
+        };
+        if proposal.is_pending() || self.records.iter().any(|r| r.proposal_id == proposal.id) {
+            return;
+        }
+
+        self.records.push(DecisionRecord {
+            proposal_id: proposal.id,
+            proposer: proposal.proposer.clone(),
+            strategy: strategy.to_string(),
+            status: proposal.status,
+            created_at: proposal.created_at,
+            resolved_at,
+            votes: votes
+                .iter()
+                .map(|v| VoteRecord {
+                    voter: v.voter.clone(),
+                    decision: v.decision,
+                    weight: v.weight,
+                })
+                .collect(),
+            overturned: false,
+            overturn_reason: None,
+        });
+    }
+
+    /// Mark a decision as overturned by its realized outcome
+    pub fn mark_overturned(&mut self, proposal_id: ProposalId, reason: Option<String>) -> bool {
+        match self.records.iter_mut().find(|r| r.proposal_id == proposal_id) {
+            Some(record) => {
+                record.overturned = true;
+                record.overturn_reason = reason;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn records(&self) -> &[DecisionRecord] {
+        &self.records
+    }
+
+    /// Build a report over decisions resolved at or after `since`
+    pub fn report(&self, since: Option<DateTime<Utc>>) -> GovernanceReport {
+        let mut report = GovernanceReport::default();
+        let mut resolution_totals: BTreeMap<String, i64> = BTreeMap::new();
+        let mut winning_weight = 0.0f32;
+        let mut agent_weight: BTreeMap<String, f32> = BTreeMap::new();
+
+        for record in self
+            .records
+            .iter()
+            .filter(|r| since.map(|s| r.resolved_at >= s).unwrap_or(true))
+        {
+            report.decisions += 1;
+
+            let stats = report.by_strategy.entry(record.strategy.clone()).or_default();
+            stats.decisions += 1;
+            match record.status {
+                ProposalStatus::Approved => stats.approved += 1,
+                ProposalStatus::Rejected => stats.rejected += 1,
+                ProposalStatus::Expired => stats.expired += 1,
+                _ => {}
+            }
+            if record.overturned {
+                stats.overturned += 1;
+            }
+            *resolution_totals.entry(record.strategy.clone()).or_default() +=
+                record.resolution_seconds();
+
+            let winner = record.winning_decision();
+            for vote in &record.votes {
+                let influence = report.by_agent.entry(vote.voter.to_string()).or_default();
+                influence.votes += 1;
+                if Some(vote.decision) == winner {
+                    influence.aligned += 1;
+                    winning_weight += vote.weight;
+                    *agent_weight.entry(vote.voter.to_string()).or_default() += vote.weight;
+                }
+            }
+        }
+
+        for (strategy, stats) in report.by_strategy.iter_mut() {
+            let voted = stats.approved + stats.rejected;
+            stats.approval_rate = if voted == 0 {
+                0.0
+            } else {
+                stats.approved as f32 / voted as f32
+            };
+            stats.avg_resolution_seconds =
+                resolution_totals.get(strategy).copied().unwrap_or(0) as f64
+                    / stats.decisions as f64;
+        }
+
+        if winning_weight > 0.0 {
+            for (agent, influence) in report.by_agent.iter_mut() {
+                influence.influence =
+                    agent_weight.get(agent).copied().unwrap_or(0.0) / winning_weight;
+            }
+        }
+
+        report
+    }
+
+    /// Export telemetry to JSON
+    pub fn export(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Import telemetry from JSON, appending to existing records
+    pub fn import(&mut self, data: serde_json::Value) -> Result<(), String> {
+        let imported: GovernanceTelemetry =
+            serde_json::from_value(data).map_err(|e| e.to_string())?;
+        self.records.extend(imported.records);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::capabilities::CapabilityConfig;
+    use crate::coordinator::proposal::{ProposalManager, ProposalTarget};
+    use crate::coordinator::voting::VotingCoordinator;
+    use crate::schema::Operation;
+    use serde_json::json;
+
+    fn resolved_proposal(
+        manager: &mut ProposalManager,
+        voting: &mut VotingCoordinator,
+        votes: &[(AgentId, VoteDecision)],
+    ) -> ProposalId {
+        let config = CapabilityConfig::default();
+        let id = manager.submit(Proposal::new(
+            AgentId::Claude,
+            Operation::Create,
+            ProposalTarget::Node { id: None, kind: Some("insight".into()) },
+            json!({}),
+        ));
+        for (agent, decision) in votes {
+            voting
+                .cast_vote(Vote::new(id, agent.clone(), *decision), &config)
+                .unwrap();
+        }
+        voting.process_proposal(id, manager);
+        id
+    }
+
+    #[test]
+    fn test_report_rates_and_influence() {
+        let mut manager = ProposalManager::new();
+        let mut voting = VotingCoordinator::new(VotingStrategy::SimpleMajority);
+        let mut telemetry = GovernanceTelemetry::new();
+
+        let approved = resolved_proposal(
+            &mut manager,
+            &mut voting,
+            &[
+                (AgentId::User, VoteDecision::Approve),
+                (AgentId::Llama, VoteDecision::Reject),
+                (AgentId::Claude, VoteDecision::Approve),
+            ],
+        );
+        let rejected = resolved_proposal(
+            &mut manager,
+            &mut voting,
+            &[(AgentId::User, VoteDecision::Reject)],
+        );
+
+        for id in [approved, rejected] {
+            telemetry.record(manager.get(id).unwrap(), voting.strategy(), voting.get_votes(id));
+        }
+        assert!(telemetry.mark_overturned(approved, Some("reverted".into())));
+
+        let report = telemetry.report(None);
+        let stats = &report.by_strategy["simple_majority"];
+        assert_eq!(stats.decisions, 2);
+        assert_eq!(stats.overturned, 1);
+        assert!((stats.approval_rate - 0.5).abs() < f32::EPSILON);
+
+        let user = &report.by_agent["user"];
+        assert_eq!(user.votes, 2);
+        assert_eq!(user.aligned, 2);
+        assert!(user.influence > report.by_agent["claude"].influence);
+        assert_eq!(report.by_agent["llama"].aligned, 0);
+    }
+
+    #[test]
+    fn test_pending_proposals_ignored() {
+        let mut telemetry = GovernanceTelemetry::new();
+        let proposal = Proposal::new(
+            AgentId::Llama,
+            Operation::Delete,
+            ProposalTarget::Node { id: None, kind: None },
+            json!({}),
+        );
+
+        telemetry.record(&proposal, &VotingStrategy::FirstVote, &[]);
+        assert!(telemetry.records().is_empty());
+    }
+
+    #[test]
+    fn test_each_proposal_recorded_once() {
+        let mut telemetry = GovernanceTelemetry::new();
+        let mut proposal = Proposal::new(
+            AgentId::Llama,
+            Operation::Delete,
+            ProposalTarget::Node { id: None, kind: None },
+            json!({}),
+        );
+        proposal.approve(None);
+
+        telemetry.record(&proposal, &VotingStrategy::FirstVote, &[]);
+        telemetry.record(&proposal, &VotingStrategy::FirstVote, &[]);
+        assert_eq!(telemetry.records().len(), 1);
+        assert!(telemetry.mark_overturned(proposal.id, Some("undone".into())));
+        assert!(telemetry.records()[0].overturned);
+    }
+}
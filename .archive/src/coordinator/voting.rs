@@ -8,7 +8,7 @@
 use ulid::Ulid;
 
 use crate::schema::AgentId;
-use super::proposal::{ProposalId, Proposal, ProposalManager};
+use super::proposal::{ProposalId, ProposalFilter, ProposalManager};
 use super::capabilities::CapabilityConfig;
 
 pub type VoteId = Ulid;
@@ -34,6 +34,29 @@ pub enum VoteDecision {
     Abstain,
 }
 
+impl std::fmt::Display for VoteDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            VoteDecision::Approve => "approve",
+            VoteDecision::Reject => "reject",
+            VoteDecision::Abstain => "abstain",
+        })
+    }
+}
+
+impl std::str::FromStr for VoteDecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "approve" | "yes" => Ok(VoteDecision::Approve),
+            "reject" | "no" => Ok(VoteDecision::Reject),
+            "abstain" => Ok(VoteDecision::Abstain),
+            _ => Err(format!("Unknown vote decision: {}", s)),
+        }
+    }
+}
+
 impl Vote {
     pub fn new(proposal_id: ProposalId, voter: AgentId, decision: VoteDecision) -> Self {
         Self {
@@ -59,7 +82,7 @@ pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
 }
 
 /// Voting strategy for determining approval
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum VotingStrategy {
     /// All voters must approve
@@ -90,6 +113,20 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+impl VotingStrategy {
+    /// Strategy name without parameters, e.g. `weighted`
+    pub fn name(&self) -> &'static str {
+        match self {
+            VotingStrategy::Unanimous => "unanimous",
+            VotingStrategy::SimpleMajority => "simple_majority",
+            VotingStrategy::Supermajority => "supermajority",
+            VotingStrategy::Weighted { .. } => "weighted",
+            VotingStrategy::FirstVote => "first_vote",
+            VotingStrategy::SingleApprover { .. } => "single_approver",
+        }
+    }
+}
+
 /// Result of evaluating votes
 #[derive(Debug, Clone, PartialEq)]
 pub enum VotingResult {
@@ -101,7 +138,25 @@ pub enum VotingResult {
     Pending { votes_for: f32, votes_against: f32, votes_needed: f32 },
 }
 
+/// Audit record for one batch vote
+///
+/// Each proposal still gets its own `Vote`, whose reason names the batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchVote {
+    pub id: Ulid,
+    pub voter: AgentId,
+    pub decision: VoteDecision,
+    pub filter: ProposalFilter,
+    pub reason: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    /// Proposals voted on, with the vote cast on each
+    pub votes: Vec<(ProposalId, VoteId)>,
+    /// Matching proposals the vote could not be cast on
+    pub failed: Vec<(ProposalId, String)>,
+}
+
 /// Coordinates voting on proposals
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VotingCoordinator {
     strategy: VotingStrategy,
     votes: HashMap<ProposalId, Vec<Vote>>,
@@ -167,6 +222,53 @@ pub fn cast_vote(
         Ok(())
     }
 
+    /// Cast the same vote on every pending proposal matching `filter`
+    ///
+    /// Only the user and agents allowed to batch-vote may do this. Each
+    /// vote is cast individually and each proposal re-evaluated, so the
+    /// configured strategy still decides the outcome.
+    pub fn cast_batch(
+        &mut self,
+        voter: AgentId,
+        decision: VoteDecision,
+        filter: &ProposalFilter,
+        reason: Option<String>,
+        proposal_manager: &mut ProposalManager,
+        capabilities: &CapabilityConfig,
+    ) -> Result<BatchVote, String> {
+        if !capabilities.can_batch_vote(&voter) {
+            return Err(format!("{} is not allowed to batch vote", voter));
+        }
+
+        let mut batch = BatchVote {
+            id: Ulid::new(),
+            voter: voter.clone(),
+            decision,
+            filter: filter.clone(),
+            reason: reason.clone(),
+            timestamp: Utc::now(),
+            votes: Vec::new(),
+            failed: Vec::new(),
+        };
+        let matching: Vec<ProposalId> = proposal_manager.matching(filter).iter().map(|p| p.id).collect();
+        for proposal_id in matching {
+            let note = match &reason {
+                Some(reason) => format!("batch {}: {}", batch.id, reason),
+                None => format!("batch {}", batch.id),
+            };
+            let vote = Vote::new(proposal_id, voter.clone(), decision).with_reason(note);
+            let vote_id = vote.id;
+            match self.cast_vote(vote, capabilities) {
+                Ok(()) => {
+                    self.process_proposal(proposal_id, proposal_manager);
+                    batch.votes.push((proposal_id, vote_id));
+                }
+                Err(e) => batch.failed.push((proposal_id, e)),
+            }
+        }
+        Ok(batch)
+    }
+
     /// Get votes for a proposal
     pub fn get_votes(&self, proposal_id: ProposalId) -> &[Vote] {
         self.votes.get(&proposal_id).map(|v| v.as_slice()).unwrap_or(&[])
@@ -333,13 +435,34 @@ pub fn evaluate(&self, proposal_id: ProposalId) -> VotingResult {
         }
     }
 
+    /// Decision of the arbiter a stalled proposal was escalated to, if cast
+    pub fn evaluate_arbiter(&self, proposal_id: ProposalId, arbiter: &AgentId) -> Option<VotingResult> {
+        let vote = self.get_votes(proposal_id).iter().find(|v| &v.voter == arbiter)?;
+        match vote.decision {
+            VoteDecision::Approve => Some(VotingResult::Approved {
+                reason: format!("Approved by arbiter {} after escalation", arbiter),
+            }),
+            VoteDecision::Reject => Some(VotingResult::Rejected {
+                reason: format!("Rejected by arbiter {} after escalation", arbiter),
+            }),
+            VoteDecision::Abstain => None,
+        }
+    }
+
     /// Process a proposal through the voting system
+    ///
+    /// Once a proposal is escalated, its arbiter's vote takes precedence
+    /// over the strategy.
     pub fn process_proposal(
         &self,
         proposal_id: ProposalId,
         proposal_manager: &mut ProposalManager,
     ) -> VotingResult {
-        let result = self.evaluate(proposal_id);
+        let result = proposal_manager
+            .get(proposal_id)
+            .and_then(|p| p.escalated_to.as_ref())
+            .and_then(|arbiter| self.evaluate_arbiter(proposal_id, arbiter))
+            .unwrap_or_else(|| self.evaluate(proposal_id));
 
         if let Some(proposal) = proposal_manager.get_mut(proposal_id) {
             match &result {
@@ -363,6 +486,7 @@ pub fn cleanup(&mut self, proposal_manager: &ProposalManager) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coordinator::proposal::Proposal;
 
     #[test]
     fn test_simple_majority() {
@@ -430,6 +554,46 @@ fn test_weighted_voting() {
         assert!(matches!(result, VotingResult::Approved { .. }));
     }
 
+    #[test]
+    fn test_batch_vote() {
+        use crate::schema::Operation;
+        use super::super::proposal::ProposalTarget;
+
+        let mut manager = ProposalManager::new();
+        let mut coordinator = VotingCoordinator::new(VotingStrategy::FirstVote);
+        let config = CapabilityConfig::default();
+        let propose = |proposer: AgentId, kind: &str| {
+            Proposal::new(
+                proposer,
+                Operation::Create,
+                ProposalTarget::Node { id: None, kind: Some(kind.into()) },
+                serde_json::json!({}),
+            )
+        };
+        let scraper = AgentId::Module("scraper".into());
+        let routine = manager.submit(propose(scraper.clone(), "context"));
+        manager.submit(propose(scraper.clone(), "insight"));
+        manager.submit(propose(AgentId::Llama, "context"));
+
+        let filter: ProposalFilter = "proposer=module:scraper AND kind=context".parse().unwrap();
+        assert!(coordinator
+            .cast_batch(AgentId::Claude, VoteDecision::Approve, &filter, None, &mut manager, &config)
+            .is_err());
+
+        let batch = coordinator
+            .cast_batch(AgentId::User, VoteDecision::Approve, &filter, None, &mut manager, &config)
+            .unwrap();
+        assert_eq!(batch.votes.len(), 1);
+        assert_eq!(batch.votes[0].0, routine);
+        let reason = coordinator.get_votes(routine)[0].reason.clone().unwrap();
+        assert!(reason.contains(&batch.id.to_string()));
+        assert_eq!(
+            manager.get(routine).unwrap().status,
+            super::super::proposal::ProposalStatus::Approved
+        );
+        assert_eq!(manager.pending().len(), 2);
+    }
+
     #[test]
     fn test_duplicate_vote_rejected() {
         let mut coordinator = VotingCoordinator::new(VotingStrategy::SimpleMajority);
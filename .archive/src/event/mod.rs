@@ -1,3 +1,3 @@
 mod sourcing;
 
-pub use sourcing::EventSourcer;
+pub use sourcing::{fingerprint, EventSourcer, Fingerprint, ReplayReport};
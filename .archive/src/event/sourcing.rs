@@ -1,11 +1,50 @@
-use crate::schema::StateEvent;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::schema::{EventId, StateEvent};
 use crate::store::{SledStore, Store, Result};
 
-/// Event sourcing utilities for replay and undo
+/// Event sourcing utilities for replay
 pub struct EventSourcer<'a> {
     store: &'a SledStore,
 }
 
+/// Digest of a store's nodes and edges
+///
+/// Archived nodes count as nodes, since archiving is not an event and a
+/// replayed store holds them in the hot tier.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Fingerprint {
+    pub nodes: usize,
+    pub edges: usize,
+    /// Hex SHA-256 over every node and edge in ID order
+    pub digest: String,
+}
+
+impl std::fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} nodes, {} edges, {}", self.nodes, self.edges, &self.digest[..16])
+    }
+}
+
+/// Outcome of rebuilding a store from an event log
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub events: usize,
+    pub applied: usize,
+    /// Events that could not be applied, with the reason
+    pub skipped: Vec<(EventId, String)>,
+    pub source: Fingerprint,
+    pub replayed: Fingerprint,
+}
+
+impl ReplayReport {
+    /// The log fully describes the source graph
+    pub fn is_consistent(&self) -> bool {
+        self.skipped.is_empty() && self.source == self.replayed
+    }
+}
+
 impl<'a> EventSourcer<'a> {
     pub fn new(store: &'a SledStore) -> Self {
         Self { store }
@@ -21,7 +60,169 @@ pub fn last_events(&self, n: usize) -> Result<Vec<StateEvent>> {
         self.store.get_events(None, n)
     }
 
-    // Future: replay events to rebuild state
-    // Future: undo last N operations
-    // Future: point-in-time recovery
+    /// Every event, oldest first
+    ///
+    /// Ordered by timestamp rather than ID: event IDs minted in the same
+    /// millisecond do not sort in creation order.
+    pub fn all_events(&self) -> Result<Vec<StateEvent>> {
+        let mut events = self.store.get_events(None, usize::MAX)?;
+        events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.id.cmp(&b.id)));
+        Ok(events)
+    }
+
+    /// Rebuild `target` from this store's event log and compare the result
+    ///
+    /// `target` should be empty. Events that cannot be applied are skipped
+    /// and reported rather than aborting the replay.
+    pub fn replay_into(&self, target: &SledStore) -> Result<ReplayReport> {
+        let events = self.all_events()?;
+        let skipped = apply_all(target, &events);
+
+        Ok(ReplayReport {
+            events: events.len(),
+            applied: events.len() - skipped.len(),
+            skipped,
+            source: fingerprint(self.store)?,
+            replayed: fingerprint(target)?,
+        })
+    }
+
+    /// Build a graph from `events` alone, in a fresh temporary store
+    ///
+    /// Events are applied oldest first, whatever order they come in.
+    /// Those that can't be applied, such as ones that captured only a
+    /// hash of their node, are skipped and returned with the reason.
+    pub fn replay(events: &[StateEvent]) -> Result<(SledStore, Vec<(EventId, String)>)> {
+        let mut events = events.to_vec();
+        events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.id.cmp(&b.id)));
+        let store = SledStore::open_temporary()?;
+        let skipped = apply_all(&store, &events);
+        Ok((store, skipped))
+    }
+
+    /// The graph as it stood at `at`: the events logged up to then,
+    /// replayed into a read-only temporary store, and those skipped
+    pub fn materialize_at(&self, at: chrono::DateTime<chrono::Utc>) -> Result<(SledStore, Vec<(EventId, String)>)> {
+        let events: Vec<StateEvent> = self.all_events()?.into_iter().take_while(|e| e.timestamp <= at).collect();
+        let (store, skipped) = Self::replay(&events)?;
+        Ok((store.read_only(), skipped))
+    }
+}
+
+/// Apply `events` in order, collecting the ones that fail
+fn apply_all(target: &SledStore, events: &[StateEvent]) -> Vec<(EventId, String)> {
+    events
+        .iter()
+        .filter_map(|event| target.apply_event(event).err().map(|e| (event.id, e.to_string())))
+        .collect()
+}
+
+/// Fingerprint the live graph of a store
+pub fn fingerprint(store: &SledStore) -> Result<Fingerprint> {
+    let mut nodes = store.list_nodes(None, usize::MAX)?;
+    nodes.extend(store.list_archived(None, usize::MAX)?);
+    nodes.sort_by_key(|n| n.id);
+    let edges = store.list_edges()?;
+
+    // Round-trip through Value so metadata hashes in key order
+    let mut hasher = Sha256::new();
+    for node in &nodes {
+        hasher.update(serde_json::to_value(node).unwrap_or_default().to_string());
+        hasher.update([0u8]);
+    }
+    for edge in &edges {
+        hasher.update(serde_json::to_value(edge).unwrap_or_default().to_string());
+        hasher.update([0u8]);
+    }
+
+    Ok(Fingerprint {
+        nodes: nodes.len(),
+        edges: edges.len(),
+        digest: hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, EdgeKind, NodeKind, StateEdge, StateNode};
+
+    #[test]
+    fn test_replay_reproduces_graph() {
+        let source = SledStore::open_temporary().unwrap();
+        let a = source
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 1})), AgentId::User)
+            .unwrap();
+        let b = source
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 2})), AgentId::User)
+            .unwrap();
+        let c = source
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        source.create_edge(StateEdge::new(a.id, b.id, EdgeKind::References), AgentId::User).unwrap();
+        source.create_edge(StateEdge::new(c.id, a.id, EdgeKind::PartOf), AgentId::User).unwrap();
+        source.update_node(a.id, serde_json::json!({"n": 3}), None, AgentId::User).unwrap();
+        source.delete_node(c.id, AgentId::User).unwrap();
+
+        let target = SledStore::open_temporary().unwrap();
+        let report = EventSourcer::new(&source).replay_into(&target).unwrap();
+        assert!(report.is_consistent(), "{:?}", report);
+        assert_eq!(report.replayed.nodes, 2);
+        assert_eq!(report.replayed.edges, 1);
+        assert_eq!(target.get_node(a.id).unwrap().unwrap().version, 2);
+        assert_eq!(target.count_nodes(Some(NodeKind::Insight)).unwrap(), 0);
+        assert_eq!(target.count_events().unwrap(), report.events);
+
+        // Divergence shows up in the fingerprint
+        target
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        assert_ne!(fingerprint(&target).unwrap(), report.source);
+    }
+
+    #[test]
+    fn test_replay_rebuilds_graph_in_place() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 1})), AgentId::User).unwrap();
+        let b = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 2})), AgentId::User).unwrap();
+        let archived = store.create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.archive_nodes(&[archived.id]).unwrap();
+        let before = fingerprint(&store).unwrap();
+
+        // Events arrive newest first; replay orders them itself
+        let mut events = EventSourcer::new(&store).all_events().unwrap();
+        events.reverse();
+        let (replayed, skipped) = EventSourcer::replay(&events).unwrap();
+        assert!(skipped.is_empty(), "{:?}", skipped);
+        assert_eq!(fingerprint(&replayed).unwrap(), before);
+
+        // Drift in the hot graph is overwritten; the archive tier is kept
+        store.delete_node(b.id, AgentId::User).unwrap();
+        assert_eq!(store.replace_graph(&replayed).unwrap(), (2, 1));
+        assert_eq!(fingerprint(&store).unwrap(), before);
+        assert_eq!(store.edges_from(a.id).unwrap().len(), 1);
+        assert!(store.get_node(archived.id).unwrap().is_none());
+        assert_eq!(store.count_nodes(Some(NodeKind::Task)).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_materialize_at() {
+        let store = SledStore::open_temporary().unwrap();
+        let pause = || std::thread::sleep(std::time::Duration::from_millis(5));
+        let a = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 1})), AgentId::User).unwrap();
+        pause();
+        let before = chrono::Utc::now();
+        pause();
+        store.update_node(a.id, serde_json::json!({"n": 2}), None, AgentId::User).unwrap();
+        store.create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User).unwrap();
+
+        let (past, skipped) = EventSourcer::new(&store).materialize_at(before).unwrap();
+        assert!(skipped.is_empty());
+        assert!(past.is_read_only());
+        assert_eq!(past.list_nodes(None, usize::MAX).unwrap().len(), 1);
+        assert_eq!(past.get_node(a.id).unwrap().unwrap().content, serde_json::json!({"n": 1}));
+        let (now, _) = EventSourcer::new(&store).materialize_at(chrono::Utc::now()).unwrap();
+        assert_eq!(fingerprint(&now).unwrap(), fingerprint(&store).unwrap());
+    }
 }
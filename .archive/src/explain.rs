@@ -0,0 +1,297 @@
+//! Provenance reports: why a node exists and whether to trust it
+//!
+//! `explain` gathers everything the store knows about a node's origin in
+//! one place: the event that created it and every later change, the chain
+//! of `DerivedFrom` sources behind it, proposals that touched it with their
+//! votes, annotations, reactions (the inputs to its trust and freshness
+//! scores) and connector provenance, for rendering as JSON or markdown.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
+
+use crate::connector::PROVENANCE_KEY;
+use crate::coordinator::{touched_nodes, Proposal, ProposalManager, Vote, VotingCoordinator, PROPOSALS_KEY};
+use crate::schema::{
+    AgentId, Annotation, EdgeId, EdgeKind, EventId, NodeId, Operation, Reaction, ReactionCounts, StateNode,
+    Target,
+};
+use crate::server_config::VOTES_KEY;
+use crate::store::{history, Result, SledStore, Store, StoreError};
+
+/// One logged change to the node
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeRecord {
+    pub event: EventId,
+    pub agent: AgentId,
+    pub operation: Operation,
+    pub timestamp: DateTime<Utc>,
+    /// Instance the change was first made on, for replicated events
+    pub origin: Option<String>,
+}
+
+/// A node the explained one was derived from, directly or through others
+#[derive(Debug, Clone, Serialize)]
+pub struct Ancestor {
+    pub id: NodeId,
+    /// 1 for direct sources
+    pub depth: usize,
+    /// The `DerivedFrom` edge reaching it
+    pub edge: EdgeId,
+    /// `None` once the source has been deleted
+    pub node: Option<StateNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProposalRecord {
+    pub proposal: Proposal,
+    pub votes: Vec<Vote>,
+}
+
+/// Reactions and the scores computed from them
+#[derive(Debug, Clone, Serialize)]
+pub struct TrustInputs {
+    pub counts: ReactionCounts,
+    pub trust: f32,
+    pub freshness: f32,
+    pub reactions: Vec<Reaction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub node: StateNode,
+    /// Oldest first; the first is the creation unless the log was pruned
+    pub changes: Vec<ChangeRecord>,
+    pub ancestors: Vec<Ancestor>,
+    pub proposals: Vec<ProposalRecord>,
+    pub annotations: Vec<Annotation>,
+    pub trust: TrustInputs,
+    /// Where a connector fetched the node from, if it did
+    pub provenance: Option<Value>,
+}
+
+/// Gather the provenance of node `id`
+pub fn explain(store: &SledStore, id: NodeId) -> Result<Explanation> {
+    let node = store.get_node(id)?.ok_or(StoreError::NodeNotFound(id))?;
+
+    let mut events: Vec<_> = store
+        .get_events(None, usize::MAX)?
+        .into_iter()
+        .filter(|e| matches!(e.target, Target::Node(target) if target == id))
+        .collect();
+    history::chronological(&mut events);
+    let changes = events
+        .into_iter()
+        .map(|e| ChangeRecord {
+            event: e.id,
+            agent: e.agent,
+            operation: e.operation,
+            timestamp: e.timestamp,
+            origin: e.origin,
+        })
+        .collect();
+
+    // Breadth-first, so each source is listed at its shortest distance
+    let mut ancestors = Vec::new();
+    let mut seen = HashSet::from([id]);
+    let mut queue = VecDeque::from([(id, 0)]);
+    while let Some((current, depth)) = queue.pop_front() {
+        for edge in store.edges_from(current)?.into_iter().filter(|e| e.kind == EdgeKind::DerivedFrom) {
+            if !seen.insert(edge.to) {
+                continue;
+            }
+            let source = store.get_node(edge.to)?;
+            if source.is_some() {
+                queue.push_back((edge.to, depth + 1));
+            }
+            ancestors.push(Ancestor { id: edge.to, depth: depth + 1, edge: edge.id, node: source });
+        }
+    }
+
+    let manager: ProposalManager = load(store, PROPOSALS_KEY)?;
+    let voting: VotingCoordinator = load(store, VOTES_KEY)?;
+    let endpoints = |edge| store.get_edge(edge).ok().flatten().map(|e| (e.from, e.to));
+    let mut proposals: Vec<ProposalRecord> = manager
+        .all()
+        .into_iter()
+        .filter(|p| touched_nodes(p, endpoints).contains(&id))
+        .map(|p| ProposalRecord { proposal: p.clone(), votes: voting.get_votes(p.id).to_vec() })
+        .collect();
+    proposals.sort_by_key(|p| p.proposal.created_at);
+
+    let reactions = store.reactions(id)?;
+    let counts = ReactionCounts::from_reactions(&reactions);
+    Ok(Explanation {
+        provenance: node.metadata.get(PROVENANCE_KEY).cloned(),
+        node,
+        changes,
+        ancestors,
+        proposals,
+        annotations: store.annotations(id)?,
+        trust: TrustInputs { counts, trust: counts.trust(), freshness: counts.freshness(), reactions },
+    })
+}
+
+fn load<T: serde::de::DeserializeOwned + Default>(store: &SledStore, key: &str) -> Result<T> {
+    match store.get_meta(key)? {
+        Some(value) => serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string())),
+        None => Ok(T::default()),
+    }
+}
+
+/// A node's `title`, `name` or `text`, else its JSON, cut to one line
+fn summary(node: &StateNode) -> String {
+    let text = ["title", "name", "text"]
+        .iter()
+        .find_map(|field| node.content.get(*field).and_then(Value::as_str))
+        .map(str::to_string)
+        .unwrap_or_else(|| node.content.to_string());
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+impl Explanation {
+    /// Render as a markdown report
+    pub fn to_markdown(&self) -> String {
+        let time = |t: &DateTime<Utc>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut out = String::new();
+        let node = &self.node;
+        let _ = writeln!(out, "# {} `{}`\n", node.kind, node.id);
+        let _ = writeln!(out, "{}\n", summary(node));
+        let _ = writeln!(out, "Version {}, last updated {}", node.version, time(&node.updated_at));
+
+        let _ = writeln!(out, "\n## Origin\n");
+        match self.changes.first() {
+            Some(first) if first.operation == Operation::Create => {
+                let _ = write!(out, "Created by {} at {} (event `{}`)", first.agent, time(&first.timestamp), first.event);
+                match &first.origin {
+                    Some(origin) => {
+                        let _ = writeln!(out, " on instance `{}`", origin);
+                    }
+                    None => out.push('\n'),
+                }
+            }
+            _ => out.push_str("The creating event is no longer in the log\n"),
+        }
+        if let Some(provenance) = &self.provenance {
+            let _ = writeln!(out, "\nFetched through a connector: `{}`", provenance);
+        }
+
+        let _ = writeln!(out, "\n## Changes\n");
+        for change in &self.changes {
+            let _ = writeln!(
+                out,
+                "- {} {:?} by {} (`{}`)",
+                time(&change.timestamp),
+                change.operation,
+                change.agent,
+                change.event
+            );
+        }
+
+        let _ = writeln!(out, "\n## Derived from\n");
+        if self.ancestors.is_empty() {
+            out.push_str("No recorded sources\n");
+        }
+        for ancestor in &self.ancestors {
+            let hops = if ancestor.depth > 1 { format!(" ({} hops)", ancestor.depth) } else { String::new() };
+            match &ancestor.node {
+                Some(source) => {
+                    let _ = writeln!(out, "- {} `{}`: {}{}", source.kind, source.id, summary(source), hops);
+                }
+                None => {
+                    let _ = writeln!(out, "- `{}` (deleted){}", ancestor.id, hops);
+                }
+            }
+        }
+
+        let _ = writeln!(out, "\n## Proposals\n");
+        if self.proposals.is_empty() {
+            out.push_str("None\n");
+        }
+        for record in &self.proposals {
+            let proposal = &record.proposal;
+            let _ = writeln!(
+                out,
+                "- `{}` {:?} by {}: {:?}{}",
+                proposal.id,
+                proposal.operation,
+                proposal.proposer,
+                proposal.status,
+                proposal.rationale.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default()
+            );
+            for vote in &record.votes {
+                let _ = writeln!(
+                    out,
+                    "  - {} voted {:?} (weight {:.2}){}",
+                    vote.voter,
+                    vote.decision,
+                    vote.weight,
+                    vote.reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default()
+                );
+            }
+        }
+
+        let _ = writeln!(out, "\n## Annotations\n");
+        if self.annotations.is_empty() {
+            out.push_str("None\n");
+        }
+        for annotation in &self.annotations {
+            let _ = writeln!(out, "- {} at {}: {}", annotation.author, time(&annotation.created_at), annotation.text);
+        }
+
+        let trust = &self.trust;
+        let _ = writeln!(out, "\n## Trust\n");
+        let _ = writeln!(
+            out,
+            "Trust {:.2}, freshness {:.2} from {} useful, {} outdated, {} disputed",
+            trust.trust, trust.freshness, trust.counts.useful, trust.counts.outdated, trust.counts.disputed
+        );
+        for reaction in &trust.reactions {
+            let _ = writeln!(out, "- {} marked it {} at {}", reaction.agent, reaction.kind, time(&reaction.created_at));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{NodeKind, ReactionKind, StateEdge};
+    use serde_json::json;
+
+    #[test]
+    fn test_explain() {
+        let store = SledStore::open_temporary().unwrap();
+        let paper = store.create_node(StateNode::new(NodeKind::Context, json!({"title": "Paper"})), AgentId::User).unwrap();
+        let notes = store.create_node(StateNode::new(NodeKind::Context, json!({"text": "Notes"})), AgentId::Llama).unwrap();
+        let insight =
+            store.create_node(StateNode::new(NodeKind::Insight, json!({"text": "Claim"})), AgentId::Claude).unwrap();
+        store.create_edge(StateEdge::new(insight.id, notes.id, EdgeKind::DerivedFrom), AgentId::Claude).unwrap();
+        store.create_edge(StateEdge::new(notes.id, paper.id, EdgeKind::DerivedFrom), AgentId::Llama).unwrap();
+        store.create_edge(StateEdge::new(paper.id, insight.id, EdgeKind::DerivedFrom), AgentId::User).unwrap();
+        store.update_node(insight.id, json!({"text": "Claim, revised"}), None, AgentId::User).unwrap();
+        store.add_annotation(Annotation::new(insight.id, AgentId::User, "check the source")).unwrap();
+        store.react(Reaction::new(insight.id, AgentId::Llama, ReactionKind::Useful)).unwrap();
+
+        let explanation = explain(&store, insight.id).unwrap();
+        assert_eq!(explanation.changes.len(), 2);
+        assert_eq!(explanation.changes[0].agent, AgentId::Claude);
+        // The loop back to the node itself stops the walk
+        let chain: Vec<(NodeId, usize)> = explanation.ancestors.iter().map(|a| (a.id, a.depth)).collect();
+        assert_eq!(chain, [(notes.id, 1), (paper.id, 2)]);
+        assert_eq!(explanation.annotations.len(), 1);
+        assert_eq!(explanation.trust.counts.useful, 1);
+
+        let markdown = explanation.to_markdown();
+        assert!(markdown.contains("Created by claude"));
+        assert!(markdown.contains(": Paper (2 hops)"));
+        assert!(markdown.contains("check the source"));
+        assert!(explain(&store, ulid::Ulid::new()).is_err());
+    }
+}
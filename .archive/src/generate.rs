@@ -0,0 +1,446 @@
+//! Fabricated nodes and edges for demos and stress tests
+//!
+//! `generate` creates nodes of one kind with plausible content and links
+//! each to nodes created before it. With `respect_schema`, content is
+//! built from the kind's registered JSON Schema: required properties,
+//! enums and consts, length, size and range bounds, `format`s such as
+//! `date-time` and `email`, and property names (`title`, `status`,
+//! `email`...) steer the fakers; each value is checked against the schema
+//! and regenerated if it misses. Without it, or for kinds without a
+//! schema, content follows the usual shape of the kind, and anything the
+//! store refuses (a schema, a cycle rule) is counted rather than fatal, so
+//! the run doubles as a test of the constraints.
+
+use chrono::{Duration, Utc};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::schema::{AgentId, EdgeKind, NodeKind, StateEdge, StateNode};
+use crate::store::{ContentSchemas, Result, SledStore, Store, StoreError};
+
+/// Attempts at schema-conforming content before giving up
+const ATTEMPTS: usize = 20;
+
+/// Nesting past which objects and arrays are left empty
+const MAX_DEPTH: usize = 6;
+
+const WORDS: &[&str] = &[
+    "index", "graph", "cache", "schema", "agent", "proposal", "vote", "edge", "query", "search", "snapshot",
+    "replay", "event", "sled", "module", "config", "ingest", "export", "review", "latency", "budget", "release",
+    "migration", "parser", "webhook", "token", "cluster", "summary", "draft", "backlog",
+];
+
+const VERBS: &[&str] =
+    &["fix", "add", "measure", "document", "refactor", "review", "benchmark", "remove", "rename", "split"];
+
+const NAMES: &[&str] = &["Ada", "Grace", "Linus", "Barbara", "Ken", "Margaret", "Dennis", "Frances", "Alan", "Radia"];
+
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub kind: NodeKind,
+    pub count: usize,
+    /// Build content from the kind's JSON Schema
+    pub respect_schema: bool,
+    /// Average edges from each new node to earlier ones
+    pub edges_per_node: f64,
+    /// RNG seed for reproducible runs
+    pub seed: Option<u64>,
+}
+
+impl GenerateOptions {
+    pub fn new(kind: NodeKind, count: usize) -> Self {
+        Self { kind, count, respect_schema: false, edges_per_node: 1.0, seed: None }
+    }
+
+    pub fn with_respect_schema(mut self, respect_schema: bool) -> Self {
+        self.respect_schema = respect_schema;
+        self
+    }
+
+    pub fn with_edges_per_node(mut self, edges_per_node: f64) -> Self {
+        self.edges_per_node = edges_per_node.max(0.0);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// What a run created, and what the store refused
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Generated {
+    pub nodes: Vec<StateNode>,
+    pub edges: Vec<StateEdge>,
+    /// Refusals by the store, as error messages
+    pub rejected: Vec<String>,
+}
+
+/// Create `options.count` fabricated nodes as `agent`
+pub fn generate(store: &SledStore, options: &GenerateOptions, agent: AgentId) -> Result<Generated> {
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let schemas = store.content_schemas()?;
+    let schema = options.respect_schema.then(|| schemas.get(&options.kind)).flatten();
+
+    // Earlier nodes only, so generated edges never close a cycle
+    let mut pool: Vec<(ulid::Ulid, NodeKind)> =
+        store.list_nodes(None, usize::MAX)?.into_iter().map(|node| (node.id, node.kind)).collect();
+    let mut generated = Generated::default();
+    for _ in 0..options.count {
+        let content = match schema {
+            Some(schema) => conforming(&mut rng, &schemas, &options.kind, schema)?,
+            None => kind_content(&mut rng, &options.kind),
+        };
+        let node = match store.create_node(StateNode::new(options.kind.clone(), content), agent.clone()) {
+            Ok(node) => node,
+            Err(e @ StoreError::SchemaViolation(..)) => {
+                generated.rejected.push(e.to_string());
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut links = options.edges_per_node.floor() as usize;
+        if rng.gen_bool(options.edges_per_node.fract()) {
+            links += 1;
+        }
+        for _ in 0..links.min(pool.len()) {
+            let (target, target_kind) = pool[rng.gen_range(0..pool.len())].clone();
+            let kind = plausible_edge(&mut rng, &node.kind, &target_kind);
+            match store.create_edge(StateEdge::new(node.id, target, kind), agent.clone()) {
+                Ok(edge) => generated.edges.push(edge),
+                Err(e @ (StoreError::WouldCycle(_) | StoreError::InvalidOperation(_))) => {
+                    generated.rejected.push(e.to_string());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        pool.push((node.id, node.kind.clone()));
+        generated.nodes.push(node);
+    }
+    Ok(generated)
+}
+
+/// Content for `kind` that passes `schema`
+fn conforming(rng: &mut StdRng, schemas: &ContentSchemas, kind: &NodeKind, schema: &Value) -> Result<Value> {
+    let mut attempts = 0;
+    loop {
+        let content = fake(rng, schema, "", 0);
+        let violations = schemas.violations(kind, &content);
+        attempts += 1;
+        if violations.is_empty() {
+            return Ok(content);
+        }
+        if attempts == ATTEMPTS {
+            let problems: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            return Err(StoreError::InvalidOperation(format!(
+                "Couldn't generate content for the {} schema: {}",
+                kind,
+                problems.join("; ")
+            )));
+        }
+    }
+}
+
+/// Content in the usual shape of `kind`
+fn kind_content(rng: &mut StdRng, kind: &NodeKind) -> Value {
+    match kind {
+        NodeKind::Task => {
+            let status = *["todo", "in_progress", "done"].choose(rng).unwrap();
+            json!({
+                "title": title(rng),
+                "status": status,
+                "priority": rng.gen_range(1..=5),
+                "due": date_time(rng),
+            })
+        }
+        NodeKind::Project => json!({"name": title(rng), "description": sentence(rng)}),
+        NodeKind::Insight => json!({"text": sentence(rng), "confidence": (rng.gen::<f64>() * 100.0).round() / 100.0}),
+        NodeKind::Conversation => json!({"title": title(rng), "participants": [person(rng), person(rng)]}),
+        NodeKind::Context => json!({"title": title(rng), "text": sentence(rng)}),
+        NodeKind::Module | NodeKind::Agent => json!({"name": slug(rng), "description": sentence(rng)}),
+        NodeKind::Custom(_) => json!({"title": title(rng), "text": sentence(rng)}),
+    }
+}
+
+/// An edge kind that makes sense from a `from` node to a `to` node
+fn plausible_edge(rng: &mut StdRng, from: &NodeKind, to: &NodeKind) -> EdgeKind {
+    match (from, to) {
+        (_, NodeKind::Project) if from != to => EdgeKind::PartOf,
+        (NodeKind::Task, NodeKind::Task) => {
+            if rng.gen_bool(0.5) {
+                EdgeKind::Blocks
+            } else {
+                EdgeKind::Enables
+            }
+        }
+        (NodeKind::Insight, NodeKind::Context | NodeKind::Conversation) => EdgeKind::DerivedFrom,
+        (NodeKind::Task, NodeKind::Insight | NodeKind::Context) => EdgeKind::References,
+        _ => {
+            if rng.gen_bool(0.5) {
+                EdgeKind::RelatedTo
+            } else {
+                EdgeKind::References
+            }
+        }
+    }
+}
+
+/// A value for `schema`; `name` is the property it's for, if any
+fn fake(rng: &mut StdRng, schema: &Value, name: &str, depth: usize) -> Value {
+    let object = match schema {
+        Value::Object(object) => flatten(rng, object),
+        Value::Bool(false) => return Value::Null,
+        _ => return Value::String(text_for(rng, name, None)),
+    };
+    if let Some(value) = object.get("const") {
+        return value.clone();
+    }
+    if let Some(options) = object.get("enum").and_then(Value::as_array).filter(|o| !o.is_empty()) {
+        return options.choose(rng).cloned().unwrap_or_default();
+    }
+
+    let count = |keyword: &str| object.get(keyword).and_then(Value::as_u64).map(|n| n as usize);
+    match pick_type(rng, &object) {
+        "null" => Value::Null,
+        "boolean" => Value::Bool(rng.gen()),
+        "integer" | "number" => number(rng, &object),
+        "array" => {
+            let min = count("minItems").unwrap_or(0);
+            let max = count("maxItems").unwrap_or(min.max(3)).max(min);
+            let len = if depth >= MAX_DEPTH { min } else { rng.gen_range(min.max(1).min(max)..=max) };
+            let items = object.get("items").cloned().unwrap_or(Value::Bool(true));
+            let singular = name.strip_suffix('s').unwrap_or(name);
+            Value::Array((0..len).map(|_| fake(rng, &items, singular, depth + 1)).collect())
+        }
+        "object" => {
+            let properties = object.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+            let required: Vec<&str> =
+                object.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str).collect();
+            let mut content = Map::new();
+            for (property, schema) in &properties {
+                let wanted = required.contains(&property.as_str()) || (depth < MAX_DEPTH && rng.gen_bool(0.7));
+                if wanted {
+                    content.insert(property.clone(), fake(rng, schema, property, depth + 1));
+                }
+            }
+            for property in required {
+                if !content.contains_key(property) {
+                    let value = match object.get("additionalProperties") {
+                        Some(schema) => fake(rng, schema, property, depth + 1),
+                        None => Value::String(text_for(rng, property, None)),
+                    };
+                    content.insert(property.to_string(), value);
+                }
+            }
+            Value::Object(content)
+        }
+        _ => {
+            let min = count("minLength").unwrap_or(0);
+            let max = count("maxLength").unwrap_or(usize::MAX).max(min);
+            let format = object.get("format").and_then(Value::as_str);
+            let mut text = text_for(rng, name, format);
+            if text.chars().count() > max {
+                text = text.chars().take(max).collect();
+            }
+            while text.chars().count() < min {
+                text.push_str(WORDS.choose(rng).unwrap_or(&"x"));
+            }
+            Value::String(text.chars().take(max).collect())
+        }
+    }
+}
+
+/// `object` with one `anyOf`/`oneOf` branch and every `allOf` part folded in
+fn flatten(rng: &mut StdRng, object: &Map<String, Value>) -> Map<String, Value> {
+    let mut merged = object.clone();
+    let mut parts: Vec<Value> = Vec::new();
+    if let Some(all) = merged.remove("allOf").and_then(|v| v.as_array().cloned()) {
+        parts.extend(all);
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(branch) = merged.remove(keyword).and_then(|v| v.as_array().and_then(|b| b.choose(rng).cloned())) {
+            parts.push(branch);
+        }
+    }
+    merged.remove("not");
+    for part in parts {
+        let Value::Object(part) = part else {
+            continue;
+        };
+        for (keyword, value) in flatten(rng, &part) {
+            match merged.get_mut(&keyword) {
+                None => {
+                    merged.insert(keyword, value);
+                }
+                Some(Value::Object(existing)) if keyword == "properties" => {
+                    if let Value::Object(more) = value {
+                        existing.extend(more);
+                    }
+                }
+                Some(Value::Array(existing)) if keyword == "required" => {
+                    if let Value::Array(more) = value {
+                        existing.extend(more);
+                    }
+                }
+                // The outer schema's own keywords win
+                Some(_) => {}
+            }
+        }
+    }
+    merged
+}
+
+/// One of the types `object` allows, inferred from its keywords if unstated
+fn pick_type<'a>(rng: &mut StdRng, object: &'a Map<String, Value>) -> &'a str {
+    match object.get("type") {
+        Some(Value::String(name)) => name.as_str(),
+        Some(Value::Array(names)) => {
+            let names: Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+            let useful: Vec<&str> = names.iter().copied().filter(|n| *n != "null").collect();
+            useful.choose(rng).or(names.first()).copied().unwrap_or("string")
+        }
+        _ if object.contains_key("properties") || object.contains_key("required") => "object",
+        _ if object.contains_key("items") || object.contains_key("minItems") => "array",
+        _ if object.contains_key("minimum") || object.contains_key("maximum") => "number",
+        _ => "string",
+    }
+}
+
+/// A number within the schema's bounds
+fn number(rng: &mut StdRng, object: &Map<String, Value>) -> Value {
+    let bound = |keyword: &str| object.get(keyword).and_then(Value::as_f64);
+    let integer = object.get("type").and_then(Value::as_str) == Some("integer");
+    let step = if integer { 1.0 } else { 0.01 };
+    let low = bound("minimum").or(bound("exclusiveMinimum").map(|n| n + step));
+    let high = bound("maximum").or(bound("exclusiveMaximum").map(|n| n - step));
+    let (low, high) = match (low, high) {
+        (Some(low), Some(high)) => (low, high.max(low)),
+        (Some(low), None) => (low, low + 100.0),
+        (None, Some(high)) => (high - 100.0, high),
+        (None, None) => (0.0, 100.0),
+    };
+    if integer {
+        let (low, high) = (low.ceil() as i64, high.floor() as i64);
+        json!(rng.gen_range(low..=high.max(low)))
+    } else {
+        json!((rng.gen_range(low..=high) / step).round() * step)
+    }
+}
+
+/// A string suited to a property called `name` with `format`
+fn text_for(rng: &mut StdRng, name: &str, format: Option<&str>) -> String {
+    match format {
+        Some("date-time") => return date_time(rng),
+        Some("date") => return date_time(rng)[..10].to_string(),
+        Some("email") => return email(rng),
+        Some("uri" | "url") => return format!("https://example.com/{}", slug(rng)),
+        Some("uuid") => return uuid_like(rng),
+        _ => {}
+    }
+    let name = name.to_lowercase();
+    match name.as_str() {
+        "title" | "summary" | "subject" => title(rng),
+        "name" | "author" | "owner" | "assignee" | "participant" => person(rng),
+        "email" => email(rng),
+        "url" | "link" | "href" | "source" => format!("https://example.com/{}", slug(rng)),
+        "status" | "state" => ["todo", "in_progress", "done"].choose(rng).unwrap_or(&"todo").to_string(),
+        "id" | "slug" | "key" => slug(rng),
+        _ if name.ends_with("_at") || name.contains("date") || name == "due" => date_time(rng),
+        "text" | "body" | "description" | "notes" | "content" | "message" => sentence(rng),
+        "tag" | "label" | "category" => WORDS.choose(rng).unwrap_or(&"misc").to_string(),
+        _ => words(rng, 2),
+    }
+}
+
+fn words(rng: &mut StdRng, count: usize) -> String {
+    (0..count).map(|_| *WORDS.choose(rng).unwrap_or(&"item")).collect::<Vec<_>>().join(" ")
+}
+
+fn title(rng: &mut StdRng) -> String {
+    let verb = VERBS.choose(rng).unwrap_or(&"review");
+    let count = rng.gen_range(1..=3);
+    let mut title = format!("{} {}", verb, words(rng, count));
+    title[..1].make_ascii_uppercase();
+    title
+}
+
+fn sentence(rng: &mut StdRng) -> String {
+    let task = title(rng);
+    let count = rng.gen_range(1..=2);
+    format!("{} before the {}.", task, words(rng, count))
+}
+
+fn person(rng: &mut StdRng) -> String {
+    NAMES.choose(rng).unwrap_or(&"Ada").to_string()
+}
+
+fn email(rng: &mut StdRng) -> String {
+    format!("{}@example.com", person(rng).to_lowercase())
+}
+
+fn slug(rng: &mut StdRng) -> String {
+    words(rng, 2).replace(' ', "-")
+}
+
+fn uuid_like(rng: &mut StdRng) -> String {
+    let n: u128 = rng.gen();
+    let hex = format!("{:032x}", n);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Within the last 90 days
+fn date_time(rng: &mut StdRng) -> String {
+    (Utc::now() - Duration::seconds(rng.gen_range(0..90 * 24 * 3600))).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_respects_schema() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut schemas = ContentSchemas::default();
+        schemas
+            .set(
+                &NodeKind::Task,
+                json!({
+                    "type": "object",
+                    "required": ["title", "status", "estimate"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "title": {"type": "string", "minLength": 3, "maxLength": 40},
+                        "status": {"enum": ["open", "closed"]},
+                        "estimate": {"type": "integer", "minimum": 1, "maximum": 8},
+                        "due": {"type": "string", "format": "date-time"},
+                        "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2}
+                    }
+                }),
+            )
+            .unwrap();
+        store.set_content_schemas(&schemas).unwrap();
+
+        let options = GenerateOptions::new(NodeKind::Task, 20).with_respect_schema(true).with_seed(7);
+        let generated = generate(&store, &options, AgentId::System).unwrap();
+        assert_eq!(generated.nodes.len(), 20);
+        assert!(generated.rejected.is_empty());
+        for node in &generated.nodes {
+            assert!(schemas.violations(&NodeKind::Task, &node.content).is_empty());
+        }
+        // One edge per node after the first, each to an earlier node
+        assert_eq!(generated.edges.len(), 19);
+        assert!(generated.edges.iter().all(|e| matches!(e.kind, EdgeKind::Blocks | EdgeKind::Enables)));
+
+        // The kind's usual shape fails this schema; refusals are counted
+        let options = GenerateOptions::new(NodeKind::Task, 3).with_seed(7);
+        let generated = generate(&store, &options, AgentId::System).unwrap();
+        assert!(generated.nodes.is_empty());
+        assert_eq!(generated.rejected.len(), 3);
+    }
+}
@@ -0,0 +1,235 @@
+//! Git commit history as knowledge
+//!
+//! `state-cli hook install` drops a post-commit hook into a repository that
+//! runs `state-cli ingest commit`, which stores each commit (message,
+//! changed files, diff stats) as a Context node linked to the repository's
+//! Project node. Commits are read with the `git` CLI.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+use crate::schema::{Metadata, NodeKind, StateNode};
+
+/// Metadata field holding a commit node's hash
+pub const COMMIT_KEY: &str = "commit";
+
+/// Line identifying hooks this crate installed
+pub const HOOK_MARKER: &str = "# Installed by state-cli hook install";
+
+/// Separates header fields in `git log` output
+const FIELD: char = '\x1f';
+/// Ends the header in `git log` output
+const RECORD: char = '\x1e';
+
+#[derive(Error, Debug)]
+pub enum GitError {
+    #[error("git failed: {0}")]
+    Command(String),
+
+    #[error("Unexpected git output: {0}")]
+    Parse(String),
+
+    #[error("{0} already exists; use --force to replace it")]
+    HookExists(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, GitError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    pub path: String,
+    /// `None` for binary files
+    pub added: Option<u64>,
+    pub deleted: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitInfo {
+    pub hash: String,
+    pub author: String,
+    pub date: DateTime<Utc>,
+    pub message: String,
+    pub files: Vec<FileChange>,
+}
+
+impl CommitInfo {
+    pub fn insertions(&self) -> u64 {
+        self.files.iter().filter_map(|f| f.added).sum()
+    }
+
+    pub fn deletions(&self) -> u64 {
+        self.files.iter().filter_map(|f| f.deleted).sum()
+    }
+
+    /// First line of the message
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+
+    /// A Context node describing the commit
+    pub fn to_node(&self) -> StateNode {
+        let mut metadata = Metadata::new();
+        metadata.insert(COMMIT_KEY.into(), self.hash.clone().into());
+        StateNode::new(
+            NodeKind::Context,
+            serde_json::json!({
+                "title": self.subject(),
+                "text": self.message,
+                "commit": self.hash,
+                "author": self.author,
+                "date": self.date,
+                "files": self.files,
+                "stats": {
+                    "files_changed": self.files.len(),
+                    "insertions": self.insertions(),
+                    "deletions": self.deletions(),
+                },
+            }),
+        )
+        .with_metadata(metadata)
+    }
+}
+
+fn git(repo: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo);
+    command
+}
+
+fn run(mut command: Command) -> Result<String> {
+    let output = command
+        .output()
+        .map_err(|e| GitError::Command(format!("git not found: {}", e)))?;
+    if !output.status.success() {
+        return Err(GitError::Command(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Read commit `rev` of the repository at `repo`
+pub fn read_commit(repo: &Path, rev: &str) -> Result<CommitInfo> {
+    let mut command = git(repo);
+    command
+        .args(["log", "-1", "--no-color", "--numstat"])
+        .arg(format!("--format=%H{f}%an <%ae>{f}%aI{f}%B{r}", f = FIELD, r = RECORD))
+        .arg(rev)
+        .arg("--");
+    parse_commit(&run(command)?)
+}
+
+/// Parse `git log -1 --numstat` output in the format `read_commit` asks for
+pub fn parse_commit(output: &str) -> Result<CommitInfo> {
+    let (header, numstat) = output
+        .split_once(RECORD)
+        .ok_or_else(|| GitError::Parse("missing record separator".into()))?;
+    let fields: Vec<&str> = header.splitn(4, FIELD).collect();
+    let [hash, author, date, message] = fields[..] else {
+        return Err(GitError::Parse(format!("expected 4 header fields, got {}", fields.len())));
+    };
+    let date = DateTime::parse_from_rfc3339(date.trim())
+        .map_err(|e| GitError::Parse(format!("bad date {:?}: {}", date, e)))?
+        .with_timezone(&Utc);
+
+    let mut files = Vec::new();
+    for line in numstat.lines().filter(|l| !l.trim().is_empty()) {
+        let mut parts = line.splitn(3, '\t');
+        let (Some(added), Some(deleted), Some(path)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(GitError::Parse(format!("bad numstat line {:?}", line)));
+        };
+        files.push(FileChange {
+            path: path.to_string(),
+            added: added.parse().ok(),
+            deleted: deleted.parse().ok(),
+        });
+    }
+
+    Ok(CommitInfo {
+        hash: hash.trim().to_string(),
+        author: author.to_string(),
+        date,
+        message: message.trim_end().to_string(),
+        files,
+    })
+}
+
+/// Where git looks for hooks in `repo`, honouring `core.hooksPath`
+pub fn hooks_dir(repo: &Path) -> Result<PathBuf> {
+    let mut command = git(repo);
+    command.args(["rev-parse", "--git-path", "hooks"]);
+    let dir = PathBuf::from(run(command)?.trim());
+    Ok(if dir.is_absolute() { dir } else { repo.join(dir) })
+}
+
+/// The post-commit hook script; `db_path` is passed on when given
+pub fn hook_script(db_path: Option<&str>) -> String {
+    let db = db_path
+        .map(|p| format!(" --db-path '{}'", p.replace('\'', r"'\''")))
+        .unwrap_or_default();
+    format!(
+        "#!/bin/sh\n{}\n# Record the commit in the state graph; never fail the commit\nstate-cli{} ingest commit --quiet HEAD || true\n",
+        HOOK_MARKER, db
+    )
+}
+
+/// Install the post-commit hook in `repo`, returning its path
+///
+/// A hook this crate didn't write is only replaced with `force`.
+pub fn install_hook(repo: &Path, db_path: Option<&str>, force: bool) -> Result<PathBuf> {
+    let dir = hooks_dir(repo)?;
+    let hook = dir.join("post-commit");
+    if !force && hook.exists() && !std::fs::read_to_string(&hook)?.contains(HOOK_MARKER) {
+        return Err(GitError::HookExists(hook));
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&hook, hook_script(db_path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(hook)
+}
+
+/// Remove the post-commit hook if this crate installed it; returns whether
+/// one was removed
+pub fn uninstall_hook(repo: &Path) -> Result<bool> {
+    let hook = hooks_dir(repo)?.join("post-commit");
+    if !hook.exists() || !std::fs::read_to_string(&hook)?.contains(HOOK_MARKER) {
+        return Ok(false);
+    }
+    std::fs::remove_file(hook)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commit() {
+        let output = "3f2c1e9\x1fAda <ada@example.org>\x1f2024-05-01T12:30:00+02:00\x1fFix the parser\n\nLonger body.\n\x1e\n\n12\t3\tsrc/parse.rs\n-\t-\tlogo.png\n0\t7\tREADME with\ttab.md\n";
+        let commit = parse_commit(output).unwrap();
+        assert_eq!(commit.hash, "3f2c1e9");
+        assert_eq!(commit.subject(), "Fix the parser");
+        assert_eq!(commit.message, "Fix the parser\n\nLonger body.");
+        assert_eq!(commit.date.to_rfc3339(), "2024-05-01T10:30:00+00:00");
+        assert_eq!(commit.files.len(), 3);
+        assert_eq!(commit.files[1], FileChange { path: "logo.png".into(), added: None, deleted: None });
+        assert_eq!(commit.files[2].path, "README with\ttab.md");
+        assert_eq!((commit.insertions(), commit.deletions()), (12, 10));
+
+        let node = commit.to_node();
+        assert_eq!(node.kind, NodeKind::Context);
+        assert_eq!(node.metadata[COMMIT_KEY], "3f2c1e9");
+        assert_eq!(node.content["stats"]["files_changed"], 3);
+
+        assert!(matches!(parse_commit("garbage"), Err(GitError::Parse(_))));
+        assert!(hook_script(Some("/tmp/it's")).contains(r"--db-path '/tmp/it'\''s'"));
+    }
+}
@@ -0,0 +1,374 @@
+//! Client for remote elegant-state servers
+//!
+//! Speaks GraphQL over blocking HTTP, which is all the CLI and the
+//! federation connectors need. The transport is the client crate's, so
+//! `https://` URLs use rustls with the Mozilla root certificates.
+
+use serde_json::Value;
+use std::time::Duration;
+
+/// Standard introspection query, covering everything needed to print SDL
+pub const INTROSPECTION_QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types { ...FullType }
+  }
+}
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+    isDeprecated
+    deprecationReason
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) { name description isDeprecated deprecationReason }
+  possibleTypes { ...TypeRef }
+}
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+fragment TypeRef on __Type {
+  kind name ofType { kind name ofType { kind name ofType { kind name
+  ofType { kind name ofType { kind name ofType { kind name } } } } } }
+}
+"#;
+
+const BUILTIN_SCALARS: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+/// A GraphQL endpoint plus the headers sent with every request
+#[derive(Debug, Clone)]
+pub struct GraphqlClient {
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+}
+
+impl GraphqlClient {
+    /// `url` is the full endpoint, e.g. `http://host:4000/graphql/v1`
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send `Authorization: Bearer <token>`
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        let value = format!("Bearer {}", token.as_ref());
+        self.with_header("Authorization", value)
+    }
+
+    /// Select a namespace on the server
+    pub fn with_namespace(self, namespace: impl Into<String>) -> Self {
+        self.with_header(super::NAMESPACE_HEADER, namespace)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Execute an operation and return the whole response
+    ///
+    /// GraphQL errors come back inside the response; only transport and
+    /// HTTP failures are errors here.
+    pub fn execute(
+        &self,
+        query: &str,
+        variables: Option<Value>,
+        operation_name: Option<&str>,
+    ) -> Result<Value, String> {
+        let mut body = serde_json::json!({ "query": query });
+        if let Some(variables) = variables {
+            body["variables"] = variables;
+        }
+        if let Some(name) = operation_name {
+            body["operationName"] = Value::String(name.to_string());
+        }
+        let headers: Vec<(&str, &str)> =
+            self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+        post_json_with_timeout(&self.url, &body, &headers, self.timeout)
+    }
+
+    /// The server's `__schema`, as returned by [`INTROSPECTION_QUERY`]
+    pub fn introspect(&self) -> Result<Value, String> {
+        let response = self.execute(INTROSPECTION_QUERY, None, None)?;
+        if let Some(errors) = response.get("errors") {
+            return Err(errors.to_string());
+        }
+        match response.pointer("/data/__schema") {
+            Some(schema) if schema.is_object() => Ok(schema.clone()),
+            _ => Err("response has no __schema".into()),
+        }
+    }
+}
+
+/// Blocking JSON POST; an empty body reads as `null`
+pub(crate) fn post_json(
+    url: &str,
+    body: &Value,
+    headers: &[(&str, &str)],
+) -> Result<Value, String> {
+    post_json_with_timeout(url, body, headers, Duration::from_secs(30))
+}
+
+fn post_json_with_timeout(
+    url: &str,
+    body: &Value,
+    headers: &[(&str, &str)],
+    timeout: Duration,
+) -> Result<Value, String> {
+    elegant_state_client::http::post_json(url, body, headers, timeout).map_err(|e| e.to_string())
+}
+
+/// Print an introspected `__schema` as SDL
+///
+/// Built-in scalars and introspection types are omitted, as in the SDL the
+/// server itself exports, so the result can be fed to `diff::diff_sdl`.
+pub fn introspection_to_sdl(schema: &Value) -> String {
+    let mut sdl = String::new();
+    let root = |key: &str| schema[key]["name"].as_str().map(str::to_string);
+    let (query, mutation, subscription) =
+        (root("queryType"), root("mutationType"), root("subscriptionType"));
+    if query.as_deref().is_some_and(|q| q != "Query")
+        || mutation.as_deref().is_some_and(|m| m != "Mutation")
+        || subscription.as_deref().is_some_and(|s| s != "Subscription")
+    {
+        sdl.push_str("schema {\n");
+        for (op, name) in [("query", query), ("mutation", mutation), ("subscription", subscription)] {
+            if let Some(name) = name {
+                sdl.push_str(&format!("  {}: {}\n", op, name));
+            }
+        }
+        sdl.push_str("}\n\n");
+    }
+
+    let empty = Vec::new();
+    let list = |value: &Value| value.as_array().unwrap_or(&empty).clone();
+    for ty in list(&schema["types"]) {
+        let name = ty["name"].as_str().unwrap_or_default();
+        if name.starts_with("__") || BUILTIN_SCALARS.contains(&name) {
+            continue;
+        }
+        push_description(&mut sdl, &ty["description"], "");
+        match ty["kind"].as_str().unwrap_or_default() {
+            "SCALAR" => sdl.push_str(&format!("scalar {}\n\n", name)),
+            "UNION" => {
+                let members: Vec<String> = list(&ty["possibleTypes"]).iter().map(type_ref).collect();
+                sdl.push_str(&format!("union {} = {}\n\n", name, members.join(" | ")));
+            }
+            "ENUM" => {
+                sdl.push_str(&format!("enum {} {{\n", name));
+                for value in list(&ty["enumValues"]) {
+                    push_description(&mut sdl, &value["description"], "  ");
+                    sdl.push_str(&format!("  {}{}\n", value["name"].as_str().unwrap_or_default(), deprecation(&value)));
+                }
+                sdl.push_str("}\n\n");
+            }
+            "INPUT_OBJECT" => {
+                sdl.push_str(&format!("input {} {{\n", name));
+                for field in list(&ty["inputFields"]) {
+                    push_description(&mut sdl, &field["description"], "  ");
+                    sdl.push_str(&format!("  {}\n", input_value(&field)));
+                }
+                sdl.push_str("}\n\n");
+            }
+            kind @ ("OBJECT" | "INTERFACE") => {
+                let keyword = if kind == "OBJECT" { "type" } else { "interface" };
+                sdl.push_str(&format!("{} {}", keyword, name));
+                let interfaces: Vec<String> = list(&ty["interfaces"]).iter().map(type_ref).collect();
+                if !interfaces.is_empty() {
+                    sdl.push_str(&format!(" implements {}", interfaces.join(" & ")));
+                }
+                sdl.push_str(" {\n");
+                for field in list(&ty["fields"]) {
+                    push_description(&mut sdl, &field["description"], "  ");
+                    let args: Vec<String> = list(&field["args"]).iter().map(input_value).collect();
+                    let args = if args.is_empty() { String::new() } else { format!("({})", args.join(", ")) };
+                    sdl.push_str(&format!(
+                        "  {}{}: {}{}\n",
+                        field["name"].as_str().unwrap_or_default(),
+                        args,
+                        type_ref(&field["type"]),
+                        deprecation(&field)
+                    ));
+                }
+                sdl.push_str("}\n\n");
+            }
+            _ => {}
+        }
+    }
+    sdl
+}
+
+fn type_ref(ty: &Value) -> String {
+    match ty["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", type_ref(&ty["ofType"])),
+        Some("LIST") => format!("[{}]", type_ref(&ty["ofType"])),
+        _ => ty["name"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+fn input_value(value: &Value) -> String {
+    let mut out = format!("{}: {}", value["name"].as_str().unwrap_or_default(), type_ref(&value["type"]));
+    if let Some(default) = value["defaultValue"].as_str() {
+        out.push_str(&format!(" = {}", default));
+    }
+    out
+}
+
+fn deprecation(value: &Value) -> String {
+    if value["isDeprecated"].as_bool() != Some(true) {
+        return String::new();
+    }
+    match value["deprecationReason"].as_str() {
+        Some(reason) => format!(" @deprecated(reason: {})", Value::String(reason.to_string())),
+        None => " @deprecated".to_string(),
+    }
+}
+
+fn push_description(sdl: &mut String, description: &Value, indent: &str) {
+    if let Some(text) = description.as_str().filter(|t| !t.is_empty()) {
+        sdl.push_str(&format!("{}\"\"\"\n", indent));
+        for line in text.replace("\"\"\"", "\\\"\"\"").lines() {
+            sdl.push_str(&format!("{}{}\n", indent, line));
+        }
+        sdl.push_str(&format!("{}\"\"\"\n", indent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::diff::diff_sdl;
+    use serde_json::json;
+    use std::io::{BufRead, Read, Write};
+    use std::net::TcpListener;
+
+    fn named(kind: &str, name: &str) -> Value {
+        json!({ "kind": kind, "name": name, "ofType": null })
+    }
+
+    fn wrap(kind: &str, of: Value) -> Value {
+        json!({ "kind": kind, "name": null, "ofType": of })
+    }
+
+    #[test]
+    fn test_remote_introspection() {
+        let node_id = json!({ "name": "id", "description": null, "args": [],
+            "type": wrap("NON_NULL", named("SCALAR", "ID")), "isDeprecated": false, "deprecationReason": null });
+        let schema = json!({
+            "queryType": { "name": "Query" },
+            "mutationType": null,
+            "subscriptionType": null,
+            "types": [
+                { "kind": "OBJECT", "name": "Query", "description": null, "interfaces": [], "fields": [
+                    { "name": "nodes", "description": "All nodes", "args": [
+                        { "name": "limit", "description": null, "type": named("SCALAR", "Int"), "defaultValue": "10" }
+                    ], "type": wrap("NON_NULL", wrap("LIST", wrap("NON_NULL", named("OBJECT", "Node")))),
+                      "isDeprecated": false, "deprecationReason": null },
+                    { "name": "legacy", "description": null, "args": [], "type": named("SCALAR", "String"),
+                      "isDeprecated": true, "deprecationReason": "use nodes" }
+                ]},
+                { "kind": "OBJECT", "name": "Node", "description": null, "interfaces": [], "fields": [node_id] },
+                { "kind": "ENUM", "name": "Kind", "description": null, "enumValues": [
+                    { "name": "A", "description": null, "isDeprecated": false, "deprecationReason": null }
+                ]},
+                { "kind": "SCALAR", "name": "String", "description": null },
+                { "kind": "OBJECT", "name": "__Type", "description": null, "interfaces": [], "fields": [] }
+            ]
+        });
+
+        // A one-shot server that checks the auth header and replies chunked
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/graphql/v1", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream);
+            let mut authorized = false;
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                authorized |= line.eq_ignore_ascii_case("authorization: Bearer s3cret\r\n");
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+            assert!(request["query"].as_str().unwrap().contains("__schema"));
+
+            let payload = json!({ "data": { "__schema": schema } }).to_string();
+            let (head, tail) = payload.split_at(payload.len() / 2);
+            let status = if authorized { "200 OK" } else { "401 Unauthorized" };
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                status,
+                head.len(),
+                head,
+                tail.len(),
+                tail
+            )
+            .unwrap();
+        });
+
+        let client = GraphqlClient::new(url).with_bearer_token("s3cret");
+        let schema = client.introspect().unwrap();
+        server.join().unwrap();
+
+        let sdl = introspection_to_sdl(&schema);
+        assert!(!sdl.contains("__Type") && !sdl.contains("scalar String"));
+        let expected = r#"
+            type Query {
+                nodes(limit: Int = 10): [Node!]!
+                legacy: String @deprecated(reason: "use nodes")
+            }
+            type Node { id: ID! }
+            enum Kind { A }
+        "#;
+        assert!(diff_sdl(expected, &sdl).unwrap().is_empty());
+
+
+        // https speaks TLS from the first byte, a handshake record
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/graphql", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut first = [0; 1];
+            stream.read_exact(&mut first).unwrap();
+            first[0]
+        });
+        assert!(GraphqlClient::new(url).introspect().is_err());
+        assert_eq!(server.join().unwrap(), 0x16);
+    }
+}
@@ -0,0 +1,360 @@
+//! Client type definitions generated from an introspected schema
+//!
+//! Both generators work from the `__schema` JSON returned by
+//! `client::INTROSPECTION_QUERY`, so they serve the local schema and remote
+//! servers alike. Introspection types and the built-in scalars are skipped.
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Output formats of `state-cli graphql schema`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaFormat {
+    #[default]
+    Sdl,
+    Json,
+    TypeScript,
+    Go,
+}
+
+impl std::fmt::Display for SchemaFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            SchemaFormat::Sdl => "sdl",
+            SchemaFormat::Json => "json",
+            SchemaFormat::TypeScript => "typescript",
+            SchemaFormat::Go => "go",
+        })
+    }
+}
+
+impl std::str::FromStr for SchemaFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sdl" | "graphql" => Ok(SchemaFormat::Sdl),
+            "json" | "introspection" => Ok(SchemaFormat::Json),
+            "typescript" | "ts" => Ok(SchemaFormat::TypeScript),
+            "go" | "golang" => Ok(SchemaFormat::Go),
+            _ => Err(format!("Unknown schema format: {} (expected sdl, json, typescript or go)", s)),
+        }
+    }
+}
+
+const BUILTIN_SCALARS: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+/// Named types worth generating, in schema order
+fn user_types(schema: &Value) -> Vec<&Value> {
+    schema["types"]
+        .as_array()
+        .map(|types| {
+            types
+                .iter()
+                .filter(|ty| {
+                    let name = ty["name"].as_str().unwrap_or_default();
+                    !name.starts_with("__") && !BUILTIN_SCALARS.contains(&name)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn items(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+fn name(value: &Value) -> &str {
+    value["name"].as_str().unwrap_or_default()
+}
+
+fn upper_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// TypeScript definitions in the style of graphql-codegen's `typescript`
+/// plugin: scalars through a `Scalars` map, enums as string unions, and an
+/// `...Args` interface for every field that takes arguments.
+pub fn typescript(schema: &Value, descriptions: bool) -> String {
+    let types = user_types(schema);
+    let mut out = String::from("// Generated by `state-cli graphql schema --format typescript`. Do not edit.\n\n");
+
+    out.push_str("export type Maybe<T> = T | null;\n\n");
+    out.push_str("export type Scalars = {\n");
+    out.push_str("  ID: string;\n  String: string;\n  Boolean: boolean;\n  Int: number;\n  Float: number;\n");
+    for ty in types.iter().filter(|ty| ty["kind"] == "SCALAR") {
+        let ts = match name(ty) {
+            "DateTime" | "Date" | "Ulid" | "UUID" | "Url" | "NodeKind" | "EdgeKind" => "string",
+            _ => "unknown",
+        };
+        out.push_str(&format!("  {}: {};\n", name(ty), ts));
+    }
+    out.push_str("};\n");
+
+    let comment = |out: &mut String, value: &Value, indent: &str| {
+        if let Some(text) = value["description"].as_str().filter(|t| descriptions && !t.is_empty()) {
+            out.push_str(&format!("{}/** {} */\n", indent, text.replace("*/", "*\\/").replace('\n', " ")));
+        }
+    };
+
+    for ty in types {
+        let type_name = name(ty);
+        match ty["kind"].as_str().unwrap_or_default() {
+            "ENUM" => {
+                out.push('\n');
+                comment(&mut out, ty, "");
+                let values: Vec<String> =
+                    items(&ty["enumValues"]).iter().map(|v| format!("\"{}\"", name(v))).collect();
+                out.push_str(&format!("export type {} = {};\n", type_name, values.join(" | ")));
+            }
+            "UNION" => {
+                out.push('\n');
+                comment(&mut out, ty, "");
+                let members: Vec<&str> = items(&ty["possibleTypes"]).iter().map(name).collect();
+                out.push_str(&format!("export type {} = {};\n", type_name, members.join(" | ")));
+            }
+            "INPUT_OBJECT" => {
+                out.push('\n');
+                comment(&mut out, ty, "");
+                out.push_str(&format!("export interface {} {{\n", type_name));
+                for field in items(&ty["inputFields"]) {
+                    comment(&mut out, field, "  ");
+                    out.push_str(&ts_input_field(field));
+                }
+                out.push_str("}\n");
+            }
+            kind @ ("OBJECT" | "INTERFACE") => {
+                out.push('\n');
+                comment(&mut out, ty, "");
+                out.push_str(&format!("export interface {} {{\n", type_name));
+                if kind == "OBJECT" {
+                    out.push_str(&format!("  __typename?: \"{}\";\n", type_name));
+                }
+                for field in items(&ty["fields"]) {
+                    comment(&mut out, field, "  ");
+                    out.push_str(&format!("  {}: {};\n", name(field), ts_type(&field["type"])));
+                }
+                out.push_str("}\n");
+
+                for field in items(&ty["fields"]).iter().filter(|f| !items(&f["args"]).is_empty()) {
+                    out.push_str(&format!(
+                        "\nexport interface {}{}Args {{\n",
+                        type_name,
+                        upper_first(name(field))
+                    ));
+                    for arg in items(&field["args"]) {
+                        out.push_str(&ts_input_field(arg));
+                    }
+                    out.push_str("}\n");
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn ts_input_field(field: &Value) -> String {
+    let optional = field["type"]["kind"] != "NON_NULL" || !field["defaultValue"].is_null();
+    format!(
+        "  {}{}: {};\n",
+        name(field),
+        if optional { "?" } else { "" },
+        ts_type(&field["type"])
+    )
+}
+
+fn ts_type(ty: &Value) -> String {
+    match ty["kind"].as_str() {
+        Some("NON_NULL") => ts_non_null(&ty["ofType"]),
+        _ => format!("Maybe<{}>", ts_non_null(ty)),
+    }
+}
+
+fn ts_non_null(ty: &Value) -> String {
+    match ty["kind"].as_str() {
+        Some("LIST") => format!("Array<{}>", ts_type(&ty["ofType"])),
+        Some("SCALAR") => format!("Scalars['{}']", name(ty)),
+        _ => name(ty).to_string(),
+    }
+}
+
+/// Go types for decoding responses and encoding inputs with encoding/json
+///
+/// Nullable fields become pointers (lists stay slices), enums become string
+/// types with one constant per value, and unknown scalars decode as
+/// `json.RawMessage`.
+pub fn go(schema: &Value, package: &str, descriptions: bool) -> String {
+    let mut imports = BTreeSet::new();
+    let mut body = String::new();
+
+    let comment = |out: &mut String, value: &Value, indent: &str| {
+        if let Some(text) = value["description"].as_str().filter(|t| descriptions && !t.is_empty()) {
+            for line in text.lines() {
+                out.push_str(&format!("{}// {}\n", indent, line));
+            }
+        }
+    };
+
+    for ty in user_types(schema) {
+        let type_name = name(ty);
+        match ty["kind"].as_str().unwrap_or_default() {
+            "SCALAR" => {
+                body.push('\n');
+                comment(&mut body, ty, "");
+                let go = go_scalar(type_name, &mut imports);
+                body.push_str(&format!("type {} = {}\n", type_name, go));
+            }
+            "ENUM" => {
+                body.push('\n');
+                comment(&mut body, ty, "");
+                body.push_str(&format!("type {} string\n\nconst (\n", type_name));
+                for value in items(&ty["enumValues"]) {
+                    body.push_str(&format!(
+                        "\t{}{} {} = \"{}\"\n",
+                        type_name,
+                        go_enum_value(name(value)),
+                        type_name,
+                        name(value)
+                    ));
+                }
+                body.push_str(")\n");
+            }
+            "UNION" | "INTERFACE" => {
+                // Decoded by the caller once __typename is known
+                imports.insert("encoding/json");
+                body.push('\n');
+                comment(&mut body, ty, "");
+                body.push_str(&format!("type {} = json.RawMessage\n", type_name));
+            }
+            kind @ ("OBJECT" | "INPUT_OBJECT") => {
+                let fields = if kind == "OBJECT" { &ty["fields"] } else { &ty["inputFields"] };
+                body.push('\n');
+                comment(&mut body, ty, "");
+                body.push_str(&format!("type {} struct {{\n", type_name));
+                for field in items(fields) {
+                    comment(&mut body, field, "\t");
+                    let nullable = field["type"]["kind"] != "NON_NULL";
+                    let omit = if nullable && kind == "INPUT_OBJECT" { ",omitempty" } else { "" };
+                    body.push_str(&format!(
+                        "\t{} {} `json:\"{}{}\"`\n",
+                        go_field_name(name(field)),
+                        go_type(&field["type"], &mut imports),
+                        name(field),
+                        omit
+                    ));
+                }
+                body.push_str("}\n");
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = String::from("// Code generated by `state-cli graphql schema --format go`. DO NOT EDIT.\n\n");
+    out.push_str(&format!("package {}\n", package));
+    if !imports.is_empty() {
+        out.push_str("\nimport (\n");
+        for import in &imports {
+            out.push_str(&format!("\t\"{}\"\n", import));
+        }
+        out.push_str(")\n");
+    }
+    out.push_str(&body);
+    out
+}
+
+fn go_scalar(scalar: &str, imports: &mut BTreeSet<&'static str>) -> &'static str {
+    match scalar {
+        "ID" | "String" | "NodeKind" | "EdgeKind" => "string",
+        "Int" => "int",
+        "Float" => "float64",
+        "Boolean" => "bool",
+        "DateTime" => {
+            imports.insert("time");
+            "time.Time"
+        }
+        _ => {
+            imports.insert("encoding/json");
+            "json.RawMessage"
+        }
+    }
+}
+
+fn go_type(ty: &Value, imports: &mut BTreeSet<&'static str>) -> String {
+    match ty["kind"].as_str() {
+        Some("NON_NULL") => go_non_null(&ty["ofType"], imports),
+        Some("LIST") => go_non_null(ty, imports),
+        _ => format!("*{}", go_non_null(ty, imports)),
+    }
+}
+
+fn go_non_null(ty: &Value, imports: &mut BTreeSet<&'static str>) -> String {
+    match ty["kind"].as_str() {
+        Some("LIST") => format!("[]{}", go_type(&ty["ofType"], imports)),
+        // Built-in scalars map straight to Go types; custom ones are aliased
+        Some("SCALAR") if BUILTIN_SCALARS.contains(&name(ty)) => go_scalar(name(ty), imports).to_string(),
+        Some("SCALAR") => {
+            go_scalar(name(ty), imports);
+            name(ty).to_string()
+        }
+        _ => name(ty).to_string(),
+    }
+}
+
+/// `nodeId` -> `NodeID`, following Go's initialism convention
+fn go_field_name(field: &str) -> String {
+    let name = upper_first(field);
+    for (suffix, initialism) in [("Ids", "IDs"), ("Id", "ID"), ("Url", "URL")] {
+        if let Some(stem) = name.strip_suffix(suffix) {
+            return format!("{}{}", stem, initialism);
+        }
+    }
+    name
+}
+
+/// `IN_PROGRESS` -> `InProgress`
+fn go_enum_value(value: &str) -> String {
+    value
+        .split('_')
+        .map(|part| upper_first(&part.to_lowercase()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::{build_schema, introspection};
+    use crate::store::SledStore;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_codegen_from_local_schema() {
+        let schema = build_schema(Arc::new(SledStore::open_temporary().unwrap()));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let introspected = runtime.block_on(introspection(&schema));
+        assert!(introspected["types"].as_array().is_some_and(|t| !t.is_empty()));
+
+        let ts = typescript(&introspected, true);
+        assert!(ts.contains("export interface QueryRoot {"));
+        assert!(ts.contains("  JSON: unknown;"));
+        assert!(ts.contains("export type AgentKind = \""));
+        assert!(ts.contains("  NodeKind: string;"));
+        assert!(!ts.contains("__Type"));
+
+        let go = go(&introspected, "state", false);
+        assert!(go.starts_with("// Code generated"));
+        assert!(go.contains("package state\n"));
+        assert!(go.contains("type AgentKind string"));
+        assert!(go.contains("type NodeKind = string"));
+        assert!(go.contains("\"encoding/json\""));
+
+        assert_eq!("ts".parse::<SchemaFormat>(), Ok(SchemaFormat::TypeScript));
+        assert!("xml".parse::<SchemaFormat>().is_err());
+        assert_eq!(go_field_name("nodeId"), "NodeID");
+        assert_eq!(go_enum_value("IN_PROGRESS"), "InProgress");
+    }
+}
@@ -0,0 +1,300 @@
+//! Schema diffing for API compatibility checks
+//!
+//! Compares two SDL documents and classifies each change. Removing or
+//! narrowing anything a client may rely on is breaking; removing a field
+//! that was already `@deprecated` is reported as dangerous, since clients
+//! were warned in the previous version.
+
+use async_graphql::parser::{
+    parse_schema,
+    types::{
+        ConstDirective, FieldDefinition, InputValueDefinition, TypeKind, TypeSystemDefinition,
+    },
+    Positioned,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeLevel {
+    Safe,
+    Dangerous,
+    Breaking,
+}
+
+impl std::fmt::Display for ChangeLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ChangeLevel::Safe => "safe",
+            ChangeLevel::Dangerous => "dangerous",
+            ChangeLevel::Breaking => "breaking",
+        })
+    }
+}
+
+/// One difference between two schemas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChange {
+    pub level: ChangeLevel,
+    /// `Type` or `Type.field` or `Type.field(arg)`
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct InputShape {
+    ty: String,
+    has_default: bool,
+}
+
+#[derive(Debug, Default)]
+struct FieldShape {
+    ty: String,
+    args: BTreeMap<String, InputShape>,
+    deprecated: bool,
+}
+
+#[derive(Debug, Default)]
+struct TypeShape {
+    kind: &'static str,
+    fields: BTreeMap<String, FieldShape>,
+    inputs: BTreeMap<String, InputShape>,
+    /// Enum values or union members
+    members: BTreeSet<String>,
+}
+
+/// Compare an old SDL document against a new one
+pub fn diff_sdl(old: &str, new: &str) -> Result<Vec<SchemaChange>, String> {
+    let old = shapes(old).map_err(|e| format!("old schema: {}", e))?;
+    let new = shapes(new).map_err(|e| format!("new schema: {}", e))?;
+    let mut changes = Vec::new();
+
+    for (name, old_ty) in &old {
+        let Some(new_ty) = new.get(name) else {
+            changes.push(change(ChangeLevel::Breaking, name, "type removed"));
+            continue;
+        };
+        if old_ty.kind != new_ty.kind {
+            changes.push(change(
+                ChangeLevel::Breaking,
+                name,
+                format!("changed from {} to {}", old_ty.kind, new_ty.kind),
+            ));
+            continue;
+        }
+
+        for (field, old_field) in &old_ty.fields {
+            let path = format!("{}.{}", name, field);
+            let Some(new_field) = new_ty.fields.get(field) else {
+                let level = if old_field.deprecated {
+                    ChangeLevel::Dangerous
+                } else {
+                    ChangeLevel::Breaking
+                };
+                changes.push(change(level, path, "field removed"));
+                continue;
+            };
+            if old_field.ty != new_field.ty {
+                // Output types may only get stricter
+                let level = if new_field.ty == format!("{}!", old_field.ty) {
+                    ChangeLevel::Safe
+                } else {
+                    ChangeLevel::Breaking
+                };
+                changes.push(change(level, &path, format!("type {} -> {}", old_field.ty, new_field.ty)));
+            }
+            if !old_field.deprecated && new_field.deprecated {
+                changes.push(change(ChangeLevel::Safe, &path, "deprecated"));
+            }
+            changes.extend(diff_inputs(&path, &old_field.args, &new_field.args, "argument"));
+        }
+        for field in new_ty.fields.keys().filter(|f| !old_ty.fields.contains_key(*f)) {
+            changes.push(change(ChangeLevel::Safe, format!("{}.{}", name, field), "field added"));
+        }
+
+        changes.extend(diff_inputs(name, &old_ty.inputs, &new_ty.inputs, "input field"));
+
+        for member in old_ty.members.difference(&new_ty.members) {
+            changes.push(change(ChangeLevel::Breaking, format!("{}.{}", name, member), "value removed"));
+        }
+        for member in new_ty.members.difference(&old_ty.members) {
+            // Exhaustive matches in clients may not handle the new value
+            changes.push(change(ChangeLevel::Dangerous, format!("{}.{}", name, member), "value added"));
+        }
+    }
+
+    for name in new.keys().filter(|n| !old.contains_key(*n)) {
+        changes.push(change(ChangeLevel::Safe, name, "type added"));
+    }
+
+    changes.sort_by(|a, b| b.level.cmp(&a.level).then(a.path.cmp(&b.path)));
+    Ok(changes)
+}
+
+fn change(level: ChangeLevel, path: impl Into<String>, message: impl Into<String>) -> SchemaChange {
+    SchemaChange {
+        level,
+        path: path.into(),
+        message: message.into(),
+    }
+}
+
+/// Arguments and input fields: inputs may only get looser
+fn diff_inputs(
+    parent: &str,
+    old: &BTreeMap<String, InputShape>,
+    new: &BTreeMap<String, InputShape>,
+    what: &str,
+) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for (name, old_input) in old {
+        let path = format!("{}({})", parent, name);
+        match new.get(name) {
+            None => changes.push(change(ChangeLevel::Breaking, path, format!("{} removed", what))),
+            Some(new_input) if new_input.ty != old_input.ty => {
+                let level = if old_input.ty == format!("{}!", new_input.ty) {
+                    ChangeLevel::Safe
+                } else {
+                    ChangeLevel::Breaking
+                };
+                changes.push(change(level, path, format!("type {} -> {}", old_input.ty, new_input.ty)));
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, new_input) in new.iter().filter(|(n, _)| !old.contains_key(*n)) {
+        let required = new_input.ty.ends_with('!') && !new_input.has_default;
+        changes.push(change(
+            if required { ChangeLevel::Breaking } else { ChangeLevel::Safe },
+            format!("{}({})", parent, name),
+            format!("{} {} added", if required { "required" } else { "optional" }, what),
+        ));
+    }
+    changes
+}
+
+fn shapes(sdl: &str) -> Result<BTreeMap<String, TypeShape>, String> {
+    let document = parse_schema(sdl).map_err(|e| e.to_string())?;
+    let mut types = BTreeMap::new();
+
+    for definition in document.definitions {
+        let TypeSystemDefinition::Type(ty) = definition else {
+            continue;
+        };
+        let ty = ty.node;
+        let mut shape = TypeShape::default();
+
+        match ty.kind {
+            TypeKind::Scalar => shape.kind = "scalar",
+            TypeKind::Object(object) => {
+                shape.kind = "object";
+                shape.fields = fields(object.fields);
+            }
+            TypeKind::Interface(interface) => {
+                shape.kind = "interface";
+                shape.fields = fields(interface.fields);
+            }
+            TypeKind::Union(union) => {
+                shape.kind = "union";
+                shape.members = union.members.into_iter().map(|m| m.node.to_string()).collect();
+            }
+            TypeKind::Enum(enum_type) => {
+                shape.kind = "enum";
+                shape.members = enum_type
+                    .values
+                    .into_iter()
+                    .map(|v| v.node.value.node.to_string())
+                    .collect();
+            }
+            TypeKind::InputObject(input) => {
+                shape.kind = "input";
+                shape.inputs = inputs(input.fields);
+            }
+        }
+
+        types.insert(ty.name.node.to_string(), shape);
+    }
+
+    Ok(types)
+}
+
+fn fields(values: Vec<Positioned<FieldDefinition>>) -> BTreeMap<String, FieldShape> {
+    values
+        .into_iter()
+        .map(|f| {
+            let f = f.node;
+            (
+                f.name.node.to_string(),
+                FieldShape {
+                    ty: f.ty.node.to_string(),
+                    args: inputs(f.arguments),
+                    deprecated: is_deprecated(&f.directives),
+                },
+            )
+        })
+        .collect()
+}
+
+fn inputs(values: Vec<Positioned<InputValueDefinition>>) -> BTreeMap<String, InputShape> {
+    values
+        .into_iter()
+        .map(|v| {
+            let v = v.node;
+            (
+                v.name.node.to_string(),
+                InputShape {
+                    ty: v.ty.node.to_string(),
+                    has_default: v.default_value.is_some(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn is_deprecated(directives: &[Positioned<ConstDirective>]) -> bool {
+    directives.iter().any(|d| d.node.name.node.as_str() == "deprecated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"
+        type Query {
+            node(id: ID!): Node
+            legacy: String @deprecated(reason: "use node")
+            nodes(limit: Int): [Node!]!
+        }
+        type Node { id: ID! name: String! }
+        enum Kind { A B }
+    "#;
+
+    const NEW: &str = r#"
+        type Query {
+            node(id: ID!, at: String!): Node
+            nodes(limit: Int, kind: Kind): [Node!]!
+            count: Int!
+        }
+        type Node { id: ID! name: String }
+        enum Kind { A C }
+    "#;
+
+    #[test]
+    fn test_diff_classifies_changes() {
+        let changes = diff_sdl(OLD, NEW).unwrap();
+        let find = |path: &str| changes.iter().find(|c| c.path == path).unwrap().level;
+
+        assert_eq!(find("Query.node(at)"), ChangeLevel::Breaking);
+        assert_eq!(find("Node.name"), ChangeLevel::Breaking);
+        assert_eq!(find("Kind.B"), ChangeLevel::Breaking);
+        assert_eq!(find("Query.legacy"), ChangeLevel::Dangerous);
+        assert_eq!(find("Kind.C"), ChangeLevel::Dangerous);
+        assert_eq!(find("Query.nodes(kind)"), ChangeLevel::Safe);
+        assert_eq!(find("Query.count"), ChangeLevel::Safe);
+        assert_eq!(changes[0].level, ChangeLevel::Breaking);
+    }
+
+    #[test]
+    fn test_identical_schemas() {
+        assert!(diff_sdl(OLD, OLD).unwrap().is_empty());
+    }
+}
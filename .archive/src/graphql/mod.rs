@@ -1,19 +1,87 @@
 mod query;
 mod mutation;
 mod types;
+pub mod diff;
+pub mod client;
+pub mod codegen;
+pub mod request_log;
+pub mod usage;
 
 pub use query::QueryRoot;
 pub use mutation::MutationRoot;
 pub use types::*;
 
-use async_graphql::{EmptySubscription, Schema};
-use crate::store::SledStore;
+use async_graphql::{Context, EmptySubscription, ErrorExtensions, Result, Schema};
+use crate::schema::NodeId;
+use crate::store::{SledStore, StoreError};
 use std::sync::Arc;
 
 pub type StateSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
 
+/// Current API version, served at `/graphql/<version>`
+///
+/// Changes within a version are additive. Fields are retired by marking them
+/// `#[graphql(deprecation = "...")]` for at least one release before removal;
+/// `state-cli graphql diff` reports anything else that would break clients.
+pub const API_VERSION: &str = "v1";
+
+/// HTTP header used to select a namespace per request
+pub const NAMESPACE_HEADER: &str = "x-state-namespace";
+
+/// HTTP header naming the agent behind a request, for request logs
+pub const AGENT_HEADER: &str = "x-state-agent";
+
+/// Namespace selected for a single request
+#[derive(Debug, Clone)]
+pub struct Namespace(pub String);
+
+/// Resolve the store for the current request, honoring namespace selection
+pub(crate) fn namespaced_store(ctx: &Context<'_>) -> Result<Arc<SledStore>> {
+    let store = ctx.data::<Arc<SledStore>>()?;
+    match ctx.data_opt::<Namespace>() {
+        Some(ns) => Ok(Arc::new(store.namespaced(ns.0.clone())?)),
+        None => Ok(store.clone()),
+    }
+}
+
+/// Convert a store error, listing schema violations under `extensions` so
+/// clients can point at the offending fields
+pub(crate) fn store_error(error: StoreError) -> async_graphql::Error {
+    let message = error.to_string();
+    match error {
+        StoreError::SchemaViolation(kind, violations) => {
+            let violations = serde_json::to_value(&violations).unwrap_or_default();
+            async_graphql::Error::new(message).extend_with(|_, e| {
+                e.set("code", "SCHEMA_VIOLATION");
+                e.set("kind", kind);
+                e.set("violations", async_graphql::Value::from_json(violations).unwrap_or_default());
+            })
+        }
+        StoreError::ConstraintViolation(violations) => {
+            let violations = serde_json::to_value(&violations).unwrap_or_default();
+            async_graphql::Error::new(message).extend_with(|_, e| {
+                e.set("code", "CONSTRAINT_VIOLATION");
+                e.set("violations", async_graphql::Value::from_json(violations).unwrap_or_default());
+            })
+        }
+        error => error.into(),
+    }
+}
+
+/// Resolve a node ID or alias argument
+pub(crate) fn resolve_node_id(store: &SledStore, reference: &str) -> Result<NodeId> {
+    store.resolve_node(reference).map_err(store_error)
+}
+
 pub fn build_schema(store: Arc<SledStore>) -> StateSchema {
     Schema::build(QueryRoot, MutationRoot, EmptySubscription)
         .data(store)
         .finish()
 }
+
+/// The schema's `__schema` introspection result, as a remote client sees it
+pub async fn introspection(schema: &StateSchema) -> serde_json::Value {
+    let response = schema.execute(client::INTROSPECTION_QUERY).await;
+    let mut data = response.data.into_json().unwrap_or_default();
+    data["__schema"].take()
+}
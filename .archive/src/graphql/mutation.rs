@@ -2,11 +2,18 @@
 use crate::store::{SledStore, Store};
 use crate::schema::{
     self as domain,
-    AgentId, NodeId, EdgeId,
+    AgentId, EdgeId, AnnotationAnchor,
 };
-use super::types::{StateNode, StateEdge, CreateNodeInput, UpdateNodeInput, CreateEdgeInput, AgentKind};
-use std::sync::Arc;
+use super::types::{
+    StateNode, StateEdge, CreateNodeInput, UpdateNodeInput, CreateEdgeInput, UpdateEdgeInput, AgentKind,
+    Annotation, AnnotateNodeInput, ReactionKind, ReactionSummary, CompactionResult, DeleteMode,
+    PatchFormat, Proposal, SubmitProposalInput, KindEntry, RegisterKindInput, NodeKind,
+    Template, AppliedTemplate, RetentionClass, NodeAlias,
+};
+use super::{namespaced_store, resolve_node_id, store_error};
+use crate::coordinator::{self, ProposalManager, ProposalTarget, PROPOSALS_KEY};
 use ulid::Ulid;
+use std::sync::Arc;
 
 pub struct MutationRoot;
 
@@ -19,7 +26,7 @@ async fn create_node(
         input: CreateNodeInput,
         #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
     ) -> Result<StateNode> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
 
         let mut node = domain::StateNode::new(input.kind.into(), input.content.0);
         if let Some(meta) = input.metadata {
@@ -27,8 +34,20 @@ async fn create_node(
                 node = node.with_metadata(map);
             }
         }
+        if let Some(ttl) = input.ttl_seconds {
+            node = node.with_ttl(chrono::Duration::seconds(ttl));
+        }
+        if let Some(pinned) = input.pinned {
+            node = node.with_pinned(pinned);
+        }
+        if let Some(retention) = input.retention {
+            node = node.with_retention(retention.into());
+        }
+        if let Some(tags) = input.tags {
+            node = node.with_tags(tags);
+        }
 
-        let created = store.create_node(node, agent.into())?;
+        let created = store.create_node(node, agent.into()).map_err(store_error)?;
         Ok(created.into())
     }
 
@@ -39,24 +58,105 @@ async fn update_node(
         input: UpdateNodeInput,
         #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
     ) -> Result<StateNode> {
-        let store = ctx.data::<Arc<SledStore>>()?;
-        let node_id: NodeId = input.id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &input.id)?;
 
-        let updated = store.update_node(node_id, input.content.0, agent.into())?;
+        let updated = store
+            .update_node(node_id, input.content.0, input.expected_version, agent.into())
+            .map_err(store_error)?;
         Ok(updated.into())
     }
 
-    /// Delete a node
+    /// Patch part of a node's content with RFC 6902 operations or an RFC
+    /// 7386 merge patch, instead of replacing all of it
+    async fn patch_node(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        patch: async_graphql::Json<serde_json::Value>,
+        #[graphql(default_with = "PatchFormat::Auto")] format: PatchFormat,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<StateNode> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        let patch = format.parse(patch.0)?;
+        Ok(store.patch_node(node_id, &patch, agent.into()).map_err(store_error)?.into())
+    }
+
+    /// Add and remove tags on a node
+    async fn tag_node(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(default)] add: Vec<String>,
+        #[graphql(default)] remove: Vec<String>,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<StateNode> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        Ok(store.update_tags(node_id, &add, &remove, agent.into()).map_err(store_error)?.into())
+    }
+
+    /// Pin a node, or unpin it with `pinned: false`
+    async fn pin_node(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(default = true)] pinned: bool,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<StateNode> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        Ok(store.set_pinned(node_id, pinned, agent.into()).map_err(store_error)?.into())
+    }
+
+    /// Override a node's retention class, or omit `retention` to go back to
+    /// its kind's
+    async fn set_retention(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        retention: Option<RetentionClass>,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<StateNode> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        Ok(store.set_retention(node_id, retention.map(Into::into), agent.into()).map_err(store_error)?.into())
+    }
+
+    /// Give a node an alias usable in place of its ID, replacing any it had
+    async fn set_alias(&self, ctx: &Context<'_>, id: ID, alias: String) -> Result<NodeAlias> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        store.set_alias(node_id, &alias).map_err(store_error)?;
+        Ok(NodeAlias { alias, node: ID(node_id.to_string()) })
+    }
+
+    /// Remove a node's alias, returning it
+    async fn remove_alias(&self, ctx: &Context<'_>, id: ID) -> Result<Option<String>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+
+        Ok(store.remove_alias(node_id)?)
+    }
+
+    /// Delete a node; by default its edges are deleted with it
     async fn delete_node(
         &self,
         ctx: &Context<'_>,
         id: ID,
         #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+        #[graphql(default_with = "DeleteMode::Cascade")] mode: DeleteMode,
     ) -> Result<bool> {
-        let store = ctx.data::<Arc<SledStore>>()?;
-        let node_id: NodeId = id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
 
-        store.delete_node(node_id, agent.into())?;
+        store.delete_node_with(node_id, agent.into(), mode.into())?;
         Ok(true)
     }
 
@@ -67,20 +167,41 @@ async fn create_edge(
         input: CreateEdgeInput,
         #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
     ) -> Result<StateEdge> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
 
-        let from_id: NodeId = input.from.parse::<Ulid>().map_err(|e| format!("Invalid from ID: {}", e))?;
-        let to_id: NodeId = input.to.parse::<Ulid>().map_err(|e| format!("Invalid to ID: {}", e))?;
+        let from_id = resolve_node_id(&store, &input.from)?;
+        let to_id = resolve_node_id(&store, &input.to)?;
 
         let mut edge = domain::StateEdge::new(from_id, to_id, input.kind.into());
         if let Some(w) = input.weight {
             edge = edge.with_weight(w);
         }
+        if let Some(meta) = input.metadata {
+            let map = serde_json::from_value(meta.0).map_err(|e| format!("Invalid metadata: {}", e))?;
+            edge = edge.with_metadata(map);
+        }
 
-        let created = store.create_edge(edge, agent.into())?;
+        let created = store.create_edge(edge, agent.into()).map_err(store_error)?;
         Ok(created.into())
     }
 
+    /// Change an edge's weight or metadata
+    async fn update_edge(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateEdgeInput,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<StateEdge> {
+        let store = namespaced_store(ctx)?;
+        let edge_id: EdgeId = input.id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+        let metadata: domain::Metadata = match input.metadata {
+            Some(meta) => serde_json::from_value(meta.0).map_err(|e| format!("Invalid metadata: {}", e))?,
+            None => domain::Metadata::new(),
+        };
+
+        Ok(store.update_edge(edge_id, input.weight, metadata, agent.into())?.into())
+    }
+
     /// Delete an edge
     async fn delete_edge(
         &self,
@@ -88,10 +209,196 @@ async fn delete_edge(
         id: ID,
         #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
     ) -> Result<bool> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
         let edge_id: EdgeId = id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
 
         store.delete_edge(edge_id, agent.into())?;
         Ok(true)
     }
+
+    /// Propose a mutation for the agents to vote on
+    async fn submit_proposal(
+        &self,
+        ctx: &Context<'_>,
+        input: SubmitProposalInput,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<Proposal> {
+        let store = namespaced_store(ctx)?;
+        let operation: domain::Operation = input.operation.parse()?;
+        let mut target: ProposalTarget = input.target.parse()?;
+        if let ProposalTarget::Node { id: Some(id), kind } = &mut target {
+            *kind = store.get_node(*id)?.map(|n| n.kind.to_string());
+        }
+
+        let mut proposal = coordinator::Proposal::new(agent.into(), operation, target, input.payload.0);
+        if let Some(rationale) = input.rationale {
+            proposal = proposal.with_rationale(rationale);
+        }
+        let mut proposals: ProposalManager = match store.get_meta(PROPOSALS_KEY)? {
+            Some(value) => serde_json::from_value(value)?,
+            None => ProposalManager::new(),
+        };
+        proposals.submit(proposal.clone());
+        store.set_meta(PROPOSALS_KEY, &serde_json::to_value(&proposals)?)?;
+        Ok(proposal.into())
+    }
+
+    /// Comment on a node without modifying it
+    async fn annotate_node(
+        &self,
+        ctx: &Context<'_>,
+        input: AnnotateNodeInput,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<Annotation> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &input.node_id)?;
+
+        let author: AgentId = agent.into();
+        let mut annotation = domain::Annotation::new(node_id, author, input.text);
+        if let Some(anchor) = input.anchor {
+            let anchor: AnnotationAnchor = anchor.parse()?;
+            annotation = annotation.with_anchor(anchor);
+        }
+
+        Ok(store.add_annotation(annotation)?.into())
+    }
+
+    /// Delete an annotation
+    async fn delete_annotation(&self, ctx: &Context<'_>, node_id: ID, id: ID) -> Result<bool> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
+        let id = id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+
+        store.delete_annotation(node_id, id)?;
+        Ok(true)
+    }
+
+    /// React to a node; reacting twice with the same kind has no effect
+    async fn react(
+        &self,
+        ctx: &Context<'_>,
+        node_id: ID,
+        kind: ReactionKind,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<ReactionSummary> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
+
+        let reaction = domain::Reaction::new(node_id, agent.into(), kind.into());
+        Ok(store.react(reaction)?.into())
+    }
+
+    /// Withdraw a reaction
+    async fn unreact(
+        &self,
+        ctx: &Context<'_>,
+        node_id: ID,
+        kind: ReactionKind,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<ReactionSummary> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
+
+        let agent: AgentId = agent.into();
+        Ok(store.unreact(node_id, &agent, kind.into())?.into())
+    }
+
+    /// Register a custom kind, or update its description and colour
+    async fn register_kind(&self, ctx: &Context<'_>, input: RegisterKindInput) -> Result<KindEntry> {
+        let store = namespaced_store(ctx)?;
+        let mut registry = store.kind_registry()?;
+        let info = crate::store::KindInfo { description: input.description, color: input.color };
+        let name = if input.edge {
+            registry.register_edge(&input.name, info.clone())?.to_string()
+        } else {
+            registry.register_node(&input.name, info.clone())?.to_string()
+        };
+        store.set_kind_registry(&registry)?;
+        Ok(KindEntry {
+            name,
+            edge: input.edge,
+            built_in: false,
+            description: info.description,
+            color: info.color,
+        })
+    }
+
+    /// Forget a custom kind; nodes and edges of the kind keep it
+    async fn unregister_kind(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default)] edge: bool,
+    ) -> Result<bool> {
+        let store = namespaced_store(ctx)?;
+        let mut registry = store.kind_registry()?;
+        let removed = if edge { registry.unregister_edge(&name) } else { registry.unregister_node(&name) };
+        if removed {
+            store.set_kind_registry(&registry)?;
+        }
+        Ok(removed)
+    }
+
+    /// Require a node kind's content to match a JSON Schema; `null` drops
+    /// the requirement. Existing nodes aren't rechecked.
+    async fn set_content_schema(
+        &self,
+        ctx: &Context<'_>,
+        kind: NodeKind,
+        schema: Option<async_graphql::Json<serde_json::Value>>,
+    ) -> Result<bool> {
+        let store = namespaced_store(ctx)?;
+        let mut schemas = store.content_schemas()?;
+        match schema {
+            Some(schema) => schemas.set(&kind.0, schema.0)?,
+            None => {
+                if !schemas.remove(&kind.0) {
+                    return Ok(false);
+                }
+            }
+        }
+        store.set_content_schemas(&schemas)?;
+        Ok(true)
+    }
+
+    /// Save a node template, replacing any of the same name; see
+    /// `templates` for the shape of a definition
+    async fn save_template(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        definition: async_graphql::Json<serde_json::Value>,
+    ) -> Result<Template> {
+        let store = namespaced_store(ctx)?;
+        let template: crate::store::NodeTemplate =
+            serde_json::from_value(definition.0).map_err(|e| format!("Invalid template: {}", e))?;
+        store.save_template(&name, &template)?;
+        Ok((name, template).into())
+    }
+
+    /// Create a node and its edges from a saved template, filling its
+    /// `{{variable}}` placeholders from a JSON object
+    async fn apply_template(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        variables: Option<async_graphql::Json<serde_json::Value>>,
+        #[graphql(default_with = "AgentKind::User")] agent: AgentKind,
+    ) -> Result<AppliedTemplate> {
+        let store = namespaced_store(ctx)?;
+        let variables = match variables {
+            Some(variables) => serde_json::from_value(variables.0)
+                .map_err(|e| format!("Variables must be an object: {}", e))?,
+            None => Default::default(),
+        };
+        let (node, edges) = store.apply_template(&name, &variables, agent.into()).map_err(store_error)?;
+        Ok(AppliedTemplate { node: node.into(), edges: edges.into_iter().map(Into::into).collect() })
+    }
+
+        /// Compact the whole database (every namespace) while serving
+    async fn compact_database(&self, ctx: &Context<'_>) -> Result<CompactionResult> {
+        let store = ctx.data::<Arc<SledStore>>()?.clone();
+        let report = tokio::task::spawn_blocking(move || store.compact()).await??;
+        Ok(report.into())
+    }
 }
@@ -1,37 +1,175 @@
 use async_graphql::{Context, Object, Result, ID};
-use crate::store::{SledStore, Store};
-use crate::schema::{NodeId, NodeKind as DomainNodeKind};
-use super::types::{StateNode, StateEdge, StateEvent, NodeKind, EdgeKind};
-use std::sync::Arc;
-use ulid::Ulid;
+use crate::store::{Sort, Store};
+use crate::schema::{EdgeKind as DomainEdgeKind, NodeKind as DomainNodeKind};
+use super::types::{
+    StateNode, StateEdge, StateEvent, NodeKind, EdgeKind, Annotation, Attachment, ReactionSummary,
+    RenderFormat, RenderedContent, DiskUsage, GraphPath, PathMode, SortKey, Cycle, GraphStats, UsageReport, NodeAsOf, NodeVersion, KindEntry, NodeAlias, Template, kind_name,
+};
+use crate::render::Renderer;
+use super::{namespaced_store, resolve_node_id};
 
 pub struct QueryRoot;
 
 #[Object]
 impl QueryRoot {
-    /// Get a node by ID
-    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<StateNode>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
-        let node_id: NodeId = id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
-        Ok(store.get_node(node_id)?.map(Into::into))
+    /// Get a node by ID, or with `resolveSuperseded` the newest node
+    /// superseding it
+    async fn node(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(default = false)] resolve_superseded: bool,
+    ) -> Result<Option<StateNode>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        let node = if resolve_superseded { store.latest_version(node_id)? } else { store.get_node(node_id)? };
+        Ok(node.map(Into::into))
+    }
+
+    /// Nodes a node is transitively part of, nearest first
+    async fn ancestors(&self, ctx: &Context<'_>, id: ID) -> Result<Vec<StateNode>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        Ok(store.ancestors(node_id)?.into_iter().map(Into::into).collect())
+    }
+
+    /// Nodes transitively part of a node, breadth first, at most `depth`
+    /// levels down
+    async fn descendants(&self, ctx: &Context<'_>, id: ID, depth: Option<i32>) -> Result<Vec<StateNode>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        let depth = depth.map(|d| d.max(0) as usize);
+        Ok(store.descendants(node_id, depth)?.into_iter().map(Into::into).collect())
+    }
+
+    /// A node as it stood at `at` (RFC 3339), rebuilt from the event log,
+    /// optionally with the edges it had then
+    async fn node_as_of(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        at: String,
+        #[graphql(default = false)] with_edges: bool,
+    ) -> Result<NodeAsOf> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        let at = chrono::DateTime::parse_from_rfc3339(&at)
+            .map_err(|e| format!("Invalid timestamp: {}", e))?
+            .with_timezone(&chrono::Utc);
+        let edges = if with_edges { store.edges_as_of(node_id, at)? } else { Vec::new() };
+        Ok(NodeAsOf {
+            at: at.to_rfc3339(),
+            node: store.node_as_of(node_id, at)?.map(Into::into),
+            edges: edges.into_iter().map(Into::into).collect(),
+        })
     }
 
-    /// List nodes, optionally filtered by kind
+    /// Every version of a node, oldest first, rebuilt from the event log
+    async fn node_versions(&self, ctx: &Context<'_>, id: ID) -> Result<Vec<NodeVersion>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        Ok(store.node_versions(node_id)?.into_iter().map(Into::into).collect())
+    }
+
+    /// Render a node's content according to its content type
+    async fn rendered(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+        #[graphql(default_with = "RenderFormat::Html")] format: RenderFormat,
+    ) -> Result<Option<RenderedContent>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
+        let Some(node) = store.get_node(node_id)? else {
+            return Ok(None);
+        };
+
+        // Rendering may shell out to pandoc or mmdc
+        let rendered =
+            tokio::task::spawn_blocking(move || Renderer::new().render(&node, format.into())).await??;
+        Ok(Some(rendered.into()))
+    }
+
+    /// List nodes, optionally filtered by kind and sorted through an index
     async fn nodes(
         &self,
         ctx: &Context<'_>,
         kind: Option<NodeKind>,
         #[graphql(default = 100)] limit: i32,
+        sort: Option<SortKey>,
+        #[graphql(default)] desc: bool,
+    ) -> Result<Vec<StateNode>> {
+        let store = namespaced_store(ctx)?;
+        let domain_kind: Option<DomainNodeKind> = kind.map(Into::into);
+        let nodes = match sort {
+            Some(key) => store.list_nodes_sorted(domain_kind.as_ref(), Sort::new(key.into(), desc), limit as usize)?,
+            None => store.list_nodes(domain_kind, limit as usize)?,
+        };
+        Ok(nodes.into_iter().map(Into::into).collect())
+    }
+
+    /// Nodes carrying a tag, optionally of one kind, read from the tag index
+    async fn nodes_by_tag(
+        &self,
+        ctx: &Context<'_>,
+        tag: String,
+        kind: Option<NodeKind>,
+        #[graphql(default = 100)] limit: i32,
+    ) -> Result<Vec<StateNode>> {
+        let store = namespaced_store(ctx)?;
+        let domain_kind: Option<DomainNodeKind> = kind.map(Into::into);
+        Ok(store
+            .nodes_by_tag(&tag, domain_kind.as_ref(), limit as usize)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// All node aliases, sorted by alias
+    async fn aliases(&self, ctx: &Context<'_>) -> Result<Vec<NodeAlias>> {
+        let store = namespaced_store(ctx)?;
+        Ok(store
+            .aliases()?
+            .into_iter()
+            .map(|(alias, node)| NodeAlias { alias, node: ID(node.to_string()) })
+            .collect())
+    }
+
+    /// Pinned nodes, optionally of one kind: the context every agent
+    /// should load
+    async fn pinned_nodes(
+        &self,
+        ctx: &Context<'_>,
+        kind: Option<NodeKind>,
+        #[graphql(default = 100)] limit: i32,
     ) -> Result<Vec<StateNode>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
         let domain_kind: Option<DomainNodeKind> = kind.map(Into::into);
         Ok(store
-            .list_nodes(domain_kind, limit as usize)?
+            .pinned_nodes(domain_kind.as_ref(), limit as usize)?
             .into_iter()
             .map(Into::into)
             .collect())
     }
 
+    /// Count nodes, optionally of one kind, without loading them
+    async fn node_count(&self, ctx: &Context<'_>, kind: Option<NodeKind>) -> Result<u64> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.count_nodes(kind.map(Into::into))? as u64)
+    }
+
+    /// Count edges
+    async fn edge_count(&self, ctx: &Context<'_>) -> Result<u64> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.count_edges()? as u64)
+    }
+
+    /// Count recorded events
+    async fn event_count(&self, ctx: &Context<'_>) -> Result<u64> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.count_events()? as u64)
+    }
+
     /// Get edges, optionally filtered by from, to, or kind
     async fn edges(
         &self,
@@ -40,13 +178,13 @@ async fn edges(
         to: Option<ID>,
         _kind: Option<EdgeKind>,
     ) -> Result<Vec<StateEdge>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
 
         let edges = if let Some(from_id) = from {
-            let node_id: NodeId = from_id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+            let node_id = resolve_node_id(&store, &from_id)?;
             store.edges_from(node_id)?
         } else if let Some(to_id) = to {
-            let node_id: NodeId = to_id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+            let node_id = resolve_node_id(&store, &to_id)?;
             store.edges_to(node_id)?
         } else {
             vec![] // Would need a list_edges method
@@ -61,7 +199,7 @@ async fn events(
         ctx: &Context<'_>,
         #[graphql(default = 50)] limit: i32,
     ) -> Result<Vec<StateEvent>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
         Ok(store
             .get_events(None, limit as usize)?
             .into_iter()
@@ -76,8 +214,8 @@ async fn neighbors(
         id: ID,
         #[graphql(default = 1)] depth: i32,
     ) -> Result<Vec<StateNode>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
-        let node_id: NodeId = id.parse::<Ulid>().map_err(|e| format!("Invalid ID: {}", e))?;
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &id)?;
         Ok(store
             .neighbors(node_id, depth as usize)?
             .into_iter()
@@ -85,20 +223,167 @@ async fn neighbors(
             .collect())
     }
 
+    /// Cheapest or strongest path between two nodes, following edges in
+    /// either direction
+    async fn path(
+        &self,
+        ctx: &Context<'_>,
+        from: ID,
+        to: ID,
+        kinds: Option<Vec<EdgeKind>>,
+        #[graphql(default_with = "PathMode::MinCost")] mode: PathMode,
+    ) -> Result<Option<GraphPath>> {
+        let store = namespaced_store(ctx)?;
+        let from = resolve_node_id(&store, &from)?;
+        let to = resolve_node_id(&store, &to)?;
+        let kinds: Option<Vec<DomainEdgeKind>> = kinds.map(|ks| ks.into_iter().map(Into::into).collect());
+        Ok(store.shortest_path(from, to, kinds.as_deref(), mode.into())?.map(Into::into))
+    }
+
+    /// Loops among edges of the given kinds (default: blocks and part_of)
+    async fn cycles(&self, ctx: &Context<'_>, kinds: Option<Vec<EdgeKind>>) -> Result<Vec<Cycle>> {
+        let store = namespaced_store(ctx)?;
+        let kinds: Vec<DomainEdgeKind> = match kinds {
+            Some(ks) => ks.into_iter().map(Into::into).collect(),
+            None => crate::store::cycles::default_cycle_kinds(),
+        };
+        Ok(crate::store::cycles::find_cycles(&store, &kinds)?.into_iter().map(Into::into).collect())
+    }
+
     /// Search nodes by content
     async fn search(
         &self,
         ctx: &Context<'_>,
         query: String,
         kinds: Option<Vec<NodeKind>>,
+        #[graphql(default = false)] resolve_superseded: bool,
     ) -> Result<Vec<StateNode>> {
-        let store = ctx.data::<Arc<SledStore>>()?;
+        let store = namespaced_store(ctx)?;
         let domain_kinds: Option<Vec<DomainNodeKind>> =
             kinds.map(|ks| ks.into_iter().map(Into::into).collect());
+        let mut results = store.search(&query, domain_kinds)?;
+        if resolve_superseded {
+            results = store.latest_versions(results)?;
+        }
+        Ok(results.into_iter().map(Into::into).collect())
+    }
+
+    /// Get annotations on a node
+    async fn annotations(&self, ctx: &Context<'_>, node_id: ID) -> Result<Vec<Annotation>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
         Ok(store
-            .search(&query, domain_kinds)?
+            .annotations(node_id)?
             .into_iter()
             .map(Into::into)
             .collect())
     }
+
+    /// Get attachment metadata and download URLs for a node
+    async fn attachments(&self, ctx: &Context<'_>, node_id: ID) -> Result<Vec<Attachment>> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
+        Ok(store
+            .attachments(node_id)?
+            .into_iter()
+            .map(|a| Attachment::new(a, store.namespace()))
+            .collect())
+    }
+
+    /// Get aggregated reactions on a node
+    async fn reactions(&self, ctx: &Context<'_>, node_id: ID) -> Result<ReactionSummary> {
+        let store = namespaced_store(ctx)?;
+        let node_id = resolve_node_id(&store, &node_id)?;
+        Ok(store.reaction_counts(node_id)?.into())
+    }
+
+    /// API version of this schema
+    async fn api_version(&self) -> &'static str {
+        super::API_VERSION
+    }
+
+    /// Size on disk against live data, across every namespace
+    async fn disk_usage(&self, ctx: &Context<'_>) -> Result<DiskUsage> {
+        let store = ctx.data::<std::sync::Arc<crate::store::SledStore>>()?.clone();
+        let usage = tokio::task::spawn_blocking(move || store.disk_usage()).await??;
+        Ok(usage.into())
+    }
+
+    /// Node, edge and event counts from the store's maintained counters
+    async fn stats(&self, ctx: &Context<'_>) -> Result<GraphStats> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.graph_stats()?.into())
+    }
+
+    /// Per-agent request counts, mutation/read ratios, error rates and
+    /// latency from the server's request journal, over the last
+    /// `since_seconds` and in windows of `window_seconds`
+    async fn usage(
+        &self,
+        ctx: &Context<'_>,
+        since_seconds: Option<i64>,
+        window_seconds: Option<i64>,
+    ) -> Result<UsageReport> {
+        let journal = ctx
+            .data_opt::<super::usage::RequestJournal>()
+            .ok_or("The server is not journalling requests")?
+            .0
+            .clone();
+        if window_seconds.is_some_and(|w| w <= 0) {
+            return Err("windowSeconds must be positive".into());
+        }
+        let since = since_seconds.map(|s| chrono::Utc::now() - chrono::Duration::seconds(s));
+        let window = window_seconds.map(chrono::Duration::seconds);
+        let records = tokio::task::spawn_blocking(move || super::usage::read_journal(&journal)).await??;
+        Ok(super::usage::report(&records, since, window).into())
+    }
+
+    /// Built-in node and edge kinds, then the registered custom ones
+    async fn kinds(&self, ctx: &Context<'_>) -> Result<Vec<KindEntry>> {
+        let store = namespaced_store(ctx)?;
+        let registry = store.kind_registry()?;
+        let built_in = |name: String, edge: bool| KindEntry {
+            name,
+            edge,
+            built_in: true,
+            description: None,
+            color: None,
+        };
+        let custom = |name: String, edge: bool, info: &crate::store::KindInfo| KindEntry {
+            name,
+            edge,
+            built_in: false,
+            description: info.description.clone(),
+            color: info.color.clone(),
+        };
+
+        let mut kinds: Vec<KindEntry> =
+            DomainNodeKind::BUILT_IN.iter().map(|kind| built_in(kind_name(kind), false)).collect();
+        kinds.extend(DomainEdgeKind::BUILT_IN.iter().map(|kind| built_in(kind_name(kind), true)));
+        kinds.extend(registry.nodes.iter().map(|(name, info)| custom(format!("custom:{}", name), false, info)));
+        kinds.extend(registry.edges.iter().map(|(name, info)| custom(format!("custom:{}", name), true, info)));
+        Ok(kinds)
+    }
+
+    /// The JSON Schema a node kind's content must match, if any
+    async fn content_schema(
+        &self,
+        ctx: &Context<'_>,
+        kind: NodeKind,
+    ) -> Result<Option<async_graphql::Json<serde_json::Value>>> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.content_schemas()?.get(&kind.0).cloned().map(async_graphql::Json))
+    }
+
+    /// Saved node templates
+    async fn templates(&self, ctx: &Context<'_>) -> Result<Vec<Template>> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.templates()?.into_iter().map(Into::into).collect())
+    }
+
+    /// List namespaces that contain data
+    async fn namespaces(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        let store = namespaced_store(ctx)?;
+        Ok(store.list_namespaces()?)
+    }
 }
@@ -0,0 +1,320 @@
+//! Per-request logging and the slow-query log for `serve http`
+//!
+//! Every GraphQL request is logged through `tracing` (target
+//! `elegant_state::request`) with its agent, operation, duration, response
+//! size and errors. Requests at or over the slow-query threshold are also
+//! appended as JSON lines to a size-rotated file, which
+//! `state-cli serve logs tail` reads back. The request journal behind
+//! `report usage` is the same kind of file with no threshold.
+
+use async_graphql::parser::types::{DocumentOperations, OperationDefinition, OperationType, Selection};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Requests at least this slow go to the slow-query log by default
+pub const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Size at which the slow-query log is rotated by default
+pub const DEFAULT_MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated slow-query logs kept by default
+pub const DEFAULT_MAX_LOG_FILES: usize = 5;
+
+/// One served GraphQL request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RequestRecord {
+    pub timestamp: DateTime<Utc>,
+    /// From the `x-state-agent` header, when the client sent one
+    pub agent: Option<String>,
+    pub namespace: Option<String>,
+    /// Operation name, or its type and root fields when unnamed
+    pub operation: String,
+    /// Whether the operation run was a mutation
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mutation: bool,
+    pub duration_ms: f64,
+    pub response_bytes: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Query text; only kept in the slow-query log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+impl RequestRecord {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.duration_ms.max(0.0) / 1000.0)
+    }
+
+    /// Emit the record as a structured `tracing` event
+    pub fn log(&self) {
+        let agent = self.agent.as_deref().unwrap_or("-");
+        let namespace = self.namespace.as_deref().unwrap_or("-");
+        if self.errors.is_empty() {
+            tracing::info!(
+                target: "elegant_state::request",
+                agent,
+                namespace,
+                operation = %self.operation,
+                duration_ms = self.duration_ms,
+                response_bytes = self.response_bytes,
+                "graphql request"
+            );
+        } else {
+            tracing::warn!(
+                target: "elegant_state::request",
+                agent,
+                namespace,
+                operation = %self.operation,
+                duration_ms = self.duration_ms,
+                response_bytes = self.response_bytes,
+                errors = %self.errors.join("; "),
+                "graphql request failed"
+            );
+        }
+    }
+}
+
+fn field_names(operation: &OperationDefinition) -> Vec<String> {
+    operation
+        .selection_set
+        .node
+        .items
+        .iter()
+        .filter_map(|item| match &item.node {
+            Selection::Field(field) => Some(field.node.name.node.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Label for a request: its operation name, else its type and root fields
+/// (`query nodes,edges`). Unparseable queries are labelled `invalid`.
+pub fn operation_label(query: &str, operation_name: Option<&str>) -> String {
+    if let Some(name) = operation_name {
+        return name.to_string();
+    }
+    let Ok(document) = async_graphql::parser::parse_query(query) else {
+        return "invalid".to_string();
+    };
+    match &document.operations {
+        DocumentOperations::Single(operation) => {
+            format!("{} {}", operation.node.ty, field_names(&operation.node).join(","))
+        }
+        DocumentOperations::Multiple(operations) => match operations.keys().next() {
+            Some(name) if operations.len() == 1 => name.to_string(),
+            _ => "multiple".to_string(),
+        },
+    }
+}
+
+/// Type and root field names of the operation a request runs; `None` when
+/// the query doesn't parse or doesn't say which operation to run
+pub fn root_fields(query: &str, operation_name: Option<&str>) -> Option<(OperationType, Vec<String>)> {
+    let document = async_graphql::parser::parse_query(query).ok()?;
+    let operation = match &document.operations {
+        DocumentOperations::Single(operation) => &operation.node,
+        DocumentOperations::Multiple(operations) => match operation_name {
+            Some(name) => &operations.get(name)?.node,
+            None if operations.len() == 1 => &operations.values().next()?.node,
+            None => return None,
+        },
+    };
+    Some((operation.ty, field_names(operation)))
+}
+
+/// Appends slow requests to a file, rotating it by size
+///
+/// `slow.log` rotates to `slow.log.1`, which moves to `slow.log.2` and so
+/// on; the oldest beyond `max_files` is deleted.
+pub struct SlowQueryLog {
+    path: PathBuf,
+    threshold: Duration,
+    max_bytes: u64,
+    max_files: usize,
+    file: Mutex<Option<File>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            threshold: DEFAULT_SLOW_QUERY_THRESHOLD,
+            max_bytes: DEFAULT_MAX_LOG_BYTES,
+            max_files: DEFAULT_MAX_LOG_FILES,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes.max(1);
+        self
+    }
+
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    pub fn is_slow(&self, record: &RequestRecord) -> bool {
+        record.duration() >= self.threshold
+    }
+
+    /// Append `record` if it is slow; returns whether it was written
+    pub fn record(&self, record: &RequestRecord) -> std::io::Result<bool> {
+        if !self.is_slow(record) {
+            return Ok(false);
+        }
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            *file = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        let handle = file.as_mut().expect("opened above");
+        handle.write_all(&line)?;
+        if handle.metadata()?.len() >= self.max_bytes {
+            *file = None;
+            self.rotate()?;
+        }
+        Ok(true)
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        let oldest = rotated_path(&self.path, self.max_files);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated_path(&self.path, 1))
+    }
+}
+
+/// Path of the `index`th rotated log; 0 is the live file
+pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// Parse the records in one log file, skipping lines that aren't records
+pub fn read_records(path: &Path) -> std::io::Result<Vec<RequestRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str(&line?) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// The last `n` records, oldest first, reading back into rotated files
+pub fn tail(path: &Path, n: usize) -> std::io::Result<Vec<RequestRecord>> {
+    let mut records: Vec<RequestRecord> = Vec::new();
+    for index in 0.. {
+        let file = rotated_path(path, index);
+        if records.len() >= n || (index > 0 && !file.exists()) {
+            break;
+        }
+        let mut older = read_records(&file)?;
+        older.append(&mut records);
+        records = older;
+    }
+    let skip = records.len().saturating_sub(n);
+    Ok(records.split_off(skip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(operation: &str, duration_ms: f64) -> RequestRecord {
+        RequestRecord {
+            timestamp: Utc::now(),
+            agent: Some("claude".into()),
+            namespace: None,
+            operation: operation.into(),
+            mutation: false,
+            duration_ms,
+            response_bytes: 10,
+            errors: vec![],
+            query: Some("{ nodes { id } }".into()),
+        }
+    }
+
+    #[test]
+    fn test_operation_label() {
+        assert_eq!(operation_label("{ nodes { id } edges { id } }", None), "query nodes,edges");
+        assert_eq!(operation_label("mutation { createNode(input: {}) { id } }", None), "mutation createNode");
+        assert_eq!(operation_label("query Recent { events { id } }", None), "Recent");
+        assert_eq!(operation_label("query A { a } query B { b }", Some("B")), "B");
+        assert_eq!(operation_label("query A { a } query B { b }", None), "multiple");
+        assert_eq!(operation_label("{ nodes {", None), "invalid");
+
+        let (ty, fields) = root_fields("query A { a } mutation B { b c }", Some("B")).unwrap();
+        assert_eq!((ty, fields), (OperationType::Mutation, vec!["b".to_string(), "c".to_string()]));
+        assert!(root_fields("query A { a } query B { b }", None).is_none());
+    }
+
+    #[test]
+    fn test_slow_query_log_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/slow.log");
+        let line_len = serde_json::to_vec(&record("query q0", 5.0)).unwrap().len() as u64 + 1;
+        let log = SlowQueryLog::new(&path)
+            .with_threshold(Duration::from_millis(2))
+            .with_max_bytes(line_len * 2)
+            .with_max_files(2);
+
+        assert!(!log.record(&record("query fast", 1.0)).unwrap());
+        for i in 0..7 {
+            assert!(log.record(&record(&format!("query q{}", i), 5.0)).unwrap());
+        }
+        // Two lines per file: q6 live, q4-q5 and q2-q3 rotated, q0-q1 dropped
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+
+        let operations = |records: Vec<RequestRecord>| records.into_iter().map(|r| r.operation).collect::<Vec<_>>();
+        assert_eq!(operations(tail(&path, 3).unwrap()), ["query q4", "query q5", "query q6"]);
+        assert_eq!(tail(&path, 100).unwrap().len(), 5);
+        assert!(tail(&dir.path().join("missing.log"), 5).unwrap().is_empty());
+    }
+}
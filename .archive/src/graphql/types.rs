@@ -1,88 +1,154 @@
-use async_graphql::{Enum, InputObject, SimpleObject, ID};
+use async_graphql::{
+    ComplexObject, Enum, InputObject, InputValueError, InputValueResult, Scalar, ScalarType, SimpleObject, Value,
+    ID,
+};
 use crate::schema::{self as domain, NodeKind as DomainNodeKind, EdgeKind as DomainEdgeKind, AgentId as DomainAgentId};
 
-// GraphQL enum for NodeKind
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
-pub enum NodeKind {
-    Conversation,
-    Project,
-    Insight,
-    Task,
-    Context,
-    Module,
-    Agent,
+/// Wire name of a kind: built-ins as they were when kinds were enums
+/// (`DERIVED_FROM`), custom kinds as `custom:<name>`
+pub(crate) fn kind_name(kind: &impl std::fmt::Display) -> String {
+    let name = kind.to_string();
+    if name.starts_with("custom:") {
+        name
+    } else {
+        name.to_uppercase()
+    }
+}
+
+/// Parse a kind from a string or enum literal; `None` for any other value
+fn parse_kind<T: std::str::FromStr<Err = String>>(value: &Value) -> Option<Result<T, String>> {
+    match value {
+        Value::String(name) => Some(name.parse()),
+        // Enum literals from queries written against the old enums
+        Value::Enum(name) => Some(name.as_str().parse()),
+        _ => None,
+    }
+}
+
+/// A node kind: `INSIGHT` and the other built-ins, or `custom:<name>`;
+/// any case is accepted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeKind(pub DomainNodeKind);
+
+#[Scalar(name = "NodeKind")]
+impl ScalarType for NodeKind {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match parse_kind(&value) {
+            Some(kind) => kind.map(NodeKind).map_err(InputValueError::custom),
+            None => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(kind_name(&self.0))
+    }
 }
 
 impl From<NodeKind> for DomainNodeKind {
     fn from(k: NodeKind) -> Self {
-        match k {
-            NodeKind::Conversation => DomainNodeKind::Conversation,
-            NodeKind::Project => DomainNodeKind::Project,
-            NodeKind::Insight => DomainNodeKind::Insight,
-            NodeKind::Task => DomainNodeKind::Task,
-            NodeKind::Context => DomainNodeKind::Context,
-            NodeKind::Module => DomainNodeKind::Module,
-            NodeKind::Agent => DomainNodeKind::Agent,
-        }
+        k.0
     }
 }
 
 impl From<DomainNodeKind> for NodeKind {
     fn from(k: DomainNodeKind) -> Self {
-        match k {
-            DomainNodeKind::Conversation => NodeKind::Conversation,
-            DomainNodeKind::Project => NodeKind::Project,
-            DomainNodeKind::Insight => NodeKind::Insight,
-            DomainNodeKind::Task => NodeKind::Task,
-            DomainNodeKind::Context => NodeKind::Context,
-            DomainNodeKind::Module => NodeKind::Module,
-            DomainNodeKind::Agent => NodeKind::Agent,
-            DomainNodeKind::Custom(_) => NodeKind::Context, // Fallback
-        }
+        NodeKind(k)
     }
 }
 
-// GraphQL enum for EdgeKind
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
-pub enum EdgeKind {
-    References,
-    DerivedFrom,
-    RelatedTo,
-    PartOf,
-    Blocks,
-    Enables,
-    Supersedes,
+/// An edge kind: `PART_OF` and the other built-ins, or `custom:<name>`;
+/// any case is accepted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EdgeKind(pub DomainEdgeKind);
+
+#[Scalar(name = "EdgeKind")]
+impl ScalarType for EdgeKind {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match parse_kind(&value) {
+            Some(kind) => kind.map(EdgeKind).map_err(InputValueError::custom),
+            None => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(kind_name(&self.0))
+    }
 }
 
 impl From<EdgeKind> for DomainEdgeKind {
     fn from(k: EdgeKind) -> Self {
-        match k {
-            EdgeKind::References => DomainEdgeKind::References,
-            EdgeKind::DerivedFrom => DomainEdgeKind::DerivedFrom,
-            EdgeKind::RelatedTo => DomainEdgeKind::RelatedTo,
-            EdgeKind::PartOf => DomainEdgeKind::PartOf,
-            EdgeKind::Blocks => DomainEdgeKind::Blocks,
-            EdgeKind::Enables => DomainEdgeKind::Enables,
-            EdgeKind::Supersedes => DomainEdgeKind::Supersedes,
-        }
+        k.0
     }
 }
 
 impl From<DomainEdgeKind> for EdgeKind {
     fn from(k: DomainEdgeKind) -> Self {
-        match k {
-            DomainEdgeKind::References => EdgeKind::References,
-            DomainEdgeKind::DerivedFrom => EdgeKind::DerivedFrom,
-            DomainEdgeKind::RelatedTo => EdgeKind::RelatedTo,
-            DomainEdgeKind::PartOf => EdgeKind::PartOf,
-            DomainEdgeKind::Blocks => EdgeKind::Blocks,
-            DomainEdgeKind::Enables => EdgeKind::Enables,
-            DomainEdgeKind::Supersedes => EdgeKind::Supersedes,
-            DomainEdgeKind::Custom(_) => EdgeKind::RelatedTo, // Fallback
+        EdgeKind(k)
+    }
+}
+
+/// A built-in or registered kind, as listed by the `kinds` query
+#[derive(SimpleObject)]
+pub struct KindEntry {
+    /// As accepted by `NodeKind` and `EdgeKind` arguments
+    pub name: String,
+    /// True for edge kinds, false for node kinds
+    pub edge: bool,
+    pub built_in: bool,
+    pub description: Option<String>,
+    pub color: Option<String>,
+}
+
+/// An alias and the node it names
+#[derive(SimpleObject)]
+pub struct NodeAlias {
+    pub alias: String,
+    pub node: ID,
+}
+
+#[derive(InputObject)]
+pub struct RegisterKindInput {
+    /// Lowercase name, with or without the `custom:` prefix
+    pub name: String,
+    /// Register an edge kind rather than a node kind
+    #[graphql(default)]
+    pub edge: bool,
+    pub description: Option<String>,
+    /// `#rrggbb`
+    pub color: Option<String>,
+}
+
+/// A saved node template, as listed by the `templates` query
+#[derive(SimpleObject)]
+pub struct Template {
+    pub name: String,
+    pub kind: NodeKind,
+    /// Variables `applyTemplate` must be given
+    pub variables: Vec<String>,
+    pub description: Option<String>,
+    /// The whole template as saved
+    pub definition: async_graphql::Json<serde_json::Value>,
+}
+
+impl From<(String, crate::store::NodeTemplate)> for Template {
+    fn from((name, template): (String, crate::store::NodeTemplate)) -> Self {
+        Self {
+            name,
+            kind: NodeKind(template.kind.clone()),
+            variables: template.variables().into_iter().collect(),
+            description: template.description.clone(),
+            definition: async_graphql::Json(serde_json::to_value(&template).unwrap_or_default()),
         }
     }
 }
 
+/// The node and edges made by `applyTemplate`
+#[derive(SimpleObject)]
+pub struct AppliedTemplate {
+    pub node: StateNode,
+    pub edges: Vec<StateEdge>,
+}
+
 #[derive(Enum, Copy, Clone, Eq, PartialEq)]
 pub enum AgentKind {
     User,
@@ -102,8 +168,150 @@ fn from(a: AgentKind) -> Self {
     }
 }
 
+// GraphQL enum for DeleteMode
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum DeleteMode {
+    /// Delete incident edges along with the node
+    Cascade,
+    /// Fail while the node still has edges
+    Restrict,
+}
+
+impl From<DeleteMode> for crate::store::DeleteMode {
+    fn from(m: DeleteMode) -> Self {
+        match m {
+            DeleteMode::Cascade => crate::store::DeleteMode::Cascade,
+            DeleteMode::Restrict => crate::store::DeleteMode::Restrict,
+        }
+    }
+}
+
+// GraphQL enum for the patch formats `patchNode` accepts
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum PatchFormat {
+    /// RFC 6902 if the patch is an array, otherwise RFC 7386
+    Auto,
+    /// RFC 6902 JSON Patch operations
+    JsonPatch,
+    /// RFC 7386 JSON Merge Patch
+    MergePatch,
+}
+
+impl PatchFormat {
+    pub fn parse(self, patch: serde_json::Value) -> crate::store::Result<crate::store::ContentPatch> {
+        use crate::store::ContentPatch;
+        match self {
+            PatchFormat::Auto => ContentPatch::detect(patch),
+            PatchFormat::JsonPatch => ContentPatch::json(patch),
+            PatchFormat::MergePatch => Ok(ContentPatch::Merge(patch)),
+        }
+    }
+}
+
+// GraphQL enum for PathMode
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum PathMode {
+    /// Lowest sum of edge weights
+    MinCost,
+    /// Highest product of edge weights
+    MaxProduct,
+}
+
+impl From<PathMode> for crate::store::PathMode {
+    fn from(m: PathMode) -> Self {
+        match m {
+            PathMode::MinCost => crate::store::PathMode::MinCost,
+            PathMode::MaxProduct => crate::store::PathMode::MaxProduct,
+        }
+    }
+}
+
+// GraphQL enum for SortKey
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum SortKey {
+    CreatedAt,
+    UpdatedAt,
+    Kind,
+    Degree,
+}
+
+impl From<SortKey> for crate::store::SortKey {
+    fn from(k: SortKey) -> Self {
+        match k {
+            SortKey::CreatedAt => crate::store::SortKey::CreatedAt,
+            SortKey::UpdatedAt => crate::store::SortKey::UpdatedAt,
+            SortKey::Kind => crate::store::SortKey::Kind,
+            SortKey::Degree => crate::store::SortKey::Degree,
+        }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum RetentionClass {
+    Ephemeral,
+    Standard,
+    Permanent,
+}
+
+impl From<RetentionClass> for domain::RetentionClass {
+    fn from(c: RetentionClass) -> Self {
+        match c {
+            RetentionClass::Ephemeral => domain::RetentionClass::Ephemeral,
+            RetentionClass::Standard => domain::RetentionClass::Standard,
+            RetentionClass::Permanent => domain::RetentionClass::Permanent,
+        }
+    }
+}
+
+impl From<domain::RetentionClass> for RetentionClass {
+    fn from(c: domain::RetentionClass) -> Self {
+        match c {
+            domain::RetentionClass::Ephemeral => RetentionClass::Ephemeral,
+            domain::RetentionClass::Standard => RetentionClass::Standard,
+            domain::RetentionClass::Permanent => RetentionClass::Permanent,
+        }
+    }
+}
+
+// GraphQL enum for RenderTarget
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum RenderFormat {
+    Html,
+    Ansi,
+    Plain,
+}
+
+impl From<RenderFormat> for crate::render::RenderTarget {
+    fn from(f: RenderFormat) -> Self {
+        match f {
+            RenderFormat::Html => crate::render::RenderTarget::Html,
+            RenderFormat::Ansi => crate::render::RenderTarget::Ansi,
+            RenderFormat::Plain => crate::render::RenderTarget::Plain,
+        }
+    }
+}
+
+// GraphQL enum for ReactionKind
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum ReactionKind {
+    Useful,
+    Outdated,
+    Disputed,
+}
+
+impl From<ReactionKind> for domain::ReactionKind {
+    fn from(k: ReactionKind) -> Self {
+        match k {
+            ReactionKind::Useful => domain::ReactionKind::Useful,
+            ReactionKind::Outdated => domain::ReactionKind::Outdated,
+            ReactionKind::Disputed => domain::ReactionKind::Disputed,
+        }
+    }
+}
+
 // GraphQL output types
 #[derive(SimpleObject)]
+#[graphql(complex)]
 pub struct StateNode {
     pub id: ID,
     pub kind: NodeKind,
@@ -111,6 +319,46 @@ pub struct StateNode {
     pub metadata: async_graphql::Json<serde_json::Value>,
     pub created_at: String,
     pub updated_at: String,
+    pub expires_at: Option<String>,
+    /// Pass back as `expectedVersion` to detect concurrent updates
+    pub version: u64,
+    pub tags: Vec<String>,
+    pub pinned: bool,
+    /// The node's own retention class; null when it follows its kind's
+    pub retention: Option<RetentionClass>,
+}
+
+#[ComplexObject]
+impl StateNode {
+    /// Typed content of a task node; null for other kinds, an error for
+    /// content without a title
+    async fn task(&self) -> async_graphql::Result<Option<TaskContent>> {
+        Ok(self.typed::<domain::TaskContent>()?.map(Into::into))
+    }
+
+    /// Typed content of an insight node; null for other kinds, an error
+    /// for content without text
+    async fn insight(&self) -> async_graphql::Result<Option<InsightContent>> {
+        Ok(self.typed::<domain::InsightContent>()?.map(Into::into))
+    }
+
+    /// Typed content of a conversation node; null for other kinds
+    async fn conversation(&self) -> async_graphql::Result<Option<ConversationContent>> {
+        Ok(self.typed::<domain::ConversationContent>()?.map(Into::into))
+    }
+}
+
+impl StateNode {
+    /// The content read as `T`, or `None` if the node is another kind;
+    /// parsed only when a query selects the typed field
+    fn typed<T: domain::TypedContent>(&self) -> Result<Option<T>, String> {
+        if self.kind.0 != T::kind() {
+            return Ok(None);
+        }
+        serde_json::from_value(self.content.0.clone())
+            .map(Some)
+            .map_err(|e| format!("Content of {} node {} doesn't fit: {}", self.kind.0, self.id.as_str(), e))
+    }
 }
 
 impl From<domain::StateNode> for StateNode {
@@ -122,6 +370,80 @@ fn from(n: domain::StateNode) -> Self {
             metadata: async_graphql::Json(serde_json::to_value(&n.metadata).unwrap_or_default()),
             created_at: n.created_at.to_rfc3339(),
             updated_at: n.updated_at.to_rfc3339(),
+            expires_at: n.expires_at.map(|t| t.to_rfc3339()),
+            version: n.version,
+            tags: n.tags,
+            pinned: n.pinned,
+            retention: n.retention.map(Into::into),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct TaskContent {
+    pub title: String,
+    pub status: Option<String>,
+    pub description: Option<String>,
+    pub priority: Option<i64>,
+    /// RFC 3339
+    pub due: Option<String>,
+    pub assignee: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl From<domain::TaskContent> for TaskContent {
+    fn from(t: domain::TaskContent) -> Self {
+        Self {
+            title: t.title,
+            status: t.status,
+            description: t.description,
+            priority: t.priority,
+            due: t.due.map(|d| d.to_rfc3339()),
+            assignee: t.assignee,
+            tags: t.tags,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct InsightContent {
+    pub text: String,
+    pub confidence: Option<f64>,
+    pub source: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl From<domain::InsightContent> for InsightContent {
+    fn from(i: domain::InsightContent) -> Self {
+        Self { text: i.text, confidence: i.confidence, source: i.source, tags: i.tags }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ConversationMessage {
+    pub role: String,
+    pub text: String,
+    /// RFC 3339
+    pub at: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct ConversationContent {
+    pub title: Option<String>,
+    pub participants: Vec<String>,
+    pub messages: Vec<ConversationMessage>,
+}
+
+impl From<domain::ConversationContent> for ConversationContent {
+    fn from(c: domain::ConversationContent) -> Self {
+        Self {
+            title: c.title,
+            participants: c.participants,
+            messages: c
+                .messages
+                .into_iter()
+                .map(|m| ConversationMessage { role: m.role, text: m.text, at: m.at.map(|at| at.to_rfc3339()) })
+                .collect(),
         }
     }
 }
@@ -133,6 +455,8 @@ pub struct StateEdge {
     pub to: ID,
     pub kind: EdgeKind,
     pub weight: f32,
+    /// Why the nodes are linked, and anything else agents record about it
+    pub metadata: async_graphql::Json<serde_json::Value>,
     pub created_at: String,
 }
 
@@ -144,19 +468,121 @@ fn from(e: domain::StateEdge) -> Self {
             to: ID(e.to.to_string()),
             kind: e.kind.into(),
             weight: e.weight,
+            metadata: async_graphql::Json(serde_json::to_value(&e.metadata).unwrap_or_default()),
             created_at: e.created_at.to_rfc3339(),
         }
     }
 }
 
+/// One hop along a path
+#[derive(SimpleObject)]
+pub struct PathStep {
+    pub edge: StateEdge,
+    /// True when the edge was followed from its source to its target
+    pub forward: bool,
+}
+
+/// A path between two nodes: `nodes[i]` and `nodes[i + 1]` are joined by `steps[i]`
+#[derive(SimpleObject)]
+pub struct GraphPath {
+    pub nodes: Vec<StateNode>,
+    pub steps: Vec<PathStep>,
+    /// Sum of the edge weights along the path
+    pub cost: f64,
+    /// Product of the edge weights along the path, each capped at 1
+    pub strength: f64,
+    pub hops: i32,
+}
+
+impl From<crate::store::GraphPath> for GraphPath {
+    fn from(p: crate::store::GraphPath) -> Self {
+        Self {
+            hops: p.hops() as i32,
+            nodes: p.nodes.into_iter().map(Into::into).collect(),
+            steps: p
+                .steps
+                .into_iter()
+                .map(|s| PathStep { edge: s.edge.into(), forward: s.forward })
+                .collect(),
+            cost: p.cost,
+            strength: p.strength,
+        }
+    }
+}
+
+/// A node as it stood at a point in time
+#[derive(SimpleObject)]
+pub struct NodeAsOf {
+    /// RFC 3339 time the node was reconstructed at
+    pub at: String,
+    /// Null if the node didn't exist then
+    pub node: Option<StateNode>,
+    /// Edges to or from the node at that time; empty unless requested
+    pub edges: Vec<StateEdge>,
+}
+
+/// One version of a node and the change that produced it
+#[derive(SimpleObject)]
+pub struct NodeVersion {
+    pub node: StateNode,
+    pub event: ID,
+    pub agent: String,
+    pub operation: String,
+    pub timestamp: String,
+}
+
+impl From<crate::store::NodeVersion> for NodeVersion {
+    fn from(v: crate::store::NodeVersion) -> Self {
+        Self {
+            node: v.node.into(),
+            event: ID(v.event.to_string()),
+            agent: v.agent.to_string(),
+            operation: format!("{:?}", v.operation),
+            timestamp: v.timestamp.to_rfc3339(),
+        }
+    }
+}
+
+/// A loop of edges: `edges[i]` leaves `nodes[i]` and the last edge returns to `nodes[0]`
+#[derive(SimpleObject)]
+pub struct Cycle {
+    pub nodes: Vec<ID>,
+    pub edges: Vec<StateEdge>,
+}
+
+impl From<crate::store::cycles::Cycle> for Cycle {
+    fn from(c: crate::store::cycles::Cycle) -> Self {
+        Self {
+            nodes: c.nodes.into_iter().map(|id| ID(id.to_string())).collect(),
+            edges: c.edges.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct StateEvent {
     pub id: ID,
     pub timestamp: String,
     pub agent: String,
     pub operation: String,
+    /// How `before` and `after` were captured: full, diff or hash
+    pub capture: String,
     pub before: Option<async_graphql::Json<serde_json::Value>>,
     pub after: Option<async_graphql::Json<serde_json::Value>>,
+    /// Shared by the events of one composite operation
+    pub group: Option<ID>,
+    /// Instance ID of the store that first recorded the event
+    pub origin: Option<String>,
+    /// Events per instance the origin had seen; order events from
+    /// several instances by this rather than by timestamp
+    pub clock: Vec<ClockEntry>,
+}
+
+/// One instance's count in an event's vector clock
+#[derive(SimpleObject)]
+pub struct ClockEntry {
+    pub instance: String,
+    pub count: u64,
 }
 
 impl From<domain::StateEvent> for StateEvent {
@@ -166,8 +592,268 @@ fn from(e: domain::StateEvent) -> Self {
             timestamp: e.timestamp.to_rfc3339(),
             agent: e.agent.to_string(),
             operation: format!("{:?}", e.operation),
+            capture: e.capture.to_string(),
             before: e.before.map(async_graphql::Json),
             after: e.after.map(async_graphql::Json),
+            group: e.group.map(|g| ID(g.to_string())),
+            origin: e.origin,
+            clock: e.clock.0.into_iter().map(|(instance, count)| ClockEntry { instance, count }).collect(),
+        }
+    }
+}
+
+/// A mutation awaiting (or past) a vote
+#[derive(SimpleObject)]
+pub struct Proposal {
+    pub id: ID,
+    pub proposer: String,
+    pub operation: String,
+    pub target: async_graphql::Json<serde_json::Value>,
+    pub payload: async_graphql::Json<serde_json::Value>,
+    pub rationale: Option<String>,
+    /// pending, approved, rejected, expired or withdrawn
+    pub status: String,
+    pub created_at: String,
+}
+
+impl From<crate::coordinator::Proposal> for Proposal {
+    fn from(p: crate::coordinator::Proposal) -> Self {
+        Self {
+            id: ID(p.id.to_string()),
+            proposer: p.proposer.to_string(),
+            operation: format!("{:?}", p.operation),
+            target: async_graphql::Json(serde_json::to_value(&p.target).unwrap_or_default()),
+            payload: async_graphql::Json(p.payload),
+            rationale: p.rationale,
+            status: p.status.to_string(),
+            created_at: p.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Annotation {
+    pub id: ID,
+    pub node_id: ID,
+    pub author: String,
+    pub text: String,
+    /// JSON path or `start..end` text range
+    pub anchor: Option<String>,
+    pub created_at: String,
+}
+
+impl From<domain::Annotation> for Annotation {
+    fn from(a: domain::Annotation) -> Self {
+        Self {
+            id: ID(a.id.to_string()),
+            node_id: ID(a.node_id.to_string()),
+            author: a.author.to_string(),
+            text: a.text,
+            anchor: a.anchor.map(|anchor| anchor.to_string()),
+            created_at: a.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Attachment {
+    pub node_id: ID,
+    /// Hex SHA-256 of the content
+    pub hash: String,
+    pub mime: String,
+    pub size: u64,
+    pub created_at: String,
+    /// Server-relative download path
+    pub url: String,
+}
+
+impl Attachment {
+    /// Convert, pointing the download URL at the given namespace
+    pub fn new(a: crate::store::Attachment, namespace: Option<&str>) -> Self {
+        let url = match namespace {
+            Some(ns) => format!("{}?namespace={}", a.download_path(), ns),
+            None => a.download_path(),
+        };
+        Self {
+            node_id: ID(a.node_id.to_string()),
+            hash: a.hash,
+            mime: a.mime,
+            size: a.size,
+            created_at: a.created_at.to_rfc3339(),
+            url,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ReactionSummary {
+    pub useful: i32,
+    pub outdated: i32,
+    pub disputed: i32,
+    pub trust: f32,
+    pub freshness: f32,
+}
+
+impl From<domain::ReactionCounts> for ReactionSummary {
+    fn from(c: domain::ReactionCounts) -> Self {
+        Self {
+            useful: c.useful as i32,
+            outdated: c.outdated as i32,
+            disputed: c.disputed as i32,
+            trust: c.trust(),
+            freshness: c.freshness(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct RenderedContent {
+    /// Declared or detected content type (markdown, csv, mermaid, json, plain)
+    pub content_type: String,
+    pub output: String,
+}
+
+impl From<crate::render::Rendered> for RenderedContent {
+    fn from(r: crate::render::Rendered) -> Self {
+        Self {
+            content_type: r.content_type.to_string(),
+            output: r.output,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DiskUsage {
+    pub on_disk_bytes: u64,
+    pub live_bytes: u64,
+    /// Estimate of what `compactDatabase` could give back
+    pub reclaimable_bytes: u64,
+}
+
+impl From<crate::store::DiskUsage> for DiskUsage {
+    fn from(u: crate::store::DiskUsage) -> Self {
+        Self {
+            on_disk_bytes: u.on_disk,
+            live_bytes: u.live,
+            reclaimable_bytes: u.reclaimable(),
+        }
+    }
+}
+
+/// One named count in `GraphStats`
+#[derive(SimpleObject)]
+pub struct Count {
+    pub name: String,
+    pub count: u64,
+}
+
+#[derive(SimpleObject)]
+pub struct GraphStats {
+    pub nodes: u64,
+    pub edges: u64,
+    pub events: u64,
+    pub nodes_by_kind: Vec<Count>,
+    pub edges_by_kind: Vec<Count>,
+    pub events_by_agent: Vec<Count>,
+    /// Events per UTC day (`YYYY-MM-DD`), oldest first
+    pub events_by_day: Vec<Count>,
+}
+
+impl From<crate::store::GraphStats> for GraphStats {
+    fn from(s: crate::store::GraphStats) -> Self {
+        let counts = |map: std::collections::BTreeMap<String, u64>| -> Vec<Count> {
+            map.into_iter().map(|(name, count)| Count { name, count }).collect()
+        };
+        Self {
+            nodes: s.nodes(),
+            edges: s.edges(),
+            events: s.events(),
+            nodes_by_kind: counts(s.nodes_by_kind),
+            edges_by_kind: counts(s.edges_by_kind),
+            events_by_agent: counts(s.events_by_agent),
+            events_by_day: counts(s.events_by_day),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CompactionResult {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+    pub reclaimed_bytes: u64,
+    pub entries: u64,
+}
+
+impl From<crate::store::CompactionReport> for CompactionResult {
+    fn from(r: crate::store::CompactionReport) -> Self {
+        Self {
+            before_bytes: r.before.on_disk,
+            after_bytes: r.after.on_disk,
+            reclaimed_bytes: r.reclaimed_bytes(),
+            entries: r.entries,
+        }
+    }
+}
+
+/// Request totals for one agent in `UsageReport`
+#[derive(SimpleObject)]
+pub struct AgentUsage {
+    pub agent: String,
+    pub requests: u64,
+    pub mutations: u64,
+    pub reads: u64,
+    pub errors: u64,
+    /// Mutations per read; null until the agent has read
+    pub mutation_ratio: Option<f64>,
+    pub error_rate: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+fn agent_usage(by_agent: std::collections::BTreeMap<String, super::usage::AgentUsage>) -> Vec<AgentUsage> {
+    by_agent
+        .into_iter()
+        .map(|(agent, u)| AgentUsage {
+            agent,
+            requests: u.requests as u64,
+            mutations: u.mutations as u64,
+            reads: u.reads as u64,
+            errors: u.errors as u64,
+            mutation_ratio: u.mutation_ratio,
+            error_rate: u.error_rate,
+            mean_ms: u.mean_ms,
+            max_ms: u.max_ms,
+        })
+        .collect()
+}
+
+#[derive(SimpleObject)]
+pub struct UsageWindow {
+    /// Start of the window, RFC 3339
+    pub start: String,
+    pub agents: Vec<AgentUsage>,
+}
+
+#[derive(SimpleObject)]
+pub struct UsageReport {
+    pub since: Option<String>,
+    pub window_seconds: Option<i64>,
+    pub agents: Vec<AgentUsage>,
+    /// Windows with at least one request, oldest first
+    pub windows: Vec<UsageWindow>,
+}
+
+impl From<super::usage::UsageReport> for UsageReport {
+    fn from(r: super::usage::UsageReport) -> Self {
+        Self {
+            since: r.since.map(|t| t.to_rfc3339()),
+            window_seconds: r.window_seconds,
+            agents: agent_usage(r.by_agent),
+            windows: r
+                .windows
+                .into_iter()
+                .map(|w| UsageWindow { start: w.start.to_rfc3339(), agents: agent_usage(w.by_agent) })
+                .collect(),
         }
     }
 }
@@ -178,12 +864,21 @@ pub struct CreateNodeInput {
     pub kind: NodeKind,
     pub content: async_graphql::Json<serde_json::Value>,
     pub metadata: Option<async_graphql::Json<serde_json::Value>>,
+    /// Delete the node this many seconds after creation
+    pub ttl_seconds: Option<i64>,
+    pub tags: Option<Vec<String>>,
+    /// Pin the node for every agent to load
+    pub pinned: Option<bool>,
+    /// Override the retention class configured for the kind
+    pub retention: Option<RetentionClass>,
 }
 
 #[derive(InputObject)]
 pub struct UpdateNodeInput {
     pub id: ID,
     pub content: async_graphql::Json<serde_json::Value>,
+    /// Reject the update unless the node is still at this version
+    pub expected_version: Option<u64>,
 }
 
 #[derive(InputObject)]
@@ -192,4 +887,31 @@ pub struct CreateEdgeInput {
     pub to: ID,
     pub kind: EdgeKind,
     pub weight: Option<f32>,
+    pub metadata: Option<async_graphql::Json<serde_json::Value>>,
+}
+
+#[derive(InputObject)]
+pub struct UpdateEdgeInput {
+    pub id: ID,
+    pub weight: Option<f32>,
+    /// Merged into the edge's metadata; fields set to null are removed
+    pub metadata: Option<async_graphql::Json<serde_json::Value>>,
+}
+
+#[derive(InputObject)]
+pub struct SubmitProposalInput {
+    /// create, update, delete, link or unlink
+    pub operation: String,
+    /// `node:ID`, `edge:ID` or `new:kind`
+    pub target: String,
+    pub payload: async_graphql::Json<serde_json::Value>,
+    pub rationale: Option<String>,
+}
+
+#[derive(InputObject)]
+pub struct AnnotateNodeInput {
+    pub node_id: ID,
+    pub text: String,
+    /// JSON path (`$.field`) or `start..end` text range
+    pub anchor: Option<String>,
 }
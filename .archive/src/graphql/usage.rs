@@ -0,0 +1,155 @@
+//! Per-agent API usage, aggregated from the request journal
+//!
+//! `serve http` appends every request it serves to a journal: the
+//! slow-query log's rotating writer, with no threshold. `state-cli report
+//! usage` and the `usage` query read it back and total requests, mutations,
+//! errors and latency for each agent, overall and per time window.
+
+use super::request_log::{self, RequestRecord};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Agent name for requests sent without an `x-state-agent` header
+pub const ANONYMOUS_AGENT: &str = "anonymous";
+
+/// Where the server journals requests; attached to each request so the
+/// `usage` query can read it
+#[derive(Debug, Clone)]
+pub struct RequestJournal(pub PathBuf);
+
+/// Request totals for one agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AgentUsage {
+    pub requests: usize,
+    pub mutations: usize,
+    pub reads: usize,
+    pub errors: usize,
+    /// Mutations per read; `None` until the agent has read
+    pub mutation_ratio: Option<f64>,
+    /// Share of requests that returned errors
+    pub error_rate: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    #[serde(skip)]
+    total_ms: f64,
+}
+
+impl AgentUsage {
+    fn add(&mut self, record: &RequestRecord) {
+        self.requests += 1;
+        if record.mutation {
+            self.mutations += 1;
+        } else {
+            self.reads += 1;
+        }
+        if !record.errors.is_empty() {
+            self.errors += 1;
+        }
+        self.total_ms += record.duration_ms;
+        self.max_ms = self.max_ms.max(record.duration_ms);
+
+        self.mutation_ratio = (self.reads > 0).then(|| self.mutations as f64 / self.reads as f64);
+        self.error_rate = self.errors as f64 / self.requests as f64;
+        self.mean_ms = self.total_ms / self.requests as f64;
+    }
+}
+
+/// Usage within one time window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageWindow {
+    pub start: DateTime<Utc>,
+    pub by_agent: BTreeMap<String, AgentUsage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub since: Option<DateTime<Utc>>,
+    pub window_seconds: Option<i64>,
+    pub by_agent: BTreeMap<String, AgentUsage>,
+    /// Windows with at least one request, oldest first
+    pub windows: Vec<UsageWindow>,
+}
+
+/// Aggregate `records` from `since` on, bucketing them into windows of
+/// `window` aligned to the Unix epoch when one is given
+pub fn report(records: &[RequestRecord], since: Option<DateTime<Utc>>, window: Option<Duration>) -> UsageReport {
+    let window_ms = window.map(|w| w.num_milliseconds().max(1));
+    let mut by_agent: BTreeMap<String, AgentUsage> = BTreeMap::new();
+    let mut windows: BTreeMap<i64, BTreeMap<String, AgentUsage>> = BTreeMap::new();
+
+    for record in records.iter().filter(|r| since.map_or(true, |since| r.timestamp >= since)) {
+        let agent = record.agent.as_deref().unwrap_or(ANONYMOUS_AGENT);
+        by_agent.entry(agent.to_string()).or_default().add(record);
+        if let Some(window_ms) = window_ms {
+            let start = record.timestamp.timestamp_millis().div_euclid(window_ms) * window_ms;
+            windows.entry(start).or_default().entry(agent.to_string()).or_default().add(record);
+        }
+    }
+
+    UsageReport {
+        since,
+        window_seconds: window.map(|w| w.num_seconds()),
+        by_agent,
+        windows: windows
+            .into_iter()
+            .filter_map(|(start, by_agent)| Some(UsageWindow { start: DateTime::from_timestamp_millis(start)?, by_agent }))
+            .collect(),
+    }
+}
+
+/// Every journalled request, oldest first, including rotated files
+pub fn read_journal(path: &Path) -> std::io::Result<Vec<RequestRecord>> {
+    request_log::tail(path, usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(agent: Option<&str>, at: DateTime<Utc>, mutation: bool, failed: bool, duration_ms: f64) -> RequestRecord {
+        RequestRecord {
+            timestamp: at,
+            agent: agent.map(str::to_string),
+            namespace: None,
+            operation: if mutation { "mutation createNode" } else { "query nodes" }.into(),
+            mutation,
+            duration_ms,
+            response_bytes: 10,
+            errors: if failed { vec!["boom".into()] } else { vec![] },
+            query: None,
+        }
+    }
+
+    #[test]
+    fn test_usage_report() {
+        let hour = DateTime::from_timestamp(1_700_000_000 - 1_700_000_000 % 3600, 0).unwrap();
+        let records = vec![
+            record(Some("claude"), hour - Duration::minutes(5), true, false, 100.0),
+            record(Some("claude"), hour + Duration::minutes(1), false, false, 10.0),
+            record(Some("claude"), hour + Duration::minutes(2), true, true, 30.0),
+            record(Some("claude"), hour + Duration::minutes(70), false, false, 20.0),
+            record(None, hour + Duration::minutes(3), false, false, 4.0),
+        ];
+
+        let usage = report(&records, Some(hour), Some(Duration::hours(1)));
+        let claude = &usage.by_agent["claude"];
+        assert_eq!((claude.requests, claude.mutations, claude.reads, claude.errors), (3, 1, 2, 1));
+        assert_eq!(claude.mutation_ratio, Some(0.5));
+        assert!((claude.error_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!((claude.mean_ms, claude.max_ms), (20.0, 30.0));
+        assert_eq!(usage.by_agent[ANONYMOUS_AGENT].mutation_ratio, Some(0.0));
+
+        assert_eq!(usage.window_seconds, Some(3600));
+        let starts: Vec<DateTime<Utc>> = usage.windows.iter().map(|w| w.start).collect();
+        assert_eq!(starts, [hour, hour + Duration::hours(1)]);
+        assert_eq!(usage.windows[0].by_agent["claude"].requests, 2);
+        assert_eq!(usage.windows[0].by_agent.len(), 2);
+        assert_eq!(usage.windows[1].by_agent["claude"].requests, 1);
+
+        let unwindowed = report(&records, None, None);
+        assert_eq!(unwindowed.by_agent["claude"].requests, 4);
+        assert!(unwindowed.windows.is_empty());
+    }
+}
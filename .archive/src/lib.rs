@@ -7,11 +7,30 @@
 pub mod store;
 pub mod graphql;
 pub mod event;
+pub mod coordinator;
+pub mod render;
+pub mod viz;
+pub mod watch;
+pub mod workspace;
+pub mod connector;
+pub mod git;
+pub mod server_config;
+pub mod watchdog;
+pub mod explain;
+pub mod generate;
+#[cfg(feature = "ask")]
+pub mod ask;
 
-pub use schema::{StateNode, StateEdge, StateEvent, NodeKind, EdgeKind, AgentId};
-pub use store::{SledStore, Store, StoreError};
-pub use graphql::{build_schema, StateSchema};
+pub use schema::{StateNode, StateEdge, StateEvent, NodeKind, EdgeKind, AgentId, Operation};
+pub use store::{DedupeMode, DedupeOutcome, DeleteMode, SledStore, Store, StoreError};
+pub use graphql::{build_schema, Namespace, StateSchema, API_VERSION, AGENT_HEADER, NAMESPACE_HEADER};
 pub use event::EventSourcer;
+pub use coordinator::{
+    CapabilityMode, AgentCapabilities, CapabilityConfig,
+    Proposal, ProposalStatus, ProposalTarget, ProposalManager,
+    Vote, VoteDecision, VotingStrategy, VotingCoordinator, VotingResult,
+    Reputation, ReputationTracker,
+};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
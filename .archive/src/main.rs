@@ -1,12 +1,141 @@
 use anyhow::Result;
 use clap::Parser;
+use elegant_state::schema::{
+    Annotation, AnnotationAnchor, CaptureMode, Metadata, NodeId, Reaction, ReactionCounts, ReactionKind,
+    RetentionClass,
+};
 use elegant_state::{
-    build_schema, NodeKind, StateEdge, StateNode, SledStore, Store, AgentId, EdgeKind,
+    build_schema, DedupeMode, DeleteMode, EventSourcer, NodeKind, StateEdge, StateNode, SledStore, Store,
+    AgentId, EdgeKind, Operation, VotingStrategy,
+};
+use elegant_state::render::{Renderer, CONTENT_TYPE_KEY};
+use elegant_state::viz::{self, DiagramFormat};
+use elegant_state::watch::{self, WatchFilter, Watcher};
+use elegant_state::workspace::{self, Workspace};
+use elegant_state::git;
+use elegant_state::graphql::client::{introspection_to_sdl, GraphqlClient};
+use elegant_state::graphql::codegen::{self, SchemaFormat};
+use elegant_state::graphql::introspection;
+use elegant_state::server_config::{self, LiveConfig, CAPABILITIES_KEY, VOTES_KEY};
+use elegant_state::watchdog::{self, spawn_watchdog, Refusal, Watchdog};
+use elegant_state::connector::{ConnectorSpec, Federation, SourceSpec, CONNECTORS_META_KEY};
+use elegant_state::coordinator::{
+    AutoApprovalPolicy, AutoApprovalRule, BatchVote, CapabilityConfig, Escalation,
+    EscalationPolicy, EscalationRule, ImpactGraph, Vertex, GovernanceTelemetry, Proposal, ProposalFilter, ProposalId,
+    ProposalManager, ProposalStatus, ProposalTarget, Simulation, PROPOSALS_KEY, SimulationConfig, Vote,
+    ReputationTracker, VoteDecision, VotingCoordinator, VotingResult,
 };
+use elegant_state::store::{
+    chunks, detect_format, expand, guess_mime, import_nodes, list_snapshots, spawn_expiry_sweeper,
+    verify_dump, xref, FullTextIndex, ImportOptions, IndexSync, InputFormat, MetaQuery, NodeTemplate, PandocConverter, Reversal, Sort,
+    SortKey, TemplateEdge, SCHEMA_VERSION,
+};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 mod cli;
-use cli::{Cli, Commands, NodeCommands, EdgeCommands, ServeCommands};
+use cli::{
+    Cli, Commands, NodeCommands, EdgeCommands, GraphCommands, ServeCommands, ServeLogsCommands, CoordinatorCommands, DbCommands,
+    ReportCommands, SearchCommands, SearchIndexCommands, SnapshotCommands, GraphqlCommands, ShareCommands,
+    ConnectorCommands, EventCommands, IndexCommands, ProposalCommands, AutoApproveCommands,
+    EscalationCommands, VoteCommands, VotingStrategyArg, HookCommands, IngestCommands, KindCommands,
+    TemplateCommands, TagCommands, AliasCommands, EmbeddingCommands, AgentCommands, DevCommands, ConstraintCommands,
+    RetentionCommands, AtCommands,
+};
+
+fn print_reaction_counts(counts: &ReactionCounts) {
+    println!(
+        "useful: {}  outdated: {}  disputed: {}  (trust {:.2}, freshness {:.2})",
+        counts.useful,
+        counts.outdated,
+        counts.disputed,
+        counts.trust(),
+        counts.freshness()
+    );
+}
+
+/// Render a byte count with a binary unit
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Parse a duration like "30s", "15m", "2h", "7d" or "1w"
+fn parse_duration(s: &str) -> Result<chrono::Duration> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: i64 = value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {}", s))?;
+    match unit {
+        "s" | "" => Ok(chrono::Duration::seconds(value)),
+        "m" => Ok(chrono::Duration::minutes(value)),
+        "h" => Ok(chrono::Duration::hours(value)),
+        "d" => Ok(chrono::Duration::days(value)),
+        "w" => Ok(chrono::Duration::weeks(value)),
+        _ => anyhow::bail!("Invalid duration unit in {}", s),
+    }
+}
+
+/// `--sort` and `--desc` as a sort order, if a key was given
+fn parse_sort(key: Option<&str>, descending: bool) -> Result<Option<Sort>> {
+    key.map(|key| {
+        let key: SortKey = key.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+        Ok(Sort::new(key, descending))
+    })
+    .transpose()
+}
+
+/// Parse an RFC 3339 time, or an age like "2h" counted back from now
+fn parse_time(s: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    match chrono::DateTime::parse_from_rfc3339(s.trim()) {
+        Ok(t) => Ok(t.with_timezone(&chrono::Utc)),
+        Err(_) => parse_duration(s)
+            .map(|d| chrono::Utc::now() - d)
+            .map_err(|_| anyhow::anyhow!("Invalid time (expected RFC 3339 or an age like 2h): {}", s)),
+    }
+}
+
+/// Parse `key=value` fields; values parse as JSON, else as text
+fn parse_fields(fields: &[String]) -> Result<Metadata> {
+    fields
+        .iter()
+        .map(|field| {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected key=value, got {}", field))?;
+            let value = serde_json::from_str(value).unwrap_or_else(|_| serde_json::json!(value));
+            Ok((key.to_string(), value))
+        })
+        .collect()
+}
+
+/// Parse a size like "512", "64KiB", "4MiB" or "1GB"
+fn parse_size(s: &str) -> Result<usize> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let value: usize = value.parse().map_err(|_| anyhow::anyhow!("Invalid size: {}", s))?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => anyhow::bail!("Invalid size unit in {}", s),
+    };
+    value
+        .checked_mul(scale)
+        .ok_or_else(|| anyhow::anyhow!("Size too large: {}", s))
+}
 
 fn expand_path(path: &str) -> String {
     if path.starts_with("~/") {
@@ -24,29 +153,148 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     let db_path = expand_path(&cli.db_path);
 
+    // Reading a running server's logs must not wait on its database lock
+    if let Commands::Serve { command: ServeCommands::Logs { command } } = &cli.command {
+        return handle_serve_logs_command(command, &db_path).await;
+    }
+
+    // Watching a server must not contend for the database it has open
+    if let Commands::Watch { url, filters, filter_file, interval, headers, token } = &cli.command {
+        return watch_server(url, filters, filter_file.as_deref(), interval, headers, token.as_deref(), cli.namespace.as_deref())
+            .await;
+    }
+
     // Ensure parent directory exists
-    if let Some(parent) = std::path::Path::new(&db_path).parent() {
-        std::fs::create_dir_all(parent)?;
+    if !cli.read_only {
+        if let Some(parent) = std::path::Path::new(&db_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
     }
 
-    let store = Arc::new(SledStore::open(&db_path)?);
+    let compression_threshold = match cli.compression_threshold {
+        0 => None,
+        n => Some(n),
+    };
+    let store = match (cli.read_only, cli.wait) {
+        (true, false) => SledStore::open_read_only(&db_path)?,
+        (true, true) => {
+            if !std::path::Path::new(&db_path).exists() {
+                anyhow::bail!("No database at {}", db_path);
+            }
+            SledStore::open_wait(&db_path)?.read_only()
+        }
+        (false, false) => SledStore::open(&db_path)?,
+        (false, true) => SledStore::open_wait(&db_path)?,
+    };
+    let mut store = store.with_compression_threshold(compression_threshold).with_strict(cli.strict);
+    // Writes would mix layouts, so the database is only read until
+    // `db migrate` has brought it up to date
+    if !matches!(cli.command, Commands::Db { command: DbCommands::Migrate { .. } })
+        && !store.pending_migrations()?.is_empty()
+    {
+        eprintln!(
+            "warning: database schema is at version {}; opening it read-only until `state-cli db migrate` upgrades it",
+            store.schema_version()?
+        );
+        store = store.read_only();
+    }
+    // Writes keep the full-text index current; it covers the default
+    // namespace, so register before switching views
+    let index_sync = match store.is_read_only() {
+        true => None,
+        false => match FullTextIndex::open(fulltext_index_path(&db_path)) {
+            Ok(index) => {
+                let sync = IndexSync::new(index);
+                sync.register(&store);
+                Some(sync)
+            }
+            Err(e) => {
+                eprintln!("warning: full-text index unavailable, run `search reindex` later: {}", e);
+                None
+            }
+        },
+    };
+    if let Some(namespace) = cli.namespace {
+        store = store.with_namespace(namespace)?;
+    }
+    let store = Arc::new(store);
+    let workspace = match cli.command {
+        Commands::Node { .. } | Commands::Search { .. } | Commands::Ingest { .. } if !cli.no_workspace => {
+            Workspace::detect(&std::env::current_dir()?)?
+        }
+        _ => None,
+    };
 
     match cli.command {
-        Commands::Node { command } => handle_node_command(command, &store)?,
+        Commands::Node { command } => handle_node_command(command, &store, workspace.as_ref())?,
         Commands::Edge { command } => handle_edge_command(command, &store)?,
-        Commands::Search { query, kinds } => {
-            let kind_list: Option<Vec<NodeKind>> = kinds.map(|k| {
-                k.split(',')
-                    .filter_map(|s| s.trim().parse().ok())
-                    .collect()
-            });
-            let results = store.search(&query, kind_list)?;
-            for node in results {
-                println!("{}", serde_json::to_string_pretty(&node)?);
+        Commands::Graph { command } => handle_graph_command(command, &store)?,
+        Commands::Kind { command } => handle_kind_command(command, &store)?,
+        Commands::Embeddings { command } => handle_embedding_command(command, &store)?,
+        Commands::Agent { command } => handle_agent_command(command, &store)?,
+        Commands::Dev { command } => handle_dev_command(command, &store)?,
+        Commands::Search { command, query, kinds } => {
+            let command = match (command, query) {
+                (Some(command), _) => command,
+                (None, Some(query)) => SearchCommands::Fulltext {
+                    query,
+                    kinds,
+                    limit: 20,
+                    expand_context: 0,
+                    include_archived: false,
+                    federated: false,
+                    resolve_superseded: false,
+                },
+                (None, None) => anyhow::bail!("Give a query or a search subcommand; see `search --help`"),
+            };
+            handle_search_command(command, &store, workspace.as_ref(), &db_path)?
+        }
+        #[cfg(feature = "ask")]
+        Commands::Ask { question, top_k, model_command, json } => {
+            use elegant_state::ask::{AnswerModel, Asker, CommandModel, ExtractiveModel};
+
+            let model: Box<dyn AnswerModel> = match model_command {
+                Some(command) => Box::new(CommandModel::from_command_line(&command)?),
+                None => Box::new(ExtractiveModel),
+            };
+            let answer = Asker::new(store.as_ref(), model.as_ref())
+                .with_top_k(top_k)
+                .ask(&question, AgentId::User)?;
+
+            if json {
+                let output = serde_json::json!({
+                    "answer": answer.text,
+                    "citations": answer.citations.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+                    "node": answer.node.id.to_string(),
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            } else {
+                println!("{}", answer.text);
+                println!();
+                println!("Sources:");
+                for id in &answer.citations {
+                    println!("  {}", id);
+                }
+                println!("Stored as: {}", answer.node.id);
+            }
+        }
+        Commands::Render { id, format, output } => {
+            let node_id = store.resolve_node(&id)?;
+            let node = store
+                .get_node(node_id)?
+                .ok_or_else(|| anyhow::anyhow!("Node not found: {}", id))?;
+            let rendered = Renderer::new().render(&node, format.into())?;
+            match output {
+                Some(path) => std::fs::write(path, rendered.output)?,
+                None => print!("{}", rendered.output),
             }
         }
-        Commands::Events { limit, agent: _ } => {
-            let events = store.get_events(None, limit)?;
+        Commands::Events { command: Some(command), .. } => handle_event_command(command, &store)?,
+        Commands::Events { limit, agent: _, sort, desc, command: None } => {
+            let events = match parse_sort(sort.as_deref(), desc)? {
+                Some(sort) => store.list_events_sorted(sort, limit)?,
+                None => store.get_events(None, limit)?,
+            };
             for event in events {
                 println!(
                     "[{}] {} {:?} by {}",
@@ -57,34 +305,147 @@ async fn main() -> Result<()> {
                 );
             }
         }
-        Commands::Export { format: _ } => {
-            let nodes = store.list_nodes(None, usize::MAX)?;
-            let export = serde_json::json!({
-                "version": "0.1.0",
-                "nodes": nodes,
-            });
-            println!("{}", serde_json::to_string_pretty(&export)?);
+        Commands::Undo { agent, json } => {
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            print_reversal(store.undo_last(&agent)?, "undo", json)?;
+        }
+        Commands::Redo { agent, json } => {
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            print_reversal(store.redo_last(&agent)?, "redo", json)?;
+        }
+        Commands::Export { format, root, depth, kinds } => print_export(&store, &store, &format, root, depth, &kinds)?,
+        Commands::Import { file, dedupe, batch_size, source, quiet } => {
+            let mut options = ImportOptions::default()
+                .with_batch_size(batch_size)
+                .with_dedupe(dedupe.unwrap_or_default());
+            if let Some(source) = source {
+                options = options.with_source(source);
+            }
+            let (reader, total): (Box<dyn std::io::Read>, Option<u64>) = if file == "-" {
+                (Box::new(std::io::stdin().lock()), None)
+            } else {
+                let input = std::fs::File::open(&file)?;
+                let total = input.metadata().ok().map(|m| m.len());
+                (Box::new(input), total)
+            };
+            let reader = std::io::BufReader::with_capacity(1 << 20, reader);
+
+            let totals = import_nodes(&store, reader, &options, |progress| {
+                if quiet {
+                    return;
+                }
+                match total {
+                    Some(total) if total > 0 => eprint!(
+                        "\rImported {} node(s), {} / {} ({:.0}%)",
+                        progress.nodes,
+                        format_bytes(progress.bytes),
+                        format_bytes(total),
+                        progress.bytes as f64 * 100.0 / total as f64
+                    ),
+                    _ => eprint!("\rImported {} node(s), {}", progress.nodes, format_bytes(progress.bytes)),
+                }
+            })?;
+            if !quiet && totals.nodes > 0 {
+                eprintln!();
+            }
+            println!("Imported {} nodes", totals.created + totals.linked);
+            if totals.updated > 0 {
+                println!("Updated {} node(s) from an earlier import", totals.updated);
+            }
+            if totals.reused > 0 {
+                println!("Skipped {} duplicate(s)", totals.reused);
+            }
+        }
+        Commands::Serve { command } => handle_serve_command(command, store, index_sync.clone(), &db_path).await?,
+        Commands::Graphql { command } => handle_graphql_command(command, store).await?,
+        Commands::Index { command } => handle_index_command(command, &store)?,
+        Commands::Connector { command } => handle_connector_command(command, &store)?,
+        Commands::Share { command } => handle_share_command(command, &store)?,
+        Commands::Watch { .. } => unreachable!("handled before opening the database"),
+        Commands::Hook { command } => {
+            let repo = std::env::current_dir()?;
+            match command {
+                HookCommands::Install { force } => {
+                    let hook = git::install_hook(&repo, Some(&db_path), force)?;
+                    println!("Installed {}", hook.display());
+                }
+                HookCommands::Uninstall => {
+                    if git::uninstall_hook(&repo)? {
+                        println!("Removed post-commit hook");
+                    } else {
+                        println!("No post-commit hook installed by state-cli");
+                    }
+                }
+            }
         }
-        Commands::Import { file } => {
-            let content = std::fs::read_to_string(&file)?;
-            let import: serde_json::Value = serde_json::from_str(&content)?;
-            if let Some(nodes) = import.get("nodes").and_then(|n| n.as_array()) {
-                for node_value in nodes {
-                    let node: StateNode = serde_json::from_value(node_value.clone())?;
-                    store.create_node(node, AgentId::System)?;
+        Commands::Ingest { command: IngestCommands::Commit { rev, quiet } } => {
+            let commit = git::read_commit(&std::env::current_dir()?, &rev)?;
+            let seen = store.find_by_metadata(
+                git::COMMIT_KEY,
+                &MetaQuery::Equals(commit.hash.clone()),
+                Some(vec![NodeKind::Context]),
+            )?;
+            if let Some(existing) = seen.first() {
+                if !quiet {
+                    println!("Already ingested as {}", existing.id);
                 }
-                println!("Imported {} nodes", nodes.len());
+                return Ok(());
+            }
+            let agent = AgentId::Module("git".into());
+            let node = store.create_node(commit.to_node(), agent.clone())?;
+            if let Some(ws) = &workspace {
+                let project = ws.project(&store, agent.clone())?;
+                workspace::attach(&store, node.id, project.id, agent)?;
+            }
+            if !quiet {
+                println!(
+                    "Ingested {} ({} file(s), +{} -{}) as {}",
+                    commit.hash.get(..7).unwrap_or(&commit.hash),
+                    commit.files.len(),
+                    commit.insertions(),
+                    commit.deletions(),
+                    node.id
+                );
             }
         }
-        Commands::Serve { command } => handle_serve_command(command, store).await?,
+        Commands::Db { command } => handle_db_command(command, &store, &db_path)?,
+        Commands::Report { command } => handle_report_command(command, &store, &db_path)?,
+        Commands::Proposal { command } => handle_proposal_command(command, &store)?,
+        Commands::Vote { command } => handle_vote_command(command, &store)?,
+        Commands::Coordinator { command } => handle_coordinator_command(command)?,
     }
 
+    // Under --strict a change the full-text index missed fails the command
+    if let Some(sync) = index_sync {
+        match sync.commit() {
+            Err(e) if cli.strict => return Err(e.into()),
+            Err(e) => eprintln!("warning: full-text index changes lost: {}", e),
+            Ok(()) => {}
+        }
+    }
     Ok(())
 }
 
-fn handle_node_command(command: NodeCommands, store: &Arc<SledStore>) -> Result<()> {
+/// Nodes belonging to the workspace's project, creating the project unless
+/// read-only; `None` outside a workspace
+fn workspace_scope(store: &SledStore, workspace: Option<&Workspace>) -> Result<Option<HashSet<NodeId>>> {
+    let Some(workspace) = workspace else {
+        return Ok(None);
+    };
+    let project = if store.is_read_only() {
+        workspace.find_project(store)?
+    } else {
+        Some(workspace.project(store, AgentId::User)?)
+    };
+    Ok(Some(match project {
+        Some(project) => workspace::project_scope(store, project.id)?,
+        None => HashSet::new(),
+    }))
+}
+
+fn handle_node_command(command: NodeCommands, store: &Arc<SledStore>, workspace: Option<&Workspace>) -> Result<()> {
     match command {
-        NodeCommands::Create { kind, content, metadata } => {
+        NodeCommands::Create { kind, content, metadata, ttl, tags, pin, retention } => {
             let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
             let content: serde_json::Value = serde_json::from_str(&content)?;
             let mut node = StateNode::new(kind, content);
@@ -92,33 +453,212 @@ fn handle_node_command(command: NodeCommands, store: &Arc<SledStore>) -> Result<
                 let meta_map = serde_json::from_str(&meta)?;
                 node = node.with_metadata(meta_map);
             }
+            if let Some(ttl) = ttl {
+                node = node.with_ttl(parse_duration(&ttl)?);
+            }
+            node = node.with_tags(tags).with_pinned(pin);
+            if let Some(retention) = retention {
+                node = node.with_retention(retention.parse().map_err(|e: String| anyhow::anyhow!(e))?);
+            }
+            let project = workspace.map(|ws| ws.project(store, AgentId::User)).transpose()?;
             let created = store.create_node(node, AgentId::User)?;
             println!("Created node: {}", created.id);
+            if let (Some(project), Some(ws)) = (project, workspace) {
+                workspace::attach(store, created.id, project.id, AgentId::User)?;
+                println!("Part of project: {} ({})", ws.name, project.id);
+            }
             println!("{}", serde_json::to_string_pretty(&created)?);
         }
-        NodeCommands::Get { id } => {
-            let node_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
-            match store.get_node(node_id)? {
+        // clap refuses --resolve-superseded together with --as-of
+        NodeCommands::Get { id, include_archived: _, as_of: Some(at), edges, resolve_superseded: _ } => {
+            let node_id = store.resolve_node(&id)?;
+            let at = parse_time(&at)?;
+            match store.node_as_of(node_id, at)? {
+                Some(node) if edges => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "node": node,
+                        "edges": store.edges_as_of(node_id, at)?,
+                    }))?
+                ),
+                Some(node) => println!("{}", serde_json::to_string_pretty(&node)?),
+                None => println!("Node did not exist at {}", at.to_rfc3339()),
+            }
+        }
+        NodeCommands::Get { id, include_archived, resolve_superseded, .. } => {
+            let node_id = store.resolve_node(&id)?;
+            let hot = if resolve_superseded { store.latest_version(node_id)? } else { store.get_node(node_id)? };
+            if let Some(node) = hot.as_ref().filter(|node| node.id != node_id) {
+                eprintln!("{} is superseded by {}", node_id, node.id);
+            }
+            let node = match hot {
+                None if include_archived => store.get_archived(node_id)?,
+                node => node,
+            };
+            match node {
                 Some(node) => println!("{}", serde_json::to_string_pretty(&node)?),
                 None => println!("Node not found"),
             }
         }
-        NodeCommands::List { kind, limit } => {
+        NodeCommands::Versions { id, json } => {
+            let node_id = store.resolve_node(&id)?;
+            let versions = store.node_versions(node_id)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&versions)?);
+                return Ok(());
+            }
+            for version in versions {
+                let mut preview = version.node.content.to_string();
+                if preview.chars().count() > 60 {
+                    preview = preview.chars().take(57).collect::<String>() + "...";
+                }
+                let operation = format!("{:?}", version.operation);
+                println!(
+                    "v{:<4} {}  {:<8} {:<12} {}",
+                    version.node.version,
+                    version.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    operation,
+                    version.agent,
+                    preview
+                );
+            }
+        }
+        NodeCommands::At { id, version } => {
+            let node_id = store.resolve_node(&id)?;
+            let found = store
+                .node_versions(node_id)?
+                .into_iter()
+                .find(|v| v.node.version == version)
+                .ok_or_else(|| anyhow::anyhow!("Node {} has no version {}", id, version))?;
+            println!("{}", serde_json::to_string_pretty(&found.node)?);
+        }
+        NodeCommands::List { kind, limit, tag, pinned, sort, desc, include_archived } => {
             let kind: Option<NodeKind> = kind
                 .map(|k| k.parse().map_err(|e: String| anyhow::anyhow!(e)))
                 .transpose()?;
-            let nodes = store.list_nodes(kind, limit)?;
+            let scope = workspace_scope(store, workspace)?;
+            let in_scope = |node: &StateNode| scope.as_ref().map_or(true, |s| s.contains(&node.id));
+            let selected = |node: &StateNode| {
+                tag.as_ref().map_or(true, |tag| node.has_tag(tag)) && (!pinned || node.pinned)
+            };
+            let fetch = if scope.is_some() { usize::MAX } else { limit };
+            let nodes = match (&tag, parse_sort(sort.as_deref(), desc)?) {
+                (_, None) if pinned => store.pinned_nodes(kind.as_ref(), usize::MAX)?,
+                (Some(tag), None) => store.nodes_by_tag(tag, kind.as_ref(), fetch)?,
+                (None, None) => store.list_nodes(kind.clone(), fetch)?,
+                // Only one index serves a listing; further filters need all of it
+                (Some(_), Some(sort)) => store.list_nodes_sorted(kind.as_ref(), sort, usize::MAX)?,
+                (None, Some(sort)) if pinned => store.list_nodes_sorted(kind.as_ref(), sort, usize::MAX)?,
+                (None, Some(sort)) => store.list_nodes_sorted(kind.as_ref(), sort, fetch)?,
+            };
+            let nodes: Vec<StateNode> = nodes.into_iter().filter(selected).filter(in_scope).take(limit).collect();
+            // The archive has no tag or pin index; archived nodes are filtered from a full listing
+            let archived: Vec<StateNode> = if include_archived {
+                let fetch = if tag.is_some() || pinned { usize::MAX } else { fetch.saturating_sub(nodes.len()) };
+                store
+                    .list_archived(kind, fetch)?
+                    .into_iter()
+                    .filter(selected)
+                    .filter(in_scope)
+                    .take(limit - nodes.len())
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            if let Some(ws) = workspace {
+                eprintln!("Scoped to project {} (--no-workspace for all nodes)", ws.name);
+            }
             for node in nodes {
                 println!("{} [{}] {:?}", node.id, node.kind, node.content);
             }
+            for node in archived {
+                println!("{} [{}] (archived) {:?}", node.id, node.kind, node.content);
+            }
+        }
+        NodeCommands::Archive { ids, older_than, kind, query, dry_run } => {
+            let ids: Vec<NodeId> = if ids.is_empty() {
+                let Some(older_than) = older_than else {
+                    anyhow::bail!("Give node IDs or --older-than");
+                };
+                let kind: Option<NodeKind> = kind
+                    .map(|k| k.parse().map_err(|e: String| anyhow::anyhow!(e)))
+                    .transpose()?;
+                let cutoff = chrono::Utc::now() - parse_duration(&older_than)?;
+                store.archive_candidates(cutoff, kind, query.as_deref())?
+            } else {
+                ids.iter()
+                    .map(|id| store.resolve_node(id).map_err(anyhow::Error::from))
+                    .collect::<Result<_>>()?
+            };
+
+            if dry_run {
+                for id in &ids {
+                    println!("{}", id);
+                }
+                println!("{} node(s) would be archived", ids.len());
+            } else {
+                let archived = store.archive_nodes(&ids)?;
+                println!("Archived {} node(s)", archived.len());
+            }
+        }
+        NodeCommands::Unarchive { id } => {
+            let node_id = store.resolve_node(&id)?;
+            store.unarchive_node(node_id)?;
+            println!("Restored node: {}", id);
         }
-        NodeCommands::Update { id, content } => {
-            let node_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+        NodeCommands::Update { id, content, expected_version } => {
+            let node_id = store.resolve_node(&id)?;
             let content: serde_json::Value = serde_json::from_str(&content)?;
-            let updated = store.update_node(node_id, content, AgentId::User)?;
-            println!("Updated node: {}", updated.id);
+            let updated = store.update_node(node_id, content, expected_version, AgentId::User)?;
+            println!("Updated node: {} (version {})", updated.id, updated.version);
         }
-        NodeCommands::Delete { id, force } => {
+        NodeCommands::Replace { old, new } => {
+            let old_id = store.resolve_node(&old)?;
+            let new_id = store.resolve_node(&new)?;
+            let moved = store.replace_node(old_id, new_id, AgentId::User)?;
+            println!("Moved {} edge(s) from {} to {}", moved.len(), old, new);
+        }
+        NodeCommands::Explain { id, json } => {
+            let node_id = store.resolve_node(&id)?;
+            let explanation = elegant_state::explain::explain(store, node_id)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&explanation)?);
+            } else {
+                print!("{}", explanation.to_markdown());
+            }
+        }
+        NodeCommands::Merge { winner, losers, strategy } => {
+            let winner_id = store.resolve_node(&winner)?;
+            let loser_ids = losers
+                .iter()
+                .map(|id| store.resolve_node(id).map_err(anyhow::Error::from))
+                .collect::<Result<Vec<NodeId>>>()?;
+            let (node, edges) = store.merge_nodes(winner_id, &loser_ids, strategy, AgentId::User)?;
+            println!(
+                "Merged {} node(s) into {} (version {}), which gained {} edge(s)",
+                loser_ids.len(),
+                winner,
+                node.version,
+                edges.len()
+            );
+        }
+        NodeCommands::Reparent { id, parent } => {
+            let node_id = store.resolve_node(&id)?;
+            let parent_id = store.resolve_node(&parent)?;
+            let edge = store.reparent(node_id, parent_id, AgentId::User)?;
+            println!("{} is now part of {} (edge {})", id, parent, edge.id);
+        }
+        NodeCommands::Split { id, parts } => {
+            let node_id = store.resolve_node(&id)?;
+            let parts = parts
+                .iter()
+                .map(|part| serde_json::from_str(part))
+                .collect::<std::result::Result<Vec<serde_json::Value>, _>>()?;
+            for part in store.split_node(node_id, parts, AgentId::User)? {
+                println!("Created part: {}", part.id);
+            }
+        }
+        NodeCommands::Delete { id, force, restrict } => {
             if !force {
                 print!("Are you sure you want to delete node {}? [y/N] ", id);
                 std::io::Write::flush(&mut std::io::stdout())?;
@@ -129,75 +669,3374 @@ fn handle_node_command(command: NodeCommands, store: &Arc<SledStore>) -> Result<
                     return Ok(());
                 }
             }
-            let node_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
-            store.delete_node(node_id, AgentId::User)?;
+            let node_id = store.resolve_node(&id)?;
+            let mode = if restrict { DeleteMode::Restrict } else { DeleteMode::Cascade };
+            store.delete_node_with(node_id, AgentId::User, mode)?;
             println!("Deleted node: {}", id);
         }
+        NodeCommands::Comment { id, text, anchor, author } => {
+            let node_id = store.resolve_node(&id)?;
+            let author: AgentId = author.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut annotation = Annotation::new(node_id, author, text);
+            if let Some(anchor) = anchor {
+                let anchor: AnnotationAnchor =
+                    anchor.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                annotation = annotation.with_anchor(anchor);
+            }
+            let created = store.add_annotation(annotation)?;
+            println!("Added comment: {}", created.id);
+        }
+        NodeCommands::Attach { id, file, mime } => {
+            let node_id = store.resolve_node(&id)?;
+            let path = std::path::Path::new(&file);
+            let bytes = std::fs::read(path)?;
+            let mime = mime.unwrap_or_else(|| guess_mime(path).to_string());
+            let attachment = store.put_attachment(node_id, &bytes, &mime)?;
+            println!(
+                "Attached {} ({}, {})",
+                attachment.hash,
+                attachment.mime,
+                format_bytes(attachment.size)
+            );
+        }
+        NodeCommands::Attachments { id, hash: Some(hash), output: Some(output) } => {
+            let node_id = store.resolve_node(&id)?;
+            let (_, bytes) = store
+                .get_attachment(node_id, &hash)?
+                .ok_or_else(|| anyhow::anyhow!("No attachment {} on {}", hash, id))?;
+            std::fs::write(&output, bytes)?;
+            println!("Wrote {}", output);
+        }
+        NodeCommands::Attachments { id, .. } => {
+            let node_id = store.resolve_node(&id)?;
+            for attachment in store.attachments(node_id)? {
+                println!(
+                    "{}  {:<24} {:>10}  {}",
+                    attachment.hash,
+                    attachment.mime,
+                    format_bytes(attachment.size),
+                    attachment.created_at.format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        NodeCommands::Detach { id, hash } => {
+            let node_id = store.resolve_node(&id)?;
+            store.remove_attachment(node_id, &hash)?;
+            println!("Detached {}", hash);
+        }
+        NodeCommands::Comments { id } => {
+            let node_id = store.resolve_node(&id)?;
+            for annotation in store.annotations(node_id)? {
+                let anchor = annotation
+                    .anchor
+                    .map(|a| format!(" @ {}", a))
+                    .unwrap_or_default();
+                println!(
+                    "[{}] {}{}: {}",
+                    annotation.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    annotation.author,
+                    anchor,
+                    annotation.text
+                );
+            }
+        }
+        NodeCommands::Ingest { file, kind, chunk, dedupe } => {
+            let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let raw = std::fs::read_to_string(&file)?;
+            let markdown = match detect_format(&file) {
+                InputFormat::Markdown => raw,
+                format => PandocConverter::new().to_markdown(&raw, format)?,
+            };
+            let mut node = StateNode::new(
+                kind,
+                serde_json::json!({ "text": markdown, "source": file }),
+            );
+            node.metadata
+                .insert(CONTENT_TYPE_KEY.to_string(), serde_json::json!("text/markdown"));
+            let original = match dedupe.unwrap_or_default() {
+                DedupeMode::Off => None,
+                _ => store.find_duplicate(&node)?,
+            };
+            if let (Some(DedupeMode::Reuse), Some(original)) = (dedupe, &original) {
+                println!("Already ingested: {}", original.id);
+                return Ok(());
+            }
+            let ingested = xref::ingest_markdown(store.as_ref(), node, AgentId::User)?;
+            println!("Ingested: {}", ingested.node.id);
+            if let Some(original) = original {
+                let edge = StateEdge::new(ingested.node.id, original.id, EdgeKind::DerivedFrom);
+                store.create_edge(edge, AgentId::User)?;
+                println!("  derived from duplicate {}", original.id);
+            }
+            println!("  {} reference edge(s)", ingested.references.len());
+            if chunk {
+                let chunks = chunks::chunk_document(store.as_ref(), &ingested.node, AgentId::User)?;
+                println!("  {} chunk(s)", chunks.len());
+            }
+            for target in &ingested.unresolved {
+                println!("  unresolved: {}", target);
+            }
+        }
+        NodeCommands::Section { id, anchor } => {
+            let node_id = store.resolve_node(&id)?;
+            let node = store
+                .get_node(node_id)?
+                .ok_or_else(|| anyhow::anyhow!("Node not found: {}", id))?;
+            let text = node.content.get("text").and_then(|t| t.as_str()).unwrap_or_default();
+            match anchor {
+                Some(anchor) => {
+                    let section = xref::section(text, &anchor)
+                        .ok_or_else(|| anyhow::anyhow!("No section: {}", anchor))?;
+                    println!("{}", section);
+                }
+                None => {
+                    for heading in xref::extract(text).headings {
+                        println!(
+                            "{}{} ({})",
+                            "  ".repeat(heading.level as usize - 1),
+                            heading.title,
+                            heading.anchor
+                        );
+                    }
+                }
+            }
+        }
+        NodeCommands::React { id, kind, agent, remove } => {
+            let node_id = store.resolve_node(&id)?;
+            let kind: ReactionKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let counts = if remove {
+                store.unreact(node_id, &agent, kind)?
+            } else {
+                store.react(Reaction::new(node_id, agent, kind))?
+            };
+            print_reaction_counts(&counts);
+        }
+        NodeCommands::Reactions { id, all } => {
+            let node_id = store.resolve_node(&id)?;
+            let reactions = store.reactions(node_id)?;
+            print_reaction_counts(&ReactionCounts::from_reactions(&reactions));
+            if all {
+                for reaction in reactions {
+                    println!(
+                        "[{}] {}: {}",
+                        reaction.created_at.format("%Y-%m-%d %H:%M:%S"),
+                        reaction.agent,
+                        reaction.kind
+                    );
+                }
+            }
+        }
+        NodeCommands::Template { command } => handle_template_command(command, store)?,
+        NodeCommands::Tag { command } => handle_tag_command(command, store)?,
+        NodeCommands::Alias { command } => handle_alias_command(command, store)?,
+        NodeCommands::Pin { id, agent } => {
+            let node_id = store.resolve_node(&id)?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            println!("Pinned {}", store.set_pinned(node_id, true, agent)?.id);
+        }
+        NodeCommands::Unpin { id, agent } => {
+            let node_id = store.resolve_node(&id)?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            println!("Unpinned {}", store.set_pinned(node_id, false, agent)?.id);
+        }
+        NodeCommands::Retention { id, class, agent } => {
+            let node_id = store.resolve_node(&id)?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let class: Option<RetentionClass> = match class.as_str() {
+                "inherit" => None,
+                class => Some(class.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+            };
+            let node = store.set_retention(node_id, class, agent)?;
+            let effective = store.retention_policy()?.class_for(&node);
+            match node.expires_at {
+                Some(at) => println!("{} is {} (expires {})", node.id, effective, at.to_rfc3339()),
+                None => println!("{} is {}", node.id, effective),
+            }
+        }
     }
     Ok(())
 }
 
-fn handle_edge_command(command: EdgeCommands, store: &Arc<SledStore>) -> Result<()> {
+fn handle_tag_command(command: TagCommands, store: &Arc<SledStore>) -> Result<()> {
     match command {
-        EdgeCommands::Create { from, to, kind, weight } => {
-            let from_id = from.parse().map_err(|e| anyhow::anyhow!("Invalid from ID: {}", e))?;
-            let to_id = to.parse().map_err(|e| anyhow::anyhow!("Invalid to ID: {}", e))?;
-            let kind: EdgeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
-            let mut edge = StateEdge::new(from_id, to_id, kind);
-            if let Some(w) = weight {
-                edge = edge.with_weight(w);
+        TagCommands::Add { id, tags, agent } => {
+            let node_id = store.resolve_node(&id)?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let node = store.update_tags(node_id, &tags, &[], agent)?;
+            println!("{} tags: {}", node.id, node.tags.join(", "));
+        }
+        TagCommands::Remove { id, tags, agent } => {
+            let node_id = store.resolve_node(&id)?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let node = store.update_tags(node_id, &[], &tags, agent)?;
+            println!("{} tags: {}", node.id, node.tags.join(", "));
+        }
+        TagCommands::List => {
+            for (tag, count) in store.tag_counts()? {
+                println!("{:>6}  {}", count, tag);
             }
-            let created = store.create_edge(edge, AgentId::User)?;
-            println!("Created edge: {}", created.id);
         }
-        EdgeCommands::From { id } => {
-            let node_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
-            let edges = store.edges_from(node_id)?;
-            for edge in edges {
-                println!("{} --[{}]--> {}", edge.from, edge.kind, edge.to);
+    }
+    Ok(())
+}
+
+fn handle_alias_command(command: AliasCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        AliasCommands::Set { id, alias } => {
+            let node_id = store.resolve_node(&id)?;
+            store.set_alias(node_id, &alias)?;
+            println!("{} is now {}", node_id, alias);
+        }
+        AliasCommands::Remove { id } => {
+            let node_id = store.resolve_node(&id)?;
+            match store.remove_alias(node_id)? {
+                Some(alias) => println!("Removed alias {} from {}", alias, node_id),
+                None => println!("{} has no alias", node_id),
             }
         }
-        EdgeCommands::To { id } => {
-            let node_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
-            let edges = store.edges_to(node_id)?;
-            for edge in edges {
-                println!("{} --[{}]--> {}", edge.from, edge.kind, edge.to);
+        AliasCommands::List { json } => {
+            let aliases = store.aliases()?;
+            if json {
+                let map: serde_json::Map<String, serde_json::Value> =
+                    aliases.into_iter().map(|(alias, id)| (alias, id.to_string().into())).collect();
+                println!("{}", serde_json::to_string_pretty(&map)?);
+            } else {
+                for (alias, id) in aliases {
+                    println!("{}  {}", id, alias);
+                }
             }
         }
-        EdgeCommands::Delete { id } => {
-            let edge_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
-            store.delete_edge(edge_id, AgentId::User)?;
-            println!("Deleted edge: {}", id);
+    }
+    Ok(())
+}
+
+fn handle_template_command(command: TemplateCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        TemplateCommands::Save { name, kind, content, metadata, edges, incoming, description } => {
+            let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut template =
+                NodeTemplate::new(kind, serde_json::from_str(&content)?).with_metadata(parse_fields(&metadata)?);
+            for (edge, incoming) in edges.iter().map(|e| (e, false)).chain(incoming.iter().map(|e| (e, true))) {
+                let (kind, node) = edge
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Expected kind=node, got {}", edge))?;
+                let kind: EdgeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                template.edges.push(TemplateEdge { kind, node: node.to_string(), incoming });
+            }
+            if let Some(description) = description {
+                template = template.with_description(description);
+            }
+            store.save_template(&name, &template)?;
+            println!("Saved template {}", name);
+        }
+        TemplateCommands::Apply { name, variables, agent } => {
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let variables = parse_fields(&variables)?.into_iter().collect();
+            let (node, edges) = store.apply_template(&name, &variables, agent)?;
+            println!("Created node: {}", node.id);
+            for edge in &edges {
+                println!("  {} -[{}]-> {}", edge.from, edge.kind, edge.to);
+            }
+            println!("{}", serde_json::to_string_pretty(&node)?);
+        }
+        TemplateCommands::List => {
+            for (name, template) in store.templates()? {
+                let variables: Vec<String> = template.variables().into_iter().collect();
+                println!(
+                    "{:<20} {:<14} {:<30} {}",
+                    name,
+                    template.kind,
+                    variables.join(", "),
+                    template.description.as_deref().unwrap_or("")
+                );
+            }
+        }
+        TemplateCommands::Show { name } => {
+            let template =
+                store.templates()?.remove(&name).ok_or_else(|| anyhow::anyhow!("No template named {}", name))?;
+            println!("{}", serde_json::to_string_pretty(&template)?);
+        }
+        TemplateCommands::Delete { name } => {
+            if !store.delete_template(&name)? {
+                anyhow::bail!("No template named {}", name);
+            }
+            println!("Deleted template {}", name);
         }
     }
     Ok(())
 }
 
-async fn handle_serve_command(command: ServeCommands, store: Arc<SledStore>) -> Result<()> {
+fn handle_snapshot_command(command: SnapshotCommands, store: &Arc<SledStore>, db_path: &str) -> Result<()> {
+    let snapshot_dir = |dir: Option<String>| {
+        dir.map(|d| expand_path(&d))
+            .unwrap_or_else(|| format!("{}.snapshots", db_path))
+    };
+
     match command {
-        ServeCommands::Http { port, host } => {
-            use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
-            use axum::{routing::post, Extension, Router};
+        SnapshotCommands::Create { name, dir } => {
+            let dir = snapshot_dir(dir);
+            let info = store.create_snapshot(std::path::Path::new(&dir), &name)?;
+            println!(
+                "Created snapshot {} ({} entries in {} trees)",
+                info.name, info.entries, info.trees
+            );
+        }
+        SnapshotCommands::List { dir } => {
+            let dir = snapshot_dir(dir);
+            for info in list_snapshots(std::path::Path::new(&dir))? {
+                println!(
+                    "{:<24} {}  {} entries",
+                    info.name,
+                    info.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    info.entries
+                );
+            }
+        }
+        SnapshotCommands::Restore { name, dir, force } => {
+            if !force {
+                anyhow::bail!(
+                    "Restoring replaces the current graph with snapshot {}; re-run with --force",
+                    name
+                );
+            }
+            let dir = snapshot_dir(dir);
+            let report = store.restore_snapshot(std::path::Path::new(&dir), &name)?;
+            println!(
+                "Restored snapshot {} from {}",
+                report.restored.name,
+                report.restored.created_at.format("%Y-%m-%d %H:%M:%S")
+            );
+            println!("The replaced graph is snapshot {}", report.previous.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Client for `--url`, with `--header` and `--token` applied
+///
+/// The global `--namespace` is forwarded to the server.
+fn graphql_client(
+    url: String,
+    headers: Vec<String>,
+    token: Option<String>,
+    store: &SledStore,
+) -> Result<GraphqlClient> {
+    let mut client = GraphqlClient::new(url);
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid header (expected \"Name: value\"): {}", header))?;
+        client = client.with_header(name.trim(), value.trim());
+    }
+    if let Some(token) = token {
+        client = client.with_bearer_token(token);
+    }
+    if let Some(namespace) = store.namespace() {
+        client = client.with_namespace(namespace);
+    }
+    Ok(client)
+}
 
+async fn handle_graphql_command(command: GraphqlCommands, store: Arc<SledStore>) -> Result<()> {
+    use elegant_state::graphql::diff::{diff_sdl, ChangeLevel};
+
+    match command {
+        GraphqlCommands::Schema { output, format, package, descriptions } => {
             let schema = build_schema(store);
+            let text = match format {
+                SchemaFormat::Sdl => schema.sdl(),
+                SchemaFormat::Json => serde_json::to_string_pretty(&introspection(&schema).await)?,
+                SchemaFormat::TypeScript => codegen::typescript(&introspection(&schema).await, descriptions),
+                SchemaFormat::Go => codegen::go(&introspection(&schema).await, &package, descriptions),
+            };
+            if output == "-" {
+                println!("{}", text);
+            } else {
+                std::fs::write(&output, text)?;
+                println!("Wrote {} schema to {}", format, output);
+            }
+        }
+        GraphqlCommands::Diff { against, breaking_only } => {
+            let old = std::fs::read_to_string(&against)?;
+            let new = build_schema(store).sdl();
+            let changes = diff_sdl(&old, &new).map_err(|e| anyhow::anyhow!(e))?;
 
-            async fn graphql_handler(
-                Extension(schema): Extension<elegant_state::StateSchema>,
-                req: GraphQLRequest,
-            ) -> GraphQLResponse {
-                schema.execute(req.into_inner()).await.into()
+            let mut breaking = 0;
+            for change in &changes {
+                if change.level == ChangeLevel::Breaking {
+                    breaking += 1;
+                } else if breaking_only {
+                    continue;
+                }
+                println!("{:<9} {}: {}", change.level, change.path, change.message);
+            }
+            if breaking > 0 {
+                anyhow::bail!("{} breaking change(s) against {}", breaking, against);
+            }
+            if changes.is_empty() {
+                println!("No changes");
+            }
+        }
+        GraphqlCommands::Query { query, variables, operation, pretty, url, headers, token } => {
+            let query = match query.strip_prefix('@') {
+                Some(file) => std::fs::read_to_string(file)?,
+                None => query,
+            };
+            let variables: Option<serde_json::Value> =
+                variables.map(|v| serde_json::from_str(&v)).transpose()?;
+            let response = match url {
+                Some(url) => graphql_client(url, headers, token, &store)?
+                    .execute(&query, variables, operation.as_deref())
+                    .map_err(|e| anyhow::anyhow!(e))?,
+                None => {
+                    let mut request = async_graphql::Request::new(query);
+                    if let Some(variables) = variables {
+                        request = request.variables(async_graphql::Variables::from_json(variables));
+                    }
+                    if let Some(operation) = operation {
+                        request = request.operation_name(operation);
+                    }
+                    serde_json::to_value(build_schema(store).execute(request).await)?
+                }
+            };
+            if pretty {
+                println!("{}", serde_json::to_string_pretty(&response)?);
+            } else {
+                println!("{}", response);
+            }
+            if let Some(errors) = response["errors"].as_array().filter(|e| !e.is_empty()) {
+                anyhow::bail!("Query returned {} error(s)", errors.len());
+            }
+        }
+        GraphqlCommands::Introspect { url, format, headers, token } => {
+            let schema = match url {
+                Some(url) => graphql_client(url, headers, token, &store)?
+                    .introspect()
+                    .map_err(|e| anyhow::anyhow!(e))?,
+                None if format == "sdl" => {
+                    println!("{}", build_schema(store).sdl());
+                    return Ok(());
+                }
+                None => introspection(&build_schema(store)).await,
+            };
+            match format.as_str() {
+                "sdl" => print!("{}", introspection_to_sdl(&schema)),
+                "json" => println!("{}", serde_json::to_string_pretty(&schema)?),
+                other => anyhow::bail!("Unknown format: {} (expected json or sdl)", other),
             }
+        }
+    }
 
-            let app = Router::new()
-                .route("/graphql", post(graphql_handler))
-                .layer(Extension(schema));
+    Ok(())
+}
 
-            let addr = format!("{}:{}", host, port);
-            println!("GraphQL server running at https://{}/graphql", addr);
+fn handle_search_command(
+    command: SearchCommands,
+    store: &Arc<SledStore>,
+    workspace: Option<&Workspace>,
+    db_path: &str,
+) -> Result<()> {
+    match command {
+        SearchCommands::Fulltext {
+            query, kinds, limit, expand_context, include_archived, federated, resolve_superseded,
+        } => {
+            let mut results = if federated {
+                let found = Federation::from_specs(&connector_specs(store)?)?
+                    .search(store.as_ref(), &query, kinds.clone(), limit)?;
+                for (name, error) in &found.errors {
+                    eprintln!("warning: connector {}: {}", name, error);
+                }
+                found.local.into_iter().chain(found.fetched).collect()
+            } else {
+                store.search(&query, kinds.clone())?
+            };
+            if include_archived {
+                results.extend(store.search_archived(&query, kinds)?);
+            }
+            // Federated hits come from other stores, so only local searches are scoped
+            if let Some(scope) = workspace_scope(store, workspace.filter(|_| !federated))? {
+                results.retain(|node| scope.contains(&node.id));
+            }
+            if resolve_superseded {
+                results = store.latest_versions(results)?;
+            }
+            for node in results.into_iter().take(limit) {
+                if expand_context == 0 {
+                    println!("{}", serde_json::to_string_pretty(&node)?);
+                    continue;
+                }
+                let context = chunks::expand_context(store.as_ref(), node, expand_context)?;
+                let output = serde_json::json!({
+                    "hit": context.hit.id.to_string(),
+                    "parent": context.parent.as_ref().map(|p| p.id.to_string()),
+                    "summary": context.summary,
+                    "passage": context.passage(),
+                    "chunks": context.chunks,
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+        }
+        SearchCommands::Expand { query, kinds, limit, max_queries, verbose } => {
+            use expand::QueryExpander;
 
-            let listener = tokio::net::TcpListener::bind(&addr).await?;
-            axum::serve(listener, app).await?;
+            let queries = expand::SynonymExpander::new()
+                .with_max_queries(max_queries)
+                .expand(&query);
+            if verbose {
+                println!("Subqueries:");
+                for q in &queries {
+                    println!("  {}", q);
+                }
+                println!();
+            }
+
+            for hit in expand::search_expanded(store.as_ref(), &queries, kinds, limit)? {
+                if verbose {
+                    let matched: Vec<String> = hit
+                        .matched_by
+                        .iter()
+                        .map(|(q, rank)| format!("\"{}\" #{}", q, rank))
+                        .collect();
+                    println!("[{:.4}] {} ({})", hit.score, hit.node.id, matched.join(", "));
+                }
+                println!("{}", serde_json::to_string_pretty(&hit.node)?);
+            }
+        }
+        SearchCommands::Meta { field, value, kinds } => {
+            let query = MetaQuery::parse(&value);
+            for node in store.find_by_metadata(&field, &query, kinds)? {
+                println!("{}", serde_json::to_string_pretty(&node)?);
+            }
+        }
+        SearchCommands::Related { id, direction, edge_kinds, depth, json } => {
+            let node_id = store.resolve_node(&id)?;
+            let reached = store.traverse(node_id, direction, edge_kinds.as_deref(), depth)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reached)?);
+                return Ok(());
+            }
+            for hit in reached {
+                println!("{} {} [{}] {:?}", hit.depth, hit.node.id, hit.node.kind, hit.node.content);
+                let mut trail = id.clone();
+                for step in &hit.path {
+                    if step.forward {
+                        trail.push_str(&format!(" --[{}]--> {}", step.edge.kind, step.edge.to));
+                    } else {
+                        trail.push_str(&format!(" <--[{}]-- {}", step.edge.kind, step.edge.from));
+                    }
+                }
+                println!("    {}", trail);
+            }
+        }
+        SearchCommands::Bench { queries, backends, baseline, runs, max_errors, verbose, json } => {
+            use elegant_state::store::bench::{self, BenchOptions};
+
+            let queries = bench::parse_queries(&std::fs::read_to_string(&queries)?);
+            let mut options = BenchOptions::default()
+                .with_baseline(baseline)
+                .with_runs(runs)
+                .with_max_errors(max_errors);
+            if !backends.is_empty() {
+                options = options.with_backends(backends);
+            }
+            let reports = bench::bench(store, &queries, &options)?;
+            let summary = bench::summarize(&reports);
+            if json {
+                let output = serde_json::json!({"queries": reports, "summary": summary});
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                return Ok(());
+            }
+            if verbose {
+                for report in &reports {
+                    println!("[{}] {}", report.class, report.query);
+                    for run in &report.runs {
+                        println!(
+                            "  {:<9} {:>9.2}ms {:>6} hit(s)  overlap {:.2}",
+                            run.backend, run.latency_ms, run.hits, run.overlap
+                        );
+                    }
+                }
+                println!();
+            }
+            println!("{:<12} {:<9} {:>7} {:>11} {:>9} {:>8}", "class", "backend", "queries", "latency", "hits", "overlap");
+            for row in &summary {
+                println!(
+                    "{:<12} {:<9} {:>7} {:>9.2}ms {:>9.1} {:>8.2}",
+                    row.class, row.backend, row.queries, row.mean_latency_ms, row.mean_hits, row.mean_overlap
+                );
+            }
+            println!("Overlap is against {}", baseline);
+        }
+        SearchCommands::Index { command } => match command {
+            SearchIndexCommands::Export { output, dir } => {
+                let dir = dir.map_or_else(|| fulltext_index_path(db_path), std::path::PathBuf::from);
+                let header = elegant_state::store::export_index(&dir, std::path::Path::new(&output), store.revision()?)?;
+                println!(
+                    "Exported {} file(s), {} bytes, at revision {} to {}",
+                    header.files, header.bytes, header.revision, output
+                );
+            }
+            SearchIndexCommands::Import { archive, dir, force } => {
+                let dir = dir.map_or_else(|| fulltext_index_path(db_path), std::path::PathBuf::from);
+                let revision = store.revision()?;
+                let expected = (!force).then_some(&revision);
+                let header = elegant_state::store::import_index(std::path::Path::new(&archive), &dir, expected)?;
+                println!("Imported {} file(s) into {}", header.files, dir.display());
+                if header.revision != revision {
+                    println!("Archive revision {} differs from the store's {}; reindex to catch up", header.revision, revision);
+                }
+            }
+        },
+        SearchCommands::Reindex { kinds, progress } => {
+            if let Some(namespace) = store.namespace() {
+                anyhow::bail!("The full-text index covers the default namespace, not {}", namespace);
+            }
+            let index = FullTextIndex::open(fulltext_index_path(db_path))?;
+            let indexed = index.reindex(store, kinds.as_deref(), |indexed| {
+                if progress && indexed % 1000 == 0 {
+                    eprint!("\rIndexed {} node(s)", indexed);
+                }
+            })?;
+            if progress {
+                eprintln!();
+            }
+            println!("Indexed {} node(s)", indexed);
         }
     }
+
     Ok(())
 }
+
+fn handle_edge_command(command: EdgeCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        EdgeCommands::Create { from, to, kind, weight, metadata } => {
+            let from_id = store.resolve_node(&from)?;
+            let to_id = store.resolve_node(&to)?;
+            let kind: EdgeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut edge = StateEdge::new(from_id, to_id, kind).with_metadata(parse_fields(&metadata)?);
+            if let Some(w) = weight {
+                edge = edge.with_weight(w);
+            }
+            let created = store.create_edge(edge, AgentId::User)?;
+            println!("Created edge: {}", created.id);
+        }
+        EdgeCommands::Update { id, weight, metadata, unset } => {
+            let edge_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let mut fields = parse_fields(&metadata)?;
+            fields.extend(unset.into_iter().map(|field| (field, serde_json::Value::Null)));
+            if weight.is_none() && fields.is_empty() {
+                anyhow::bail!("Nothing to update; give --weight, --meta or --unset");
+            }
+            let updated = store.update_edge(edge_id, weight, fields, AgentId::User)?;
+            println!("{}", serde_json::to_string_pretty(&updated)?);
+        }
+        EdgeCommands::From { id } => {
+            let node_id = store.resolve_node(&id)?;
+            let edges = store.edges_from(node_id)?;
+            for edge in edges {
+                println!("{} --[{}]--> {}", edge.from, edge.kind, edge.to);
+            }
+        }
+        EdgeCommands::To { id } => {
+            let node_id = store.resolve_node(&id)?;
+            let edges = store.edges_to(node_id)?;
+            for edge in edges {
+                println!("{} --[{}]--> {}", edge.from, edge.kind, edge.to);
+            }
+        }
+        EdgeCommands::Delete { id } => {
+            let edge_id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            store.delete_edge(edge_id, AgentId::User)?;
+            println!("Deleted edge: {}", id);
+        }
+        EdgeCommands::PruneOrphans { dry_run } => {
+            let orphans = if dry_run {
+                store.orphan_edges()?
+            } else {
+                store.prune_orphan_edges(AgentId::User)?
+            };
+            for edge in &orphans {
+                println!("{} {} --[{}]--> {}", edge.id, edge.from, edge.kind, edge.to);
+            }
+            if dry_run {
+                println!("{} orphaned edge(s) would be deleted", orphans.len());
+            } else {
+                println!("Deleted {} orphaned edge(s)", orphans.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_retention_command(command: RetentionCommands, store: &Arc<SledStore>) -> Result<()> {
+    let parse_class = |class: &str| -> Result<RetentionClass> { class.parse().map_err(|e: String| anyhow::anyhow!(e)) };
+    let mut policy = store.retention_policy()?;
+    match command {
+        RetentionCommands::Show { json } => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&policy)?);
+                return Ok(());
+            }
+            println!("default          {}", policy.default);
+            for (kind, class) in &policy.kinds {
+                println!("{:<16} {}", kind, class);
+            }
+            println!();
+            let seconds = |s: Option<i64>| s.map_or_else(|| "never".to_string(), |s| format!("{}s", s));
+            println!("{:<10} {:>10} {:>14} {:>8} {:>6}", "class", "ttl", "archive after", "capture", "backup");
+            for class in [RetentionClass::Ephemeral, RetentionClass::Standard, RetentionClass::Permanent] {
+                let rules = policy.rules(class);
+                println!(
+                    "{:<10} {:>10} {:>14} {:>8} {:>6}",
+                    class,
+                    seconds(rules.ttl_seconds),
+                    seconds(rules.archive_after_seconds),
+                    rules.capture.map_or_else(|| "policy".to_string(), |mode| mode.to_string()),
+                    if rules.backup { "yes" } else { "no" }
+                );
+            }
+            return Ok(());
+        }
+        RetentionCommands::Assign { class, kind } => {
+            let class = parse_class(&class)?;
+            match kind {
+                Some(kind) => {
+                    let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                    policy.kinds.insert(kind.to_string(), class);
+                }
+                None => policy.default = class,
+            }
+        }
+        RetentionCommands::Clear { kind } => {
+            let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            if policy.kinds.remove(&kind.to_string()).is_none() {
+                println!("{} already follows the default", kind);
+                return Ok(());
+            }
+        }
+        RetentionCommands::Rules { class, ttl, no_ttl, archive_after, no_archive, capture, backup } => {
+            let rules = policy.rules_mut(parse_class(&class)?);
+            if let Some(ttl) = ttl {
+                rules.ttl_seconds = Some(parse_duration(&ttl)?.num_seconds());
+            } else if no_ttl {
+                rules.ttl_seconds = None;
+            }
+            if let Some(after) = archive_after {
+                rules.archive_after_seconds = Some(parse_duration(&after)?.num_seconds());
+            } else if no_archive {
+                rules.archive_after_seconds = None;
+            }
+            match capture.as_deref() {
+                Some("policy") => rules.capture = None,
+                Some(mode) => rules.capture = Some(mode.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+                None => {}
+            }
+            if let Some(backup) = backup {
+                rules.backup = backup;
+            }
+        }
+    }
+    store.set_retention_policy(&policy)?;
+    println!("Retention policy updated");
+    Ok(())
+}
+
+fn handle_constraint_command(command: ConstraintCommands, store: &Arc<SledStore>) -> Result<()> {
+    use elegant_state::store::Constraint;
+
+    let mut constraints = store.graph_constraints()?;
+    let (name, rule) = match command {
+        ConstraintCommands::List { json } => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&constraints)?);
+            } else if constraints.is_empty() {
+                println!("No graph constraints");
+            } else {
+                for (name, rule) in &constraints.rules {
+                    println!("{:<24} {}", name, rule);
+                }
+            }
+            return Ok(());
+        }
+        ConstraintCommands::Remove { name } => {
+            if !constraints.remove(&name) {
+                anyhow::bail!("No constraint named {}", name);
+            }
+            store.set_graph_constraints(&constraints)?;
+            println!("Removed {}", name);
+            return Ok(());
+        }
+        ConstraintCommands::MaxDegree { name, edge, max, incoming, node } => {
+            let node = node.map(|kind| kind.to_string());
+            (name, Constraint::MaxDegree { edge: edge.to_string(), max, incoming, node })
+        }
+        ConstraintCommands::Pairs { name, edge, pairs } => {
+            let pairs = pairs
+                .iter()
+                .map(|pair| -> Result<(String, String)> {
+                    let (from, to) =
+                        pair.split_once("->").ok_or_else(|| anyhow::anyhow!("Expected FROM->TO, got {}", pair))?;
+                    let from: NodeKind = from.trim().parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                    let to: NodeKind = to.trim().parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                    Ok((from.to_string(), to.to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (name, Constraint::AllowedPairs { edge: edge.to_string(), pairs })
+        }
+        ConstraintCommands::Unique { name, kind, field } => {
+            (name, Constraint::Unique { kind: kind.to_string(), field })
+        }
+    };
+    let shown = format!("{:<24} {}", name, rule);
+    constraints.set(&name, rule)?;
+    store.set_graph_constraints(&constraints)?;
+    println!("{}", shown);
+    Ok(())
+}
+
+fn handle_graph_command(command: GraphCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        GraphCommands::Path { from, to, kinds, mode, json } => {
+            let from_id = store.resolve_node(&from)?;
+            let to_id = store.resolve_node(&to)?;
+            let kinds = (!kinds.is_empty()).then_some(kinds.as_slice());
+            let path = store.shortest_path(from_id, to_id, kinds, mode)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&path)?);
+                return Ok(());
+            }
+            let Some(path) = path else {
+                println!("No path from {} to {}", from, to);
+                return Ok(());
+            };
+            println!("{} [{}] {:?}", path.nodes[0].id, path.nodes[0].kind, path.nodes[0].content);
+            for (step, node) in path.steps.iter().zip(&path.nodes[1..]) {
+                let arrow = if step.forward {
+                    format!("--[{}]-->", step.edge.kind)
+                } else {
+                    format!("<--[{}]--", step.edge.kind)
+                };
+                println!("  {} {} [{}] {:?}", arrow, node.id, node.kind, node.content);
+            }
+            println!("{} hop(s), cost {}, strength {:.4}", path.hops(), path.cost, path.strength);
+        }
+        GraphCommands::Rank { algorithm, kinds, node_kinds, damping, field, limit, dry_run, json } => {
+            use elegant_state::store::rank::{self, RankOptions};
+
+            let mut options = RankOptions::default().with_centrality(algorithm).with_damping(damping);
+            if !kinds.is_empty() {
+                options = options.with_edge_kinds(kinds);
+            }
+            if let Some(node_kinds) = node_kinds {
+                options = options.with_node_kinds(node_kinds);
+            }
+            let scores = rank::rank(store, &options)?;
+            let field = field.unwrap_or_else(|| algorithm.metadata_key().to_string());
+            if !dry_run {
+                rank::write_scores(store, &scores, &field, AgentId::System)?;
+            }
+
+            let top = &scores[..limit.min(scores.len())];
+            if json {
+                println!("{}", serde_json::to_string_pretty(top)?);
+            } else {
+                for score in top {
+                    println!("{:.6}  {} [{}]", score.score, score.node, score.kind);
+                }
+                if !dry_run {
+                    println!("Wrote {} to {} node(s)", field, scores.len());
+                }
+            }
+        }
+        GraphCommands::Clusters { kinds, node_kinds, field, min_size, dry_run, json } => {
+            use elegant_state::store::cluster::{self, ClusterOptions};
+
+            let mut options = ClusterOptions::default();
+            if !kinds.is_empty() {
+                options = options.with_edge_kinds(kinds);
+            }
+            if let Some(node_kinds) = node_kinds {
+                options = options.with_node_kinds(node_kinds);
+            }
+            let found = cluster::clusters(store, &options)?;
+            if !dry_run {
+                cluster::write_clusters(store, &found, &field, AgentId::System)?;
+            }
+
+            let shown: Vec<_> = found.iter().filter(|c| c.members.len() >= min_size).collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&shown)?);
+            } else {
+                for cluster in &shown {
+                    println!("Cluster {} ({} node(s))", cluster.id, cluster.members.len());
+                    for id in &cluster.members {
+                        println!("  {}", id);
+                    }
+                }
+                let nodes: usize = found.iter().map(|c| c.members.len()).sum();
+                println!("{} cluster(s) over {} node(s), {} smaller than {}", found.len(), nodes, found.len() - shown.len(), min_size);
+                if !dry_run {
+                    println!("Wrote {} to {} node(s)", field, nodes);
+                }
+            }
+        }
+        GraphCommands::Cycles { kinds, json } => {
+            use elegant_state::store::cycles;
+
+            let kinds = if kinds.is_empty() { cycles::default_cycle_kinds() } else { kinds };
+            let found = cycles::find_cycles(store, &kinds)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&found)?);
+                return Ok(());
+            }
+            for cycle in &found {
+                let trail: Vec<String> = cycle
+                    .nodes
+                    .iter()
+                    .zip(&cycle.edges)
+                    .map(|(node, edge)| format!("{} --[{}]-->", node, edge.kind))
+                    .collect();
+                println!("{} {}", trail.join(" "), cycle.nodes[0]);
+            }
+            let kinds: Vec<String> = kinds.iter().map(ToString::to_string).collect();
+            println!("{} cycle(s) in {}", found.len(), kinds.join(", "));
+        }
+        GraphCommands::Stats { samples, json } => {
+            let report = elegant_state::store::structure::analyze(store, samples)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            println!("Nodes: {}", report.nodes);
+            for (kind, count) in &report.nodes_by_kind {
+                println!("  {:<16} {}", kind, count);
+            }
+            println!("Edges: {}", report.edges);
+            for (kind, count) in &report.edges_by_kind {
+                println!("  {:<16} {}", kind, count);
+            }
+            println!();
+            let degree = &report.degree;
+            println!(
+                "Degree: min {}, median {}, mean {:.2}, max {}",
+                degree.min, degree.median, degree.mean, degree.max
+            );
+            for (edges, nodes) in &degree.histogram {
+                println!("  {:>6} edge(s)  {} node(s)", edges, nodes);
+            }
+            println!();
+            let components = &report.components;
+            println!("Components: {} (largest {} nodes)", components.count, components.largest);
+            for (size, count) in components.sizes.iter().rev() {
+                println!("  {:>6} node(s)  {} component(s)", size, count);
+            }
+            println!("Isolated nodes: {}", report.isolated);
+            for (kind, count) in &report.isolated_by_kind {
+                println!("  {:<16} {}", kind, count);
+            }
+            println!();
+            match report.paths.mean_hops {
+                Some(mean) => println!(
+                    "Path length: mean {:.2} hops, longest {} over {} pair(s) from {} sampled node(s)",
+                    mean, report.paths.max_hops, report.paths.pairs, report.paths.sources
+                ),
+                None => println!("Path length: no connected pairs among {} sampled node(s)", report.paths.sources),
+            }
+        }
+        GraphCommands::Toposort { kinds, edge_kinds, json } => {
+            use elegant_state::store::toposort;
+
+            let edge_kinds = if edge_kinds.is_empty() { toposort::default_order_kinds() } else { edge_kinds };
+            let sorted = toposort::toposort(store, (!kinds.is_empty()).then_some(kinds.as_slice()), &edge_kinds)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&sorted)?);
+            } else {
+                for scheduled in &sorted.order {
+                    println!("{:>3}  {} [{}]", scheduled.level, scheduled.node, scheduled.kind);
+                }
+                if !sorted.is_satisfiable() {
+                    println!("\nUnsatisfiable ({} node(s) on or behind a cycle):", sorted.unsatisfiable.len());
+                    for id in &sorted.unsatisfiable {
+                        println!("  {}", id);
+                    }
+                    for cycle in &sorted.cycles {
+                        let trail: Vec<String> = cycle.nodes.iter().map(ToString::to_string).collect();
+                        println!("  cycle: {} -> {}", trail.join(" -> "), cycle.nodes[0]);
+                    }
+                }
+            }
+            if !sorted.is_satisfiable() {
+                anyhow::bail!("{} node(s) can't be scheduled", sorted.unsatisfiable.len());
+            }
+        }
+        GraphCommands::Acyclic { kinds, clear } => {
+            use elegant_state::store::cycles;
+
+            if kinds.is_empty() && !clear {
+                let kinds: Vec<String> = store.acyclic_kinds()?.iter().map(ToString::to_string).collect();
+                if kinds.is_empty() {
+                    println!("No edge kinds are kept acyclic");
+                } else {
+                    println!("{}", kinds.join(", "));
+                }
+                return Ok(());
+            }
+            store.set_acyclic_kinds(&kinds)?;
+            let existing = cycles::find_cycles(store, &kinds)?;
+            if !existing.is_empty() {
+                eprintln!("Warning: {} existing cycle(s); see `graph cycles`", existing.len());
+            }
+            println!("Acyclic edge kinds updated");
+        }
+        GraphCommands::Constraint { command } => handle_constraint_command(command, store)?,
+        GraphCommands::Symmetric { kinds, clear } => {
+            if kinds.is_empty() && !clear {
+                let kinds: Vec<String> = store.symmetric_kinds()?.iter().map(ToString::to_string).collect();
+                if kinds.is_empty() {
+                    println!("All edge kinds are directed");
+                } else {
+                    println!("{}", kinds.join(", "));
+                }
+                return Ok(());
+            }
+            store.set_symmetric_kinds(&kinds)?;
+            println!("Symmetric edge kinds updated");
+        }
+        GraphCommands::Reciprocal { pairs, clear, backfill, agent } => {
+            if !pairs.is_empty() || clear {
+                let pairs = pairs
+                    .iter()
+                    .map(|pair| {
+                        let (kind, reciprocal) = pair.split_once('=').unwrap_or((pair.as_str(), pair.as_str()));
+                        Ok((
+                            kind.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                            reciprocal.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                        ))
+                    })
+                    .collect::<Result<Vec<(EdgeKind, EdgeKind)>>>()?;
+                store.set_reciprocal_kinds(&pairs)?;
+                println!("Reciprocal edge kinds updated");
+            } else if !backfill {
+                let pairs = store.reciprocal_kinds()?;
+                if pairs.is_empty() {
+                    println!("No edge kinds are reciprocal");
+                }
+                for (kind, reciprocal) in pairs {
+                    println!("{} <-> {}", kind, reciprocal);
+                }
+            }
+            if backfill {
+                let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                let created = store.backfill_reciprocals(agent)?;
+                println!("Created {} reciprocal edge(s)", created.len());
+            }
+        }
+        GraphCommands::Tree { root, depth, ancestors, json } => {
+            let root = store.resolve_node(&root)?;
+            let tree = elegant_state::store::hierarchy::tree(store.as_ref(), root, depth)?
+                .ok_or_else(|| anyhow::anyhow!("Node not found: {}", root))?;
+            let ancestors = if ancestors { store.ancestors(root)? } else { Vec::new() };
+            if json {
+                let output = serde_json::json!({ "ancestors": ancestors, "tree": tree });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+                return Ok(());
+            }
+            let label = |node: &StateNode| format!("{}  {}", node.id, viz::node_label(node));
+            if !ancestors.is_empty() {
+                let trail: Vec<String> = ancestors.iter().rev().map(viz::node_label).collect();
+                println!("{} >", trail.join(" > "));
+            }
+            print!("{}", tree.render(label));
+        }
+        GraphCommands::Viz { root, depth, kinds, format, output } => {
+            let (nodes, edges) = diagram_graph(store, root.as_deref(), depth, &kinds)?;
+            let diagram = viz::render_with_kinds(format, &nodes, &edges, &store.kind_registry()?);
+            match output {
+                Some(path) => std::fs::write(path, diagram)?,
+                None => print!("{}", diagram),
+            }
+        }
+        GraphCommands::Diff { before, after } => {
+            let before = graph_side(store, &before)?;
+            let after = match after {
+                Some(after) => graph_side(store, &after)?,
+                None => store.graph_as_of(chrono::Utc::now())?,
+            };
+            let diff = elegant_state::store::GraphDiff::between(before, after)?;
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        }
+    }
+    Ok(())
+}
+
+/// One side of `graph diff`: an export file if one exists at `side`,
+/// otherwise the graph as of the time it names
+fn graph_side(store: &SledStore, side: &str) -> Result<(Vec<StateNode>, Vec<StateEdge>)> {
+    if std::path::Path::new(side).is_file() {
+        let serde_json::Value::Object(mut export) = serde_json::from_str(&std::fs::read_to_string(side)?)? else {
+            anyhow::bail!("{} is not an export document", side);
+        };
+        let nodes = serde_json::from_value(export.remove("nodes").unwrap_or_default())
+            .map_err(|e| anyhow::anyhow!("{} is not an export document: {}", side, e))?;
+        let edges = export.remove("edges").map(serde_json::from_value).transpose()?.unwrap_or_default();
+        return Ok((nodes, edges));
+    }
+    Ok(store.graph_as_of(parse_time(side)?)?)
+}
+
+/// Print `store`'s graph as `export` does; `live` supplies the instance ID
+/// and kind registry when `store` is a materialized past state
+fn print_export(
+    store: &SledStore,
+    live: &SledStore,
+    format: &str,
+    root: Option<String>,
+    depth: usize,
+    kinds: &[EdgeKind],
+) -> Result<()> {
+    // Aliases live outside the event log, so only the live store knows them
+    let root = root.map(|root| live.resolve_node(&root)).transpose()?;
+    match format {
+        "json" => {
+            // Importers key their ID remapping on this
+            let instance = match live.instance_id() {
+                Err(elegant_state::StoreError::ReadOnly) => None,
+                id => Some(id?),
+            };
+            // Events up to this point are in the export; CDC consumers resume after it
+            let clock = store.vector_clock()?;
+            let export = match root {
+                Some(root) => {
+                    let kinds = (!kinds.is_empty()).then_some(kinds);
+                    let graph = store.subgraph(root, depth, kinds)?;
+                    serde_json::json!({
+                        "version": "0.1.0",
+                        "instance": instance,
+                        "clock": clock,
+                        "root": graph.root,
+                        "depth": depth,
+                        "nodes": graph.nodes,
+                        "edges": graph.edges,
+                    })
+                }
+                None => serde_json::json!({
+                    "version": "0.1.0",
+                    "instance": instance,
+                    "clock": clock,
+                    "nodes": store.list_nodes(None, usize::MAX)?,
+                    "edges": store.list_edges()?,
+                }),
+            };
+            println!("{}", serde_json::to_string_pretty(&export)?);
+        }
+        format => {
+            let format: DiagramFormat = format.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let (nodes, edges) = diagram_graph(store, root.map(|id| id.to_string()).as_deref(), depth, kinds)?;
+            print!("{}", viz::render_with_kinds(format, &nodes, &edges, &live.kind_registry()?));
+        }
+    }
+    Ok(())
+}
+
+/// Nodes and edges to draw: the whole graph, or `root`'s surroundings
+fn diagram_graph(
+    store: &SledStore,
+    root: Option<&str>,
+    depth: usize,
+    kinds: &[EdgeKind],
+) -> Result<(Vec<StateNode>, Vec<StateEdge>)> {
+    match root {
+        Some(root) => {
+            let root = store.resolve_node(root)?;
+            let graph = store.subgraph(root, depth, (!kinds.is_empty()).then_some(kinds))?;
+            Ok((graph.nodes, graph.edges))
+        }
+        None => {
+            let edges = store
+                .list_edges()?
+                .into_iter()
+                .filter(|e| kinds.is_empty() || kinds.contains(&e.kind))
+                .collect();
+            Ok((store.list_nodes(None, usize::MAX)?, edges))
+        }
+    }
+}
+
+fn connector_specs(store: &SledStore) -> Result<Vec<ConnectorSpec>> {
+    Ok(match store.get_meta(CONNECTORS_META_KEY)? {
+        Some(value) => serde_json::from_value(value)?,
+        None => Vec::new(),
+    })
+}
+
+fn print_reversal(reversal: Option<Reversal>, action: &str, json: bool) -> Result<()> {
+    let Some(reversal) = reversal else {
+        println!("Nothing to {}", action);
+        return Ok(());
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reversal.recorded)?);
+        return Ok(());
+    }
+    for (original, event) in reversal.reverted.iter().zip(&reversal.recorded) {
+        println!("  {} {:?} {:?} -> {:?}", original.id, original.target, original.operation, event.operation);
+    }
+    println!("Reverted {} event(s)", reversal.recorded.len());
+    Ok(())
+}
+
+fn handle_event_command(command: EventCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        EventCommands::Replay { into, verbose } => {
+            let mut target = SledStore::open(expand_path(&into))?;
+            if let Some(namespace) = store.namespace() {
+                target = target.with_namespace(namespace)?;
+            }
+            if target.count_events()? > 0 || target.count_nodes(None)? > 0 {
+                anyhow::bail!("{} is not empty; replay needs a fresh database", into);
+            }
+
+            let report = EventSourcer::new(store.as_ref()).replay_into(&target)?;
+            println!("Events:   {} replayed, {} skipped", report.applied, report.skipped.len());
+            println!("Source:   {}", report.source);
+            println!("Replayed: {}", report.replayed);
+            if verbose {
+                for (id, reason) in &report.skipped {
+                    println!("  skipped {}: {}", id, reason);
+                }
+            }
+            if !report.is_consistent() {
+                anyhow::bail!("Replayed state does not match the source");
+            }
+            println!("Consistent");
+        }
+    }
+    Ok(())
+}
+
+fn handle_index_command(command: IndexCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        IndexCommands::Create { field } => {
+            let entries = store.create_metadata_index(&field)?;
+            println!("Indexed {} ({} entries)", field, entries);
+        }
+        IndexCommands::Drop { field } => {
+            if !store.drop_metadata_index(&field)? {
+                anyhow::bail!("No index on {}", field);
+            }
+            println!("Dropped index on {}", field);
+        }
+        IndexCommands::List => {
+            for field in store.metadata_indexes()? {
+                println!("metadata.{}", field);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_connector_command(command: ConnectorCommands, store: &Arc<SledStore>) -> Result<()> {
+    let mut specs = connector_specs(store)?;
+    match command {
+        ConnectorCommands::List => {
+            for spec in &specs {
+                let source = match &spec.source {
+                    SourceSpec::Command { command } => format!("command: {}", command),
+                    SourceSpec::State { url, namespace: Some(ns) } => format!("state: {} ({})", url, ns),
+                    SourceSpec::State { url, namespace: None } => format!("state: {}", url),
+                };
+                println!("{:<16} ttl={}s  {}", spec.name, spec.ttl().num_seconds(), source);
+            }
+        }
+        ConnectorCommands::Add { name, command, url, namespace, ttl } => {
+            let source = match (command, url) {
+                (Some(command), _) => SourceSpec::Command { command },
+                (None, Some(url)) => SourceSpec::State { url, namespace },
+                (None, None) => anyhow::bail!("Give --command or --url"),
+            };
+            let spec = ConnectorSpec {
+                name: name.clone(),
+                source,
+                ttl_secs: Some(parse_duration(&ttl)?.num_seconds()),
+            };
+            // Fail on a bad command line now rather than at search time
+            spec.build()?;
+            specs.retain(|s| s.name != name);
+            specs.push(spec);
+            store.set_meta(CONNECTORS_META_KEY, &serde_json::to_value(&specs)?)?;
+            println!("Registered connector: {}", name);
+        }
+        ConnectorCommands::Remove { name } => {
+            let before = specs.len();
+            specs.retain(|s| s.name != name);
+            if specs.len() == before {
+                anyhow::bail!("No connector named {}", name);
+            }
+            store.set_meta(CONNECTORS_META_KEY, &serde_json::to_value(&specs)?)?;
+            println!("Removed connector: {}", name);
+        }
+    }
+    Ok(())
+}
+
+fn handle_kind_command(command: KindCommands, store: &Arc<SledStore>) -> Result<()> {
+    use elegant_state::store::{KindInfo, TextRule};
+
+    let mut registry = store.kind_registry()?;
+    match command {
+        KindCommands::List { json } => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&registry)?);
+                return Ok(());
+            }
+            println!("Node kinds: {}", NodeKind::BUILT_IN.map(|k| k.to_string()).join(", "));
+            println!("Edge kinds: {}", EdgeKind::BUILT_IN.map(|k| k.to_string()).join(", "));
+            for (edge, kinds) in [(false, &registry.nodes), (true, &registry.edges)] {
+                for (name, info) in kinds {
+                    println!(
+                        "{:<6} custom:{:<20} {:<8} {}",
+                        if edge { "edge" } else { "node" },
+                        name,
+                        info.color.as_deref().unwrap_or("-"),
+                        info.description.as_deref().unwrap_or("")
+                    );
+                }
+            }
+        }
+        KindCommands::Register { name, edge, description, color } => {
+            let info = KindInfo { description, color };
+            let kind = if edge {
+                registry.register_edge(&name, info)?.to_string()
+            } else {
+                registry.register_node(&name, info)?.to_string()
+            };
+            store.set_kind_registry(&registry)?;
+            println!("Registered {}", kind);
+        }
+        KindCommands::Remove { name, edge } => {
+            let removed = if edge { registry.unregister_edge(&name) } else { registry.unregister_node(&name) };
+            if !removed {
+                anyhow::bail!("{} is not a registered {} kind", name, if edge { "edge" } else { "node" });
+            }
+            store.set_kind_registry(&registry)?;
+            println!("Removed {}", name);
+        }
+        KindCommands::Schema { kind, file, clear } => {
+            let mut schemas = store.content_schemas()?;
+            if clear {
+                if !schemas.remove(&kind) {
+                    anyhow::bail!("{} has no content schema", kind);
+                }
+                store.set_content_schemas(&schemas)?;
+                println!("Cleared the {} schema", kind);
+            } else if let Some(file) = file {
+                let schema: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+                schemas.set(&kind, schema)?;
+                store.set_content_schemas(&schemas)?;
+                println!("New {} nodes must match {}", kind, file.display());
+            } else {
+                match schemas.get(&kind) {
+                    Some(schema) => println!("{}", serde_json::to_string_pretty(schema)?),
+                    None => println!("{} has no content schema", kind),
+                }
+            }
+        }
+        KindCommands::Text { kind, include, exclude, clear } => {
+            let mut extraction = store.text_extraction()?;
+            if clear {
+                if !extraction.remove(&kind) {
+                    anyhow::bail!("{} has no text rule", kind);
+                }
+                store.set_text_extraction(&extraction)?;
+                println!("Search reads all {} content again", kind);
+            } else if !include.is_empty() || !exclude.is_empty() {
+                let rule = TextRule { include, exclude };
+                extraction.set(&kind, rule, &store.content_schemas()?)?;
+                store.set_text_extraction(&extraction)?;
+                println!("Updated the {} text rule", kind);
+            } else {
+                match extraction.get(&kind) {
+                    Some(rule) => println!("{}", serde_json::to_string_pretty(rule)?),
+                    None => println!("{} has no text rule; search reads all its content", kind),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_dev_command(command: DevCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        DevCommands::Generate { kind, count, respect_schema, edges, seed, agent, json } => {
+            use elegant_state::generate::{self, GenerateOptions};
+
+            let kind: NodeKind = kind.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut options =
+                GenerateOptions::new(kind, count).with_respect_schema(respect_schema).with_edges_per_node(edges);
+            if let Some(seed) = seed {
+                options = options.with_seed(seed);
+            }
+            let generated = generate::generate(store, &options, agent)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&generated)?);
+                return Ok(());
+            }
+            println!(
+                "Created {} {} node(s) and {} edge(s)",
+                generated.nodes.len(),
+                options.kind,
+                generated.edges.len()
+            );
+            if !generated.rejected.is_empty() {
+                println!("{} write(s) refused by the store:", generated.rejected.len());
+                for reason in &generated.rejected {
+                    println!("  {}", reason);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_agent_command(command: AgentCommands, store: &Arc<SledStore>) -> Result<()> {
+    let mut capabilities: CapabilityConfig = load_meta(store, CAPABILITIES_KEY)?;
+    let demotions = watchdog::demotions(store)?;
+    match command {
+        AgentCommands::List { json } => {
+            let mut names: Vec<&String> = capabilities.agent_overrides.keys().chain(demotions.keys()).collect();
+            names.sort();
+            names.dedup();
+            let mut effective = capabilities.clone();
+            watchdog::apply_demotions(&mut effective, &demotions);
+            if json {
+                let agents: Vec<serde_json::Value> = names
+                    .iter()
+                    .map(|name| {
+                        serde_json::json!({
+                            "agent": name,
+                            "mode": effective.agent_overrides.get(*name).map(|c| c.mode),
+                            "demotion": demotions.get(*name),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&agents)?);
+                return Ok(());
+            }
+            println!("Default mode: {}", capabilities.default_mode);
+            for name in names {
+                let mode = effective.agent_overrides.get(name).map_or(capabilities.default_mode, |c| c.mode);
+                match demotions.get(name) {
+                    Some(demotion) => println!("  {:<20} {:<9} (demoted from {})", name, mode, demotion.from),
+                    None => println!("  {:<20} {}", name, mode),
+                }
+            }
+        }
+        AgentCommands::Show { agent } => {
+            let agent_id: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let caps = capabilities.get_capabilities(&agent_id);
+            println!("Agent: {}", agent_id);
+            println!("Mode: {}", caps.mode);
+            println!("Can vote: {} (weight {:.2})", caps.can_vote, caps.vote_weight);
+            if let Some(demotion) = demotions.get(&agent_id.to_string()) {
+                println!(
+                    "Demoted to {} by the watchdog at {}: {}",
+                    demotion.to,
+                    demotion.at.format("%Y-%m-%d %H:%M:%S"),
+                    demotion.reason
+                );
+                println!("Run `agent reinstate {}` to restore it", agent_id);
+            }
+        }
+        AgentCommands::Set { agent, mode, can_vote, vote_weight } => {
+            let agent_id: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut caps = capabilities.get_capabilities(&agent_id);
+            if let Some(mode) = mode {
+                caps.mode = mode;
+            }
+            if let Some(can_vote) = can_vote {
+                caps.can_vote = can_vote;
+            }
+            if let Some(weight) = vote_weight {
+                if !(0.0..=2.0).contains(&weight) {
+                    anyhow::bail!("Vote weight must be between 0.0 and 2.0");
+                }
+                caps.vote_weight = weight;
+            }
+            capabilities.set_capabilities(caps).map_err(|e| anyhow::anyhow!(e))?;
+            save_meta(store, CAPABILITIES_KEY, &capabilities)?;
+            println!("Updated {}", agent_id);
+            if demotions.contains_key(&agent_id.to_string()) {
+                println!("Still held down by a watchdog demotion; run `agent reinstate {}`", agent_id);
+            }
+        }
+        AgentCommands::Reinstate { agent } => {
+            let agent_id: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            match watchdog::reinstate(store, &agent_id.to_string())? {
+                Some(demotion) => println!("Reinstated {} to {}", agent_id, demotion.from),
+                None => anyhow::bail!("{} is not demoted", agent_id),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_embedding_command(command: EmbeddingCommands, store: &Arc<SledStore>) -> Result<()> {
+    use elegant_state::store::{node_text, CommandEmbedder, Embedder, ModelStats};
+
+    match command {
+        EmbeddingCommands::Embed { ids, kind, model, command } => {
+            let embedder = CommandEmbedder::from_command_line(model, &command)?;
+            let nodes = if ids.is_empty() {
+                let kind = kind.map(|k| k.parse::<NodeKind>()).transpose().map_err(|e| anyhow::anyhow!(e))?;
+                store.list_nodes(kind, usize::MAX)?
+            } else {
+                ids.iter()
+                    .map(|id| {
+                        let node_id = store.resolve_node(id)?;
+                        store.get_node(node_id)?.ok_or_else(|| anyhow::anyhow!("Node not found: {}", id))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let mut cached = 0;
+            for node in &nodes {
+                let text = node_text(node);
+                if store.cached_embedding(embedder.model(), &text)?.is_some() {
+                    cached += 1;
+                }
+                store.embedding(&embedder, &text)?;
+            }
+            println!("Embedded {} nodes ({} from cache)", nodes.len(), cached);
+        }
+        EmbeddingCommands::Stats { json } => {
+            let stats = store.embedding_stats()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            let row = |model: &str, s: &ModelStats| {
+                let rate = s.hit_rate().map_or("-".to_string(), |r| format!("{:.1}%", r * 100.0));
+                println!("{:<24} {:>8} {:>10} {:>8} {:>8} {:>8}", model, s.entries, s.bytes, s.hits, s.misses, rate);
+            };
+            println!("{:<24} {:>8} {:>10} {:>8} {:>8} {:>8}", "MODEL", "VECTORS", "BYTES", "HITS", "MISSES", "HIT RATE");
+            for (model, model_stats) in &stats.models {
+                row(model, model_stats);
+            }
+            row("(total)", &stats.total());
+        }
+        EmbeddingCommands::Purge { model } => {
+            let purged = store.purge_embeddings(model.as_deref())?;
+            println!("Purged {} cached embeddings", purged);
+        }
+    }
+    Ok(())
+}
+
+fn handle_share_command(command: ShareCommands, store: &Arc<SledStore>) -> Result<()> {
+    match command {
+        ShareCommands::Create { root, ttl, depth, base_url } => {
+            let root = store.resolve_node(&root)?;
+            let (link, token) = store.create_share(root, depth, parse_duration(&ttl)?, AgentId::User)?;
+            println!("Share:   {}", link.id);
+            println!("Expires: {}", link.expires_at.format("%Y-%m-%d %H:%M UTC"));
+            println!("{}/share/{}", base_url.trim_end_matches('/'), token);
+        }
+        ShareCommands::List { all } => {
+            let now = chrono::Utc::now();
+            for link in store.list_shares()? {
+                let status = if link.revoked_at.is_some() {
+                    "revoked"
+                } else if link.is_active(now) {
+                    "active"
+                } else {
+                    "expired"
+                };
+                if all || status == "active" {
+                    println!(
+                        "{}  {:<8} root={} depth={} expires={}",
+                        link.id,
+                        status,
+                        link.root,
+                        link.depth,
+                        link.expires_at.format("%Y-%m-%d %H:%M")
+                    );
+                }
+            }
+        }
+        ShareCommands::Revoke { id } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            store.revoke_share(id, AgentId::User)?;
+            println!("Revoked share: {}", id);
+        }
+        ShareCommands::Audit { id } => {
+            let id = id
+                .map(|id| id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e)))
+                .transpose()?;
+            for entry in store.share_audit(id)? {
+                println!(
+                    "{}  {}  {:<8} {}",
+                    entry.at.format("%Y-%m-%d %H:%M:%S"),
+                    entry.share,
+                    entry.action,
+                    entry.detail.unwrap_or_default()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_db_command(command: DbCommands, store: &Arc<SledStore>, db_path: &str) -> Result<()> {
+    match command {
+        DbCommands::Stats { verbose, index: _, compression, recount } => {
+            if recount {
+                store.rebuild_stats()?;
+            }
+            println!("Nodes:  {}", store.count_nodes(None)?);
+            println!("Edges:  {}", store.count_edges()?);
+            println!("Events: {}", store.count_events()?);
+            println!("Schema: version {}", store.schema_version()?);
+
+            let usage = store.disk_usage()?;
+            println!(
+                "Disk:   {} on disk, {} live, about {} reclaimable with `db compact`",
+                format_bytes(usage.on_disk),
+                format_bytes(usage.live),
+                format_bytes(usage.reclaimable())
+            );
+
+            if verbose {
+                println!();
+                let counts = store.graph_stats()?;
+                println!("Nodes by kind:");
+                for (kind, count) in &counts.nodes_by_kind {
+                    println!("  {:<16} {}", kind, count);
+                }
+                println!();
+                println!("Edges by kind:");
+                for (kind, count) in &counts.edges_by_kind {
+                    println!("  {:<16} {}", kind, count);
+                }
+                println!();
+                println!("Events by agent:");
+                for (agent, count) in &counts.events_by_agent {
+                    println!("  {:<16} {}", agent, count);
+                }
+                println!();
+                println!("Events by day (latest 7):");
+                for (day, count) in counts.events_by_day.iter().rev().take(7) {
+                    println!("  {:<16} {}", day, count);
+                }
+
+                println!();
+                println!("Live data by tree:");
+                for tree in store.tree_usage()? {
+                    println!("  {:<24} {:>8} entries  {:>10}", tree.name, tree.entries, format_bytes(tree.bytes));
+                }
+            }
+
+            if compression {
+                let stats = store.compression_stats()?;
+                println!();
+                println!("Compression:");
+                println!(
+                    "  compressed values: {} / {}",
+                    stats.compressed_values, stats.values
+                );
+                println!("  stored bytes:      {}", stats.stored_bytes);
+                println!("  raw bytes:         {}", stats.raw_bytes);
+                println!("  ratio:             {:.2}x", stats.ratio());
+            }
+        }
+        DbCommands::Migrate { to_version, pending, dry_run } => {
+            let target = to_version
+                .map(|v| v.trim_start_matches('v').parse::<u32>())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("Invalid schema version: {}", e))?;
+            let current = store.schema_version()?;
+            let migrations: Vec<_> = store
+                .pending_migrations()?
+                .into_iter()
+                .filter(|m| target.map_or(true, |t| m.version <= t))
+                .collect();
+            if pending || dry_run {
+                println!("Schema version {} (this build writes {})", current, SCHEMA_VERSION);
+                for migration in &migrations {
+                    println!("  pending v{}: {}", migration.version, migration.description);
+                }
+                return Ok(());
+            }
+
+            for report in store.migrate(target)? {
+                println!(
+                    "v{}: {} ({} record(s) rewritten)",
+                    report.version, report.description, report.rewritten
+                );
+            }
+            println!("Schema version {}", store.schema_version()?);
+        }
+        DbCommands::Dump { output } => {
+            let summary = store.dump_to(std::path::Path::new(&output))?;
+            println!(
+                "Dumped {} node(s), {} edge(s), {} event(s) from {} namespace(s) to {} ({} records)",
+                summary.nodes, summary.edges, summary.events, summary.namespaces, output, summary.records
+            );
+        }
+        DbCommands::Load { input, verify } => {
+            if verify {
+                let header = verify_dump(std::path::Path::new(&input))?;
+                println!(
+                    "{}: valid dump v{} of schema v{}, taken {}",
+                    input,
+                    header.version,
+                    header.schema_version,
+                    header.created_at.format("%Y-%m-%d %H:%M:%S")
+                );
+                return Ok(());
+            }
+            let summary = store.load_from(std::path::Path::new(&input))?;
+            println!(
+                "Loaded {} node(s), {} edge(s), {} event(s) into {} namespace(s) ({} records)",
+                summary.nodes, summary.edges, summary.events, summary.namespaces, summary.records
+            );
+        }
+        DbCommands::At { time, command } => {
+            let at = parse_time(&time)?;
+            let (past, skipped) = EventSourcer::new(store.as_ref()).materialize_at(at)?;
+            if !skipped.is_empty() {
+                eprintln!("warning: {} event(s) before {} can't be replayed and are missing", skipped.len(), at.to_rfc3339());
+            }
+            match command {
+                AtCommands::Export { format, root, depth, kinds } => {
+                    print_export(&past, store, &format, root, depth, &kinds)?
+                }
+            }
+        }
+        DbCommands::Rebuild { from_events, dry_run, force } => {
+            use elegant_state::event::fingerprint;
+
+            if !from_events {
+                anyhow::bail!("Nothing to rebuild from; pass --from-events");
+            }
+            let events = EventSourcer::new(store.as_ref()).all_events()?;
+            let (replayed, skipped) = EventSourcer::replay(&events)?;
+            for (id, reason) in &skipped {
+                println!("  skipped {}: {}", id, reason);
+            }
+            println!("Events:   {} replayed, {} skipped", events.len() - skipped.len(), skipped.len());
+            match fingerprint(store) {
+                Ok(current) => println!("Current:  {}", current),
+                Err(e) => println!("Current:  unreadable ({})", e),
+            }
+            println!("Replayed: {}", fingerprint(&replayed)?);
+            if dry_run {
+                return Ok(());
+            }
+            if !skipped.is_empty() && !force {
+                anyhow::bail!(
+                    "{} event(s) can't be replayed and their changes would be lost; use --force to rebuild anyway",
+                    skipped.len()
+                );
+            }
+            let (nodes, edges) = store.replace_graph(&replayed)?;
+            println!("Rebuilt {} node(s) and {} edge(s) from the event log", nodes, edges);
+        }
+        DbCommands::Path => println!("{}", db_path),
+        DbCommands::Snapshot { command } => handle_snapshot_command(command, store, db_path)?,
+        DbCommands::Namespaces => {
+            for namespace in store.list_namespaces()? {
+                println!("{}", namespace);
+            }
+        }
+        DbCommands::Compact { threshold, force, dry_run } => {
+            let usage = store.disk_usage()?;
+            if dry_run {
+                println!("On disk:     {}", format_bytes(usage.on_disk));
+                println!("Live data:   {}", format_bytes(usage.live));
+                println!("Reclaimable: about {}", format_bytes(usage.reclaimable()));
+                return Ok(());
+            }
+            if let Some(threshold_mb) = threshold {
+                if !force && usage.reclaimable() < (threshold_mb as u64) * 1024 * 1024 {
+                    println!(
+                        "About {} reclaimable, below the {} MB threshold; use --force to compact anyway",
+                        format_bytes(usage.reclaimable()),
+                        threshold_mb
+                    );
+                    return Ok(());
+                }
+            }
+
+            let report = store.compact()?;
+            println!("Rewrote {} entries in {} trees", report.entries, report.trees);
+            println!("  before:    {}", format_bytes(report.before.on_disk));
+            println!("  after:     {}", format_bytes(report.after.on_disk));
+            println!("  reclaimed: {}", format_bytes(report.reclaimed_bytes()));
+        }
+        DbCommands::Check { fix, index } => {
+            let report = store.check(fix)?;
+            for issue in &report.issues {
+                println!(
+                    "{:<18} {}/{}: {}{}",
+                    issue.kind,
+                    issue.tree,
+                    issue.record,
+                    issue.detail,
+                    if issue.fixed { " (fixed)" } else { "" }
+                );
+            }
+            if index {
+                // Full-text search scans the store directly; there is no separate index
+                println!("Full-text index: served from the store, nothing to check");
+            }
+            println!(
+                "Checked {} nodes, {} edges, {} events: {} issue(s), {} fixed",
+                report.nodes,
+                report.edges,
+                report.events,
+                report.issues.len(),
+                report.issues.len() - report.unfixed()
+            );
+            if report.unfixed() > 0 {
+                anyhow::bail!("{} integrity issue(s) remain", report.unfixed());
+            }
+        }
+        DbCommands::Gc { dry_run } => {
+            let now = chrono::Utc::now();
+            if dry_run {
+                let expired = store.expired_nodes(now)?;
+                for id in &expired {
+                    println!("{}", id);
+                }
+                println!("{} expired node(s) would be deleted", expired.len());
+                let due = store.archive_due(now)?;
+                for id in &due {
+                    println!("{}", id);
+                }
+                println!("{} node(s) would be archived", due.len());
+            } else {
+                let purged = store.purge_expired(now)?;
+                println!("Deleted {} expired node(s)", purged.len());
+                let archived = store.archive_nodes(&store.archive_due(now)?)?;
+                println!("Archived {} node(s) past their retention class's age", archived.len());
+            }
+        }
+        DbCommands::Retention { command } => handle_retention_command(command, store)?,
+        DbCommands::Capture { mode, kind, clear } => {
+            let mut policy = store.capture_policy()?;
+            let kind = kind
+                .map(|k| k.parse::<NodeKind>().map_err(|e| anyhow::anyhow!(e)))
+                .transpose()?;
+            match (mode, kind) {
+                (None, Some(kind)) if clear => {
+                    policy.kinds.remove(&kind.to_string());
+                }
+                (Some(mode), kind) => {
+                    let mode: CaptureMode = mode.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                    match kind {
+                        Some(kind) => {
+                            policy.kinds.insert(kind.to_string(), mode);
+                        }
+                        None => policy.default = mode,
+                    }
+                }
+                _ => {
+                    println!("default          {}", policy.default);
+                    for (kind, mode) in &policy.kinds {
+                        println!("{:<16} {}", kind, mode);
+                    }
+                    return Ok(());
+                }
+            }
+            store.set_capture_policy(&policy)?;
+            println!("Capture policy updated");
+        }
+        DbCommands::Stamp { agent, fields, remove, clear } => {
+            let mut defaults = store.agent_defaults()?;
+            let Some(agent) = agent else {
+                for (agent, fields) in &defaults.agents {
+                    for (field, value) in fields {
+                        println!("{:<20} {}={}", agent, field, value);
+                    }
+                }
+                return Ok(());
+            };
+            let agent: AgentId = agent.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            if clear {
+                defaults.clear(&agent);
+            }
+            for field in &remove {
+                if !defaults.remove(&agent, field) {
+                    eprintln!("{} has no default for {}", agent, field);
+                }
+            }
+            for (key, value) in parse_fields(&fields)? {
+                defaults.set(&agent, &key, value)?;
+            }
+            if !clear && fields.is_empty() && remove.is_empty() {
+                for (field, value) in defaults.for_agent(&agent).into_iter().flatten() {
+                    println!("{}={}", field, value);
+                }
+                return Ok(());
+            }
+            store.set_agent_defaults(&defaults)?;
+            println!("Defaults for {} updated", agent);
+        }
+        DbCommands::Remaps { source, clear } => {
+            let Some(source) = source else {
+                println!("This instance: {}", store.instance_id()?);
+                return Ok(());
+            };
+            if clear {
+                println!("Forgot {} remapped node(s) from {}", store.clear_remaps(&source)?, source);
+                return Ok(());
+            }
+            for (remote, local) in store.remaps(&source)? {
+                println!("{} -> {}", remote, local);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Metadata key under which governance telemetry is persisted
+const GOVERNANCE_TELEMETRY_KEY: &str = "governance_telemetry";
+
+fn handle_report_command(command: ReportCommands, store: &Arc<SledStore>, db_path: &str) -> Result<()> {
+    match command {
+        ReportCommands::Governance { since, json } => {
+            let mut telemetry = GovernanceTelemetry::new();
+            if let Some(data) = store.get_meta(GOVERNANCE_TELEMETRY_KEY)? {
+                telemetry.import(data).map_err(|e| anyhow::anyhow!(e))?;
+            }
+
+            let since = since
+                .map(|s| parse_duration(&s).map(|d| chrono::Utc::now() - d))
+                .transpose()?;
+            let report = telemetry.report(since);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+
+            println!("Decisions: {}", report.decisions);
+            println!();
+            println!(
+                "{:<24} {:>9} {:>9} {:>9} {:>9} {:>10} {:>12}",
+                "STRATEGY", "DECIDED", "APPROVED", "REJECTED", "EXPIRED", "OVERTURNED", "AVG RESOLVE"
+            );
+            for (strategy, stats) in &report.by_strategy {
+                println!(
+                    "{:<24} {:>9} {:>9} {:>9} {:>9} {:>10} {:>11.0}s",
+                    strategy,
+                    stats.decisions,
+                    stats.approved,
+                    stats.rejected,
+                    stats.expired,
+                    stats.overturned,
+                    stats.avg_resolution_seconds
+                );
+            }
+
+            println!();
+            println!("{:<24} {:>7} {:>8} {:>10}", "AGENT", "VOTES", "ALIGNED", "INFLUENCE");
+            for (agent, influence) in &report.by_agent {
+                println!(
+                    "{:<24} {:>7} {:>8} {:>9.1}%",
+                    agent,
+                    influence.votes,
+                    influence.aligned,
+                    influence.influence * 100.0
+                );
+            }
+        }
+        ReportCommands::Maintenance { since, kind, json } => {
+            use elegant_state::store::maintenance;
+
+            let since = since
+                .map(|s| parse_duration(&s).map(|d| chrono::Utc::now() - d))
+                .transpose()?;
+            let mut records = store.maintenance_history()?;
+            if let Some(kind) = kind {
+                records.retain(|r| r.kind == kind);
+            }
+            let report = maintenance::report(&records, since);
+            // Trends need the earlier runs; the listing doesn't
+            let runs: Vec<_> = records
+                .iter()
+                .filter(|r| since.map_or(true, |since| r.started_at >= since))
+                .collect();
+
+            if json {
+                let runs = kind.map(|_| &runs);
+                println!("{}", serde_json::to_string_pretty(&serde_json::json!({ "report": report, "runs": runs }))?);
+                return Ok(());
+            }
+
+            if let Some(kind) = kind {
+                for record in &runs {
+                    let sizes = match (record.measured.bytes_before, record.measured.bytes_after) {
+                        (Some(before), Some(after)) => format!("{} -> {}", format_bytes(before), format_bytes(after)),
+                        _ => String::new(),
+                    };
+                    println!(
+                        "{}  {:>8}ms  {:>8} item(s)  {:<24} {}",
+                        record.started_at.format("%Y-%m-%d %H:%M:%S"),
+                        record.duration_ms,
+                        record.measured.items.map(|n| n.to_string()).unwrap_or_else(|| "-".into()),
+                        sizes,
+                        record.error.as_deref().map(|e| format!("FAILED: {}", e)).unwrap_or_default()
+                    );
+                }
+                if report.by_kind.is_empty() {
+                    println!("No {} runs recorded", kind);
+                    return Ok(());
+                }
+                println!();
+            }
+
+            println!(
+                "{:<12} {:>6} {:>7} {:>10} {:>10} {:>10} {:>7} {:>12}",
+                "OPERATION", "RUNS", "FAILED", "MEAN", "MAX", "LAST", "TREND", "RECLAIMED"
+            );
+            for (kind, summary) in &report.by_kind {
+                println!(
+                    "{:<12} {:>6} {:>7} {:>8.0}ms {:>8}ms {:>8}ms {:>7} {:>12}",
+                    kind,
+                    summary.runs,
+                    summary.failed,
+                    summary.mean_ms,
+                    summary.max_ms,
+                    summary.last_ms,
+                    summary.trend.map(|t| format!("x{:.2}", t)).unwrap_or_else(|| "-".into()),
+                    format_bytes(summary.reclaimed_bytes)
+                );
+            }
+            if report.by_kind.is_empty() {
+                println!("No maintenance runs recorded");
+            }
+        }
+        ReportCommands::Usage { since, window, file, json } => {
+            use elegant_state::graphql::usage::{self, AgentUsage};
+
+            let path = file.as_deref().map_or_else(|| request_journal_path(db_path), |p| expand_path(p).into());
+            let since = since
+                .map(|s| parse_duration(&s).map(|d| chrono::Utc::now() - d))
+                .transpose()?;
+            let window = window.map(|w| parse_duration(&w)).transpose()?;
+            if window.is_some_and(|w| w <= chrono::Duration::zero()) {
+                anyhow::bail!("Usage windows must be longer than zero");
+            }
+            let report = usage::report(&usage::read_journal(&path)?, since, window);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            if report.by_agent.is_empty() {
+                println!("No requests journalled in {}", path.display());
+                return Ok(());
+            }
+
+            let print_table = |by_agent: &std::collections::BTreeMap<String, AgentUsage>| {
+                println!(
+                    "{:<24} {:>9} {:>9} {:>9} {:>9} {:>7} {:>10} {:>10}",
+                    "AGENT", "REQUESTS", "MUTATIONS", "READS", "MUT/READ", "ERRORS", "MEAN", "MAX"
+                );
+                for (agent, u) in by_agent {
+                    println!(
+                        "{:<24} {:>9} {:>9} {:>9} {:>9} {:>6.1}% {:>8.1}ms {:>8.1}ms",
+                        agent,
+                        u.requests,
+                        u.mutations,
+                        u.reads,
+                        u.mutation_ratio.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "-".into()),
+                        u.error_rate * 100.0,
+                        u.mean_ms,
+                        u.max_ms
+                    );
+                }
+            };
+            print_table(&report.by_agent);
+            for window in &report.windows {
+                println!();
+                println!("{}", window.start.format("%Y-%m-%d %H:%M:%S"));
+                print_table(&window.by_agent);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn voting_strategy(
+    arg: VotingStrategyArg,
+    threshold: Option<f32>,
+    approver: Option<AgentId>,
+) -> Result<VotingStrategy> {
+    Ok(match arg {
+        VotingStrategyArg::Unanimous => VotingStrategy::Unanimous,
+        VotingStrategyArg::SimpleMajority => VotingStrategy::SimpleMajority,
+        VotingStrategyArg::Supermajority => VotingStrategy::Supermajority,
+        VotingStrategyArg::Weighted => VotingStrategy::Weighted {
+            threshold: threshold
+                .ok_or_else(|| anyhow::anyhow!("Weighted strategy requires a threshold"))?,
+        },
+        VotingStrategyArg::FirstVote => VotingStrategy::FirstVote,
+        VotingStrategyArg::SingleApprover => VotingStrategy::SingleApprover {
+            approver: approver.unwrap_or(AgentId::User),
+        },
+    })
+}
+
+/// Metadata keys under which coordination state is persisted
+const VOTE_BATCHES_KEY: &str = "vote_batches";
+const REPUTATIONS_KEY: &str = "reputations";
+const AUTO_APPROVAL_KEY: &str = "auto_approval";
+const ESCALATION_POLICY_KEY: &str = "escalation_policy";
+const ESCALATIONS_KEY: &str = "escalations";
+
+fn load_meta<T: serde::de::DeserializeOwned + Default>(store: &SledStore, key: &str) -> Result<T> {
+    Ok(match store.get_meta(key)? {
+        Some(value) => serde_json::from_value(value)?,
+        None => T::default(),
+    })
+}
+
+fn save_meta<T: serde::Serialize>(store: &SledStore, key: &str, value: &T) -> Result<()> {
+    store.set_meta(key, &serde_json::to_value(value)?)?;
+    Ok(())
+}
+
+/// Persisted proposals, votes, capabilities and reputations
+struct Governance {
+    proposals: ProposalManager,
+    voting: VotingCoordinator,
+    capabilities: CapabilityConfig,
+    reputation: ReputationTracker,
+}
+
+impl Governance {
+    fn load(store: &SledStore) -> Result<Self> {
+        let mut reputation = ReputationTracker::new();
+        if let Some(data) = store.get_meta(REPUTATIONS_KEY)? {
+            reputation.import(data).map_err(|e| anyhow::anyhow!(e))?;
+        }
+        Ok(Self {
+            proposals: load_meta(store, PROPOSALS_KEY)?,
+            voting: load_meta(store, VOTES_KEY)?,
+            capabilities: load_meta(store, CAPABILITIES_KEY)?,
+            reputation,
+        })
+    }
+
+    fn save(&self, store: &SledStore) -> Result<()> {
+        save_meta(store, PROPOSALS_KEY, &self.proposals)?;
+        save_meta(store, VOTES_KEY, &self.voting)
+    }
+
+    /// Record proposals that left pending in governance telemetry and
+    /// voter reputations
+    fn record_resolved(&mut self, store: &SledStore, ids: &[ProposalId]) -> Result<()> {
+        let mut telemetry: GovernanceTelemetry = load_meta(store, GOVERNANCE_TELEMETRY_KEY)?;
+        for id in ids {
+            let Some(proposal) = self.proposals.get(*id).filter(|p| !p.is_pending()) else {
+                continue;
+            };
+            let votes = self.voting.get_votes(*id);
+            telemetry.record(proposal, self.voting.strategy(), votes);
+            for earlier in self.proposals.all() {
+                if proposal.reverses(earlier) {
+                    telemetry.mark_overturned(earlier.id, Some(format!("reversed by proposal {}", id)));
+                }
+            }
+
+            let reason = proposal.resolution_reason.clone().unwrap_or_default();
+            let result = match proposal.status {
+                ProposalStatus::Approved => VotingResult::Approved { reason },
+                ProposalStatus::Rejected => VotingResult::Rejected { reason },
+                _ => continue,
+            };
+            for vote in votes {
+                self.reputation.record_outcome(&vote.voter, vote.decision, &result);
+            }
+        }
+        store.set_meta(GOVERNANCE_TELEMETRY_KEY, &telemetry.export())?;
+        store.set_meta(REPUTATIONS_KEY, &self.reputation.export())?;
+        Ok(())
+    }
+
+    fn vote(&mut self, store: &SledStore, vote: Vote) -> Result<String> {
+        let id = vote.proposal_id;
+        if !self.proposals.get(id).is_some_and(|p| p.is_pending()) {
+            anyhow::bail!("Proposal {} is not pending", id);
+        }
+        self.voting.cast_vote(vote, &self.capabilities).map_err(|e| anyhow::anyhow!(e))?;
+        let result = self.voting.process_proposal(id, &mut self.proposals);
+        self.save(store)?;
+        self.record_resolved(store, &[id])?;
+        Ok(format!("{:?}", result))
+    }
+}
+
+fn print_proposal(proposal: &Proposal) {
+    let target = match &proposal.target {
+        ProposalTarget::Node { id: Some(id), .. } => format!("node:{}", id),
+        ProposalTarget::Node { kind, .. } => format!("new:{}", kind.as_deref().unwrap_or("?")),
+        ProposalTarget::Edge { id: Some(id), .. } => format!("edge:{}", id),
+        ProposalTarget::Edge { .. } => "new edge".to_string(),
+    };
+    println!(
+        "{}  {:<9} {:?} {} by {}",
+        proposal.id, proposal.status, proposal.operation, target, proposal.proposer
+    );
+}
+
+fn parse_agent(agent: &str) -> Result<AgentId> {
+    agent.parse().map_err(|e: String| anyhow::anyhow!(e))
+}
+
+fn handle_proposal_command(command: ProposalCommands, store: &Arc<SledStore>) -> Result<()> {
+    let mut governance = Governance::load(store)?;
+    match command {
+        ProposalCommands::List { pending, mine, status, limit, sort, desc, verbose } => {
+            let status: Option<ProposalStatus> = match (pending, status) {
+                (true, _) => Some(ProposalStatus::Pending),
+                (false, Some(s)) => Some(s.parse().map_err(|e: String| anyhow::anyhow!(e))?),
+                (false, None) => None,
+            };
+            let mut proposals: Vec<&Proposal> = governance
+                .proposals
+                .all()
+                .into_iter()
+                .filter(|p| status.map_or(true, |s| p.status == s))
+                .filter(|p| !mine || p.proposer == AgentId::User)
+                .collect();
+            match parse_sort(sort.as_deref(), desc)? {
+                None => proposals.sort_by_key(|p| std::cmp::Reverse(p.created_at)),
+                Some(sort) => {
+                    // Proposals are one metadata record, so there is no index to walk
+                    match sort.key {
+                        SortKey::CreatedAt => proposals.sort_by_key(|p| (p.created_at, p.id)),
+                        SortKey::UpdatedAt => proposals.sort_by_key(|p| {
+                            let last_change = p.history.last().map(|h| h.timestamp).or(p.resolved_at);
+                            (last_change.unwrap_or(p.created_at), p.id)
+                        }),
+                        SortKey::Kind => {
+                            proposals.sort_by_key(|p| (p.target.kind().unwrap_or_default().to_string(), p.id))
+                        }
+                        SortKey::Degree => anyhow::bail!("Proposals can't be sorted by degree"),
+                    }
+                    if sort.descending {
+                        proposals.reverse();
+                    }
+                }
+            }
+            for proposal in proposals.into_iter().take(limit) {
+                print_proposal(proposal);
+                if verbose {
+                    if let Some(rationale) = &proposal.rationale {
+                        println!("    {}", rationale);
+                    }
+                }
+            }
+        }
+        ProposalCommands::Show { id, votes, payload } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let proposal = governance
+                .proposals
+                .get(id)
+                .ok_or_else(|| anyhow::anyhow!("No proposal {}", id))?;
+            print_proposal(proposal);
+            if let Some(rationale) = &proposal.rationale {
+                println!("Rationale: {}", rationale);
+            }
+            if let Some(reason) = &proposal.resolution_reason {
+                println!("Resolution: {}", reason);
+            }
+            if let Some(arbiter) = &proposal.escalated_to {
+                println!("Escalated to: {}", arbiter);
+            }
+            for entry in &proposal.history {
+                println!(
+                    "  {}  {}  {}",
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.status,
+                    entry.note.as_deref().unwrap_or("")
+                );
+            }
+            if payload {
+                println!("{}", serde_json::to_string_pretty(&proposal.payload)?);
+            }
+            if votes {
+                for vote in governance.voting.get_votes(id) {
+                    println!("  {} {} ({:.2})", vote.voter, vote.decision, vote.weight);
+                }
+            }
+        }
+        ProposalCommands::Create { operation, target, payload, rationale } => {
+            let operation: Operation = operation.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut target: ProposalTarget = target.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            if let ProposalTarget::Node { id: Some(id), kind } = &mut target {
+                *kind = store.get_node(*id)?.map(|n| n.kind.to_string());
+            }
+            let mut proposal =
+                Proposal::new(AgentId::User, operation, target, serde_json::from_str(&payload)?);
+            if let Some(rationale) = rationale {
+                proposal = proposal.with_rationale(rationale);
+            }
+            let id = governance.proposals.submit(proposal);
+            governance.save(store)?;
+            println!("Created proposal: {}", id);
+        }
+        ProposalCommands::Withdraw { id, reason } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let proposal = governance
+                .proposals
+                .get_mut(id)
+                .filter(|p| p.is_pending())
+                .ok_or_else(|| anyhow::anyhow!("No pending proposal {}", id))?;
+            proposal.withdraw();
+            proposal.resolution_reason = reason;
+            governance.save(store)?;
+            governance.record_resolved(store, &[id])?;
+            println!("Withdrew proposal: {}", id);
+        }
+        ProposalCommands::Approve { id, reason } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let mut vote = Vote::new(id, AgentId::User, VoteDecision::Approve);
+            if let Some(reason) = reason {
+                vote = vote.with_reason(reason);
+            }
+            println!("{}", governance.vote(store, vote)?);
+        }
+        ProposalCommands::Reject { id, reason } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let mut vote = Vote::new(id, AgentId::User, VoteDecision::Reject);
+            if let Some(reason) = reason {
+                vote = vote.with_reason(reason);
+            }
+            println!("{}", governance.vote(store, vote)?);
+        }
+        ProposalCommands::Votes { id, verbose } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            for vote in governance.voting.get_votes(id) {
+                println!("{} {} ({:.2})", vote.voter, vote.decision, vote.weight);
+                if verbose {
+                    if let Some(reason) = &vote.reason {
+                        println!("    {}", reason);
+                    }
+                }
+            }
+        }
+        ProposalCommands::Impact { node, all, dot } => {
+            let proposals: Vec<&Proposal> = governance
+                .proposals
+                .all()
+                .into_iter()
+                .filter(|p| all || p.is_pending())
+                .collect();
+            let endpoints = |id| store.get_edge(id).ok().flatten().map(|e| (e.from, e.to));
+            let graph = ImpactGraph::build(proposals.iter().copied(), endpoints);
+
+            if dot {
+                print!("{}", graph.to_dot(|vertex| match vertex {
+                    Vertex::Proposal(id) => governance
+                        .proposals
+                        .get(id)
+                        .map(|p| format!("{:?} by {} ({})", p.operation, p.proposer, p.status))
+                        .unwrap_or_else(|| id.to_string()),
+                    Vertex::Node(id) => match store.get_node(id).ok().flatten() {
+                        Some(n) => format!("{} {}", n.kind, id),
+                        None => id.to_string(),
+                    },
+                }));
+                return Ok(());
+            }
+
+            if let Some(node) = node {
+                let node = store.resolve_node(&node)?;
+                for id in graph.proposals_for(node) {
+                    if let Some(proposal) = governance.proposals.get(id) {
+                        print_proposal(proposal);
+                        let overlapping = graph.overlapping(id);
+                        if !overlapping.is_empty() {
+                            println!("    overlaps {} other proposal(s)", overlapping.len());
+                        }
+                    }
+                }
+                return Ok(());
+            }
+
+            let contended = graph.contended();
+            for (node, ids) in &contended {
+                println!("{}  touched by {} proposals", node, ids.len());
+                for id in ids {
+                    if let Some(proposal) = governance.proposals.get(*id) {
+                        print!("    ");
+                        print_proposal(proposal);
+                    }
+                }
+            }
+            println!(
+                "{} proposal(s), {} contended node(s)",
+                proposals.len(),
+                contended.len()
+            );
+        }
+        ProposalCommands::AutoApprove { command } => {
+            handle_auto_approve_command(command, store, &mut governance)?
+        }
+        ProposalCommands::Escalation { command } => {
+            handle_escalation_command(command, store, &mut governance)?
+        }
+    }
+    Ok(())
+}
+
+fn handle_escalation_command(
+    command: EscalationCommands,
+    store: &SledStore,
+    governance: &mut Governance,
+) -> Result<()> {
+    let mut policy: EscalationPolicy = load_meta(store, ESCALATION_POLICY_KEY)?;
+    match command {
+        EscalationCommands::Add { strategy, after, arbiter } => {
+            let mut rule =
+                EscalationRule::new(parse_duration(&after)?.num_seconds(), parse_agent(&arbiter)?);
+            if let Some(strategy) = strategy {
+                rule = rule.for_strategy(strategy.to_lowercase());
+            }
+            println!("Added rule {}", rule);
+            policy.add(rule);
+            save_meta(store, ESCALATION_POLICY_KEY, &policy)?;
+        }
+        EscalationCommands::Remove { strategy } => {
+            let strategy = strategy.map(|s| s.to_lowercase());
+            if !policy.remove(strategy.as_deref()) {
+                anyhow::bail!("No escalation rule for {}", strategy.as_deref().unwrap_or("any"));
+            }
+            save_meta(store, ESCALATION_POLICY_KEY, &policy)?;
+            println!("Removed rule for {}", strategy.as_deref().unwrap_or("any"));
+        }
+        EscalationCommands::Rules => {
+            for rule in policy.rules() {
+                println!("{}", rule);
+            }
+        }
+        EscalationCommands::Run => {
+            let escalated = policy.escalate_stalled(
+                &mut governance.proposals,
+                &governance.voting,
+                chrono::Utc::now(),
+            );
+            governance.save(store)?;
+            let mut inbox: Vec<Escalation> = load_meta(store, ESCALATIONS_KEY)?;
+            inbox.extend(escalated.iter().cloned());
+            save_meta(store, ESCALATIONS_KEY, &inbox)?;
+            for escalation in &escalated {
+                println!(
+                    "Escalated {} to {} ({} stalled)",
+                    escalation.proposal_id, escalation.arbiter, escalation.strategy
+                );
+            }
+            println!("{} proposal(s) escalated", escalated.len());
+        }
+        EscalationCommands::Inbox { arbiter } => {
+            let arbiter = arbiter.as_deref().map(parse_agent).transpose()?;
+            let inbox: Vec<Escalation> = load_meta(store, ESCALATIONS_KEY)?;
+            for escalation in inbox
+                .iter()
+                .filter(|e| arbiter.as_ref().map_or(true, |a| &e.arbiter == a))
+            {
+                let Some(proposal) =
+                    governance.proposals.get(escalation.proposal_id).filter(|p| p.is_pending())
+                else {
+                    continue;
+                };
+                print_proposal(proposal);
+                println!(
+                    "    waiting on {} since {}",
+                    escalation.arbiter,
+                    escalation.escalated_at.format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_auto_approve_command(
+    command: AutoApproveCommands,
+    store: &SledStore,
+    governance: &mut Governance,
+) -> Result<()> {
+    let mut policy: AutoApprovalPolicy = load_meta(store, AUTO_APPROVAL_KEY)?;
+    match command {
+        AutoApproveCommands::Add { name, filter, min_reputation, max_payload, grace } => {
+            let filter: ProposalFilter = filter.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut rule = AutoApprovalRule::new(name, filter)
+                .with_grace_period(parse_duration(&grace)?.num_seconds());
+            if let Some(score) = min_reputation {
+                rule = rule.with_min_reputation(score);
+            }
+            if let Some(bytes) = max_payload {
+                rule = rule.with_max_payload_bytes(bytes);
+            }
+            println!("Added rule {}", rule);
+            policy.add(rule);
+            save_meta(store, AUTO_APPROVAL_KEY, &policy)?;
+        }
+        AutoApproveCommands::Remove { name } => {
+            if !policy.remove(&name) {
+                anyhow::bail!("No auto-approval rule named {}", name);
+            }
+            save_meta(store, AUTO_APPROVAL_KEY, &policy)?;
+            println!("Removed rule {}", name);
+        }
+        AutoApproveCommands::List => {
+            for rule in policy.rules() {
+                println!("{}", rule);
+            }
+        }
+        AutoApproveCommands::Run { dry_run } => {
+            let now = chrono::Utc::now();
+            if dry_run {
+                let eligible = policy.eligible(
+                    &governance.proposals,
+                    &governance.voting,
+                    &governance.reputation,
+                    now,
+                );
+                for (id, rule) in &eligible {
+                    if let Some(proposal) = governance.proposals.get(*id) {
+                        print_proposal(proposal);
+                        println!("    by rule {}", rule);
+                    }
+                }
+                println!("{} proposal(s) would be auto-approved", eligible.len());
+                return Ok(());
+            }
+
+            let approved = policy.apply(
+                &mut governance.proposals,
+                &governance.voting,
+                &governance.reputation,
+                now,
+            );
+            governance.save(store)?;
+            let ids: Vec<ProposalId> = approved.iter().map(|(id, _)| *id).collect();
+            governance.record_resolved(store, &ids)?;
+            for id in &ids {
+                if let Some(proposal) = governance.proposals.get(*id) {
+                    print_proposal(proposal);
+                }
+            }
+            println!("Auto-approved {} proposal(s)", ids.len());
+        }
+    }
+    Ok(())
+}
+
+fn handle_vote_command(command: VoteCommands, store: &Arc<SledStore>) -> Result<()> {
+    let mut governance = Governance::load(store)?;
+    match command {
+        VoteCommands::Cast { id, decision, agent, reason } => {
+            let id = id.parse().map_err(|e| anyhow::anyhow!("Invalid ID: {}", e))?;
+            let decision: VoteDecision = decision.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let mut vote = Vote::new(id, parse_agent(&agent)?, decision);
+            if let Some(reason) = reason {
+                vote = vote.with_reason(reason);
+            }
+            println!("{}", governance.vote(store, vote)?);
+        }
+        VoteCommands::Batch { filter, decision, agent, reason, dry_run } => {
+            let filter: ProposalFilter = filter.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            let decision: VoteDecision = decision.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+            if dry_run {
+                let matching = governance.proposals.matching(&filter);
+                for proposal in &matching {
+                    print_proposal(proposal);
+                }
+                println!("{} proposal(s) would get a {} vote", matching.len(), decision);
+                return Ok(());
+            }
+
+            let batch = governance
+                .voting
+                .cast_batch(
+                    parse_agent(&agent)?,
+                    decision,
+                    &filter,
+                    reason,
+                    &mut governance.proposals,
+                    &governance.capabilities,
+                )
+                .map_err(|e| anyhow::anyhow!(e))?;
+            governance.save(store)?;
+            let voted: Vec<ProposalId> = batch.votes.iter().map(|(id, _)| *id).collect();
+            governance.record_resolved(store, &voted)?;
+
+            let mut batches: Vec<BatchVote> = load_meta(store, VOTE_BATCHES_KEY)?;
+            batches.push(batch.clone());
+            save_meta(store, VOTE_BATCHES_KEY, &batches)?;
+
+            for id in &voted {
+                if let Some(proposal) = governance.proposals.get(*id) {
+                    print_proposal(proposal);
+                }
+            }
+            for (id, error) in &batch.failed {
+                eprintln!("warning: {}: {}", id, error);
+            }
+            println!("Batch {}: {} vote(s) cast, {} failed", batch.id, voted.len(), batch.failed.len());
+        }
+        VoteCommands::Batches { limit } => {
+            let batches: Vec<BatchVote> = load_meta(store, VOTE_BATCHES_KEY)?;
+            for batch in batches.iter().rev().take(limit) {
+                println!(
+                    "{}  {}  {} {} on {} proposal(s) where {}",
+                    batch.id,
+                    batch.timestamp.format("%Y-%m-%d %H:%M"),
+                    batch.voter,
+                    batch.decision,
+                    batch.votes.len(),
+                    batch.filter
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_coordinator_command(command: CoordinatorCommands) -> Result<()> {
+    match command {
+        CoordinatorCommands::Simulate {
+            agents,
+            proposals,
+            strategy,
+            threshold,
+            mix,
+            honest_accuracy,
+            no_reputation,
+            seed,
+            json,
+        } => {
+            let mut config = match mix {
+                Some(mix) => {
+                    let counts: Vec<usize> = mix
+                        .split(',')
+                        .map(|s| s.trim().parse())
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|e| anyhow::anyhow!("Invalid mix: {}", e))?;
+                    if counts.len() != 3 {
+                        anyhow::bail!("Mix must be honest,random,adversarial counts");
+                    }
+                    SimulationConfig::with_mix(counts[0], counts[1], counts[2], proposals)
+                }
+                None => SimulationConfig::new(agents, proposals),
+            };
+
+            let agent_count = config.behaviors.len();
+            let strategy = voting_strategy(
+                strategy,
+                Some(threshold.unwrap_or(agent_count as f32 * 0.5)),
+                Some(SimulationConfig::agent_id(0)),
+            )?;
+            config = config
+                .with_strategy(strategy)
+                .with_reputation_weighting(!no_reputation);
+            config.honest_accuracy = honest_accuracy;
+            if let Some(seed) = seed {
+                config = config.with_seed(seed);
+            }
+
+            let report = Simulation::new(config).run();
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+
+            println!("Strategy: {}", report.strategy);
+            println!(
+                "Proposals: {} (approved {}, rejected {}, unresolved {})",
+                report.proposals, report.approved, report.rejected, report.unresolved
+            );
+            println!(
+                "Decision accuracy: {:.1}% (false approvals {}, false rejections {})",
+                report.decision_accuracy() * 100.0,
+                report.false_approvals,
+                report.false_rejections
+            );
+
+            println!();
+            println!("Convergence:");
+            for checkpoint in &report.checkpoints {
+                let reps: Vec<String> = checkpoint
+                    .mean_reputation
+                    .iter()
+                    .map(|(behavior, score)| format!("{}={:.2}", behavior, score))
+                    .collect();
+                println!(
+                    "  after {:>5}: accuracy {:>5.1}%  {}",
+                    checkpoint.proposals_processed,
+                    checkpoint.decision_accuracy * 100.0,
+                    reps.join(" ")
+                );
+            }
+
+            println!();
+            println!("Agents:");
+            for agent in &report.agents {
+                println!(
+                    "  {:<16} {:<12} reputation {:.2}  accuracy {:>5.1}%  weight {:.2}",
+                    agent.agent.to_string(),
+                    agent.behavior.to_string(),
+                    agent.reputation,
+                    agent.accuracy * 100.0,
+                    agent.vote_weight
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Default slow-query log: `slow-queries.log` in the database's parent directory
+fn slow_query_log_path(db_path: &str) -> std::path::PathBuf {
+    std::path::Path::new(db_path)
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("slow-queries.log")
+}
+
+/// How often `serve` publishes full-text index changes
+const INDEX_COMMIT_SECS: u64 = 5;
+
+/// Default full-text index directory: `fulltext` in the database's parent directory
+fn fulltext_index_path(db_path: &str) -> std::path::PathBuf {
+    std::path::Path::new(db_path)
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("fulltext")
+}
+
+fn request_journal_path(db_path: &str) -> std::path::PathBuf {
+    std::path::Path::new(db_path)
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("requests.log")
+}
+
+async fn handle_serve_command(
+    command: ServeCommands,
+    store: Arc<SledStore>,
+    index_sync: Option<IndexSync>,
+    db_path: &str,
+) -> Result<()> {
+    match command {
+        ServeCommands::Http {
+            port,
+            host,
+            gc_interval,
+            watchdog_interval,
+            slow_op_ms,
+            cors_origins,
+            cors_methods,
+            cors_headers,
+            max_body,
+            slow_query_ms,
+            slow_query_log,
+            slow_query_log_size,
+            slow_query_log_files,
+            request_journal,
+            no_request_journal,
+            config,
+            record_cassette,
+        } => {
+            use async_graphql_axum::GraphQLRequest;
+            use axum::{routing::post, Extension, Router};
+            use elegant_state::graphql::request_log::{operation_label, root_fields, RequestRecord, SlowQueryLog};
+            use elegant_state::graphql::usage::RequestJournal;
+            use elegant_state_client::cassette::Recorder;
+            use tower_http::set_header::SetResponseHeaderLayer;
+
+            store
+                .metrics_registry()
+                .set_slow_op_threshold((slow_op_ms > 0).then(|| std::time::Duration::from_millis(slow_op_ms)));
+
+            if gc_interval > 0 && !store.is_read_only() {
+                spawn_expiry_sweeper(store.clone(), std::time::Duration::from_secs(gc_interval));
+            }
+
+            if let Some(sync) = &index_sync {
+                sync.spawn_commits(std::time::Duration::from_secs(INDEX_COMMIT_SECS));
+            }
+
+            let schema = build_schema(store.clone());
+            let live = Arc::new(LiveConfig::load(store.clone(), config.map(|p| expand_path(&p).into()))?);
+            live.notify_changes();
+            let watchdog = Arc::new(Watchdog::new(store.clone(), live.clone()));
+            if watchdog_interval > 0 && !store.is_read_only() {
+                spawn_watchdog(watchdog.clone(), std::time::Duration::from_secs(watchdog_interval));
+            }
+            #[cfg(unix)]
+            {
+                let live = live.clone();
+                let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+                tokio::spawn(async move {
+                    while hangups.recv().await.is_some() {
+                        let live = live.clone();
+                        match tokio::task::spawn_blocking(move || live.reload("sighup")).await {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(e)) => tracing::error!("configuration reload failed: {}", e),
+                            Err(e) => tracing::error!("configuration reload failed: {}", e),
+                        }
+                    }
+                });
+            }
+
+            let slow_log = (slow_query_ms > 0).then(|| -> Result<Arc<SlowQueryLog>> {
+                let path = slow_query_log.map_or_else(|| slow_query_log_path(db_path), |p| expand_path(&p).into());
+                Ok(Arc::new(
+                    SlowQueryLog::new(path)
+                        .with_threshold(std::time::Duration::from_millis(slow_query_ms))
+                        .with_max_bytes(parse_size(&slow_query_log_size)? as u64)
+                        .with_max_files(slow_query_log_files),
+                ))
+            });
+            let slow_log = slow_log.transpose()?;
+            if let Some(log) = &slow_log {
+                println!("Logging queries slower than {}ms to {}", slow_query_ms, log.path().display());
+            }
+            // The journal is a slow-query log that every request clears
+            let journal = (!no_request_journal).then(|| {
+                let path = request_journal.map_or_else(|| request_journal_path(db_path), |p| expand_path(&p).into());
+                Arc::new(SlowQueryLog::new(path).with_threshold(std::time::Duration::ZERO))
+            });
+            if let Some(journal) = &journal {
+                println!("Journalling requests to {}", journal.path().display());
+            }
+            let recorder = record_cassette.map(|path| Arc::new(Recorder::new(expand_path(&path))));
+            if let Some(recorder) = &recorder {
+                println!("Recording requests to cassette {}", recorder.path().display());
+            }
+
+            /// Kept apart from the slow-query log's extension by type
+            #[derive(Clone)]
+            struct Journal(Option<Arc<SlowQueryLog>>);
+
+            async fn graphql_handler(
+                Extension(schema): Extension<elegant_state::StateSchema>,
+                Extension(slow_log): Extension<Option<Arc<SlowQueryLog>>>,
+                Extension(journal): Extension<Journal>,
+                Extension(recorder): Extension<Option<Arc<Recorder>>>,
+                Extension(live): Extension<Arc<LiveConfig>>,
+                Extension(watchdog): Extension<Arc<Watchdog>>,
+                headers: axum::http::HeaderMap,
+                req: GraphQLRequest,
+            ) -> axum::response::Response {
+                use async_graphql::parser::types::OperationType;
+                use axum::{http::{header, StatusCode}, response::IntoResponse};
+
+                let header_value =
+                    |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+                let agent = header_value(elegant_state::AGENT_HEADER);
+                let namespace = header_value(elegant_state::NAMESPACE_HEADER);
+                let agent_name = agent.as_deref().unwrap_or("anonymous");
+
+                let mut request = req.into_inner();
+                if let Some(namespace) = &namespace {
+                    request = request.data(elegant_state::Namespace(namespace.clone()));
+                }
+                if let Some(journal) = &journal.0 {
+                    request = request.data(RequestJournal(journal.path().to_path_buf()));
+                }
+                let operation = operation_label(&request.query, request.operation_name.as_deref());
+                let mutation = match root_fields(&request.query, request.operation_name.as_deref()) {
+                    Some((OperationType::Mutation, fields)) => Some(fields),
+                    _ => None,
+                };
+                let query = slow_log.is_some().then(|| request.query.clone());
+                let cassette_request = recorder
+                    .is_some()
+                    .then(|| (request.query.clone(), serde_json::to_value(&request.variables).unwrap_or_default()));
+
+                // The configuration this request runs under, even if a reload lands meanwhile
+                let config = live.current();
+                let refused = if !live.admit(agent_name) {
+                    watchdog.record_refusal(agent_name, Refusal::RateLimited);
+                    Some((StatusCode::TOO_MANY_REQUESTS, format!("Rate limit exceeded for {}", agent_name)))
+                } else if mutation.is_some() && !config.may_mutate(agent_name) {
+                    watchdog.record_refusal(agent_name, Refusal::Denied);
+                    Some((StatusCode::FORBIDDEN, format!("{} is an observer and may not mutate", agent_name)))
+                } else {
+                    None
+                };
+
+                let started = std::time::Instant::now();
+                let (status, body, errors, http_headers) = match refused {
+                    Some((status, message)) => {
+                        let body = serde_json::json!({ "errors": [{ "message": message }] });
+                        (status, body.to_string().into_bytes(), vec![message], Default::default())
+                    }
+                    None => {
+                        let response = schema.execute(request).await;
+                        let errors: Vec<String> = response.errors.iter().map(|e| e.message.clone()).collect();
+                        let body = match serde_json::to_vec(&response) {
+                            Ok(body) => body,
+                            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                        };
+                        if let (Some(recorder), Some((query, variables))) = (&recorder, &cassette_request) {
+                            let recorded = serde_json::from_slice(&body).unwrap_or_default();
+                            if let Err(e) = recorder.record(query, variables, &recorded) {
+                                tracing::warn!("could not write cassette {}: {}", recorder.path().display(), e);
+                            }
+                        }
+                        (StatusCode::OK, body, errors, response.http_headers)
+                    }
+                };
+                let duration = started.elapsed();
+
+                let mut record = RequestRecord {
+                    timestamp: chrono::Utc::now(),
+                    agent,
+                    namespace,
+                    operation,
+                    mutation: mutation.is_some(),
+                    duration_ms: duration.as_secs_f64() * 1000.0,
+                    response_bytes: body.len(),
+                    errors,
+                    query: None,
+                };
+                record.log();
+                if let Some(fields) = mutation.filter(|_| status.is_success() && record.errors.is_empty()) {
+                    config.notify(&record, &fields);
+                }
+                if let Some(journal) = &journal.0 {
+                    if let Err(e) = journal.record(&record) {
+                        tracing::warn!("could not write request journal {}: {}", journal.path().display(), e);
+                    }
+                }
+                if let Some(slow_log) = &slow_log {
+                    if slow_log.is_slow(&record) {
+                        record.query = query;
+                        if let Err(e) = slow_log.record(&record) {
+                            tracing::warn!("could not write slow-query log {}: {}", slow_log.path().display(), e);
+                        }
+                    }
+                }
+
+                let mut response = (status, [(header::CONTENT_TYPE, "application/json")], body).into_response();
+                response.headers_mut().extend(http_headers);
+                response
+            }
+
+            /// Admin endpoints only answer on the loopback interface
+            async fn admin_guard(
+                axum::extract::ConnectInfo(peer): axum::extract::ConnectInfo<std::net::SocketAddr>,
+                request: axum::extract::Request,
+                next: axum::middleware::Next,
+            ) -> axum::response::Response {
+                use axum::{http::StatusCode, response::IntoResponse};
+
+                if !peer.ip().is_loopback() {
+                    return (StatusCode::FORBIDDEN, "Admin endpoints are only served to localhost").into_response();
+                }
+                next.run(request).await
+            }
+
+            async fn reload_handler(Extension(live): Extension<Arc<LiveConfig>>) -> axum::response::Response {
+                use axum::{http::StatusCode, response::IntoResponse, Json};
+
+                let result = {
+                    let live = live.clone();
+                    tokio::task::spawn_blocking(move || live.reload("endpoint")).await
+                };
+                match result {
+                    Ok(Ok(changed)) => Json(serde_json::json!({
+                        "version": live.version(),
+                        "changed": changed,
+                    }))
+                    .into_response(),
+                    Ok(Err(e)) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+                    Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                }
+            }
+
+            async fn config_handler(
+                Extension(live): Extension<Arc<LiveConfig>>,
+                Extension(store): Extension<Arc<SledStore>>,
+            ) -> axum::response::Response {
+                use axum::{http::StatusCode, response::IntoResponse, Json};
+
+                match server_config::config_changes(&store) {
+                    Ok(changes) => Json(serde_json::json!({
+                        "version": live.version(),
+                        "file": live.file(),
+                        "config": &*live.current(),
+                        "changes": changes,
+                    }))
+                    .into_response(),
+                    Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                }
+            }
+
+            async fn reinstate_handler(
+                Extension(live): Extension<Arc<LiveConfig>>,
+                Extension(store): Extension<Arc<SledStore>>,
+                axum::extract::Path(agent): axum::extract::Path<String>,
+            ) -> axum::response::Response {
+                use axum::{http::StatusCode, response::IntoResponse, Json};
+
+                let result = tokio::task::spawn_blocking(move || -> elegant_state::store::Result<_> {
+                    let lifted = watchdog::reinstate(&store, &agent)?;
+                    if lifted.is_some() {
+                        live.reload("reinstate")?;
+                    }
+                    Ok(lifted)
+                })
+                .await;
+                match result {
+                    Ok(Ok(Some(demotion))) => Json(demotion).into_response(),
+                    Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+                    Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                    Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                }
+            }
+
+            async fn share_handler(
+                Extension(store): Extension<Arc<SledStore>>,
+                axum::extract::Path(token): axum::extract::Path<String>,
+            ) -> axum::response::Response {
+                use axum::{http::StatusCode, response::IntoResponse, Json};
+                use elegant_state::StoreError;
+
+                let result = tokio::task::spawn_blocking(move || store.open_share(&token)).await;
+                match result {
+                    Ok(Ok(graph)) => Json(graph).into_response(),
+                    Ok(Err(e @ (StoreError::ShareDenied(_) | StoreError::ShareNotFound(_)))) => {
+                        (StatusCode::FORBIDDEN, e.to_string()).into_response()
+                    }
+                    Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                    Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                }
+            }
+
+            #[derive(serde::Deserialize)]
+            struct AttachmentQuery {
+                namespace: Option<String>,
+            }
+
+            async fn attachment_handler(
+                Extension(store): Extension<Arc<SledStore>>,
+                axum::extract::Path((node, hash)): axum::extract::Path<(String, String)>,
+                axum::extract::Query(query): axum::extract::Query<AttachmentQuery>,
+            ) -> axum::response::Response {
+                use axum::{http::{header, StatusCode}, response::IntoResponse};
+
+                let result = tokio::task::spawn_blocking(move || {
+                    let store = match query.namespace {
+                        Some(ns) => store.namespaced(ns)?,
+                        None => (*store).clone(),
+                    };
+                    store.get_attachment(store.resolve_node(&node)?, &hash)
+                })
+                .await;
+                match result {
+                    Ok(Ok(Some((attachment, bytes)))) => {
+                        ([(header::CONTENT_TYPE, attachment.mime)], bytes).into_response()
+                    }
+                    Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+                    Ok(Err(e @ elegant_state::StoreError::UnknownNodeRef(_))) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+                    Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                    Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+                }
+            }
+
+            async fn metrics_handler(Extension(store): Extension<Arc<SledStore>>) -> impl axum::response::IntoResponse {
+                use axum::http::header;
+                (
+                    [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                    store.metrics().to_prometheus(),
+                )
+            }
+
+            let max_body = parse_size(&max_body)?;
+            let cors = cors_layer(&cors_origins, &cors_methods, &cors_headers)?;
+
+            let versioned = format!("/graphql/{}", elegant_state::API_VERSION);
+            let mut app = Router::new()
+                .route(&versioned, post(graphql_handler))
+                // Unversioned path always serves the current version
+                .route("/graphql", post(graphql_handler))
+                // Read-only subgraph behind a signed, expiring token
+                .route("/share/:token", axum::routing::get(share_handler))
+                .route("/attachments/:node/:hash", axum::routing::get(attachment_handler))
+                // Store operation metrics in Prometheus text format
+                .route("/metrics", axum::routing::get(metrics_handler))
+                // Reload or inspect the live configuration
+                .nest(
+                    "/admin",
+                    Router::new()
+                        .route("/reload", post(reload_handler))
+                        .route("/config", axum::routing::get(config_handler))
+                        // Lift a watchdog demotion
+                        .route("/reinstate/:agent", post(reinstate_handler))
+                        .layer(axum::middleware::from_fn(admin_guard)),
+                )
+                .layer(Extension(schema))
+                .layer(Extension(store))
+                .layer(Extension(slow_log))
+                .layer(Extension(Journal(journal)))
+                .layer(Extension(recorder))
+                .layer(Extension(live))
+                .layer(Extension(watchdog))
+                .layer(tower_http::limit::RequestBodyLimitLayer::new(max_body))
+                .layer(axum::extract::DefaultBodyLimit::max(max_body));
+            for (name, value) in security_headers() {
+                app = app.layer(SetResponseHeaderLayer::if_not_present(name, value));
+            }
+            // Outermost, so preflight requests are answered before anything else
+            if let Some(cors) = cors {
+                app = app.layer(cors);
+            }
+
+            let addr = format!("{}:{}", host, port);
+            println!("GraphQL server running at http://{}{}", addr, versioned);
+
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
+        }
+        ServeCommands::Logs { command } => handle_serve_logs_command(&command, db_path).await?,
+    }
+    Ok(())
+}
+
+async fn handle_serve_logs_command(command: &ServeLogsCommands, db_path: &str) -> Result<()> {
+    use elegant_state::graphql::request_log::{self, RequestRecord};
+
+    match command {
+        ServeLogsCommands::Tail { lines, file, follow, json } => {
+            let path = file.as_deref().map_or_else(|| slow_query_log_path(db_path), |p| expand_path(p).into());
+            let print = |record: &RequestRecord| -> Result<()> {
+                if *json {
+                    println!("{}", serde_json::to_string(record)?);
+                } else {
+                    println!(
+                        "{} {:>9.1}ms {:>8}  {}  agent={} namespace={}{}",
+                        record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                        record.duration_ms,
+                        format_bytes(record.response_bytes as u64),
+                        record.operation,
+                        record.agent.as_deref().unwrap_or("-"),
+                        record.namespace.as_deref().unwrap_or("-"),
+                        if record.errors.is_empty() {
+                            String::new()
+                        } else {
+                            format!("  errors: {}", record.errors.join("; "))
+                        },
+                    );
+                }
+                Ok(())
+            };
+
+            for record in request_log::tail(&path, *lines)? {
+                print(&record)?;
+            }
+            if !*follow {
+                return Ok(());
+            }
+
+            // Poll the live file; a shrinking file means it was rotated
+            let mut seen = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            loop {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                if len < seen {
+                    seen = 0;
+                }
+                if len == seen {
+                    continue;
+                }
+                let bytes = std::fs::read(&path)?;
+                let end = bytes[seen as usize..].iter().rposition(|&b| b == b'\n').map(|i| seen as usize + i + 1);
+                let Some(end) = end else { continue };
+                for line in String::from_utf8_lossy(&bytes[seen as usize..end]).lines() {
+                    if let Ok(record) = serde_json::from_str::<RequestRecord>(line) {
+                        print(&record)?;
+                    }
+                }
+                seen = end as u64;
+            }
+        }
+    }
+}
+
+async fn watch_server(
+    url: &str,
+    filters: &[WatchFilter],
+    filter_file: Option<&str>,
+    interval: &str,
+    headers: &[String],
+    token: Option<&str>,
+    namespace: Option<&str>,
+) -> Result<()> {
+    let mut filters = filters.to_vec();
+    if let Some(path) = filter_file {
+        let loaded: Vec<WatchFilter> = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        filters.extend(loaded);
+    }
+    if filters.is_empty() {
+        // Report everything, quietly
+        filters.push(WatchFilter { name: "all".into(), ..Default::default() });
+    }
+    let interval = parse_duration(interval)?
+        .to_std()
+        .map_err(|_| anyhow::anyhow!("Invalid interval: {}", interval))?;
+
+    let mut client = GraphqlClient::new(url);
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid header (expected \"Name: value\"): {}", header))?;
+        client = client.with_header(name.trim(), value.trim());
+    }
+    if let Some(token) = token {
+        client = client.with_bearer_token(token);
+    }
+    if let Some(namespace) = namespace {
+        client = client.with_namespace(namespace);
+    }
+
+    let mut watcher = Watcher::new(client);
+    watcher.poll().map_err(|e| anyhow::anyhow!("{}: {}", url, e))?;
+    eprintln!("Watching {} with {} filter(s)", url, filters.len());
+    loop {
+        tokio::time::sleep(interval).await;
+        let events = match watcher.poll() {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("warning: {}", e);
+                continue;
+            }
+        };
+        for event in events {
+            let Some(filter) = filters.iter().find(|f| f.matches(&event)) else {
+                continue;
+            };
+            let summary = event.summary();
+            println!("[{}] {}: {}", event.timestamp, filter.name, summary);
+            watch::alert(&filter.alerts, &format!("elegant-state: {}", filter.name), &summary);
+        }
+    }
+}
+
+/// CORS policy for `serve http`; `None` when no origin is allowed
+fn cors_layer(
+    origins: &[String],
+    methods: &[String],
+    headers: &[String],
+) -> Result<Option<tower_http::cors::CorsLayer>> {
+    use axum::http::{HeaderName, HeaderValue, Method};
+    use tower_http::cors::{AllowOrigin, CorsLayer};
+
+    if origins.is_empty() {
+        return Ok(None);
+    }
+    let origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| HeaderValue::from_str(o.trim_end_matches('/')))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid CORS origin: {}", e))?;
+        AllowOrigin::list(origins)
+    };
+    let methods = methods
+        .iter()
+        .map(|m| m.trim().to_ascii_uppercase().parse::<Method>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("Invalid CORS method: {}", e))?;
+    let headers = headers
+        .iter()
+        .map(|h| h.trim().parse::<HeaderName>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("Invalid CORS header: {}", e))?;
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .max_age(std::time::Duration::from_secs(600)),
+    ))
+}
+
+/// Standard hardening headers on every response
+///
+/// The API serves JSON and attachments only, so nothing should be framed,
+/// sniffed or allowed to load further resources.
+fn security_headers() -> Vec<(axum::http::HeaderName, axum::http::HeaderValue)> {
+    use axum::http::{header, HeaderValue};
+
+    vec![
+        (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        (header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+        (header::REFERRER_POLICY, HeaderValue::from_static("no-referrer")),
+        (
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'none'; frame-ancestors 'none'"),
+        ),
+        (
+            axum::http::HeaderName::from_static("cross-origin-resource-policy"),
+            HeaderValue::from_static("same-site"),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("4k").unwrap(), 4 << 10);
+        assert_eq!(parse_size(" 2 MiB ").unwrap(), 2 << 20);
+        assert_eq!(parse_size("1GB").unwrap(), 1 << 30);
+
+        for bad in ["4XB", "MB", "-1", "1.5m", ""] {
+            assert!(parse_size(bad).is_err(), "{:?} parsed", bad);
+        }
+        // Too large for a usize before and after scaling
+        assert!(parse_size("99999999999999999999999").is_err());
+        assert!(parse_size(&format!("{}k", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_cors_layer() {
+        let origins = ["https://app.example/".to_string()];
+        let get = ["GET".to_string()];
+        assert!(cors_layer(&[], &get, &[]).unwrap().is_none());
+        assert!(cors_layer(&origins, &["get".into(), "post".into()], &["content-type".into()]).unwrap().is_some());
+        assert!(cors_layer(&["*".into()], &get, &[]).unwrap().is_some());
+
+        assert!(cors_layer(&origins, &["NOT A METHOD".into()], &[]).is_err());
+        assert!(cors_layer(&origins, &get, &["bad header".into()]).is_err());
+        assert!(cors_layer(&["https://app.example\n".into()], &get, &[]).is_err());
+    }
+
+    #[test]
+    fn test_security_headers() {
+        let headers = security_headers();
+        let names: HashSet<_> = headers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names.len(), headers.len());
+        assert!(headers.iter().any(|(name, value)| name == "x-content-type-options" && value == "nosniff"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Content-type aware rendering of node content
+//!
+//! A node declares its content type in the `content_type` metadata key
+//! (either a MIME type such as `text/csv` or a short name such as `csv`).
+//! Without one, the type is guessed from the extension of `content.source`,
+//! then from the shape of the content.
+//!
+//! Markdown is rendered to HTML with pandoc and to ANSI with a built-in
+//! formatter, CSV becomes a table, and Mermaid diagrams are rendered to SVG
+//! with the mermaid CLI (`mmdc`).
+
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+use crate::schema::StateNode;
+use crate::store::{InputFormat, OutputFormat, PandocConverter};
+
+/// Metadata key declaring a node's content type
+pub const CONTENT_TYPE_KEY: &str = "content_type";
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    #[error("{0}")]
+    Tool(String),
+
+    #[error("Invalid {0} content: {1}")]
+    Invalid(ContentType, String),
+}
+
+pub type Result<T> = std::result::Result<T, RenderError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Markdown,
+    Csv,
+    Mermaid,
+    Json,
+    Plain,
+}
+
+impl std::fmt::Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ContentType::Markdown => "markdown",
+            ContentType::Csv => "csv",
+            ContentType::Mermaid => "mermaid",
+            ContentType::Json => "json",
+            ContentType::Plain => "plain",
+        })
+    }
+}
+
+impl std::str::FromStr for ContentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        // Drop MIME parameters such as "; charset=utf-8"
+        let s = s.split(';').next().unwrap_or_default().trim();
+        match s {
+            "markdown" | "md" | "text/markdown" | "text/x-markdown" => Ok(ContentType::Markdown),
+            "csv" | "text/csv" => Ok(ContentType::Csv),
+            "mermaid" | "mmd" | "text/vnd.mermaid" | "text/x-mermaid" => Ok(ContentType::Mermaid),
+            "json" | "application/json" => Ok(ContentType::Json),
+            "plain" | "text" | "txt" | "text/plain" => Ok(ContentType::Plain),
+            _ => Err(format!("Unknown content type: {}", s)),
+        }
+    }
+}
+
+impl ContentType {
+    /// Declared type, else a guess from the source file or content shape
+    pub fn of(node: &StateNode) -> Self {
+        let declared = node
+            .metadata
+            .get(CONTENT_TYPE_KEY)
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.parse().ok());
+        if let Some(content_type) = declared {
+            return content_type;
+        }
+
+        let extension = node
+            .content
+            .get("source")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.rsplit_once('.'))
+            .and_then(|(_, ext)| ext.parse().ok());
+        if let Some(content_type) = extension {
+            return content_type;
+        }
+
+        match &node.content {
+            serde_json::Value::String(_) => ContentType::Plain,
+            content if content.get("text").is_some_and(|t| t.is_string()) => ContentType::Plain,
+            _ => ContentType::Json,
+        }
+    }
+}
+
+/// Output the content is rendered into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderTarget {
+    Html,
+    /// Terminal text with ANSI styling
+    Ansi,
+    Plain,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub content_type: ContentType,
+    pub target: RenderTarget,
+    pub output: String,
+}
+
+/// Renders node content, shelling out to pandoc and mmdc where needed
+pub struct Renderer {
+    pandoc: PandocConverter,
+    mermaid_path: String,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            pandoc: PandocConverter::new(),
+            mermaid_path: "mmdc".to_string(),
+        }
+    }
+
+    pub fn with_pandoc(mut self, pandoc: PandocConverter) -> Self {
+        self.pandoc = pandoc;
+        self
+    }
+
+    /// Path to the mermaid CLI
+    pub fn with_mermaid_path(mut self, path: impl Into<String>) -> Self {
+        self.mermaid_path = path.into();
+        self
+    }
+
+    pub fn render(&self, node: &StateNode, target: RenderTarget) -> Result<Rendered> {
+        let content_type = ContentType::of(node);
+        let output = match content_type {
+            ContentType::Json => {
+                let json = serde_json::to_string_pretty(&node.content)
+                    .map_err(|e| RenderError::Invalid(content_type, e.to_string()))?;
+                match target {
+                    RenderTarget::Html => format!("<pre>{}</pre>", escape_html(&json)),
+                    _ => json,
+                }
+            }
+            _ => {
+                let text = body(node);
+                match (content_type, target) {
+                    (ContentType::Markdown, RenderTarget::Html) => self
+                        .pandoc
+                        .convert(&text, InputFormat::Markdown, OutputFormat::Html)
+                        .map_err(|e| RenderError::Tool(e.to_string()))?,
+                    (ContentType::Markdown, RenderTarget::Ansi) => markdown_to_ansi(&text),
+                    (ContentType::Csv, target) => {
+                        let rows = parse_csv(&text);
+                        match target {
+                            RenderTarget::Html => csv_to_html(&rows),
+                            RenderTarget::Ansi => csv_to_table(&rows, true),
+                            RenderTarget::Plain => csv_to_table(&rows, false),
+                        }
+                    }
+                    (ContentType::Mermaid, RenderTarget::Html) => self.mermaid_to_svg(&text)?,
+                    (_, RenderTarget::Html) => format!("<pre>{}</pre>", escape_html(&text)),
+                    _ => text,
+                }
+            }
+        };
+
+        Ok(Rendered {
+            content_type,
+            target,
+            output,
+        })
+    }
+
+    fn mermaid_to_svg(&self, source: &str) -> Result<String> {
+        let dir = std::env::temp_dir().join(format!("state-render-{}", ulid::Ulid::new()));
+        std::fs::create_dir_all(&dir).map_err(|e| RenderError::Tool(e.to_string()))?;
+        let input = dir.join("diagram.mmd");
+        let output = dir.join("diagram.svg");
+
+        let result = std::fs::write(&input, source)
+            .map_err(|e| RenderError::Tool(e.to_string()))
+            .and_then(|_| {
+                let status = Command::new(&self.mermaid_path)
+                    .arg("-i")
+                    .arg(&input)
+                    .arg("-o")
+                    .arg(&output)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .output()
+                    .map_err(|e| RenderError::Tool(format!("{} not found: {}", self.mermaid_path, e)))?;
+                if !status.status.success() {
+                    return Err(RenderError::Invalid(
+                        ContentType::Mermaid,
+                        String::from_utf8_lossy(&status.stderr).trim().to_string(),
+                    ));
+                }
+                std::fs::read_to_string(&output).map_err(|e| RenderError::Tool(e.to_string()))
+            });
+
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+}
+
+/// The text to render: `content.text` when present, otherwise the content
+fn body(node: &StateNode) -> String {
+    match &node.content {
+        serde_json::Value::String(s) => s.clone(),
+        content => content
+            .get("text")
+            .and_then(|t| t.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| content.to_string()),
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const ITALIC: &str = "\x1b[3m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Line-oriented Markdown styling for terminals
+fn markdown_to_ansi(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut in_code = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            out.push_str(&format!("    {}{}{}\n", CYAN, line, RESET));
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            out.push_str(&format!("{}{}{}\n", BOLD, trimmed[hashes..].trim(), RESET));
+        } else if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            let indent = &line[..line.len() - trimmed.len()];
+            out.push_str(&format!("{}• {}\n", indent, inline_ansi(item)));
+        } else {
+            out.push_str(&inline_ansi(line));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Style `**strong**`, `*emphasis*` and `` `code` `` spans
+fn inline_ansi(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(['*', '`']) {
+        let (marker, style) = if rest[start..].starts_with("**") {
+            ("**", BOLD)
+        } else if rest[start..].starts_with('`') {
+            ("`", CYAN)
+        } else {
+            ("*", ITALIC)
+        };
+        let inner = &rest[start + marker.len()..];
+        match inner.find(marker) {
+            Some(end) if end > 0 => {
+                out.push_str(&rest[..start]);
+                out.push_str(&format!("{}{}{}", style, &inner[..end], RESET));
+                rest = &inner[end + marker.len()..];
+            }
+            _ => {
+                out.push_str(&rest[..start + marker.len()]);
+                rest = inner;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Split CSV into rows, honouring double-quoted fields
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Aligned text table; the first row is the header
+fn csv_to_table(rows: &[Vec<String>], ansi: bool) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .map(|f| f.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for (n, row) in rows.iter().enumerate() {
+        let line: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, width)| format!("{:<width$}", row.get(i).map(String::as_str).unwrap_or(""), width = width))
+            .collect();
+        let line = line.join(" │ ");
+        if n == 0 && ansi {
+            out.push_str(&format!("{}{}{}\n", BOLD, line.trim_end(), RESET));
+        } else {
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        if n == 0 {
+            let rule: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+            out.push_str(&rule.join("─┼─"));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn csv_to_html(rows: &[Vec<String>]) -> String {
+    let mut out = String::from("<table>\n");
+    for (n, row) in rows.iter().enumerate() {
+        let tag = if n == 0 { "th" } else { "td" };
+        out.push_str("  <tr>");
+        for field in row {
+            out.push_str(&format!("<{tag}>{}</{tag}>", escape_html(field)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::NodeKind;
+
+    fn node(content_type: &str, text: &str) -> StateNode {
+        let mut node = StateNode::new(NodeKind::Context, serde_json::json!({ "text": text }));
+        node.metadata
+            .insert(CONTENT_TYPE_KEY.to_string(), serde_json::json!(content_type));
+        node
+    }
+
+    #[test]
+    fn test_csv_renders_as_table() {
+        let node = node("text/csv", "name,count\n\"a, b\",1\nc,22\n");
+        let rendered = Renderer::new().render(&node, RenderTarget::Plain).unwrap();
+        assert_eq!(rendered.content_type, ContentType::Csv);
+        assert_eq!(
+            rendered.output,
+            "name │ count\n─────┼──────\na, b │ 1\nc    │ 22\n"
+        );
+
+        let html = Renderer::new().render(&node, RenderTarget::Html).unwrap();
+        assert!(html.output.contains("<th>name</th>"));
+    }
+
+    #[test]
+    fn test_content_type_detection() {
+        let source = StateNode::new(
+            NodeKind::Context,
+            serde_json::json!({ "text": "# Title", "source": "notes/README.md" }),
+        );
+        assert_eq!(ContentType::of(&source), ContentType::Markdown);
+        assert_eq!(
+            ContentType::of(&StateNode::new(NodeKind::Task, serde_json::json!({ "done": false }))),
+            ContentType::Json
+        );
+
+        let ansi = Renderer::new()
+            .render(&node("markdown", "# Title\n- **bold** item"), RenderTarget::Ansi)
+            .unwrap();
+        assert_eq!(ansi.output, "\x1b[1mTitle\x1b[0m\n• \x1b[1mbold\x1b[0m item\n");
+    }
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use super::{AgentId, NodeId};
+
+pub type AnnotationId = Ulid;
+
+/// Where in a node's content an annotation points
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationAnchor {
+    /// A JSON path into the content, e.g. `$.steps[2].title`
+    JsonPath(String),
+    /// A character range within the content's text
+    TextRange { start: usize, end: usize },
+}
+
+impl std::fmt::Display for AnnotationAnchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnnotationAnchor::JsonPath(path) => write!(f, "{}", path),
+            AnnotationAnchor::TextRange { start, end } => write!(f, "{}..{}", start, end),
+        }
+    }
+}
+
+impl std::str::FromStr for AnnotationAnchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('$') {
+            return Ok(AnnotationAnchor::JsonPath(s.to_string()));
+        }
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("Unknown anchor: {}", s))?;
+        let start = start.parse().map_err(|_| format!("Invalid range start: {}", start))?;
+        let end = end.parse().map_err(|_| format!("Invalid range end: {}", end))?;
+        if end < start {
+            return Err(format!("Invalid range: {}", s));
+        }
+        Ok(AnnotationAnchor::TextRange { start, end })
+    }
+}
+
+/// A reviewer comment attached to a node, stored apart from its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: AnnotationId,
+    pub node_id: NodeId,
+    pub author: AgentId,
+    pub text: String,
+    pub anchor: Option<AnnotationAnchor>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Annotation {
+    pub fn new(node_id: NodeId, author: AgentId, text: impl Into<String>) -> Self {
+        Self {
+            id: Ulid::new(),
+            node_id,
+            author,
+            text: text.into(),
+            anchor: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_anchor(mut self, anchor: AnnotationAnchor) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+}
@@ -0,0 +1,212 @@
+//! Typed views of the content of well-known node kinds
+//!
+//! Content stays free-form JSON in the store; these structs name the
+//! fields agents conventionally use for tasks, insights and conversations.
+//! Fields they don't know are kept in `extra`, so a node read with
+//! `StateNode::content_as` and written back with `StateNode::from_typed`
+//! loses nothing; only empty lists are left out.
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::node::{NodeKind, StateNode};
+
+/// Content with a fixed shape for one node kind
+pub trait TypedContent: Serialize + DeserializeOwned {
+    fn kind() -> NodeKind;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskContent {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 1 is the most urgent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl TaskContent {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self { title: title.into(), ..Default::default() }
+    }
+
+    pub fn with_status(mut self, status: impl Into<String>) -> Self {
+        self.status = Some(status.into());
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i64) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn with_due(mut self, due: DateTime<Utc>) -> Self {
+        self.due = Some(due);
+        self
+    }
+}
+
+impl TypedContent for TaskContent {
+    fn kind() -> NodeKind {
+        NodeKind::Task
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InsightContent {
+    pub text: String,
+    /// How sure the author is, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl InsightContent {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into(), ..Default::default() }
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = Some(confidence);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl TypedContent for InsightContent {
+    fn kind() -> NodeKind {
+        NodeKind::Insight
+    }
+}
+
+/// One turn of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    /// Who spoke: `user`, `assistant`, an agent name
+    pub role: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<DateTime<Utc>>,
+}
+
+impl ConversationMessage {
+    pub fn new(role: impl Into<String>, text: impl Into<String>) -> Self {
+        Self { role: role.into(), text: text.into(), at: None }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub participants: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ConversationMessage>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ConversationContent {
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_message(mut self, message: ConversationMessage) -> Self {
+        if !self.participants.contains(&message.role) {
+            self.participants.push(message.role.clone());
+        }
+        self.messages.push(message);
+        self
+    }
+}
+
+impl TypedContent for ConversationContent {
+    fn kind() -> NodeKind {
+        NodeKind::Conversation
+    }
+}
+
+impl StateNode {
+    /// A new node of `T`'s kind holding `content`
+    pub fn from_typed<T: TypedContent>(content: &T) -> Self {
+        // Plain structs and string-keyed maps always serialize
+        let value = serde_json::to_value(content).expect("typed content serializes to JSON");
+        Self::new(T::kind(), value)
+    }
+
+    /// The content read as `T`; fails if the node is another kind or the
+    /// content lacks `T`'s required fields
+    pub fn content_as<T: TypedContent>(&self) -> Result<T, String> {
+        if self.kind != T::kind() {
+            return Err(format!("{} node {} is not a {}", self.kind, self.id, T::kind()));
+        }
+        serde_json::from_value(self.content.clone())
+            .map_err(|e| format!("Content of {} node {} doesn't fit: {}", self.kind, self.id, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_keeps_unknown_fields() {
+        let content = json!({"title": "Ship it", "status": "open", "estimate": {"hours": 3}, "labels": ["x"]});
+        let node = StateNode::new(NodeKind::Task, content.clone());
+        let task: TaskContent = node.content_as().unwrap();
+        assert_eq!(task.extra["estimate"], json!({"hours": 3}));
+        assert_eq!(StateNode::from_typed(&task).content, content);
+
+        let task = TaskContent::new("Review").with_priority(1).with_due(Utc::now());
+        let node = StateNode::from_typed(&task);
+        assert_eq!(node.kind, NodeKind::Task);
+        assert_eq!(node.content_as::<TaskContent>().unwrap(), task);
+    }
+
+    #[test]
+    fn test_empty_tags_are_left_out() {
+        let node = StateNode::new(NodeKind::Insight, json!({"text": "cache misses", "tags": []}));
+        let insight: InsightContent = node.content_as().unwrap();
+        assert!(insight.tags.is_empty() && insight.extra.is_empty());
+
+        let written = StateNode::from_typed(&insight);
+        assert_eq!(written.content, json!({"text": "cache misses"}));
+        assert_eq!(written.content_as::<InsightContent>().unwrap(), insight);
+    }
+
+    #[test]
+    fn test_content_as_checks_kind_and_required_fields() {
+        let untitled = StateNode::new(NodeKind::Task, json!({"status": "open"}));
+        assert!(untitled.content_as::<TaskContent>().is_err());
+        let task = StateNode::from_typed(&TaskContent::new("Review"));
+        assert!(task.content_as::<InsightContent>().is_err());
+
+        let conversation = StateNode::new(NodeKind::Conversation, json!({}));
+        assert_eq!(conversation.content_as::<ConversationContent>().unwrap(), ConversationContent::default());
+    }
+}
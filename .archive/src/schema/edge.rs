@@ -19,6 +19,19 @@ pub enum EdgeKind {
     Custom(String),
 }
 
+impl EdgeKind {
+    /// Every kind except `Custom`
+    pub const BUILT_IN: [EdgeKind; 7] = [
+        EdgeKind::References,
+        EdgeKind::DerivedFrom,
+        EdgeKind::RelatedTo,
+        EdgeKind::PartOf,
+        EdgeKind::Blocks,
+        EdgeKind::Enables,
+        EdgeKind::Supersedes,
+    ];
+}
+
 impl std::fmt::Display for EdgeKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -59,6 +72,7 @@ pub struct StateEdge {
     pub to: NodeId,
     pub kind: EdgeKind,
     pub weight: f32,
+    #[serde(with = "super::json_text")]
     pub metadata: Metadata,
     pub created_at: DateTime<Utc>,
 }
@@ -85,4 +99,10 @@ pub fn with_metadata(mut self, metadata: Metadata) -> Self {
         self.metadata = metadata;
         self
     }
+
+    /// The same edge seen from its other end
+    pub fn reversed(mut self) -> Self {
+        std::mem::swap(&mut self.from, &mut self.to);
+        self
+    }
 }
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use ulid::Ulid;
 
 use super::{EdgeId, NodeId};
@@ -54,6 +56,21 @@ pub enum Operation {
     Unlink,
 }
 
+impl std::str::FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "create" => Ok(Operation::Create),
+            "update" => Ok(Operation::Update),
+            "delete" => Ok(Operation::Delete),
+            "link" => Ok(Operation::Link),
+            "unlink" => Ok(Operation::Unlink),
+            _ => Err(format!("Unknown operation: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Target {
@@ -61,6 +78,133 @@ pub enum Target {
     Edge(EdgeId),
 }
 
+/// How much of a node's state an event records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// Full before and after snapshots
+    #[default]
+    Full,
+    /// Updates record an RFC 6902 patch from the before to the after
+    /// snapshot in `after`; creates and deletes still carry a snapshot
+    Diff,
+    /// `before` and `after` hold only the SHA-256 of each snapshot
+    Hash,
+}
+
+impl std::fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            CaptureMode::Full => "full",
+            CaptureMode::Diff => "diff",
+            CaptureMode::Hash => "hash",
+        })
+    }
+}
+
+impl std::str::FromStr for CaptureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(CaptureMode::Full),
+            "diff" | "json-patch" => Ok(CaptureMode::Diff),
+            "hash" | "hash-only" => Ok(CaptureMode::Hash),
+            _ => Err(format!("Unknown capture mode: {}", s)),
+        }
+    }
+}
+
+/// Capture mode per node kind; edge events are always captured in full
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturePolicy {
+    pub default: CaptureMode,
+    /// Overrides keyed by node kind name
+    #[serde(default)]
+    pub kinds: BTreeMap<String, CaptureMode>,
+}
+
+impl CapturePolicy {
+    pub fn mode_for(&self, kind: &super::NodeKind) -> CaptureMode {
+        self.kinds.get(&kind.to_string()).copied().unwrap_or(self.default)
+    }
+}
+
+/// Hex SHA-256 of a snapshot, as recorded by `CaptureMode::Hash`
+pub fn snapshot_digest(snapshot: &Value) -> String {
+    Sha256::digest(snapshot.to_string().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Events recorded per instance, as seen by one event
+///
+/// Event A happened before B exactly when A's clock is below B's; when
+/// neither is below the other the two were written concurrently on
+/// different instances, and timestamps alone can't order them. A missing
+/// instance counts as 0, so clocks that differ only in zero counters are
+/// equal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VectorClock(pub BTreeMap<String, u64>);
+
+impl VectorClock {
+    /// Events from `instance` this clock has seen
+    pub fn get(&self, instance: &str) -> u64 {
+        self.0.get(instance).copied().unwrap_or(0)
+    }
+
+    /// Count one more event from `instance`
+    pub fn tick(&mut self, instance: &str) -> u64 {
+        let counter = self.0.entry(instance.to_string()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    /// Take in everything `other` has seen
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (instance, &counter) in &other.0 {
+            let mine = self.0.entry(instance.clone()).or_insert(0);
+            *mine = (*mine).max(counter);
+        }
+    }
+
+    /// Neither clock has seen all the other has
+    pub fn concurrent_with(&self, other: &VectorClock) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+}
+
+impl PartialEq for VectorClock {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+    }
+}
+
+impl Eq for VectorClock {}
+
+impl PartialOrd for VectorClock {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        use std::cmp::Ordering;
+
+        let instances = self.0.keys().chain(other.0.keys());
+        let (mut less, mut greater) = (false, false);
+        for instance in instances {
+            match self.get(instance).cmp(&other.get(instance)) {
+                Ordering::Less => less = true,
+                Ordering::Greater => greater = true,
+                Ordering::Equal => {}
+            }
+        }
+        match (less, greater) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateEvent {
     pub id: EventId,
@@ -68,8 +212,23 @@ pub struct StateEvent {
     pub agent: AgentId,
     pub operation: Operation,
     pub target: Target,
+    /// Snapshot, patch or digest depending on `capture`
+    #[serde(with = "super::json_text")]
     pub before: Option<Value>,
+    #[serde(with = "super::json_text")]
     pub after: Option<Value>,
+    #[serde(default)]
+    pub capture: CaptureMode,
+    /// Shared by the events of one composite operation, such as
+    /// `SledStore::replace_node`
+    #[serde(default)]
+    pub group: Option<Ulid>,
+    /// Instance ID of the store that first recorded the event
+    #[serde(default)]
+    pub origin: Option<String>,
+    /// What the origin had seen when it recorded the event
+    #[serde(default)]
+    pub clock: VectorClock,
 }
 
 impl StateEvent {
@@ -82,6 +241,10 @@ pub fn new(agent: AgentId, operation: Operation, target: Target) -> Self {
             target,
             before: None,
             after: None,
+            capture: CaptureMode::Full,
+            group: None,
+            origin: None,
+            clock: VectorClock::default(),
         }
     }
 
@@ -94,4 +257,29 @@ pub fn with_after(mut self, after: Value) -> Self {
         self.after = Some(after);
         self
     }
+
+    pub fn with_group(mut self, group: Ulid) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    /// Record node snapshots according to `mode`
+    pub fn with_snapshots(mut self, mode: CaptureMode, before: Option<Value>, after: Option<Value>) -> Self {
+        self.capture = mode;
+        match (mode, before, after) {
+            (CaptureMode::Diff, Some(before), Some(after)) => {
+                let patch = json_patch::diff(&before, &after);
+                self.after = Some(serde_json::to_value(patch).unwrap_or_default());
+            }
+            (CaptureMode::Hash, before, after) => {
+                self.before = before.as_ref().map(|v| Value::String(snapshot_digest(v)));
+                self.after = after.as_ref().map(|v| Value::String(snapshot_digest(v)));
+            }
+            (_, before, after) => {
+                self.before = before;
+                self.after = after;
+            }
+        }
+        self
+    }
 }
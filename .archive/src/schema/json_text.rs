@@ -0,0 +1,34 @@
+//! Serde adapter for JSON-typed fields
+//!
+//! `serde_json::Value` can only be decoded by self-describing formats, so
+//! binary formats (bincode in the store) carry such fields as JSON text.
+//! Human-readable formats keep them as plain JSON.
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    if serializer.is_human_readable() {
+        value.serialize(serializer)
+    } else {
+        let text = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&text)
+    }
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    if deserializer.is_human_readable() {
+        T::deserialize(deserializer)
+    } else {
+        let text = String::deserialize(deserializer)?;
+        serde_json::from_str(&text).map_err(D::Error::custom)
+    }
+}
@@ -1,7 +1,17 @@
 mod node;
 mod edge;
 mod event;
+mod annotation;
+mod reaction;
+mod content;
+pub(crate) mod json_text;
 
-pub use node::{NodeId, NodeKind, StateNode, Metadata};
+pub use node::{NodeId, NodeKind, RetentionClass, StateNode, Metadata, normalize_tags};
 pub use edge::{EdgeId, EdgeKind, StateEdge};
-pub use event::{EventId, StateEvent, Operation, AgentId, Target};
+pub use event::{
+    snapshot_digest, AgentId, CaptureMode, CapturePolicy, EventId, Operation, StateEvent, Target,
+    VectorClock,
+};
+pub use annotation::{AnnotationId, Annotation, AnnotationAnchor};
+pub use reaction::{Reaction, ReactionKind, ReactionCounts};
+pub use content::{ConversationContent, ConversationMessage, InsightContent, TaskContent, TypedContent};
@@ -20,6 +20,19 @@ pub enum NodeKind {
     Custom(String),
 }
 
+impl NodeKind {
+    /// Every kind except `Custom`
+    pub const BUILT_IN: [NodeKind; 7] = [
+        NodeKind::Conversation,
+        NodeKind::Project,
+        NodeKind::Insight,
+        NodeKind::Task,
+        NodeKind::Context,
+        NodeKind::Module,
+        NodeKind::Agent,
+    ];
+}
+
 impl std::fmt::Display for NodeKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -53,14 +66,66 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
     }
 }
 
+/// How long a node lives and how carefully it is kept; see `RetentionPolicy`
+///
+/// Permanent nodes are never expired or archived, whatever their expiry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionClass {
+    Ephemeral,
+    #[default]
+    Standard,
+    Permanent,
+}
+
+impl std::fmt::Display for RetentionClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            RetentionClass::Ephemeral => "ephemeral",
+            RetentionClass::Standard => "standard",
+            RetentionClass::Permanent => "permanent",
+        })
+    }
+}
+
+impl std::str::FromStr for RetentionClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ephemeral" => Ok(RetentionClass::Ephemeral),
+            "standard" => Ok(RetentionClass::Standard),
+            "permanent" => Ok(RetentionClass::Permanent),
+            _ => Err(format!("Unknown retention class: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateNode {
     pub id: NodeId,
     pub kind: NodeKind,
+    #[serde(with = "super::json_text")]
     pub content: Value,
+    #[serde(with = "super::json_text")]
     pub metadata: Metadata,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When set, the node is removed by the expiry sweeper after this time
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Incremented on every update; used for optimistic concurrency
+    #[serde(default)]
+    pub version: u64,
+    /// Sorted, deduplicated labels, indexed for `nodes_by_tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Marked as context every agent should load, listed by `pinned_nodes`
+    #[serde(default)]
+    pub pinned: bool,
+    /// Overrides the retention class configured for the node's kind
+    #[serde(default)]
+    pub retention: Option<RetentionClass>,
 }
 
 impl StateNode {
@@ -73,6 +138,11 @@ pub fn new(kind: NodeKind, content: Value) -> Self {
             metadata: HashMap::new(),
             created_at: now,
             updated_at: now,
+            expires_at: None,
+            version: 1,
+            tags: Vec::new(),
+            pinned: false,
+            retention: None,
         }
     }
 
@@ -85,4 +155,52 @@ pub fn with_id(mut self, id: NodeId) -> Self {
         self.id = id;
         self
     }
+
+    pub fn with_expiry(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Expire the node `ttl` after its creation time
+    pub fn with_ttl(mut self, ttl: chrono::Duration) -> Self {
+        self.expires_at = Some(self.created_at + ttl);
+        self
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    pub fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self.tags = normalize_tags(std::mem::take(&mut self.tags));
+        self
+    }
+
+    pub fn with_pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: RetentionClass) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.binary_search_by(|t| t.as_str().cmp(tag)).is_ok()
+    }
+}
+
+/// Trim, sort and deduplicate tags, dropping empty ones
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> =
+        tags.into_iter().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
 }
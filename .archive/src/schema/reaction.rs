@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{AgentId, NodeId};
+
+/// Lightweight quality signal an agent can leave on a node
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReactionKind {
+    Useful,
+    Outdated,
+    Disputed,
+}
+
+impl ReactionKind {
+    /// Stable tag used in storage keys
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            ReactionKind::Useful => 0,
+            ReactionKind::Outdated => 1,
+            ReactionKind::Disputed => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for ReactionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReactionKind::Useful => write!(f, "useful"),
+            ReactionKind::Outdated => write!(f, "outdated"),
+            ReactionKind::Disputed => write!(f, "disputed"),
+        }
+    }
+}
+
+impl std::str::FromStr for ReactionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "useful" | "+1" | "👍" => Ok(ReactionKind::Useful),
+            "outdated" | "stale" | "🕸" => Ok(ReactionKind::Outdated),
+            "disputed" | "-1" | "👎" => Ok(ReactionKind::Disputed),
+            _ => Err(format!("Unknown reaction: {}", s)),
+        }
+    }
+}
+
+/// One agent's reaction to a node; an agent holds at most one of each kind
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reaction {
+    pub node_id: NodeId,
+    pub agent: AgentId,
+    pub kind: ReactionKind,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Reaction {
+    pub fn new(node_id: NodeId, agent: AgentId, kind: ReactionKind) -> Self {
+        Self {
+            node_id,
+            agent,
+            kind,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Aggregated reaction counters for a node
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReactionCounts {
+    pub useful: usize,
+    pub outdated: usize,
+    pub disputed: usize,
+}
+
+impl ReactionCounts {
+    pub fn from_reactions<'a>(reactions: impl IntoIterator<Item = &'a Reaction>) -> Self {
+        let mut counts = Self::default();
+        for reaction in reactions {
+            match reaction.kind {
+                ReactionKind::Useful => counts.useful += 1,
+                ReactionKind::Outdated => counts.outdated += 1,
+                ReactionKind::Disputed => counts.disputed += 1,
+            }
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.useful + self.outdated + self.disputed
+    }
+
+    /// Trust in [0, 1]: smoothed share of useful over useful + disputed,
+    /// so a node with no signals sits at 0.5
+    pub fn trust(&self) -> f32 {
+        (self.useful as f32 + 1.0) / ((self.useful + self.disputed) as f32 + 2.0)
+    }
+
+    /// Freshness in [0, 1]: drops as agents flag the node outdated
+    pub fn freshness(&self) -> f32 {
+        (self.useful as f32 + 1.0) / ((self.useful + self.outdated) as f32 + 1.0)
+    }
+}
+
@@ -0,0 +1,451 @@
+//! Reloadable configuration for `serve http`
+//!
+//! Capability policies, the voting strategy, rate limits, webhook
+//! registrations and watchdog thresholds live in a JSON file passed with
+//! `--config`. Sections the
+//! file leaves out fall back to what the database holds (capabilities and
+//! the voting strategy) or to their defaults. A running server re-reads the
+//! file on SIGHUP or `POST /admin/reload`: requests already in flight keep
+//! the configuration they started with, later ones see the new one, and
+//! every reload that changes something is appended to the config audit log.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use crate::coordinator::{CapabilityConfig, CapabilityMode, VotingCoordinator, VotingStrategy};
+use crate::graphql::request_log::RequestRecord;
+use crate::schema::{AgentId, StateEvent};
+use crate::store::{HookPoint, Result, SledStore, StoreError};
+use crate::watchdog::{self, WatchdogPolicy};
+
+/// Metadata key holding the capability policy
+pub const CAPABILITIES_KEY: &str = "capabilities";
+
+/// Metadata key holding votes and the voting strategy
+pub const VOTES_KEY: &str = "votes";
+
+/// Metadata key holding the config audit log
+pub const CONFIG_AUDIT_KEY: &str = "config_audit";
+
+/// Config changes kept in the audit log; older ones are dropped
+const CONFIG_AUDIT_LIMIT: usize = 500;
+
+/// Webhook deliveries waiting to be posted; later ones are dropped
+const WEBHOOK_QUEUE: usize = 1024;
+
+/// Per-agent request limits, counted in one-minute windows
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    /// Requests per minute for agents without an override; `None` is unlimited
+    #[serde(default)]
+    pub per_minute: Option<u32>,
+    /// Overrides keyed by agent name, as sent in `x-state-agent`
+    #[serde(default)]
+    pub agents: BTreeMap<String, u32>,
+}
+
+impl RateLimits {
+    pub fn limit_for(&self, agent: &str) -> Option<u32> {
+        self.agents.get(agent).copied().or(self.per_minute)
+    }
+}
+
+/// A URL notified after successful mutations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    /// Plain `http://` URL
+    pub url: String,
+    /// Mutation fields (e.g. `createNode`), store changes (`create`,
+    /// `update`, `delete`) or alerts (`watchdog`) that trigger it; empty
+    /// for all
+    #[serde(default)]
+    pub mutations: Vec<String>,
+}
+
+impl Webhook {
+    pub fn matches(&self, fields: &[String]) -> bool {
+        self.mutations.is_empty() || fields.iter().any(|f| self.mutations.contains(f))
+    }
+}
+
+/// The `--config` file; every section is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    pub capabilities: Option<CapabilityConfig>,
+    pub voting_strategy: Option<VotingStrategy>,
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub watchdog: WatchdogPolicy,
+}
+
+impl ConfigFile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| StoreError::InvalidOperation(format!("config {}: {}", path.display(), e)))?;
+        serde_json::from_str(&text)
+            .map_err(|e| StoreError::Serialization(format!("config {}: {}", path.display(), e)))
+    }
+}
+
+/// Configuration in effect for a request
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerConfig {
+    pub capabilities: CapabilityConfig,
+    pub voting_strategy: VotingStrategy,
+    pub rate_limits: RateLimits,
+    pub webhooks: Vec<Webhook>,
+    pub watchdog: WatchdogPolicy,
+}
+
+impl ServerConfig {
+    /// Combine `file` with the capability policy and voting strategy stored
+    /// in `store`, holding agents the watchdog demoted at their demotion
+    pub fn load(store: &SledStore, file: Option<&Path>) -> Result<Self> {
+        let file = file.map(ConfigFile::read).transpose()?.unwrap_or_default();
+        let mut capabilities = match file.capabilities {
+            Some(capabilities) => capabilities,
+            None => match store.get_meta(CAPABILITIES_KEY)? {
+                Some(value) => serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))?,
+                None => CapabilityConfig::default(),
+            },
+        };
+        watchdog::apply_demotions(&mut capabilities, &watchdog::demotions(store)?);
+        let voting_strategy = match file.voting_strategy {
+            Some(strategy) => strategy,
+            None => match store.get_meta(VOTES_KEY)? {
+                Some(value) => serde_json::from_value::<VotingCoordinator>(value)
+                    .map_err(|e| StoreError::Serialization(e.to_string()))?
+                    .strategy()
+                    .clone(),
+                None => VotingStrategy::default(),
+            },
+        };
+        Ok(Self {
+            capabilities,
+            voting_strategy,
+            rate_limits: file.rate_limits,
+            webhooks: file.webhooks,
+            watchdog: file.watchdog,
+        })
+    }
+
+    /// Sections that differ between `self` and `other`
+    pub fn changed_sections(&self, other: &Self) -> Vec<String> {
+        let (before, after) = (serde_json::to_value(self), serde_json::to_value(other));
+        let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) = (before, after) else {
+            return Vec::new();
+        };
+        before
+            .iter()
+            .filter(|(section, value)| after.get(*section) != Some(*value))
+            .map(|(section, _)| section.clone())
+            .collect()
+    }
+
+    /// Whether `agent` may run mutations; agents in observer mode may not
+    pub fn may_mutate(&self, agent: &str) -> bool {
+        match agent.parse::<AgentId>() {
+            Ok(agent) => self.capabilities.get_capabilities(&agent).mode != CapabilityMode::Observer,
+            // Unknown names get the default mode
+            Err(_) => self.capabilities.default_mode != CapabilityMode::Observer,
+        }
+    }
+
+    /// POST `record` to every webhook registered for its mutation fields,
+    /// queued so the caller isn't held up
+    pub fn notify(&self, record: &RequestRecord, fields: &[String]) {
+        let payload = serde_json::json!({
+            "event": "mutation",
+            "timestamp": record.timestamp,
+            "agent": record.agent,
+            "namespace": record.namespace,
+            "operation": record.operation,
+            "fields": fields,
+        });
+        self.post(&payload, fields);
+    }
+
+    /// POST an `event` alert to webhooks registered for it by name (or for
+    /// everything)
+    pub fn alert(&self, event: &str, details: serde_json::Value) {
+        let payload = serde_json::json!({
+            "event": event,
+            "timestamp": Utc::now(),
+            "details": details,
+        });
+        self.post(&payload, &[event.to_string()]);
+    }
+
+    fn post(&self, payload: &serde_json::Value, fields: &[String]) {
+        for webhook in self.webhooks.iter().filter(|w| w.matches(fields)) {
+            deliver(webhook.url.clone(), payload.clone());
+        }
+    }
+}
+
+/// Hand a webhook POST to the process's delivery worker, started on first
+/// use
+///
+/// The worker posts one at a time, so a burst of mutations queues up
+/// instead of starting a thread each; once `WEBHOOK_QUEUE` deliveries are
+/// waiting, further ones are dropped with a warning.
+fn deliver(url: String, payload: serde_json::Value) {
+    static QUEUE: OnceLock<SyncSender<(String, serde_json::Value)>> = OnceLock::new();
+    let queue = QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel::<(String, serde_json::Value)>(WEBHOOK_QUEUE);
+        std::thread::spawn(move || {
+            for (url, payload) in receiver {
+                if let Err(e) = crate::graphql::client::post_json(&url, &payload, &[]) {
+                    tracing::warn!("webhook {} failed: {}", url, e);
+                }
+            }
+        });
+        sender
+    });
+    match queue.try_send((url, payload)) {
+        Ok(()) => {}
+        Err(TrySendError::Full((url, _))) => tracing::warn!("webhook queue full, dropped a delivery to {}", url),
+        Err(TrySendError::Disconnected((url, _))) => {
+            tracing::warn!("webhook worker gone, dropped a delivery to {}", url)
+        }
+    }
+}
+
+/// One recorded configuration change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigChange {
+    pub at: DateTime<Utc>,
+    /// Configuration version after the change
+    pub version: u64,
+    /// What asked for the reload (`sighup`, `endpoint`, ...)
+    pub trigger: String,
+    pub sections: Vec<String>,
+}
+
+/// Recorded configuration changes, oldest first
+pub fn config_changes(store: &SledStore) -> Result<Vec<ConfigChange>> {
+    match store.get_meta(CONFIG_AUDIT_KEY)? {
+        Some(value) => serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string())),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn record_change(store: &SledStore, change: ConfigChange) -> Result<()> {
+    let mut changes = config_changes(store)?;
+    changes.push(change);
+    let excess = changes.len().saturating_sub(CONFIG_AUDIT_LIMIT);
+    changes.drain(..excess);
+    let value = serde_json::to_value(&changes).map_err(|e| StoreError::Serialization(e.to_string()))?;
+    store.set_meta(CONFIG_AUDIT_KEY, &value)
+}
+
+/// The configuration a running server reads, swapped whole on reload
+pub struct LiveConfig {
+    store: Arc<SledStore>,
+    file: Option<PathBuf>,
+    current: RwLock<Arc<ServerConfig>>,
+    version: AtomicU64,
+    /// Requests per agent in the current minute
+    windows: Mutex<HashMap<String, (i64, u32)>>,
+}
+
+impl LiveConfig {
+    pub fn load(store: Arc<SledStore>, file: Option<PathBuf>) -> Result<Self> {
+        let config = ServerConfig::load(&store, file.as_deref())?;
+        Ok(Self {
+            store,
+            file,
+            current: RwLock::new(Arc::new(config)),
+            version: AtomicU64::new(1),
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Alert webhooks to every store change after it is logged, whether a
+    /// request, the sweeper or the watchdog made it, under the name of its
+    /// hook: `create`, `update` or `delete`
+    pub fn notify_changes(self: &Arc<Self>) {
+        for (point, name) in [(HookPoint::Create, "create"), (HookPoint::Update, "update"), (HookPoint::Delete, "delete")] {
+            // The store's hooks would otherwise keep its own config alive
+            let live = Arc::downgrade(self);
+            self.store.hooks().register(
+                point,
+                Arc::new(move |event: &StateEvent| {
+                    if let Some(live) = live.upgrade() {
+                        let details = serde_json::json!({
+                            "id": event.id,
+                            "agent": event.agent,
+                            "operation": event.operation,
+                            "target": event.target,
+                        });
+                        live.current().alert(name, details);
+                    }
+                }),
+            );
+        }
+    }
+
+    pub fn current(&self) -> Arc<ServerConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Starts at 1 and increases with every reload that changes something
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Re-read the configuration, returning the sections that changed
+    ///
+    /// A file that fails to parse leaves the current configuration in place.
+    pub fn reload(&self, trigger: &str) -> Result<Vec<String>> {
+        let next = ServerConfig::load(&self.store, self.file.as_deref())?;
+        let sections = {
+            let mut current = self.current.write().unwrap();
+            let sections = current.changed_sections(&next);
+            if !sections.is_empty() {
+                *current = Arc::new(next);
+            }
+            sections
+        };
+        if sections.is_empty() {
+            return Ok(sections);
+        }
+
+        let version = self.version.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!("configuration reloaded ({}): {} now at version {}", trigger, sections.join(", "), version);
+        let change = ConfigChange {
+            at: Utc::now(),
+            version,
+            trigger: trigger.to_string(),
+            sections: sections.clone(),
+        };
+        if self.store.is_read_only() {
+            tracing::warn!("read-only store; configuration change not recorded in the audit log");
+        } else {
+            record_change(&self.store, change)?;
+        }
+        Ok(sections)
+    }
+
+    /// Count a request by `agent`; false once it is over its limit this minute
+    pub fn admit(&self, agent: &str) -> bool {
+        let Some(limit) = self.current().rate_limits.limit_for(agent) else {
+            return true;
+        };
+        let minute = Utc::now().timestamp() / 60;
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows.entry(agent.to_string()).or_insert((minute, 0));
+        if window.0 != minute {
+            *window = (minute, 0);
+        }
+        window.1 += 1;
+        window.1 <= limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload_swaps_config_and_audits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.json");
+        std::fs::write(&path, r#"{"rate_limits": {"per_minute": 2}}"#).unwrap();
+
+        let store = Arc::new(SledStore::open_temporary().unwrap());
+        let live = LiveConfig::load(store.clone(), Some(path.clone())).unwrap();
+        let before = live.current();
+        assert_eq!(before.voting_strategy, VotingStrategy::SimpleMajority);
+        assert!(live.admit("claude") && live.admit("claude"));
+        assert!(!live.admit("claude"));
+
+        // Nothing changed: no new version, no audit entry
+        assert!(live.reload("endpoint").unwrap().is_empty());
+        assert_eq!(live.version(), 1);
+
+        std::fs::write(
+            &path,
+            r#"{
+                "capabilities": {"default_mode": "observer", "agent_overrides": {}, "allow_runtime_changes": true},
+                "voting_strategy": "unanimous",
+                "rate_limits": {"per_minute": 2, "agents": {"claude": 5}},
+                "webhooks": [{"url": "http://127.0.0.1:9/hook", "mutations": ["createNode"]}]
+            }"#,
+        )
+        .unwrap();
+        let sections = live.reload("sighup").unwrap();
+        assert_eq!(sections.len(), 4);
+        assert_eq!(live.version(), 2);
+        // The old snapshot is untouched; the counter carries across the reload
+        assert_eq!(before.voting_strategy, VotingStrategy::SimpleMajority);
+        assert_eq!(live.current().voting_strategy, VotingStrategy::Unanimous);
+        assert!(live.admit("claude"));
+        assert!(!live.current().may_mutate("llama"));
+        assert!(live.current().webhooks[0].matches(&["createNode".to_string()]));
+
+        let changes = config_changes(&store).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].version, changes[0].trigger.as_str()), (2, "sighup"));
+
+        // A broken file keeps the last good configuration
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(live.reload("sighup").is_err());
+        assert_eq!(live.current().voting_strategy, VotingStrategy::Unanimous);
+    }
+
+    #[test]
+    fn test_store_changes_reach_webhooks() {
+        use crate::schema::{NodeKind, StateNode};
+        use crate::store::Store;
+        use std::io::{BufRead, Read, Write};
+
+        // A one-shot receiver for the webhook's POST
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.json");
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        std::fs::write(&path, serde_json::json!({"webhooks": [{"url": url, "mutations": ["delete"]}]}).to_string())
+            .unwrap();
+
+        let store = Arc::new(SledStore::open_temporary().unwrap());
+        let live = Arc::new(LiveConfig::load(store.clone(), Some(path)).unwrap());
+        live.notify_changes();
+        let node = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User).unwrap();
+        store.delete_node(node.id, AgentId::User).unwrap();
+
+        // Only the deletion was subscribed to
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = std::io::BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader.into_inner().write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+
+        let alert: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(alert["event"], "delete");
+        assert_eq!(alert["details"]["target"]["node"], node.id.to_string());
+    }
+}
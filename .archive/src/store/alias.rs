@@ -0,0 +1,44 @@
+//! Human-readable node aliases
+//!
+//! An alias is a slug like `project-roadmap` that stands in for a node's ID
+//! wherever one is accepted. Each node has at most one and each alias names
+//! one node. Aliases start with a lowercase letter, so one never parses as
+//! a ULID, whose first character is a digit.
+
+use super::{Result, StoreError};
+
+/// Longest alias accepted
+pub const MAX_ALIAS_LEN: usize = 64;
+
+/// Check that `alias` is a lowercase slug: a letter, then letters, digits,
+/// `-`, `_` or `.`
+pub fn validate_alias(alias: &str) -> Result<()> {
+    let invalid = |reason: &str| Err(StoreError::InvalidOperation(format!("Invalid alias {:?}: {}", alias, reason)));
+    if alias.is_empty() || alias.len() > MAX_ALIAS_LEN {
+        return invalid(&format!("must be 1 to {} characters", MAX_ALIAS_LEN));
+    }
+    if !alias.starts_with(|c: char| c.is_ascii_lowercase()) {
+        return invalid("must start with a lowercase letter");
+    }
+    if !alias.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.')) {
+        return invalid("may only contain lowercase letters, digits, '-', '_' and '.'");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_alias() {
+        for alias in ["project-roadmap", "q3.goals", "a", "v2_notes"] {
+            assert!(validate_alias(alias).is_ok(), "{}", alias);
+        }
+        let ulid = ulid::Ulid::new().to_string();
+        for alias in ["", "Roadmap", "2024-plan", "has space", "-x", ulid.as_str()] {
+            assert!(validate_alias(alias).is_err(), "{}", alias);
+        }
+        assert!(validate_alias(&"a".repeat(MAX_ALIAS_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! Binary attachments on nodes
+//!
+//! Blob bytes are stored once per namespace, keyed by their SHA-256, so the
+//! same file attached to several nodes takes space once. Each attachment is
+//! a small metadata record keyed by node and hash; a blob is dropped when
+//! its last attachment goes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::schema::NodeId;
+
+/// Used when no type is given and none can be guessed
+pub const DEFAULT_MIME: &str = "application/octet-stream";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub node_id: NodeId,
+    /// Hex SHA-256 of the bytes
+    pub hash: String,
+    pub mime: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    /// Path under which the HTTP server serves the bytes
+    pub fn download_path(&self) -> String {
+        format!("/attachments/{}/{}", self.node_id, self.hash)
+    }
+}
+
+pub(crate) fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `node id ++ hash` so a node's attachments share a prefix
+pub(crate) fn attachment_key(node_id: NodeId, hash: &str) -> Vec<u8> {
+    let mut key = node_id.to_bytes().to_vec();
+    key.extend_from_slice(hash.as_bytes());
+    key
+}
+
+/// Guess a MIME type from a file extension
+pub fn guess_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        _ => DEFAULT_MIME,
+    }
+}
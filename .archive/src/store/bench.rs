@@ -0,0 +1,319 @@
+//! Comparing search backends on a set of queries
+//!
+//! Each query runs through every backend a few times; the report gives the
+//! median latency, the number of hits and how far each backend's result set
+//! agrees with a baseline (Jaccard overlap, 1.0 for identical sets).
+//! Queries can be tagged with a class (`identifier`, `phrase`, `typo`...)
+//! so the summary shows which backend suits which kind of query on this
+//! data. Only backends this build has are benchmarked; there is no vector
+//! or hybrid search yet.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+use super::expand::{self, QueryExpander, SynonymExpander};
+use super::{Result, SledStore, Store};
+use crate::schema::NodeId;
+
+/// Class of queries whose line doesn't name one
+pub const DEFAULT_CLASS: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchBackend {
+    /// Case-sensitive substring
+    Exact,
+    /// Case-insensitive substring, as `search fulltext`
+    Fulltext,
+    /// Fulltext over synonym reformulations, as `search expand`
+    Expanded,
+    /// Query characters in order, gaps allowed
+    Fuzzy,
+    /// Substring within a few edits
+    Agrep,
+}
+
+impl SearchBackend {
+    pub const ALL: [SearchBackend; 5] = [
+        SearchBackend::Exact,
+        SearchBackend::Fulltext,
+        SearchBackend::Expanded,
+        SearchBackend::Fuzzy,
+        SearchBackend::Agrep,
+    ];
+
+    /// IDs of every node matching `query`
+    pub fn run(self, store: &SledStore, query: &str, max_errors: usize) -> Result<Vec<NodeId>> {
+        let scan = |matches: &dyn Fn(&str) -> bool| -> Result<Vec<NodeId>> {
+            Ok(store
+                .list_nodes(None, usize::MAX)?
+                .into_iter()
+                .filter(|node| matches(&node.content.to_string()))
+                .map(|node| node.id)
+                .collect())
+        };
+        match self {
+            SearchBackend::Exact => scan(&|text| text.contains(query)),
+            SearchBackend::Fulltext => Ok(store.search(query, None)?.into_iter().map(|n| n.id).collect()),
+            SearchBackend::Expanded => {
+                let queries = SynonymExpander::new().expand(query);
+                Ok(expand::search_expanded(store, &queries, None, usize::MAX)?
+                    .into_iter()
+                    .map(|hit| hit.node.id)
+                    .collect())
+            }
+            SearchBackend::Fuzzy => {
+                let pattern: Vec<char> = query.to_lowercase().chars().filter(|c| !c.is_whitespace()).collect();
+                scan(&|text| is_subsequence(&pattern, &text.to_lowercase()))
+            }
+            SearchBackend::Agrep => {
+                let pattern: Vec<char> = query.to_lowercase().chars().collect();
+                scan(&|text| within_edits(&pattern, &text.to_lowercase(), max_errors))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SearchBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            SearchBackend::Exact => "exact",
+            SearchBackend::Fulltext => "fulltext",
+            SearchBackend::Expanded => "expanded",
+            SearchBackend::Fuzzy => "fuzzy",
+            SearchBackend::Agrep => "agrep",
+        })
+    }
+}
+
+impl std::str::FromStr for SearchBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "exact" => Ok(SearchBackend::Exact),
+            "fulltext" | "full_text" => Ok(SearchBackend::Fulltext),
+            "expanded" | "expand" => Ok(SearchBackend::Expanded),
+            "fuzzy" => Ok(SearchBackend::Fuzzy),
+            "agrep" => Ok(SearchBackend::Agrep),
+            _ => Err(format!("Unknown search backend: {}", s)),
+        }
+    }
+}
+
+fn is_subsequence(pattern: &[char], text: &str) -> bool {
+    let mut chars = text.chars();
+    pattern.iter().all(|&p| chars.any(|c| c == p))
+}
+
+/// Whether some substring of `text` is within `max_errors` edits of
+/// `pattern` (Sellers' algorithm)
+fn within_edits(pattern: &[char], text: &str, max_errors: usize) -> bool {
+    if pattern.len() <= max_errors {
+        return true;
+    }
+    // column[i]: fewest edits matching pattern[..i] ending at this text position
+    let mut column: Vec<usize> = (0..=pattern.len()).collect();
+    for c in text.chars() {
+        let mut diagonal = column[0];
+        for i in 1..=pattern.len() {
+            let next = (diagonal + usize::from(pattern[i - 1] != c)).min(column[i] + 1).min(column[i - 1] + 1);
+            diagonal = column[i];
+            column[i] = next;
+        }
+        if column[pattern.len()] <= max_errors {
+            return true;
+        }
+    }
+    false
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BenchQuery {
+    pub class: String,
+    pub query: String,
+}
+
+/// One query per line, optionally `class<TAB>query`; blank lines and `#`
+/// comments are skipped
+pub fn parse_queries(text: &str) -> Vec<BenchQuery> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once('\t') {
+            Some((class, query)) => BenchQuery { class: class.trim().to_string(), query: query.trim().to_string() },
+            None => BenchQuery { class: DEFAULT_CLASS.to_string(), query: line.to_string() },
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub backends: Vec<SearchBackend>,
+    /// Backend the others' result sets are compared with
+    pub baseline: SearchBackend,
+    /// Timed runs per query and backend; the median is reported
+    pub runs: usize,
+    /// Edits `agrep` allows
+    pub max_errors: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self { backends: SearchBackend::ALL.to_vec(), baseline: SearchBackend::Fulltext, runs: 3, max_errors: 1 }
+    }
+}
+
+impl BenchOptions {
+    pub fn with_backends(mut self, backends: Vec<SearchBackend>) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_baseline(mut self, baseline: SearchBackend) -> Self {
+        self.baseline = baseline;
+        self
+    }
+
+    pub fn with_runs(mut self, runs: usize) -> Self {
+        self.runs = runs.max(1);
+        self
+    }
+
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendRun {
+    pub backend: SearchBackend,
+    pub hits: usize,
+    pub latency_ms: f64,
+    /// Jaccard overlap with the baseline's results
+    pub overlap: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryReport {
+    pub class: String,
+    pub query: String,
+    pub runs: Vec<BackendRun>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassSummary {
+    pub class: String,
+    pub backend: SearchBackend,
+    pub queries: usize,
+    pub mean_latency_ms: f64,
+    pub mean_hits: f64,
+    pub mean_overlap: f64,
+}
+
+fn jaccard(a: &HashSet<NodeId>, b: &HashSet<NodeId>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Run every query through every backend
+pub fn bench(store: &SledStore, queries: &[BenchQuery], options: &BenchOptions) -> Result<Vec<QueryReport>> {
+    let mut reports = Vec::with_capacity(queries.len());
+    for query in queries {
+        let mut results = Vec::new();
+        for &backend in &options.backends {
+            let mut latencies = Vec::with_capacity(options.runs);
+            let mut hits = Vec::new();
+            for _ in 0..options.runs.max(1) {
+                let start = Instant::now();
+                hits = backend.run(store, &query.query, options.max_errors)?;
+                latencies.push(start.elapsed().as_secs_f64() * 1000.0);
+            }
+            latencies.sort_by(f64::total_cmp);
+            results.push((backend, hits.into_iter().collect::<HashSet<_>>(), latencies[latencies.len() / 2]));
+        }
+
+        let baseline = match results.iter().find(|(b, _, _)| *b == options.baseline) {
+            Some((_, hits, _)) => hits.clone(),
+            None => options.baseline.run(store, &query.query, options.max_errors)?.into_iter().collect(),
+        };
+        reports.push(QueryReport {
+            class: query.class.clone(),
+            query: query.query.clone(),
+            runs: results
+                .into_iter()
+                .map(|(backend, hits, latency_ms)| BackendRun {
+                    backend,
+                    hits: hits.len(),
+                    latency_ms,
+                    overlap: jaccard(&hits, &baseline),
+                })
+                .collect(),
+        });
+    }
+    Ok(reports)
+}
+
+/// Means per query class and backend
+pub fn summarize(reports: &[QueryReport]) -> Vec<ClassSummary> {
+    let mut groups: BTreeMap<(&str, SearchBackend), Vec<&BackendRun>> = BTreeMap::new();
+    for report in reports {
+        for run in &report.runs {
+            groups.entry((report.class.as_str(), run.backend)).or_default().push(run);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((class, backend), runs)| {
+            let n = runs.len() as f64;
+            ClassSummary {
+                class: class.to_string(),
+                backend,
+                queries: runs.len(),
+                mean_latency_ms: runs.iter().map(|r| r.latency_ms).sum::<f64>() / n,
+                mean_hits: runs.iter().map(|r| r.hits as f64).sum::<f64>() / n,
+                mean_overlap: runs.iter().map(|r| r.overlap).sum::<f64>() / n,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, NodeKind, StateNode};
+
+    #[test]
+    fn test_search_bench() {
+        let store = SledStore::open_temporary().unwrap();
+        for text in ["Database migration", "database backup", "the store is slow", "unrelated"] {
+            store.create_node(StateNode::new(NodeKind::Insight, serde_json::json!({"text": text})), AgentId::User).unwrap();
+        }
+        let queries = parse_queries("# classes\nword\tdatabase\n\ntypo\tdatabse\nDatabase\n");
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[2], BenchQuery { class: DEFAULT_CLASS.into(), query: "Database".into() });
+
+        let reports = bench(&store, &queries, &BenchOptions::default().with_runs(1)).unwrap();
+        let hits = |report: &QueryReport, backend| report.runs.iter().find(|r| r.backend == backend).unwrap().clone();
+        assert_eq!(hits(&reports[0], SearchBackend::Fulltext).hits, 2);
+        assert_eq!(hits(&reports[0], SearchBackend::Fulltext).overlap, 1.0);
+        assert_eq!(hits(&reports[0], SearchBackend::Exact).hits, 1);
+        assert_eq!(hits(&reports[0], SearchBackend::Exact).overlap, 0.5);
+        // "database" expands to "store" as well
+        assert_eq!(hits(&reports[0], SearchBackend::Expanded).hits, 3);
+        assert_eq!(hits(&reports[1], SearchBackend::Fulltext).hits, 0);
+        assert_eq!(hits(&reports[1], SearchBackend::Fuzzy).hits, 2);
+        assert_eq!(hits(&reports[1], SearchBackend::Agrep).hits, 2);
+        let strict = bench(&store, &queries[1..2], &BenchOptions::default().with_runs(1).with_max_errors(0)).unwrap();
+        assert_eq!(hits(&strict[0], SearchBackend::Agrep).hits, 0);
+
+        let summary = summarize(&reports);
+        assert_eq!(summary.len(), 3 * SearchBackend::ALL.len());
+        assert!(summary.iter().all(|s| s.queries == 1));
+    }
+}
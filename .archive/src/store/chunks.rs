@@ -0,0 +1,184 @@
+//! Chunk-level retrieval with parent stitching
+//!
+//! Long documents are stored as a parent node plus chunk nodes, each linked
+//! to the parent with a `PartOf` edge and ordered by `chunk_index` metadata.
+//! A search hit on a chunk can be expanded into the surrounding chunks and
+//! the parent's summary so consumers read coherent passages.
+
+use super::{xref, Result, Store};
+use crate::schema::{AgentId, EdgeKind, NodeKind, StateEdge, StateNode};
+
+/// Metadata key holding a chunk's position within its parent
+pub const CHUNK_INDEX_KEY: &str = "chunk_index";
+
+/// Length of the summary derived from a parent without an explicit one
+const SUMMARY_CHARS: usize = 280;
+
+/// A hit together with its neighbouring chunks and parent document
+#[derive(Debug, Clone)]
+pub struct ChunkContext {
+    pub hit: StateNode,
+    pub parent: Option<StateNode>,
+    pub summary: Option<String>,
+    /// The hit and up to `radius` siblings either side, in document order
+    pub chunks: Vec<StateNode>,
+}
+
+impl ChunkContext {
+    /// Chunk texts joined in document order
+    pub fn passage(&self) -> String {
+        self.chunks
+            .iter()
+            .filter_map(|c| c.content.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+pub fn chunk_index(node: &StateNode) -> Option<u64> {
+    node.metadata.get(CHUNK_INDEX_KEY).and_then(|i| i.as_u64())
+}
+
+/// Expand a hit into its surrounding chunks and parent summary
+///
+/// Nodes that are not chunks come back on their own with no parent.
+pub fn expand_context<S: Store>(store: &S, hit: StateNode, radius: usize) -> Result<ChunkContext> {
+    let parent = store
+        .edges_from(hit.id)?
+        .into_iter()
+        .filter(|e| e.kind == EdgeKind::PartOf)
+        .find_map(|e| store.get_node(e.to).transpose())
+        .transpose()?;
+
+    let (Some(parent), Some(position)) = (parent, chunk_index(&hit)) else {
+        return Ok(ChunkContext {
+            chunks: vec![hit.clone()],
+            hit,
+            parent: None,
+            summary: None,
+        });
+    };
+
+    let lo = position.saturating_sub(radius as u64);
+    let hi = position.saturating_add(radius as u64);
+    let mut chunks = Vec::new();
+    for edge in store.edges_to(parent.id)? {
+        if edge.kind != EdgeKind::PartOf {
+            continue;
+        }
+        if let Some(sibling) = store.get_node(edge.from)? {
+            if chunk_index(&sibling).is_some_and(|i| (lo..=hi).contains(&i)) {
+                chunks.push(sibling);
+            }
+        }
+    }
+    chunks.sort_by_key(|c| chunk_index(c).unwrap_or_default());
+
+    Ok(ChunkContext {
+        summary: summarize(&parent),
+        hit,
+        parent: Some(parent),
+        chunks,
+    })
+}
+
+/// The parent's `summary` (content or metadata), else the start of its text
+fn summarize(parent: &StateNode) -> Option<String> {
+    let explicit = parent
+        .content
+        .get("summary")
+        .or_else(|| parent.metadata.get("summary"))
+        .and_then(|s| s.as_str());
+    if let Some(summary) = explicit {
+        return Some(summary.to_string());
+    }
+
+    let text = parent.content.get("text").and_then(|t| t.as_str())?;
+    let mut summary: String = text.chars().take(SUMMARY_CHARS).collect();
+    if text.chars().count() > SUMMARY_CHARS {
+        summary.push('…');
+    }
+    Some(summary)
+}
+
+/// Split a document node's markdown into one chunk per heading section
+///
+/// Text before the first heading becomes chunk 0. Each chunk is a `Context`
+/// node linked to the parent with `PartOf`.
+pub fn chunk_document<S: Store>(store: &S, parent: &StateNode, agent: AgentId) -> Result<Vec<StateNode>> {
+    let text = parent
+        .content
+        .get("text")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default();
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut starts: Vec<(usize, Option<String>)> = xref::extract(text)
+        .headings
+        .into_iter()
+        .map(|h| (h.line, Some(h.title)))
+        .collect();
+    if starts.first().map(|(line, _)| *line > 0).unwrap_or(true) {
+        starts.insert(0, (0, None));
+    }
+
+    let mut chunks = Vec::new();
+    for (i, (start, heading)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map(|(line, _)| *line).unwrap_or(lines.len());
+        let body = lines[*start..end].join("\n");
+        if body.trim().is_empty() {
+            continue;
+        }
+
+        let mut chunk = StateNode::new(
+            NodeKind::Context,
+            serde_json::json!({ "text": body.trim_end(), "heading": heading }),
+        );
+        chunk
+            .metadata
+            .insert(CHUNK_INDEX_KEY.to_string(), serde_json::json!(chunks.len()));
+        let chunk = store.create_node(chunk, agent.clone())?;
+        store.create_edge(StateEdge::new(chunk.id, parent.id, EdgeKind::PartOf), agent.clone())?;
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_expand_context_stitches_siblings() {
+        let store = SledStore::open_temporary().unwrap();
+        let text = "Preface.\n# One\nalpha\n# Two\nbeta\n# Three\ngamma\n# Four\ndelta";
+        let parent = store
+            .create_node(
+                StateNode::new(
+                    NodeKind::Context,
+                    serde_json::json!({ "text": text, "summary": "Greek letters" }),
+                ),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let chunks = chunk_document(&store, &parent, AgentId::User).unwrap();
+        assert_eq!(chunks.len(), 5);
+
+        let hit = chunks[2].clone();
+        let context = expand_context(&store, hit, 1).unwrap();
+        assert_eq!(context.parent.as_ref().unwrap().id, parent.id);
+        assert_eq!(context.summary.as_deref(), Some("Greek letters"));
+
+        let indices: Vec<_> = context.chunks.iter().filter_map(chunk_index).collect();
+        assert_eq!(indices, [1, 2, 3]);
+        assert!(context.passage().contains("alpha"));
+        assert!(!context.passage().contains("delta"));
+
+        let standalone = expand_context(&store, parent, 2).unwrap();
+        assert_eq!(standalone.chunks.len(), 1);
+        assert!(standalone.parent.is_none());
+    }
+}
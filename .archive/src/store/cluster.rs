@@ -0,0 +1,173 @@
+//! Topic clusters by label propagation
+//!
+//! Every node starts in a cluster of its own, then repeatedly joins the
+//! cluster with the most edge weight among its neighbours, edges counted in
+//! either direction. Densely linked groups settle on a shared label within
+//! a few rounds. Nodes are visited in ID order and ties keep the current
+//! label (else take the lowest), so the same graph always clusters the same
+//! way. Clusters are numbered by size, largest first, and can be written
+//! into node metadata so agents can work through the graph a topic at a time.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Result, SledStore, Store};
+use crate::schema::{AgentId, EdgeKind, Metadata, NodeId, NodeKind};
+
+/// Metadata field cluster numbers are written to by default
+pub const CLUSTER_KEY: &str = "cluster";
+
+/// Edge kinds that tie nodes into a topic by default
+pub fn default_cluster_kinds() -> Vec<EdgeKind> {
+    vec![EdgeKind::RelatedTo, EdgeKind::References]
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterOptions {
+    pub edge_kinds: Vec<EdgeKind>,
+    /// Only cluster nodes of these kinds; edges to other nodes are ignored
+    pub node_kinds: Option<Vec<NodeKind>>,
+    pub max_iterations: usize,
+}
+
+impl Default for ClusterOptions {
+    fn default() -> Self {
+        Self { edge_kinds: default_cluster_kinds(), node_kinds: None, max_iterations: 100 }
+    }
+}
+
+impl ClusterOptions {
+    pub fn with_edge_kinds(mut self, kinds: Vec<EdgeKind>) -> Self {
+        self.edge_kinds = kinds;
+        self
+    }
+
+    pub fn with_node_kinds(mut self, kinds: Vec<NodeKind>) -> Self {
+        self.node_kinds = Some(kinds);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cluster {
+    /// 0 for the largest cluster
+    pub id: usize,
+    /// Members in ID order
+    pub members: Vec<NodeId>,
+}
+
+/// Cluster every matching node; unlinked nodes end up alone
+pub fn clusters(store: &SledStore, options: &ClusterOptions) -> Result<Vec<Cluster>> {
+    let ids: Vec<NodeId> = {
+        let mut ids: Vec<NodeId> = store
+            .list_nodes(None, usize::MAX)?
+            .into_iter()
+            .filter(|n| options.node_kinds.as_ref().map_or(true, |ks| ks.contains(&n.kind)))
+            .map(|n| n.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    let index: HashMap<NodeId, usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+
+    let mut neighbors: Vec<Vec<(usize, f64)>> = vec![Vec::new(); ids.len()];
+    for edge in store.list_edges()? {
+        if !options.edge_kinds.contains(&edge.kind) || edge.from == edge.to {
+            continue;
+        }
+        if let (Some(&from), Some(&to)) = (index.get(&edge.from), index.get(&edge.to)) {
+            let weight = (edge.weight as f64).max(0.0);
+            neighbors[from].push((to, weight));
+            neighbors[to].push((from, weight));
+        }
+    }
+
+    let mut labels: Vec<usize> = (0..ids.len()).collect();
+    for _ in 0..options.max_iterations {
+        let mut changed = false;
+        for node in 0..ids.len() {
+            let mut tally: BTreeMap<usize, f64> = BTreeMap::new();
+            for &(other, weight) in &neighbors[node] {
+                *tally.entry(labels[other]).or_default() += weight;
+            }
+            let Some(best) = tally.values().copied().reduce(f64::max) else {
+                continue;
+            };
+            let current = labels[node];
+            if tally.get(&current).is_some_and(|&w| w >= best) {
+                continue;
+            }
+            // BTreeMap order makes this the lowest of the tied labels
+            let (&label, _) = tally.iter().find(|(_, &w)| w >= best).expect("best is in the tally");
+            labels[node] = label;
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<NodeId>> = BTreeMap::new();
+    for (node, label) in labels.into_iter().enumerate() {
+        groups.entry(label).or_default().push(ids[node]);
+    }
+    let mut members: Vec<Vec<NodeId>> = groups.into_values().collect();
+    members.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+    Ok(members.into_iter().enumerate().map(|(id, members)| Cluster { id, members }).collect())
+}
+
+/// Write each member's cluster number into its `field` metadata, returning
+/// how many nodes were updated
+pub fn write_clusters(store: &SledStore, clusters: &[Cluster], field: &str, agent: AgentId) -> Result<usize> {
+    let mut written = 0;
+    for cluster in clusters {
+        for &node in &cluster.members {
+            let fields = Metadata::from([(field.to_string(), serde_json::json!(cluster.id))]);
+            store.update_metadata(node, fields, agent.clone())?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::StateEdge;
+    use crate::store::test_util::{link, node, nodes};
+
+    #[test]
+    fn test_label_propagation_clusters() {
+        let store = SledStore::open_temporary().unwrap();
+        let big: [NodeId; 4] = nodes(&store, NodeKind::Insight);
+        let small: [NodeId; 3] = nodes(&store, NodeKind::Insight);
+        let alone = node(&store, NodeKind::Insight);
+        for group in [&big[..], &small[..]] {
+            for (i, &a) in group.iter().enumerate() {
+                for &b in &group[i + 1..] {
+                    link(&store, a, b, EdgeKind::RelatedTo);
+                }
+            }
+        }
+        // A light bridge and an ignored kind don't merge the groups
+        let mut bridge = StateEdge::new(big[0], small[0], EdgeKind::References);
+        bridge.weight = 0.5;
+        store.create_edge(bridge, AgentId::User).unwrap();
+        link(&store, big[1], small[1], EdgeKind::Blocks);
+
+        let found = clusters(&store, &ClusterOptions::default()).unwrap();
+        let sorted = |ids: &[NodeId]| {
+            let mut ids = ids.to_vec();
+            ids.sort();
+            ids
+        };
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].members, sorted(&big));
+        assert_eq!(found[1].members, sorted(&small));
+        assert_eq!(found[2].members, vec![alone]);
+        assert_eq!(clusters(&store, &ClusterOptions::default()).unwrap(), found);
+
+        assert_eq!(write_clusters(&store, &found, CLUSTER_KEY, AgentId::System).unwrap(), 8);
+        assert_eq!(store.get_node(small[2]).unwrap().unwrap().metadata[CLUSTER_KEY], 1);
+    }
+}
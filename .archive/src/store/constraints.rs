@@ -0,0 +1,321 @@
+//! Declarative graph constraints
+//!
+//! Named rules on the shape of the graph: how many edges of a kind a node
+//! may have, which node kinds an edge kind may join, and content fields
+//! that must be unique within a kind. `create_node`, `create_nodes`,
+//! `create_edge` and rewiring operations fail with
+//! `StoreError::ConstraintViolation`, naming every rule broken. Existing
+//! data isn't rechecked when a rule is added, and replaying the event log
+//! writes history as it was.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::{GraphReads, Result, Store, StoreError};
+use crate::schema::{EdgeKind, NodeKind, StateEdge, StateNode};
+
+/// One rule; kinds are written as on the command line (`part_of`,
+/// `custom:cites`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Constraint {
+    /// At most `max` `edge` edges leave a node, or arrive at it with
+    /// `incoming`; only nodes of kind `node` when one is given
+    MaxDegree {
+        edge: String,
+        max: usize,
+        #[serde(default)]
+        incoming: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        node: Option<String>,
+    },
+    /// `edge` edges may only run between these `[from, to]` node kinds
+    AllowedPairs { edge: String, pairs: Vec<(String, String)> },
+    /// No two `kind` nodes share a value at JSON Pointer `field` of their
+    /// content; nodes without the field are exempt
+    Unique { kind: String, field: String },
+}
+
+impl Constraint {
+    fn validate(&self) -> Result<()> {
+        // Rules compare kinds by name, so each must be spelled as it displays
+        let canonical = |kind: &str, parsed: std::result::Result<String, String>| -> Result<()> {
+            match parsed {
+                Ok(name) if name == kind => Ok(()),
+                Ok(name) => Err(StoreError::InvalidOperation(format!("Write kind {} as {}", kind, name))),
+                Err(e) => Err(StoreError::InvalidOperation(e)),
+            }
+        };
+        let node_kind = |kind: &str| canonical(kind, kind.parse::<NodeKind>().map(|k| k.to_string()));
+        let edge_kind = |kind: &str| canonical(kind, kind.parse::<EdgeKind>().map(|k| k.to_string()));
+        match self {
+            Constraint::MaxDegree { edge, node, .. } => {
+                edge_kind(edge)?;
+                node.as_deref().map(node_kind).transpose()?;
+            }
+            Constraint::AllowedPairs { edge, pairs } => {
+                edge_kind(edge)?;
+                for (from, to) in pairs {
+                    node_kind(from)?;
+                    node_kind(to)?;
+                }
+            }
+            Constraint::Unique { kind, field } => {
+                node_kind(kind)?;
+                if !field.is_empty() && !field.starts_with('/') {
+                    return Err(StoreError::InvalidOperation(format!(
+                        "Unique field must be a JSON Pointer like /title, not {}",
+                        field
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constraint::MaxDegree { edge, max, incoming, node } => write!(
+                f,
+                "at most {} {} edge(s) {} {} node",
+                max,
+                edge,
+                if *incoming { "into" } else { "out of" },
+                node.as_deref().map_or("any".to_string(), |kind| format!("a {}", kind))
+            ),
+            Constraint::AllowedPairs { edge, pairs } => {
+                let pairs: Vec<String> = pairs.iter().map(|(from, to)| format!("{} -> {}", from, to)).collect();
+                write!(f, "{} edges only join {}", edge, pairs.join(", "))
+            }
+            Constraint::Unique { kind, field } => write!(f, "{} nodes have unique {}", kind, field),
+        }
+    }
+}
+
+/// One way a write breaks a rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstraintViolation {
+    /// Name of the rule
+    pub constraint: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.constraint, self.message)
+    }
+}
+
+/// Rules keyed by name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphConstraints {
+    #[serde(default)]
+    pub rules: BTreeMap<String, Constraint>,
+}
+
+impl GraphConstraints {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Add or replace the rule `name`
+    pub fn set(&mut self, name: &str, constraint: Constraint) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(StoreError::InvalidOperation("Constraint name is empty".into()));
+        }
+        constraint.validate()?;
+        self.rules.insert(name.to_string(), constraint);
+        Ok(())
+    }
+
+    /// Drop the rule `name`; returns whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.rules.remove(name).is_some()
+    }
+
+    /// Check every rule, as when loaded from a file
+    pub fn validate(&self) -> Result<()> {
+        self.rules.values().try_for_each(Constraint::validate)
+    }
+
+    /// Every rule `edge` would break if written to `store` now
+    pub fn edge_violations<S: GraphReads + ?Sized>(&self, store: &S, edge: &StateEdge) -> Result<Vec<ConstraintViolation>> {
+        let kind = edge.kind.to_string();
+        let mut violations = Vec::new();
+        let node_kind = |id| -> Result<Option<String>> { Ok(store.node(id)?.map(|node| node.kind.to_string())) };
+
+        for (name, rule) in &self.rules {
+            let violation = |message: String| ConstraintViolation { constraint: name.clone(), message };
+            match rule {
+                Constraint::MaxDegree { edge: rule_edge, max, incoming, node } if *rule_edge == kind => {
+                    let at = if *incoming { edge.to } else { edge.from };
+                    if node.is_some() && node_kind(at)? != *node {
+                        continue;
+                    }
+                    let existing = if *incoming { store.incoming(at)? } else { store.outgoing(at)? };
+                    let count = existing.iter().filter(|e| e.kind == edge.kind && e.id != edge.id).count();
+                    if count >= *max {
+                        violations.push(violation(format!(
+                            "{} already has {} {} edge(s) {}; the limit is {}",
+                            at,
+                            count,
+                            kind,
+                            if *incoming { "in" } else { "out" },
+                            max
+                        )));
+                    }
+                }
+                Constraint::AllowedPairs { edge: rule_edge, pairs } if *rule_edge == kind => {
+                    let (Some(from), Some(to)) = (node_kind(edge.from)?, node_kind(edge.to)?) else {
+                        continue;
+                    };
+                    if !pairs.iter().any(|(f, t)| *f == from && *t == to) {
+                        violations.push(violation(format!("{} edges may not run from {} to {}", kind, from, to)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Every rule `node` would break if created in `store` along with the
+    /// not-yet-written `pending` nodes
+    pub fn node_violations<S: Store + ?Sized>(
+        &self,
+        store: &S,
+        node: &StateNode,
+        pending: &[StateNode],
+    ) -> Result<Vec<ConstraintViolation>> {
+        let kind = node.kind.to_string();
+        let mut violations = Vec::new();
+        for (name, rule) in &self.rules {
+            let Constraint::Unique { kind: rule_kind, field } = rule else {
+                continue;
+            };
+            let Some(value) = (*rule_kind == kind).then(|| node.content.pointer(field)).flatten() else {
+                continue;
+            };
+            let shares = |other: &StateNode| {
+                other.id != node.id && other.kind == node.kind && other.content.pointer(field) == Some(value)
+            };
+            let clash = match pending.iter().find(|other| shares(other)) {
+                Some(other) => Some(other.id),
+                None => store.list_nodes(Some(node.kind.clone()), usize::MAX)?.iter().find(|other| shares(other)).map(|n| n.id),
+            };
+            if let Some(other) = clash {
+                violations.push(ConstraintViolation {
+                    constraint: name.clone(),
+                    message: format!("{} {} is already {} on {}", kind, field, compact(value), other),
+                });
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Fail with `StoreError::ConstraintViolation` if `edge` breaks a rule
+    pub fn check_edge<S: GraphReads + ?Sized>(&self, store: &S, edge: &StateEdge) -> Result<()> {
+        violated(self.edge_violations(store, edge)?)
+    }
+
+    /// Fail with `StoreError::ConstraintViolation` if `node` breaks a rule
+    pub fn check_node<S: Store + ?Sized>(&self, store: &S, node: &StateNode, pending: &[StateNode]) -> Result<()> {
+        violated(self.node_violations(store, node, pending)?)
+    }
+}
+
+fn violated(violations: Vec<ConstraintViolation>) -> Result<()> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(StoreError::ConstraintViolation(violations))
+    }
+}
+
+fn compact(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() > 40 {
+        text.chars().take(37).collect::<String>() + "..."
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, NodeId};
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_constraints() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut constraints = GraphConstraints::default();
+        let rule = |json: Value| serde_json::from_value::<Constraint>(json).unwrap();
+        constraints
+            .set("one-successor", rule(serde_json::json!({"type": "max_degree", "edge": "supersedes", "max": 1})))
+            .unwrap();
+        constraints
+            .set(
+                "small-projects",
+                rule(serde_json::json!({"type": "max_degree", "edge": "part_of", "max": 2, "incoming": true, "node": "project"})),
+            )
+            .unwrap();
+        constraints
+            .set(
+                "cite-conversations",
+                rule(serde_json::json!({"type": "allowed_pairs", "edge": "references", "pairs": [["insight", "conversation"]]})),
+            )
+            .unwrap();
+        constraints.set("unique-titles", rule(serde_json::json!({"type": "unique", "kind": "task", "field": "/title"}))).unwrap();
+        assert!(constraints.set("bad", rule(serde_json::json!({"type": "unique", "kind": "task", "field": "title"}))).is_err());
+        assert!(constraints.set("bad", rule(serde_json::json!({"type": "max_degree", "edge": "nope", "max": 1}))).is_err());
+        store.set_graph_constraints(&constraints).unwrap();
+
+        let node = |kind: NodeKind, content: Value| store.create_node(StateNode::new(kind, content), AgentId::User);
+        let link = |from: NodeId, to: NodeId, kind: EdgeKind| {
+            store.create_edge(StateEdge::new(from, to, kind), AgentId::User)
+        };
+        let broken = |result: Result<StateEdge>| match result {
+            Err(StoreError::ConstraintViolation(violations)) => {
+                violations.into_iter().map(|v| v.constraint).collect::<Vec<_>>()
+            }
+            other => panic!("expected a constraint violation, got {:?}", other.map(|e| e.id)),
+        };
+
+        let a = node(NodeKind::Task, serde_json::json!({"title": "a"})).unwrap().id;
+        let b = node(NodeKind::Task, serde_json::json!({"title": "b"})).unwrap().id;
+        let c = node(NodeKind::Task, serde_json::json!({})).unwrap().id;
+        assert!(node(NodeKind::Task, serde_json::json!({})).is_ok());
+        match node(NodeKind::Task, serde_json::json!({"title": "a"})) {
+            Err(StoreError::ConstraintViolation(violations)) => assert_eq!(violations[0].constraint, "unique-titles"),
+            other => panic!("expected a constraint violation, got {:?}", other.map(|n| n.id)),
+        }
+        let batch = vec![
+            StateNode::new(NodeKind::Task, serde_json::json!({"title": "d"})),
+            StateNode::new(NodeKind::Task, serde_json::json!({"title": "d"})),
+        ];
+        assert!(store.create_nodes(batch, AgentId::User, crate::store::DedupeMode::Off).is_err());
+
+        link(a, b, EdgeKind::Supersedes).unwrap();
+        assert_eq!(broken(link(a, c, EdgeKind::Supersedes)), ["one-successor"]);
+        link(b, c, EdgeKind::Supersedes).unwrap();
+
+        let project = node(NodeKind::Project, serde_json::json!({})).unwrap().id;
+        link(a, project, EdgeKind::PartOf).unwrap();
+        link(b, project, EdgeKind::PartOf).unwrap();
+        assert_eq!(broken(link(c, project, EdgeKind::PartOf)), ["small-projects"]);
+        // The limit only applies to projects
+        link(c, a, EdgeKind::PartOf).unwrap();
+        link(b, a, EdgeKind::PartOf).unwrap();
+        link(project, a, EdgeKind::PartOf).unwrap();
+
+        let insight = node(NodeKind::Insight, serde_json::json!({})).unwrap().id;
+        let conversation = node(NodeKind::Conversation, serde_json::json!({})).unwrap().id;
+        link(insight, conversation, EdgeKind::References).unwrap();
+        assert_eq!(broken(link(a, conversation, EdgeKind::References)), ["cite-conversations"]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Cycle detection over chosen edge kinds
+//!
+//! Blocking and containment only make sense without loops: a task that
+//! transitively blocks itself can never start. `find_cycles` reports the
+//! loops already in the graph, and the store can be told to refuse edges
+//! that would close a new one (`SledStore::set_acyclic_kinds`). Edges of
+//! every listed kind count together, so `a blocks b` and `b part_of a` form
+//! a cycle over `[Blocks, PartOf]`.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use super::{GraphReads, Result, SledStore};
+use crate::schema::{EdgeKind, NodeId, StateEdge};
+
+/// Kinds `graph cycles` checks when none are given
+pub fn default_cycle_kinds() -> Vec<EdgeKind> {
+    vec![EdgeKind::Blocks, EdgeKind::PartOf]
+}
+
+/// A directed loop: `edges[i]` leaves `nodes[i]`, and the last edge returns
+/// to `nodes[0]`
+#[derive(Debug, Clone, Serialize)]
+pub struct Cycle {
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<StateEdge>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    OnPath,
+    Done,
+}
+
+/// Loops formed by edges of `kinds`, one per edge that closes a loop
+///
+/// Every node on a cycle appears in at least one reported cycle, though
+/// not every distinct cycle through a tangle of edges is listed.
+pub fn find_cycles(store: &SledStore, kinds: &[EdgeKind]) -> Result<Vec<Cycle>> {
+    let mut adjacency: BTreeMap<NodeId, Vec<StateEdge>> = BTreeMap::new();
+    for edge in store.list_edges()? {
+        if kinds.contains(&edge.kind) {
+            adjacency.entry(edge.from).or_default().push(edge);
+        }
+    }
+
+    let mut visits: HashMap<NodeId, Visit> = HashMap::new();
+    let mut cycles = Vec::new();
+    for &root in adjacency.keys() {
+        if visits.contains_key(&root) {
+            continue;
+        }
+        // Depth-first, with the next edge to try at each node on the path
+        let mut path: Vec<(NodeId, usize)> = vec![(root, 0)];
+        let mut taken: Vec<&StateEdge> = Vec::new();
+        visits.insert(root, Visit::OnPath);
+        while let Some(&(node, next)) = path.last() {
+            let Some(edge) = adjacency.get(&node).and_then(|edges| edges.get(next)) else {
+                visits.insert(node, Visit::Done);
+                path.pop();
+                taken.pop();
+                continue;
+            };
+            path.last_mut().expect("path is not empty").1 += 1;
+            match visits.get(&edge.to) {
+                Some(Visit::OnPath) => {
+                    let start = path.iter().position(|&(n, _)| n == edge.to).expect("node is on the path");
+                    let mut edges: Vec<StateEdge> = taken[start..].iter().map(|&e| e.clone()).collect();
+                    edges.push(edge.clone());
+                    cycles.push(Cycle { nodes: path[start..].iter().map(|&(n, _)| n).collect(), edges });
+                }
+                Some(Visit::Done) => {}
+                None => {
+                    visits.insert(edge.to, Visit::OnPath);
+                    path.push((edge.to, 0));
+                    taken.push(edge);
+                }
+            }
+        }
+    }
+    Ok(cycles)
+}
+
+/// The loop `edge` would close among existing edges of `kinds`, starting
+/// with `edge.from`, or `None` if it wouldn't close one
+pub fn would_close_cycle<S: GraphReads + ?Sized>(
+    store: &S,
+    edge: &StateEdge,
+    kinds: &[EdgeKind],
+) -> Result<Option<Vec<NodeId>>> {
+    if !kinds.contains(&edge.kind) {
+        return Ok(None);
+    }
+    if edge.from == edge.to {
+        return Ok(Some(vec![edge.from]));
+    }
+
+    // Breadth-first from the new edge's target, looking for its source
+    let mut parents: HashMap<NodeId, NodeId> = HashMap::from([(edge.to, edge.to)]);
+    let mut queue = VecDeque::from([edge.to]);
+    while let Some(node) = queue.pop_front() {
+        for next in store.outgoing(node)? {
+            if !kinds.contains(&next.kind) || parents.contains_key(&next.to) {
+                continue;
+            }
+            parents.insert(next.to, node);
+            if next.to == edge.from {
+                let mut trail = Vec::new();
+                let mut at = node;
+                while at != edge.to {
+                    trail.push(at);
+                    at = parents[&at];
+                }
+                trail.push(edge.to);
+                trail.push(edge.from);
+                trail.reverse();
+                return Ok(Some(trail));
+            }
+            queue.push_back(next.to);
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, NodeKind};
+    use crate::store::test_util::nodes;
+    use crate::store::{Store, StoreError};
+
+    #[test]
+    fn test_cycles_found_and_rejected() {
+        let store = SledStore::open_temporary().unwrap();
+        let [a, b, c] = nodes(&store, NodeKind::Task);
+        let link = |from, to, kind| store.create_edge(StateEdge::new(from, to, kind), AgentId::User);
+
+        link(a, b, EdgeKind::Blocks).unwrap();
+        link(b, c, EdgeKind::Blocks).unwrap();
+        link(c, a, EdgeKind::RelatedTo).unwrap();
+        assert!(find_cycles(&store, &default_cycle_kinds()).unwrap().is_empty());
+
+        link(c, a, EdgeKind::PartOf).unwrap();
+        let cycles = find_cycles(&store, &default_cycle_kinds()).unwrap();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), 3);
+        assert_eq!(cycles[0].edges.last().unwrap().to, cycles[0].nodes[0]);
+        assert!(find_cycles(&store, &[EdgeKind::Blocks]).unwrap().is_empty());
+
+        store.set_acyclic_kinds(&[EdgeKind::Blocks]).unwrap();
+        match link(c, a, EdgeKind::Blocks) {
+            Err(StoreError::WouldCycle(trail)) => assert_eq!(trail, vec![c, a, b]),
+            other => panic!("expected a cycle error, got {:?}", other),
+        }
+        assert!(matches!(link(a, a, EdgeKind::Blocks), Err(StoreError::WouldCycle(_))));
+        // Other kinds are unconstrained
+        link(b, a, EdgeKind::Enables).unwrap();
+    }
+}
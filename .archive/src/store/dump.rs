@@ -0,0 +1,222 @@
+//! Portable whole-database dumps
+//!
+//! A dump is a zstd-compressed stream of JSON lines: a header, one line per
+//! record, and a trailer holding the record count and a SHA-256 over every
+//! line before it. Records are written in their JSON form rather than as
+//! stored bytes, so a dump does not depend on sled or on the on-disk schema
+//! and loads into any later version of the crate.
+//!
+//! Indexes are not dumped; loading rebuilds them as records are written.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use super::attachment::Attachment;
+use super::share::{ShareAuditEntry, ShareLink};
+use super::{Result, StoreError};
+use crate::schema::{Annotation, EdgeId, NodeId, Reaction, StateEdge, StateEvent, StateNode};
+
+pub const DUMP_FORMAT: &str = "elegant-state-dump";
+pub const DUMP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpHeader {
+    pub format: String,
+    pub version: u32,
+    /// Schema version of the database the dump was taken from
+    pub schema_version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Value of the database's ID counter, so sequence keys stay unique
+    pub counter: u64,
+}
+
+/// One record; `namespace` is `None` for the default namespace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DumpRecord {
+    Node {
+        namespace: Option<String>,
+        node: StateNode,
+        #[serde(default)]
+        archived: bool,
+    },
+    Edge { namespace: Option<String>, edge: StateEdge },
+    Event { namespace: Option<String>, event: StateEvent },
+    Annotation { namespace: Option<String>, annotation: Annotation },
+    Reaction { namespace: Option<String>, reaction: Reaction },
+    Meta { namespace: Option<String>, key: String, value: Value },
+    MetaIndex { namespace: Option<String>, field: String },
+    Alias { namespace: Option<String>, alias: String, node: NodeId },
+    /// Two edges created as each other's reciprocal
+    Reciprocal { namespace: Option<String>, edge: EdgeId, reciprocal: EdgeId },
+    Attachment {
+        namespace: Option<String>,
+        attachment: Attachment,
+        /// Hex-encoded bytes
+        data: String,
+    },
+    Share { namespace: Option<String>, link: ShareLink },
+    ShareAudit { namespace: Option<String>, seq: u64, entry: ShareAuditEntry },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DumpTrailer {
+    records: u64,
+    sha256: String,
+}
+
+/// What a dump or load covered
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DumpSummary {
+    pub schema_version: u32,
+    pub namespaces: usize,
+    pub nodes: usize,
+    pub edges: usize,
+    pub events: usize,
+    pub records: u64,
+}
+
+impl DumpSummary {
+    pub(crate) fn count(&mut self, record: &DumpRecord) {
+        self.records += 1;
+        match record {
+            DumpRecord::Node { .. } => self.nodes += 1,
+            DumpRecord::Edge { .. } => self.edges += 1,
+            DumpRecord::Event { .. } => self.events += 1,
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn io_error(path: &Path, e: impl std::fmt::Display) -> StoreError {
+    StoreError::InvalidOperation(format!("{}: {}", path.display(), e))
+}
+
+pub(crate) fn json_error(e: serde_json::Error) -> StoreError {
+    StoreError::Serialization(e.to_string())
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return Err(StoreError::Serialization("odd-length hex data".into()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|e| StoreError::Serialization(format!("bad hex data: {}", e)))
+        })
+        .collect()
+}
+
+/// Writes the framed, checksummed stream
+pub(crate) struct DumpWriter {
+    encoder: zstd::Encoder<'static, std::fs::File>,
+    hasher: Sha256,
+    records: u64,
+    path: std::path::PathBuf,
+}
+
+impl DumpWriter {
+    pub fn create(path: &Path, header: &DumpHeader) -> Result<Self> {
+        let file = std::fs::File::create(path).map_err(|e| io_error(path, e))?;
+        let encoder = zstd::Encoder::new(file, 3).map_err(|e| io_error(path, e))?;
+        let mut writer = Self {
+            encoder,
+            hasher: Sha256::new(),
+            records: 0,
+            path: path.to_path_buf(),
+        };
+        writer.line(&serde_json::to_vec(header).map_err(json_error)?)?;
+        Ok(writer)
+    }
+
+    fn line(&mut self, json: &[u8]) -> Result<()> {
+        self.hasher.update(json);
+        self.hasher.update(b"\n");
+        self.encoder.write_all(json).map_err(|e| io_error(&self.path, e))?;
+        self.encoder.write_all(b"\n").map_err(|e| io_error(&self.path, e))
+    }
+
+    pub fn record(&mut self, record: &DumpRecord) -> Result<()> {
+        self.line(&serde_json::to_vec(record).map_err(json_error)?)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<u64> {
+        let trailer = DumpTrailer {
+            records: self.records,
+            sha256: to_hex(&self.hasher.clone().finalize()),
+        };
+        let json = serde_json::to_vec(&trailer).map_err(json_error)?;
+        self.encoder.write_all(&json).map_err(|e| io_error(&self.path, e))?;
+        self.encoder.write_all(b"\n").map_err(|e| io_error(&self.path, e))?;
+        self.encoder.finish().map_err(|e| io_error(&self.path, e))?;
+        Ok(self.records)
+    }
+}
+
+fn lines(path: &Path) -> Result<impl Iterator<Item = Result<String>>> {
+    let file = std::fs::File::open(path).map_err(|e| io_error(path, e))?;
+    let decoder = zstd::Decoder::new(file).map_err(|e| io_error(path, e))?;
+    let path = path.to_path_buf();
+    Ok(BufReader::new(decoder).lines().map(move |line| line.map_err(|e| io_error(&path, e))))
+}
+
+/// Check a dump's framing and checksum without loading it
+pub fn verify_dump(path: &Path) -> Result<DumpHeader> {
+    let mut lines = lines(path)?.peekable();
+    let mut hasher = Sha256::new();
+    let header_line = lines
+        .next()
+        .ok_or_else(|| StoreError::Serialization("empty dump".into()))??;
+    let header: DumpHeader = serde_json::from_str(&header_line).map_err(json_error)?;
+    if header.format != DUMP_FORMAT {
+        return Err(StoreError::Serialization(format!("not a dump: {}", header.format)));
+    }
+    if header.version > DUMP_VERSION {
+        return Err(StoreError::Serialization(format!(
+            "dump format version {} is newer than this build supports ({})",
+            header.version, DUMP_VERSION
+        )));
+    }
+    hasher.update(header_line.as_bytes());
+    hasher.update(b"\n");
+
+    let mut records = 0u64;
+    while let Some(line) = lines.next() {
+        let line = line?;
+        if lines.peek().is_none() {
+            let trailer: DumpTrailer = serde_json::from_str(&line).map_err(json_error)?;
+            if trailer.records != records || trailer.sha256 != to_hex(&hasher.finalize()) {
+                return Err(StoreError::Serialization("dump checksum mismatch".into()));
+            }
+            return Ok(header);
+        }
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+        records += 1;
+    }
+    Err(StoreError::Serialization("dump is truncated (no trailer)".into()))
+}
+
+/// Records of a verified dump, in file order
+pub(crate) fn read_records(path: &Path) -> Result<impl Iterator<Item = Result<DumpRecord>>> {
+    let mut lines = lines(path)?.peekable();
+    lines.next(); // header
+    Ok(std::iter::from_fn(move || {
+        let line = lines.next()?;
+        // The last line is the trailer
+        lines.peek()?;
+        Some(line.and_then(|l| serde_json::from_str(&l).map_err(json_error)))
+    }))
+}
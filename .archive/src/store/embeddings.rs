@@ -0,0 +1,199 @@
+//! Cache of text embeddings, keyed by model and content hash
+//!
+//! Embeddings from external APIs cost money per call, so each vector is
+//! kept under its model name and the SHA-256 of the embedded text: nodes
+//! with identical text, and re-embedding after restarts, are served from
+//! the cache. Hits and misses are counted per model for `embeddings stats`.
+//! Purge a model's entries after changing what it means (a new version
+//! behind the same name).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::{Result, StoreError};
+use crate::schema::StateNode;
+
+pub(crate) const EMBEDDINGS_TREE: &str = "embeddings";
+
+/// Hit and miss counters, keyed `hits/<model>` and `misses/<model>`
+pub(crate) const EMBEDDING_COUNTERS_TREE: &str = "embedding_counters";
+
+/// Computes embeddings, typically by calling out to an API
+pub trait Embedder {
+    /// Cache namespace; vectors from different models never mix
+    fn model(&self) -> &str;
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Embedder that runs an external command, writing the text to its stdin
+/// and reading a JSON array of numbers from stdout
+pub struct CommandEmbedder {
+    model: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandEmbedder {
+    /// Build from a shell-style command line split on whitespace
+    pub fn from_command_line(model: impl Into<String>, command: &str) -> Result<Self> {
+        let mut parts = command.split_whitespace().map(str::to_string);
+        let program = parts
+            .next()
+            .ok_or_else(|| StoreError::Embedding("empty embedding command".into()))?;
+        Ok(Self { model: model.into(), program, args: parts.collect() })
+    }
+}
+
+impl Embedder for CommandEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| StoreError::Embedding(format!("failed to run {}: {}", self.program, e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| StoreError::Embedding(format!("failed to write text: {}", e)))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| StoreError::Embedding(format!("{} failed: {}", self.program, e)))?;
+        if !output.status.success() {
+            return Err(StoreError::Embedding(String::from_utf8_lossy(&output.stderr).into_owned()));
+        }
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| StoreError::Embedding(format!("expected a JSON array of numbers: {}", e)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedEmbedding {
+    pub vector: Vec<f32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Cache contents and effectiveness for one model
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelStats {
+    pub entries: u64,
+    /// Stored size of the cached vectors
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ModelStats {
+    /// Share of lookups served from the cache, if there were any
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EmbeddingStats {
+    pub models: BTreeMap<String, ModelStats>,
+}
+
+impl EmbeddingStats {
+    /// Everything summed across models
+    pub fn total(&self) -> ModelStats {
+        self.models.values().fold(ModelStats::default(), |mut total, model| {
+            total.entries += model.entries;
+            total.bytes += model.bytes;
+            total.hits += model.hits;
+            total.misses += model.misses;
+            total
+        })
+    }
+}
+
+/// Cache key: model, a zero byte, then the text's hash
+pub(crate) fn cache_key(model: &str, text: &str) -> Vec<u8> {
+    let mut key = model.as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(super::attachment::content_hash(text.as_bytes()).as_bytes());
+    key
+}
+
+/// Model a cache key belongs to
+pub(crate) fn key_model(key: &[u8]) -> String {
+    let end = key.iter().position(|&b| b == 0).unwrap_or(key.len());
+    String::from_utf8_lossy(&key[..end]).into_owned()
+}
+
+/// Text of a node as embedded and cited: `content.text` when present,
+/// otherwise the JSON content
+pub fn node_text(node: &StateNode) -> String {
+    node.content
+        .get("text")
+        .and_then(|t| t.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| node.content.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+    use std::cell::Cell;
+
+    /// Counts calls, as a stand-in for a billed API
+    struct Counting {
+        model: &'static str,
+        calls: Cell<usize>,
+    }
+
+    impl Embedder for Counting {
+        fn model(&self) -> &str {
+            self.model
+        }
+
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(vec![text.len() as f32, 1.0])
+        }
+    }
+
+    #[test]
+    fn test_embedding_cache() {
+        let store = SledStore::open_temporary().unwrap();
+        let small = Counting { model: "small-v1", calls: Cell::new(0) };
+        let large = Counting { model: "large-v1", calls: Cell::new(0) };
+
+        assert_eq!(store.embedding(&small, "same text").unwrap(), vec![9.0, 1.0]);
+        assert_eq!(store.embedding(&small, "same text").unwrap(), vec![9.0, 1.0]);
+        store.embedding(&small, "other").unwrap();
+        store.embedding(&large, "same text").unwrap();
+        assert_eq!((small.calls.get(), large.calls.get()), (2, 1));
+        assert!(store.cached_embedding("small-v1", "same text").unwrap().is_some());
+
+        let stats = store.embedding_stats().unwrap();
+        let small_stats = &stats.models["small-v1"];
+        assert_eq!((small_stats.entries, small_stats.hits, small_stats.misses), (2, 1, 2));
+        assert_eq!(small_stats.hit_rate(), Some(1.0 / 3.0));
+        assert_eq!(stats.total().entries, 3);
+
+        // A purge forgets one model's vectors and counters
+        assert_eq!(store.purge_embeddings(Some("small-v1")).unwrap(), 2);
+        let stats = store.embedding_stats().unwrap();
+        assert!(!stats.models.contains_key("small-v1"));
+        assert_eq!(stats.models["large-v1"].entries, 1);
+        store.embedding(&small, "same text").unwrap();
+        assert_eq!(small.calls.get(), 3);
+
+        assert_eq!(store.purge_embeddings(None).unwrap(), 2);
+        assert_eq!(store.embedding_stats().unwrap(), EmbeddingStats::default());
+    }
+}
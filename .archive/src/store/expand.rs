@@ -0,0 +1,216 @@
+//! Multi-query retrieval expansion
+//!
+//! A query is expanded into reformulations, each reformulation is searched
+//! in parallel, and the ranked lists are merged with reciprocal rank fusion.
+//! Every fused hit remembers which subqueries found it.
+
+use std::collections::HashMap;
+
+use super::{Result, Store};
+use crate::schema::{NodeId, NodeKind, StateNode};
+
+/// Rank offset for reciprocal rank fusion; damps the weight of top ranks
+const RRF_K: f64 = 60.0;
+
+/// Produces reformulations of a query; the original should come first
+pub trait QueryExpander {
+    fn expand(&self, query: &str) -> Vec<String>;
+}
+
+/// Expands queries by swapping in synonyms and searching significant terms
+/// on their own
+pub struct SynonymExpander {
+    synonyms: HashMap<String, Vec<String>>,
+    max_queries: usize,
+}
+
+impl Default for SynonymExpander {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SynonymExpander {
+    /// Expander with a small built-in vocabulary
+    pub fn new() -> Self {
+        let groups: &[&[&str]] = &[
+            &["db", "database", "store"],
+            &["doc", "document", "docs"],
+            &["bug", "issue", "defect"],
+            &["todo", "task"],
+            &["config", "configuration", "settings"],
+            &["error", "failure"],
+            &["remove", "delete"],
+            &["create", "add"],
+            &["search", "find", "query"],
+            &["llm", "model"],
+            &["agent", "bot"],
+        ];
+
+        let mut expander = Self {
+            synonyms: HashMap::new(),
+            max_queries: 8,
+        };
+        for group in groups {
+            expander = expander.with_group(group);
+        }
+        expander
+    }
+
+    /// Treat every word in `group` as a synonym of the others
+    pub fn with_group(mut self, group: &[&str]) -> Self {
+        for word in group {
+            let others = group
+                .iter()
+                .filter(|w| *w != word)
+                .map(|w| w.to_string());
+            self.synonyms
+                .entry(word.to_lowercase())
+                .or_default()
+                .extend(others);
+        }
+        self
+    }
+
+    pub fn with_max_queries(mut self, max_queries: usize) -> Self {
+        self.max_queries = max_queries.max(1);
+        self
+    }
+}
+
+impl QueryExpander for SynonymExpander {
+    fn expand(&self, query: &str) -> Vec<String> {
+        let query = query.trim().to_string();
+        let words: Vec<&str> = query.split_whitespace().collect();
+        let mut queries = vec![query.clone()];
+
+        for (i, word) in words.iter().enumerate() {
+            for synonym in self.synonyms.get(&word.to_lowercase()).into_iter().flatten() {
+                let mut rewritten = words.clone();
+                rewritten[i] = synonym.as_str();
+                queries.push(rewritten.join(" "));
+            }
+        }
+
+        if words.len() > 1 {
+            queries.extend(
+                words
+                    .iter()
+                    .filter(|w| w.chars().count() > 3)
+                    .map(|w| w.to_string()),
+            );
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        queries.retain(|q| seen.insert(q.to_lowercase()));
+        queries.truncate(self.max_queries);
+        queries
+    }
+}
+
+/// A fused search hit
+#[derive(Debug, Clone)]
+pub struct ExpandedHit {
+    pub node: StateNode,
+    /// Reciprocal rank fusion score
+    pub score: f64,
+    /// Subqueries that found this node, with its rank in each (1-based)
+    pub matched_by: Vec<(String, usize)>,
+}
+
+/// Search every query in parallel and fuse the results
+pub fn search_expanded<S: Store + Sync>(
+    store: &S,
+    queries: &[String],
+    kinds: Option<Vec<NodeKind>>,
+    limit: usize,
+) -> Result<Vec<ExpandedHit>> {
+    let ranked: Vec<Result<Vec<StateNode>>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = queries
+            .iter()
+            .map(|query| {
+                let kinds = kinds.clone();
+                scope.spawn(move || ranked_search(store, query, kinds))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("search thread panicked"))
+            .collect()
+    });
+
+    let mut fused: HashMap<NodeId, ExpandedHit> = HashMap::new();
+    for (query, hits) in queries.iter().zip(ranked) {
+        for (rank, node) in hits?.into_iter().enumerate() {
+            let hit = fused.entry(node.id).or_insert_with(|| ExpandedHit {
+                node,
+                score: 0.0,
+                matched_by: Vec::new(),
+            });
+            hit.score += 1.0 / (RRF_K + rank as f64 + 1.0);
+            hit.matched_by.push((query.clone(), rank + 1));
+        }
+    }
+
+    let mut hits: Vec<ExpandedHit> = fused.into_values().collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.node.id.cmp(&a.node.id))
+    });
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Store search ranked by how often the query occurs in each node
+fn ranked_search<S: Store>(store: &S, query: &str, kinds: Option<Vec<NodeKind>>) -> Result<Vec<StateNode>> {
+    let needle = query.to_lowercase();
+    let mut hits: Vec<(usize, StateNode)> = store
+        .search(query, kinds)?
+        .into_iter()
+        .map(|node| (node.content.to_string().to_lowercase().matches(&needle).count(), node))
+        .collect();
+    hits.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.id.cmp(&a.1.id)));
+    Ok(hits.into_iter().map(|(_, node)| node).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::AgentId;
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_expand_with_synonyms() {
+        let queries = SynonymExpander::new().expand("db errors");
+        assert_eq!(queries[0], "db errors");
+        assert!(queries.contains(&"database errors".to_string()));
+        assert!(queries.contains(&"errors".to_string()));
+    }
+
+    #[test]
+    fn test_fused_hits_are_attributed() {
+        let store = SledStore::open_temporary().unwrap();
+        let both = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "db and database tuning"})),
+                AgentId::User,
+            )
+            .unwrap();
+        store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "database backups"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let queries = vec!["db".to_string(), "database".to_string()];
+        let hits = search_expanded(&store, &queries, None, 10).unwrap();
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].node.id, both.id);
+        assert_eq!(hits[0].matched_by.len(), 2);
+        assert_eq!(hits[1].matched_by.len(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+//! Which parts of node content search reads, per kind
+//!
+//! By default search matches against a node's whole JSON content, keys and
+//! all. A kind's rule narrows that to the values under its `include` JSON
+//! Pointers (all of the content when there are none), minus anything under
+//! its `exclude` pointers, so base64 blobs and bookkeeping fields stop
+//! bloating the index and producing false hits. When the kind has a
+//! content schema, every pointer must lead somewhere the schema allows.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::schemas::ContentSchemas;
+use super::{Result, StoreError};
+use crate::schema::{NodeKind, StateNode};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextRule {
+    /// JSON Pointers whose values are searched; empty for all content
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// JSON Pointers left out, even inside included values
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl TextRule {
+    pub fn with_include(mut self, pointer: impl Into<String>) -> Self {
+        self.include.push(pointer.into());
+        self
+    }
+
+    pub fn with_exclude(mut self, pointer: impl Into<String>) -> Self {
+        self.exclude.push(pointer.into());
+        self
+    }
+}
+
+/// Text rules keyed by kind (`context`, `custom:paper`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextExtraction {
+    #[serde(default)]
+    pub kinds: BTreeMap<String, TextRule>,
+}
+
+impl TextExtraction {
+    pub fn get(&self, kind: &NodeKind) -> Option<&TextRule> {
+        self.kinds.get(&kind.to_string())
+    }
+
+    /// Set `kind`'s rule, checking its pointers against the kind's schema
+    pub fn set(&mut self, kind: &NodeKind, rule: TextRule, schemas: &ContentSchemas) -> Result<()> {
+        check_rule(kind, &rule, schemas)?;
+        self.kinds.insert(kind.to_string(), rule);
+        Ok(())
+    }
+
+    /// Search `kind`'s whole content again; returns whether it had a rule
+    pub fn remove(&mut self, kind: &NodeKind) -> bool {
+        self.kinds.remove(&kind.to_string()).is_some()
+    }
+
+    /// Check every rule, as when loaded from a file
+    pub fn validate(&self, schemas: &ContentSchemas) -> Result<()> {
+        for (kind, rule) in &self.kinds {
+            let kind = kind.parse::<NodeKind>().map_err(StoreError::InvalidOperation)?;
+            check_rule(&kind, rule, schemas)?;
+        }
+        Ok(())
+    }
+
+    /// The text search matches a node against
+    pub fn text(&self, node: &StateNode) -> String {
+        let Some(rule) = self.get(&node.kind) else {
+            return node.content.to_string();
+        };
+        let mut content = node.content.clone();
+        for pointer in &rule.exclude {
+            remove_pointer(&mut content, pointer);
+        }
+
+        let mut parts = Vec::new();
+        if rule.include.is_empty() {
+            collect_leaves(&content, &mut parts);
+        } else {
+            for value in rule.include.iter().filter_map(|pointer| content.pointer(pointer)) {
+                collect_leaves(value, &mut parts);
+            }
+        }
+        parts.join("\n")
+    }
+}
+
+fn check_rule(kind: &NodeKind, rule: &TextRule, schemas: &ContentSchemas) -> Result<()> {
+    for pointer in rule.include.iter().chain(&rule.exclude) {
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(StoreError::InvalidOperation(format!(
+                "Invalid JSON Pointer {:?}: expected \"\" or a path starting with /",
+                pointer
+            )));
+        }
+        if !schemas.allows_pointer(kind, pointer) {
+            return Err(StoreError::InvalidOperation(format!(
+                "{} content can't have {}, according to its schema",
+                kind, pointer
+            )));
+        }
+    }
+    if rule.exclude.iter().any(String::is_empty) {
+        return Err(StoreError::InvalidOperation("Excluding \"\" would leave nothing to search".into()));
+    }
+    Ok(())
+}
+
+/// Strings, numbers and booleans under `value`, in document order
+fn collect_leaves(value: &Value, parts: &mut Vec<String>) {
+    match value {
+        Value::String(text) => parts.push(text.clone()),
+        Value::Number(_) | Value::Bool(_) => parts.push(value.to_string()),
+        Value::Array(items) => items.iter().for_each(|item| collect_leaves(item, parts)),
+        Value::Object(fields) => fields.values().for_each(|field| collect_leaves(field, parts)),
+        Value::Null => {}
+    }
+}
+
+fn remove_pointer(content: &mut Value, pointer: &str) {
+    let Some((parent, last)) = pointer.rsplit_once('/') else {
+        return;
+    };
+    let last = last.replace("~1", "/").replace("~0", "~");
+    match content.pointer_mut(parent) {
+        Some(Value::Object(fields)) => {
+            fields.remove(&last);
+        }
+        Some(Value::Array(items)) => {
+            if let Some(index) = last.parse::<usize>().ok().filter(|&i| i < items.len()) {
+                items.remove(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::AgentId;
+    use crate::store::{SledStore, Store};
+    use serde_json::json;
+
+    #[test]
+    fn test_text_extraction() {
+        let store = SledStore::open_temporary().unwrap();
+        let blob = "aGVsbG8gd29ybGQ=";
+        let context = store
+            .create_node(
+                StateNode::new(NodeKind::Context, json!({"text": "meeting notes", "raw": blob, "source": "zoom"})),
+                AgentId::User,
+            )
+            .unwrap();
+        let task = store
+            .create_node(
+                StateNode::new(NodeKind::Task, json!({"title": "review", "attachment": {"data": blob}})),
+                AgentId::User,
+            )
+            .unwrap();
+        assert_eq!(store.search("aGVsbG8", None).unwrap().len(), 2);
+
+        let mut extraction = store.text_extraction().unwrap();
+        let schemas = store.content_schemas().unwrap();
+        extraction.set(&NodeKind::Context, TextRule::default().with_include("/text"), &schemas).unwrap();
+        extraction.set(&NodeKind::Task, TextRule::default().with_exclude("/attachment/data"), &schemas).unwrap();
+        store.set_text_extraction(&extraction).unwrap();
+
+        assert!(store.search("aGVsbG8", None).unwrap().is_empty());
+        assert!(store.search("zoom", None).unwrap().is_empty());
+        assert_eq!(store.search("meeting", None).unwrap()[0].id, context.id);
+        assert_eq!(store.search("review", None).unwrap()[0].id, task.id);
+        // Keys aren't searched once a kind has a rule
+        assert!(store.search("title", Some(vec![NodeKind::Task])).unwrap().is_empty());
+
+        // Pointers must fit the kind's schema
+        let mut schemas = store.content_schemas().unwrap();
+        schemas
+            .set(
+                &NodeKind::Insight,
+                json!({"type": "object", "properties": {"text": {"type": "string"}}, "additionalProperties": false}),
+            )
+            .unwrap();
+        store.set_content_schemas(&schemas).unwrap();
+        let rule = TextRule::default().with_include("/text");
+        extraction.set(&NodeKind::Insight, rule, &schemas).unwrap();
+        assert!(extraction.set(&NodeKind::Insight, TextRule::default().with_include("/body"), &schemas).is_err());
+        assert!(extraction.set(&NodeKind::Insight, TextRule::default().with_include("/text/0"), &schemas).is_err());
+        assert!(extraction.set(&NodeKind::Insight, TextRule::default().with_exclude("text"), &schemas).is_err());
+    }
+}
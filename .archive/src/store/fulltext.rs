@@ -1,28 +1,37 @@
 //! Full-text search using tantivy
 //!
-//! Provides indexing and querying capabilities for StateNodes.
+//! Provides indexing and querying capabilities for StateNodes. Tags are
+//! indexed as facets, so searches can be narrowed to tags and matches
+//! counted per tag. `IndexSync` keeps an index current through the store's
+//! hooks; `reindex` rebuilds it from scratch.
 
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tantivy::{
-    collector::TopDocs,
+    collector::{FacetCollector, TopDocs},
     directory::MmapDirectory,
-    query::QueryParser,
-    schema::{Schema, STORED, TEXT, STRING, Field},
+    query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::{Facet, FacetOptions, IndexRecordOption, Schema, STORED, TEXT, STRING, Field, Value},
     Index, IndexWriter, IndexReader, TantivyDocument,
 };
-use crate::schema::{NodeId, NodeKind, StateNode};
-use crate::store::StoreError;
+use crate::schema::{NodeId, NodeKind, Operation, StateEvent, StateNode, Target};
+use crate::store::{HookPoint, SledStore, Store, StoreError, TextExtraction};
+
+/// Memory budget of an index writer
+pub const WRITER_HEAP_BYTES: usize = 50_000_000;
 
 /// Full-text search index for StateNodes
 pub struct FullTextIndex {
     index: Index,
     reader: IndexReader,
-    schema: Schema,
     // Fields
     id_field: Field,
     kind_field: Field,
     content_field: Field,
     metadata_field: Field,
+    tags_field: Field,
 }
 
 impl FullTextIndex {
@@ -34,6 +43,7 @@ pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
         let kind_field = schema_builder.add_text_field("kind", STRING | STORED);
         let content_field = schema_builder.add_text_field("content", TEXT | STORED);
         let metadata_field = schema_builder.add_text_field("metadata", TEXT);
+        let tags_field = schema_builder.add_facet_field("tags", FacetOptions::default());
 
         let schema = schema_builder.build();
 
@@ -54,11 +64,11 @@ pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
         Ok(Self {
             index,
             reader,
-            schema,
             id_field,
             kind_field,
             content_field,
             metadata_field,
+            tags_field,
         })
     }
 
@@ -70,6 +80,7 @@ pub fn open_in_memory() -> Result<Self, StoreError> {
         let kind_field = schema_builder.add_text_field("kind", STRING | STORED);
         let content_field = schema_builder.add_text_field("content", TEXT | STORED);
         let metadata_field = schema_builder.add_text_field("metadata", TEXT);
+        let tags_field = schema_builder.add_facet_field("tags", FacetOptions::default());
 
         let schema = schema_builder.build();
 
@@ -82,11 +93,11 @@ pub fn open_in_memory() -> Result<Self, StoreError> {
         Ok(Self {
             index,
             reader,
-            schema,
             id_field,
             kind_field,
             content_field,
             metadata_field,
+            tags_field,
         })
     }
 
@@ -97,16 +108,24 @@ pub fn writer(&self, heap_size: usize) -> Result<IndexWriter, StoreError> {
             .map_err(|e| StoreError::Serialization(e.to_string()))
     }
 
-    /// Index a node
-    pub fn index_node(&self, writer: &IndexWriter, node: &StateNode) -> Result<(), StoreError> {
+    /// Index a node's searchable text, as chosen by `extraction`
+    pub fn index_node(
+        &self,
+        writer: &IndexWriter,
+        node: &StateNode,
+        extraction: &TextExtraction,
+    ) -> Result<(), StoreError> {
         let mut doc = TantivyDocument::new();
         doc.add_text(self.id_field, node.id.to_string());
         doc.add_text(self.kind_field, node.kind.to_string());
-        doc.add_text(self.content_field, node.content.to_string());
+        doc.add_text(self.content_field, extraction.text(node));
         doc.add_text(
             self.metadata_field,
             serde_json::to_string(&node.metadata).unwrap_or_default(),
         );
+        for tag in &node.tags {
+            doc.add_facet(self.tags_field, Facet::from_path([tag]));
+        }
 
         writer
             .add_document(doc)
@@ -122,11 +141,49 @@ pub fn remove_node(&self, writer: &IndexWriter, id: NodeId) -> Result<(), StoreE
         Ok(())
     }
 
-    /// Search for nodes matching the query
+    /// Rebuild the index from `store`'s nodes, or only its nodes of `kinds`,
+    /// calling `progress` with the number indexed so far
+    pub fn reindex(
+        &self,
+        store: &SledStore,
+        kinds: Option<&[NodeKind]>,
+        mut progress: impl FnMut(usize),
+    ) -> Result<usize, StoreError> {
+        let mut writer = self.writer(WRITER_HEAP_BYTES)?;
+        match kinds {
+            Some(kinds) => {
+                for kind in kinds {
+                    writer.delete_term(tantivy::Term::from_field_text(self.kind_field, &kind.to_string()));
+                }
+            }
+            None => {
+                writer
+                    .delete_all_documents()
+                    .map_err(|e| StoreError::Serialization(e.to_string()))?;
+            }
+        }
+
+        let extraction = store.text_extraction()?;
+        let mut indexed = 0;
+        for node in store.list_nodes(None, usize::MAX)? {
+            if kinds.is_some_and(|kinds| !kinds.contains(&node.kind)) {
+                continue;
+            }
+            self.index_node(&writer, &node, &extraction)?;
+            indexed += 1;
+            progress(indexed);
+        }
+        writer.commit().map_err(|e| StoreError::Serialization(e.to_string()))?;
+        Ok(indexed)
+    }
+
+    /// Search for nodes matching the query, optionally narrowed to any of
+    /// `kinds` and to nodes carrying every one of `tags`
     pub fn search(
         &self,
         query: &str,
         kinds: Option<&[NodeKind]>,
+        tags: &[String],
         limit: usize,
     ) -> Result<Vec<SearchResult>, StoreError> {
         self.reader
@@ -134,14 +191,10 @@ pub fn search(
             .map_err(|e| StoreError::Serialization(e.to_string()))?;
 
         let searcher = self.reader.searcher();
-
-        let query_parser = QueryParser::for_index(&self.index, vec![self.content_field]);
-        let parsed_query = query_parser
-            .parse_query(query)
-            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+        let query = self.filtered_query(Some(query), kinds, tags)?;
 
         let top_docs = searcher
-            .search(&parsed_query, &TopDocs::with_limit(limit))
+            .search(&query, &TopDocs::with_limit(limit))
             .map_err(|e| StoreError::Serialization(e.to_string()))?;
 
         let mut results = Vec::new();
@@ -163,13 +216,6 @@ pub fn search(
                 .unwrap_or_default()
                 .to_string();
 
-            // Filter by kind if specified
-            if let Some(allowed_kinds) = kinds {
-                if !allowed_kinds.iter().any(|k| k.to_string() == kind) {
-                    continue;
-                }
-            }
-
             let content = doc
                 .get_first(self.content_field)
                 .and_then(|v| v.as_str())
@@ -186,6 +232,185 @@ pub fn search(
 
         Ok(results)
     }
+
+    /// Number of nodes matching `query` (every node when `None`) under each tag
+    pub fn tag_counts(&self, query: Option<&str>, kinds: Option<&[NodeKind]>) -> Result<BTreeMap<String, u64>, StoreError> {
+        self.reader
+            .reload()
+            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+
+        let mut collector = FacetCollector::for_field("tags");
+        collector.add_facet(Facet::root());
+        let counts = self
+            .reader
+            .searcher()
+            .search(&self.filtered_query(query, kinds, &[])?, &collector)
+            .map_err(|e| StoreError::Serialization(e.to_string()))?;
+
+        Ok(counts
+            .get("/")
+            .filter_map(|(facet, count)| Some((facet.to_path().last()?.to_string(), count)))
+            .collect())
+    }
+
+    /// `query` over the content, with kind and tag filters applied in the
+    /// query rather than afterwards, so a kind or tag that is rare among
+    /// the matches still fills `limit`
+    fn filtered_query(
+        &self,
+        query: Option<&str>,
+        kinds: Option<&[NodeKind]>,
+        tags: &[String],
+    ) -> Result<Box<dyn Query>, StoreError> {
+        let parsed_query: Box<dyn Query> = match query {
+            Some(query) => QueryParser::for_index(&self.index, vec![self.content_field])
+                .parse_query(query)
+                .map_err(|e| StoreError::Serialization(e.to_string()))?,
+            None => Box::new(AllQuery),
+        };
+
+        let mut clauses = vec![(Occur::Must, parsed_query)];
+        if let Some(kinds) = kinds {
+            let any_kind = kinds
+                .iter()
+                .map(|kind| {
+                    let term = tantivy::Term::from_field_text(self.kind_field, &kind.to_string());
+                    let query: Box<dyn Query> = Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                    (Occur::Should, query)
+                })
+                .collect();
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(any_kind))));
+        }
+        for tag in tags {
+            let term = tantivy::Term::from_facet(self.tags_field, &Facet::from_path([tag]));
+            clauses.push((Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
+        }
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+}
+
+/// Keeps a full-text index in step with a store's writes
+///
+/// `register` hooks it onto the store: created and updated nodes are read
+/// back and reindexed, deleted ones removed. Changes are committed by
+/// `commit`, and once the store and every handle are dropped. The index
+/// follows the view it was registered on; nodes in other namespaces are
+/// left out.
+#[derive(Clone)]
+pub struct IndexSync {
+    state: Arc<SyncState>,
+}
+
+struct SyncState {
+    index: FullTextIndex,
+    /// Opened on the first change, so read-only use never takes the lock
+    writer: Mutex<Option<IndexWriter>>,
+    dirty: AtomicBool,
+    /// Changes a strict store's hooks failed to index, reported by `commit`
+    missed: Mutex<Vec<String>>,
+}
+
+impl IndexSync {
+    pub fn new(index: FullTextIndex) -> Self {
+        Self {
+            state: Arc::new(SyncState {
+                index,
+                writer: Mutex::new(None),
+                dirty: AtomicBool::new(false),
+                missed: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Maintain the index from `store`'s create, update and delete hooks
+    ///
+    /// A change that fails to index is logged, or on a strict store kept
+    /// for the next `commit` to fail with.
+    pub fn register(&self, store: &SledStore) {
+        // The hooks live in the store, so they read through a view without
+        // them rather than keep the store alive
+        let reader = store.without_hooks();
+        for point in [HookPoint::Create, HookPoint::Update, HookPoint::Delete] {
+            let (state, reader) = (self.state.clone(), reader.clone());
+            store.hooks().register(
+                point,
+                Arc::new(move |event: &StateEvent| {
+                    if let Err(e) = state.apply(&reader, event) {
+                        let problem = format!("full-text index not updated for event {}: {}", event.id, e);
+                        if reader.is_strict() {
+                            state.missed.lock().unwrap_or_else(|e| e.into_inner()).push(problem);
+                        } else {
+                            tracing::warn!("{}", problem);
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Make the changes so far visible to searches, failing if a strict
+    /// store's change was left out since the last commit
+    pub fn commit(&self) -> Result<(), StoreError> {
+        self.state.commit()
+    }
+
+    /// Spawn a tokio task that commits pending changes every `interval`,
+    /// for long-running processes
+    pub fn spawn_commits(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let sync = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = sync.commit() {
+                    tracing::warn!("full-text index commit failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+impl SyncState {
+    fn apply(&self, store: &SledStore, event: &StateEvent) -> Result<(), StoreError> {
+        let Target::Node(id) = event.target else {
+            return Ok(());
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if writer.is_none() {
+            *writer = Some(self.index.writer(WRITER_HEAP_BYTES)?);
+        }
+        let writer = writer.as_ref().expect("writer opened above");
+
+        self.index.remove_node(writer, id)?;
+        if event.operation != Operation::Delete {
+            if let Some(node) = store.get_node(id)? {
+                self.index.index_node(writer, &node, &store.text_extraction()?)?;
+            }
+        }
+        self.dirty.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<(), StoreError> {
+        if self.dirty.swap(false, Ordering::AcqRel) {
+            if let Some(writer) = self.writer.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                writer.commit().map_err(|e| StoreError::Serialization(e.to_string()))?;
+            }
+        }
+        let missed = std::mem::take(&mut *self.missed.lock().unwrap_or_else(|e| e.into_inner()));
+        match missed.is_empty() {
+            true => Ok(()),
+            false => Err(StoreError::Degraded(missed.join("; "))),
+        }
+    }
+}
+
+impl Drop for SyncState {
+    fn drop(&mut self) {
+        if let Err(e) = self.commit() {
+            tracing::warn!("full-text index changes lost: {}", e);
+        }
+    }
 }
 
 /// A search result with relevance score
@@ -200,6 +425,7 @@ pub struct SearchResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schema::AgentId;
     use serde_json::json;
 
     #[test]
@@ -208,11 +434,86 @@ fn test_fulltext_search() {
         let mut writer = index.writer(50_000_000).unwrap();
 
         let node = StateNode::new(NodeKind::Insight, json!({"text": "hello world rust programming"}));
-        index.index_node(&writer, &node).unwrap();
+        index.index_node(&writer, &node, &TextExtraction::default()).unwrap();
         writer.commit().unwrap();
 
-        let results = index.search("rust", None, 10).unwrap();
+        let results = index.search("rust", None, &[], 10).unwrap();
         assert!(!results.is_empty());
         assert!(results[0].content.contains("rust"));
     }
+
+    #[test]
+    fn test_tag_facets() {
+        let index = FullTextIndex::open_in_memory().unwrap();
+        let mut writer = index.writer(50_000_000).unwrap();
+
+        let node = StateNode::new(NodeKind::Insight, json!({"text": "hello world rust programming"}))
+            .with_tags(["lang", "systems"]);
+        index.index_node(&writer, &node, &TextExtraction::default()).unwrap();
+        let other = StateNode::new(NodeKind::Insight, json!({"text": "rust belt history"})).with_tags(["history"]);
+        index.index_node(&writer, &other, &TextExtraction::default()).unwrap();
+        writer.commit().unwrap();
+
+        assert_eq!(index.search("rust", None, &[], 10).unwrap().len(), 2);
+        let tagged = index.search("rust", None, &["lang".to_string()], 10).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].id, node.id.to_string());
+
+        let counts = index.tag_counts(Some("programming"), None).unwrap();
+        assert_eq!(counts, BTreeMap::from([("lang".to_string(), 1), ("systems".to_string(), 1)]));
+        assert_eq!(index.tag_counts(None, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_index_follows_store_writes() {
+        let store = SledStore::open_temporary().unwrap();
+        let sync = IndexSync::new(FullTextIndex::open_in_memory().unwrap());
+        sync.register(&store);
+
+        let node = store
+            .create_node(StateNode::new(NodeKind::Insight, json!({"text": "tantivy keeps up"})), AgentId::User)
+            .unwrap();
+        sync.commit().unwrap();
+        let hits = |query: &str| sync.state.index.search(query, None, &[], 10).unwrap().len();
+        assert_eq!(hits("tantivy"), 1);
+
+        store.update_node(node.id, json!({"text": "sled underneath"}), None, AgentId::User).unwrap();
+        sync.commit().unwrap();
+        assert_eq!((hits("tantivy"), hits("sled")), (0, 1));
+
+        store.delete_node(node.id, AgentId::User).unwrap();
+        sync.commit().unwrap();
+        assert_eq!(hits("sled"), 0);
+    }
+
+    #[test]
+    fn test_strict_store_reports_unindexed_changes() {
+        let store = SledStore::open_temporary().unwrap().with_strict(true);
+        let sync = IndexSync::new(FullTextIndex::open_in_memory().unwrap());
+        sync.register(&store);
+
+        // Holding the index's only writer makes the hook fail
+        let writer = sync.state.index.writer(WRITER_HEAP_BYTES).unwrap();
+        store.create_node(StateNode::new(NodeKind::Insight, json!({"text": "missed"})), AgentId::User).unwrap();
+        assert!(matches!(sync.commit(), Err(StoreError::Degraded(_))));
+        drop(writer);
+        assert!(sync.commit().is_ok());
+    }
+
+    #[test]
+    fn test_reindex() {
+        let store = SledStore::open_temporary().unwrap();
+        let index = FullTextIndex::open_in_memory().unwrap();
+        for (kind, text) in [(NodeKind::Task, "alpha"), (NodeKind::Insight, "alpha beta")] {
+            store.create_node(StateNode::new(kind, json!({"text": text})), AgentId::User).unwrap();
+        }
+
+        assert_eq!(index.reindex(&store, Some(&[NodeKind::Task]), |_| {}).unwrap(), 1);
+        assert_eq!(index.search("alpha", None, &[], 10).unwrap().len(), 1);
+        assert_eq!(index.reindex(&store, None, |_| {}).unwrap(), 2);
+        assert_eq!(index.search("alpha", None, &[], 10).unwrap().len(), 2);
+        // Kind-limited rebuilds replace only that kind's documents
+        assert_eq!(index.reindex(&store, Some(&[NodeKind::Insight]), |_| {}).unwrap(), 1);
+        assert_eq!(index.search("alpha", None, &[], 10).unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,150 @@
+//! Hierarchies built from `PartOf` edges
+//!
+//! `a part_of b` makes `a` a child of `b`, so a project's tasks and their
+//! subtasks form a tree under it. A node with several parents appears under
+//! each; a `PartOf` cycle is cut where it would revisit a node on the
+//! current branch.
+
+use serde::Serialize;
+use std::collections::HashSet;
+
+use super::{Result, Store};
+use crate::schema::{EdgeKind, NodeId, StateNode};
+
+/// A node and the nodes `PartOf` it
+#[derive(Debug, Clone, Serialize)]
+pub struct Tree {
+    pub node: StateNode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Tree>,
+}
+
+impl Tree {
+    /// Number of nodes in the tree, counting repeats
+    pub fn size(&self) -> usize {
+        1 + self.children.iter().map(Tree::size).sum::<usize>()
+    }
+
+    /// Indented outline, one labelled node per line
+    pub fn render(&self, label: impl Fn(&StateNode) -> String) -> String {
+        let mut out = format!("{}\n", label(&self.node));
+        render_children(&self.children, "", &label, &mut out);
+        out
+    }
+}
+
+fn render_children(children: &[Tree], prefix: &str, label: &impl Fn(&StateNode) -> String, out: &mut String) {
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        out.push_str(&format!("{}{}{}\n", prefix, if last { "└── " } else { "├── " }, label(&child.node)));
+        render_children(&child.children, &format!("{}{}", prefix, if last { "    " } else { "│   " }), label, out);
+    }
+}
+
+/// The hierarchy under `root`, at most `max_depth` levels deep; `None`
+/// if the root doesn't exist
+pub fn tree<S: Store + ?Sized>(store: &S, root: NodeId, max_depth: Option<usize>) -> Result<Option<Tree>> {
+    let Some(node) = store.get_node(root)? else {
+        return Ok(None);
+    };
+    let mut branch = HashSet::from([root]);
+    Ok(Some(subtree(store, node, max_depth, &mut branch)?))
+}
+
+fn subtree<S: Store + ?Sized>(
+    store: &S,
+    node: StateNode,
+    depth: Option<usize>,
+    branch: &mut HashSet<NodeId>,
+) -> Result<Tree> {
+    let mut children = Vec::new();
+    if depth != Some(0) {
+        for id in linked(store, node.id, false)? {
+            if !branch.insert(id) {
+                continue;
+            }
+            if let Some(child) = store.get_node(id)? {
+                children.push(subtree(store, child, depth.map(|d| d - 1), branch)?);
+            }
+            branch.remove(&id);
+        }
+    }
+    Ok(Tree { node, children })
+}
+
+/// Parents (`up`) or children of a node, in ID order
+fn linked<S: Store + ?Sized>(store: &S, id: NodeId, up: bool) -> Result<Vec<NodeId>> {
+    let edges = if up { store.edges_from(id)? } else { store.edges_to(id)? };
+    let mut ids: Vec<NodeId> = edges
+        .into_iter()
+        .filter(|e| e.kind == EdgeKind::PartOf)
+        .map(|e| if up { e.to } else { e.from })
+        .collect();
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Breadth-first walk over `PartOf` edges for `Store::ancestors` and
+/// `Store::descendants`
+pub(super) fn part_of_closure<S: Store + ?Sized>(
+    store: &S,
+    id: NodeId,
+    max_depth: Option<usize>,
+    up: bool,
+) -> Result<Vec<StateNode>> {
+    let mut seen = HashSet::from([id]);
+    let mut frontier = vec![id];
+    let mut found = Vec::new();
+    let mut depth = 0;
+    while !frontier.is_empty() && max_depth.map_or(true, |max| depth < max) {
+        let mut next = Vec::new();
+        for node in frontier {
+            next.extend(linked(store, node, up)?.into_iter().filter(|n| seen.insert(*n)));
+        }
+        for &node in &next {
+            found.extend(store.get_node(node)?);
+        }
+        frontier = next;
+        depth += 1;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, NodeKind, StateEdge};
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_part_of_hierarchy() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = |n: u64, kind, title: &str| {
+            let node = StateNode::new(kind, serde_json::json!({ "title": title })).with_id(ulid::Ulid::from_parts(n, 0));
+            store.create_node(node, AgentId::User).unwrap().id
+        };
+        let part_of = |child, parent| {
+            store.create_edge(StateEdge::new(child, parent, EdgeKind::PartOf), AgentId::User).unwrap();
+        };
+        let project = node(1, NodeKind::Project, "Launch");
+        let (design, build) = (node(2, NodeKind::Task, "Design"), node(3, NodeKind::Task, "Build"));
+        let tests = node(4, NodeKind::Task, "Tests");
+        part_of(design, project);
+        part_of(build, project);
+        part_of(tests, build);
+        // A cycle back to the root is cut rather than followed forever
+        part_of(project, tests);
+
+        let ids = |nodes: Vec<StateNode>| nodes.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.ancestors(tests).unwrap()), [build, project]);
+        assert_eq!(ids(store.descendants(project, None).unwrap()), [design, build, tests]);
+        assert_eq!(ids(store.descendants(project, Some(1)).unwrap()), [design, build]);
+
+        let full = tree(&store, project, None).unwrap().unwrap();
+        assert_eq!(full.size(), 4);
+        let outline = full.render(|n| n.content["title"].as_str().unwrap().to_string());
+        assert_eq!(outline, "Launch\n├── Design\n└── Build\n    └── Tests\n");
+        assert_eq!(tree(&store, project, Some(1)).unwrap().unwrap().size(), 3);
+    }
+}
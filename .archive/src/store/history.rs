@@ -0,0 +1,235 @@
+//! Point-in-time reconstruction from the event log
+//!
+//! Every node and edge change is logged, so the graph at any earlier moment
+//! can be rebuilt by folding the events up to it: creates and full-capture
+//! updates replace the snapshot, diff-capture updates patch it, deletes
+//! clear it. A node whose history passes through a hash-only event can't be
+//! reconstructed past that point. Archiving isn't an event, so an archived
+//! node still shows its state as of the last change.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::{Result, StoreError};
+use crate::schema::{
+    AgentId, CaptureMode, EdgeId, EventId, NodeId, Operation, StateEdge, StateEvent, StateNode, Target,
+};
+
+/// Order events as they happened; IDs minted in the same millisecond don't
+/// sort in creation order, timestamps do
+pub(crate) fn chronological(events: &mut [StateEvent]) {
+    events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then(a.id.cmp(&b.id)));
+}
+
+fn decode<T: serde::de::DeserializeOwned>(event: &StateEvent, value: &Option<Value>) -> Result<T> {
+    let value = value
+        .clone()
+        .ok_or_else(|| StoreError::InvalidOperation(format!("Event {} has no payload", event.id)))?;
+    serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+}
+
+/// One stored state of a node, with the change that produced it
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeVersion {
+    pub node: StateNode,
+    pub event: EventId,
+    pub agent: AgentId,
+    pub operation: Operation,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Fold one event into the snapshot of node `id`
+fn apply(snapshot: &mut Option<Value>, event: &StateEvent, id: NodeId) -> Result<()> {
+    match event.operation {
+        Operation::Delete => *snapshot = None,
+        Operation::Create | Operation::Update if event.capture == CaptureMode::Hash => {
+            return Err(StoreError::InvalidOperation(format!(
+                "Event {} captured only a hash of node {}",
+                event.id, id
+            )));
+        }
+        Operation::Update if event.capture == CaptureMode::Diff => {
+            let current = snapshot.as_mut().ok_or_else(|| {
+                StoreError::InvalidOperation(format!("Event {} patches node {} before it exists", event.id, id))
+            })?;
+            let patch: json_patch::Patch = decode(event, &event.after)?;
+            json_patch::patch(current, &patch)
+                .map_err(|e| StoreError::InvalidOperation(format!("Event {}: {}", event.id, e)))?;
+        }
+        Operation::Create | Operation::Update => *snapshot = Some(decode(event, &event.after)?),
+        Operation::Link | Operation::Unlink => {}
+    }
+    Ok(())
+}
+
+fn targets(event: &StateEvent, id: NodeId) -> bool {
+    matches!(&event.target, Target::Node(target) if *target == id)
+}
+
+fn to_node(value: Value) -> Result<StateNode> {
+    serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+}
+
+/// The node `id` as of `at`, from chronologically ordered events; `None` if
+/// it didn't exist then
+pub fn node_as_of(events: &[StateEvent], id: NodeId, at: DateTime<Utc>) -> Result<Option<StateNode>> {
+    let mut snapshot: Option<Value> = None;
+    for event in events.iter().take_while(|e| e.timestamp <= at).filter(|e| targets(e, id)) {
+        apply(&mut snapshot, event, id)?;
+    }
+    snapshot.map(to_node).transpose()
+}
+
+/// Every state node `id` has had, oldest first, from chronologically
+/// ordered events
+pub fn node_versions(events: &[StateEvent], id: NodeId) -> Result<Vec<NodeVersion>> {
+    let mut snapshot: Option<Value> = None;
+    let mut versions = Vec::new();
+    for event in events.iter().filter(|e| targets(e, id)) {
+        apply(&mut snapshot, event, id)?;
+        if let (Operation::Create | Operation::Update, Some(value)) = (&event.operation, &snapshot) {
+            versions.push(NodeVersion {
+                node: to_node(value.clone())?,
+                event: event.id,
+                agent: event.agent.clone(),
+                operation: event.operation.clone(),
+                timestamp: event.timestamp,
+            });
+        }
+    }
+    Ok(versions)
+}
+
+/// Node `id` just before and just after `event`, from chronologically
+/// ordered events; `None` on a side where it didn't exist
+pub(crate) fn around(
+    events: &[StateEvent],
+    id: NodeId,
+    event: EventId,
+) -> Result<(Option<StateNode>, Option<StateNode>)> {
+    let mut snapshot: Option<Value> = None;
+    for e in events.iter().filter(|e| targets(e, id)) {
+        let before = snapshot.clone();
+        apply(&mut snapshot, e, id)?;
+        if e.id == event {
+            return Ok((before.map(to_node).transpose()?, snapshot.map(to_node).transpose()?));
+        }
+    }
+    Err(StoreError::InvalidOperation(format!("Event {} is not in the history of node {}", event, id)))
+}
+
+/// Edges touching node `id` as of `at`, from chronologically ordered events
+pub fn edges_as_of(events: &[StateEvent], id: NodeId, at: DateTime<Utc>) -> Result<Vec<StateEdge>> {
+    let mut edges: BTreeMap<EdgeId, StateEdge> = BTreeMap::new();
+    for event in events.iter().take_while(|e| e.timestamp <= at) {
+        let Target::Edge(edge_id) = &event.target else {
+            continue;
+        };
+        match event.operation {
+            Operation::Link | Operation::Update => {
+                let edge: StateEdge = decode(event, &event.after)?;
+                if edge.from == id || edge.to == id {
+                    edges.insert(*edge_id, edge);
+                }
+            }
+            Operation::Unlink => {
+                edges.remove(edge_id);
+            }
+            _ => {}
+        }
+    }
+    Ok(edges.into_values().collect())
+}
+
+/// Every node and edge as of `at`, from chronologically ordered events
+pub fn graph_as_of(events: &[StateEvent], at: DateTime<Utc>) -> Result<(Vec<StateNode>, Vec<StateEdge>)> {
+    let mut nodes: BTreeMap<NodeId, Option<Value>> = BTreeMap::new();
+    let mut edges: BTreeMap<EdgeId, StateEdge> = BTreeMap::new();
+    for event in events.iter().take_while(|e| e.timestamp <= at) {
+        match &event.target {
+            Target::Node(id) => apply(nodes.entry(*id).or_default(), event, *id)?,
+            Target::Edge(id) => match event.operation {
+                Operation::Link | Operation::Update => {
+                    edges.insert(*id, decode(event, &event.after)?);
+                }
+                Operation::Unlink | Operation::Delete => {
+                    edges.remove(id);
+                }
+                Operation::Create => {}
+            },
+        }
+    }
+    let nodes: Vec<StateNode> = nodes.into_values().flatten().map(to_node).collect::<Result<_>>()?;
+    Ok((nodes, edges.into_values().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, CapturePolicy, EdgeKind, NodeKind};
+    use crate::store::{SledStore, Store};
+
+    #[test]
+    fn test_node_as_of() {
+        let store = SledStore::open_temporary().unwrap();
+        let pause = || std::thread::sleep(std::time::Duration::from_millis(5));
+        let node = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"v": 1})), AgentId::User).unwrap();
+        let other = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User).unwrap();
+        pause();
+        let first = Utc::now();
+        pause();
+        store.update_node(node.id, serde_json::json!({"v": 2}), None, AgentId::User).unwrap();
+        let edge = store.create_edge(StateEdge::new(node.id, other.id, EdgeKind::Blocks), AgentId::User).unwrap();
+        pause();
+        let second = Utc::now();
+        pause();
+        store.set_capture_policy(&CapturePolicy { default: CaptureMode::Diff, ..Default::default() }).unwrap();
+        store.update_node(node.id, serde_json::json!({"v": 3}), None, AgentId::User).unwrap();
+        store.delete_node(node.id, AgentId::User).unwrap();
+        pause();
+        let third = Utc::now();
+
+        let before = node.created_at - chrono::Duration::milliseconds(1);
+        assert!(store.node_as_of(node.id, before).unwrap().is_none());
+        assert_eq!(store.node_as_of(node.id, first).unwrap().unwrap().content["v"], 1);
+        assert!(store.edges_as_of(node.id, first).unwrap().is_empty());
+        assert_eq!(store.node_as_of(node.id, second).unwrap().unwrap().content["v"], 2);
+        assert_eq!(store.edges_as_of(node.id, second).unwrap()[0].id, edge.id);
+        assert!(store.node_as_of(node.id, third).unwrap().is_none());
+        assert!(store.edges_as_of(node.id, third).unwrap().is_empty());
+
+        let mut events = store.get_events(None, usize::MAX).unwrap();
+        chronological(&mut events);
+        // The delete is diff-captured too, but carries a snapshot, not a patch
+        let patched = events
+            .iter()
+            .rposition(|e| e.capture == CaptureMode::Diff && e.operation == Operation::Update)
+            .unwrap();
+        let just_after = events[patched].timestamp;
+        assert_eq!(node_as_of(&events, node.id, just_after).unwrap().unwrap().content["v"], 3);
+    }
+
+    #[test]
+    fn test_node_versions() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({"text": "draft"})), AgentId::Claude)
+            .unwrap();
+        store.update_node(node.id, serde_json::json!({"text": "revised"}), None, AgentId::User).unwrap();
+        store.set_capture_policy(&CapturePolicy { default: CaptureMode::Diff, ..Default::default() }).unwrap();
+        store.update_node(node.id, serde_json::json!({"text": "final", "tags": ["x"]}), None, AgentId::Claude).unwrap();
+
+        let versions = store.node_versions(node.id).unwrap();
+        let texts: Vec<&str> = versions.iter().map(|v| v.node.content["text"].as_str().unwrap()).collect();
+        assert_eq!(texts, ["draft", "revised", "final"]);
+        assert_eq!(versions.iter().map(|v| v.node.version).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(versions[1].agent, AgentId::User);
+        assert_eq!(versions[0].operation, Operation::Create);
+
+        store.delete_node(node.id, AgentId::User).unwrap();
+        assert_eq!(store.node_versions(node.id).unwrap().len(), 3);
+        assert!(store.node_versions(ulid::Ulid::new()).unwrap().is_empty());
+    }
+}
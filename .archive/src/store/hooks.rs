@@ -0,0 +1,77 @@
+//! Callbacks run after store mutations
+//!
+//! Hooks are invoked synchronously on the writing thread once the event has
+//! been logged, so they see every mutation exactly once and in order. Keep
+//! them cheap or hand the event off to a channel. Namespaced views of a
+//! store share its registry.
+
+use std::sync::{Arc, RwLock};
+
+use crate::schema::{Operation, StateEvent};
+
+pub type Hook = Arc<dyn Fn(&StateEvent) + Send + Sync>;
+
+/// Which mutations a hook fires on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPoint {
+    /// Node creation and edge creation (`Link`)
+    Create,
+    Update,
+    /// Node deletion and edge deletion (`Unlink`)
+    Delete,
+}
+
+impl HookPoint {
+    pub fn of(operation: &Operation) -> Self {
+        match operation {
+            Operation::Create | Operation::Link => HookPoint::Create,
+            Operation::Update => HookPoint::Update,
+            Operation::Delete | Operation::Unlink => HookPoint::Delete,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Hooks {
+    registered: Arc<RwLock<Vec<(HookPoint, Hook)>>>,
+}
+
+impl Hooks {
+    pub fn register(&self, point: HookPoint, hook: Hook) {
+        self.registered
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((point, hook));
+    }
+
+    pub fn len(&self) -> usize {
+        self.registered.read().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run every hook registered for the event's operation
+    pub(crate) fn run(&self, event: &StateEvent) {
+        let point = HookPoint::of(&event.operation);
+        // Release the lock first so a hook may register further hooks
+        let matching: Vec<Hook> = self
+            .registered
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(p, _)| *p == point)
+            .map(|(_, hook)| hook.clone())
+            .collect();
+        for hook in matching {
+            hook(event);
+        }
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks").field("registered", &self.len()).finish()
+    }
+}
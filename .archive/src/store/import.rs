@@ -0,0 +1,377 @@
+//! Streaming node import
+//!
+//! Accepts NDJSON (one node per line), a JSON array of nodes, or the
+//! `{"version": ..., "nodes": [...]}` document written by `state-cli export`.
+//! Input is parsed incrementally, so memory use is bounded by the batch
+//! size rather than the file size, and nodes reach the store in batches
+//! through `SledStore::create_nodes`.
+//!
+//! Nodes from another instance can be remapped: each gets a fresh local
+//! ID, and the pairing is kept per source instance (`SledStore::remaps`),
+//! so importing the same source again updates the nodes it made last time
+//! instead of duplicating them. The source is the `instance` an export
+//! document names, or `ImportOptions::with_source` for other input.
+
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::io::Read;
+
+use super::{DedupeMode, DedupeOutcome, Result, SledStore, Store, StoreError, INSTANCE_ID_KEY};
+use crate::schema::{AgentId, NodeId, StateNode};
+
+/// Nodes written per store batch by default
+pub const DEFAULT_IMPORT_BATCH: usize = 1000;
+
+/// Running totals, reported after every batch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportProgress {
+    /// Input bytes consumed so far
+    pub bytes: u64,
+    pub nodes: usize,
+    pub created: usize,
+    pub reused: usize,
+    pub linked: usize,
+    /// Remapped nodes whose local copy changed
+    pub updated: usize,
+}
+
+impl ImportProgress {
+    fn record(&mut self, outcome: &DedupeOutcome) {
+        self.nodes += 1;
+        match outcome {
+            DedupeOutcome::Created(_) => self.created += 1,
+            DedupeOutcome::Reused(_) => self.reused += 1,
+            DedupeOutcome::Linked { .. } => self.linked += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub batch_size: usize,
+    pub dedupe: DedupeMode,
+    pub agent: AgentId,
+    /// Instance the input came from; overrides an export document's own
+    pub source: Option<String>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_IMPORT_BATCH,
+            dedupe: DedupeMode::Off,
+            agent: AgentId::System,
+            source: None,
+        }
+    }
+}
+
+impl ImportOptions {
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_dedupe(mut self, dedupe: DedupeMode) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    pub fn with_agent(mut self, agent: AgentId) -> Self {
+        self.agent = agent;
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+/// Import every node in `reader`, calling `progress` after each batch
+pub fn import_nodes<R: Read>(
+    store: &SledStore,
+    reader: R,
+    options: &ImportOptions,
+    mut progress: impl FnMut(&ImportProgress),
+) -> Result<ImportProgress> {
+    let bytes = Cell::new(0);
+    let reader = CountingReader { inner: reader, bytes: &bytes };
+    let mut totals = ImportProgress::default();
+    let mut batch = Vec::with_capacity(options.batch_size);
+    let mut failure = None;
+    let documented = RefCell::new(None);
+    // Our own export needs no remapping
+    let own = store.get_meta(INSTANCE_ID_KEY)?;
+
+    let mut flush = |batch: &mut Vec<StateNode>, totals: &mut ImportProgress| -> Result<()> {
+        let mut nodes = std::mem::take(batch);
+        let source = options
+            .source
+            .clone()
+            .or_else(|| documented.borrow().clone())
+            .filter(|source| own.as_ref().and_then(Value::as_str) != Some(source.as_str()));
+        let mut remote_ids = Vec::new();
+        if let Some(source) = &source {
+            (nodes, remote_ids) = remap(store, source, nodes, &options.agent, totals)?;
+        }
+        let outcomes = store.create_nodes(nodes, options.agent.clone(), options.dedupe)?;
+        for (i, outcome) in outcomes.iter().enumerate() {
+            if let (Some(source), Some(remote)) = (&source, remote_ids.get(i)) {
+                store.record_remap(source, *remote, outcome.node().id)?;
+            }
+            totals.record(outcome);
+        }
+        totals.bytes = bytes.get();
+        progress(totals);
+        Ok(())
+    };
+
+    let mut sink = |node: StateNode| -> std::result::Result<(), String> {
+        batch.push(node);
+        if batch.len() >= options.batch_size {
+            if let Err(e) = flush(&mut batch, &mut totals) {
+                let message = e.to_string();
+                failure = Some(e);
+                return Err(message);
+            }
+        }
+        Ok(())
+    };
+
+    // Top-level values follow each other separated by whitespace, which
+    // covers NDJSON as well as a single array or export document
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let mut parse = || -> std::result::Result<(), serde_json::Error> {
+        while de.end().is_err() {
+            TopLevel { sink: &mut sink, instance: &documented }.deserialize(&mut de)?;
+        }
+        Ok(())
+    };
+    let parsed = parse();
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    parsed.map_err(|e| StoreError::Serialization(format!("import: {}", e)))?;
+
+    if !batch.is_empty() {
+        flush(&mut batch, &mut totals)?;
+    }
+    totals.bytes = bytes.get();
+    Ok(totals)
+}
+
+/// Update the local copies of nodes `source` sent before, returning the
+/// rest with fresh IDs, alongside the remote IDs they replace
+fn remap(
+    store: &SledStore,
+    source: &str,
+    nodes: Vec<StateNode>,
+    agent: &AgentId,
+    totals: &mut ImportProgress,
+) -> Result<(Vec<StateNode>, Vec<NodeId>)> {
+    let mut fresh = Vec::with_capacity(nodes.len());
+    let mut remote_ids = Vec::with_capacity(nodes.len());
+    for mut node in nodes {
+        let local = match store.remapped(source, node.id)? {
+            Some(id) => store.get_node(id)?,
+            None => None,
+        };
+        let Some(local) = local else {
+            remote_ids.push(node.id);
+            node.id = NodeId::new();
+            fresh.push(node);
+            continue;
+        };
+
+        let mut changed = false;
+        if local.content != node.content {
+            store.update_node(local.id, node.content, None, agent.clone())?;
+            changed = true;
+        }
+        if node.metadata.iter().any(|(key, value)| local.metadata.get(key) != Some(value)) {
+            store.update_metadata(local.id, node.metadata, agent.clone())?;
+            changed = true;
+        }
+        totals.nodes += 1;
+        if changed {
+            totals.updated += 1;
+        } else {
+            totals.reused += 1;
+        }
+    }
+    Ok((fresh, remote_ids))
+}
+
+struct CountingReader<'c, R> {
+    inner: R,
+    bytes: &'c Cell<u64>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes.set(self.bytes.get() + n as u64);
+        Ok(n)
+    }
+}
+
+type Sink<'a> = dyn FnMut(StateNode) -> std::result::Result<(), String> + 'a;
+
+/// One top-level value: a node, an array of nodes or an export document
+struct TopLevel<'s, 'a> {
+    sink: &'s mut Sink<'a>,
+    /// Set to the `instance` an export document names
+    instance: &'s RefCell<Option<String>>,
+}
+
+impl<'de, 's, 'a> DeserializeSeed<'de> for TopLevel<'s, 'a> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 's, 'a> Visitor<'de> for TopLevel<'s, 'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a node, an array of nodes or an export document")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> std::result::Result<(), A::Error> {
+        Nodes { sink: self.sink }.visit_seq(seq)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        // Nodes have no `nodes` field, so its presence marks an export
+        // document, whose array is streamed rather than buffered
+        let mut fields = serde_json::Map::new();
+        let mut document = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "nodes" {
+                // Exports are written with sorted keys, so `instance` has
+                // been seen by now
+                *self.instance.borrow_mut() =
+                    fields.get("instance").and_then(Value::as_str).map(str::to_string);
+                map.next_value_seed(Nodes { sink: &mut *self.sink })?;
+                document = true;
+            } else if document {
+                map.next_value::<IgnoredAny>()?;
+            } else {
+                fields.insert(key, map.next_value()?);
+            }
+        }
+        if document {
+            return Ok(());
+        }
+        let node: StateNode = serde_json::from_value(Value::Object(fields)).map_err(de::Error::custom)?;
+        (self.sink)(node).map_err(de::Error::custom)
+    }
+}
+
+/// An array of nodes, passed on one at a time
+struct Nodes<'s, 'a> {
+    sink: &'s mut Sink<'a>,
+}
+
+impl<'de, 's, 'a> DeserializeSeed<'de> for Nodes<'s, 'a> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 's, 'a> Visitor<'de> for Nodes<'s, 'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an array of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(node) = seq.next_element::<StateNode>()? {
+            (self.sink)(node).map_err(de::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::NodeKind;
+
+    fn node(text: &str) -> String {
+        serde_json::to_string(&StateNode::new(NodeKind::Insight, serde_json::json!({ "text": text }))).unwrap()
+    }
+
+    #[test]
+    fn test_streaming_import() {
+        let store = SledStore::open_temporary().unwrap();
+        let options = ImportOptions::default().with_batch_size(2);
+
+        // NDJSON, with a blank line
+        let ndjson = format!("{}\n\n{}\n{}\n", node("a"), node("b"), node("c"));
+        let mut reports = Vec::new();
+        let totals = import_nodes(&store, ndjson.as_bytes(), &options, |p| reports.push(*p)).unwrap();
+        assert_eq!(totals.created, 3);
+        assert_eq!(totals.bytes, ndjson.len() as u64);
+        assert_eq!(reports.iter().map(|p| p.nodes).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(store.count_nodes(Some(NodeKind::Insight)).unwrap(), 3);
+        assert_eq!(store.count_events().unwrap(), 3);
+
+        // A bare array and an export document; duplicates are reused
+        let array = format!("[{}, {}]", node("a"), node("d"));
+        let document = format!("{{\"version\": \"0.1.0\", \"nodes\": [{}], \"extra\": 1}}", node("e"));
+        let options = options.with_dedupe(DedupeMode::Reuse);
+        let totals = import_nodes(&store, array.as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!((totals.created, totals.reused), (1, 1));
+        let totals = import_nodes(&store, document.as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!(totals.created, 1);
+        assert_eq!(store.count_nodes(None).unwrap(), 5);
+        assert!(store.check(false).unwrap().is_clean());
+
+        // Bad input fails after the batches before it were written
+        let broken = format!("{}\n{}\n{{\"id\": 3", node("f"), node("g"));
+        assert!(import_nodes(&store, broken.as_bytes(), &options, |_| {}).is_err());
+        assert_eq!(store.count_nodes(None).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_remapped_import() {
+        let store = SledStore::open_temporary().unwrap();
+        let remote = StateNode::new(NodeKind::Insight, serde_json::json!({ "text": "v1" }));
+        let document = |node: &StateNode| {
+            serde_json::to_string(&serde_json::json!({ "instance": "01REMOTE", "nodes": [node] })).unwrap()
+        };
+        let options = ImportOptions::default();
+
+        let totals = import_nodes(&store, document(&remote).as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!(totals.created, 1);
+        let local = store.remapped("01REMOTE", remote.id).unwrap().unwrap();
+        assert_ne!(local, remote.id);
+        assert!(store.get_node(remote.id).unwrap().is_none());
+
+        // Again, unchanged, then changed: the same local node either way
+        let totals = import_nodes(&store, document(&remote).as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!((totals.created, totals.reused), (0, 1));
+        let mut changed = remote.clone();
+        changed.content = serde_json::json!({ "text": "v2" });
+        let totals = import_nodes(&store, document(&changed).as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!((totals.created, totals.updated), (0, 1));
+        assert_eq!(store.get_node(local).unwrap().unwrap().content["text"], "v2");
+        assert_eq!(store.count_nodes(None).unwrap(), 1);
+
+        // An explicit source keeps a separate table
+        let ndjson = node("elsewhere");
+        let options = options.with_source("01OTHER");
+        import_nodes(&store, ndjson.as_bytes(), &options, |_| {}).unwrap();
+        assert_eq!(store.remaps("01OTHER").unwrap().len(), 1);
+        assert_eq!(store.remaps("01REMOTE").unwrap(), vec![(remote.id, local)]);
+        assert_eq!(store.clear_remaps("01REMOTE").unwrap(), 1);
+    }
+}
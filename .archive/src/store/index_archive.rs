@@ -0,0 +1,248 @@
+//! Portable archives of the full-text index directory
+//!
+//! An archive is a zstd-compressed stream: a JSON header naming the store
+//! revision the index was built at, then each file of the directory as a
+//! JSON line (relative path, length, SHA-256) followed by its bytes.
+//! Importing checks every digest and, unless forced, that the store is at
+//! the archive's revision, so a restored backup can be searched with the
+//! index exported alongside it instead of being reindexed.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+
+use super::dump::{io_error, json_error, to_hex};
+use super::{Result, StoreError};
+use crate::schema::EventId;
+
+pub const INDEX_ARCHIVE_FORMAT: &str = "elegant-state-index";
+pub const INDEX_ARCHIVE_VERSION: u32 = 1;
+
+/// The point in a store's history an index reflects: its latest event and
+/// how many events it holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreRevision {
+    pub last_event: Option<EventId>,
+    pub events: u64,
+}
+
+impl std::fmt::Display for StoreRevision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.last_event {
+            Some(id) => write!(f, "{} ({} events)", id, self.events),
+            None => f.pad("empty"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub revision: StoreRevision,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileEntry {
+    /// Relative to the index directory, `/`-separated
+    path: String,
+    len: u64,
+    sha256: String,
+}
+
+/// Every file under `dir`, relative to it, in sorted order
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let entries = std::fs::read_dir(dir.join(&relative)).map_err(|e| io_error(dir, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error(dir, e))?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type().map_err(|e| io_error(dir, e))?.is_dir() {
+                pending.push(path);
+            } else {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Write the index in `dir` to `out`, stamped with `revision`
+pub fn export_index(dir: &Path, out: &Path, revision: StoreRevision) -> Result<IndexArchiveHeader> {
+    if !dir.is_dir() {
+        return Err(StoreError::InvalidOperation(format!("No search index at {}", dir.display())));
+    }
+    let files = files(dir)?;
+    let mut bytes = 0;
+    for file in &files {
+        bytes += std::fs::metadata(dir.join(file)).map_err(|e| io_error(dir, e))?.len();
+    }
+    let header = IndexArchiveHeader {
+        format: INDEX_ARCHIVE_FORMAT.to_string(),
+        version: INDEX_ARCHIVE_VERSION,
+        created_at: Utc::now(),
+        revision,
+        files: files.len(),
+        bytes,
+    };
+
+    let file = std::fs::File::create(out).map_err(|e| io_error(out, e))?;
+    let mut encoder = zstd::Encoder::new(file, 3).map_err(|e| io_error(out, e))?;
+    write_json(&mut encoder, &header, out)?;
+    for file in &files {
+        let data = std::fs::read(dir.join(file)).map_err(|e| io_error(dir, e))?;
+        let path: Vec<String> = file.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+        let entry = FileEntry { path: path.join("/"), len: data.len() as u64, sha256: to_hex(&Sha256::digest(&data)) };
+        write_json(&mut encoder, &entry, out)?;
+        encoder.write_all(&data).map_err(|e| io_error(out, e))?;
+    }
+    encoder.finish().map_err(|e| io_error(out, e))?;
+    Ok(header)
+}
+
+fn write_json(writer: &mut impl Write, value: &impl Serialize, path: &Path) -> Result<()> {
+    let mut json = serde_json::to_vec(value).map_err(json_error)?;
+    json.push(b'\n');
+    writer.write_all(&json).map_err(|e| io_error(path, e))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(reader: &mut impl BufRead, path: &Path) -> Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(|e| io_error(path, e))? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line).map(Some).map_err(json_error)
+}
+
+fn open(archive: &Path) -> Result<(IndexArchiveHeader, impl BufRead)> {
+    let file = std::fs::File::open(archive).map_err(|e| io_error(archive, e))?;
+    let mut reader = BufReader::new(zstd::Decoder::new(file).map_err(|e| io_error(archive, e))?);
+    let header: IndexArchiveHeader = read_json(&mut reader, archive)?
+        .ok_or_else(|| StoreError::Serialization("empty index archive".into()))?;
+    if header.format != INDEX_ARCHIVE_FORMAT {
+        return Err(StoreError::Serialization(format!("not an index archive: {}", header.format)));
+    }
+    if header.version > INDEX_ARCHIVE_VERSION {
+        return Err(StoreError::Serialization(format!(
+            "index archive version {} is newer than this build supports ({})",
+            header.version, INDEX_ARCHIVE_VERSION
+        )));
+    }
+    Ok((header, reader))
+}
+
+/// The header of an archive, without unpacking it
+pub fn read_header(archive: &Path) -> Result<IndexArchiveHeader> {
+    Ok(open(archive)?.0)
+}
+
+/// Write each file in the rest of an archive under `staging`; returns how many
+fn unpack(reader: &mut impl BufRead, archive: &Path, staging: &Path) -> Result<usize> {
+    let mut count = 0;
+    while let Some(entry) = read_json::<FileEntry>(reader, archive)? {
+        let relative = Path::new(&entry.path);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(StoreError::Serialization(format!("unsafe path in index archive: {}", entry.path)));
+        }
+        let mut data = vec![0; entry.len as usize];
+        reader.read_exact(&mut data).map_err(|e| io_error(archive, e))?;
+        if to_hex(&Sha256::digest(&data)) != entry.sha256 {
+            return Err(StoreError::Serialization(format!("checksum mismatch for {}", entry.path)));
+        }
+        let target = staging.join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+        }
+        std::fs::write(&target, &data).map_err(|e| io_error(&target, e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Unpack `archive` into `dir`, replacing any index there
+///
+/// With `expected`, the archive must have been exported at that revision.
+/// Files are unpacked beside `dir` and checked before anything in `dir`
+/// is touched.
+pub fn import_index(archive: &Path, dir: &Path, expected: Option<&StoreRevision>) -> Result<IndexArchiveHeader> {
+    let (header, mut reader) = open(archive)?;
+    if let Some(expected) = expected.filter(|expected| **expected != header.revision) {
+        return Err(StoreError::InvalidOperation(format!(
+            "Index archive was exported at revision {}, but the store is at {}",
+            header.revision, expected
+        )));
+    }
+
+    let mut staging = dir.as_os_str().to_owned();
+    staging.push(".importing");
+    let staging = PathBuf::from(staging);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging).map_err(|e| io_error(&staging, e))?;
+    }
+    std::fs::create_dir_all(&staging).map_err(|e| io_error(&staging, e))?;
+
+    let unpacked = unpack(&mut reader, archive, &staging);
+    match unpacked {
+        Ok(count) if count == header.files => {}
+        Ok(count) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(StoreError::Serialization(format!(
+                "index archive is truncated: {} of {} files",
+                count, header.files
+            )));
+        }
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    }
+
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).map_err(|e| io_error(dir, e))?;
+    }
+    std::fs::rename(&staging, dir).map_err(|e| io_error(dir, e))?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_archive_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("fulltext");
+        std::fs::create_dir_all(index.join("segments")).unwrap();
+        std::fs::write(index.join("meta.json"), b"{}").unwrap();
+        std::fs::write(index.join("segments/a.idx"), vec![7u8; 4096]).unwrap();
+
+        let revision = StoreRevision { last_event: Some(EventId::from_parts(1, 2)), events: 3 };
+        let archive = dir.path().join("index.zst");
+        let header = export_index(&index, &archive, revision.clone()).unwrap();
+        assert_eq!((header.files, header.bytes), (2, 4098));
+        assert_eq!(read_header(&archive).unwrap().revision, revision);
+
+        let restored = dir.path().join("restored");
+        std::fs::create_dir_all(&restored).unwrap();
+        std::fs::write(restored.join("stale.idx"), b"old").unwrap();
+        import_index(&archive, &restored, Some(&revision)).unwrap();
+        assert_eq!(std::fs::read(restored.join("segments/a.idx")).unwrap(), vec![7u8; 4096]);
+        assert!(!restored.join("stale.idx").exists());
+
+        // A different revision is refused and leaves the index alone
+        let later = StoreRevision { last_event: Some(EventId::from_parts(5, 0)), events: 4 };
+        assert!(import_index(&archive, &restored, Some(&later)).is_err());
+        assert!(restored.join("meta.json").exists());
+        assert!(import_index(&archive, &dir.path().join("forced"), None).is_ok());
+
+        assert!(export_index(&dir.path().join("missing"), &archive, revision).is_err());
+    }
+}
@@ -1,7 +1,9 @@
 // Additional index utilities for advanced queries
-// This module is reserved for future full-text search integration
+//
+// Metadata indexes live here; full-text and semantic indexes are still to come.
 
 use super::Result;
+use crate::schema::{NodeId, StateNode};
 
 pub struct Indices;
 
@@ -9,3 +11,92 @@ impl Indices {
     // Future: integrate tantivy for full-text search
     // Future: integrate semantic search with embeddings
 }
+
+/// Separates field, value and node ID inside a metadata index key
+const SEPARATOR: u8 = 0;
+
+/// A lookup on one metadata field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetaQuery {
+    Equals(String),
+    Prefix(String),
+}
+
+impl MetaQuery {
+    /// `value*` is a prefix query, anything else must match exactly
+    pub fn parse(value: &str) -> Self {
+        match value.strip_suffix('*') {
+            Some(prefix) if !prefix.contains('*') => MetaQuery::Prefix(prefix.to_string()),
+            _ => MetaQuery::Equals(value.to_string()),
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            MetaQuery::Equals(expected) => value == expected,
+            MetaQuery::Prefix(prefix) => value.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// Normalise a field name: `metadata.project` and `project` are the same
+pub fn field_name(field: &str) -> &str {
+    field.strip_prefix("metadata.").unwrap_or(field)
+}
+
+/// Values a node holds for a metadata field, as indexed strings
+///
+/// Dotted fields descend into objects; arrays contribute every scalar
+/// element. Objects and nulls are not indexed.
+pub fn field_values(node: &StateNode, field: &str) -> Vec<String> {
+    let mut parts = field_name(field).split('.');
+    let Some(first) = parts.next() else {
+        return Vec::new();
+    };
+    let mut value = node.metadata.get(first);
+    for part in parts {
+        value = value.and_then(|v| v.get(part));
+    }
+
+    let scalar = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    match value {
+        Some(serde_json::Value::Array(items)) => items.iter().filter_map(scalar).collect(),
+        Some(v) => scalar(v).into_iter().collect(),
+        None => Vec::new(),
+    }
+}
+
+/// `field \0 value \0 node-id`, so one field's entries sort by value
+pub(crate) fn entry_key(field: &str, value: &str, id: NodeId) -> Vec<u8> {
+    let mut key = scan_prefix(field, &MetaQuery::Equals(value.to_string()));
+    key.extend_from_slice(&id.to_bytes());
+    key
+}
+
+/// Key prefix covering every entry a query can match
+pub(crate) fn scan_prefix(field: &str, query: &MetaQuery) -> Vec<u8> {
+    let mut key = field_name(field).as_bytes().to_vec();
+    key.push(SEPARATOR);
+    match query {
+        MetaQuery::Equals(value) => {
+            key.extend_from_slice(value.as_bytes());
+            key.push(SEPARATOR);
+        }
+        MetaQuery::Prefix(prefix) => key.extend_from_slice(prefix.as_bytes()),
+    }
+    key
+}
+
+/// Node ID at the end of an index key
+pub(crate) fn entry_node(key: &[u8]) -> Result<NodeId> {
+    let bytes: [u8; 16] = key
+        .get(key.len().saturating_sub(16)..)
+        .and_then(|tail| tail.try_into().ok())
+        .ok_or_else(|| super::StoreError::Serialization("corrupt metadata index key".into()))?;
+    Ok(NodeId::from_bytes(bytes))
+}
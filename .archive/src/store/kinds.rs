@@ -0,0 +1,165 @@
+//! Registry of custom node and edge kinds
+//!
+//! `custom:<name>` kinds can be used without registering them, but a
+//! registered kind carries a description and a colour: diagrams fill its
+//! nodes with the colour, and `kind list` and the `kinds` GraphQL query
+//! show it next to the built-in kinds. Names are lowercase, since kind
+//! parsing is case-insensitive.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{Result, StoreError};
+use crate::schema::{EdgeKind, NodeKind};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KindInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+impl KindInfo {
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+}
+
+/// Registered custom kinds, keyed by name without the `custom:` prefix
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KindRegistry {
+    #[serde(default)]
+    pub nodes: BTreeMap<String, KindInfo>,
+    #[serde(default)]
+    pub edges: BTreeMap<String, KindInfo>,
+}
+
+impl KindRegistry {
+    /// Register or redescribe a node kind
+    pub fn register_node(&mut self, name: &str, info: KindInfo) -> Result<NodeKind> {
+        let built_in = matches!(name.parse::<NodeKind>(), Ok(kind) if !matches!(kind, NodeKind::Custom(_)));
+        let name = custom_name(name, built_in)?;
+        validate_info(&info)?;
+        self.nodes.insert(name.clone(), info);
+        Ok(NodeKind::Custom(name))
+    }
+
+    /// Register or redescribe an edge kind
+    pub fn register_edge(&mut self, name: &str, info: KindInfo) -> Result<EdgeKind> {
+        let built_in = matches!(name.parse::<EdgeKind>(), Ok(kind) if !matches!(kind, EdgeKind::Custom(_)));
+        let name = custom_name(name, built_in)?;
+        validate_info(&info)?;
+        self.edges.insert(name.clone(), info);
+        Ok(EdgeKind::Custom(name))
+    }
+
+    /// Forget a node kind; nodes of the kind keep it. Returns whether it
+    /// was registered.
+    pub fn unregister_node(&mut self, name: &str) -> bool {
+        self.nodes.remove(strip_prefix(name)).is_some()
+    }
+
+    pub fn unregister_edge(&mut self, name: &str) -> bool {
+        self.edges.remove(strip_prefix(name)).is_some()
+    }
+
+    /// Registered details of a custom node kind
+    pub fn node_info(&self, kind: &NodeKind) -> Option<&KindInfo> {
+        match kind {
+            NodeKind::Custom(name) => self.nodes.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn edge_info(&self, kind: &EdgeKind) -> Option<&KindInfo> {
+        match kind {
+            EdgeKind::Custom(name) => self.edges.get(name),
+            _ => None,
+        }
+    }
+
+    /// Check every entry, as when loaded from a file
+    pub fn validate(&self) -> Result<()> {
+        for (name, info) in self.nodes.iter().chain(&self.edges) {
+            custom_name(name, false)?;
+            validate_info(info)?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_prefix(name: &str) -> &str {
+    name.strip_prefix("custom:").unwrap_or(name)
+}
+
+/// The registry name for `name`, which may carry the `custom:` prefix
+fn custom_name(name: &str, built_in: bool) -> Result<String> {
+    let name = strip_prefix(name);
+    if built_in {
+        return Err(StoreError::InvalidOperation(format!("Kind {} is built in", name)));
+    }
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-'));
+    if !valid {
+        return Err(StoreError::InvalidOperation(format!(
+            "Invalid kind name {:?}: use lowercase letters, digits, _ and -",
+            name
+        )));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_info(info: &KindInfo) -> Result<()> {
+    if let Some(color) = &info.color {
+        let valid = color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+        if !valid {
+            return Err(StoreError::InvalidOperation(format!(
+                "Invalid colour {:?}: expected #rrggbb",
+                color
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_kind_registry() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut registry = store.kind_registry().unwrap();
+        let paper = registry
+            .register_node("custom:paper", KindInfo::default().with_description("A published paper").with_color("#a0c4ff"))
+            .unwrap();
+        assert_eq!(paper, "custom:paper".parse::<NodeKind>().unwrap());
+        assert_eq!(registry.register_edge("cites", KindInfo::default()).unwrap(), EdgeKind::Custom("cites".into()));
+
+        assert!(registry.register_node("insight", KindInfo::default()).is_err());
+        assert!(registry.register_edge("part_of", KindInfo::default()).is_err());
+        assert!(registry.register_node("Has Space", KindInfo::default()).is_err());
+        assert!(registry.register_node("memo", KindInfo::default().with_color("blue")).is_err());
+
+        store.set_kind_registry(&registry).unwrap();
+        let loaded = store.kind_registry().unwrap();
+        assert_eq!(loaded.node_info(&paper).unwrap().color.as_deref(), Some("#a0c4ff"));
+        assert!(loaded.node_info(&NodeKind::Insight).is_none());
+        assert!(loaded.edge_info(&EdgeKind::Custom("cites".into())).is_some());
+
+        registry.unregister_node("paper");
+        assert!(registry.node_info(&paper).is_none());
+        assert!(!registry.unregister_node("paper"));
+    }
+}
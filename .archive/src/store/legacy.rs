@@ -0,0 +1,112 @@
+//! Upgrades for records written before schema versions were stamped
+//!
+//! The first migration runs these over every namespace. Each one only
+//! rewrites records that are still in its old layout, so running it again
+//! changes nothing.
+
+use super::sled_store::{COMPRESSION_LEVEL, ZSTD_MAGIC};
+use super::{Result, StoreError};
+use sled::Tree;
+
+/// Bring every node in one namespace's tree up to the current layout
+pub(super) fn upgrade(nodes: &Tree) -> Result<usize> {
+    upgrade_node_expiry(nodes)
+}
+
+/// Append an empty expiry time to nodes written before nodes could expire
+///
+/// The expiry time is the last node field, so an old record is the new one
+/// without its trailing `None` tag.
+fn upgrade_node_expiry(nodes: &Tree) -> Result<usize> {
+    let mut upgraded = 0;
+    for entry in nodes.iter() {
+        let (key, stored) = entry?;
+        let compressed = stored.starts_with(&ZSTD_MAGIC);
+        let mut raw = if compressed {
+            zstd::decode_all(stored.as_ref())
+                .map_err(|e| StoreError::Serialization(format!("decompression failed: {e}")))?
+        } else {
+            stored.to_vec()
+        };
+        if has_expiry_field(&raw) || trailing_timestamp(&raw).is_none() {
+            continue;
+        }
+        raw.push(0);
+        let value = if compressed {
+            zstd::encode_all(raw.as_slice(), COMPRESSION_LEVEL)
+                .map_err(|e| StoreError::Serialization(format!("compression failed: {e}")))?
+        } else {
+            raw
+        };
+        nodes.insert(key, value)?;
+        upgraded += 1;
+    }
+    Ok(upgraded)
+}
+
+/// Length of the bincode-encoded timestamp that ends `bytes`, if one does
+fn trailing_timestamp(bytes: &[u8]) -> Option<usize> {
+    // RFC 3339 strings from chrono run from 20 to 35 characters
+    (20..=35usize)
+        .find(|&len| {
+            let Some(start) = bytes.len().checked_sub(len + 8) else {
+                return false;
+            };
+            let prefix = u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap());
+            prefix == len as u64
+                && std::str::from_utf8(&bytes[start + 8..])
+                    .is_ok_and(|text| text.parse::<chrono::DateTime<chrono::Utc>>().is_ok())
+        })
+        .map(|len| len + 8)
+}
+
+/// Whether a node record already ends in an `Option<DateTime>` expiry time
+fn has_expiry_field(raw: &[u8]) -> bool {
+    match raw.split_last() {
+        Some((0, rest)) => trailing_timestamp(rest).is_some(),
+        _ => trailing_timestamp(raw).is_some_and(|len| {
+            matches!(raw[..raw.len() - len].split_last(), Some((1, rest)) if trailing_timestamp(rest).is_some())
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::*;
+
+    #[test]
+    fn test_nodes_without_expiry_are_upgraded() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let nodes = db.open_tree("nodes").unwrap();
+
+        // Field by field, so the test keeps describing these layouts as the
+        // schema moves on
+        let layout = |node: &StateNode, with_expiry: bool| {
+            let fields = (node.id, &node.kind, &node.content, &node.metadata, node.created_at, node.updated_at);
+            match with_expiry {
+                true => bincode::serialize(&(fields, node.expires_at)),
+                false => bincode::serialize(&fields),
+            }
+            .unwrap()
+        };
+        let small = StateNode::new(NodeKind::Task, serde_json::json!({"title": "old"}));
+        let large = StateNode::new(NodeKind::Context, serde_json::json!({"notes": "x".repeat(8192)}));
+        for (node, compress) in [(&small, false), (&large, true)] {
+            let legacy = layout(node, false);
+            let value = if compress { zstd::encode_all(legacy.as_slice(), COMPRESSION_LEVEL).unwrap() } else { legacy };
+            nodes.insert(node.id.to_bytes(), value).unwrap();
+        }
+        let expiring = StateNode::new(NodeKind::Context, serde_json::json!({}))
+            .with_ttl(chrono::Duration::hours(1));
+        nodes.insert(expiring.id.to_bytes(), layout(&expiring, true)).unwrap();
+
+        assert_eq!(upgrade_node_expiry(&nodes).unwrap(), 2);
+        assert_eq!(upgrade_node_expiry(&nodes).unwrap(), 0);
+        for node in [&small, &large, &expiring] {
+            let stored = nodes.get(node.id.to_bytes()).unwrap().unwrap();
+            let raw = if stored.starts_with(&ZSTD_MAGIC) { zstd::decode_all(stored.as_ref()).unwrap() } else { stored.to_vec() };
+            assert_eq!(raw, layout(node, true));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Cross-process database locking
+//!
+//! A single writer owns a database directory at a time. The owner holds an
+//! advisory lock on `state.lock` inside the directory and records its PID
+//! there, so a second process can report who is holding the database.
+
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Result, StoreError};
+
+const LOCK_FILE: &str = "state.lock";
+
+/// Held for as long as the store is open; the lock is released on drop
+#[derive(Debug)]
+pub struct DbLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl DbLock {
+    /// Lock the database directory, failing fast if another process owns it
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let (file, path) = Self::open_file(dir)?;
+        if file.try_lock_exclusive().is_err() {
+            return Err(StoreError::Locked(Self::describe_holder(&path)));
+        }
+        Self::claim(file, path)
+    }
+
+    /// Lock the database directory, blocking until any other owner exits
+    pub fn acquire_blocking(dir: &Path) -> Result<Self> {
+        let (file, path) = Self::open_file(dir)?;
+        if file.try_lock_exclusive().is_err() {
+            tracing::info!(
+                "database is locked by {}, waiting",
+                Self::describe_holder(&path)
+            );
+            file.lock_exclusive()
+                .map_err(|e| StoreError::InvalidOperation(format!("Failed to lock database: {}", e)))?;
+        }
+        Self::claim(file, path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn open_file(dir: &Path) -> Result<(File, PathBuf)> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| StoreError::InvalidOperation(format!("Failed to create {}: {}", dir.display(), e)))?;
+        let path = dir.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| StoreError::InvalidOperation(format!("Failed to open {}: {}", path.display(), e)))?;
+        Ok((file, path))
+    }
+
+    /// Record our PID in the lock file once the lock is held
+    fn claim(mut file: File, path: PathBuf) -> Result<Self> {
+        let write_pid = |file: &mut File| -> std::io::Result<()> {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            write!(file, "{}", std::process::id())?;
+            file.sync_all()
+        };
+        write_pid(&mut file)
+            .map_err(|e| StoreError::InvalidOperation(format!("Failed to write {}: {}", path.display(), e)))?;
+        Ok(Self { file, path })
+    }
+
+    fn describe_holder(path: &Path) -> String {
+        let mut contents = String::new();
+        let pid = File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .ok()
+            .and_then(|_| contents.trim().parse::<u32>().ok());
+        match pid {
+            Some(pid) => format!("PID {}", pid),
+            None => "another process".to_string(),
+        }
+    }
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = fs2::FileExt::unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_reports_pid() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = DbLock::acquire(dir.path()).unwrap();
+
+        match DbLock::acquire(dir.path()) {
+            Err(StoreError::Locked(holder)) => {
+                assert_eq!(holder, format!("PID {}", std::process::id()));
+            }
+            other => panic!("expected lock error, got {:?}", other.map(|_| ())),
+        }
+
+        drop(lock);
+        assert!(DbLock::acquire(dir.path()).is_ok());
+    }
+}
@@ -0,0 +1,191 @@
+//! History of maintenance runs
+//!
+//! Compaction, repairs (`db check --fix`), backups (snapshots and dumps),
+//! stats reindexing, migrations and expiry sweeps each log one record when
+//! they finish, successful or not: when the run started, how long it took,
+//! the database size before and after where it matters, and how many
+//! records it touched. Records are attributed to the system agent and kept
+//! database-wide rather than per namespace. `report` summarizes them per
+//! operation, comparing the later half of a window with the earlier half
+//! so a reindex that keeps getting slower stands out.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub(crate) const MAINTENANCE_TREE: &str = "maintenance";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceKind {
+    Compaction,
+    Repair,
+    Backup,
+    Reindex,
+    Migration,
+    Gc,
+}
+
+impl std::fmt::Display for MaintenanceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            MaintenanceKind::Compaction => "compaction",
+            MaintenanceKind::Repair => "repair",
+            MaintenanceKind::Backup => "backup",
+            MaintenanceKind::Reindex => "reindex",
+            MaintenanceKind::Migration => "migration",
+            MaintenanceKind::Gc => "gc",
+        })
+    }
+}
+
+impl std::str::FromStr for MaintenanceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "compaction" | "compact" => Ok(MaintenanceKind::Compaction),
+            "repair" => Ok(MaintenanceKind::Repair),
+            "backup" => Ok(MaintenanceKind::Backup),
+            "reindex" => Ok(MaintenanceKind::Reindex),
+            "migration" | "migrate" => Ok(MaintenanceKind::Migration),
+            "gc" => Ok(MaintenanceKind::Gc),
+            _ => Err(format!("Unknown maintenance operation: {}", s)),
+        }
+    }
+}
+
+/// Sizes and counts a maintenance run reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Measured {
+    /// Database size on disk before the run
+    pub bytes_before: Option<u64>,
+    pub bytes_after: Option<u64>,
+    /// Records rewritten, copied, repaired or deleted
+    pub items: Option<u64>,
+}
+
+/// One finished maintenance run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceRecord {
+    pub kind: MaintenanceKind,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// The error, if the run failed
+    pub error: Option<String>,
+    pub measured: Measured,
+}
+
+impl MaintenanceRecord {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Runs of one operation within a report's window
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MaintenanceSummary {
+    pub runs: usize,
+    pub failed: usize,
+    pub mean_ms: f64,
+    pub max_ms: u64,
+    pub last_ms: u64,
+    pub last_at: Option<DateTime<Utc>>,
+    /// Mean duration of the later half of the runs over the earlier half;
+    /// above 1 means the operation is getting slower. `None` with fewer
+    /// than four runs.
+    pub trend: Option<f64>,
+    /// Bytes freed by the runs that shrank the database
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MaintenanceReport {
+    pub since: Option<DateTime<Utc>>,
+    pub by_kind: BTreeMap<MaintenanceKind, MaintenanceSummary>,
+}
+
+/// Summarize `records` (oldest first) started at or after `since`
+pub fn report(records: &[MaintenanceRecord], since: Option<DateTime<Utc>>) -> MaintenanceReport {
+    let mut runs: BTreeMap<MaintenanceKind, Vec<&MaintenanceRecord>> = BTreeMap::new();
+    for record in records.iter().filter(|r| since.map_or(true, |since| r.started_at >= since)) {
+        runs.entry(record.kind).or_default().push(record);
+    }
+
+    let mean = |runs: &[&MaintenanceRecord]| {
+        runs.iter().map(|r| r.duration_ms as f64).sum::<f64>() / runs.len().max(1) as f64
+    };
+    let by_kind = runs
+        .into_iter()
+        .map(|(kind, runs)| {
+            let last = runs.last().expect("kinds only appear with runs");
+            let (earlier, later) = runs.split_at(runs.len() / 2);
+            let summary = MaintenanceSummary {
+                runs: runs.len(),
+                failed: runs.iter().filter(|r| !r.succeeded()).count(),
+                mean_ms: mean(&runs),
+                max_ms: runs.iter().map(|r| r.duration_ms).max().unwrap_or_default(),
+                last_ms: last.duration_ms,
+                last_at: Some(last.started_at),
+                trend: (runs.len() >= 4 && mean(earlier) > 0.0).then(|| mean(later) / mean(earlier)),
+                reclaimed_bytes: runs
+                    .iter()
+                    .filter_map(|r| Some(r.measured.bytes_before?.saturating_sub(r.measured.bytes_after?)))
+                    .sum(),
+            };
+            (kind, summary)
+        })
+        .collect();
+    MaintenanceReport { since, by_kind }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SledStore;
+
+    #[test]
+    fn test_maintenance_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SledStore::open(dir.path().join("db")).unwrap();
+        let start = Utc::now();
+        store.compact().unwrap();
+        store.rebuild_stats().unwrap();
+        store.check(true).unwrap();
+        store.check(false).unwrap();
+        store.create_snapshot(&dir.path().join("snapshots"), "nightly").unwrap();
+        assert!(store.create_snapshot(&dir.path().join("snapshots"), "nightly").is_err());
+
+        let records = store.maintenance_history().unwrap();
+        let kinds: Vec<MaintenanceKind> = records.iter().map(|r| r.kind).collect();
+        // Read-only checks aren't maintenance; failed runs are logged too
+        assert_eq!(
+            kinds,
+            [
+                MaintenanceKind::Compaction,
+                MaintenanceKind::Reindex,
+                MaintenanceKind::Repair,
+                MaintenanceKind::Backup,
+                MaintenanceKind::Backup
+            ]
+        );
+        assert!(records[0].measured.bytes_before.is_some());
+        assert!(records[4].error.as_deref().is_some_and(|e| e.contains("already exists")));
+
+        let summary = &report(&records, Some(start)).by_kind[&MaintenanceKind::Backup];
+        assert_eq!((summary.runs, summary.failed), (2, 1));
+        assert_eq!(summary.trend, None);
+        assert!(report(&records, Some(Utc::now() + chrono::Duration::hours(1))).by_kind.is_empty());
+
+        // Slower runs later in the window show as a rising trend
+        let run = |ms| MaintenanceRecord {
+            kind: MaintenanceKind::Reindex,
+            started_at: start,
+            duration_ms: ms,
+            error: None,
+            measured: Measured::default(),
+        };
+        let slowing = [run(10), run(10), run(30), run(30)];
+        assert_eq!(report(&slowing, None).by_kind[&MaintenanceKind::Reindex].trend, Some(3.0));
+    }
+}
@@ -0,0 +1,162 @@
+//! Folding duplicate nodes into one
+//!
+//! `SledStore::merge_nodes` keeps a winner, merges the losers' content and
+//! metadata into it under a `MergeStrategy`, moves every edge of the losers
+//! onto it and links it `Supersedes` each loser. The losers stay, with only
+//! that edge, so IDs quoted elsewhere still resolve. System metadata
+//! (ranks, clusters, chunk numbers) is never taken from a loser.
+
+use serde_json::Value;
+
+use super::stamp::SYSTEM_FIELDS;
+use crate::schema::{Metadata, StateNode};
+
+/// How content and metadata from merged nodes combine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The winner's content as-is; losers only fill metadata it lacks
+    #[default]
+    Keep,
+    /// Deep-merge everything; the winner's values win conflicts, losers fill
+    /// gaps in the order given and arrays gain the items they lack
+    Union,
+    /// Deep-merge everything, the most recently updated node winning
+    /// conflicts
+    Latest,
+}
+
+impl std::fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            MergeStrategy::Keep => "keep",
+            MergeStrategy::Union => "union",
+            MergeStrategy::Latest => "latest",
+        })
+    }
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(MergeStrategy::Keep),
+            "union" => Ok(MergeStrategy::Union),
+            "latest" => Ok(MergeStrategy::Latest),
+            _ => Err(format!("Unknown merge strategy: {}", s)),
+        }
+    }
+}
+
+/// Content and metadata of `winner` once `losers` are merged into it
+pub(crate) fn merge(winner: &StateNode, losers: &[StateNode], strategy: MergeStrategy) -> (Value, Metadata) {
+    // Highest priority first; each later node only fills what's missing
+    let mut order: Vec<&StateNode> = std::iter::once(winner).chain(losers).collect();
+    if strategy == MergeStrategy::Latest {
+        order.sort_by_key(|n| std::cmp::Reverse(n.updated_at));
+    }
+
+    let mut content = order[0].content.clone();
+    if strategy != MergeStrategy::Keep {
+        for node in &order[1..] {
+            fill(&mut content, &node.content);
+        }
+    }
+
+    let mut metadata = Metadata::new();
+    for node in &order {
+        for (field, value) in &node.metadata {
+            if node.id != winner.id && SYSTEM_FIELDS.contains(&field.as_str()) {
+                continue;
+            }
+            metadata.entry(field.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    (content, metadata)
+}
+
+/// Add what `base` lacks from `other`, recursing into objects
+fn fill(base: &mut Value, other: &Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (field, value) in other {
+                match base.get_mut(field) {
+                    Some(existing) => fill(existing, value),
+                    None => {
+                        base.insert(field.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(other)) => {
+            for item in other {
+                if !base.contains(item) {
+                    base.push(item.clone());
+                }
+            }
+        }
+        (base @ Value::Null, other) => *base = other.clone(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, EdgeKind, NodeKind, Operation, StateEdge};
+    use crate::store::{SledStore, Store};
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_nodes() {
+        let store = SledStore::open_temporary().unwrap();
+        let winner = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, json!({"text": "sled is fast", "tags": ["db"]}))
+                    .with_metadata(Metadata::from([("source".to_string(), json!("claude"))])),
+                AgentId::Claude,
+            )
+            .unwrap();
+        let loser = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, json!({"text": "sled: fast", "tags": ["rust"], "by": "x"}))
+                    .with_metadata(Metadata::from([
+                        ("source".to_string(), json!("llama")),
+                        ("lang".to_string(), json!("en")),
+                    ])),
+                AgentId::Llama,
+            )
+            .unwrap();
+        let project = store.create_node(StateNode::new(NodeKind::Project, json!({})), AgentId::User).unwrap();
+        let other = store.create_node(StateNode::new(NodeKind::Task, json!({})), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(winner.id, project.id, EdgeKind::PartOf), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(loser.id, project.id, EdgeKind::PartOf), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(other.id, loser.id, EdgeKind::References), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(loser.id, winner.id, EdgeKind::RelatedTo), AgentId::User).unwrap();
+
+        assert!(store.merge_nodes(winner.id, &[winner.id], MergeStrategy::Keep, AgentId::User).is_err());
+        let before = store.count_events().unwrap();
+        let (merged, edges) = store.merge_nodes(winner.id, &[loser.id], MergeStrategy::Union, AgentId::User).unwrap();
+        assert_eq!(merged.content, json!({"text": "sled is fast", "tags": ["db", "rust"], "by": "x"}));
+        assert_eq!(merged.metadata["source"], "claude");
+        assert_eq!(merged.metadata["lang"], "en");
+        assert_eq!(merged.version, winner.version + 1);
+
+        // The duplicate PartOf and the link between the two are dropped
+        let outgoing = store.edges_from(winner.id).unwrap();
+        assert_eq!(outgoing.iter().filter(|e| e.kind == EdgeKind::PartOf).count(), 1);
+        assert!(outgoing.iter().any(|e| e.kind == EdgeKind::Supersedes && e.to == loser.id));
+        assert!(store.edges_from(other.id).unwrap().iter().any(|e| e.to == winner.id));
+        assert_eq!(store.edges_from(loser.id).unwrap().len(), 0);
+        assert_eq!(store.edges_to(loser.id).unwrap().len(), 1);
+        assert_eq!(edges.len(), 2);
+
+        // One group of events: three unlinks, two links and the update
+        let events = store.get_events(None, usize::MAX).unwrap();
+        let new: Vec<_> = events.iter().filter(|e| e.group.is_some()).collect();
+        assert_eq!(store.count_events().unwrap() - before, 6);
+        assert_eq!(new.len(), 6);
+        assert!(new.iter().all(|e| e.group == new[0].group));
+        assert_eq!(new.iter().filter(|e| e.operation == Operation::Update).count(), 1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Per-operation store metrics and slow-operation logging
+//!
+//! Each instrumented store method counts calls, accumulates latency and
+//! attributes the bytes it writes. Index maintenance is timed as its own
+//! operations (`index:list`, `index:metadata`), nested inside the method
+//! that triggered it, so a slow write can be told apart from a slow index.
+//! Namespaced views of a store share its metrics.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Operations slower than this are logged unless configured otherwise
+pub const DEFAULT_SLOW_OP_THRESHOLD: Duration = Duration::from_millis(250);
+
+thread_local! {
+    /// Operations in progress on this thread, innermost last
+    static ACTIVE: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct OpStats {
+    calls: u64,
+    total: Duration,
+    max: Duration,
+    bytes_written: u64,
+}
+
+struct Registry {
+    ops: Mutex<HashMap<&'static str, OpStats>>,
+    /// Microseconds; zero disables slow-operation logging
+    slow_threshold: AtomicU64,
+    since: Mutex<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            registry: Arc::new(Registry {
+                ops: Mutex::new(HashMap::new()),
+                slow_threshold: AtomicU64::new(DEFAULT_SLOW_OP_THRESHOLD.as_micros() as u64),
+                since: Mutex::new(Utc::now()),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Log operations that take at least `threshold`; `None` disables it
+    pub fn set_slow_op_threshold(&self, threshold: Option<Duration>) {
+        let micros = threshold.map_or(0, |t| t.as_micros().max(1) as u64);
+        self.registry.slow_threshold.store(micros, Ordering::Relaxed);
+    }
+
+    pub fn slow_op_threshold(&self) -> Option<Duration> {
+        match self.registry.slow_threshold.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    /// Start timing `op`; the time is recorded when the timer drops
+    pub(crate) fn start(&self, op: &'static str) -> OpTimer<'_> {
+        ACTIVE.with(|active| active.borrow_mut().push(op));
+        OpTimer {
+            metrics: self,
+            op,
+            started: Instant::now(),
+        }
+    }
+
+    /// Attribute written bytes to the innermost operation in progress
+    pub(crate) fn add_bytes(&self, bytes: usize) {
+        let op = ACTIVE.with(|active| active.borrow().last().copied()).unwrap_or("other");
+        let mut ops = self.registry.ops.lock().unwrap();
+        ops.entry(op).or_default().bytes_written += bytes as u64;
+    }
+
+    fn record(&self, op: &'static str, elapsed: Duration) {
+        {
+            let mut ops = self.registry.ops.lock().unwrap();
+            let stats = ops.entry(op).or_default();
+            stats.calls += 1;
+            stats.total += elapsed;
+            stats.max = stats.max.max(elapsed);
+        }
+        if self.slow_op_threshold().is_some_and(|threshold| elapsed >= threshold) {
+            tracing::warn!("slow store operation: {} took {:.1}ms", op, elapsed.as_secs_f64() * 1000.0);
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut ops: Vec<OpMetrics> = self
+            .registry
+            .ops
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(op, stats)| OpMetrics {
+                op: op.to_string(),
+                calls: stats.calls,
+                total_micros: stats.total.as_micros() as u64,
+                max_micros: stats.max.as_micros() as u64,
+                bytes_written: stats.bytes_written,
+            })
+            .collect();
+        ops.sort_by(|a, b| b.total_micros.cmp(&a.total_micros).then_with(|| a.op.cmp(&b.op)));
+        MetricsSnapshot {
+            since: *self.registry.since.lock().unwrap(),
+            ops,
+        }
+    }
+
+    /// Clear all counters
+    pub fn reset(&self) {
+        self.registry.ops.lock().unwrap().clear();
+        *self.registry.since.lock().unwrap() = Utc::now();
+    }
+}
+
+/// Records an operation's latency when dropped
+pub(crate) struct OpTimer<'a> {
+    metrics: &'a Metrics,
+    op: &'static str,
+    started: Instant,
+}
+
+impl Drop for OpTimer<'_> {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.borrow_mut().pop());
+        self.metrics.record(self.op, self.started.elapsed());
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpMetrics {
+    pub op: String,
+    pub calls: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+    pub bytes_written: u64,
+}
+
+impl OpMetrics {
+    pub fn mean_micros(&self) -> u64 {
+        self.total_micros.checked_div(self.calls).unwrap_or_default()
+    }
+}
+
+/// Counters since the store was opened or last reset, slowest total first
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub since: DateTime<Utc>,
+    pub ops: Vec<OpMetrics>,
+}
+
+impl MetricsSnapshot {
+    pub fn get(&self, op: &str) -> Option<&OpMetrics> {
+        self.ops.iter().find(|m| m.op == op)
+    }
+
+    /// Total time spent maintaining indexes, in microseconds
+    pub fn index_micros(&self) -> u64 {
+        self.ops.iter().filter(|m| m.op.starts_with("index:")).map(|m| m.total_micros).sum()
+    }
+
+    /// Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&OpMetrics) -> String| {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+            for m in &self.ops {
+                out.push_str(&format!("{}{{op=\"{}\"}} {}\n", name, m.op, value(m)));
+            }
+        };
+        family("state_store_calls_total", "counter", "Store operation calls", &|m| m.calls.to_string());
+        family(
+            "state_store_duration_seconds_total",
+            "counter",
+            "Time spent in store operations",
+            &|m| format!("{:.6}", m.total_micros as f64 / 1e6),
+        );
+        family(
+            "state_store_duration_seconds_max",
+            "gauge",
+            "Slowest single store operation",
+            &|m| format!("{:.6}", m.max_micros as f64 / 1e6),
+        );
+        family("state_store_bytes_written_total", "counter", "Bytes written by store operations", &|m| {
+            m.bytes_written.to_string()
+        });
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_timers_attribute_bytes() {
+        let metrics = Metrics::default();
+        {
+            let _outer = metrics.start("create_node");
+            metrics.add_bytes(100);
+            {
+                let _index = metrics.start("index:list");
+                metrics.add_bytes(40);
+            }
+            metrics.add_bytes(10);
+        }
+        metrics.add_bytes(1);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.get("create_node").unwrap().calls, 1);
+        assert_eq!(snapshot.get("create_node").unwrap().bytes_written, 110);
+        assert_eq!(snapshot.get("index:list").unwrap().bytes_written, 40);
+        assert_eq!(snapshot.get("other").unwrap().calls, 0);
+        assert!(snapshot.to_prometheus().contains("state_store_calls_total{op=\"index:list\"} 1"));
+
+        metrics.set_slow_op_threshold(None);
+        assert_eq!(metrics.slow_op_threshold(), None);
+        metrics.reset();
+        assert!(metrics.snapshot().ops.is_empty());
+    }
+}
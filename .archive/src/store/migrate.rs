@@ -0,0 +1,375 @@
+//! On-disk schema versions and the migrations between them
+//!
+//! The version is stamped once per database, covering every namespace.
+//! Migrations run in order and are idempotent: each rewrites only records
+//! that no longer decode with the current layout, so running one over data
+//! that is already current changes nothing.
+
+use serde::Serialize;
+
+use super::{Result, SledStore};
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Version assumed for a database with data but no stamp
+pub const UNSTAMPED_VERSION: u32 = 1;
+
+/// One step between schema versions
+pub struct Migration {
+    /// Version the database is at after this migration
+    pub version: u32,
+    pub description: &'static str,
+    /// Rewrite the records of one namespace; returns how many changed
+    pub(crate) run: fn(&SledStore) -> Result<usize>,
+}
+
+impl std::fmt::Debug for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migration")
+            .field("version", &self.version)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Every migration, oldest first
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "Add optimistic-concurrency version to nodes",
+        run: |store| {
+            Ok(store.upgrade_unstamped()? + store.upgrade_nodes(|node: v1::StateNode| node.into())?)
+        },
+    },
+    Migration {
+        version: 3,
+        description: "Record the capture mode on events",
+        run: |store| store.upgrade_events(|event: v1::StateEvent| event.into()),
+    },
+    Migration {
+        version: 4,
+        description: "Group the events of composite operations",
+        run: |store| store.upgrade_events(|event: v3::StateEvent| event.into()),
+    },
+    Migration {
+        version: 5,
+        description: "Record the origin instance and vector clock on events",
+        run: |store| store.upgrade_events(|event: v4::StateEvent| event.into()),
+    },
+    Migration {
+        version: 6,
+        description: "Add indexed tags to nodes",
+        run: |store| store.upgrade_nodes(|node: v5::StateNode| node.into()),
+    },
+    Migration {
+        version: 7,
+        description: "Index nodes by update time",
+        run: |store| store.index_update_times(),
+    },
+    Migration {
+        version: 8,
+        description: "Add pin flag to nodes",
+        run: |store| store.upgrade_nodes(|node: v7::StateNode| node.into()),
+    },
+    Migration {
+        version: 9,
+        description: "Add retention class override to nodes",
+        run: |store| store.upgrade_nodes(|node: v8::StateNode| node.into()),
+    },
+];
+
+pub fn migrations() -> &'static [Migration] {
+    MIGRATIONS
+}
+
+/// What one migration did across all namespaces
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub version: u32,
+    pub description: &'static str,
+    pub rewritten: usize,
+}
+
+/// Record layouts as they were at schema version 1
+pub(crate) mod v1 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::schema::{
+        AgentId, CaptureMode, EventId, Metadata, NodeId, NodeKind, Operation, Target, VectorClock,
+    };
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateNode {
+        pub id: NodeId,
+        pub kind: NodeKind,
+        #[serde(with = "crate::schema::json_text")]
+        pub content: Value,
+        #[serde(with = "crate::schema::json_text")]
+        pub metadata: Metadata,
+        pub created_at: DateTime<Utc>,
+        pub updated_at: DateTime<Utc>,
+        pub expires_at: Option<DateTime<Utc>>,
+    }
+
+    impl From<StateNode> for crate::schema::StateNode {
+        fn from(node: StateNode) -> Self {
+            Self {
+                id: node.id,
+                kind: node.kind,
+                content: node.content,
+                metadata: node.metadata,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+                expires_at: node.expires_at,
+                version: 1,
+                tags: Vec::new(),
+                pinned: false,
+                retention: None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateEvent {
+        pub id: EventId,
+        pub timestamp: DateTime<Utc>,
+        pub agent: AgentId,
+        pub operation: Operation,
+        pub target: Target,
+        #[serde(with = "crate::schema::json_text")]
+        pub before: Option<Value>,
+        #[serde(with = "crate::schema::json_text")]
+        pub after: Option<Value>,
+    }
+
+    impl From<StateEvent> for crate::schema::StateEvent {
+        fn from(event: StateEvent) -> Self {
+            Self {
+                id: event.id,
+                timestamp: event.timestamp,
+                agent: event.agent,
+                operation: event.operation,
+                target: event.target,
+                before: event.before,
+                after: event.after,
+                capture: CaptureMode::Full,
+                group: None,
+                origin: None,
+                clock: VectorClock::default(),
+            }
+        }
+    }
+}
+
+/// Record layouts as they were at schema version 3
+pub(crate) mod v3 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::schema::{AgentId, CaptureMode, EventId, Operation, Target, VectorClock};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateEvent {
+        pub id: EventId,
+        pub timestamp: DateTime<Utc>,
+        pub agent: AgentId,
+        pub operation: Operation,
+        pub target: Target,
+        #[serde(with = "crate::schema::json_text")]
+        pub before: Option<Value>,
+        #[serde(with = "crate::schema::json_text")]
+        pub after: Option<Value>,
+        pub capture: CaptureMode,
+    }
+
+    impl From<StateEvent> for crate::schema::StateEvent {
+        fn from(event: StateEvent) -> Self {
+            Self {
+                id: event.id,
+                timestamp: event.timestamp,
+                agent: event.agent,
+                operation: event.operation,
+                target: event.target,
+                before: event.before,
+                after: event.after,
+                capture: event.capture,
+                group: None,
+                origin: None,
+                clock: VectorClock::default(),
+            }
+        }
+    }
+}
+
+/// Record layouts as they were at schema version 4
+pub(crate) mod v4 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+    use ulid::Ulid;
+
+    use crate::schema::{AgentId, CaptureMode, EventId, Operation, Target, VectorClock};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateEvent {
+        pub id: EventId,
+        pub timestamp: DateTime<Utc>,
+        pub agent: AgentId,
+        pub operation: Operation,
+        pub target: Target,
+        #[serde(with = "crate::schema::json_text")]
+        pub before: Option<Value>,
+        #[serde(with = "crate::schema::json_text")]
+        pub after: Option<Value>,
+        pub capture: CaptureMode,
+        pub group: Option<Ulid>,
+    }
+
+    impl From<StateEvent> for crate::schema::StateEvent {
+        fn from(event: StateEvent) -> Self {
+            Self {
+                id: event.id,
+                timestamp: event.timestamp,
+                agent: event.agent,
+                operation: event.operation,
+                target: event.target,
+                before: event.before,
+                after: event.after,
+                capture: event.capture,
+                group: event.group,
+                origin: None,
+                clock: VectorClock::default(),
+            }
+        }
+    }
+}
+
+/// Record layouts as they were at schema version 5
+pub(crate) mod v5 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::schema::{Metadata, NodeId, NodeKind};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateNode {
+        pub id: NodeId,
+        pub kind: NodeKind,
+        #[serde(with = "crate::schema::json_text")]
+        pub content: Value,
+        #[serde(with = "crate::schema::json_text")]
+        pub metadata: Metadata,
+        pub created_at: DateTime<Utc>,
+        pub updated_at: DateTime<Utc>,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub version: u64,
+    }
+
+    impl From<StateNode> for crate::schema::StateNode {
+        fn from(node: StateNode) -> Self {
+            Self {
+                id: node.id,
+                kind: node.kind,
+                content: node.content,
+                metadata: node.metadata,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+                expires_at: node.expires_at,
+                version: node.version,
+                tags: Vec::new(),
+                pinned: false,
+                retention: None,
+            }
+        }
+    }
+}
+
+/// Record layouts as they were at schema version 7
+pub(crate) mod v7 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::schema::{Metadata, NodeId, NodeKind};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateNode {
+        pub id: NodeId,
+        pub kind: NodeKind,
+        #[serde(with = "crate::schema::json_text")]
+        pub content: Value,
+        #[serde(with = "crate::schema::json_text")]
+        pub metadata: Metadata,
+        pub created_at: DateTime<Utc>,
+        pub updated_at: DateTime<Utc>,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub version: u64,
+        pub tags: Vec<String>,
+    }
+
+    impl From<StateNode> for crate::schema::StateNode {
+        fn from(node: StateNode) -> Self {
+            Self {
+                id: node.id,
+                kind: node.kind,
+                content: node.content,
+                metadata: node.metadata,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+                expires_at: node.expires_at,
+                version: node.version,
+                tags: node.tags,
+                pinned: false,
+                retention: None,
+            }
+        }
+    }
+}
+
+/// Record layouts as they were at schema version 8
+pub(crate) mod v8 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use crate::schema::{Metadata, NodeId, NodeKind};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StateNode {
+        pub id: NodeId,
+        pub kind: NodeKind,
+        #[serde(with = "crate::schema::json_text")]
+        pub content: Value,
+        #[serde(with = "crate::schema::json_text")]
+        pub metadata: Metadata,
+        pub created_at: DateTime<Utc>,
+        pub updated_at: DateTime<Utc>,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub version: u64,
+        pub tags: Vec<String>,
+        pub pinned: bool,
+    }
+
+    impl From<StateNode> for crate::schema::StateNode {
+        fn from(node: StateNode) -> Self {
+            Self {
+                id: node.id,
+                kind: node.kind,
+                content: node.content,
+                metadata: node.metadata,
+                created_at: node.created_at,
+                updated_at: node.updated_at,
+                expires_at: node.expires_at,
+                version: node.version,
+                tags: node.tags,
+                pinned: node.pinned,
+                retention: None,
+            }
+        }
+    }
+}
@@ -1,8 +1,87 @@
 mod sled_store;
+mod legacy;
 mod indices;
+mod sweeper;
+mod lock;
+mod pandoc;
+pub mod xref;
+pub mod chunks;
+pub mod expand;
+pub mod bench;
+pub mod rank;
+pub mod cluster;
+pub mod cycles;
+pub mod toposort;
+pub mod hierarchy;
+pub mod structure;
+mod snapshot;
+mod share;
+mod hooks;
+mod attachment;
+mod migrate;
+mod dump;
+mod index_archive;
+mod import;
+mod metrics;
+mod path;
+mod stamp;
+mod kinds;
+mod schemas;
+mod constraints;
+mod alias;
+mod retention;
+mod extract;
+mod fulltext;
+mod templates;
+mod embeddings;
+mod stats;
+pub mod history;
+mod diff;
+mod merge;
+mod sort;
+mod undo;
+pub mod maintenance;
+#[cfg(test)]
+mod test_util;
 
-pub use sled_store::SledStore;
-pub use indices::Indices;
+pub use sled_store::{
+    SledStore, CheckIssue, CheckIssueKind, CheckReport, CompactionReport, CompressionStats,
+    DiskUsage, TreeUsage, ACYCLIC_KINDS_KEY, AGENT_DEFAULTS_KEY, CAPTURE_POLICY_KEY, DEFAULT_COMPRESSION_THRESHOLD,
+    CONTENT_SCHEMAS_KEY, GRAPH_CONSTRAINTS_KEY, INSTANCE_ID_KEY, KIND_REGISTRY_KEY, RECIPROCAL_KINDS_KEY, RETENTION_POLICY_KEY,
+    SYMMETRIC_KINDS_KEY, TEMPLATES_KEY, TEXT_EXTRACTION_KEY, VECTOR_CLOCK_KEY,
+};
+pub use indices::{Indices, MetaQuery};
+pub use sweeper::spawn_expiry_sweeper;
+pub use lock::DbLock;
+pub use hooks::{Hook, HookPoint, Hooks};
+pub use attachment::{guess_mime, Attachment, DEFAULT_MIME};
+pub use dump::{verify_dump, DumpHeader, DumpRecord, DumpSummary, DUMP_VERSION};
+pub use index_archive::{export_index, import_index, read_header, IndexArchiveHeader, StoreRevision};
+pub use import::{import_nodes, ImportOptions, ImportProgress, DEFAULT_IMPORT_BATCH};
+pub use path::{Direction, GraphPath, PathMode, PathStep, Reached, Subgraph};
+pub use stamp::{validate_field, AgentDefaults, SYSTEM_FIELDS};
+pub use kinds::{KindInfo, KindRegistry};
+pub use schemas::{ContentSchemas, Violation};
+pub use constraints::{Constraint, ConstraintViolation, GraphConstraints};
+pub use alias::{validate_alias, MAX_ALIAS_LEN};
+pub use retention::{ClassRules, RetentionPolicy};
+pub use extract::{TextExtraction, TextRule};
+pub use fulltext::{FullTextIndex, IndexSync, SearchResult, WRITER_HEAP_BYTES};
+pub use templates::{NodeTemplate, TemplateEdge};
+pub use embeddings::{node_text, CommandEmbedder, Embedder, EmbeddingStats, ModelStats};
+pub use stats::GraphStats;
+pub use history::NodeVersion;
+pub use diff::{Change, Changes, GraphDiff};
+pub use merge::MergeStrategy;
+pub use sort::{Sort, SortKey};
+pub use undo::Reversal;
+pub use metrics::{Metrics, MetricsSnapshot, OpMetrics, DEFAULT_SLOW_OP_THRESHOLD};
+pub use migrate::{migrations, Migration, MigrationReport, SCHEMA_VERSION, UNSTAMPED_VERSION};
+pub use snapshot::{list_snapshots, RestoreReport, SnapshotInfo, PRE_RESTORE_PREFIX};
+pub use share::{
+    ShareAction, ShareAuditEntry, ShareId, ShareLink, SharedGraph, DEFAULT_SHARE_DEPTH,
+};
+pub use pandoc::{PandocConverter, InputFormat, OutputFormat, detect_format};
 
 use crate::schema::*;
 use thiserror::Error;
@@ -12,43 +91,379 @@ pub enum StoreError {
     #[error("Node not found: {0}")]
     NodeNotFound(NodeId),
 
+    #[error("Not a node ID or alias: {0}")]
+    UnknownNodeRef(String),
+
+    #[error("Alias {0:?} already names node {1}")]
+    AliasTaken(String, NodeId),
+
     #[error("Edge not found: {0}")]
     EdgeNotFound(EdgeId),
 
+    #[error("Annotation not found: {0}")]
+    AnnotationNotFound(AnnotationId),
+
+    #[error("Attachment not found: {0}")]
+    AttachmentNotFound(String),
+
     #[error("Database error: {0}")]
     Database(#[from] sled::Error),
 
     #[error("Serialization error: {0}")]
     Serialization(String),
 
+    #[error("Database is locked by {0}")]
+    Locked(String),
+
+    #[error("Store is read-only")]
+    ReadOnly,
+
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    #[error("Version conflict on node {0}: expected version {1}, found {2}")]
+    Conflict(NodeId, u64, u64),
+
+    #[error("Node {0} still has {1} edge(s)")]
+    NodeHasEdges(NodeId, usize),
+
+    #[error("Edge would close a cycle: {}", display_cycle(.0))]
+    WouldCycle(Vec<NodeId>),
+
+    #[error("Share link not found: {0}")]
+    ShareNotFound(ulid::Ulid),
+
+    #[error("Share link rejected: {0}")]
+    ShareDenied(String),
+
+    #[error("Database schema version {0} is newer than this build supports ({1})")]
+    UnsupportedSchema(u32, u32),
+
+    #[error("Content of {0} node fails its schema: {}", display_violations(.1))]
+    SchemaViolation(String, Vec<Violation>),
+
+    #[error("Graph constraint violated: {}", display_violations(.0))]
+    ConstraintViolation(Vec<ConstraintViolation>),
+
+    #[error("Embedding failed: {0}")]
+    Embedding(String),
+
+    #[error("Strict mode: {0}")]
+    Degraded(String),
 }
 
 pub type Result<T> = std::result::Result<T, StoreError>;
 
+fn display_violations<V: std::fmt::Display>(violations: &[V]) -> String {
+    violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+}
+
+fn display_cycle(nodes: &[NodeId]) -> String {
+    let mut trail: Vec<String> = nodes.iter().map(ToString::to_string).collect();
+    trail.extend(nodes.first().map(ToString::to_string));
+    trail.join(" -> ")
+}
+
+/// What happens to a node's edges when the node is deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Delete every incident edge along with the node
+    #[default]
+    Cascade,
+    /// Refuse with `StoreError::NodeHasEdges` while any edge remains
+    Restrict,
+}
+
+/// A partial change to a node's content, for `SledStore::patch_node`
+#[derive(Debug, Clone)]
+pub enum ContentPatch {
+    /// RFC 6902 operations, applied all or nothing
+    Json(json_patch::Patch),
+    /// RFC 7386 merge patch: objects merge recursively and `null` removes a field
+    Merge(serde_json::Value),
+}
+
+impl ContentPatch {
+    /// RFC 6902 when `value` is an array of operations, else RFC 7386
+    pub fn detect(value: serde_json::Value) -> Result<Self> {
+        if value.is_array() {
+            Self::json(value)
+        } else {
+            Ok(ContentPatch::Merge(value))
+        }
+    }
+
+    /// Parse RFC 6902 operations
+    pub fn json(value: serde_json::Value) -> Result<Self> {
+        serde_json::from_value(value)
+            .map(ContentPatch::Json)
+            .map_err(|e| StoreError::InvalidOperation(format!("Invalid JSON patch: {}", e)))
+    }
+
+    pub fn apply(&self, content: &mut serde_json::Value) -> Result<()> {
+        match self {
+            ContentPatch::Json(patch) => json_patch::patch(content, patch)
+                .map_err(|e| StoreError::InvalidOperation(format!("Patch failed: {}", e))),
+            ContentPatch::Merge(patch) => {
+                json_patch::merge(content, patch);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What `create_node_deduped` does when a node of the same kind already has
+/// identical content
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Always create a new node
+    #[default]
+    Off,
+    /// Return the existing node instead of creating one
+    Reuse,
+    /// Create the node and link it to the existing one with `DerivedFrom`
+    Link,
+}
+
+impl std::fmt::Display for DedupeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            DedupeMode::Off => "off",
+            DedupeMode::Reuse => "reuse",
+            DedupeMode::Link => "link",
+        })
+    }
+}
+
+impl std::str::FromStr for DedupeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(DedupeMode::Off),
+            "reuse" => Ok(DedupeMode::Reuse),
+            "link" => Ok(DedupeMode::Link),
+            _ => Err(format!("Unknown dedupe mode: {}", s)),
+        }
+    }
+}
+
+/// Result of `create_node_deduped`
+#[derive(Debug, Clone)]
+pub enum DedupeOutcome {
+    /// No duplicate; the node was created
+    Created(StateNode),
+    /// A duplicate existed and was returned instead
+    Reused(StateNode),
+    /// The node was created and linked to its duplicate
+    Linked { node: StateNode, original: NodeId, edge: StateEdge },
+}
+
+impl DedupeOutcome {
+    /// The node the caller should use from here on
+    pub fn node(&self) -> &StateNode {
+        match self {
+            DedupeOutcome::Created(node) | DedupeOutcome::Reused(node) => node,
+            DedupeOutcome::Linked { node, .. } => node,
+        }
+    }
+
+    pub fn into_node(self) -> StateNode {
+        match self {
+            DedupeOutcome::Created(node) | DedupeOutcome::Reused(node) => node,
+            DedupeOutcome::Linked { node, .. } => node,
+        }
+    }
+
+    /// True unless a new node was written
+    pub fn is_reused(&self) -> bool {
+        matches!(self, DedupeOutcome::Reused(_))
+    }
+}
+
 /// Core trait for state storage backends
 pub trait Store: Send + Sync {
     // Node operations
     fn create_node(&self, node: StateNode, agent: AgentId) -> Result<StateNode>;
     fn get_node(&self, id: NodeId) -> Result<Option<StateNode>>;
-    fn update_node(&self, id: NodeId, content: serde_json::Value, agent: AgentId) -> Result<StateNode>;
-    fn delete_node(&self, id: NodeId, agent: AgentId) -> Result<()>;
+    /// Replace a node's content, bumping its version
+    ///
+    /// With `expected_version`, fails with `StoreError::Conflict` unless the
+    /// stored node is still at that version.
+    fn update_node(
+        &self,
+        id: NodeId,
+        content: serde_json::Value,
+        expected_version: Option<u64>,
+        agent: AgentId,
+    ) -> Result<StateNode>;
+    fn delete_node_with(&self, id: NodeId, agent: AgentId, mode: DeleteMode) -> Result<()>;
     fn list_nodes(&self, kind: Option<NodeKind>, limit: usize) -> Result<Vec<StateNode>>;
 
+    /// Delete a node and its incident edges
+    fn delete_node(&self, id: NodeId, agent: AgentId) -> Result<()> {
+        self.delete_node_with(id, agent, DeleteMode::Cascade)
+    }
+
+    /// Follow `Supersedes` edges from `id` to the newest node nothing
+    /// supersedes: the node itself if nothing does, `None` if it doesn't
+    /// exist. A node superseded more than once resolves through its most
+    /// recently created successor. If the chain runs into a cycle, the
+    /// newest node on the cycle is the head, wherever the walk started.
+    fn latest_version(&self, id: NodeId) -> Result<Option<StateNode>> {
+        let Some(mut node) = self.get_node(id)? else {
+            return Ok(None);
+        };
+        let mut path = vec![node.clone()];
+        loop {
+            let mut newest: Option<StateNode> = None;
+            for edge in self.edges_to(node.id)? {
+                if edge.kind != EdgeKind::Supersedes || edge.from == node.id {
+                    continue;
+                }
+                if let Some(successor) = self.get_node(edge.from)? {
+                    if newest.as_ref().map_or(true, |n| (successor.created_at, successor.id) > (n.created_at, n.id)) {
+                        newest = Some(successor);
+                    }
+                }
+            }
+            let Some(successor) = newest else {
+                return Ok(Some(node));
+            };
+            if let Some(start) = path.iter().position(|n| n.id == successor.id) {
+                let head = path.drain(start..).max_by_key(|n| (n.created_at, n.id));
+                return Ok(head);
+            }
+            path.push(successor.clone());
+            node = successor;
+        }
+    }
+
+    /// Each node replaced by its latest version, keeping the first
+    /// occurrence when several resolve to the same one; nodes this store
+    /// doesn't hold are kept as they are
+    fn latest_versions(&self, nodes: Vec<StateNode>) -> Result<Vec<StateNode>> {
+        let mut seen = std::collections::HashSet::new();
+        let mut latest = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let node = self.latest_version(node.id)?.unwrap_or(node);
+            if seen.insert(node.id) {
+                latest.push(node);
+            }
+        }
+        Ok(latest)
+    }
+
+    /// Nodes `id` is transitively `PartOf`, nearest first
+    fn ancestors(&self, id: NodeId) -> Result<Vec<StateNode>> {
+        hierarchy::part_of_closure(self, id, None, true)
+    }
+
+    /// Nodes transitively `PartOf` `id`, breadth first and at most
+    /// `max_depth` levels down
+    fn descendants(&self, id: NodeId, max_depth: Option<usize>) -> Result<Vec<StateNode>> {
+        hierarchy::part_of_closure(self, id, max_depth, false)
+    }
+
     // Edge operations
     fn create_edge(&self, edge: StateEdge, agent: AgentId) -> Result<StateEdge>;
     fn get_edge(&self, id: EdgeId) -> Result<Option<StateEdge>>;
     fn delete_edge(&self, id: EdgeId, agent: AgentId) -> Result<()>;
+    /// Set an edge's weight and merge `metadata` into its own; fields set
+    /// to null are removed. Endpoints and kind can't change.
+    fn update_edge(&self, id: EdgeId, weight: Option<f32>, metadata: Metadata, agent: AgentId) -> Result<StateEdge>;
+    /// Edges leaving a node. Edges of a symmetric kind are listed from
+    /// both ends, turned around so they leave the node asked about.
     fn edges_from(&self, node_id: NodeId) -> Result<Vec<StateEdge>>;
+    /// Edges arriving at a node, symmetric kinds included either way
     fn edges_to(&self, node_id: NodeId) -> Result<Vec<StateEdge>>;
 
+    // Annotation operations (no events; the node itself is untouched)
+    fn add_annotation(&self, annotation: Annotation) -> Result<Annotation>;
+    fn annotations(&self, node_id: NodeId) -> Result<Vec<Annotation>>;
+    fn delete_annotation(&self, node_id: NodeId, id: AnnotationId) -> Result<()>;
+
+    // Reactions (one per agent per kind; re-reacting is idempotent)
+    fn react(&self, reaction: Reaction) -> Result<ReactionCounts>;
+    fn unreact(&self, node_id: NodeId, agent: &AgentId, kind: ReactionKind) -> Result<ReactionCounts>;
+    fn reactions(&self, node_id: NodeId) -> Result<Vec<Reaction>>;
+
+    fn reaction_counts(&self, node_id: NodeId) -> Result<ReactionCounts> {
+        Ok(ReactionCounts::from_reactions(&self.reactions(node_id)?))
+    }
+
     // Event operations
     fn get_events(&self, since: Option<chrono::DateTime<chrono::Utc>>, limit: usize) -> Result<Vec<StateEvent>>;
+    /// The node as it stood at `at`, rebuilt from the event log; `None` if
+    /// it didn't exist then
+    fn node_as_of(&self, id: NodeId, at: chrono::DateTime<chrono::Utc>) -> Result<Option<StateNode>>;
+    /// Every state the node has had, oldest first, rebuilt from the event
+    /// log; deleted nodes keep their history
+    fn node_versions(&self, id: NodeId) -> Result<Vec<NodeVersion>>;
+    /// Edges to or from the node that existed at `at`
+    fn edges_as_of(&self, id: NodeId, at: chrono::DateTime<chrono::Utc>) -> Result<Vec<StateEdge>>;
+
+    // Counting (without loading records)
+    fn count_nodes(&self, kind: Option<NodeKind>) -> Result<usize>;
+    fn count_edges(&self) -> Result<usize>;
+    fn count_events(&self) -> Result<usize>;
 
     // Search
     fn search(&self, query: &str, kinds: Option<Vec<NodeKind>>) -> Result<Vec<StateNode>>;
 
     // Graph traversal
     fn neighbors(&self, id: NodeId, depth: usize) -> Result<Vec<StateNode>>;
+    /// Breadth-first walk from `id` up to `depth` hops
+    ///
+    /// Follows edges in `direction`, and only edges of `edge_kinds` when
+    /// given. Each node reached comes with the path that first reached it.
+    fn traverse(
+        &self,
+        id: NodeId,
+        direction: Direction,
+        edge_kinds: Option<&[EdgeKind]>,
+        depth: usize,
+    ) -> Result<Vec<Reached>>;
+    /// Best path between two nodes, following edges in either direction
+    ///
+    /// `PathMode::MinCost` minimizes the sum of edge weights,
+    /// `PathMode::MaxProduct` maximizes their product; with `edge_kinds`,
+    /// only edges of those kinds are followed. `None` when the nodes aren't
+    /// connected.
+    fn shortest_path(
+        &self,
+        from: NodeId,
+        to: NodeId,
+        edge_kinds: Option<&[EdgeKind]>,
+        mode: PathMode,
+    ) -> Result<Option<GraphPath>>;
+    /// `root` and the nodes within `depth` hops of it, either direction,
+    /// with the edges among them; only edges of `edge_kinds` when given
+    fn subgraph(&self, root: NodeId, depth: usize, edge_kinds: Option<&[EdgeKind]>) -> Result<Subgraph>;
+
+    // Diagnostics
+    /// Per-operation call counts, latencies and bytes written
+    fn metrics(&self) -> MetricsSnapshot;
+}
+
+/// The reads edge checks make, so a change can be checked against the graph
+/// as it would be before anything is written
+pub trait GraphReads {
+    fn node(&self, id: NodeId) -> Result<Option<StateNode>>;
+    fn outgoing(&self, node_id: NodeId) -> Result<Vec<StateEdge>>;
+    fn incoming(&self, node_id: NodeId) -> Result<Vec<StateEdge>>;
+}
+
+impl<S: Store + ?Sized> GraphReads for S {
+    fn node(&self, id: NodeId) -> Result<Option<StateNode>> {
+        self.get_node(id)
+    }
+
+    fn outgoing(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        self.edges_from(node_id)
+    }
+
+    fn incoming(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        self.edges_to(node_id)
+    }
 }
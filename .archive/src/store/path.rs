@@ -0,0 +1,340 @@
+//! Graph traversal: shortest paths, filtered breadth-first walks and subgraphs
+//!
+//! Shortest paths follow edges in either direction: when explaining why two
+//! nodes are connected, the connection matters more than which way it
+//! points. In `PathMode::MinCost` each hop costs its edge's weight
+//! (negative weights count as zero), so with the default weight of 1.0 the
+//! cheapest path is also the shortest. `PathMode::MaxProduct` reads weights
+//! as strengths in (0, 1] instead and finds the chain whose product is
+//! highest; weights above 1 count as 1 and edges without a positive weight
+//! aren't followed. Walks can be restricted to one direction.
+
+use serde::Serialize;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::{Result, Store, StoreError};
+use crate::schema::{EdgeKind, NodeId, StateEdge, StateNode};
+
+/// One hop along a path
+#[derive(Debug, Clone, Serialize)]
+pub struct PathStep {
+    pub edge: StateEdge,
+    /// True when the edge was followed from its source to its target
+    pub forward: bool,
+}
+
+/// A path between two nodes: `nodes[i]` and `nodes[i + 1]` are joined by `steps[i]`
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphPath {
+    pub nodes: Vec<StateNode>,
+    pub steps: Vec<PathStep>,
+    /// Sum of the edge weights along the path
+    pub cost: f64,
+    /// Product of the edge weights along the path, each capped at 1
+    pub strength: f64,
+}
+
+impl GraphPath {
+    pub fn hops(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+/// What a weighted path optimizes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathMode {
+    /// Lowest sum of edge weights
+    #[default]
+    MinCost,
+    /// Highest product of edge weights: the most strongly related chain
+    MaxProduct,
+}
+
+impl std::fmt::Display for PathMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            PathMode::MinCost => "cost",
+            PathMode::MaxProduct => "strongest",
+        })
+    }
+}
+
+impl std::str::FromStr for PathMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "cost" | "min_cost" | "cheapest" => Ok(PathMode::MinCost),
+            "strongest" | "max_product" | "strength" => Ok(PathMode::MaxProduct),
+            _ => Err(format!("Unknown path mode: {}", s)),
+        }
+    }
+}
+
+/// Which edges a walk follows from each node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Edges leaving the node
+    Outgoing,
+    /// Edges arriving at the node
+    Incoming,
+    #[default]
+    Both,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Direction::Outgoing => "out",
+            Direction::Incoming => "in",
+            Direction::Both => "both",
+        })
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "out" | "outgoing" => Ok(Direction::Outgoing),
+            "in" | "incoming" => Ok(Direction::Incoming),
+            "both" | "any" => Ok(Direction::Both),
+            _ => Err(format!("Unknown direction: {}", s)),
+        }
+    }
+}
+
+/// A node reached by a walk, with the path that first reached it
+#[derive(Debug, Clone, Serialize)]
+pub struct Reached {
+    pub node: StateNode,
+    /// Hops from the start; equal to `path.len()`
+    pub depth: usize,
+    pub path: Vec<PathStep>,
+}
+
+/// The nodes within some hops of a root and the edges among them
+#[derive(Debug, Clone, Serialize)]
+pub struct Subgraph {
+    pub root: NodeId,
+    /// The root first, then the rest nearest first
+    pub nodes: Vec<StateNode>,
+    pub edges: Vec<StateEdge>,
+}
+
+#[derive(PartialEq)]
+struct Frontier {
+    cost: f64,
+    node: NodeId,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.total_cmp(&other.cost).then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn edge_cost(edge: &StateEdge) -> f64 {
+    let weight = edge.weight as f64;
+    if weight.is_nan() || weight < 0.0 {
+        0.0
+    } else {
+        weight
+    }
+}
+
+/// The edge's weight as a strength in [0, 1]
+fn edge_strength(edge: &StateEdge) -> f64 {
+    let weight = edge.weight as f64;
+    if weight.is_nan() || weight < 0.0 {
+        0.0
+    } else {
+        weight.min(1.0)
+    }
+}
+
+/// What crossing `edge` adds to a path's distance under `mode`, or `None`
+/// if the edge can't be used
+fn hop_cost(mode: PathMode, edge: &StateEdge) -> Option<f64> {
+    match mode {
+        PathMode::MinCost => Some(edge_cost(edge)),
+        // Maximizing a product of strengths is minimizing the sum of their
+        // negative logs, which keeps the distances Dijkstra needs non-negative
+        PathMode::MaxProduct => {
+            let strength = edge_strength(edge);
+            (strength > 0.0).then(|| -strength.ln())
+        }
+    }
+}
+
+/// Best path from `from` to `to` under `mode`, or `None` if they aren't
+/// connected
+///
+/// Only edges whose kind is in `edge_kinds` are followed when it is given.
+pub fn shortest_path<S: Store + ?Sized>(
+    store: &S,
+    from: NodeId,
+    to: NodeId,
+    edge_kinds: Option<&[EdgeKind]>,
+    mode: PathMode,
+) -> Result<Option<GraphPath>> {
+    let start = store.get_node(from)?.ok_or(StoreError::NodeNotFound(from))?;
+    if store.get_node(to)?.is_none() {
+        return Err(StoreError::NodeNotFound(to));
+    }
+
+    let followed = |edge: &StateEdge| edge_kinds.map_or(true, |kinds| kinds.contains(&edge.kind));
+    let mut best: HashMap<NodeId, f64> = HashMap::from([(from, 0.0)]);
+    let mut via: HashMap<NodeId, PathStep> = HashMap::new();
+    let mut frontier = BinaryHeap::from([Reverse(Frontier { cost: 0.0, node: from })]);
+
+    let mut found = false;
+    while let Some(Reverse(Frontier { cost: reached, node })) = frontier.pop() {
+        if node == to {
+            found = true;
+            break;
+        }
+        if best.get(&node).is_some_and(|&known| reached > known) {
+            continue;
+        }
+
+        for (next, step) in incident(store, node, Direction::Both)? {
+            if !followed(&step.edge) {
+                continue;
+            }
+            let Some(hop) = hop_cost(mode, &step.edge) else {
+                continue;
+            };
+            let candidate = reached + hop;
+            if best.get(&next).is_some_and(|&known| candidate >= known) {
+                continue;
+            }
+            best.insert(next, candidate);
+            via.insert(next, step);
+            frontier.push(Reverse(Frontier { cost: candidate, node: next }));
+        }
+    }
+    if !found {
+        return Ok(None);
+    }
+
+    let mut steps = Vec::new();
+    let mut node = to;
+    while node != from {
+        let step = via.remove(&node).expect("every reached node records its step");
+        node = if step.forward { step.edge.from } else { step.edge.to };
+        steps.push(step);
+    }
+    steps.reverse();
+
+    let mut nodes = vec![start];
+    for step in &steps {
+        let id = if step.forward { step.edge.to } else { step.edge.from };
+        nodes.push(store.get_node(id)?.ok_or(StoreError::NodeNotFound(id))?);
+    }
+    let cost = steps.iter().map(|step| edge_cost(&step.edge)).sum();
+    let strength = steps.iter().map(|step| edge_strength(&step.edge)).product();
+    Ok(Some(GraphPath { nodes, steps, cost, strength }))
+}
+
+/// Edges incident to `node` in `direction`, each with the node at its far end
+fn incident<S: Store + ?Sized>(
+    store: &S,
+    node: NodeId,
+    direction: Direction,
+) -> Result<Vec<(NodeId, PathStep)>> {
+    let mut steps = Vec::new();
+    if direction != Direction::Incoming {
+        steps.extend(store.edges_from(node)?.into_iter().map(|edge| (edge.to, PathStep { edge, forward: true })));
+    }
+    if direction != Direction::Outgoing {
+        // A symmetric edge shows up in both lists; walk it once
+        let outgoing: HashSet<_> = steps.iter().map(|(_, step)| step.edge.id).collect();
+        steps.extend(
+            store
+                .edges_to(node)?
+                .into_iter()
+                .filter(|edge| !outgoing.contains(&edge.id))
+                .map(|edge| (edge.from, PathStep { edge, forward: false })),
+        );
+    }
+    Ok(steps)
+}
+
+/// Breadth-first walk from `start` up to `depth` hops
+///
+/// Each node is reported once, nearest first, with the path that reached
+/// it first. Edges whose far node no longer exists are skipped.
+pub fn traverse<S: Store + ?Sized>(
+    store: &S,
+    start: NodeId,
+    direction: Direction,
+    edge_kinds: Option<&[EdgeKind]>,
+    depth: usize,
+) -> Result<Vec<Reached>> {
+    let mut seen = HashSet::from([start]);
+    let mut reached: Vec<Reached> = Vec::new();
+    // Nodes to expand next, with their entry in `reached` (none for the start)
+    let mut level: Vec<(NodeId, Option<usize>)> = vec![(start, None)];
+
+    for hops in 1..=depth {
+        let mut next = Vec::new();
+        for (node, index) in level {
+            for (far, step) in incident(store, node, direction)? {
+                if edge_kinds.is_some_and(|kinds| !kinds.contains(&step.edge.kind)) || !seen.insert(far) {
+                    continue;
+                }
+                let Some(far_node) = store.get_node(far)? else {
+                    continue;
+                };
+                let mut path = index.map(|i| reached[i].path.clone()).unwrap_or_default();
+                path.push(step);
+                next.push((far, Some(reached.len())));
+                reached.push(Reached { node: far_node, depth: hops, path });
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        level = next;
+    }
+    Ok(reached)
+}
+
+/// `root` and everything within `depth` hops of it in either direction,
+/// with the edges between those nodes
+///
+/// With `edge_kinds`, only edges of those kinds are followed or returned.
+pub fn subgraph<S: Store + ?Sized>(
+    store: &S,
+    root: NodeId,
+    depth: usize,
+    edge_kinds: Option<&[EdgeKind]>,
+) -> Result<Subgraph> {
+    let start = store.get_node(root)?.ok_or(StoreError::NodeNotFound(root))?;
+    let mut nodes = vec![start];
+    nodes.extend(traverse(store, root, Direction::Both, edge_kinds, depth)?.into_iter().map(|r| r.node));
+
+    let ids: HashSet<NodeId> = nodes.iter().map(|n| n.id).collect();
+    let mut edges = Vec::new();
+    // Symmetric edges are listed from both ends; keep one copy of each
+    let mut seen = HashSet::new();
+    for node in &nodes {
+        edges.extend(store.edges_from(node.id)?.into_iter().filter(|e| {
+            ids.contains(&e.to)
+                && edge_kinds.map_or(true, |kinds| kinds.contains(&e.kind))
+                && seen.insert(e.id)
+        }));
+    }
+    Ok(Subgraph { root, nodes, edges })
+}
@@ -0,0 +1,303 @@
+//! Centrality scores over the graph
+//!
+//! PageRank follows edges in their direction, so nodes that many others
+//! reference (or derive from, or depend on) rank highest; edge weights
+//! split a node's rank between its targets. Betweenness ignores direction
+//! and counts the shortest paths passing through each node, which finds
+//! the nodes that bridge otherwise separate parts of the graph. Either can
+//! be restricted to some edge and node kinds and written into node
+//! metadata so agents can sort by it.
+
+use std::collections::{HashMap, VecDeque};
+
+use super::{Result, SledStore, Store};
+use crate::schema::{AgentId, EdgeKind, Metadata, NodeId, NodeKind};
+
+/// Metadata field PageRank scores are written to by default
+pub const PAGERANK_KEY: &str = "pagerank";
+
+/// Metadata field betweenness scores are written to by default
+pub const BETWEENNESS_KEY: &str = "betweenness";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Centrality {
+    #[default]
+    PageRank,
+    Betweenness,
+}
+
+impl Centrality {
+    /// Metadata field scores are written to by default
+    pub fn metadata_key(self) -> &'static str {
+        match self {
+            Centrality::PageRank => PAGERANK_KEY,
+            Centrality::Betweenness => BETWEENNESS_KEY,
+        }
+    }
+}
+
+impl std::fmt::Display for Centrality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Centrality::PageRank => "pagerank",
+            Centrality::Betweenness => "betweenness",
+        })
+    }
+}
+
+impl std::str::FromStr for Centrality {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pagerank" | "page_rank" => Ok(Centrality::PageRank),
+            "betweenness" => Ok(Centrality::Betweenness),
+            _ => Err(format!("Unknown centrality: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RankOptions {
+    pub centrality: Centrality,
+    /// Only follow edges of these kinds
+    pub edge_kinds: Option<Vec<EdgeKind>>,
+    /// Only rank nodes of these kinds; edges to other nodes are ignored
+    pub node_kinds: Option<Vec<NodeKind>>,
+    /// PageRank damping factor
+    pub damping: f64,
+    pub max_iterations: usize,
+    /// PageRank stops once scores move less than this in total
+    pub tolerance: f64,
+}
+
+impl Default for RankOptions {
+    fn default() -> Self {
+        Self {
+            centrality: Centrality::PageRank,
+            edge_kinds: None,
+            node_kinds: None,
+            damping: 0.85,
+            max_iterations: 100,
+            tolerance: 1e-9,
+        }
+    }
+}
+
+impl RankOptions {
+    pub fn with_centrality(mut self, centrality: Centrality) -> Self {
+        self.centrality = centrality;
+        self
+    }
+
+    pub fn with_edge_kinds(mut self, kinds: Vec<EdgeKind>) -> Self {
+        self.edge_kinds = Some(kinds);
+        self
+    }
+
+    pub fn with_node_kinds(mut self, kinds: Vec<NodeKind>) -> Self {
+        self.node_kinds = Some(kinds);
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.damping = damping.clamp(0.0, 1.0);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct NodeScore {
+    pub node: NodeId,
+    pub kind: NodeKind,
+    pub score: f64,
+}
+
+/// The graph as index-addressed adjacency lists
+struct Graph {
+    ids: Vec<NodeId>,
+    kinds: Vec<NodeKind>,
+    /// Outgoing edges with their weights
+    out: Vec<Vec<(usize, f64)>>,
+}
+
+impl Graph {
+    fn load(store: &SledStore, options: &RankOptions) -> Result<Self> {
+        let nodes: Vec<_> = store
+            .list_nodes(None, usize::MAX)?
+            .into_iter()
+            .filter(|n| options.node_kinds.as_ref().map_or(true, |ks| ks.contains(&n.kind)))
+            .collect();
+        let index: HashMap<NodeId, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id, i)).collect();
+
+        let mut out = vec![Vec::new(); nodes.len()];
+        for edge in store.list_edges()? {
+            if options.edge_kinds.as_ref().is_some_and(|ks| !ks.contains(&edge.kind)) || edge.from == edge.to {
+                continue;
+            }
+            if let (Some(&from), Some(&to)) = (index.get(&edge.from), index.get(&edge.to)) {
+                out[from].push((to, (edge.weight as f64).max(0.0)));
+            }
+        }
+        Ok(Self {
+            ids: nodes.iter().map(|n| n.id).collect(),
+            kinds: nodes.into_iter().map(|n| n.kind).collect(),
+            out,
+        })
+    }
+
+    fn pagerank(&self, options: &RankOptions) -> Vec<f64> {
+        let n = self.ids.len();
+        let mut rank = vec![1.0 / n as f64; n];
+        let totals: Vec<f64> = self.out.iter().map(|edges| edges.iter().map(|(_, w)| w).sum()).collect();
+
+        for _ in 0..options.max_iterations {
+            // Rank of nodes with no usable outgoing edges is spread evenly
+            let dangling: f64 = (0..n).filter(|&i| totals[i] <= 0.0).map(|i| rank[i]).sum();
+            let base = (1.0 - options.damping + options.damping * dangling) / n as f64;
+            let mut next = vec![base; n];
+            for (from, edges) in self.out.iter().enumerate() {
+                if totals[from] <= 0.0 {
+                    continue;
+                }
+                for &(to, weight) in edges {
+                    next[to] += options.damping * rank[from] * weight / totals[from];
+                }
+            }
+            let delta: f64 = rank.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+            rank = next;
+            if delta < options.tolerance {
+                break;
+            }
+        }
+        rank
+    }
+
+    /// Brandes' algorithm over the undirected graph, normalised to 0..=1
+    fn betweenness(&self) -> Vec<f64> {
+        let n = self.ids.len();
+        let mut neighbors = vec![Vec::new(); n];
+        for (from, edges) in self.out.iter().enumerate() {
+            for &(to, _) in edges {
+                neighbors[from].push(to);
+                neighbors[to].push(from);
+            }
+        }
+        for list in &mut neighbors {
+            list.sort_unstable();
+            list.dedup();
+        }
+
+        let mut centrality = vec![0.0; n];
+        for source in 0..n {
+            let mut order = Vec::with_capacity(n);
+            let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+            let mut paths = vec![0.0; n];
+            let mut dist = vec![usize::MAX; n];
+            paths[source] = 1.0;
+            dist[source] = 0;
+
+            let mut queue = VecDeque::from([source]);
+            while let Some(v) = queue.pop_front() {
+                order.push(v);
+                for &w in &neighbors[v] {
+                    if dist[w] == usize::MAX {
+                        dist[w] = dist[v] + 1;
+                        queue.push_back(w);
+                    }
+                    if dist[w] == dist[v] + 1 {
+                        paths[w] += paths[v];
+                        preds[w].push(v);
+                    }
+                }
+            }
+
+            let mut dependency = vec![0.0; n];
+            for &w in order.iter().rev() {
+                for &v in &preds[w] {
+                    dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+                }
+                if w != source {
+                    centrality[w] += dependency[w];
+                }
+            }
+        }
+
+        // Each pair was counted from both ends
+        let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 } else { 1.0 };
+        centrality.iter().map(|c| c / pairs).collect()
+    }
+}
+
+/// Score every matching node, highest first
+pub fn rank(store: &SledStore, options: &RankOptions) -> Result<Vec<NodeScore>> {
+    let graph = Graph::load(store, options)?;
+    if graph.ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let scores = match options.centrality {
+        Centrality::PageRank => graph.pagerank(options),
+        Centrality::Betweenness => graph.betweenness(),
+    };
+    let mut ranked: Vec<NodeScore> = graph
+        .ids
+        .into_iter()
+        .zip(graph.kinds)
+        .zip(scores)
+        .map(|((node, kind), score)| NodeScore { node, kind, score })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.node.cmp(&b.node)));
+    Ok(ranked)
+}
+
+/// Write each score into its node's `field` metadata, returning how many
+/// nodes were updated
+pub fn write_scores(store: &SledStore, scores: &[NodeScore], field: &str, agent: AgentId) -> Result<usize> {
+    for score in scores {
+        let fields = Metadata::from([(field.to_string(), serde_json::json!(score.score))]);
+        store.update_metadata(score.node, fields, agent.clone())?;
+    }
+    Ok(scores.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{StateEdge, StateNode};
+
+    #[test]
+    fn test_pagerank_and_betweenness() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = |kind: NodeKind| {
+            store.create_node(StateNode::new(kind, serde_json::json!({})), AgentId::User).unwrap().id
+        };
+        let hub = node(NodeKind::Insight);
+        let (a, b, c) = (node(NodeKind::Insight), node(NodeKind::Insight), node(NodeKind::Insight));
+        let task = node(NodeKind::Task);
+        for from in [a, b, c] {
+            store.create_edge(StateEdge::new(from, hub, EdgeKind::References), AgentId::User).unwrap();
+        }
+        // Only this edge makes `c` a bridge, and only for betweenness over any kind
+        store.create_edge(StateEdge::new(task, c, EdgeKind::Blocks), AgentId::User).unwrap();
+
+        let options = RankOptions::default().with_edge_kinds(vec![EdgeKind::References]);
+        let ranked = rank(&store, &options).unwrap();
+        assert_eq!(ranked.len(), 5);
+        assert_eq!(ranked[0].node, hub);
+        let total: f64 = ranked.iter().map(|s| s.score).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+
+        let insights = rank(&store, &options.clone().with_node_kinds(vec![NodeKind::Insight])).unwrap();
+        assert!(insights.iter().all(|s| s.kind == NodeKind::Insight));
+
+        let between = rank(&store, &RankOptions::default().with_centrality(Centrality::Betweenness)).unwrap();
+        assert_eq!(between[0].node, hub);
+        assert_eq!(between[1].node, c);
+        assert_eq!(between.iter().find(|s| s.node == task).unwrap().score, 0.0);
+
+        assert_eq!(write_scores(&store, &ranked, PAGERANK_KEY, AgentId::System).unwrap(), 5);
+        let stored = store.get_node(hub).unwrap().unwrap();
+        assert_eq!(stored.metadata[PAGERANK_KEY].as_f64(), Some(ranked[0].score));
+        assert_eq!(stored.version, 2);
+    }
+}
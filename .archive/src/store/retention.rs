@@ -0,0 +1,132 @@
+//! Retention classes: one setting for a node's whole lifecycle
+//!
+//! Each node is ephemeral, standard or permanent: its own override if it
+//! has one, else the class configured for its kind, else the default. The
+//! class's rules set the expiry stamped when the node is created, when
+//! `db gc` archives it, how its events capture payloads and whether dumps
+//! include it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::schema::{CaptureMode, NodeKind, RetentionClass, StateNode};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// What a retention class means for its nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassRules {
+    /// Expire nodes this long after creation unless given an expiry
+    #[serde(default)]
+    pub ttl_seconds: Option<i64>,
+    /// Archive nodes on `db gc` once they go this long without an update
+    #[serde(default)]
+    pub archive_after_seconds: Option<i64>,
+    /// Capture mode for the nodes' events, in place of the capture policy
+    #[serde(default)]
+    pub capture: Option<CaptureMode>,
+    /// Include the nodes, and edges touching them, in dumps
+    #[serde(default = "included")]
+    pub backup: bool,
+}
+
+fn included() -> bool {
+    true
+}
+
+impl ClassRules {
+    fn ephemeral() -> Self {
+        Self { ttl_seconds: Some(7 * DAY), archive_after_seconds: None, capture: Some(CaptureMode::Hash), backup: false }
+    }
+
+    fn standard() -> Self {
+        Self { ttl_seconds: None, archive_after_seconds: None, capture: None, backup: true }
+    }
+
+    fn permanent() -> Self {
+        Self { ttl_seconds: None, archive_after_seconds: None, capture: Some(CaptureMode::Full), backup: true }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Class of nodes whose kind has none
+    #[serde(default)]
+    pub default: RetentionClass,
+    /// Classes keyed by node kind name
+    #[serde(default)]
+    pub kinds: BTreeMap<String, RetentionClass>,
+    #[serde(default = "ClassRules::ephemeral")]
+    pub ephemeral: ClassRules,
+    #[serde(default = "ClassRules::standard")]
+    pub standard: ClassRules,
+    #[serde(default = "ClassRules::permanent")]
+    pub permanent: ClassRules,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            default: RetentionClass::default(),
+            kinds: BTreeMap::new(),
+            ephemeral: ClassRules::ephemeral(),
+            standard: ClassRules::standard(),
+            permanent: ClassRules::permanent(),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn class_for_kind(&self, kind: &NodeKind) -> RetentionClass {
+        self.kinds.get(&kind.to_string()).copied().unwrap_or(self.default)
+    }
+
+    /// The node's own class, else its kind's
+    pub fn class_for(&self, node: &StateNode) -> RetentionClass {
+        node.retention.unwrap_or_else(|| self.class_for_kind(&node.kind))
+    }
+
+    pub fn rules(&self, class: RetentionClass) -> &ClassRules {
+        match class {
+            RetentionClass::Ephemeral => &self.ephemeral,
+            RetentionClass::Standard => &self.standard,
+            RetentionClass::Permanent => &self.permanent,
+        }
+    }
+
+    pub fn rules_mut(&mut self, class: RetentionClass) -> &mut ClassRules {
+        match class {
+            RetentionClass::Ephemeral => &mut self.ephemeral,
+            RetentionClass::Standard => &mut self.standard,
+            RetentionClass::Permanent => &mut self.permanent,
+        }
+    }
+
+    pub fn rules_for(&self, node: &StateNode) -> &ClassRules {
+        self.rules(self.class_for(node))
+    }
+
+    /// Stamp the class's expiry on a node that has none
+    pub fn apply_ttl(&self, node: &mut StateNode) {
+        if node.expires_at.is_none() {
+            if let Some(ttl) = self.rules_for(node).ttl_seconds {
+                node.expires_at = Some(node.created_at + chrono::Duration::seconds(ttl));
+            }
+        }
+    }
+
+    /// Whether gc may delete the node once it has expired
+    pub fn expires(&self, node: &StateNode) -> bool {
+        self.class_for(node) != RetentionClass::Permanent
+    }
+
+    /// Whether the node has gone unchanged long enough to archive at `now`
+    pub fn archive_due(&self, node: &StateNode, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let class = self.class_for(node);
+        class != RetentionClass::Permanent
+            && self
+                .rules(class)
+                .archive_after_seconds
+                .is_some_and(|after| node.updated_at + chrono::Duration::seconds(after) <= now)
+    }
+}
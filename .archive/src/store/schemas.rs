@@ -0,0 +1,419 @@
+//! JSON Schemas for node content, per kind
+//!
+//! A kind with a schema only accepts content that validates against it:
+//! `create_node`, `update_node`, `patch_node` and imports fail with
+//! `StoreError::SchemaViolation`, listing every problem with a JSON
+//! Pointer to where it is. Existing nodes aren't rechecked when a schema
+//! changes, and replaying the event log writes history as it was.
+//!
+//! The validator covers the structural core of JSON Schema: `type`,
+//! `enum`, `const`, `properties`, `required`, `additionalProperties`,
+//! `items`, length, size and range bounds, and `allOf`/`anyOf`/`oneOf`/
+//! `not`. Annotations (`title`, `description`, `format`, ...) are allowed
+//! and ignored. Any other keyword is rejected when the schema is set, so a
+//! schema never silently checks less than it says.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::{Result, StoreError};
+use crate::schema::NodeKind;
+
+/// Keywords that describe rather than constrain
+const ANNOTATIONS: &[&str] =
+    &["$schema", "$id", "$comment", "title", "description", "default", "examples", "format", "deprecated"];
+
+/// One way content fails its schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// JSON Pointer into the content; empty for the content itself
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { self.path.as_str() };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Content schemas keyed by kind (`task`, `custom:paper`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContentSchemas {
+    #[serde(default)]
+    pub kinds: BTreeMap<String, Value>,
+}
+
+impl ContentSchemas {
+    pub fn get(&self, kind: &NodeKind) -> Option<&Value> {
+        self.kinds.get(&kind.to_string())
+    }
+
+    /// Require `kind`'s content to match `schema`
+    pub fn set(&mut self, kind: &NodeKind, schema: Value) -> Result<()> {
+        check_schema(&schema, "")?;
+        self.kinds.insert(kind.to_string(), schema);
+        Ok(())
+    }
+
+    /// Stop checking `kind`; returns whether it had a schema
+    pub fn remove(&mut self, kind: &NodeKind) -> bool {
+        self.kinds.remove(&kind.to_string()).is_some()
+    }
+
+    /// Everything wrong with `content` as a `kind` node's
+    pub fn violations(&self, kind: &NodeKind, content: &Value) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if let Some(schema) = self.get(kind) {
+            validate(schema, content, "", &mut violations);
+        }
+        violations
+    }
+
+    /// Fail with `StoreError::SchemaViolation` unless `content` is valid
+    pub fn check(&self, kind: &NodeKind, content: &Value) -> Result<()> {
+        let violations = self.violations(kind, content);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(StoreError::SchemaViolation(kind.to_string(), violations))
+        }
+    }
+
+    /// Whether `kind`'s schema leaves room for a value at JSON Pointer
+    /// `pointer`, following `properties`, `items` and
+    /// `additionalProperties`; kinds without a schema allow anything
+    pub fn allows_pointer(&self, kind: &NodeKind, pointer: &str) -> bool {
+        let Some(mut schema) = self.get(kind) else {
+            return true;
+        };
+        for token in pointer.split('/').skip(1) {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let object = match schema {
+                Value::Bool(allowed) => return *allowed,
+                Value::Object(object) => object,
+                _ => return true,
+            };
+            let index = token.parse::<usize>().is_ok();
+            if let Some(types) = object.get("type") {
+                let names: Vec<&str> = match types {
+                    Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                    name => name.as_str().into_iter().collect(),
+                };
+                if !(names.contains(&"object") || index && names.contains(&"array")) {
+                    return false;
+                }
+            }
+            schema = match object.get("properties").and_then(|properties| properties.get(&token)) {
+                Some(property) => property,
+                None if index && object.contains_key("items") => &object["items"],
+                None => match object.get("additionalProperties") {
+                    Some(additional) => additional,
+                    None => return true,
+                },
+            };
+        }
+        !matches!(schema, Value::Bool(false))
+    }
+
+    /// Check every schema, as when loaded from a file
+    pub fn validate(&self) -> Result<()> {
+        for (kind, schema) in &self.kinds {
+            kind.parse::<NodeKind>().map_err(StoreError::InvalidOperation)?;
+            check_schema(schema, "")?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid_schema(path: &str, message: String) -> StoreError {
+    let path = if path.is_empty() { "/" } else { path };
+    StoreError::InvalidOperation(format!("Invalid schema at {}: {}", path, message))
+}
+
+/// Refuse schemas using keywords this validator would ignore
+fn check_schema(schema: &Value, path: &str) -> Result<()> {
+    let object = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(object) => object,
+        _ => return Err(invalid_schema(path, "expected an object or boolean".into())),
+    };
+    for (keyword, value) in object {
+        let here = format!("{}/{}", path, escape(keyword));
+        match keyword.as_str() {
+            "type" => {
+                let names: Vec<&Value> = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    name => vec![name],
+                };
+                for name in names {
+                    if !matches!(
+                        name.as_str(),
+                        Some("null" | "boolean" | "object" | "array" | "number" | "string" | "integer")
+                    ) {
+                        return Err(invalid_schema(&here, format!("unknown type {}", name)));
+                    }
+                }
+            }
+            "enum" if !value.is_array() => return Err(invalid_schema(&here, "expected an array".into())),
+            "enum" | "const" => {}
+            "required" => {
+                if !value.as_array().is_some_and(|names| names.iter().all(Value::is_string)) {
+                    return Err(invalid_schema(&here, "expected an array of property names".into()));
+                }
+            }
+            "properties" => {
+                let properties =
+                    value.as_object().ok_or_else(|| invalid_schema(&here, "expected an object".into()))?;
+                for (name, schema) in properties {
+                    check_schema(schema, &format!("{}/{}", here, escape(name)))?;
+                }
+            }
+            "additionalProperties" | "items" | "not" => check_schema(value, &here)?,
+            "allOf" | "anyOf" | "oneOf" => {
+                let schemas = value
+                    .as_array()
+                    .filter(|schemas| !schemas.is_empty())
+                    .ok_or_else(|| invalid_schema(&here, "expected a non-empty array".into()))?;
+                for (i, schema) in schemas.iter().enumerate() {
+                    check_schema(schema, &format!("{}/{}", here, i))?;
+                }
+            }
+            "minLength" | "maxLength" | "minItems" | "maxItems" | "minProperties" | "maxProperties" => {
+                if value.as_u64().is_none() {
+                    return Err(invalid_schema(&here, "expected a non-negative integer".into()));
+                }
+            }
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => {
+                if !value.is_number() {
+                    return Err(invalid_schema(&here, "expected a number".into()));
+                }
+            }
+            keyword if ANNOTATIONS.contains(&keyword) => {}
+            keyword => return Err(invalid_schema(path, format!("unsupported keyword {}", keyword))),
+        }
+    }
+    Ok(())
+}
+
+/// JSON Pointer escaping for one path segment
+fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => false,
+    }
+}
+
+fn is_valid(schema: &Value, value: &Value) -> bool {
+    let mut violations = Vec::new();
+    validate(schema, value, "", &mut violations);
+    violations.is_empty()
+}
+
+/// Collect every way `value` at `path` fails `schema`
+pub fn validate(schema: &Value, value: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let mut fail = |message: String| violations.push(Violation { path: path.to_string(), message });
+    let object = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return fail("no value is allowed here".into()),
+        Value::Object(object) => object,
+        _ => return,
+    };
+
+    if let Some(types) = object.get("type") {
+        let names: Vec<&str> = match types {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            name => name.as_str().into_iter().collect(),
+        };
+        if !names.iter().any(|name| type_matches(name, value)) {
+            // Nothing inside a value of the wrong type is worth reporting
+            return fail(format!("expected {}", names.join(" or ")));
+        }
+    }
+    if let Some(options) = object.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            fail(format!("must be one of {}", Value::Array(options.clone())));
+        }
+    }
+    if let Some(expected) = object.get("const") {
+        if value != expected {
+            fail(format!("must be {}", expected));
+        }
+    }
+
+    let bound = |keyword: &str| object.get(keyword).and_then(Value::as_f64);
+    let count = |keyword: &str| object.get(keyword).and_then(Value::as_u64);
+    if let Some(n) = value.as_f64() {
+        if bound("minimum").is_some_and(|min| n < min) {
+            fail(format!("must be at least {}", object["minimum"]));
+        }
+        if bound("maximum").is_some_and(|max| n > max) {
+            fail(format!("must be at most {}", object["maximum"]));
+        }
+        if bound("exclusiveMinimum").is_some_and(|min| n <= min) {
+            fail(format!("must be more than {}", object["exclusiveMinimum"]));
+        }
+        if bound("exclusiveMaximum").is_some_and(|max| n >= max) {
+            fail(format!("must be less than {}", object["exclusiveMaximum"]));
+        }
+    }
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if count("minLength").is_some_and(|min| length < min) {
+            fail(format!("must be at least {} characters", object["minLength"]));
+        }
+        if count("maxLength").is_some_and(|max| length > max) {
+            fail(format!("must be at most {} characters", object["maxLength"]));
+        }
+    }
+    if let Some(items) = value.as_array() {
+        let length = items.len() as u64;
+        if count("minItems").is_some_and(|min| length < min) {
+            fail(format!("must have at least {} items", object["minItems"]));
+        }
+        if count("maxItems").is_some_and(|max| length > max) {
+            fail(format!("must have at most {} items", object["maxItems"]));
+        }
+    }
+    if let Some(fields) = value.as_object() {
+        let length = fields.len() as u64;
+        if count("minProperties").is_some_and(|min| length < min) {
+            fail(format!("must have at least {} properties", object["minProperties"]));
+        }
+        if count("maxProperties").is_some_and(|max| length > max) {
+            fail(format!("must have at most {} properties", object["maxProperties"]));
+        }
+        for name in object.get("required").and_then(Value::as_array).into_iter().flatten() {
+            if let Some(name) = name.as_str().filter(|name| !fields.contains_key(*name)) {
+                fail(format!("missing required property {:?}", name));
+            }
+        }
+    }
+
+    let combinators: Vec<(&str, &Vec<Value>)> = ["allOf", "anyOf", "oneOf"]
+        .into_iter()
+        .filter_map(|keyword| object.get(keyword).and_then(Value::as_array).map(|schemas| (keyword, schemas)))
+        .collect();
+    for (keyword, schemas) in combinators {
+        match keyword {
+            "allOf" => {
+                for schema in schemas {
+                    validate(schema, value, path, violations);
+                }
+            }
+            "anyOf" if !schemas.iter().any(|schema| is_valid(schema, value)) => violations.push(Violation {
+                path: path.to_string(),
+                message: "matches none of the anyOf schemas".into(),
+            }),
+            "oneOf" => {
+                let matching = schemas.iter().filter(|schema| is_valid(schema, value)).count();
+                if matching != 1 {
+                    violations.push(Violation {
+                        path: path.to_string(),
+                        message: format!("matches {} of the oneOf schemas, expected exactly one", matching),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    if object.get("not").is_some_and(|schema| is_valid(schema, value)) {
+        violations.push(Violation { path: path.to_string(), message: "matches a schema it must not".into() });
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(schema) = object.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                validate(schema, item, &format!("{}/{}", path, i), violations);
+            }
+        }
+    }
+    if let Some(fields) = value.as_object() {
+        let properties = object.get("properties").and_then(Value::as_object);
+        for (name, field) in fields {
+            let here = format!("{}/{}", path, escape(name));
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(schema) => validate(schema, field, &here, violations),
+                None => {
+                    if let Some(schema) = object.get("additionalProperties") {
+                        validate(schema, field, &here, violations);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{AgentId, StateNode};
+    use crate::store::{ContentPatch, SledStore, Store};
+    use serde_json::json;
+
+    #[test]
+    fn test_content_schemas() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut schemas = store.content_schemas().unwrap();
+        let task = json!({
+            "type": "object",
+            "title": "Task",
+            "required": ["title", "status"],
+            "properties": {
+                "title": {"type": "string", "minLength": 1},
+                "status": {"enum": ["todo", "doing", "done"]},
+                "estimate": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "additionalProperties": false
+        });
+        schemas.set(&NodeKind::Task, task).unwrap();
+        assert!(schemas.set(&NodeKind::Task, json!({"pattern": "^a"})).is_err());
+        assert!(schemas.set(&NodeKind::Task, json!({"type": "text"})).is_err());
+        store.set_content_schemas(&schemas).unwrap();
+
+        let good = json!({"title": "Ship", "status": "todo", "tags": ["v2"]});
+        let node = store.create_node(StateNode::new(NodeKind::Task, good), AgentId::User).unwrap();
+        // Kinds without a schema take anything
+        store.create_node(StateNode::new(NodeKind::Insight, json!(42)), AgentId::User).unwrap();
+
+        let bad = json!({"title": "", "estimate": -1.5, "tags": ["ok", 3], "owner": "me"});
+        match store.create_node(StateNode::new(NodeKind::Task, bad), AgentId::Claude) {
+            Err(StoreError::SchemaViolation(kind, violations)) => {
+                assert_eq!(kind, "task");
+                let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+                assert_eq!(paths, vec!["", "/estimate", "/owner", "/tags/1", "/title"]);
+                assert!(violations[0].message.contains("\"status\""));
+            }
+            other => panic!("expected a schema violation, got {:?}", other),
+        }
+
+        assert!(matches!(
+            store.update_node(node.id, json!({"title": "Ship"}), None, AgentId::User),
+            Err(StoreError::SchemaViolation(..))
+        ));
+        let patch = ContentPatch::Merge(json!({"status": "blocked"}));
+        assert!(matches!(store.patch_node(node.id, &patch, AgentId::User), Err(StoreError::SchemaViolation(..))));
+        let patch = ContentPatch::Merge(json!({"status": "done"}));
+        assert_eq!(store.patch_node(node.id, &patch, AgentId::User).unwrap().content["status"], "done");
+
+        let either = json!({"oneOf": [{"type": "string"}, {"type": "integer"}], "not": {"const": 0}});
+        let mut violations = Vec::new();
+        validate(&either, &json!(0), "", &mut violations);
+        assert_eq!(violations.len(), 1);
+        validate(&either, &json!(true), "", &mut violations);
+        assert_eq!(violations.len(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+//! Time-boxed read-only share links
+//!
+//! A share link grants read access to the subgraph around one root node
+//! until it expires or is revoked. The token handed out is
+//! `<share id>.<expiry>.<signature>`, signed with HMAC-SHA256 under a
+//! per-database secret, so a token cannot be forged or have its expiry
+//! extended. Every creation, access, denial and revocation is audited.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use ulid::Ulid;
+
+use super::{Result, StoreError};
+use crate::schema::{AgentId, NodeId, StateEdge, StateNode};
+
+pub type ShareId = Ulid;
+
+/// Metadata key holding the hex-encoded signing secret
+pub(crate) const SECRET_KEY: &str = "share_secret";
+
+/// Traversal depth used when none is given
+pub const DEFAULT_SHARE_DEPTH: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: ShareId,
+    pub root: NodeId,
+    /// Edge hops from the root included in the shared subgraph
+    pub depth: usize,
+    pub created_by: AgentId,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ShareLink {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareAction {
+    Created,
+    Accessed,
+    Denied,
+    Revoked,
+}
+
+impl std::fmt::Display for ShareAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            ShareAction::Created => "created",
+            ShareAction::Accessed => "accessed",
+            ShareAction::Denied => "denied",
+            ShareAction::Revoked => "revoked",
+        })
+    }
+}
+
+/// One audit log entry for a share link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareAuditEntry {
+    pub id: Ulid,
+    pub share: ShareId,
+    pub action: ShareAction,
+    pub at: DateTime<Utc>,
+    /// Why access was denied, or who created or revoked the link
+    pub detail: Option<String>,
+}
+
+impl ShareAuditEntry {
+    pub(crate) fn new(share: ShareId, action: ShareAction, detail: Option<String>) -> Self {
+        Self {
+            id: Ulid::new(),
+            share,
+            action,
+            at: Utc::now(),
+            detail,
+        }
+    }
+}
+
+/// The subgraph visible through a share link
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedGraph {
+    pub share: ShareLink,
+    pub nodes: Vec<StateNode>,
+    /// Edges between shared nodes only
+    pub edges: Vec<StateEdge>,
+}
+
+pub(crate) fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::random();
+    to_hex(&bytes)
+}
+
+pub(crate) fn sign(secret: &str, id: ShareId, expires_at: DateTime<Utc>) -> String {
+    let payload = format!("{}.{}", id, expires_at.timestamp());
+    let signature = mac(secret, &payload).finalize().into_bytes();
+    format!("{}.{}", payload, to_hex(&signature))
+}
+
+/// Check a token's signature and return the share it names
+///
+/// Expiry is checked against the stored link, not the token, so revoking
+/// or shortening a link takes effect immediately.
+pub(crate) fn verify(secret: &str, token: &str) -> Result<ShareId> {
+    let denied = || StoreError::ShareDenied("malformed or forged token".into());
+    let (payload, signature) = token.rsplit_once('.').ok_or_else(denied)?;
+    let signature = from_hex(signature).ok_or_else(denied)?;
+    mac(secret, payload).verify_slice(&signature).map_err(|_| denied())?;
+
+    let (id, _) = payload.split_once('.').ok_or_else(denied)?;
+    id.parse().map_err(|_| denied())
+}
+
+fn mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_cannot_be_tampered_with() {
+        let id = Ulid::new();
+        let expires = Utc::now() + chrono::Duration::hours(1);
+        let token = sign("secret", id, expires);
+
+        assert_eq!(verify("secret", &token).unwrap(), id);
+        assert!(verify("other secret", &token).is_err());
+
+        let later = (expires + chrono::Duration::days(30)).timestamp();
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let forged = format!("{}.{}.{}", id, later, signature);
+        assert!(verify("secret", &forged).is_err());
+        assert!(verify("secret", "garbage").is_err());
+    }
+}
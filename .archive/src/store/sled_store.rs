@@ -1,8 +1,40 @@
-use super::{Result, Store, StoreError};
+use super::share::{self, ShareAction, ShareAuditEntry, ShareId, ShareLink, SharedGraph};
+use super::snapshot::{self, RestoreReport, SnapshotInfo};
+use super::attachment::{self, Attachment};
+use super::migrate::{self, Migration, MigrationReport, SCHEMA_VERSION};
+use super::dump::{self, DumpHeader, DumpRecord, DumpSummary, DumpWriter};
+use super::hooks::{HookPoint, Hooks};
+use super::metrics::{Metrics, MetricsSnapshot};
+use super::path::{self, Direction, GraphPath, PathMode, Reached, Subgraph};
+use super::history::{self, NodeVersion};
+use super::stamp::{AgentDefaults, SYSTEM_FIELDS};
+use super::kinds::KindRegistry;
+use super::schemas::ContentSchemas;
+use super::constraints::GraphConstraints;
+use super::retention::RetentionPolicy;
+use super::alias::validate_alias;
+use super::extract::TextExtraction;
+use super::templates::{self, NodeTemplate};
+use super::merge::{self, MergeStrategy};
+use super::embeddings::{self, CachedEmbedding, Embedder, EmbeddingStats, EMBEDDINGS_TREE, EMBEDDING_COUNTERS_TREE};
+use super::chunks::CHUNK_INDEX_KEY;
+use super::cycles;
+use super::maintenance::{MaintenanceKind, MaintenanceRecord, Measured, MAINTENANCE_TREE};
+use super::stats::{self, Counter, GraphStats, STATS_TREE};
+use super::sort::{Sort, SortKey};
+use super::indices::{self, MetaQuery};
+use super::index_archive::StoreRevision;
+use super::undo::{self, Revert, Reversal};
+use super::{ContentPatch, DbLock, DedupeMode, DedupeOutcome, DeleteMode, GraphReads, Result, Store, StoreError};
 use crate::schema::*;
 use serde_json::Value;
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionError, TransactionalTree, Transactional,
+};
 use sled::Db;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::Path;
+use std::sync::Arc;
 
 const NODES_TREE: &str = "nodes";
 const EDGES_TREE: &str = "edges";
@@ -10,462 +42,6170 @@
 const NODES_BY_KIND_TREE: &str = "nodes_by_kind";
 const EDGES_BY_FROM_TREE: &str = "edges_by_from";
 const EDGES_BY_TO_TREE: &str = "edges_by_to";
+const NODES_BY_EXPIRY_TREE: &str = "nodes_by_expiry";
+/// Update times: big-endian millis ++ NodeId -> ()
+const NODES_BY_UPDATED_TREE: &str = "nodes_by_updated";
+/// Content hashes: kind/hash -> [NodeId]
+const NODES_BY_HASH_TREE: &str = "nodes_by_hash";
+const METADATA_TREE: &str = "metadata";
+/// Namespaces that have been written to; a database-wide tree outside
+/// every namespace
+const NAMESPACES_TREE: &str = "namespaces";
+const ANNOTATIONS_TREE: &str = "annotations";
+const REACTIONS_TREE: &str = "reactions";
+const SHARES_TREE: &str = "shares";
+const SHARE_AUDIT_TREE: &str = "share_audit";
+/// Declared metadata indexes: field -> ()
+const META_INDEX_FIELDS_TREE: &str = "meta_index_fields";
+/// Metadata index entries: field \0 value \0 NodeId -> ()
+const META_INDEX_TREE: &str = "meta_index";
+/// Tag index entries: tag \0 NodeId -> ()
+const NODES_BY_TAG_TREE: &str = "nodes_by_tag";
+/// Pinned nodes: NodeId -> ()
+const PINNED_NODES_TREE: &str = "pinned_nodes";
+/// Node aliases: alias -> NodeId
+const ALIASES_TREE: &str = "aliases";
+/// Reverse of `ALIASES_TREE`: NodeId -> alias
+const ALIASES_BY_NODE_TREE: &str = "aliases_by_node";
+/// Reciprocal edge pairs, recorded both ways: EdgeId -> EdgeId
+const RECIPROCALS_TREE: &str = "edge_reciprocals";
+/// Attachment metadata: NodeId ++ hash -> Attachment
+const ATTACHMENTS_TREE: &str = "attachments";
+/// Content-addressed attachment bytes: hash -> bytes
+const BLOBS_TREE: &str = "blobs";
+/// Cold tier: NodeId -> always-compressed node, hidden from list and search
+const ARCHIVE_TREE: &str = "archive";
+/// Records removed by `check --fix`, keyed by `<tree>/<original key>`
+const QUARANTINE_TREE: &str = "quarantine";
+/// Imported node IDs: source instance \0 remote NodeId -> local NodeId
+const IMPORT_REMAP_TREE: &str = "import_remap";
+/// Undo and redo: compensating EventId -> the EventId it reverts
+const REVERTS_TREE: &str = "event_reverts";
 
+/// Key in the database's default tree holding the schema version stamp;
+/// shared by every namespace
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Metadata key holding the event `CapturePolicy`
+pub const CAPTURE_POLICY_KEY: &str = "event_capture";
+
+/// Metadata key holding the `RetentionPolicy`
+pub const RETENTION_POLICY_KEY: &str = "retention";
+
+/// Metadata key holding the per-agent `AgentDefaults`
+pub const AGENT_DEFAULTS_KEY: &str = "agent_defaults";
+
+/// Metadata key holding the edge kinds new edges may not form cycles in
+pub const ACYCLIC_KINDS_KEY: &str = "acyclic_edge_kinds";
+
+/// Metadata key holding the edge kinds that link both ways
+pub const SYMMETRIC_KINDS_KEY: &str = "symmetric_edge_kinds";
+
+/// Metadata key holding the pairs of edge kinds created as reciprocals
+pub const RECIPROCAL_KINDS_KEY: &str = "reciprocal_edge_kinds";
+
+/// Metadata key holding this store's instance ID, written into exports
+pub const INSTANCE_ID_KEY: &str = "instance_id";
+
+/// Metadata key holding the registered custom node and edge kinds
+pub const KIND_REGISTRY_KEY: &str = "kind_registry";
+
+/// Metadata key holding the vector clock of the events this store has seen
+pub const VECTOR_CLOCK_KEY: &str = "vector_clock";
+
+/// Metadata key holding the per-kind JSON Schemas for node content
+pub const CONTENT_SCHEMAS_KEY: &str = "content_schemas";
+
+/// Metadata key holding the named graph constraints
+pub const GRAPH_CONSTRAINTS_KEY: &str = "graph_constraints";
+
+/// Metadata key holding the per-kind rules for what search reads
+pub const TEXT_EXTRACTION_KEY: &str = "text_extraction";
+
+/// Metadata key holding the saved node templates, by name
+pub const TEMPLATES_KEY: &str = "node_templates";
+
+/// Frame magic written by zstd at the start of every compressed value
+pub(super) const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+pub(super) const COMPRESSION_LEVEL: i32 = 3;
+
+/// Serialized values at least this large are compressed by default
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 4096;
+
+/// Compression statistics for the node tree
+#[derive(Debug, Clone, Default)]
+pub struct CompressionStats {
+    pub values: usize,
+    pub compressed_values: usize,
+    /// Bytes as stored on disk
+    pub stored_bytes: u64,
+    /// Bytes after decompression
+    pub raw_bytes: u64,
+}
+
+impl CompressionStats {
+    /// Ratio of raw to stored size (higher is better)
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.raw_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// Space used by the database directory
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskUsage {
+    /// Bytes sled occupies on disk
+    pub on_disk: u64,
+    /// Bytes of live keys and values across all trees
+    pub live: u64,
+}
+
+impl DiskUsage {
+    /// Estimate of the space compaction could give back
+    pub fn reclaimable(&self) -> u64 {
+        self.on_disk.saturating_sub(self.live)
+    }
+}
+
+/// Live data held by one tree
+#[derive(Debug, Clone, Default)]
+pub struct TreeUsage {
+    /// Full tree name, including any namespace prefix
+    pub name: String,
+    pub entries: u64,
+    /// Bytes of keys and values
+    pub bytes: u64,
+}
+
+/// Outcome of an online compaction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactionReport {
+    pub before: DiskUsage,
+    pub after: DiskUsage,
+    pub trees: usize,
+    /// Entries rewritten
+    pub entries: u64,
+}
+
+impl CompactionReport {
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.before.on_disk.saturating_sub(self.after.on_disk)
+    }
+}
+
+/// Kind of problem found by an integrity check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckIssueKind {
+    /// A record that does not decode (including unparseable JSON content)
+    Corrupt,
+    /// An edge whose `from` or `to` node does not exist
+    DanglingEdge,
+    /// A record missing from an index that should list it
+    MissingIndexEntry,
+    /// An index entry pointing at a record that does not exist
+    StaleIndexEntry,
+    /// An event whose target neither exists nor was deleted by a later event
+    UnresolvableEvent,
+    /// A check that could not run
+    Skipped,
+}
+
+impl std::fmt::Display for CheckIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            CheckIssueKind::Corrupt => "corrupt",
+            CheckIssueKind::DanglingEdge => "dangling-edge",
+            CheckIssueKind::MissingIndexEntry => "missing-index",
+            CheckIssueKind::StaleIndexEntry => "stale-index",
+            CheckIssueKind::UnresolvableEvent => "unresolvable-event",
+            CheckIssueKind::Skipped => "skipped",
+        })
+    }
+}
+
+/// One problem found by an integrity check
+#[derive(Debug, Clone)]
+pub struct CheckIssue {
+    pub kind: CheckIssueKind,
+    /// Tree the record lives in
+    pub tree: &'static str,
+    /// Record ID, or the raw key in hex when it is not an ID
+    pub record: String,
+    pub detail: String,
+    /// Whether `--fix` repaired or quarantined the record
+    pub fixed: bool,
+}
+
+/// Outcome of an integrity check
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    pub nodes: usize,
+    pub edges: usize,
+    pub events: usize,
+    pub issues: Vec<CheckIssue>,
+}
+
+impl CheckReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Issues left in place
+    pub fn unfixed(&self) -> usize {
+        self.issues.iter().filter(|i| !i.fixed).count()
+    }
+
+    fn push(
+        &mut self,
+        kind: CheckIssueKind,
+        tree: &'static str,
+        key: &[u8],
+        detail: impl Into<String>,
+        fixed: bool,
+    ) {
+        self.issues.push(CheckIssue {
+            kind,
+            tree,
+            record: describe_key(key),
+            detail: detail.into(),
+            fixed,
+        });
+    }
+}
+
+/// ULID keys print as IDs, anything else as text or hex
+fn describe_key(key: &[u8]) -> String {
+    if let Ok(bytes) = <[u8; 16]>::try_from(key) {
+        return ulid::Ulid::from_bytes(bytes).to_string();
+    }
+    match std::str::from_utf8(key) {
+        Ok(text) => text.to_string(),
+        Err(_) => key.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+/// The reciprocal found or built for a new edge
+enum Twin {
+    Existing(EdgeId),
+    New(StateEdge),
+}
+
+/// The graph as a rewire would leave it, for checking the edges it adds
+/// before anything is written
+struct Rewired<'a> {
+    store: &'a SledStore,
+    removed: &'a HashSet<EdgeId>,
+    added: &'a [StateEdge],
+    symmetric: &'a [EdgeKind],
+}
+
+impl Rewired<'_> {
+    /// Stored edges `near` returns, less the removed ones, plus the added
+    /// edges with `node_id` at the end `at` picks and symmetric ones
+    /// mirrored from the other end
+    fn edges(
+        &self,
+        near: Vec<StateEdge>,
+        node_id: NodeId,
+        at: fn(&StateEdge) -> NodeId,
+        far: fn(&StateEdge) -> NodeId,
+    ) -> Vec<StateEdge> {
+        let mut edges: Vec<StateEdge> = near.into_iter().filter(|e| !self.removed.contains(&e.id)).collect();
+        edges.extend(self.added.iter().filter(|e| at(e) == node_id).cloned());
+        edges.extend(
+            self.added
+                .iter()
+                .filter(|e| far(e) == node_id && e.from != e.to && self.symmetric.contains(&e.kind))
+                .map(|e| e.clone().reversed()),
+        );
+        edges
+    }
+}
+
+impl GraphReads for Rewired<'_> {
+    fn node(&self, id: NodeId) -> Result<Option<StateNode>> {
+        self.store.get_node(id)
+    }
+
+    fn outgoing(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        Ok(self.edges(self.store.edges_from(node_id)?, node_id, |e| e.from, |e| e.to))
+    }
+
+    fn incoming(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        Ok(self.edges(self.store.edges_to(node_id)?, node_id, |e| e.to, |e| e.from))
+    }
+}
+
+/// Separator between a namespace and the tree name it scopes
+const NAMESPACE_SEPARATOR: &str = "::";
+
+#[derive(Clone)]
 pub struct SledStore {
     db: Db,
+    compression_threshold: Option<usize>,
+    namespace: Option<String>,
+    read_only: bool,
+    /// Fail instead of skipping records and indexes that can't be used
+    strict: bool,
+    /// Cross-process lock, shared by namespaced views of the same database
+    lock: Option<Arc<DbLock>>,
+    hooks: Hooks,
+    metrics: Metrics,
 }
 
 impl SledStore {
+    /// Open a database, failing with `StoreError::Locked` if another
+    /// process already has it open
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let lock = DbLock::acquire(path.as_ref())?;
+        Self::open_locked(path, lock)
+    }
+
+    /// Open a database, waiting for any other process to release it first
+    pub fn open_wait<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let lock = DbLock::acquire_blocking(path.as_ref())?;
+        Self::open_locked(path, lock)
+    }
+
+    fn open_locked<P: AsRef<Path>>(path: P, lock: DbLock) -> Result<Self> {
         let db = sled::open(path)?;
-        Ok(Self { db })
+        let store = Self {
+            db,
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
+            namespace: None,
+            read_only: false,
+            strict: false,
+            lock: Some(Arc::new(lock)),
+            hooks: Hooks::default(),
+            metrics: Metrics::default(),
+        };
+        store.check_schema()?;
+        Ok(store)
+    }
+
+    /// Open a database that rejects every write with `StoreError::ReadOnly`
+    ///
+    /// The database lock is still taken, so this guards against accidental
+    /// writes rather than allowing a second process alongside a writer.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(StoreError::InvalidOperation(format!(
+                "No database at {}",
+                path.display()
+            )));
+        }
+        Ok(Self::open(path)?.read_only())
     }
 
     pub fn open_temporary() -> Result<Self> {
         let db = sled::Config::new().temporary(true).open()?;
-        Ok(Self { db })
+        let store = Self {
+            db,
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
+            namespace: None,
+            read_only: false,
+            strict: false,
+            lock: None,
+            hooks: Hooks::default(),
+            metrics: Metrics::default(),
+        };
+        store.check_schema()?;
+        Ok(store)
     }
 
-    fn nodes_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(NODES_TREE)?)
+    /// Stamp a fresh database and refuse one written by a newer build
+    fn check_schema(&self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            return Err(StoreError::UnsupportedSchema(version, SCHEMA_VERSION));
+        }
+        if self.db.get(SCHEMA_VERSION_KEY)?.is_none() && version == SCHEMA_VERSION {
+            self.stamp_schema(version)?;
+        }
+        Ok(())
     }
 
-    fn edges_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(EDGES_TREE)?)
+    /// Schema version of the on-disk data
+    ///
+    /// An unstamped database is current when empty and assumed to be at
+    /// `UNSTAMPED_VERSION` otherwise.
+    pub fn schema_version(&self) -> Result<u32> {
+        if let Some(bytes) = self.db.get(SCHEMA_VERSION_KEY)? {
+            let bytes: [u8; 4] = bytes
+                .as_ref()
+                .try_into()
+                .map_err(|_| StoreError::Serialization("corrupt schema version".into()))?;
+            return Ok(u32::from_be_bytes(bytes));
+        }
+        let has_data = self.db.tree_names().iter().any(|name| {
+            let name = String::from_utf8_lossy(name);
+            [NODES_TREE, EVENTS_TREE].iter().any(|tree| {
+                name == *tree || name.ends_with(&format!("{}{}", NAMESPACE_SEPARATOR, tree))
+            }) && self.db.open_tree(name.as_bytes()).is_ok_and(|t| !t.is_empty())
+        });
+        Ok(if has_data { migrate::UNSTAMPED_VERSION } else { SCHEMA_VERSION })
     }
 
-    fn events_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(EVENTS_TREE)?)
+    fn stamp_schema(&self, version: u32) -> Result<()> {
+        self.db.insert(SCHEMA_VERSION_KEY, &version.to_be_bytes())?;
+        self.db.flush()?;
+        Ok(())
     }
 
-    fn nodes_by_kind_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(NODES_BY_KIND_TREE)?)
+    /// Migrations still to run, oldest first
+    pub fn pending_migrations(&self) -> Result<Vec<&'static Migration>> {
+        let current = self.schema_version()?;
+        Ok(migrate::migrations().iter().filter(|m| m.version > current).collect())
     }
 
-    fn edges_by_from_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(EDGES_BY_FROM_TREE)?)
+    /// Run pending migrations up to `target` (the latest by default) across
+    /// every namespace, stamping the version after each one
+    pub fn migrate(&self, target: Option<u32>) -> Result<Vec<MigrationReport>> {
+        self.maintain(MaintenanceKind::Migration, |reports: &Vec<MigrationReport>| {
+            reports.iter().map(|r| r.rewritten as u64).sum()
+        }, || {
+            self.ensure_writable()?;
+            let current = self.schema_version()?;
+            let target = target.unwrap_or(SCHEMA_VERSION);
+            if target > SCHEMA_VERSION {
+                return Err(StoreError::UnsupportedSchema(target, SCHEMA_VERSION));
+            }
+            if target < current {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Cannot downgrade schema from version {} to {}",
+                    current, target
+                )));
+            }
+
+            let root = Self { namespace: None, ..self.clone() };
+            let mut views = vec![root.clone()];
+            for namespace in root.list_namespaces()? {
+                views.push(root.namespaced(namespace)?);
+            }
+
+            let mut reports = Vec::new();
+            for migration in self.pending_migrations()?.into_iter().filter(|m| m.version <= target) {
+                let mut rewritten = 0;
+                for view in &views {
+                    rewritten += (migration.run)(view)?;
+                }
+                self.stamp_schema(migration.version)?;
+                for view in &views {
+                    view.invalidate_stats()?;
+                }
+                reports.push(MigrationReport {
+                    version: migration.version,
+                    description: migration.description,
+                    rewritten,
+                });
+            }
+            if target == SCHEMA_VERSION {
+                // Also stamps a database that had nothing to migrate
+                self.stamp_schema(target)?;
+            }
+            Ok(reports)
+        })
     }
 
-    fn edges_by_to_tree(&self) -> Result<sled::Tree> {
-        Ok(self.db.open_tree(EDGES_BY_TO_TREE)?)
+    /// The latest event and event count, which an exported search index is
+    /// stamped with
+    pub fn revision(&self) -> Result<StoreRevision> {
+        let events = self.events_tree()?;
+        let last_event = match events.last()? {
+            Some((key, _)) => Some(EventId::from_bytes(
+                key.as_ref().try_into().map_err(|_| StoreError::Serialization("Invalid event key".into()))?,
+            )),
+            None => None,
+        };
+        Ok(StoreRevision { last_event, events: events.len() as u64 })
     }
 
-    fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>> {
-        bincode::serialize(value).map_err(|e| StoreError::Serialization(e.to_string()))
+    /// Write every namespace of the database to a portable dump file
+    ///
+    /// Nodes whose retention class isn't backed up are left out, along with
+    /// edges touching them.
+    pub fn dump_to(&self, path: &Path) -> Result<DumpSummary> {
+        self.maintain(MaintenanceKind::Backup, |summary: &DumpSummary| summary.records, || {
+            let header = DumpHeader {
+                format: dump::DUMP_FORMAT.to_string(),
+                version: dump::DUMP_VERSION,
+                schema_version: self.schema_version()?,
+                created_at: chrono::Utc::now(),
+                counter: self.db.generate_id()?,
+            };
+            if !self.pending_migrations()?.is_empty() {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Schema version {} is out of date; migrate before dumping",
+                    header.schema_version
+                )));
+            }
+            let mut writer = DumpWriter::create(path, &header)?;
+            let mut summary = DumpSummary {
+                schema_version: header.schema_version,
+                ..Default::default()
+            };
+
+            let root = Self { namespace: None, ..self.clone() };
+            let mut namespaces = vec![None];
+            namespaces.extend(root.list_namespaces()?.into_iter().map(Some));
+            summary.namespaces = namespaces.len();
+            for namespace in namespaces {
+                let view = match &namespace {
+                    Some(ns) => root.namespaced(ns.clone())?,
+                    None => root.clone(),
+                };
+                let mut emit = |record: DumpRecord| -> Result<()> {
+                    summary.count(&record);
+                    writer.record(&record)
+                };
+                let ns = || namespace.clone();
+
+                // Classes kept out of backups take their edges with them
+                let retention = view.retention_policy()?;
+                let mut skipped = HashSet::new();
+                for (tree, archived) in [(view.nodes_tree()?, false), (view.archive_tree()?, true)] {
+                    for entry in tree.iter() {
+                        let node: StateNode = Self::deserialize(&entry?.1)?;
+                        if !retention.rules_for(&node).backup {
+                            skipped.insert(node.id);
+                            continue;
+                        }
+                        emit(DumpRecord::Node { namespace: ns(), node, archived })?;
+                    }
+                }
+                for entry in view.edges_tree()?.iter() {
+                    let edge: StateEdge = Self::deserialize(&entry?.1)?;
+                    if skipped.contains(&edge.from) || skipped.contains(&edge.to) {
+                        continue;
+                    }
+                    emit(DumpRecord::Edge { namespace: ns(), edge })?;
+                }
+                for entry in view.events_tree()?.iter() {
+                    emit(DumpRecord::Event { namespace: ns(), event: Self::deserialize(&entry?.1)? })?;
+                }
+                for entry in view.annotations_tree()?.iter() {
+                    let annotation = Self::deserialize(&entry?.1)?;
+                    emit(DumpRecord::Annotation { namespace: ns(), annotation })?;
+                }
+                for entry in view.reactions_tree()?.iter() {
+                    let reaction = Self::deserialize(&entry?.1)?;
+                    emit(DumpRecord::Reaction { namespace: ns(), reaction })?;
+                }
+                for entry in view.metadata_tree()?.iter() {
+                    let (key, bytes) = entry?;
+                    let value = serde_json::from_slice(&bytes)
+                        .map_err(|e| StoreError::Serialization(e.to_string()))?;
+                    let key = String::from_utf8_lossy(&key).into_owned();
+                    emit(DumpRecord::Meta { namespace: ns(), key, value })?;
+                }
+                for field in view.metadata_indexes()? {
+                    emit(DumpRecord::MetaIndex { namespace: ns(), field })?;
+                }
+                for (alias, node) in view.aliases()? {
+                    if !skipped.contains(&node) {
+                        emit(DumpRecord::Alias { namespace: ns(), alias, node })?;
+                    }
+                }
+                // Each pair once; twins join the same nodes, so both or neither were dumped
+                for entry in view.open_tree(RECIPROCALS_TREE)?.iter() {
+                    let (edge, reciprocal) = entry?;
+                    let (edge, reciprocal) = (Self::stored_edge_id(&edge)?, Self::stored_edge_id(&reciprocal)?);
+                    let dumped = view.get_edge(edge)?.is_some_and(|e| !skipped.contains(&e.from) && !skipped.contains(&e.to));
+                    if edge < reciprocal && dumped {
+                        emit(DumpRecord::Reciprocal { namespace: ns(), edge, reciprocal })?;
+                    }
+                }
+                let blobs = view.open_tree(BLOBS_TREE)?;
+                for entry in view.open_tree(ATTACHMENTS_TREE)?.iter() {
+                    let attachment: Attachment = Self::deserialize(&entry?.1)?;
+                    let data = blobs.get(attachment.hash.as_bytes())?.unwrap_or_default();
+                    let data = dump::to_hex(&data);
+                    emit(DumpRecord::Attachment { namespace: ns(), attachment, data })?;
+                }
+                for link in view.list_shares()? {
+                    emit(DumpRecord::Share { namespace: ns(), link })?;
+                }
+                for entry in view.open_tree(SHARE_AUDIT_TREE)?.iter() {
+                    let (key, bytes) = entry?;
+                    let seq = key
+                        .as_ref()
+                        .try_into()
+                        .map(u64::from_be_bytes)
+                        .map_err(|_| StoreError::Serialization("corrupt share audit key".into()))?;
+                    let entry = Self::deserialize(&bytes)?;
+                    emit(DumpRecord::ShareAudit { namespace: ns(), seq, entry })?;
+                }
+            }
+
+            writer.finish()?;
+            Ok(summary)
+        })
     }
 
-    fn deserialize<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
-        bincode::deserialize(bytes).map_err(|e| StoreError::Serialization(e.to_string()))
+    /// Load a dump into this database, which must be empty
+    ///
+    /// The file is verified in full before anything is written. Records
+    /// are re-encoded for the current schema and indexes are rebuilt.
+    pub fn load_from(&self, path: &Path) -> Result<DumpSummary> {
+        self.ensure_writable()?;
+        let root = Self { namespace: None, ..self.clone() };
+        if root.count_nodes(None)? > 0 || root.count_events()? > 0 || !root.list_namespaces()?.is_empty() {
+            return Err(StoreError::InvalidOperation(
+                "Refusing to load a dump into a database that already has data".into(),
+            ));
+        }
+        let header = dump::verify_dump(path)?;
+        let mut summary = DumpSummary {
+            schema_version: header.schema_version,
+            ..Default::default()
+        };
+
+        let mut views: HashMap<Option<String>, SledStore> = HashMap::new();
+        for record in dump::read_records(path)? {
+            let record = record?;
+            summary.count(&record);
+            let namespace = match &record {
+                DumpRecord::Node { namespace, .. }
+                | DumpRecord::Edge { namespace, .. }
+                | DumpRecord::Event { namespace, .. }
+                | DumpRecord::Annotation { namespace, .. }
+                | DumpRecord::Reaction { namespace, .. }
+                | DumpRecord::Meta { namespace, .. }
+                | DumpRecord::MetaIndex { namespace, .. }
+                | DumpRecord::Alias { namespace, .. }
+                | DumpRecord::Reciprocal { namespace, .. }
+                | DumpRecord::Attachment { namespace, .. }
+                | DumpRecord::Share { namespace, .. }
+                | DumpRecord::ShareAudit { namespace, .. } => namespace.clone(),
+            };
+            if !views.contains_key(&namespace) {
+                let view = match &namespace {
+                    Some(ns) => root.namespaced(ns.clone())?,
+                    None => root.clone(),
+                };
+                view.register_namespace()?;
+                views.insert(namespace.clone(), view);
+            }
+            let view = &views[&namespace];
+
+            match record {
+                DumpRecord::Node { node, archived: false, .. } => view.write_node(&node)?,
+                DumpRecord::Node { node, archived: true, .. } => {
+                    let compressed = zstd::encode_all(Self::serialize(&node)?.as_slice(), COMPRESSION_LEVEL)
+                        .map_err(|e| StoreError::Serialization(format!("compression failed: {e}")))?;
+                    view.archive_tree()?.insert(node.id.to_bytes(), compressed)?;
+                }
+                DumpRecord::Edge { edge, .. } => view.write_edge(&edge)?,
+                DumpRecord::Event { event, .. } => {
+                    view.events_tree()?.insert(event.id.to_bytes(), view.encode(&event)?)?;
+                }
+                DumpRecord::Annotation { annotation, .. } => {
+                    let key = Self::annotation_key(annotation.node_id, annotation.id);
+                    view.annotations_tree()?.insert(key, Self::serialize(&annotation)?)?;
+                }
+                DumpRecord::Reaction { reaction, .. } => {
+                    let key = Self::reaction_key(reaction.node_id, reaction.kind, &reaction.agent);
+                    view.reactions_tree()?.insert(key, Self::serialize(&reaction)?)?;
+                }
+                DumpRecord::Meta { key, value, .. } => view.set_meta(&key, &value)?,
+                DumpRecord::MetaIndex { field, .. } => {
+                    view.create_metadata_index(&field)?;
+                }
+                DumpRecord::Alias { alias, node, .. } => {
+                    view.open_tree(ALIASES_TREE)?.insert(alias.as_bytes(), &node.to_bytes()[..])?;
+                    view.open_tree(ALIASES_BY_NODE_TREE)?.insert(node.to_bytes(), alias.as_bytes())?;
+                }
+                DumpRecord::Reciprocal { edge, reciprocal, .. } => view.link_reciprocals(edge, reciprocal)?,
+                DumpRecord::Attachment { attachment, data, .. } => {
+                    let blobs = view.open_tree(BLOBS_TREE)?;
+                    if !blobs.contains_key(attachment.hash.as_bytes())? {
+                        blobs.insert(attachment.hash.as_bytes(), dump::from_hex(&data)?)?;
+                    }
+                    let key = attachment::attachment_key(attachment.node_id, &attachment.hash);
+                    view.open_tree(ATTACHMENTS_TREE)?.insert(key, Self::serialize(&attachment)?)?;
+                }
+                DumpRecord::Share { link, .. } => {
+                    view.open_tree(SHARES_TREE)?.insert(link.id.to_bytes(), Self::serialize(&link)?)?;
+                }
+                DumpRecord::ShareAudit { seq, entry, .. } => {
+                    view.open_tree(SHARE_AUDIT_TREE)?
+                        .insert(seq.to_be_bytes(), Self::serialize(&entry)?)?;
+                }
+            }
+        }
+        summary.namespaces = views.len();
+        for view in views.values() {
+            view.invalidate_stats()?;
+        }
+
+        // New sequence keys must sort after the loaded ones
+        while self.db.generate_id()? < header.counter {}
+        self.stamp_schema(SCHEMA_VERSION)?;
+        self.db.flush()?;
+        Ok(summary)
     }
 
-    fn log_event(&self, event: StateEvent) -> Result<()> {
-        let events = self.events_tree()?;
-        let key = event.id.to_bytes();
-        let value = Self::serialize(&event)?;
-        events.insert(key, value)?;
+    /// Rewrite hot and archived nodes stored in a legacy layout `L`
+    pub(crate) fn upgrade_nodes<L: serde::de::DeserializeOwned>(
+        &self,
+        upgrade: impl Fn(L) -> StateNode,
+    ) -> Result<usize> {
+        let hot = self.upgrade_tree(&self.nodes_tree()?, false, &upgrade)?;
+        let archived = self.upgrade_tree(&self.archive_tree()?, true, &upgrade)?;
+        Ok(hot + archived)
+    }
+
+    /// Rewrite events stored in a legacy layout `L`
+    pub(crate) fn upgrade_events<L: serde::de::DeserializeOwned>(
+        &self,
+        upgrade: impl Fn(L) -> StateEvent,
+    ) -> Result<usize> {
+        self.upgrade_tree(&self.events_tree()?, false, &upgrade)
+    }
+
+    /// Rewrite records from builds before schema versions were stamped
+    /// whose layouts not even the version 1 models read
+    pub(crate) fn upgrade_unstamped(&self) -> Result<usize> {
+        super::legacy::upgrade(&self.nodes_tree()?)
+    }
+
+    /// Re-encode every value that fails to decode as `T` but decodes as `L`
+    fn upgrade_tree<L, T>(
+        &self,
+        tree: &sled::Tree,
+        always_compress: bool,
+        upgrade: &impl Fn(L) -> T,
+    ) -> Result<usize>
+    where
+        L: serde::de::DeserializeOwned,
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let mut rewritten = 0;
+        for entry in tree.iter() {
+            let (key, bytes) = entry?;
+            if Self::deserialize::<T>(&bytes).is_ok() {
+                continue;
+            }
+            // Strict, so a newer layout isn't read as an older one it extends
+            let Ok(legacy) = Self::deserialize_exact::<L>(&bytes) else {
+                // Left for `check --fix` to quarantine
+                continue;
+            };
+            let value = upgrade(legacy);
+            let encoded = if always_compress {
+                zstd::encode_all(Self::serialize(&value)?.as_slice(), COMPRESSION_LEVEL)
+                    .map_err(|e| StoreError::Serialization(format!("compression failed: {e}")))?
+            } else {
+                self.encode(&value)?
+            };
+            tree.insert(key, encoded)?;
+            rewritten += 1;
+        }
+        Ok(rewritten)
+    }
+
+    /// Set the size above which node and event values are zstd-compressed
+    ///
+    /// `None` disables compression for new writes; existing compressed
+    /// values are still read transparently.
+    pub fn with_compression_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Reject all further writes through this handle
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Turn silent degradations into `StoreError::Degraded`: records that
+    /// fail to read or decode during scans, index entries pointing at
+    /// missing records, and metadata lookups on fields without an index
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Path of the lock file held by this process, if any
+    pub fn lock_path(&self) -> Option<&Path> {
+        self.lock.as_deref().map(DbLock::path)
+    }
+
+    /// Call `hook` after every node or edge creation
+    pub fn on_create(&self, hook: impl Fn(&StateEvent) + Send + Sync + 'static) {
+        self.hooks.register(HookPoint::Create, Arc::new(hook));
+    }
+
+    /// Call `hook` after every node update
+    pub fn on_update(&self, hook: impl Fn(&StateEvent) + Send + Sync + 'static) {
+        self.hooks.register(HookPoint::Update, Arc::new(hook));
+    }
+
+    /// Call `hook` after every node or edge deletion
+    pub fn on_delete(&self, hook: impl Fn(&StateEvent) + Send + Sync + 'static) {
+        self.hooks.register(HookPoint::Delete, Arc::new(hook));
+    }
+
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    /// A view of the same database that runs no hooks, for hooks that read
+    /// the store back without keeping its registry alive
+    pub(crate) fn without_hooks(&self) -> Self {
+        Self { hooks: Hooks::default(), ..self.clone() }
+    }
+
+    /// Operation metrics, shared with namespaced views
+    pub fn metrics_registry(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Log store operations slower than `threshold`; `None` disables it
+    pub fn with_slow_op_threshold(self, threshold: Option<std::time::Duration>) -> Self {
+        self.metrics.set_slow_op_threshold(threshold);
+        self
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(StoreError::ReadOnly);
+        }
         Ok(())
     }
 
-    fn add_to_index(&self, tree: &sled::Tree, index_key: &[u8], id: &[u8]) -> Result<()> {
-        let mut ids: Vec<Vec<u8>> = tree
-            .get(index_key)?
-            .map(|v| Self::deserialize(&v))
-            .transpose()?
-            .unwrap_or_default();
+    /// Fail with `problem` in strict mode; otherwise the caller skips what
+    /// it couldn't use
+    fn degrade(&self, problem: impl FnOnce() -> String) -> Result<()> {
+        if self.strict {
+            return Err(StoreError::Degraded(problem()));
+        }
+        Ok(())
+    }
 
-        if !ids.iter().any(|existing| existing == id) {
-            ids.push(id.to_vec());
-            tree.insert(index_key, Self::serialize(&ids)?)?;
+    /// Decode the values of a scan over `tree`, skipping entries that fail
+    /// to read or decode unless the store is strict
+    fn decoded<'a, T: serde::de::DeserializeOwned + 'a>(
+        &'a self,
+        tree: &'static str,
+        entries: impl Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>> + 'a,
+    ) -> impl Iterator<Item = Result<T>> + 'a {
+        entries.filter_map(move |entry| {
+            let problem = match entry {
+                Ok((key, bytes)) => match Self::deserialize::<T>(&bytes) {
+                    Ok(value) => return Some(Ok(value)),
+                    Err(e) => format!("{} entry {} doesn't decode: {}", tree, describe_key(&key), e),
+                },
+                Err(e) => format!("reading {} failed: {}", tree, e),
+            };
+            self.degrade(|| problem).err().map(Err)
+        })
+    }
+
+    /// Fetch the records an index lists by key from `tree`, skipping keys
+    /// with no record unless the store is strict
+    fn indexed<T: serde::de::DeserializeOwned>(
+        &self,
+        tree: &'static str,
+        records: &sled::Tree,
+        ids: impl IntoIterator<Item = Vec<u8>>,
+    ) -> Result<Vec<T>> {
+        let mut result = Vec::new();
+        for id in ids {
+            match records.get(&id)? {
+                Some(bytes) => result.push(Self::deserialize(&bytes)?),
+                None => self.degrade(|| format!("index lists {} entry {}, which doesn't exist", tree, describe_key(&id)))?,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Scope all trees to a namespace
+    ///
+    /// Nodes, edges, events and indexes in different namespaces are fully
+    /// isolated from each other and from the default (unnamed) namespace.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Result<Self> {
+        let namespace = namespace.into();
+        if namespace.is_empty() || namespace.contains(NAMESPACE_SEPARATOR) {
+            return Err(StoreError::InvalidOperation(format!(
+                "Invalid namespace: {:?}",
+                namespace
+            )));
+        }
+        self.namespace = Some(namespace);
+        Ok(self)
+    }
+
+    /// A view of the same database scoped to another namespace
+    pub fn namespaced(&self, namespace: impl Into<String>) -> Result<Self> {
+        self.clone().with_namespace(namespace)
+    }
+
+    /// The namespace this store is scoped to, if any
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// List namespaces that have been written to
+    pub fn list_namespaces(&self) -> Result<Vec<String>> {
+        let mut namespaces = BTreeSet::new();
+        for entry in self.db.open_tree(NAMESPACES_TREE)?.iter() {
+            namespaces.insert(String::from_utf8_lossy(&entry?.0).into_owned());
+        }
+        Ok(namespaces.into_iter().collect())
+    }
+
+    /// Record this store's namespace the first time it's written to; reads
+    /// open a namespace's trees too, so their existence says nothing
+    fn register_namespace(&self) -> Result<()> {
+        if let Some(ns) = &self.namespace {
+            let registry = self.db.open_tree(NAMESPACES_TREE)?;
+            if !registry.contains_key(ns.as_bytes())? {
+                registry.insert(ns.as_bytes(), &[][..])?;
+            }
         }
         Ok(())
     }
 
-    fn remove_from_index(&self, tree: &sled::Tree, index_key: &[u8], id: &[u8]) -> Result<()> {
-        if let Some(value) = tree.get(index_key)? {
-            let mut ids: Vec<Vec<u8>> = Self::deserialize(&value)?;
-            ids.retain(|existing| existing != id);
-            if ids.is_empty() {
-                tree.remove(index_key)?;
-            } else {
-                tree.insert(index_key, Self::serialize(&ids)?)?;
+    fn open_tree(&self, name: &str) -> Result<sled::Tree> {
+        let tree = match &self.namespace {
+            Some(ns) => self
+                .db
+                .open_tree(format!("{}{}{}", ns, NAMESPACE_SEPARATOR, name))?,
+            None => self.db.open_tree(name)?,
+        };
+        Ok(tree)
+    }
+
+    /// Measure how well node values compress
+    pub fn compression_stats(&self) -> Result<CompressionStats> {
+        let nodes = self.nodes_tree()?;
+        let mut stats = CompressionStats::default();
+
+        for entry in nodes.iter() {
+            let (_, bytes) = entry?;
+            stats.values += 1;
+            stats.stored_bytes += bytes.len() as u64;
+            if bytes.starts_with(&ZSTD_MAGIC) {
+                stats.compressed_values += 1;
+                stats.raw_bytes += Self::decompress(&bytes)?.len() as u64;
+            } else {
+                stats.raw_bytes += bytes.len() as u64;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Measure disk usage across the whole database (every namespace)
+    pub fn disk_usage(&self) -> Result<DiskUsage> {
+        Ok(DiskUsage {
+            on_disk: self.db.size_on_disk()?,
+            live: self.tree_usage()?.iter().map(|t| t.bytes).sum(),
+        })
+    }
+
+    /// Live data per tree across the whole database, largest first
+    pub fn tree_usage(&self) -> Result<Vec<TreeUsage>> {
+        let mut usage = Vec::new();
+        for name in self.db.tree_names() {
+            let mut tree = TreeUsage {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                ..Default::default()
+            };
+            for entry in snapshot::tree(&self.db, &name)?.iter() {
+                let (key, value) = entry?;
+                tree.entries += 1;
+                tree.bytes += (key.len() + value.len()) as u64;
+            }
+            usage.push(tree);
+        }
+        usage.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+        Ok(usage)
+    }
+
+    /// Compact the database in place while it stays open
+    ///
+    /// Every live entry is rewritten so the segments holding stale versions
+    /// become empty and sled's segment cleaner can free them. Entries changed
+    /// concurrently are skipped, since their new version is already fresh.
+    pub fn compact(&self) -> Result<CompactionReport> {
+        self.maintain(MaintenanceKind::Compaction, |report: &CompactionReport| report.entries, || {
+            self.ensure_writable()?;
+            self.db.flush()?;
+            let before = self.disk_usage()?;
+
+            let names = self.db.tree_names();
+            let mut entries = 0u64;
+            for name in &names {
+                let tree = snapshot::tree(&self.db, name)?;
+                for entry in tree.iter() {
+                    let (key, value) = entry?;
+                    let swapped = tree.compare_and_swap(&key, Some(&value), Some(value.clone()))?;
+                    if swapped.is_ok() {
+                        entries += 1;
+                    }
+                }
+            }
+
+            self.db.flush()?;
+            Ok(CompactionReport {
+                before,
+                after: self.disk_usage()?,
+                trees: names.len(),
+                entries,
+            })
+        })
+    }
+
+    /// Maintenance runs, oldest first
+    pub fn maintenance_history(&self) -> Result<Vec<MaintenanceRecord>> {
+        let mut records = Vec::new();
+        for entry in self.db.open_tree(MAINTENANCE_TREE)?.iter() {
+            records.push(Self::deserialize(&entry?.1)?);
+        }
+        Ok(records)
+    }
+
+    /// Run `op` as a `kind` maintenance operation and log how it went;
+    /// `items` counts what a successful run touched
+    fn maintain<T>(
+        &self,
+        kind: MaintenanceKind,
+        items: impl FnOnce(&T) -> u64,
+        op: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let started_at = chrono::Utc::now();
+        let timer = std::time::Instant::now();
+        let bytes_before = self.db.size_on_disk().ok();
+        let result = op();
+        if self.read_only {
+            return result;
+        }
+        let record = MaintenanceRecord {
+            kind,
+            started_at,
+            duration_ms: timer.elapsed().as_millis() as u64,
+            error: result.as_ref().err().map(ToString::to_string),
+            measured: Measured {
+                bytes_before,
+                bytes_after: self.db.size_on_disk().ok(),
+                items: result.as_ref().ok().map(items),
+            },
+        };
+        // Shared by every namespace, like the snapshots and dumps it logs
+        let logged = Self::serialize(&record).and_then(|bytes| {
+            self.db.open_tree(MAINTENANCE_TREE)?.insert(self.db.generate_id()?.to_be_bytes(), bytes)?;
+            Ok(())
+        });
+        let value = result?;
+        logged?;
+        Ok(value)
+    }
+
+    /// Verify the integrity of the graph
+    ///
+    /// Checks that every node and edge decodes, every edge's endpoints
+    /// exist, the kind and edge indexes agree with the records, and every
+    /// event's target either exists or was deleted. With `fix`, indexes are
+    /// repaired and corrupt records and dangling edges are moved to the
+    /// quarantine tree. Events are never rewritten.
+    pub fn check(&self, fix: bool) -> Result<CheckReport> {
+        if !fix {
+            return self.scan_integrity(false);
+        }
+        self.maintain(
+            MaintenanceKind::Repair,
+            |report: &CheckReport| (report.issues.len() - report.unfixed()) as u64,
+            || self.scan_integrity(true),
+        )
+    }
+
+    fn scan_integrity(&self, fix: bool) -> Result<CheckReport> {
+        if fix {
+            self.ensure_writable()?;
+        }
+        let nodes = self.nodes_tree()?;
+        let edges = self.edges_tree()?;
+        let nodes_by_kind = self.nodes_by_kind_tree()?;
+        let nodes_by_hash = self.nodes_by_hash_tree()?;
+        let edges_by_from = self.edges_by_from_tree()?;
+        let edges_by_to = self.edges_by_to_tree()?;
+        let mut report = CheckReport::default();
+
+        let mut node_ids = HashSet::new();
+        for entry in nodes.iter() {
+            let (key, bytes) = entry?;
+            report.nodes += 1;
+            let node = match Self::deserialize::<StateNode>(&bytes) {
+                Ok(node) if node.id.to_bytes() == *key => node,
+                Ok(node) => {
+                    let detail = format!("stored under the key of another ID ({})", node.id);
+                    self.quarantine_if(fix, &nodes, NODES_TREE, &key, &bytes)?;
+                    report.push(CheckIssueKind::Corrupt, NODES_TREE, &key, detail, fix);
+                    continue;
+                }
+                Err(e) => {
+                    self.quarantine_if(fix, &nodes, NODES_TREE, &key, &bytes)?;
+                    report.push(CheckIssueKind::Corrupt, NODES_TREE, &key, e.to_string(), fix);
+                    continue;
+                }
+            };
+
+            let kind_key = node.kind.to_string();
+            if !Self::index_contains(&nodes_by_kind, kind_key.as_bytes(), &key)? {
+                if fix {
+                    self.add_to_index(&nodes_by_kind, kind_key.as_bytes(), &key)?;
+                }
+                let detail = format!("not indexed under kind {}", kind_key);
+                report.push(CheckIssueKind::MissingIndexEntry, NODES_TREE, &key, detail, fix);
+            }
+            // Nodes written before content hashing existed are indexed here
+            if !Self::index_contains(&nodes_by_hash, &Self::hash_key(&node), &key)? {
+                if fix {
+                    self.add_to_index(&nodes_by_hash, &Self::hash_key(&node), &key)?;
+                }
+                let detail = "not indexed by content hash".to_string();
+                report.push(CheckIssueKind::MissingIndexEntry, NODES_TREE, &key, detail, fix);
+            }
+            node_ids.insert(key.to_vec());
+        }
+
+        // Archived nodes still own their edges and events
+        let mut endpoint_ids = node_ids.clone();
+        for entry in self.archive_tree()?.iter() {
+            endpoint_ids.insert(entry?.0.to_vec());
+        }
+
+        let mut edge_ids = HashSet::new();
+        for entry in edges.iter() {
+            let (key, bytes) = entry?;
+            report.edges += 1;
+            let edge = match Self::deserialize::<StateEdge>(&bytes) {
+                Ok(edge) if edge.id.to_bytes() == *key => edge,
+                Ok(edge) => {
+                    let detail = format!("stored under the key of another ID ({})", edge.id);
+                    self.quarantine_if(fix, &edges, EDGES_TREE, &key, &bytes)?;
+                    report.push(CheckIssueKind::Corrupt, EDGES_TREE, &key, detail, fix);
+                    continue;
+                }
+                Err(e) => {
+                    self.quarantine_if(fix, &edges, EDGES_TREE, &key, &bytes)?;
+                    report.push(CheckIssueKind::Corrupt, EDGES_TREE, &key, e.to_string(), fix);
+                    continue;
+                }
+            };
+
+            let missing: Vec<String> = [edge.from, edge.to]
+                .iter()
+                .filter(|id| !endpoint_ids.contains(id.to_bytes().as_slice()))
+                .map(|id| id.to_string())
+                .collect();
+            if !missing.is_empty() {
+                self.quarantine_if(fix, &edges, EDGES_TREE, &key, &bytes)?;
+                if fix {
+                    self.remove_from_index(&edges_by_from, &edge.from.to_bytes(), &key)?;
+                    self.remove_from_index(&edges_by_to, &edge.to.to_bytes(), &key)?;
+                }
+                let detail = format!("missing node(s) {}", missing.join(", "));
+                report.push(CheckIssueKind::DanglingEdge, EDGES_TREE, &key, detail, fix);
+                if fix {
+                    continue;
+                }
+            }
+
+            for (index, node, name) in [
+                (&edges_by_from, edge.from, EDGES_BY_FROM_TREE),
+                (&edges_by_to, edge.to, EDGES_BY_TO_TREE),
+            ] {
+                if !Self::index_contains(index, &node.to_bytes(), &key)? {
+                    if fix {
+                        self.add_to_index(index, &node.to_bytes(), &key)?;
+                    }
+                    let detail = format!("missing from {}", name);
+                    report.push(CheckIssueKind::MissingIndexEntry, EDGES_TREE, &key, detail, fix);
+                }
+            }
+            edge_ids.insert(key.to_vec());
+        }
+
+        for (index, name, live) in [
+            (&nodes_by_kind, NODES_BY_KIND_TREE, &node_ids),
+            (&nodes_by_hash, NODES_BY_HASH_TREE, &node_ids),
+            (&edges_by_from, EDGES_BY_FROM_TREE, &edge_ids),
+            (&edges_by_to, EDGES_BY_TO_TREE, &edge_ids),
+        ] {
+            self.check_index(index, name, live, fix, &mut report)?;
+        }
+
+        self.check_events(&endpoint_ids, &edge_ids, fix, &mut report)?;
+        if fix && !report.is_clean() {
+            self.invalidate_stats()?;
+        }
+        Ok(report)
+    }
+
+    /// Drop index entries for records that no longer exist
+    fn check_index(
+        &self,
+        index: &sled::Tree,
+        name: &'static str,
+        live: &HashSet<Vec<u8>>,
+        fix: bool,
+        report: &mut CheckReport,
+    ) -> Result<()> {
+        for entry in index.iter() {
+            let (key, bytes) = entry?;
+            let ids: Vec<Vec<u8>> = match Self::deserialize(&bytes) {
+                Ok(ids) => ids,
+                Err(e) => {
+                    // Indexes are derived data; a fix rebuilds the entry from the records
+                    if fix {
+                        index.remove(&key)?;
+                    }
+                    report.push(CheckIssueKind::Corrupt, name, &key, e.to_string(), fix);
+                    continue;
+                }
+            };
+            for id in ids.iter().filter(|id| !live.contains(*id)) {
+                if fix {
+                    self.remove_from_index(index, &key, id)?;
+                }
+                let detail = format!("lists missing record {}", describe_key(id));
+                report.push(CheckIssueKind::StaleIndexEntry, name, &key, detail, fix);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every event must decode and point at a record that exists or was removed
+    fn check_events(
+        &self,
+        node_ids: &HashSet<Vec<u8>>,
+        edge_ids: &HashSet<Vec<u8>>,
+        fix: bool,
+        report: &mut CheckReport,
+    ) -> Result<()> {
+        let events = self.events_tree()?;
+        let mut decoded = Vec::new();
+        for entry in events.iter() {
+            let (key, bytes) = entry?;
+            report.events += 1;
+            match Self::deserialize::<StateEvent>(&bytes) {
+                Ok(event) => decoded.push(event),
+                Err(e) => {
+                    self.quarantine_if(fix, &events, EVENTS_TREE, &key, &bytes)?;
+                    report.push(CheckIssueKind::Corrupt, EVENTS_TREE, &key, e.to_string(), fix);
+                }
+            }
+        }
+
+        let mut removed: HashSet<Vec<u8>> = decoded
+            .iter()
+            .filter(|e| matches!(e.operation, Operation::Delete | Operation::Unlink))
+            .map(|e| match e.target {
+                Target::Node(id) | Target::Edge(id) => id.to_bytes().to_vec(),
+            })
+            .collect();
+        // Quarantined records count as removed
+        for entry in self.open_tree(QUARANTINE_TREE)?.iter() {
+            let (key, _) = entry?;
+            if let Some(slash) = key.iter().position(|b| *b == b'/') {
+                removed.insert(key[slash + 1..].to_vec());
+            }
+        }
+
+        for event in &decoded {
+            let (live, target) = match event.target {
+                Target::Node(id) => (node_ids, id),
+                Target::Edge(id) => (edge_ids, id),
+            };
+            let key = target.to_bytes();
+            if !live.contains(key.as_slice()) && !removed.contains(key.as_slice()) {
+                let detail = format!("{:?} of {} that no longer exists", event.operation, target);
+                report.push(
+                    CheckIssueKind::UnresolvableEvent,
+                    EVENTS_TREE,
+                    &event.id.to_bytes(),
+                    detail,
+                    false,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn index_len(index: &sled::Tree, index_key: &[u8]) -> Result<usize> {
+        Ok(match index.get(index_key)? {
+            Some(bytes) => Self::deserialize::<Vec<Vec<u8>>>(&bytes)?.len(),
+            None => 0,
+        })
+    }
+
+    fn index_contains(index: &sled::Tree, index_key: &[u8], id: &[u8]) -> Result<bool> {
+        Ok(match index.get(index_key)? {
+            Some(bytes) => Self::deserialize::<Vec<Vec<u8>>>(&bytes)
+                .map(|ids| ids.iter().any(|existing| existing == id))
+                .unwrap_or(false),
+            None => false,
+        })
+    }
+
+    /// Move a record into the quarantine tree when fixing
+    fn quarantine_if(
+        &self,
+        fix: bool,
+        tree: &sled::Tree,
+        tree_name: &str,
+        key: &[u8],
+        bytes: &[u8],
+    ) -> Result<()> {
+        if fix {
+            let mut quarantine_key = format!("{}/", tree_name).into_bytes();
+            quarantine_key.extend_from_slice(key);
+            self.open_tree(QUARANTINE_TREE)?.insert(quarantine_key, bytes)?;
+            tree.remove(key)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the whole database (every namespace) into a named snapshot
+    pub fn create_snapshot(&self, dir: &Path, name: &str) -> Result<SnapshotInfo> {
+        self.maintain(MaintenanceKind::Backup, |info: &SnapshotInfo| info.entries, || {
+            snapshot::validate_name(name)?;
+            let path = snapshot::snapshot_db_path(dir, name);
+            if path.exists() {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Snapshot {:?} already exists",
+                    name
+                )));
+            }
+
+            let target = sled::open(&path)?;
+            let (trees, entries) = snapshot::copy_db(&self.db, &target)?;
+            let info = SnapshotInfo {
+                name: name.to_string(),
+                created_at: chrono::Utc::now(),
+                trees,
+                entries,
+            };
+            snapshot::write_manifest(dir, &info)?;
+            Ok(info)
+        })
+    }
+
+    /// Replace the whole database with a named snapshot
+    ///
+    /// The live database is first saved as a `pre-restore-<time>` snapshot
+    /// in the same directory. Restoring clears the live trees before
+    /// copying, so if the copy fails the error names that snapshot to
+    /// restore instead.
+    pub fn restore_snapshot(&self, dir: &Path, name: &str) -> Result<RestoreReport> {
+        self.ensure_writable()?;
+        snapshot::validate_name(name)?;
+        let restored = snapshot::read_manifest(dir, name)?;
+        let source = sled::open(snapshot::snapshot_db_path(dir, name))?;
+        let previous = self.create_snapshot(dir, &snapshot::pre_restore_name(chrono::Utc::now()))?;
+        if let Err(e) = snapshot::copy_db(&source, &self.db) {
+            return Err(StoreError::InvalidOperation(format!(
+                "Restoring {:?} failed ({}); the database before it is snapshot {:?}",
+                name, e, previous.name
+            )));
+        }
+        Ok(RestoreReport { restored, previous })
+    }
+
+    fn nodes_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(NODES_TREE)
+    }
+
+    fn edges_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(EDGES_TREE)
+    }
+
+    fn events_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(EVENTS_TREE)
+    }
+
+    fn nodes_by_kind_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(NODES_BY_KIND_TREE)
+    }
+
+    fn edges_by_from_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(EDGES_BY_FROM_TREE)
+    }
+
+    fn edges_by_to_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(EDGES_BY_TO_TREE)
+    }
+
+    fn nodes_by_expiry_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(NODES_BY_EXPIRY_TREE)
+    }
+
+    fn nodes_by_hash_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(NODES_BY_HASH_TREE)
+    }
+
+    fn metadata_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(METADATA_TREE)
+    }
+
+    fn annotations_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(ANNOTATIONS_TREE)
+    }
+
+    fn archive_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(ARCHIVE_TREE)
+    }
+
+    fn reactions_tree(&self) -> Result<sled::Tree> {
+        self.open_tree(REACTIONS_TREE)
+    }
+
+    /// Reaction key: node id + kind tag + agent, so re-reacting overwrites
+    fn reaction_key(node_id: NodeId, kind: ReactionKind, agent: &AgentId) -> Vec<u8> {
+        let mut key = node_id.to_bytes().to_vec();
+        key.push(kind.tag());
+        key.extend_from_slice(agent.to_string().as_bytes());
+        key
+    }
+
+    /// Annotation key: node id + annotation id, so a node's annotations are
+    /// one prefix scan in creation order
+    fn annotation_key(node_id: NodeId, id: AnnotationId) -> Vec<u8> {
+        let mut key = node_id.to_bytes().to_vec();
+        key.extend_from_slice(&id.to_bytes());
+        key
+    }
+
+    /// Read a value from the store-level metadata tree
+    pub fn get_meta(&self, key: &str) -> Result<Option<Value>> {
+        self.metadata_tree()?
+            .get(key.as_bytes())?
+            .map(|bytes| {
+                serde_json::from_slice(&bytes).map_err(|e| StoreError::Serialization(e.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Write a value to the store-level metadata tree
+    pub fn set_meta(&self, key: &str, value: &Value) -> Result<()> {
+        self.ensure_writable()?;
+        let bytes =
+            serde_json::to_vec(value).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.register_namespace()?;
+        self.metadata_tree()?.insert(key.as_bytes(), bytes)?;
+        Ok(())
+    }
+
+    /// How many events from each instance this store has recorded or applied
+    pub fn vector_clock(&self) -> Result<VectorClock> {
+        match self.get_meta(VECTOR_CLOCK_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(VectorClock::default()),
+        }
+    }
+
+    /// The ID other instances know this store by, created on first use
+    pub fn instance_id(&self) -> Result<String> {
+        if let Some(Value::String(id)) = self.get_meta(INSTANCE_ID_KEY)? {
+            return Ok(id);
+        }
+        let id = ulid::Ulid::new().to_string();
+        self.set_meta(INSTANCE_ID_KEY, &Value::String(id.clone()))?;
+        Ok(id)
+    }
+
+    fn remap_key(source: &str, remote: Option<NodeId>) -> Vec<u8> {
+        let mut key = source.as_bytes().to_vec();
+        key.push(0);
+        if let Some(remote) = remote {
+            key.extend_from_slice(&remote.to_bytes());
+        }
+        key
+    }
+
+    fn remap_id(bytes: &[u8]) -> Result<NodeId> {
+        let bytes: [u8; 16] = bytes
+            .try_into()
+            .map_err(|_| StoreError::Serialization("malformed remap entry".into()))?;
+        Ok(NodeId::from_bytes(bytes))
+    }
+
+    /// The local node an earlier import from `source` made of `remote`
+    pub fn remapped(&self, source: &str, remote: NodeId) -> Result<Option<NodeId>> {
+        self.open_tree(IMPORT_REMAP_TREE)?
+            .get(Self::remap_key(source, Some(remote)))?
+            .map(|bytes| Self::remap_id(&bytes))
+            .transpose()
+    }
+
+    pub fn record_remap(&self, source: &str, remote: NodeId, local: NodeId) -> Result<()> {
+        self.ensure_writable()?;
+        self.open_tree(IMPORT_REMAP_TREE)?
+            .insert(Self::remap_key(source, Some(remote)), &local.to_bytes())?;
+        Ok(())
+    }
+
+    /// Every `(remote, local)` pair recorded for `source`
+    pub fn remaps(&self, source: &str) -> Result<Vec<(NodeId, NodeId)>> {
+        let prefix = Self::remap_key(source, None);
+        self.open_tree(IMPORT_REMAP_TREE)?
+            .scan_prefix(&prefix)
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((Self::remap_id(&key[prefix.len()..])?, Self::remap_id(&value)?))
+            })
+            .collect()
+    }
+
+    /// Forget `source`'s remap table, so its next import creates fresh nodes
+    pub fn clear_remaps(&self, source: &str) -> Result<usize> {
+        self.ensure_writable()?;
+        let tree = self.open_tree(IMPORT_REMAP_TREE)?;
+        let mut removed = 0;
+        for key in tree.scan_prefix(Self::remap_key(source, None)).keys() {
+            tree.remove(key?)?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// How node events record their payloads
+    pub fn capture_policy(&self) -> Result<CapturePolicy> {
+        match self.get_meta(CAPTURE_POLICY_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(CapturePolicy::default()),
+        }
+    }
+
+    pub fn set_capture_policy(&self, policy: &CapturePolicy) -> Result<()> {
+        let value =
+            serde_json::to_value(policy).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(CAPTURE_POLICY_KEY, &value)
+    }
+
+    /// Retention classes per kind and what each class means
+    pub fn retention_policy(&self) -> Result<RetentionPolicy> {
+        match self.get_meta(RETENTION_POLICY_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(RetentionPolicy::default()),
+        }
+    }
+
+    pub fn set_retention_policy(&self, policy: &RetentionPolicy) -> Result<()> {
+        let value =
+            serde_json::to_value(policy).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(RETENTION_POLICY_KEY, &value)
+    }
+
+    /// Metadata stamped on the nodes each agent creates
+    pub fn agent_defaults(&self) -> Result<AgentDefaults> {
+        match self.get_meta(AGENT_DEFAULTS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(AgentDefaults::default()),
+        }
+    }
+
+    pub fn set_agent_defaults(&self, defaults: &AgentDefaults) -> Result<()> {
+        defaults.validate()?;
+        let value =
+            serde_json::to_value(defaults).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(AGENT_DEFAULTS_KEY, &value)
+    }
+
+    pub fn kind_registry(&self) -> Result<KindRegistry> {
+        match self.get_meta(KIND_REGISTRY_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(KindRegistry::default()),
+        }
+    }
+
+    pub fn set_kind_registry(&self, registry: &KindRegistry) -> Result<()> {
+        registry.validate()?;
+        let value =
+            serde_json::to_value(registry).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(KIND_REGISTRY_KEY, &value)
+    }
+
+    pub fn content_schemas(&self) -> Result<ContentSchemas> {
+        match self.get_meta(CONTENT_SCHEMAS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(ContentSchemas::default()),
+        }
+    }
+
+    pub fn set_content_schemas(&self, schemas: &ContentSchemas) -> Result<()> {
+        schemas.validate()?;
+        let value =
+            serde_json::to_value(schemas).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(CONTENT_SCHEMAS_KEY, &value)
+    }
+
+    pub fn graph_constraints(&self) -> Result<GraphConstraints> {
+        match self.get_meta(GRAPH_CONSTRAINTS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(GraphConstraints::default()),
+        }
+    }
+
+    pub fn set_graph_constraints(&self, constraints: &GraphConstraints) -> Result<()> {
+        constraints.validate()?;
+        let value =
+            serde_json::to_value(constraints).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(GRAPH_CONSTRAINTS_KEY, &value)
+    }
+
+    /// Which parts of each kind's content search reads
+    pub fn text_extraction(&self) -> Result<TextExtraction> {
+        match self.get_meta(TEXT_EXTRACTION_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(TextExtraction::default()),
+        }
+    }
+
+    /// Replace the text rules, checking them against the content schemas
+    pub fn set_text_extraction(&self, extraction: &TextExtraction) -> Result<()> {
+        extraction.validate(&self.content_schemas()?)?;
+        let value =
+            serde_json::to_value(extraction).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(TEXT_EXTRACTION_KEY, &value)
+    }
+
+    /// Saved node templates, by name
+    pub fn templates(&self) -> Result<BTreeMap<String, NodeTemplate>> {
+        match self.get_meta(TEMPLATES_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Save a template, replacing any of the same name
+    pub fn save_template(&self, name: &str, template: &NodeTemplate) -> Result<()> {
+        templates::validate_name(name)?;
+        let mut saved = self.templates()?;
+        saved.insert(name.to_string(), template.clone());
+        let value =
+            serde_json::to_value(&saved).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(TEMPLATES_KEY, &value)
+    }
+
+    /// Returns whether a template of that name existed
+    pub fn delete_template(&self, name: &str) -> Result<bool> {
+        let mut saved = self.templates()?;
+        if saved.remove(name).is_none() {
+            return Ok(false);
+        }
+        let value =
+            serde_json::to_value(&saved).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(TEMPLATES_KEY, &value)?;
+        Ok(true)
+    }
+
+    /// Embedding of `text`, from the cache when this model has embedded
+    /// the same text before
+    ///
+    /// Misses are computed and cached; a read-only store computes them
+    /// without caching or counting.
+    pub fn embedding(&self, embedder: &dyn Embedder, text: &str) -> Result<Vec<f32>> {
+        let _timer = self.metrics.start("embedding");
+        let model = embedder.model();
+        let key = embeddings::cache_key(model, text);
+        let tree = self.open_tree(EMBEDDINGS_TREE)?;
+        if let Some(bytes) = tree.get(&key)? {
+            let cached: CachedEmbedding = Self::deserialize(&bytes)?;
+            self.count_embedding("hits", model)?;
+            return Ok(cached.vector);
+        }
+
+        let vector = embedder.embed(text)?;
+        if self.read_only {
+            return Ok(vector);
+        }
+        let cached = CachedEmbedding { vector, created_at: chrono::Utc::now() };
+        tree.insert(key, Self::serialize(&cached)?)?;
+        self.count_embedding("misses", model)?;
+        Ok(cached.vector)
+    }
+
+    /// A cached embedding, without computing or counting anything
+    pub fn cached_embedding(&self, model: &str, text: &str) -> Result<Option<Vec<f32>>> {
+        self.open_tree(EMBEDDINGS_TREE)?
+            .get(embeddings::cache_key(model, text))?
+            .map(|bytes| Self::deserialize::<CachedEmbedding>(&bytes).map(|cached| cached.vector))
+            .transpose()
+    }
+
+    fn count_embedding(&self, counter: &str, model: &str) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let key = format!("{}/{}", counter, model);
+        self.open_tree(EMBEDDING_COUNTERS_TREE)?.update_and_fetch(key.as_bytes(), |old| {
+            let count = old.and_then(|bytes| <[u8; 8]>::try_from(bytes).ok()).map_or(0, u64::from_be_bytes);
+            Some((count + 1).to_be_bytes().to_vec())
+        })?;
+        Ok(())
+    }
+
+    /// Cached entries, their size, and hits and misses, per model
+    pub fn embedding_stats(&self) -> Result<EmbeddingStats> {
+        let mut stats = EmbeddingStats::default();
+        for entry in self.open_tree(EMBEDDINGS_TREE)?.iter() {
+            let (key, value) = entry?;
+            let model = stats.models.entry(embeddings::key_model(&key)).or_default();
+            model.entries += 1;
+            model.bytes += value.len() as u64;
+        }
+        for entry in self.open_tree(EMBEDDING_COUNTERS_TREE)?.iter() {
+            let (key, value) = entry?;
+            let key = String::from_utf8_lossy(&key);
+            let Some((counter, model)) = key.split_once('/') else { continue };
+            let count = <[u8; 8]>::try_from(value.as_ref()).map_or(0, u64::from_be_bytes);
+            let model = stats.models.entry(model.to_string()).or_default();
+            match counter {
+                "hits" => model.hits = count,
+                _ => model.misses = count,
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Drop cached embeddings and their counters, of one model or all;
+    /// returns how many vectors were dropped
+    pub fn purge_embeddings(&self, model: Option<&str>) -> Result<usize> {
+        self.ensure_writable()?;
+        let tree = self.open_tree(EMBEDDINGS_TREE)?;
+        let counters = self.open_tree(EMBEDDING_COUNTERS_TREE)?;
+        let Some(model) = model else {
+            let purged = tree.len();
+            tree.clear()?;
+            counters.clear()?;
+            return Ok(purged);
+        };
+
+        let mut prefix = model.as_bytes().to_vec();
+        prefix.push(0);
+        let mut purged = 0;
+        for key in tree.scan_prefix(&prefix).keys() {
+            tree.remove(key?)?;
+            purged += 1;
+        }
+        for counter in ["hits", "misses"] {
+            counters.remove(format!("{}/{}", counter, model).as_bytes())?;
+        }
+        Ok(purged)
+    }
+
+    /// Create a node and its edges from a saved template
+    ///
+    /// Every edge end is checked before the node is written, so a bad
+    /// variable leaves nothing behind.
+    pub fn apply_template(
+        &self,
+        name: &str,
+        variables: &BTreeMap<String, Value>,
+        agent: AgentId,
+    ) -> Result<(StateNode, Vec<StateEdge>)> {
+        self.ensure_writable()?;
+        let template = self
+            .templates()?
+            .remove(name)
+            .ok_or_else(|| StoreError::InvalidOperation(format!("No template named {}", name)))?;
+        let (node, edges) = template.instantiate(variables)?;
+        for edge in &edges {
+            let other = if edge.from == node.id { edge.to } else { edge.from };
+            if self.get_node(other)?.is_none() {
+                return Err(StoreError::NodeNotFound(other));
+            }
+        }
+
+        let node = self.create_node(node, agent.clone())?;
+        let edges = edges
+            .into_iter()
+            .map(|edge| self.create_edge(edge, agent.clone()))
+            .collect::<Result<_>>()?;
+        Ok((node, edges))
+    }
+
+    /// Edge kinds in which `create_edge` refuses to close a cycle
+    pub fn acyclic_kinds(&self) -> Result<Vec<EdgeKind>> {
+        match self.get_meta(ACYCLIC_KINDS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Existing cycles are left alone; `cycles::find_cycles` reports them
+    pub fn set_acyclic_kinds(&self, kinds: &[EdgeKind]) -> Result<()> {
+        Self::check_kind_overlap(kinds, &self.symmetric_kinds()?)?;
+        let value =
+            serde_json::to_value(kinds).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(ACYCLIC_KINDS_KEY, &value)
+    }
+
+    /// Edge kinds that `edges_from`, `edges_to` and traversals follow in
+    /// both directions; `RelatedTo` until set otherwise
+    pub fn symmetric_kinds(&self) -> Result<Vec<EdgeKind>> {
+        match self.get_meta(SYMMETRIC_KINDS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(vec![EdgeKind::RelatedTo]),
+        }
+    }
+
+    pub fn set_symmetric_kinds(&self, kinds: &[EdgeKind]) -> Result<()> {
+        Self::check_kind_overlap(kinds, &self.acyclic_kinds()?)?;
+        Self::check_not_reciprocal(kinds, &self.reciprocal_kinds()?)?;
+        let value =
+            serde_json::to_value(kinds).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(SYMMETRIC_KINDS_KEY, &value)
+    }
+
+    /// Pairs of edge kinds whose edges come in reversed twins: creating an
+    /// edge of either kind creates one of the other the opposite way, and
+    /// updating or deleting either does the same to its twin
+    pub fn reciprocal_kinds(&self) -> Result<Vec<(EdgeKind, EdgeKind)>> {
+        match self.get_meta(RECIPROCAL_KINDS_KEY)? {
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Existing edges keep their twins, or lack of them, until
+    /// `backfill_reciprocals`
+    pub fn set_reciprocal_kinds(&self, pairs: &[(EdgeKind, EdgeKind)]) -> Result<()> {
+        let mut seen = HashSet::new();
+        for (kind, reciprocal) in pairs {
+            let kinds = if kind == reciprocal { vec![kind] } else { vec![kind, reciprocal] };
+            if let Some(kind) = kinds.into_iter().find(|k| !seen.insert(*k)) {
+                return Err(StoreError::InvalidOperation(format!("Edge kind {} has more than one reciprocal", kind)));
+            }
+        }
+        Self::check_not_reciprocal(&self.symmetric_kinds()?, pairs)?;
+        let value =
+            serde_json::to_value(pairs).map_err(|e| StoreError::Serialization(e.to_string()))?;
+        self.set_meta(RECIPROCAL_KINDS_KEY, &value)
+    }
+
+    /// A symmetric edge already reads both ways; a twin would list it twice
+    fn check_not_reciprocal(symmetric: &[EdgeKind], pairs: &[(EdgeKind, EdgeKind)]) -> Result<()> {
+        match pairs.iter().flat_map(|(a, b)| [a, b]).find(|k| symmetric.contains(k)) {
+            Some(kind) => Err(StoreError::InvalidOperation(format!(
+                "Edge kind {} can't be both symmetric and reciprocal",
+                kind
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// A symmetric edge is a two-node cycle, so no kind can be both
+    fn check_kind_overlap(kinds: &[EdgeKind], others: &[EdgeKind]) -> Result<()> {
+        match kinds.iter().find(|k| others.contains(k)) {
+            Some(kind) => Err(StoreError::InvalidOperation(format!(
+                "Edge kind {} can't be both symmetric and acyclic",
+                kind
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Build a node event, capturing snapshots per the kind's policy
+    fn node_event(
+        &self,
+        agent: AgentId,
+        operation: Operation,
+        node: &StateNode,
+        before: Option<&StateNode>,
+        after: Option<&StateNode>,
+    ) -> Result<StateEvent> {
+        let mode = match self.retention_policy()?.rules_for(node).capture {
+            Some(mode) => mode,
+            None => self.capture_policy()?.mode_for(&node.kind),
+        };
+        let snapshot = |n: &StateNode| serde_json::to_value(n).unwrap();
+        Ok(StateEvent::new(agent, operation, Target::Node(node.id)).with_snapshots(
+            mode,
+            before.map(snapshot),
+            after.map(snapshot),
+        ))
+    }
+
+    /// Create a read-only share link for the subgraph around `root`
+    ///
+    /// Returns the link and the signed token to hand out.
+    pub fn create_share(
+        &self,
+        root: NodeId,
+        depth: usize,
+        ttl: chrono::Duration,
+        agent: AgentId,
+    ) -> Result<(ShareLink, String)> {
+        self.ensure_writable()?;
+        if self.get_node(root)?.is_none() {
+            return Err(StoreError::NodeNotFound(root));
+        }
+
+        let now = chrono::Utc::now();
+        let link = ShareLink {
+            id: ulid::Ulid::new(),
+            root,
+            depth,
+            created_by: agent.clone(),
+            created_at: now,
+            expires_at: now + ttl,
+            revoked_at: None,
+        };
+        self.open_tree(SHARES_TREE)?
+            .insert(link.id.to_bytes(), Self::serialize(&link)?)?;
+        self.audit_share(link.id, ShareAction::Created, Some(agent.to_string()))?;
+
+        let token = share::sign(&self.share_secret()?, link.id, link.expires_at);
+        Ok((link, token))
+    }
+
+    pub fn get_share(&self, id: ShareId) -> Result<Option<ShareLink>> {
+        self.open_tree(SHARES_TREE)?
+            .get(id.to_bytes())?
+            .map(|bytes| Self::deserialize(&bytes))
+            .transpose()
+    }
+
+    /// All share links, oldest first
+    pub fn list_shares(&self) -> Result<Vec<ShareLink>> {
+        self.open_tree(SHARES_TREE)?
+            .iter()
+            .map(|entry| Self::deserialize(&entry?.1))
+            .collect()
+    }
+
+    /// Revoke a share link; its token stops working immediately
+    pub fn revoke_share(&self, id: ShareId, agent: AgentId) -> Result<ShareLink> {
+        self.ensure_writable()?;
+        let mut link = self.get_share(id)?.ok_or(StoreError::ShareNotFound(id))?;
+        if link.revoked_at.is_none() {
+            link.revoked_at = Some(chrono::Utc::now());
+            self.open_tree(SHARES_TREE)?
+                .insert(id.to_bytes(), Self::serialize(&link)?)?;
+            self.audit_share(id, ShareAction::Revoked, Some(agent.to_string()))?;
+        }
+        Ok(link)
+    }
+
+    /// Resolve a token to the subgraph it grants access to
+    ///
+    /// Accesses and denials are audited unless the store is read-only.
+    pub fn open_share(&self, token: &str) -> Result<SharedGraph> {
+        let id = share::verify(&self.share_secret()?, token)?;
+        let link = self.get_share(id)?.ok_or(StoreError::ShareNotFound(id))?;
+
+        let now = chrono::Utc::now();
+        if !link.is_active(now) {
+            let reason = if link.revoked_at.is_some() { "revoked" } else { "expired" };
+            self.audit_share(id, ShareAction::Denied, Some(reason.to_string()))?;
+            return Err(StoreError::ShareDenied(format!("link {}", reason)));
+        }
+
+        let Subgraph { nodes, edges, .. } = match self.subgraph(link.root, link.depth, None) {
+            Ok(graph) => graph,
+            Err(StoreError::NodeNotFound(_)) => Subgraph { root: link.root, nodes: Vec::new(), edges: Vec::new() },
+            Err(e) => return Err(e),
+        };
+
+        self.audit_share(id, ShareAction::Accessed, None)?;
+        Ok(SharedGraph { share: link, nodes, edges })
+    }
+
+    /// Audit log, optionally for one link, oldest first
+    pub fn share_audit(&self, id: Option<ShareId>) -> Result<Vec<ShareAuditEntry>> {
+        let mut entries = Vec::new();
+        for entry in self.open_tree(SHARE_AUDIT_TREE)?.iter() {
+            let entry: ShareAuditEntry = Self::deserialize(&entry?.1)?;
+            if id.map_or(true, |id| entry.share == id) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    fn audit_share(&self, share: ShareId, action: ShareAction, detail: Option<String>) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let entry = ShareAuditEntry::new(share, action, detail);
+        // Monotonic key keeps entries in order within the same millisecond
+        self.open_tree(SHARE_AUDIT_TREE)?
+            .insert(self.db.generate_id()?.to_be_bytes(), Self::serialize(&entry)?)?;
+        Ok(())
+    }
+
+    /// Signing secret for share tokens, created on first use
+    fn share_secret(&self) -> Result<String> {
+        if let Some(Value::String(secret)) = self.get_meta(share::SECRET_KEY)? {
+            return Ok(secret);
+        }
+        let secret = share::generate_secret();
+        self.set_meta(share::SECRET_KEY, &Value::String(secret.clone()))?;
+        Ok(secret)
+    }
+
+    /// Expiry and update-time index key: big-endian millis (so keys sort
+    /// by time) + node id
+    fn time_key(expires_at: chrono::DateTime<chrono::Utc>, id: NodeId) -> Vec<u8> {
+        let mut key = (expires_at.timestamp_millis().max(0) as u64)
+            .to_be_bytes()
+            .to_vec();
+        key.extend_from_slice(&id.to_bytes());
+        key
+    }
+
+    fn expiry_entries(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(sled::IVec, NodeId)>> {
+        let expiry = self.nodes_by_expiry_tree()?;
+        let upper = (now.timestamp_millis().max(0) as u64 + 1).to_be_bytes();
+        let retention = self.retention_policy()?;
+
+        let mut entries = Vec::new();
+        for entry in expiry.range(..upper) {
+            let (key, _) = entry?;
+            let id_bytes: [u8; 16] = key[8..]
+                .try_into()
+                .map_err(|_| StoreError::Serialization("malformed expiry key".into()))?;
+            let id = NodeId::from_bytes(id_bytes);
+            // Permanent nodes outlive their expiry
+            if self.get_node(id)?.is_some_and(|node| !retention.expires(&node)) {
+                continue;
+            }
+            entries.push((key, id));
+        }
+        Ok(entries)
+    }
+
+    /// IDs of nodes whose expiry time is at or before `now`, except
+    /// permanent ones
+    pub fn expired_nodes(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<NodeId>> {
+        Ok(self.expiry_entries(now)?.into_iter().map(|(_, id)| id).collect())
+    }
+
+    /// Delete every expired node (and its edges) as `AgentId::System`
+    pub fn purge_expired(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<NodeId>> {
+        self.ensure_writable()?;
+        let expired = self.expiry_entries(now)?;
+        // The sweeper calls this constantly; only sweeps that find something are logged
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.maintain(MaintenanceKind::Gc, |purged: &Vec<NodeId>| purged.len() as u64, || {
+            let mut purged = Vec::new();
+            for (key, id) in expired {
+                match self.delete_node(id, AgentId::System) {
+                    Ok(()) => purged.push(id),
+                    // Stale index entry for a node deleted by other means
+                    Err(StoreError::NodeNotFound(_)) => {
+                        self.nodes_by_expiry_tree()?.remove(key)?;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(purged)
+        })
+    }
+
+    /// Node counts per kind, read from the kind index
+    pub fn count_nodes_by_kind(&self) -> Result<Vec<(String, usize)>> {
+        self.nodes_by_kind_tree()?
+            .iter()
+            .map(|entry| {
+                let (kind, ids) = entry?;
+                let ids: Vec<Vec<u8>> = Self::deserialize(&ids)?;
+                Ok((String::from_utf8_lossy(&kind).into_owned(), ids.len()))
+            })
+            .collect()
+    }
+
+    /// Node, edge and event counts by kind, agent and day
+    ///
+    /// Counters are maintained on every write, so this doesn't scan. They
+    /// are recounted when first read and after repairs, dump loads and
+    /// migrations; a read-only store recounts without saving.
+    pub fn graph_stats(&self) -> Result<GraphStats> {
+        let _timer = self.metrics.start("graph_stats");
+        let tree = self.open_tree(STATS_TREE)?;
+        if tree.contains_key(stats::VALID_KEY)? {
+            return stats::read(&tree);
+        }
+        let counted = self.count_stats()?;
+        if !self.read_only {
+            stats::write(&tree, &counted)?;
+        }
+        Ok(counted)
+    }
+
+    /// Replace the hot graph with `source`'s nodes and edges, rebuilding
+    /// every node and edge index from them
+    ///
+    /// The event log and everything outside the graph are left alone.
+    /// Nodes archived here stay archived rather than coming back hot.
+    /// Returns the nodes and edges written.
+    pub fn replace_graph(&self, source: &SledStore) -> Result<(usize, usize)> {
+        let _timer = self.metrics.start("replace_graph");
+        self.maintain(MaintenanceKind::Repair, |&(nodes, edges): &(usize, usize)| (nodes + edges) as u64, || {
+            self.ensure_writable()?;
+            for tree in [
+                NODES_TREE,
+                NODES_BY_KIND_TREE,
+                NODES_BY_EXPIRY_TREE,
+                NODES_BY_UPDATED_TREE,
+                NODES_BY_HASH_TREE,
+                NODES_BY_TAG_TREE,
+                PINNED_NODES_TREE,
+                META_INDEX_TREE,
+                EDGES_TREE,
+                EDGES_BY_FROM_TREE,
+                EDGES_BY_TO_TREE,
+            ] {
+                self.open_tree(tree)?.clear()?;
+            }
+            let archive = self.archive_tree()?;
+            let mut nodes = 0;
+            for node in source.list_nodes(None, usize::MAX)? {
+                if !archive.contains_key(node.id.to_bytes())? {
+                    self.write_node(&node)?;
+                    nodes += 1;
+                }
+            }
+            let edges = source.list_edges()?;
+            for edge in &edges {
+                self.write_edge(edge)?;
+            }
+            stats::write(&self.open_tree(STATS_TREE)?, &self.count_stats()?)?;
+            Ok((nodes, edges.len()))
+        })
+    }
+
+    /// Recount the stats counters from the data
+    pub fn rebuild_stats(&self) -> Result<GraphStats> {
+        self.maintain(MaintenanceKind::Reindex, |counted: &GraphStats| counted.nodes() + counted.edges(), || {
+            self.ensure_writable()?;
+            let counted = self.count_stats()?;
+            stats::write(&self.open_tree(STATS_TREE)?, &counted)?;
+            Ok(counted)
+        })
+    }
+
+    /// Scan nodes, edges and events; records that don't decode are skipped
+    /// unless the store is strict
+    fn count_stats(&self) -> Result<GraphStats> {
+        let mut counted = GraphStats::default();
+        for node in self.decoded::<StateNode>(NODES_TREE, self.nodes_tree()?.iter()) {
+            counted.count(Counter::NodeKind(&node?.kind));
+        }
+        for edge in self.decoded::<StateEdge>(EDGES_TREE, self.edges_tree()?.iter()) {
+            counted.count(Counter::EdgeKind(&edge?.kind));
+        }
+        for event in self.decoded::<StateEvent>(EVENTS_TREE, self.events_tree()?.iter()) {
+            let event = event?;
+            counted.count(Counter::Agent(&event.agent));
+            counted.count(Counter::Day(event.timestamp));
+        }
+        Ok(counted)
+    }
+
+    /// Have the next `graph_stats` recount, after writes that bypassed the counters
+    fn invalidate_stats(&self) -> Result<()> {
+        self.open_tree(STATS_TREE)?.remove(stats::VALID_KEY)?;
+        Ok(())
+    }
+
+    fn bump_stats(&self, counters: &[Counter<'_>], delta: i64) -> Result<()> {
+        stats::bump(&self.open_tree(STATS_TREE)?, counters, delta)
+    }
+
+    /// Attach bytes to a node, storing them once per distinct content
+    pub fn put_attachment(&self, node_id: NodeId, bytes: &[u8], mime: &str) -> Result<Attachment> {
+        self.ensure_writable()?;
+        if self.get_node(node_id)?.is_none() {
+            return Err(StoreError::NodeNotFound(node_id));
+        }
+
+        let hash = attachment::content_hash(bytes);
+        let blobs = self.open_tree(BLOBS_TREE)?;
+        if !blobs.contains_key(hash.as_bytes())? {
+            blobs.insert(hash.as_bytes(), bytes)?;
+        }
+
+        let attachment = Attachment {
+            node_id,
+            hash,
+            mime: mime.to_string(),
+            size: bytes.len() as u64,
+            created_at: chrono::Utc::now(),
+        };
+        self.open_tree(ATTACHMENTS_TREE)?.insert(
+            attachment::attachment_key(node_id, &attachment.hash),
+            Self::serialize(&attachment)?,
+        )?;
+        Ok(attachment)
+    }
+
+    /// Attachments on a node, oldest first
+    pub fn attachments(&self, node_id: NodeId) -> Result<Vec<Attachment>> {
+        let mut attachments: Vec<Attachment> = self
+            .open_tree(ATTACHMENTS_TREE)?
+            .scan_prefix(node_id.to_bytes())
+            .map(|entry| Self::deserialize(&entry?.1))
+            .collect::<Result<_>>()?;
+        attachments.sort_by_key(|a| a.created_at);
+        Ok(attachments)
+    }
+
+    /// An attachment's metadata and bytes
+    pub fn get_attachment(&self, node_id: NodeId, hash: &str) -> Result<Option<(Attachment, Vec<u8>)>> {
+        let Some(meta) = self
+            .open_tree(ATTACHMENTS_TREE)?
+            .get(attachment::attachment_key(node_id, hash))?
+        else {
+            return Ok(None);
+        };
+        let attachment: Attachment = Self::deserialize(&meta)?;
+        let bytes = self
+            .open_tree(BLOBS_TREE)?
+            .get(hash.as_bytes())?
+            .ok_or_else(|| StoreError::AttachmentNotFound(hash.to_string()))?;
+        Ok(Some((attachment, bytes.to_vec())))
+    }
+
+    /// Detach from a node, dropping the bytes if nothing else refers to them
+    pub fn remove_attachment(&self, node_id: NodeId, hash: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let attachments = self.open_tree(ATTACHMENTS_TREE)?;
+        if attachments.remove(attachment::attachment_key(node_id, hash))?.is_none() {
+            return Err(StoreError::AttachmentNotFound(hash.to_string()));
+        }
+        self.drop_unreferenced_blob(hash)
+    }
+
+    fn drop_unreferenced_blob(&self, hash: &str) -> Result<()> {
+        for entry in self.open_tree(ATTACHMENTS_TREE)?.iter() {
+            let (key, _) = entry?;
+            if key.ends_with(hash.as_bytes()) {
+                return Ok(());
+            }
+        }
+        self.open_tree(BLOBS_TREE)?.remove(hash.as_bytes())?;
+        Ok(())
+    }
+
+    /// Declare an index on a metadata field and build it from existing nodes
+    ///
+    /// Returns the number of entries written. The index is then maintained
+    /// on every write.
+    pub fn create_metadata_index(&self, field: &str) -> Result<usize> {
+        self.ensure_writable()?;
+        let field = indices::field_name(field);
+        if field.is_empty() {
+            return Err(StoreError::InvalidOperation("Empty metadata field".into()));
+        }
+        self.open_tree(META_INDEX_FIELDS_TREE)?
+            .insert(field.as_bytes(), Vec::<u8>::new())?;
+
+        let index = self.open_tree(META_INDEX_TREE)?;
+        let mut entries = 0;
+        for entry in self.nodes_tree()?.iter() {
+            let node: StateNode = Self::deserialize(&entry?.1)?;
+            for value in indices::field_values(&node, field) {
+                index.insert(indices::entry_key(field, &value, node.id), Vec::<u8>::new())?;
+                entries += 1;
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Remove a metadata index; returns false if it did not exist
+    pub fn drop_metadata_index(&self, field: &str) -> Result<bool> {
+        self.ensure_writable()?;
+        let field = indices::field_name(field);
+        if self.open_tree(META_INDEX_FIELDS_TREE)?.remove(field.as_bytes())?.is_none() {
+            return Ok(false);
+        }
+        let index = self.open_tree(META_INDEX_TREE)?;
+        let mut prefix = field.as_bytes().to_vec();
+        prefix.push(0);
+        for entry in index.scan_prefix(&prefix) {
+            index.remove(entry?.0)?;
+        }
+        Ok(true)
+    }
+
+    /// Fields with a metadata index
+    pub fn metadata_indexes(&self) -> Result<Vec<String>> {
+        self.open_tree(META_INDEX_FIELDS_TREE)?
+            .iter()
+            .map(|entry| Ok(String::from_utf8_lossy(&entry?.0).into_owned()))
+            .collect()
+    }
+
+    /// Nodes whose metadata field matches, using the index when one exists
+    /// and scanning every node otherwise; strict stores refuse to scan
+    pub fn find_by_metadata(
+        &self,
+        field: &str,
+        query: &MetaQuery,
+        kinds: Option<Vec<NodeKind>>,
+    ) -> Result<Vec<StateNode>> {
+        let field = indices::field_name(field);
+        let kind_matches = |node: &StateNode| kinds.as_ref().map_or(true, |ks| ks.contains(&node.kind));
+
+        if !self.open_tree(META_INDEX_FIELDS_TREE)?.contains_key(field.as_bytes())? {
+            self.degrade(|| {
+                format!("no index on metadata field '{}'; create one with `index create {}`", field, field)
+            })?;
+            return self
+                .decoded::<StateNode>(NODES_TREE, self.nodes_tree()?.iter())
+                .filter(|node| node.as_ref().map_or(true, &kind_matches))
+                .filter(|node| {
+                    node.as_ref()
+                        .map_or(true, |node| indices::field_values(node, field).iter().any(|v| query.matches(v)))
+                })
+                .collect();
+        }
+
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+        for entry in self.open_tree(META_INDEX_TREE)?.scan_prefix(indices::scan_prefix(field, query)) {
+            let id = indices::entry_node(&entry?.0)?;
+            if seen.insert(id) {
+                if let Some(node) = self.get_node(id)?.filter(|n| kind_matches(n)) {
+                    result.push(node);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Rewrite a stored node through `change`, retrying if another writer
+    /// gets in first
+    ///
+    /// `change` edits a copy of the current node and returns whether to
+    /// write it; a written node gets a new version and update time. Returns
+    /// the node before and after, both the stored node if nothing was
+    /// written. Indexes and events are left to the caller.
+    fn update_in_place(
+        &self,
+        id: NodeId,
+        mut change: impl FnMut(&mut StateNode) -> Result<bool>,
+    ) -> Result<(StateNode, StateNode)> {
+        let nodes = self.nodes_tree()?;
+        let key = id.to_bytes();
+        loop {
+            let old_bytes = nodes.get(key)?.ok_or(StoreError::NodeNotFound(id))?;
+            let old_node: StateNode = Self::deserialize(&old_bytes)?;
+            let mut new_node = old_node.clone();
+            if !change(&mut new_node)? {
+                return Ok((old_node.clone(), old_node));
+            }
+            new_node.updated_at = chrono::Utc::now();
+            new_node.version = old_node.version + 1;
+
+            if nodes.compare_and_swap(key, Some(old_bytes), Some(self.encode(&new_node)?))?.is_ok() {
+                return Ok((old_node, new_node));
+            }
+        }
+    }
+
+    /// Merge `fields` into a node's metadata; fields set to null are removed
+    ///
+    /// Bumps the node's version and records an update event, as
+    /// `update_node` does for content.
+    pub fn update_metadata(&self, id: NodeId, fields: Metadata, agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("update_metadata");
+        self.ensure_writable()?;
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            for (field, value) in &fields {
+                if value.is_null() {
+                    node.metadata.remove(field);
+                } else {
+                    node.metadata.insert(field.clone(), value.clone());
+                }
+            }
+            Ok(true)
+        })?;
+
+        self.update_node_indexes(&old_node, false)?;
+        self.update_node_indexes(&new_node, true)?;
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+        Ok(new_node)
+    }
+
+    /// Add and remove tags on a node
+    ///
+    /// Bumps the node's version and records an update event unless the
+    /// tags end up unchanged.
+    pub fn update_tags(&self, id: NodeId, add: &[String], remove: &[String], agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("update_tags");
+        self.ensure_writable()?;
+        if let Some(tag) = add.iter().find(|t| t.trim().is_empty() || t.chars().any(char::is_control)) {
+            return Err(StoreError::InvalidOperation(format!("Invalid tag: {:?}", tag)));
+        }
+        let remove = normalize_tags(remove.to_vec());
+
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            let mut tags = node.tags.clone();
+            tags.extend(add.iter().cloned());
+            let tags: Vec<String> = normalize_tags(tags).into_iter().filter(|t| !remove.contains(t)).collect();
+            if tags == node.tags {
+                return Ok(false);
+            }
+            node.tags = tags;
+            Ok(true)
+        })?;
+        if new_node.version == old_node.version {
+            return Ok(new_node);
+        }
+
+        self.update_node_indexes(&old_node, false)?;
+        self.update_node_indexes(&new_node, true)?;
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+        Ok(new_node)
+    }
+
+    /// Hot nodes carrying `tag`, in ID order, read from the tag index
+    pub fn nodes_by_tag(&self, tag: &str, kind: Option<&NodeKind>, limit: usize) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("nodes_by_tag");
+        let prefix = Self::tag_prefix(tag.trim());
+        let mut result = Vec::new();
+        for entry in self.open_tree(NODES_BY_TAG_TREE)?.scan_prefix(&prefix) {
+            if result.len() >= limit {
+                break;
+            }
+            let key = entry?.0;
+            let id = NodeId::from_bytes(
+                <[u8; 16]>::try_from(&key[prefix.len()..])
+                    .map_err(|_| StoreError::Serialization(format!("bad tag index key for '{}'", tag)))?,
+            );
+            match self.get_node(id)? {
+                Some(node) if kind.map_or(true, |k| &node.kind == k) => result.push(node),
+                Some(_) => {}
+                None => self.degrade(|| format!("tag index lists {} under '{}', which doesn't exist", id, tag))?,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Every tag in use with the number of hot nodes carrying it
+    pub fn tag_counts(&self) -> Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        for entry in self.open_tree(NODES_BY_TAG_TREE)?.iter() {
+            let key = entry?.0;
+            let tag = &key[..key.len().saturating_sub(17)];
+            *counts.entry(String::from_utf8_lossy(tag).into_owned()).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Pin or unpin a node
+    ///
+    /// Bumps the node's version and records an update event unless it was
+    /// already in that state.
+    pub fn set_pinned(&self, id: NodeId, pinned: bool, agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("set_pinned");
+        self.ensure_writable()?;
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            if node.pinned == pinned {
+                return Ok(false);
+            }
+            node.pinned = pinned;
+            Ok(true)
+        })?;
+        if new_node.version == old_node.version {
+            return Ok(new_node);
+        }
+
+        self.update_node_indexes(&old_node, false)?;
+        self.update_node_indexes(&new_node, true)?;
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+        Ok(new_node)
+    }
+
+    /// Override a node's retention class, or with `None` go back to its
+    /// kind's
+    ///
+    /// A node without an expiry takes the new class's TTL, counted from its
+    /// creation.
+    pub fn set_retention(&self, id: NodeId, retention: Option<RetentionClass>, agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("set_retention");
+        self.ensure_writable()?;
+        let policy = self.retention_policy()?;
+
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            if node.retention == retention {
+                return Ok(false);
+            }
+            node.retention = retention;
+            policy.apply_ttl(node);
+            Ok(true)
+        })?;
+        if new_node.version == old_node.version {
+            return Ok(new_node);
+        }
+
+        self.update_node_indexes(&old_node, false)?;
+        self.update_node_indexes(&new_node, true)?;
+        if let (None, Some(expires_at)) = (old_node.expires_at, new_node.expires_at) {
+            self.nodes_by_expiry_tree()?
+                .insert(Self::time_key(expires_at, new_node.id), Vec::<u8>::new())?;
+        }
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+        Ok(new_node)
+    }
+
+    /// Give a node an alias usable wherever its ID is, replacing any alias
+    /// it had
+    pub fn set_alias(&self, id: NodeId, alias: &str) -> Result<()> {
+        let _timer = self.metrics.start("set_alias");
+        self.ensure_writable()?;
+        validate_alias(alias)?;
+        if self.get_node(id)?.is_none() && self.get_archived(id)?.is_none() {
+            return Err(StoreError::NodeNotFound(id));
+        }
+        let aliases = self.open_tree(ALIASES_TREE)?;
+        let key = id.to_bytes();
+        // Claim the alias atomically, so two nodes can't both take it
+        if let Err(taken) = aliases.compare_and_swap(alias.as_bytes(), None as Option<&[u8]>, Some(&key[..]))? {
+            let owner = taken.current.map(|bytes| Self::alias_owner(&bytes)).transpose()?;
+            if owner == Some(id) {
+                return Ok(());
+            }
+            return Err(StoreError::AliasTaken(alias.to_string(), owner.unwrap_or(id)));
+        }
+        if let Some(old) = self.open_tree(ALIASES_BY_NODE_TREE)?.insert(key, alias.as_bytes())? {
+            aliases.remove(old)?;
+        }
+        Ok(())
+    }
+
+    /// Drop a node's alias, returning it
+    pub fn remove_alias(&self, id: NodeId) -> Result<Option<String>> {
+        self.ensure_writable()?;
+        let Some(alias) = self.open_tree(ALIASES_BY_NODE_TREE)?.remove(id.to_bytes())? else {
+            return Ok(None);
+        };
+        self.open_tree(ALIASES_TREE)?.remove(&alias)?;
+        Ok(Some(String::from_utf8_lossy(&alias).into_owned()))
+    }
+
+    pub fn alias_of(&self, id: NodeId) -> Result<Option<String>> {
+        Ok(self
+            .open_tree(ALIASES_BY_NODE_TREE)?
+            .get(id.to_bytes())?
+            .map(|alias| String::from_utf8_lossy(&alias).into_owned()))
+    }
+
+    pub fn node_for_alias(&self, alias: &str) -> Result<Option<NodeId>> {
+        self.open_tree(ALIASES_TREE)?.get(alias.as_bytes())?.map(|bytes| Self::alias_owner(&bytes)).transpose()
+    }
+
+    /// Every alias with its node, in alias order
+    pub fn aliases(&self) -> Result<Vec<(String, NodeId)>> {
+        self.open_tree(ALIASES_TREE)?
+            .iter()
+            .map(|entry| -> Result<(String, NodeId)> {
+                let (alias, bytes) = entry?;
+                Ok((String::from_utf8_lossy(&alias).into_owned(), Self::alias_owner(&bytes)?))
+            })
+            .collect()
+    }
+
+    /// A node ID, or the node an alias names
+    pub fn resolve_node(&self, reference: &str) -> Result<NodeId> {
+        if let Ok(id) = reference.parse::<NodeId>() {
+            return Ok(id);
+        }
+        self.node_for_alias(reference)?.ok_or_else(|| StoreError::UnknownNodeRef(reference.to_string()))
+    }
+
+    fn alias_owner(bytes: &[u8]) -> Result<NodeId> {
+        let bytes: [u8; 16] = bytes.try_into().map_err(|_| StoreError::Serialization("malformed alias entry".into()))?;
+        Ok(NodeId::from_bytes(bytes))
+    }
+
+    /// Set one edge's weight and metadata, as `update_edge` does for the
+    /// edge and its twin
+    fn update_one_edge(
+        &self,
+        id: EdgeId,
+        weight: Option<f32>,
+        metadata: &Metadata,
+        agent: AgentId,
+        group: Option<ulid::Ulid>,
+    ) -> Result<StateEdge> {
+        let edges = self.edges_tree()?;
+        let key = id.to_bytes();
+
+        let (old_edge, new_edge) = loop {
+            let old_bytes = edges.get(key)?.ok_or(StoreError::EdgeNotFound(id))?;
+            let old_edge: StateEdge = Self::deserialize(&old_bytes)?;
+
+            let mut new_edge = old_edge.clone();
+            if let Some(weight) = weight {
+                new_edge.weight = weight;
+            }
+            for (field, value) in metadata {
+                if value.is_null() {
+                    new_edge.metadata.remove(field);
+                } else {
+                    new_edge.metadata.insert(field.clone(), value.clone());
+                }
+            }
+
+            let new_bytes = Self::serialize(&new_edge)?;
+            self.metrics.add_bytes(new_bytes.len());
+            if edges.compare_and_swap(key, Some(old_bytes), Some(new_bytes))?.is_ok() {
+                break (old_edge, new_edge);
+            }
+        };
+
+        // Log event
+        let mut event = StateEvent::new(agent, Operation::Update, Target::Edge(id))
+            .with_before(serde_json::to_value(&old_edge).unwrap())
+            .with_after(serde_json::to_value(&new_edge).unwrap());
+        event.group = group;
+        self.log_event(event)?;
+
+        Ok(new_edge)
+    }
+
+    /// Delete an edge and its reciprocal, logging both in `group`
+    fn delete_edge_in(&self, id: EdgeId, agent: AgentId, group: Option<ulid::Ulid>) -> Result<()> {
+        let old_edge = self.get_edge(id)?.ok_or(StoreError::EdgeNotFound(id))?;
+        self.unwrite_edge(&old_edge)?;
+
+        // Log event
+        let mut event = StateEvent::new(agent.clone(), Operation::Unlink, Target::Edge(id))
+            .with_before(serde_json::to_value(&old_edge).unwrap());
+        event.group = group;
+        self.log_event(event)?;
+
+        // Its reciprocal goes with it
+        if let Some(twin) = self.unlink_reciprocal(id)? {
+            if self.get_edge(twin)?.is_some() {
+                self.delete_edge_in(twin, agent, group)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The edge paired with `id` as its reciprocal, if any
+    pub fn reciprocal_of(&self, id: EdgeId) -> Result<Option<EdgeId>> {
+        self.open_tree(RECIPROCALS_TREE)?.get(id.to_bytes())?.map(|bytes| Self::stored_edge_id(&bytes)).transpose()
+    }
+
+    /// Give every unpaired edge of a reciprocal kind its twin, pairing up
+    /// edges that already mirror each other; returns the edges created
+    pub fn backfill_reciprocals(&self, agent: AgentId) -> Result<Vec<StateEdge>> {
+        let _timer = self.metrics.start("backfill_reciprocals");
+        self.ensure_writable()?;
+        let mut created = Vec::new();
+        for edge in self.list_edges()? {
+            if self.reciprocal_of(edge.id)?.is_some() {
+                continue;
+            }
+            if let Some(twin) = self.reciprocal_twin(&edge)? {
+                if let Twin::New(new) = &twin {
+                    created.push(new.clone());
+                }
+                self.pair_reciprocal(&edge, twin, agent.clone(), None)?;
+            }
+        }
+        Ok(created)
+    }
+
+    /// `edge`'s twin under the reciprocal kinds: an unpaired edge already
+    /// running the other way, else a new one, checked but not yet written
+    fn reciprocal_twin(&self, edge: &StateEdge) -> Result<Option<Twin>> {
+        let pairs = self.reciprocal_kinds()?;
+        let kind = pairs.iter().find_map(|(a, b)| match () {
+            _ if *a == edge.kind => Some(b.clone()),
+            _ if *b == edge.kind => Some(a.clone()),
+            _ => None,
+        });
+        let Some(kind) = kind.filter(|_| edge.from != edge.to) else {
+            return Ok(None);
+        };
+        for existing in self.stored_edges(&self.edges_by_from_tree()?, edge.to)? {
+            if existing.to == edge.from && existing.kind == kind && self.reciprocal_of(existing.id)?.is_none() {
+                return Ok(Some(Twin::Existing(existing.id)));
+            }
+        }
+        let twin =
+            StateEdge::new(edge.to, edge.from, kind).with_weight(edge.weight).with_metadata(edge.metadata.clone());
+        if let Some(cycle) = cycles::would_close_cycle(self, &twin, &self.acyclic_kinds()?)? {
+            return Err(StoreError::WouldCycle(cycle));
+        }
+        self.graph_constraints()?.check_edge(self, &twin)?;
+        Ok(Some(Twin::New(twin)))
+    }
+
+    /// Write `twin` if it is new, logging it in `group`, and record it as
+    /// `edge`'s reciprocal
+    fn pair_reciprocal(&self, edge: &StateEdge, twin: Twin, agent: AgentId, group: Option<ulid::Ulid>) -> Result<()> {
+        let twin = match twin {
+            Twin::Existing(id) => id,
+            Twin::New(twin) => {
+                self.write_edge(&twin)?;
+                let mut event = StateEvent::new(agent, Operation::Link, Target::Edge(twin.id))
+                    .with_after(serde_json::to_value(&twin).unwrap());
+                event.group = group;
+                self.log_event(event)?;
+                twin.id
+            }
+        };
+        self.link_reciprocals(edge.id, twin)
+    }
+
+    fn link_reciprocals(&self, a: EdgeId, b: EdgeId) -> Result<()> {
+        let reciprocals = self.open_tree(RECIPROCALS_TREE)?;
+        reciprocals.insert(a.to_bytes(), &b.to_bytes()[..])?;
+        reciprocals.insert(b.to_bytes(), &a.to_bytes()[..])?;
+        Ok(())
+    }
+
+    /// Forget `id`'s pairing, returning its twin
+    fn unlink_reciprocal(&self, id: EdgeId) -> Result<Option<EdgeId>> {
+        let reciprocals = self.open_tree(RECIPROCALS_TREE)?;
+        let Some(bytes) = reciprocals.remove(id.to_bytes())? else {
+            return Ok(None);
+        };
+        let twin = Self::stored_edge_id(&bytes)?;
+        reciprocals.remove(twin.to_bytes())?;
+        Ok(Some(twin))
+    }
+
+    fn stored_edge_id(bytes: &[u8]) -> Result<EdgeId> {
+        let bytes: [u8; 16] = bytes.try_into().map_err(|_| StoreError::Serialization("malformed reciprocal entry".into()))?;
+        Ok(EdgeId::from_bytes(bytes))
+    }
+
+    /// Pinned hot nodes in ID order, read from the pin index
+    pub fn pinned_nodes(&self, kind: Option<&NodeKind>, limit: usize) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("pinned_nodes");
+        let ids = self
+            .open_tree(PINNED_NODES_TREE)?
+            .iter()
+            .keys()
+            .map(|key| -> Result<Vec<u8>> { Ok(key?.to_vec()) })
+            .collect::<Result<Vec<_>>>()?;
+        let nodes = self.indexed::<StateNode>(PINNED_NODES_TREE, &self.nodes_tree()?, ids)?;
+        Ok(nodes.into_iter().filter(|n| kind.map_or(true, |k| &n.kind == k)).take(limit).collect())
+    }
+
+    /// Apply a JSON or merge patch to a node's content
+    ///
+    /// The patch is reapplied to the latest content if another writer gets
+    /// in first, so agents touching different fields don't clobber each
+    /// other. A failing RFC 6902 `test` or a missing path leaves the node
+    /// unchanged.
+    pub fn patch_node(&self, id: NodeId, patch: &ContentPatch, agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("patch_node");
+        self.ensure_writable()?;
+        let schemas = self.content_schemas()?;
+
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            patch.apply(&mut node.content)?;
+            schemas.check(&node.kind, &node.content)?;
+            Ok(true)
+        })?;
+
+        self.reindex_content_hash(&old_node, &new_node)?;
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+        Ok(new_node)
+    }
+
+    /// Move every edge of `old` onto `new` as one change
+    ///
+    /// Edges keep their IDs. Edges between the two nodes are dropped rather
+    /// than turned into self-loops, and `old` itself is left in place.
+    /// Returns the moved edges.
+    pub fn replace_node(&self, old: NodeId, new: NodeId, agent: AgentId) -> Result<Vec<StateEdge>> {
+        let _timer = self.metrics.start("replace_node");
+        self.ensure_writable()?;
+        if old == new {
+            return Err(StoreError::InvalidOperation("Cannot replace a node with itself".into()));
+        }
+        for id in [old, new] {
+            if self.get_node(id)?.is_none() {
+                return Err(StoreError::NodeNotFound(id));
+            }
+        }
+
+        let mut remove = self.stored_edges(&self.edges_by_from_tree()?, old)?;
+        // Self-loops are in both lists
+        remove.extend(
+            self.stored_edges(&self.edges_by_to_tree()?, old)?.into_iter().filter(|e| e.from != old),
+        );
+        let add: Vec<StateEdge> = remove
+            .iter()
+            .filter(|e| e.from != new && e.to != new)
+            .map(|e| {
+                let mut moved = e.clone();
+                if moved.from == old {
+                    moved.from = new;
+                }
+                if moved.to == old {
+                    moved.to = new;
+                }
+                moved
+            })
+            .collect();
+        self.rewire(&remove, &add, agent, ulid::Ulid::new())?;
+        Ok(add)
+    }
+
+    /// Make `new_parent` the only node `id` is `PartOf`, as one change
+    ///
+    /// The new edge takes the weight and metadata of the first edge it
+    /// replaces.
+    pub fn reparent(&self, id: NodeId, new_parent: NodeId, agent: AgentId) -> Result<StateEdge> {
+        let _timer = self.metrics.start("reparent");
+        self.ensure_writable()?;
+        if id == new_parent {
+            return Err(StoreError::InvalidOperation("A node cannot be its own parent".into()));
+        }
+        for node in [id, new_parent] {
+            if self.get_node(node)?.is_none() {
+                return Err(StoreError::NodeNotFound(node));
+            }
+        }
+
+        let remove: Vec<StateEdge> = self
+            .stored_edges(&self.edges_by_from_tree()?, id)?
+            .into_iter()
+            .filter(|e| e.kind == EdgeKind::PartOf)
+            .collect();
+        let mut edge = StateEdge::new(id, new_parent, EdgeKind::PartOf);
+        if let Some(previous) = remove.first() {
+            edge = edge.with_weight(previous.weight).with_metadata(previous.metadata.clone());
+        }
+        self.rewire(&remove, std::slice::from_ref(&edge), agent, ulid::Ulid::new())?;
+        Ok(edge)
+    }
+
+    /// Split a node into parts, one per content, as one change
+    ///
+    /// Each part gets the node's kind and metadata (less system fields), is
+    /// numbered by `chunk_index` and is linked `PartOf` the node, which
+    /// stays as their parent.
+    pub fn split_node(&self, id: NodeId, parts: Vec<Value>, agent: AgentId) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("split_node");
+        self.ensure_writable()?;
+        let original = self.get_node(id)?.ok_or(StoreError::NodeNotFound(id))?;
+        if parts.is_empty() {
+            return Err(StoreError::InvalidOperation("Nothing to split into".into()));
+        }
+
+        let schemas = self.content_schemas()?;
+        for content in &parts {
+            schemas.check(&original.kind, content)?;
+        }
+
+        let group = ulid::Ulid::new();
+        let defaults = self.agent_defaults()?;
+        let retention = self.retention_policy()?;
+        let mut metadata = original.metadata.clone();
+        metadata.retain(|field, _| !SYSTEM_FIELDS.contains(&field.as_str()));
+        let nodes: Vec<StateNode> = parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, content)| {
+                let mut node = StateNode::new(original.kind.clone(), content).with_metadata(metadata.clone());
+                node.metadata.insert(CHUNK_INDEX_KEY.to_string(), serde_json::json!(index));
+                node.retention = original.retention;
+                defaults.apply(&agent, &mut node);
+                retention.apply_ttl(&mut node);
+                node
+            })
+            .collect();
+        let edges: Vec<StateEdge> = nodes.iter().map(|n| StateEdge::new(n.id, id, EdgeKind::PartOf)).collect();
+
+        for node in &nodes {
+            self.write_node(node)?;
+        }
+        for node in &nodes {
+            let event = self.node_event(agent.clone(), Operation::Create, node, None, Some(node))?;
+            self.log_event(event.with_group(group))?;
+        }
+        // New parts have no incoming edges, so their links can't close a cycle
+        self.rewire(&[], &edges, agent, group)?;
+        Ok(nodes)
+    }
+
+    /// Merge duplicates of `winner` into it as one change
+    ///
+    /// Content and metadata combine under `strategy` and must pass the
+    /// winner's content schema. Every edge of the losers moves onto the
+    /// winner, except those that would become self-loops or repeat an edge
+    /// of the same kind the winner already has. The winner then
+    /// `Supersedes` each loser; losers are otherwise left in place. If
+    /// another writer changes the winner meanwhile, the losers are merged
+    /// into its new content instead. Returns the merged node and the edges
+    /// it gained.
+    pub fn merge_nodes(
+        &self,
+        winner: NodeId,
+        losers: &[NodeId],
+        strategy: MergeStrategy,
+        agent: AgentId,
+    ) -> Result<(StateNode, Vec<StateEdge>)> {
+        let _timer = self.metrics.start("merge_nodes");
+        self.ensure_writable()?;
+        if losers.is_empty() {
+            return Err(StoreError::InvalidOperation("Nothing to merge".into()));
+        }
+        let mut merged = HashSet::from([winner]);
+        for id in losers {
+            if !merged.insert(*id) {
+                return Err(StoreError::InvalidOperation(format!("{} is listed twice in the merge", id)));
+            }
+        }
+        if self.get_node(winner)?.is_none() {
+            return Err(StoreError::NodeNotFound(winner));
+        }
+        let duplicates = losers
+            .iter()
+            .map(|id| self.get_node(*id)?.ok_or(StoreError::NodeNotFound(*id)))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Each edge of the losers once, even when it joins two of them
+        let mut remove = Vec::new();
+        let mut seen = HashSet::new();
+        for id in losers {
+            let from = self.stored_edges(&self.edges_by_from_tree()?, *id)?;
+            let to = self.stored_edges(&self.edges_by_to_tree()?, *id)?;
+            remove.extend(from.into_iter().chain(to).filter(|e| seen.insert(e.id)));
+        }
+        let mut links: HashSet<(NodeId, NodeId, EdgeKind)> = self
+            .stored_edges(&self.edges_by_from_tree()?, winner)?
+            .into_iter()
+            .chain(self.stored_edges(&self.edges_by_to_tree()?, winner)?)
+            .map(|e| (e.from, e.to, e.kind))
+            .collect();
+        let onto_winner = |id: NodeId| if merged.contains(&id) { winner } else { id };
+        let mut add: Vec<StateEdge> = Vec::new();
+        for edge in &remove {
+            let mut moved = edge.clone();
+            moved.from = onto_winner(edge.from);
+            moved.to = onto_winner(edge.to);
+            if moved.from != moved.to && links.insert((moved.from, moved.to, moved.kind.clone())) {
+                add.push(moved);
+            }
+        }
+        add.extend(losers.iter().map(|id| StateEdge::new(winner, *id, EdgeKind::Supersedes)));
+
+        let schemas = self.content_schemas()?;
+        let (old_node, new_node) = self.update_in_place(winner, |node| {
+            let (content, metadata) = merge::merge(node, &duplicates, strategy);
+            schemas.check(&node.kind, &content)?;
+            node.content = content;
+            node.metadata = metadata;
+            node.tags =
+                normalize_tags(node.tags.iter().chain(duplicates.iter().flat_map(|d| &d.tags)).cloned().collect());
+            node.pinned = node.pinned || duplicates.iter().any(|d| d.pinned);
+            Ok(true)
+        })?;
+        let group = ulid::Ulid::new();
+        if let Err(e) = self.rewire(&remove, &add, agent.clone(), group) {
+            self.nodes_tree()?.insert(winner.to_bytes(), self.encode(&old_node)?)?;
+            return Err(e);
+        }
+        self.reindex_content_hash(&old_node, &new_node)?;
+        self.update_node_indexes(&old_node, false)?;
+        self.update_node_indexes(&new_node, true)?;
+        let event = self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event.with_group(group))?;
+        Ok((new_node, add))
+    }
+
+    /// Remove `remove` and write `add` as one change
+    ///
+    /// Each added edge is checked against the acyclic constraint and the
+    /// graph constraints with the removed edges gone and the earlier added
+    /// ones in place. The edges, their indexes, the reciprocal pairings and
+    /// the events then commit in one transaction, so a failed check or
+    /// write leaves the graph as it was and nothing logged. Every event
+    /// shares `group`.
+    fn rewire(&self, remove: &[StateEdge], add: &[StateEdge], agent: AgentId, group: ulid::Ulid) -> Result<()> {
+        let acyclic = self.acyclic_kinds()?;
+        let constraints = self.graph_constraints()?;
+        let symmetric = self.symmetric_kinds()?;
+        let removed: HashSet<EdgeId> = remove.iter().map(|e| e.id).collect();
+        for (i, edge) in add.iter().enumerate() {
+            let rewired = Rewired { store: self, removed: &removed, added: &add[..i], symmetric: &symmetric };
+            if let Some(cycle) = cycles::would_close_cycle(&rewired, edge, &acyclic)? {
+                return Err(StoreError::WouldCycle(cycle));
+            }
+            constraints.check_edge(&rewired, edge)?;
+        }
+
+        let unlinks = remove.iter().map(|edge| {
+            StateEvent::new(agent.clone(), Operation::Unlink, Target::Edge(edge.id))
+                .with_before(serde_json::to_value(edge).unwrap())
+        });
+        let links = add.iter().map(|edge| {
+            StateEvent::new(agent.clone(), Operation::Link, Target::Edge(edge.id))
+                .with_after(serde_json::to_value(edge).unwrap())
+        });
+        let events = unlinks
+            .chain(links)
+            .map(|event| self.stamp_event(event.with_group(group)))
+            .collect::<Result<Vec<_>>>()?;
+        let encoded_events = events
+            .iter()
+            .map(|event| Ok((event.id.to_bytes(), self.encode(event)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let encoded_edges = add.iter().map(|edge| Ok((edge, Self::serialize(edge)?))).collect::<Result<Vec<_>>>()?;
+        // Moved edges keep their IDs, and so their pairing
+        let unpaired: Vec<EdgeId> = remove.iter().map(|e| e.id).filter(|id| !add.iter().any(|e| e.id == *id)).collect();
+
+        self.register_namespace()?;
+        let (edges, by_from, by_to) = (self.edges_tree()?, self.edges_by_from_tree()?, self.edges_by_to_tree()?);
+        let (reciprocals, log) = (self.open_tree(RECIPROCALS_TREE)?, self.events_tree()?);
+        (&edges, &by_from, &by_to, &reciprocals, &log)
+            .transaction(|(edges, by_from, by_to, reciprocals, log)| {
+                for edge in remove {
+                    let key = edge.id.to_bytes();
+                    edges.remove(&key[..])?;
+                    Self::index_in_transaction(by_from, &edge.from.to_bytes(), &key, false)?;
+                    Self::index_in_transaction(by_to, &edge.to.to_bytes(), &key, false)?;
+                }
+                for (edge, bytes) in &encoded_edges {
+                    let key = edge.id.to_bytes();
+                    edges.insert(&key[..], bytes.as_slice())?;
+                    Self::index_in_transaction(by_from, &edge.from.to_bytes(), &key, true)?;
+                    Self::index_in_transaction(by_to, &edge.to.to_bytes(), &key, true)?;
+                }
+                for id in &unpaired {
+                    if let Some(twin) = reciprocals.remove(&id.to_bytes()[..])? {
+                        reciprocals.remove(twin)?;
+                    }
+                }
+                for (key, value) in &encoded_events {
+                    log.insert(&key[..], value.as_slice())?;
+                }
+                Ok(())
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => e.into(),
+            })?;
+
+        self.metrics.add_bytes(
+            encoded_edges.iter().map(|(_, bytes)| bytes.len()).sum::<usize>()
+                + encoded_events.iter().map(|(_, bytes)| bytes.len()).sum::<usize>(),
+        );
+        for edge in remove {
+            self.bump_stats(&[Counter::EdgeKind(&edge.kind)], -1)?;
+        }
+        for edge in add {
+            self.bump_stats(&[Counter::EdgeKind(&edge.kind)], 1)?;
+        }
+        for event in &events {
+            self.bump_stats(&[Counter::Agent(&event.agent), Counter::Day(event.timestamp)], 1)?;
+            self.hooks.run(event);
+        }
+        Ok(())
+    }
+
+    /// Move a node's entries in the hash and update-time indexes after its
+    /// content changed
+    fn reindex_content_hash(&self, old_node: &StateNode, new_node: &StateNode) -> Result<()> {
+        let (old_hash, new_hash) = (Self::hash_key(old_node), Self::hash_key(new_node));
+        if old_hash != new_hash {
+            let key = new_node.id.to_bytes();
+            let nodes_by_hash = self.nodes_by_hash_tree()?;
+            self.remove_from_index(&nodes_by_hash, &old_hash, &key)?;
+            self.add_to_index(&nodes_by_hash, &new_hash, &key)?;
+        }
+        let updated = self.open_tree(NODES_BY_UPDATED_TREE)?;
+        updated.remove(Self::time_key(old_node.updated_at, old_node.id))?;
+        updated.insert(Self::time_key(new_node.updated_at, new_node.id), Vec::<u8>::new())?;
+        Ok(())
+    }
+
+    /// Add or remove a node's entries in the tag, pin and update-time
+    /// indexes and in every declared metadata index
+    fn update_node_indexes(&self, node: &StateNode, insert: bool) -> Result<()> {
+        let updated = self.open_tree(NODES_BY_UPDATED_TREE)?;
+        if insert {
+            updated.insert(Self::time_key(node.updated_at, node.id), Vec::<u8>::new())?;
+        } else {
+            updated.remove(Self::time_key(node.updated_at, node.id))?;
+        }
+
+        if node.pinned {
+            let pinned = self.open_tree(PINNED_NODES_TREE)?;
+            if insert {
+                pinned.insert(node.id.to_bytes(), Vec::<u8>::new())?;
+            } else {
+                pinned.remove(node.id.to_bytes())?;
+            }
+        }
+
+        let tags = self.open_tree(NODES_BY_TAG_TREE)?;
+        for tag in &node.tags {
+            let key = Self::tag_key(tag, node.id);
+            if insert {
+                tags.insert(key, Vec::<u8>::new())?;
+            } else {
+                tags.remove(key)?;
+            }
+        }
+
+        let fields = self.open_tree(META_INDEX_FIELDS_TREE)?;
+        if fields.is_empty() {
+            return Ok(());
+        }
+        let _timer = self.metrics.start("index:metadata");
+        let index = self.open_tree(META_INDEX_TREE)?;
+        for field in fields.iter() {
+            let field = field?.0;
+            let field = String::from_utf8_lossy(&field);
+            for value in indices::field_values(node, &field) {
+                let key = indices::entry_key(&field, &value, node.id);
+                if insert {
+                    index.insert(key, Vec::<u8>::new())?;
+                } else {
+                    index.remove(key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `tag \0 NodeId` key in the tag index
+    fn tag_key(tag: &str, id: NodeId) -> Vec<u8> {
+        let mut key = Self::tag_prefix(tag);
+        key.extend_from_slice(&id.to_bytes());
+        key
+    }
+
+    fn tag_prefix(tag: &str) -> Vec<u8> {
+        let mut prefix = tag.as_bytes().to_vec();
+        prefix.push(0);
+        prefix
+    }
+
+    /// `kind/hash` key of a node's content in the hash index
+    fn hash_key(node: &StateNode) -> Vec<u8> {
+        format!("{}/{}", node.kind, attachment::content_hash(node.content.to_string().as_bytes()))
+            .into_bytes()
+    }
+
+    /// An existing node of the same kind whose content is identical
+    ///
+    /// Content is compared by hash of its JSON, so key order and
+    /// metadata do not matter. Archived nodes are not considered.
+    pub fn find_duplicate(&self, node: &StateNode) -> Result<Option<StateNode>> {
+        let Some(ids) = self.nodes_by_hash_tree()?.get(Self::hash_key(node))? else {
+            return Ok(None);
+        };
+        let ids: Vec<Vec<u8>> = Self::deserialize(&ids)?;
+        for id in ids {
+            let Ok(bytes) = <[u8; 16]>::try_from(id.as_slice()) else {
+                continue;
+            };
+            let id = NodeId::from_bytes(bytes);
+            if id == node.id {
+                continue;
+            }
+            // Guard against a stale entry by comparing the content itself
+            if let Some(existing) = self.get_node(id)?.filter(|n| n.content == node.content) {
+                return Ok(Some(existing));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Create a node unless one of the same kind already has its content
+    ///
+    /// With `DedupeMode::Reuse` the existing node is returned and nothing is
+    /// written. With `DedupeMode::Link` the node is created and linked to
+    /// the existing one by a `DerivedFrom` edge.
+    pub fn create_node_deduped(
+        &self,
+        node: StateNode,
+        agent: AgentId,
+        mode: DedupeMode,
+    ) -> Result<DedupeOutcome> {
+        let original = match mode {
+            DedupeMode::Off => None,
+            DedupeMode::Reuse | DedupeMode::Link => self.find_duplicate(&node)?,
+        };
+        match (mode, original) {
+            (DedupeMode::Reuse, Some(existing)) => Ok(DedupeOutcome::Reused(existing)),
+            (DedupeMode::Link, Some(existing)) => {
+                let node = self.create_node(node, agent.clone())?;
+                let edge = StateEdge::new(node.id, existing.id, EdgeKind::DerivedFrom);
+                let edge = self.create_edge(edge, agent)?;
+                Ok(DedupeOutcome::Linked { node, original: existing.id, edge })
+            }
+            _ => Ok(DedupeOutcome::Created(self.create_node(node, agent)?)),
+        }
+    }
+
+    /// Create many nodes at once, as imports do
+    ///
+    /// Without deduplication, nodes and their events go in one sled batch
+    /// per tree and the kind index is rewritten once per kind rather than
+    /// once per node. Deduplication has to see earlier nodes of the same
+    /// batch, so it falls back to `create_node_deduped` node by node.
+    pub fn create_nodes(
+        &self,
+        nodes: Vec<StateNode>,
+        agent: AgentId,
+        mode: DedupeMode,
+    ) -> Result<Vec<DedupeOutcome>> {
+        let _timer = self.metrics.start("create_nodes");
+        self.ensure_writable()?;
+        if mode != DedupeMode::Off {
+            return nodes
+                .into_iter()
+                .map(|node| self.create_node_deduped(node, agent.clone(), mode))
+                .collect();
+        }
+
+        let policy = self.capture_policy()?;
+        let retention = self.retention_policy()?;
+        let defaults = self.agent_defaults()?;
+        let schemas = self.content_schemas()?;
+        let constraints = self.graph_constraints()?;
+        let mut nodes = nodes;
+        for node in &mut nodes {
+            schemas.check(&node.kind, &node.content)?;
+            defaults.apply(&agent, node);
+            retention.apply_ttl(node);
+            node.tags = normalize_tags(std::mem::take(&mut node.tags));
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            constraints.check_node(self, node, &nodes[..i])?;
+        }
+        let mut node_batch = sled::Batch::default();
+        let mut event_batch = sled::Batch::default();
+        let mut by_kind: HashMap<String, Vec<Vec<u8>>> = HashMap::new();
+        let mut events = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let key = node.id.to_bytes().to_vec();
+            let bytes = self.encode(node)?;
+            self.metrics.add_bytes(bytes.len());
+            node_batch.insert(key.clone(), bytes);
+            by_kind.entry(node.kind.to_string()).or_default().push(key);
+
+            let snapshot =
+                serde_json::to_value(node).map_err(|e| StoreError::Serialization(e.to_string()))?;
+            let event = self.stamp_event(
+                StateEvent::new(agent.clone(), Operation::Create, Target::Node(node.id))
+                    .with_snapshots(
+                        retention.rules_for(node).capture.unwrap_or_else(|| policy.mode_for(&node.kind)),
+                        None,
+                        Some(snapshot),
+                    ),
+            )?;
+            let bytes = self.encode(&event)?;
+            self.metrics.add_bytes(bytes.len());
+            event_batch.insert(event.id.to_bytes().to_vec(), bytes);
+            events.push(event);
+        }
+        self.nodes_tree()?.apply_batch(node_batch)?;
+
+        let kinds = self.nodes_by_kind_tree()?;
+        for (kind, keys) in by_kind {
+            let mut ids: Vec<Vec<u8>> = kinds
+                .get(kind.as_bytes())?
+                .map(|v| Self::deserialize(&v))
+                .transpose()?
+                .unwrap_or_default();
+            let existing: HashSet<Vec<u8>> = ids.iter().cloned().collect();
+            ids.extend(keys.into_iter().filter(|key| !existing.contains(key)));
+            let _timer = self.metrics.start("index:list");
+            let bytes = Self::serialize(&ids)?;
+            self.metrics.add_bytes(bytes.len());
+            kinds.insert(kind.as_bytes(), bytes)?;
+        }
+        let hashes = self.nodes_by_hash_tree()?;
+        let expiry = self.nodes_by_expiry_tree()?;
+        for node in &nodes {
+            self.add_to_index(&hashes, &Self::hash_key(node), &node.id.to_bytes())?;
+            self.update_node_indexes(node, true)?;
+            self.bump_stats(&[Counter::NodeKind(&node.kind)], 1)?;
+            if let Some(expires_at) = node.expires_at {
+                expiry.insert(Self::time_key(expires_at, node.id), Vec::<u8>::new())?;
+            }
+        }
+
+        self.events_tree()?.apply_batch(event_batch)?;
+        for event in &events {
+            self.bump_stats(&[Counter::Agent(&event.agent), Counter::Day(event.timestamp)], 1)?;
+            self.hooks.run(event);
+        }
+        Ok(nodes.into_iter().map(DedupeOutcome::Created).collect())
+    }
+
+    /// Hot nodes last updated before `cutoff`, optionally narrowed by kind
+    /// and a search query; permanent nodes are never candidates
+    pub fn archive_candidates(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        kind: Option<NodeKind>,
+        query: Option<&str>,
+    ) -> Result<Vec<NodeId>> {
+        // An empty query matches every node
+        let query = query.unwrap_or_default().to_lowercase();
+        let kinds = kind.map(|k| vec![k]);
+        let extraction = self.text_extraction()?;
+        let retention = self.retention_policy()?;
+        let mut ids = Vec::new();
+        for entry in self.nodes_tree()?.iter() {
+            let node: StateNode = Self::deserialize(&entry?.1)?;
+            if node.updated_at < cutoff
+                && retention.class_for(&node) != RetentionClass::Permanent
+                && Self::matches_search(&node, &query, &kinds, &extraction)
+            {
+                ids.push(node.id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Hot nodes whose retention class says to archive them by `now`
+    pub fn archive_due(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<NodeId>> {
+        let retention = self.retention_policy()?;
+        let mut ids = Vec::new();
+        for entry in self.nodes_tree()?.iter() {
+            let node: StateNode = Self::deserialize(&entry?.1)?;
+            if retention.archive_due(&node, now) {
+                ids.push(node.id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Move nodes into the compressed archive tree
+    ///
+    /// Archived nodes drop out of `list_nodes`, `search` and `get_node` but
+    /// keep their edges, annotations and reactions. Unknown IDs are skipped;
+    /// the IDs actually archived are returned.
+    pub fn archive_nodes(&self, ids: &[NodeId]) -> Result<Vec<NodeId>> {
+        self.ensure_writable()?;
+        let nodes = self.nodes_tree()?;
+        let nodes_by_kind = self.nodes_by_kind_tree()?;
+        let archive = self.archive_tree()?;
+
+        let mut archived = Vec::new();
+        for id in ids {
+            let key = id.to_bytes();
+            let Some(bytes) = nodes.get(key)? else {
+                continue;
+            };
+            let node: StateNode = Self::deserialize(&bytes)?;
+            let compressed = zstd::encode_all(Self::serialize(&node)?.as_slice(), COMPRESSION_LEVEL)
+                .map_err(|e| StoreError::Serialization(format!("compression failed: {e}")))?;
+
+            archive.insert(key, compressed)?;
+            self.remove_from_index(&nodes_by_kind, node.kind.to_string().as_bytes(), &key)?;
+            self.remove_from_index(&self.nodes_by_hash_tree()?, &Self::hash_key(&node), &key)?;
+            self.update_node_indexes(&node, false)?;
+            self.bump_stats(&[Counter::NodeKind(&node.kind)], -1)?;
+            if let Some(expires_at) = node.expires_at {
+                self.nodes_by_expiry_tree()?
+                    .remove(Self::time_key(expires_at, node.id))?;
+            }
+            nodes.remove(key)?;
+            archived.push(node.id);
+        }
+        Ok(archived)
+    }
+
+    /// Move an archived node back into the hot store
+    pub fn unarchive_node(&self, id: NodeId) -> Result<StateNode> {
+        self.ensure_writable()?;
+        let key = id.to_bytes();
+        let archive = self.archive_tree()?;
+        let node: StateNode = archive
+            .get(key)?
+            .map(|bytes| Self::deserialize(&bytes))
+            .transpose()?
+            .ok_or(StoreError::NodeNotFound(id))?;
+
+        self.nodes_tree()?.insert(key, self.encode(&node)?)?;
+        self.add_to_index(&self.nodes_by_kind_tree()?, node.kind.to_string().as_bytes(), &key)?;
+        self.add_to_index(&self.nodes_by_hash_tree()?, &Self::hash_key(&node), &key)?;
+        self.update_node_indexes(&node, true)?;
+        self.bump_stats(&[Counter::NodeKind(&node.kind)], 1)?;
+        if let Some(expires_at) = node.expires_at {
+            self.nodes_by_expiry_tree()?
+                .insert(Self::time_key(expires_at, node.id), Vec::<u8>::new())?;
+        }
+        archive.remove(key)?;
+        Ok(node)
+    }
+
+    pub fn get_archived(&self, id: NodeId) -> Result<Option<StateNode>> {
+        self.archive_tree()?
+            .get(id.to_bytes())?
+            .map(|bytes| Self::deserialize(&bytes))
+            .transpose()
+    }
+
+    /// Archived nodes, optionally filtered by kind
+    pub fn list_archived(&self, kind: Option<NodeKind>, limit: usize) -> Result<Vec<StateNode>> {
+        let mut result = Vec::new();
+        for entry in self.archive_tree()?.iter() {
+            if result.len() >= limit {
+                break;
+            }
+            let node: StateNode = Self::deserialize(&entry?.1)?;
+            if kind.as_ref().map_or(true, |k| *k == node.kind) {
+                result.push(node);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Hot nodes in `sort` order, optionally of one kind, read through the
+    /// index that fits the sort key
+    pub fn list_nodes_sorted(&self, kind: Option<&NodeKind>, sort: Sort, limit: usize) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("list_nodes_sorted");
+        let nodes = self.nodes_tree()?;
+        let ids: Vec<Vec<u8>> = match (sort.key, kind) {
+            (SortKey::UpdatedAt, _) => {
+                let mut result = Vec::new();
+                for entry in Self::ordered(&self.open_tree(NODES_BY_UPDATED_TREE)?, sort.descending) {
+                    if result.len() >= limit {
+                        break;
+                    }
+                    let key = entry?.0;
+                    let id = &key[8..];
+                    match nodes.get(id)? {
+                        Some(bytes) => {
+                            let node: StateNode = Self::deserialize(&bytes)?;
+                            if kind.map_or(true, |k| &node.kind == k) {
+                                result.push(node);
+                            }
+                        }
+                        None => self.degrade(|| {
+                            format!("update-time index lists node {}, which doesn't exist", describe_key(id))
+                        })?,
+                    }
+                }
+                return Ok(result);
+            }
+            (SortKey::CreatedAt, None) => {
+                return self
+                    .decoded::<StateNode>(NODES_TREE, Self::ordered(&nodes, sort.descending))
+                    .take(limit)
+                    .collect();
+            }
+            // Within one kind, kind order is creation order
+            (SortKey::CreatedAt | SortKey::Kind, Some(kind)) => {
+                let mut ids = self.kind_ids(kind)?;
+                ids.sort();
+                sort.apply(ids)
+            }
+            (SortKey::Kind, None) => {
+                let mut ids = Vec::new();
+                for entry in self.nodes_by_kind_tree()?.iter() {
+                    let mut of_kind: Vec<Vec<u8>> = Self::deserialize(&entry?.1)?;
+                    of_kind.sort();
+                    ids.extend(of_kind);
+                }
+                sort.apply(ids)
+            }
+            (SortKey::Degree, _) => {
+                let ids = match kind {
+                    Some(kind) => self.kind_ids(kind)?,
+                    None => nodes.iter().keys().map(|key| Ok(key?.to_vec())).collect::<Result<Vec<_>>>()?,
+                };
+                let (from, to) = (self.edges_by_from_tree()?, self.edges_by_to_tree()?);
+                let mut ranked = ids
+                    .into_iter()
+                    .map(|id| Ok((Self::index_len(&from, &id)? + Self::index_len(&to, &id)?, id)))
+                    .collect::<Result<Vec<_>>>()?;
+                ranked.sort();
+                sort.apply(ranked).into_iter().map(|(_, id)| id).collect()
+            }
+        };
+        self.indexed(NODES_TREE, &nodes, ids.into_iter().take(limit))
+    }
+
+    /// Events in `sort` order; only creation time applies to events
+    pub fn list_events_sorted(&self, sort: Sort, limit: usize) -> Result<Vec<StateEvent>> {
+        if sort.key != SortKey::CreatedAt {
+            return Err(StoreError::InvalidOperation(format!("Events can't be sorted by {}", sort.key)));
+        }
+        self.decoded::<StateEvent>(EVENTS_TREE, Self::ordered(&self.events_tree()?, sort.descending))
+            .take(limit)
+            .collect()
+    }
+
+    /// Add nodes missing from the update-time index; returns how many
+    pub(crate) fn index_update_times(&self) -> Result<usize> {
+        let updated = self.open_tree(NODES_BY_UPDATED_TREE)?;
+        let mut indexed = 0;
+        for node in self.decoded::<StateNode>(NODES_TREE, self.nodes_tree()?.iter()) {
+            let node = node?;
+            if updated.insert(Self::time_key(node.updated_at, node.id), Vec::<u8>::new())?.is_none() {
+                indexed += 1;
+            }
+        }
+        Ok(indexed)
+    }
+
+    /// `tree` in key order, or reversed
+    fn ordered(tree: &sled::Tree, descending: bool) -> impl Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>> {
+        let mut entries = tree.iter();
+        std::iter::from_fn(move || if descending { entries.next_back() } else { entries.next() })
+    }
+
+    /// IDs the kind index lists for `kind`
+    fn kind_ids(&self, kind: &NodeKind) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .nodes_by_kind_tree()?
+            .get(kind.to_string().as_bytes())?
+            .map(|v| Self::deserialize(&v))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// `Store::search` over the archive tier
+    pub fn search_archived(&self, query: &str, kinds: Option<Vec<NodeKind>>) -> Result<Vec<StateNode>> {
+        let query_lower = query.to_lowercase();
+        let extraction = self.text_extraction()?;
+        self.decoded::<StateNode>(ARCHIVE_TREE, self.archive_tree()?.iter())
+            .filter(|node| node.as_ref().map_or(true, |node| Self::matches_search(node, &query_lower, &kinds, &extraction)))
+            .collect()
+    }
+
+    /// Every edge, in ID order
+    pub fn list_edges(&self) -> Result<Vec<StateEdge>> {
+        self.edges_tree()?
+            .iter()
+            .map(|entry| Self::deserialize(&entry?.1))
+            .collect()
+    }
+
+    /// Every node and edge as of `at`, rebuilt from the event log
+    pub fn graph_as_of(&self, at: chrono::DateTime<chrono::Utc>) -> Result<(Vec<StateNode>, Vec<StateEdge>)> {
+        let _timer = self.metrics.start("graph_as_of");
+        history::graph_as_of(&self.events_until(at)?, at)
+    }
+
+    /// Logged events up to and including `at`, oldest first
+    fn events_until(&self, at: chrono::DateTime<chrono::Utc>) -> Result<Vec<StateEvent>> {
+        let mut events = Vec::new();
+        for entry in self.events_tree()?.iter() {
+            let (_, bytes) = entry?;
+            let event: StateEvent = Self::deserialize(&bytes)?;
+            if event.timestamp <= at {
+                events.push(event);
+            }
+        }
+        history::chronological(&mut events);
+        Ok(events)
+    }
+
+    /// Undo `agent`'s most recent operation that isn't already undone
+    ///
+    /// An operation is an event and every event sharing its group. Its
+    /// changes are inverted newest first: created nodes and edges are
+    /// removed, deleted ones recreated and updated ones restored to their
+    /// earlier state as a new version. Each inverse is logged as a
+    /// compensating event by `agent`, grouped when there are several. If a
+    /// target has changed since, or its history holds hash-only events,
+    /// nothing is changed. `None` when there is nothing to undo.
+    pub fn undo_last(&self, agent: &AgentId) -> Result<Option<Reversal>> {
+        let _timer = self.metrics.start("undo_last");
+        self.revert_last(agent, Revert::Undo)
+    }
+
+    /// Redo the operation `agent` most recently undid, by undoing the undo
+    ///
+    /// `None` when there is nothing to redo, including once the agent has
+    /// made another change since the undo.
+    pub fn redo_last(&self, agent: &AgentId) -> Result<Option<Reversal>> {
+        let _timer = self.metrics.start("redo_last");
+        self.revert_last(agent, Revert::Redo)
+    }
+
+    fn revert_last(&self, agent: &AgentId, direction: Revert) -> Result<Option<Reversal>> {
+        self.ensure_writable()?;
+        let events = self.events_until(chrono::Utc::now())?;
+        let reverts = self.reverts()?;
+        let Some(operation) = undo::last_operation(&events, agent, &reverts, direction) else {
+            return Ok(None);
+        };
+
+        let mut inverses = Vec::with_capacity(operation.len());
+        for original in &operation {
+            match self.revert_change(original, &events, agent.clone()) {
+                Ok(inverse) => inverses.push(inverse),
+                Err(e) => {
+                    // Put back what was already inverted
+                    for original in operation[..inverses.len()].iter().rev() {
+                        self.apply_change(original)?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let group = (inverses.len() > 1).then(ulid::Ulid::new);
+        let reverts = self.open_tree(REVERTS_TREE)?;
+        let mut recorded = Vec::with_capacity(inverses.len());
+        for (mut inverse, original) in inverses.into_iter().zip(&operation) {
+            inverse.group = group;
+            let inverse = self.stamp_event(inverse)?;
+            reverts.insert(inverse.id.to_bytes(), &original.id.to_bytes()[..])?;
+            self.record_event(inverse.clone())?;
+            recorded.push(inverse);
+        }
+        Ok(Some(Reversal { reverted: operation.into_iter().cloned().collect(), recorded }))
+    }
+
+    /// Compensating events and the events they revert
+    fn reverts(&self) -> Result<HashMap<EventId, EventId>> {
+        let id = |bytes: &[u8]| -> Result<EventId> {
+            let bytes: [u8; 16] =
+                bytes.try_into().map_err(|_| StoreError::Serialization("malformed revert entry".into()))?;
+            Ok(EventId::from_bytes(bytes))
+        };
+        self.open_tree(REVERTS_TREE)?
+            .iter()
+            .map(|entry| -> Result<(EventId, EventId)> {
+                let (inverse, original) = entry?;
+                Ok((id(inverse.as_ref())?, id(original.as_ref())?))
+            })
+            .collect()
+    }
+
+    /// Write the inverse of `event`, provided its change is still in place,
+    /// and return the compensating event to log; `events` is the whole log
+    fn revert_change(&self, event: &StateEvent, events: &[StateEvent], agent: AgentId) -> Result<StateEvent> {
+        let changed = |what: String| {
+            StoreError::InvalidOperation(format!("{} has changed since event {}", what, event.id))
+        };
+        let decode = |value: &Option<Value>| -> Result<StateEdge> {
+            let value = value
+                .clone()
+                .ok_or_else(|| StoreError::InvalidOperation(format!("Event {} has no payload", event.id)))?;
+            serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string()))
+        };
+        let same = |a: &StateEdge, b: &StateEdge| serde_json::to_value(a).ok() == serde_json::to_value(b).ok();
+
+        match (&event.operation, &event.target) {
+            (Operation::Create | Operation::Update | Operation::Delete, Target::Node(id)) => {
+                let current = self.get_node(*id)?;
+                let current_version = |after: &StateNode| match &current {
+                    Some(node) if node.version == after.version => Ok(node.clone()),
+                    Some(node) => Err(StoreError::Conflict(*id, after.version, node.version)),
+                    None => Err(StoreError::NodeNotFound(*id)),
+                };
+                match history::around(events, *id, event.id)? {
+                    (None, Some(after)) => {
+                        let node = current_version(&after)?;
+                        let edges = self.stored_edges(&self.edges_by_from_tree()?, *id)?.len()
+                            + self.stored_edges(&self.edges_by_to_tree()?, *id)?.len();
+                        if edges > 0 {
+                            return Err(StoreError::NodeHasEdges(*id, edges));
+                        }
+                        self.remove_node_extras(*id)?;
+                        self.unwrite_node(&node)?;
+                        self.node_event(agent, Operation::Delete, &node, Some(&node), None)
+                    }
+                    (Some(before), Some(after)) => {
+                        let node = current_version(&after)?;
+                        let mut restored = before;
+                        restored.version = node.version + 1;
+                        restored.updated_at = chrono::Utc::now();
+                        self.unwrite_node(&node)?;
+                        self.write_node(&restored)?;
+                        self.node_event(agent, Operation::Update, &restored, Some(&node), Some(&restored))
+                    }
+                    (Some(before), None) => {
+                        if current.is_some() {
+                            return Err(changed(format!("Node {}", id)));
+                        }
+                        self.write_node(&before)?;
+                        self.node_event(agent, Operation::Create, &before, None, Some(&before))
+                    }
+                    (None, None) => Err(StoreError::InvalidOperation(format!("Event {} changed nothing", event.id))),
+                }
+            }
+            (Operation::Link, Target::Edge(id)) => {
+                let after = decode(&event.after)?;
+                let current = self.get_edge(*id)?.ok_or(StoreError::EdgeNotFound(*id))?;
+                if !same(&current, &after) {
+                    return Err(changed(format!("Edge {}", id)));
+                }
+                self.unwrite_edge(&current)?;
+                self.unlink_reciprocal(*id)?;
+                Ok(StateEvent::new(agent, Operation::Unlink, Target::Edge(*id))
+                    .with_before(serde_json::to_value(&current).unwrap()))
+            }
+            (Operation::Update, Target::Edge(id)) => {
+                let (before, after) = (decode(&event.before)?, decode(&event.after)?);
+                let current = self.get_edge(*id)?.ok_or(StoreError::EdgeNotFound(*id))?;
+                if !same(&current, &after) {
+                    return Err(changed(format!("Edge {}", id)));
+                }
+                self.unwrite_edge(&current)?;
+                self.write_edge(&before)?;
+                Ok(StateEvent::new(agent, Operation::Update, Target::Edge(*id))
+                    .with_before(serde_json::to_value(&current).unwrap())
+                    .with_after(serde_json::to_value(&before).unwrap()))
+            }
+            (Operation::Unlink, Target::Edge(id)) => {
+                let before = decode(&event.before)?;
+                if self.get_edge(*id)?.is_some() {
+                    return Err(changed(format!("Edge {}", id)));
+                }
+                for end in [before.from, before.to] {
+                    if self.get_node(end)?.is_none() {
+                        return Err(StoreError::NodeNotFound(end));
+                    }
+                }
+                self.write_edge(&before)?;
+                // A twin restored earlier in the same undo pairs up again
+                if let Ok(Some(Twin::Existing(twin))) = self.reciprocal_twin(&before) {
+                    self.link_reciprocals(before.id, twin)?;
+                }
+                Ok(StateEvent::new(agent, Operation::Link, Target::Edge(*id))
+                    .with_after(serde_json::to_value(&before).unwrap()))
+            }
+            (operation, target) => Err(StoreError::InvalidOperation(format!(
+                "Event {} applies {:?} to {:?}",
+                event.id, operation, target
+            ))),
+        }
+    }
+
+    /// Apply a recorded event to this store as-is
+    ///
+    /// Writes the event's after-state (or removes its target) with index
+    /// maintenance and copies the event into this store's log instead of
+    /// recording a new one, so a store rebuilt from another's log keeps the
+    /// original IDs, versions and timestamps. Diff-captured updates are
+    /// patched onto the node already in this store; hash-only creates and
+    /// updates cannot be applied.
+    pub fn apply_event(&self, event: &StateEvent) -> Result<()> {
+        self.ensure_writable()?;
+        self.apply_change(event)?;
+
+        // The event keeps its own origin and clock; this store has now seen it
+        self.advance_clock(|clock| clock.merge(&event.clock))?;
+        self.record_event(event.clone())
+    }
+
+    /// Write an event's after-state, or remove its target, without logging
+    fn apply_change(&self, event: &StateEvent) -> Result<()> {
+        let payload = |value: &Option<Value>| {
+            value
+                .clone()
+                .ok_or_else(|| StoreError::InvalidOperation(format!("Event {} has no payload", event.id)))
+        };
+        let decode = |e: serde_json::Error| StoreError::Serialization(e.to_string());
+
+        match (&event.operation, &event.target) {
+            (Operation::Create | Operation::Update, Target::Node(_))
+                if event.capture == CaptureMode::Hash =>
+            {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Event {} captured only a hash of the node",
+                    event.id
+                )));
+            }
+            (Operation::Update, Target::Node(id)) if event.capture == CaptureMode::Diff => {
+                let existing = self.get_node(*id)?.ok_or(StoreError::NodeNotFound(*id))?;
+                let patch: json_patch::Patch =
+                    serde_json::from_value(payload(&event.after)?).map_err(decode)?;
+                let mut snapshot = serde_json::to_value(&existing).map_err(decode)?;
+                json_patch::patch(&mut snapshot, &patch)
+                    .map_err(|e| StoreError::InvalidOperation(format!("Event {}: {}", event.id, e)))?;
+                let node: StateNode = serde_json::from_value(snapshot).map_err(decode)?;
+                self.unwrite_node(&existing)?;
+                self.write_node(&node)?;
+            }
+            (Operation::Create | Operation::Update, Target::Node(id)) => {
+                let node: StateNode = serde_json::from_value(payload(&event.after)?).map_err(decode)?;
+                if let Some(existing) = self.get_node(*id)? {
+                    self.unwrite_node(&existing)?;
+                }
+                self.write_node(&node)?;
+            }
+            (Operation::Delete, Target::Node(id)) => {
+                let existing = self.get_node(*id)?.ok_or(StoreError::NodeNotFound(*id))?;
+                self.unwrite_node(&existing)?;
+            }
+            (Operation::Link, Target::Edge(_)) => {
+                let edge: StateEdge = serde_json::from_value(payload(&event.after)?).map_err(decode)?;
+                self.write_edge(&edge)?;
+            }
+            (Operation::Update, Target::Edge(id)) => {
+                let edge: StateEdge = serde_json::from_value(payload(&event.after)?).map_err(decode)?;
+                if let Some(existing) = self.get_edge(*id)? {
+                    self.unwrite_edge(&existing)?;
+                }
+                self.write_edge(&edge)?;
+            }
+            (Operation::Unlink, Target::Edge(id)) => {
+                let existing = self.get_edge(*id)?.ok_or(StoreError::EdgeNotFound(*id))?;
+                self.unwrite_edge(&existing)?;
+            }
+            (operation, target) => {
+                return Err(StoreError::InvalidOperation(format!(
+                    "Event {} applies {:?} to {:?}",
+                    event.id, operation, target
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a node and add it to the kind, metadata and expiry indexes
+    fn write_node(&self, node: &StateNode) -> Result<()> {
+        let key = node.id.to_bytes();
+        let bytes = self.encode(node)?;
+        self.metrics.add_bytes(bytes.len());
+        self.nodes_tree()?.insert(key, bytes)?;
+        self.add_to_index(&self.nodes_by_kind_tree()?, node.kind.to_string().as_bytes(), &key)?;
+        self.add_to_index(&self.nodes_by_hash_tree()?, &Self::hash_key(node), &key)?;
+        self.update_node_indexes(node, true)?;
+        self.bump_stats(&[Counter::NodeKind(&node.kind)], 1)?;
+        if let Some(expires_at) = node.expires_at {
+            self.nodes_by_expiry_tree()?
+                .insert(Self::time_key(expires_at, node.id), Vec::<u8>::new())?;
+        }
+        Ok(())
+    }
+
+    /// Drop what hangs off a node outside the graph: annotations,
+    /// reactions, attachments and its alias
+    fn remove_node_extras(&self, id: NodeId) -> Result<()> {
+        let key = id.to_bytes();
+        let annotations = self.annotations_tree()?;
+        for entry in annotations.scan_prefix(key) {
+            let (annotation_key, _) = entry?;
+            annotations.remove(annotation_key)?;
+        }
+
+        let reactions = self.reactions_tree()?;
+        for entry in reactions.scan_prefix(key) {
+            let (reaction_key, _) = entry?;
+            reactions.remove(reaction_key)?;
+        }
+
+        for attachment in self.attachments(id)? {
+            self.remove_attachment(id, &attachment.hash)?;
+        }
+
+        self.remove_alias(id)?;
+        Ok(())
+    }
+
+    /// Inverse of `write_node`
+    fn unwrite_node(&self, node: &StateNode) -> Result<()> {
+        let key = node.id.to_bytes();
+        self.remove_from_index(&self.nodes_by_kind_tree()?, node.kind.to_string().as_bytes(), &key)?;
+        self.remove_from_index(&self.nodes_by_hash_tree()?, &Self::hash_key(node), &key)?;
+        self.update_node_indexes(node, false)?;
+        self.bump_stats(&[Counter::NodeKind(&node.kind)], -1)?;
+        if let Some(expires_at) = node.expires_at {
+            self.nodes_by_expiry_tree()?.remove(Self::time_key(expires_at, node.id))?;
+        }
+        self.nodes_tree()?.remove(key)?;
+        Ok(())
+    }
+
+    /// Store an edge and add it to the from/to indexes
+    fn write_edge(&self, edge: &StateEdge) -> Result<()> {
+        let key = edge.id.to_bytes();
+        let bytes = Self::serialize(edge)?;
+        self.metrics.add_bytes(bytes.len());
+        self.edges_tree()?.insert(key, bytes)?;
+        self.add_to_index(&self.edges_by_from_tree()?, &edge.from.to_bytes(), &key)?;
+        self.add_to_index(&self.edges_by_to_tree()?, &edge.to.to_bytes(), &key)?;
+        self.bump_stats(&[Counter::EdgeKind(&edge.kind)], 1)
+    }
+
+    /// Inverse of `write_edge`
+    fn unwrite_edge(&self, edge: &StateEdge) -> Result<()> {
+        let key = edge.id.to_bytes();
+        self.remove_from_index(&self.edges_by_from_tree()?, &edge.from.to_bytes(), &key)?;
+        self.remove_from_index(&self.edges_by_to_tree()?, &edge.to.to_bytes(), &key)?;
+        self.edges_tree()?.remove(key)?;
+        self.bump_stats(&[Counter::EdgeKind(&edge.kind)], -1)
+    }
+
+    /// Edges as stored with `node_id` at the end `index` covers; unlike
+    /// `edges_from` and `edges_to`, symmetric edges aren't mirrored
+    fn stored_edges(&self, index: &sled::Tree, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        let edges = self.edges_tree()?;
+        let ids: Vec<Vec<u8>> = index
+            .get(node_id.to_bytes())?
+            .map(|v| Self::deserialize(&v))
+            .transpose()?
+            .unwrap_or_default();
+
+        self.indexed(EDGES_TREE, &edges, ids)
+    }
+
+    /// Stored edges from `near`, plus symmetric-kind edges from `far`
+    /// turned around so `node_id` sits at the same end
+    fn edges_with_mirrors(&self, near: &sled::Tree, far: &sled::Tree, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        let mut edges = self.stored_edges(near, node_id)?;
+        let symmetric = self.symmetric_kinds()?;
+        if !symmetric.is_empty() {
+            edges.extend(
+                self.stored_edges(far, node_id)?
+                    .into_iter()
+                    .filter(|e| e.from != e.to && symmetric.contains(&e.kind))
+                    .map(StateEdge::reversed),
+            );
+        }
+        Ok(edges)
+    }
+
+    /// Edges whose `from` or `to` node no longer exists
+    pub fn orphan_edges(&self) -> Result<Vec<StateEdge>> {
+        let nodes = self.nodes_tree()?;
+        let archive = self.archive_tree()?;
+        let exists = |id: NodeId| -> Result<bool> {
+            Ok(nodes.contains_key(id.to_bytes())? || archive.contains_key(id.to_bytes())?)
+        };
+
+        let mut orphans = Vec::new();
+        for entry in self.edges_tree()?.iter() {
+            let (_, bytes) = entry?;
+            let edge: StateEdge = Self::deserialize(&bytes)?;
+            if !exists(edge.from)? || !exists(edge.to)? {
+                orphans.push(edge);
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Delete every orphaned edge, logging an `Unlink` event for each
+    pub fn prune_orphan_edges(&self, agent: AgentId) -> Result<Vec<StateEdge>> {
+        self.ensure_writable()?;
+        let orphans = self.orphan_edges()?;
+        for edge in &orphans {
+            if self.get_edge(edge.id)?.is_some() {
+                self.delete_edge(edge.id, agent.clone())?;
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Kind filter plus case-insensitive substring match on the content's
+    /// searchable text
+    fn matches_search(
+        node: &StateNode,
+        query_lower: &str,
+        kinds: &Option<Vec<NodeKind>>,
+        extraction: &TextExtraction,
+    ) -> bool {
+        if let Some(ks) = kinds {
+            if !ks.contains(&node.kind) {
+                return false;
+            }
+        }
+        extraction.text(node).to_lowercase().contains(query_lower)
+    }
+
+    fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>> {
+        bincode::serialize(value).map_err(|e| StoreError::Serialization(e.to_string()))
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        if bytes.starts_with(&ZSTD_MAGIC) {
+            let raw = Self::decompress(bytes)?;
+            return bincode::deserialize(&raw)
+                .map_err(|e| StoreError::Serialization(e.to_string()));
+        }
+        bincode::deserialize(bytes).map_err(|e| StoreError::Serialization(e.to_string()))
+    }
+
+    /// Like `deserialize`, but fails if any bytes are left over
+    fn deserialize_exact<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        use bincode::Options;
+        let options = bincode::DefaultOptions::new().with_fixint_encoding();
+        let result = if bytes.starts_with(&ZSTD_MAGIC) {
+            options.deserialize(&Self::decompress(bytes)?)
+        } else {
+            options.deserialize(bytes)
+        };
+        result.map_err(|e| StoreError::Serialization(e.to_string()))
+    }
+
+    /// Serialize a value, compressing it if it crosses the threshold
+    fn encode<T: serde::Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        let bytes = Self::serialize(value)?;
+        match self.compression_threshold {
+            Some(threshold) if bytes.len() >= threshold => {
+                let compressed = zstd::encode_all(bytes.as_slice(), COMPRESSION_LEVEL)
+                    .map_err(|e| StoreError::Serialization(format!("compression failed: {e}")))?;
+                // Keep the raw form when compression doesn't pay off
+                if compressed.len() < bytes.len() {
+                    Ok(compressed)
+                } else {
+                    Ok(bytes)
+                }
+            }
+            _ => Ok(bytes),
+        }
+    }
+
+    fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+        zstd::decode_all(bytes)
+            .map_err(|e| StoreError::Serialization(format!("decompression failed: {e}")))
+    }
+
+    /// Record an event written by this store
+    fn log_event(&self, event: StateEvent) -> Result<()> {
+        let event = self.stamp_event(event)?;
+        self.record_event(event)
+    }
+
+    /// Make this instance the event's origin and count it on the clock
+    fn stamp_event(&self, mut event: StateEvent) -> Result<StateEvent> {
+        let instance = self.instance_id()?;
+        event.clock = self.advance_clock(|clock| {
+            clock.tick(&instance);
+        })?;
+        event.origin = Some(instance);
+        Ok(event)
+    }
+
+    /// Change the stored clock atomically, returning the new one
+    fn advance_clock(&self, change: impl Fn(&mut VectorClock)) -> Result<VectorClock> {
+        let updated = self.metadata_tree()?.update_and_fetch(VECTOR_CLOCK_KEY, |old| {
+            let mut clock: VectorClock =
+                old.and_then(|bytes| serde_json::from_slice(bytes).ok()).unwrap_or_default();
+            change(&mut clock);
+            serde_json::to_vec(&clock).ok()
+        })?;
+        updated
+            .map(|bytes| serde_json::from_slice(&bytes))
+            .transpose()
+            .map_err(|e| StoreError::Serialization(e.to_string()))
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Store an event as it is, without stamping it
+    fn record_event(&self, event: StateEvent) -> Result<()> {
+        self.register_namespace()?;
+        let events = self.events_tree()?;
+        let key = event.id.to_bytes();
+        let value = self.encode(&event)?;
+        self.metrics.add_bytes(value.len());
+        events.insert(key, value)?;
+        self.bump_stats(&[Counter::Agent(&event.agent), Counter::Day(event.timestamp)], 1)?;
+        self.hooks.run(&event);
+        Ok(())
+    }
+
+    fn add_to_index(&self, tree: &sled::Tree, index_key: &[u8], id: &[u8]) -> Result<()> {
+        let _timer = self.metrics.start("index:list");
+        let mut ids: Vec<Vec<u8>> = tree
+            .get(index_key)?
+            .map(|v| Self::deserialize(&v))
+            .transpose()?
+            .unwrap_or_default();
+
+        if !ids.iter().any(|existing| existing == id) {
+            ids.push(id.to_vec());
+            let bytes = Self::serialize(&ids)?;
+            self.metrics.add_bytes(bytes.len());
+            tree.insert(index_key, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// `add_to_index` or `remove_from_index` within a transaction
+    fn index_in_transaction(
+        tree: &TransactionalTree,
+        index_key: &[u8],
+        id: &[u8],
+        insert: bool,
+    ) -> ConflictableTransactionResult<(), StoreError> {
+        let mut ids: Vec<Vec<u8>> = match tree.get(index_key)? {
+            Some(value) => Self::deserialize(&value).map_err(ConflictableTransactionError::Abort)?,
+            None => Vec::new(),
+        };
+        let present = ids.iter().any(|existing| existing == id);
+        match insert {
+            true if present => return Ok(()),
+            true => ids.push(id.to_vec()),
+            false => ids.retain(|existing| existing != id),
+        }
+        if ids.is_empty() {
+            tree.remove(index_key)?;
+        } else {
+            tree.insert(index_key, Self::serialize(&ids).map_err(ConflictableTransactionError::Abort)?)?;
+        }
+        Ok(())
+    }
+
+    fn remove_from_index(&self, tree: &sled::Tree, index_key: &[u8], id: &[u8]) -> Result<()> {
+        let _timer = self.metrics.start("index:list");
+        if let Some(value) = tree.get(index_key)? {
+            let mut ids: Vec<Vec<u8>> = Self::deserialize(&value)?;
+            ids.retain(|existing| existing != id);
+            if ids.is_empty() {
+                tree.remove(index_key)?;
+            } else {
+                let bytes = Self::serialize(&ids)?;
+                self.metrics.add_bytes(bytes.len());
+                tree.insert(index_key, bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Store for SledStore {
+    fn create_node(&self, mut node: StateNode, agent: AgentId) -> Result<StateNode> {
+        let _timer = self.metrics.start("create_node");
+        self.ensure_writable()?;
+        self.content_schemas()?.check(&node.kind, &node.content)?;
+        self.agent_defaults()?.apply(&agent, &mut node);
+        self.retention_policy()?.apply_ttl(&mut node);
+        node.tags = normalize_tags(std::mem::take(&mut node.tags));
+        self.graph_constraints()?.check_node(self, &node, &[])?;
+        self.write_node(&node)?;
+
+        // Log event
+        let event = self.node_event(agent, Operation::Create, &node, None, Some(&node))?;
+        self.log_event(event)?;
+
+        Ok(node)
+    }
+
+    fn get_node(&self, id: NodeId) -> Result<Option<StateNode>> {
+        let _timer = self.metrics.start("get_node");
+        let nodes = self.nodes_tree()?;
+        let key = id.to_bytes();
+
+        match nodes.get(key)? {
+            Some(bytes) => Ok(Some(Self::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn update_node(
+        &self,
+        id: NodeId,
+        content: Value,
+        expected_version: Option<u64>,
+        agent: AgentId,
+    ) -> Result<StateNode> {
+        let _timer = self.metrics.start("update_node");
+        self.ensure_writable()?;
+        let schemas = self.content_schemas()?;
+
+        // Compare-and-swap so a concurrent writer can't be silently clobbered
+        let (old_node, new_node) = self.update_in_place(id, |node| {
+            if let Some(expected) = expected_version {
+                if node.version != expected {
+                    return Err(StoreError::Conflict(id, expected, node.version));
+                }
+            }
+            schemas.check(&node.kind, &content)?;
+            node.content = content.clone();
+            Ok(true)
+        })?;
+
+        self.reindex_content_hash(&old_node, &new_node)?;
+
+        // Log event
+        let event =
+            self.node_event(agent, Operation::Update, &new_node, Some(&old_node), Some(&new_node))?;
+        self.log_event(event)?;
+
+        Ok(new_node)
+    }
+
+    fn delete_node_with(&self, id: NodeId, agent: AgentId, mode: DeleteMode) -> Result<()> {
+        let _timer = self.metrics.start("delete_node_with");
+        self.ensure_writable()?;
+        let old_node = self.get_node(id)?.ok_or(StoreError::NodeNotFound(id))?;
+
+        let edges_from = self.stored_edges(&self.edges_by_from_tree()?, id)?;
+        let edges_to = self.stored_edges(&self.edges_by_to_tree()?, id)?;
+        if mode == DeleteMode::Restrict && !(edges_from.is_empty() && edges_to.is_empty()) {
+            return Err(StoreError::NodeHasEdges(id, edges_from.len() + edges_to.len()));
+        }
+
+        self.remove_node_extras(id)?;
+
+        // Delete connected edges (a self-loop appears in both lists), in
+        // one group with the node so an undo brings them back together
+        let group = (!(edges_from.is_empty() && edges_to.is_empty())).then(ulid::Ulid::new);
+        let mut deleted = HashSet::new();
+        for edge in edges_from.into_iter().chain(edges_to) {
+            // A reciprocal twin is deleted along with its edge
+            if deleted.insert(edge.id) && self.get_edge(edge.id)?.is_some() {
+                self.delete_edge_in(edge.id, agent.clone(), group)?;
+            }
+        }
+
+        self.unwrite_node(&old_node)?;
+
+        // Log event
+        let mut event = self.node_event(agent, Operation::Delete, &old_node, Some(&old_node), None)?;
+        event.group = group;
+        self.log_event(event)?;
+
+        Ok(())
+    }
+
+    fn list_nodes(&self, kind: Option<NodeKind>, limit: usize) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("list_nodes");
+        let nodes = self.nodes_tree()?;
+
+        match kind {
+            Some(k) => {
+                let nodes_by_kind = self.nodes_by_kind_tree()?;
+                let kind_key = k.to_string();
+
+                let ids: Vec<Vec<u8>> = nodes_by_kind
+                    .get(kind_key.as_bytes())?
+                    .map(|v| Self::deserialize(&v))
+                    .transpose()?
+                    .unwrap_or_default();
+
+                self.indexed(NODES_TREE, &nodes, ids.into_iter().take(limit))
+            }
+            None => nodes
+                .iter()
+                .take(limit)
+                .map(|entry| Self::deserialize(&entry?.1))
+                .collect(),
+        }
+    }
+
+    fn create_edge(&self, edge: StateEdge, agent: AgentId) -> Result<StateEdge> {
+        let _timer = self.metrics.start("create_edge");
+        self.ensure_writable()?;
+        if let Some(cycle) = cycles::would_close_cycle(self, &edge, &self.acyclic_kinds()?)? {
+            return Err(StoreError::WouldCycle(cycle));
+        }
+        self.graph_constraints()?.check_edge(self, &edge)?;
+        self.write_edge(&edge)?;
+        // The twin is checked with the edge in place, and refusing it refuses both
+        let twin = match self.reciprocal_twin(&edge) {
+            Ok(twin) => twin,
+            Err(e) => {
+                self.unwrite_edge(&edge)?;
+                return Err(e);
+            }
+        };
+
+        // Log event, in one group with a new twin
+        let group = matches!(twin, Some(Twin::New(_))).then(ulid::Ulid::new);
+        let mut event = StateEvent::new(agent.clone(), Operation::Link, Target::Edge(edge.id))
+            .with_after(serde_json::to_value(&edge).unwrap());
+        event.group = group;
+        self.log_event(event)?;
+
+        if let Some(twin) = twin {
+            self.pair_reciprocal(&edge, twin, agent, group)?;
+        }
+        Ok(edge)
+    }
+
+    fn get_edge(&self, id: EdgeId) -> Result<Option<StateEdge>> {
+        let _timer = self.metrics.start("get_edge");
+        let edges = self.edges_tree()?;
+        let key = id.to_bytes();
+
+        match edges.get(key)? {
+            Some(bytes) => Ok(Some(Self::deserialize(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn delete_edge(&self, id: EdgeId, agent: AgentId) -> Result<()> {
+        let _timer = self.metrics.start("delete_edge");
+        self.ensure_writable()?;
+        let group = self.reciprocal_of(id)?.map(|_| ulid::Ulid::new());
+        self.delete_edge_in(id, agent, group)
+    }
+
+    fn update_edge(&self, id: EdgeId, weight: Option<f32>, metadata: Metadata, agent: AgentId) -> Result<StateEdge> {
+        let _timer = self.metrics.start("update_edge");
+        self.ensure_writable()?;
+        let twin = self.reciprocal_of(id)?;
+        let group = twin.map(|_| ulid::Ulid::new());
+        let edge = self.update_one_edge(id, weight, &metadata, agent.clone(), group)?;
+        if let Some(twin) = twin {
+            if self.get_edge(twin)?.is_some() {
+                self.update_one_edge(twin, weight, &metadata, agent, group)?;
+            }
+        }
+        Ok(edge)
+    }
+
+    fn edges_from(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        let _timer = self.metrics.start("edges_from");
+        self.edges_with_mirrors(&self.edges_by_from_tree()?, &self.edges_by_to_tree()?, node_id)
+    }
+
+    fn edges_to(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
+        let _timer = self.metrics.start("edges_to");
+        self.edges_with_mirrors(&self.edges_by_to_tree()?, &self.edges_by_from_tree()?, node_id)
+    }
+
+    fn add_annotation(&self, annotation: Annotation) -> Result<Annotation> {
+        let _timer = self.metrics.start("add_annotation");
+        self.ensure_writable()?;
+        if !self.nodes_tree()?.contains_key(annotation.node_id.to_bytes())? {
+            return Err(StoreError::NodeNotFound(annotation.node_id));
+        }
+
+        let key = Self::annotation_key(annotation.node_id, annotation.id);
+        self.annotations_tree()?
+            .insert(key, Self::serialize(&annotation)?)?;
+        Ok(annotation)
+    }
+
+    fn annotations(&self, node_id: NodeId) -> Result<Vec<Annotation>> {
+        let _timer = self.metrics.start("annotations");
+        self.annotations_tree()?
+            .scan_prefix(node_id.to_bytes())
+            .map(|entry| {
+                let (_, bytes) = entry?;
+                Self::deserialize(&bytes)
+            })
+            .collect()
+    }
+
+    fn delete_annotation(&self, node_id: NodeId, id: AnnotationId) -> Result<()> {
+        let _timer = self.metrics.start("delete_annotation");
+        self.ensure_writable()?;
+        self.annotations_tree()?
+            .remove(Self::annotation_key(node_id, id))?
+            .ok_or(StoreError::AnnotationNotFound(id))?;
+        Ok(())
+    }
+
+    fn react(&self, reaction: Reaction) -> Result<ReactionCounts> {
+        let _timer = self.metrics.start("react");
+        self.ensure_writable()?;
+        if !self.nodes_tree()?.contains_key(reaction.node_id.to_bytes())? {
+            return Err(StoreError::NodeNotFound(reaction.node_id));
+        }
+
+        let key = Self::reaction_key(reaction.node_id, reaction.kind, &reaction.agent);
+        self.reactions_tree()?
+            .insert(key, Self::serialize(&reaction)?)?;
+        self.reaction_counts(reaction.node_id)
+    }
+
+    fn unreact(&self, node_id: NodeId, agent: &AgentId, kind: ReactionKind) -> Result<ReactionCounts> {
+        let _timer = self.metrics.start("unreact");
+        self.ensure_writable()?;
+        self.reactions_tree()?
+            .remove(Self::reaction_key(node_id, kind, agent))?;
+        self.reaction_counts(node_id)
+    }
+
+    fn reactions(&self, node_id: NodeId) -> Result<Vec<Reaction>> {
+        let _timer = self.metrics.start("reactions");
+        self.reactions_tree()?
+            .scan_prefix(node_id.to_bytes())
+            .map(|entry| {
+                let (_, bytes) = entry?;
+                Self::deserialize(&bytes)
+            })
+            .collect()
+    }
+
+    fn get_events(
+        &self,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        limit: usize,
+    ) -> Result<Vec<StateEvent>> {
+        let _timer = self.metrics.start("get_events");
+        let events = self.events_tree()?;
+
+        let iter = events.iter().rev(); // Newest first (ULID is time-sortable)
+
+        self.decoded::<StateEvent>(EVENTS_TREE, iter)
+            .filter(|e| {
+                e.as_ref().map_or(true, |e| since.map(|s| e.timestamp >= s).unwrap_or(true))
+            })
+            .take(limit)
+            .collect()
+    }
+
+    fn node_as_of(&self, id: NodeId, at: chrono::DateTime<chrono::Utc>) -> Result<Option<StateNode>> {
+        let _timer = self.metrics.start("node_as_of");
+        history::node_as_of(&self.events_until(at)?, id, at)
+    }
+
+    fn node_versions(&self, id: NodeId) -> Result<Vec<NodeVersion>> {
+        let _timer = self.metrics.start("node_versions");
+        history::node_versions(&self.events_until(chrono::Utc::now())?, id)
+    }
+
+    fn edges_as_of(&self, id: NodeId, at: chrono::DateTime<chrono::Utc>) -> Result<Vec<StateEdge>> {
+        let _timer = self.metrics.start("edges_as_of");
+        history::edges_as_of(&self.events_until(at)?, id, at)
+    }
+
+    fn count_nodes(&self, kind: Option<NodeKind>) -> Result<usize> {
+        let _timer = self.metrics.start("count_nodes");
+        match kind {
+            Some(k) => Ok(self
+                .nodes_by_kind_tree()?
+                .get(k.to_string().as_bytes())?
+                .map(|v| Self::deserialize::<Vec<Vec<u8>>>(&v))
+                .transpose()?
+                .map_or(0, |ids| ids.len())),
+            None => Ok(self.nodes_tree()?.len()),
+        }
+    }
+
+    fn count_edges(&self) -> Result<usize> {
+        let _timer = self.metrics.start("count_edges");
+        Ok(self.edges_tree()?.len())
+    }
+
+    fn count_events(&self) -> Result<usize> {
+        let _timer = self.metrics.start("count_events");
+        Ok(self.events_tree()?.len())
+    }
+
+    fn search(&self, query: &str, kinds: Option<Vec<NodeKind>>) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("search");
+        let nodes = self.nodes_tree()?;
+        let query_lower = query.to_lowercase();
+        let extraction = self.text_extraction()?;
+
+        self.decoded::<StateNode>(NODES_TREE, nodes.iter())
+            .filter(|node| node.as_ref().map_or(true, |node| Self::matches_search(node, &query_lower, &kinds, &extraction)))
+            .collect()
+    }
+
+    fn neighbors(&self, id: NodeId, depth: usize) -> Result<Vec<StateNode>> {
+        let _timer = self.metrics.start("neighbors");
+        Ok(path::traverse(self, id, Direction::Both, None, depth)?
+            .into_iter()
+            .map(|reached| reached.node)
+            .collect())
+    }
+
+    fn traverse(
+        &self,
+        id: NodeId,
+        direction: Direction,
+        edge_kinds: Option<&[EdgeKind]>,
+        depth: usize,
+    ) -> Result<Vec<Reached>> {
+        let _timer = self.metrics.start("traverse");
+        path::traverse(self, id, direction, edge_kinds, depth)
+    }
+
+    fn shortest_path(
+        &self,
+        from: NodeId,
+        to: NodeId,
+        edge_kinds: Option<&[EdgeKind]>,
+        mode: PathMode,
+    ) -> Result<Option<GraphPath>> {
+        let _timer = self.metrics.start("shortest_path");
+        path::shortest_path(self, from, to, edge_kinds, mode)
+    }
+
+    fn subgraph(&self, root: NodeId, depth: usize, edge_kinds: Option<&[EdgeKind]>) -> Result<Subgraph> {
+        let _timer = self.metrics.start("subgraph");
+        path::subgraph(self, root, depth, edge_kinds)
+    }
+
+    fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{list_snapshots, PRE_RESTORE_PREFIX};
+    use crate::store::test_util::{link, nodes};
+
+    #[test]
+    fn test_node_crud() {
+        let store = SledStore::open_temporary().unwrap();
+
+        let node = StateNode::new(
+            NodeKind::Project,
+            serde_json::json!({"name": "test"}),
+        );
+        let id = node.id;
+
+        // Create
+        let created = store.create_node(node, AgentId::User).unwrap();
+        assert_eq!(created.id, id);
+
+        // Read
+        let retrieved = store.get_node(id).unwrap().unwrap();
+        assert_eq!(retrieved.id, id);
+
+        // Update
+        let updated = store
+            .update_node(id, serde_json::json!({"name": "updated"}), None, AgentId::User)
+            .unwrap();
+        assert_eq!(updated.content["name"], "updated");
+
+        // Delete
+        store.delete_node(id, AgentId::User).unwrap();
+        assert!(store.get_node(id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_edge_operations() {
+        let store = SledStore::open_temporary().unwrap();
+
+        let node1 = store
+            .create_node(
+                StateNode::new(NodeKind::Project, serde_json::json!({"name": "A"})),
+                AgentId::User,
+            )
+            .unwrap();
+        let node2 = store
+            .create_node(
+                StateNode::new(NodeKind::Task, serde_json::json!({"name": "B"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let edge = StateEdge::new(node1.id, node2.id, EdgeKind::PartOf);
+        let created = store.create_edge(edge, AgentId::User).unwrap();
+
+        let edges_from = store.edges_from(node1.id).unwrap();
+        assert_eq!(edges_from.len(), 1);
+        assert_eq!(edges_from[0].id, created.id);
+
+        let edges_to = store.edges_to(node2.id).unwrap();
+        assert_eq!(edges_to.len(), 1);
+    }
+
+    #[test]
+    fn test_update_edge() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User).unwrap();
+        let b = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User).unwrap();
+        let metadata = Metadata::from([("reason".to_string(), serde_json::json!("shared schema"))]);
+        let edge = store
+            .create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks).with_metadata(metadata), AgentId::User)
+            .unwrap();
+
+        let fields = Metadata::from([
+            ("reason".to_string(), Value::Null),
+            ("source".to_string(), serde_json::json!("review")),
+        ]);
+        let updated = store.update_edge(edge.id, Some(0.25), fields, AgentId::Claude).unwrap();
+        assert_eq!(updated.weight, 0.25);
+        assert!(!updated.metadata.contains_key("reason"));
+        assert_eq!(updated.metadata["source"], "review");
+        assert_eq!(store.edges_from(a.id).unwrap()[0].weight, 0.25);
+        assert!(matches!(
+            store.update_edge(ulid::Ulid::new(), None, Metadata::new(), AgentId::User),
+            Err(StoreError::EdgeNotFound(_))
+        ));
+
+        let events = crate::event::EventSourcer::new(&store).all_events().unwrap();
+        let update = events.iter().find(|e| matches!(e.target, Target::Edge(_)) && e.operation == Operation::Update).unwrap();
+        assert_eq!(update.before.as_ref().unwrap()["weight"], 1.0);
+
+        let replica = SledStore::open_temporary().unwrap();
+        for event in &events {
+            replica.apply_event(event).unwrap();
+        }
+        assert_eq!(replica.get_edge(edge.id).unwrap().unwrap().metadata["source"], "review");
+    }
+
+    #[test]
+    fn test_large_content_is_compressed() {
+        let store = SledStore::open_temporary()
+            .unwrap()
+            .with_compression_threshold(Some(256));
+
+        let text = "compressible ".repeat(200);
+        let node = StateNode::new(NodeKind::Context, serde_json::json!({"text": text}));
+        let id = node.id;
+        store.create_node(node, AgentId::User).unwrap();
+
+        let retrieved = store.get_node(id).unwrap().unwrap();
+        assert_eq!(retrieved.content["text"], text);
+
+        let stats = store.compression_stats().unwrap();
+        assert_eq!(stats.compressed_values, 1);
+        assert!(stats.ratio() > 1.0);
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let store = SledStore::open_temporary().unwrap();
+
+        let transient = store
+            .create_node(
+                StateNode::new(NodeKind::Context, serde_json::json!({"scratch": true}))
+                    .with_ttl(chrono::Duration::seconds(-1)),
+                AgentId::Claude,
+            )
+            .unwrap();
+        let durable = store
+            .create_node(
+                StateNode::new(NodeKind::Project, serde_json::json!({"name": "keep"})),
+                AgentId::User,
+            )
+            .unwrap();
+        store
+            .create_edge(
+                StateEdge::new(transient.id, durable.id, EdgeKind::PartOf),
+                AgentId::Claude,
+            )
+            .unwrap();
+
+        let purged = store.purge_expired(chrono::Utc::now()).unwrap();
+        assert_eq!(purged, vec![transient.id]);
+        assert!(store.get_node(transient.id).unwrap().is_none());
+        assert!(store.get_node(durable.id).unwrap().is_some());
+        assert!(store.edges_to(durable.id).unwrap().is_empty());
+
+        // Events minted in one millisecond don't sort by ID, so find the delete
+        let events = store.get_events(None, usize::MAX).unwrap();
+        let delete = events
+            .iter()
+            .find(|e| matches!(e.target, Target::Node(id) if id == transient.id) && e.operation == Operation::Delete)
+            .unwrap();
+        assert_eq!(delete.agent, AgentId::System);
+    }
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let store = SledStore::open_temporary().unwrap();
+        let alpha = store.namespaced("alpha").unwrap();
+        let beta = store.namespaced("beta").unwrap();
+
+        let node = alpha
+            .create_node(
+                StateNode::new(NodeKind::Project, serde_json::json!({"name": "alpha"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        assert!(alpha.get_node(node.id).unwrap().is_some());
+        assert!(beta.get_node(node.id).unwrap().is_none());
+        assert!(store.get_node(node.id).unwrap().is_none());
+        assert!(beta.get_events(None, 10).unwrap().is_empty());
+        assert_eq!(store.list_namespaces().unwrap(), vec!["alpha".to_string()]);
+
+        assert!(store.namespaced("bad::name").is_err());
+    }
+
+    #[test]
+    fn test_annotations_do_not_touch_node() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "sled is fast"})),
+                AgentId::Claude,
+            )
+            .unwrap();
+
+        let annotation = store
+            .add_annotation(
+                Annotation::new(node.id, AgentId::User, "citation needed")
+                    .with_anchor(AnnotationAnchor::TextRange { start: 0, end: 4 }),
+            )
+            .unwrap();
+
+        let annotations = store.annotations(node.id).unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].text, "citation needed");
+        assert_eq!(store.get_events(None, 10).unwrap().len(), 1);
+
+        store.delete_annotation(node.id, annotation.id).unwrap();
+        assert!(store.annotations(node.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reactions_are_counted_once_per_agent() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "sled is fast"})),
+                AgentId::Claude,
+            )
+            .unwrap();
+
+        store.react(Reaction::new(node.id, AgentId::User, ReactionKind::Useful)).unwrap();
+        store.react(Reaction::new(node.id, AgentId::User, ReactionKind::Useful)).unwrap();
+        store.react(Reaction::new(node.id, AgentId::Llama, ReactionKind::Disputed)).unwrap();
+        let counts = store
+            .react(Reaction::new(node.id, AgentId::Llama, ReactionKind::Outdated))
+            .unwrap();
+
+        assert_eq!(counts, ReactionCounts { useful: 1, outdated: 1, disputed: 1 });
+        assert!((counts.trust() - 0.5).abs() < f32::EPSILON);
+        assert!(counts.freshness() < 1.0);
+
+        let counts = store.unreact(node.id, &AgentId::Llama, ReactionKind::Disputed).unwrap();
+        assert_eq!(counts.disputed, 0);
+        assert!(counts.trust() > 0.5);
+
+        store.delete_node(node.id, AgentId::User).unwrap();
+        assert!(store.reactions(node.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_only_rejects_writes() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "hello"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let store = store.read_only();
+        assert!(store.get_node(node.id).unwrap().is_some());
+        assert!(matches!(
+            store.update_node(node.id, serde_json::json!({}), None, AgentId::User),
+            Err(StoreError::ReadOnly)
+        ));
+        assert!(matches!(
+            store.delete_node(node.id, AgentId::User),
+            Err(StoreError::ReadOnly)
+        ));
+        assert!(matches!(
+            store.set_meta("key", &serde_json::json!(1)),
+            Err(StoreError::ReadOnly)
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SledStore::open_temporary().unwrap();
+        let kept = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "keep"})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let info = store.create_snapshot(dir.path(), "before-agent").unwrap();
+        assert!(info.entries > 0);
+        assert!(store.create_snapshot(dir.path(), "before-agent").is_err());
+
+        let mess = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "mess"})),
+                AgentId::Llama,
+            )
+            .unwrap();
+        store.delete_node(kept.id, AgentId::Llama).unwrap();
+
+        let report = store.restore_snapshot(dir.path(), "before-agent").unwrap();
+        assert_eq!(report.restored.name, "before-agent");
+        assert!(store.get_node(kept.id).unwrap().is_some());
+        assert!(store.get_node(mess.id).unwrap().is_none());
+
+        // The replaced database was saved first, and can be restored in turn
+        let snapshots = list_snapshots(dir.path()).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].name, "before-agent");
+        assert!(report.previous.name.starts_with(PRE_RESTORE_PREFIX));
+        store.restore_snapshot(dir.path(), &report.previous.name).unwrap();
+        assert!(store.get_node(mess.id).unwrap().is_some());
+        assert!(store.get_node(kept.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compact_keeps_data() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(
+                StateNode::new(NodeKind::Insight, serde_json::json!({"text": "v0"})),
+                AgentId::User,
+            )
+            .unwrap();
+        for i in 1..50 {
+            store
+                .update_node(node.id, serde_json::json!({"text": format!("v{}", i)}), None, AgentId::User)
+                .unwrap();
+        }
+
+        let usage = store.tree_usage().unwrap();
+        let nodes = usage.iter().find(|t| t.name == NODES_TREE).unwrap();
+        assert_eq!(nodes.entries, 1);
+        assert_eq!(store.disk_usage().unwrap().live, usage.iter().map(|t| t.bytes).sum::<u64>());
+
+        let report = store.compact().unwrap();
+        assert!(report.entries > 0);
+        assert!(report.after.live > 0);
+        assert_eq!(
+            store.get_node(node.id).unwrap().unwrap().content["text"],
+            "v49"
+        );
+        assert!(matches!(store.read_only().compact(), Err(StoreError::ReadOnly)));
+    }
+
+    #[test]
+    fn test_update_version_conflict() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        assert_eq!(node.version, 1);
+
+        let claude = store
+            .update_node(node.id, serde_json::json!({"by": "claude"}), Some(1), AgentId::Claude)
+            .unwrap();
+        assert_eq!(claude.version, 2);
+
+        // Llama read version 1 too; its write must not clobber Claude's
+        assert!(matches!(
+            store.update_node(node.id, serde_json::json!({"by": "llama"}), Some(1), AgentId::Llama),
+            Err(StoreError::Conflict(_, 1, 2))
+        ));
+        assert_eq!(store.get_node(node.id).unwrap().unwrap().content["by"], "claude");
+
+        let forced = store
+            .update_node(node.id, serde_json::json!({"by": "llama"}), None, AgentId::Llama)
+            .unwrap();
+        assert_eq!(forced.version, 3);
+    }
+
+    #[test]
+    fn test_agent_defaults_stamped_on_create() {
+        let store = SledStore::open_temporary().unwrap();
+        let scraper = AgentId::Module("scraper".into());
+        let mut defaults = AgentDefaults::default();
+        defaults.set(&scraper, "source", serde_json::json!("web")).unwrap();
+        defaults.set(&scraper, "pipeline", serde_json::json!("v2")).unwrap();
+        assert!(defaults.set(&scraper, "pagerank", serde_json::json!(1.0)).is_err());
+        assert!(defaults.set(&scraper, "bad key", serde_json::json!("x")).is_err());
+        assert!(defaults.set(&scraper, "tags", serde_json::json!(["a"])).is_err());
+        store.set_agent_defaults(&defaults).unwrap();
+
+        let node = StateNode::new(NodeKind::Insight, serde_json::json!({}))
+            .with_metadata(Metadata::from([("source".to_string(), serde_json::json!("rss"))]));
+        let stamped = store.create_node(node, scraper.clone()).unwrap();
+        assert_eq!(stamped.metadata["source"], "rss");
+        assert_eq!(stamped.metadata["pipeline"], "v2");
+
+        let batch = vec![StateNode::new(NodeKind::Insight, serde_json::json!({"n": 1}))];
+        let created = store.create_nodes(batch, scraper, DedupeMode::Off).unwrap();
+        let DedupeOutcome::Created(node) = &created[0] else { panic!("expected a new node") };
+        assert_eq!(store.get_node(node.id).unwrap().unwrap().metadata["source"], "web");
+
+        let other = store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        assert!(other.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_patch_node() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = store
+            .create_node(
+                StateNode::new(NodeKind::Task, serde_json::json!({"title": "a", "done": false, "tags": ["x"]})),
+                AgentId::User,
+            )
+            .unwrap();
+
+        let merge = ContentPatch::detect(serde_json::json!({"done": true, "tags": null})).unwrap();
+        let patched = store.patch_node(node.id, &merge, AgentId::Claude).unwrap();
+        assert_eq!(patched.content, serde_json::json!({"title": "a", "done": true}));
+        assert_eq!(patched.version, 2);
+
+        let ops = ContentPatch::detect(serde_json::json!([
+            {"op": "test", "path": "/title", "value": "a"},
+            {"op": "replace", "path": "/title", "value": "b"}
+        ]))
+        .unwrap();
+        assert_eq!(store.patch_node(node.id, &ops, AgentId::Llama).unwrap().content["title"], "b");
+
+        // The `test` now fails, so nothing changes
+        assert!(matches!(store.patch_node(node.id, &ops, AgentId::Llama), Err(StoreError::InvalidOperation(_))));
+        assert_eq!(store.get_node(node.id).unwrap().unwrap().version, 3);
+        assert!(ContentPatch::detect(serde_json::json!([{"op": "bogus"}])).is_err());
+    }
+
+    #[test]
+    fn test_rewiring() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = |title: &str| {
+            store
+                .create_node(StateNode::new(NodeKind::Task, serde_json::json!({ "title": title })), AgentId::User)
+                .unwrap()
+                .id
+        };
+        let (old, new, parent, other, dep) = (node("old"), node("new"), node("parent"), node("other"), node("dep"));
+        let link = |from, to, kind| store.create_edge(StateEdge::new(from, to, kind), AgentId::User);
+        let part_of = link(old, parent, EdgeKind::PartOf).unwrap();
+        link(dep, old, EdgeKind::Blocks).unwrap();
+        link(old, new, EdgeKind::RelatedTo).unwrap();
+
+        let moved = store.replace_node(old, new, AgentId::Claude).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert!(store.edges_from(old).unwrap().is_empty() && store.edges_to(old).unwrap().is_empty());
+        assert_eq!(store.edges_to(new).unwrap()[0].from, dep);
+        assert_eq!(store.edges_from(new).unwrap()[0].id, part_of.id);
+
+        let events = crate::event::EventSourcer::new(&store).all_events().unwrap();
+        let group = events.iter().find_map(|e| e.group).unwrap();
+        assert_eq!(events.iter().filter(|e| e.group == Some(group)).count(), 5);
+
+        let edge = store.reparent(new, other, AgentId::Claude).unwrap();
+        let parents: Vec<NodeId> = store.edges_from(new).unwrap().into_iter().map(|e| e.to).collect();
+        assert_eq!(parents, vec![other]);
+        assert_eq!(edge.kind, EdgeKind::PartOf);
+
+        // Refused reparenting leaves the old parent in place and logs nothing
+        store.set_acyclic_kinds(&[EdgeKind::PartOf]).unwrap();
+        link(parent, new, EdgeKind::PartOf).unwrap();
+        let logged = store.count_events().unwrap();
+        assert!(matches!(store.reparent(new, parent, AgentId::Claude), Err(StoreError::WouldCycle(_))));
+        assert_eq!(store.edges_from(new).unwrap()[0].id, edge.id);
+        assert_eq!(store.count_events().unwrap(), logged);
+
+        // Constraints are checked with the old parent already gone
+        let mut constraints = GraphConstraints::default();
+        let one_parent = crate::store::Constraint::MaxDegree { edge: "part_of".into(), max: 1, incoming: false, node: None };
+        constraints.set("one-parent", one_parent).unwrap();
+        store.set_graph_constraints(&constraints).unwrap();
+        store.reparent(new, dep, AgentId::Claude).unwrap();
+        store.reparent(new, other, AgentId::Claude).unwrap();
+        store.set_graph_constraints(&GraphConstraints::default()).unwrap();
+
+        let parts = store
+            .split_node(other, vec![serde_json::json!({"title": "a"}), serde_json::json!({"title": "b"})], AgentId::Llama)
+            .unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].metadata[CHUNK_INDEX_KEY], 1);
+        assert_eq!(store.edges_to(other).unwrap().iter().filter(|e| e.kind == EdgeKind::PartOf).count(), 3);
+        assert!(store.split_node(other, vec![], AgentId::Llama).is_err());
+        assert!(store.check(false).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_graph_stats_counters() {
+        let store = SledStore::open_temporary().unwrap();
+        let task = |agent| store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), agent).unwrap();
+        let a = task(AgentId::User);
+        assert_eq!(store.graph_stats().unwrap().nodes(), 1);
+
+        // Counters now move with each write instead of being recounted
+        let b = task(AgentId::Claude);
+        store
+            .create_nodes(
+                vec![StateNode::new(NodeKind::Insight, serde_json::json!({}))],
+                AgentId::Claude,
+                DedupeMode::Off,
+            )
+            .unwrap();
+        let edge = store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.archive_nodes(&[b.id]).unwrap();
+        let counted = store.graph_stats().unwrap();
+        assert_eq!(counted.nodes_by_kind["task"], 1);
+        assert_eq!(counted.nodes_by_kind["insight"], 1);
+        assert_eq!(counted.edges_by_kind["blocks"], 1);
+        assert_eq!(counted.events_by_agent["claude"], 2);
+        assert_eq!(counted.events(), 4);
+        assert_eq!(counted.events_by_day.len(), 1);
+
+        store.delete_edge(edge.id, AgentId::User).unwrap();
+        let counted = store.graph_stats().unwrap();
+        assert!(counted.edges_by_kind.is_empty());
+        assert_eq!(counted, store.rebuild_stats().unwrap());
+
+        // Invalidated counters are recounted on the next read
+        store.invalidate_stats().unwrap();
+        store.nodes_tree().unwrap().remove(a.id.to_bytes()).unwrap();
+        assert_eq!(store.graph_stats().unwrap().nodes_by_kind.get("task"), None);
+    }
+
+    #[test]
+    fn test_capture_modes() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut policy = CapturePolicy::default();
+        policy.kinds.insert("task".into(), CaptureMode::Diff);
+        policy.kinds.insert("insight".into(), CaptureMode::Hash);
+        store.set_capture_policy(&policy).unwrap();
+
+        let big = "x".repeat(10_000);
+        let task = store
+            .create_node(
+                StateNode::new(NodeKind::Task, serde_json::json!({"body": big, "n": 1})),
+                AgentId::User,
+            )
+            .unwrap();
+        store.update_node(task.id, serde_json::json!({"body": big, "n": 2}), None, AgentId::User).unwrap();
+        let insight = store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User)
+            .unwrap();
+
+        let events = crate::event::EventSourcer::new(&store).all_events().unwrap();
+        let update = &events[1];
+        assert_eq!(update.capture, CaptureMode::Diff);
+        assert!(update.before.is_none());
+        assert!(update.after.as_ref().unwrap().to_string().len() < 1_000);
+        let hashed = &events[2];
+        assert_eq!(
+            hashed.after,
+            Some(Value::String(snapshot_digest(&serde_json::to_value(&insight).unwrap())))
+        );
+
+        // Diffs replay onto the created node; hash-only events cannot
+        let target = SledStore::open_temporary().unwrap();
+        target.apply_event(&events[0]).unwrap();
+        target.apply_event(update).unwrap();
+        let replayed = target.get_node(task.id).unwrap().unwrap();
+        assert_eq!(replayed.content["n"], 2);
+        assert_eq!(replayed.version, 2);
+        assert!(target.apply_event(hashed).is_err());
+    }
+
+    #[test]
+    fn test_hooks() {
+        use std::sync::Mutex;
+
+        let store = SledStore::open_temporary().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for (point, label) in [("create", "c"), ("update", "u"), ("delete", "d")] {
+            let seen = seen.clone();
+            let record = move |event: &StateEvent| {
+                seen.lock().unwrap().push(format!("{}:{:?}", label, event.operation))
+            };
+            match point {
+                "create" => store.on_create(record),
+                "update" => store.on_update(record),
+                _ => store.on_delete(record),
+            }
+        }
+
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.update_node(a.id, serde_json::json!({"done": true}), None, AgentId::User).unwrap();
+        store.delete_node(a.id, AgentId::User).unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["c:Create", "c:Create", "c:Link", "u:Update", "d:Unlink", "d:Delete"]
+        );
+
+        // Namespaced views share the registry
+        store
+            .namespaced("other")
+            .unwrap()
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        assert_eq!(seen.lock().unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_attachments() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let pdf = b"%PDF-1.7 not really".to_vec();
+
+        let first = store.put_attachment(a.id, &pdf, "application/pdf").unwrap();
+        let second = store.put_attachment(b.id, &pdf, "application/pdf").unwrap();
+        assert_eq!(first.hash, second.hash);
+        assert_eq!(first.size, pdf.len() as u64);
+        assert_eq!(store.open_tree(BLOBS_TREE).unwrap().len(), 1);
+        assert!(store.put_attachment(ulid::Ulid::new(), &pdf, "application/pdf").is_err());
+
+        let (meta, bytes) = store.get_attachment(a.id, &first.hash).unwrap().unwrap();
+        assert_eq!(meta.mime, "application/pdf");
+        assert_eq!(bytes, pdf);
+
+        // The blob survives until its last attachment goes
+        store.delete_node(a.id, AgentId::User).unwrap();
+        assert!(store.attachments(a.id).unwrap().is_empty());
+        assert!(store.get_attachment(b.id, &first.hash).unwrap().is_some());
+        store.remove_attachment(b.id, &first.hash).unwrap();
+        assert!(store.open_tree(BLOBS_TREE).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_counts() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User).unwrap();
+
+        assert_eq!(store.count_nodes(None).unwrap(), 3);
+        assert_eq!(store.count_nodes(Some(NodeKind::Task)).unwrap(), 2);
+        assert_eq!(store.count_nodes(Some(NodeKind::Project)).unwrap(), 0);
+        assert_eq!(store.count_edges().unwrap(), 1);
+        assert_eq!(store.count_events().unwrap(), 4);
+        assert_eq!(
+            store.count_nodes_by_kind().unwrap(),
+            vec![("insight".to_string(), 1), ("task".to_string(), 2)]
+        );
+
+        store.delete_node(a.id, AgentId::User).unwrap();
+        assert_eq!(store.count_nodes(Some(NodeKind::Task)).unwrap(), 1);
+        assert_eq!(store.count_edges().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_metadata_index() {
+        let store = SledStore::open_temporary().unwrap();
+        let tagged = |project: serde_json::Value| {
+            let mut metadata = Metadata::new();
+            metadata.insert("project".into(), project);
+            StateNode::new(NodeKind::Task, serde_json::json!({})).with_metadata(metadata)
+        };
+        let a = store.create_node(tagged(serde_json::json!("elegant-state")), AgentId::User).unwrap();
+        store.create_node(tagged(serde_json::json!("neurophone")), AgentId::User).unwrap();
+
+        let equals = MetaQuery::Equals("elegant-state".into());
+        let scanned = store.find_by_metadata("project", &equals, None).unwrap();
+        assert_eq!(store.create_metadata_index("metadata.project").unwrap(), 2);
+        let indexed = store.find_by_metadata("project", &equals, None).unwrap();
+        assert_eq!(scanned.len(), 1);
+        assert_eq!(indexed[0].id, a.id);
+
+        // Maintained on write
+        let b = store
+            .create_node(tagged(serde_json::json!(["elegant-state", "docs"])), AgentId::User)
+            .unwrap();
+        assert_eq!(store.find_by_metadata("project", &MetaQuery::parse("eleg*"), None).unwrap().len(), 2);
+        store.delete_node(a.id, AgentId::User).unwrap();
+        let remaining = store.find_by_metadata("project", &equals, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, b.id);
+
+        assert!(store.drop_metadata_index("project").unwrap());
+        assert!(store.metadata_indexes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut old = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "old news"}));
+        old.updated_at = chrono::Utc::now() - chrono::Duration::days(100);
+        let old = store.create_node(old, AgentId::User).unwrap();
+        let fresh = store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({"text": "fresh news"})), AgentId::User)
+            .unwrap();
+        store
+            .create_edge(StateEdge::new(fresh.id, old.id, EdgeKind::Supersedes), AgentId::User)
+            .unwrap();
+
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(30);
+        let candidates = store.archive_candidates(cutoff, None, Some("NEWS")).unwrap();
+        assert_eq!(candidates, [old.id]);
+        assert_eq!(store.archive_nodes(&candidates).unwrap(), [old.id]);
+
+        assert!(store.get_node(old.id).unwrap().is_none());
+        assert_eq!(store.list_nodes(Some(NodeKind::Insight), 10).unwrap().len(), 1);
+        assert_eq!(store.search("news", None).unwrap().len(), 1);
+        assert_eq!(store.search_archived("news", None).unwrap()[0].id, old.id);
+        assert!(store.orphan_edges().unwrap().is_empty());
+        assert!(store.check(false).unwrap().is_clean());
+
+        store.unarchive_node(old.id).unwrap();
+        assert_eq!(store.list_nodes(Some(NodeKind::Insight), 10).unwrap().len(), 2);
+        assert!(store.list_archived(None, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delete_modes() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store
+            .create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User)
+            .unwrap();
+        store
+            .create_edge(StateEdge::new(a.id, a.id, EdgeKind::RelatedTo), AgentId::User)
+            .unwrap();
+
+        assert!(matches!(
+            store.delete_node_with(a.id, AgentId::User, DeleteMode::Restrict),
+            Err(StoreError::NodeHasEdges(_, 3))
+        ));
+        assert!(store.get_node(a.id).unwrap().is_some());
+
+        store.delete_node(a.id, AgentId::User).unwrap();
+        assert!(store.edges_to(b.id).unwrap().is_empty());
+        assert!(store.orphan_edges().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prune_orphan_edges() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let edge = store
+            .create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User)
+            .unwrap();
+
+        // Simulate a database written before deletes cascaded
+        store.nodes_tree().unwrap().remove(b.id.to_bytes()).unwrap();
+
+        let pruned = store.prune_orphan_edges(AgentId::System).unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].id, edge.id);
+        assert!(store.edges_from(a.id).unwrap().is_empty());
+        assert!(store.orphan_edges().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_share_links() {
+        let store = SledStore::open_temporary().unwrap();
+        let project = store
+            .create_node(StateNode::new(NodeKind::Project, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let task = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        store
+            .create_edge(StateEdge::new(task.id, project.id, EdgeKind::PartOf), AgentId::User)
+            .unwrap();
+
+        let (link, token) = store
+            .create_share(project.id, 1, chrono::Duration::hours(48), AgentId::User)
+            .unwrap();
+        let shared = store.open_share(&token).unwrap();
+        assert_eq!(shared.nodes.len(), 2);
+        assert_eq!(shared.edges.len(), 1);
+
+        store.revoke_share(link.id, AgentId::User).unwrap();
+        assert!(matches!(store.open_share(&token), Err(StoreError::ShareDenied(_))));
+
+        let actions: Vec<_> = store
+            .share_audit(Some(link.id))
+            .unwrap()
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(
+            actions,
+            [ShareAction::Created, ShareAction::Accessed, ShareAction::Revoked, ShareAction::Denied]
+        );
+    }
+
+    #[test]
+    fn test_check_repairs_dangling_edges() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User)
+            .unwrap();
+        let edge = store
+            .create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User)
+            .unwrap();
+        assert!(store.check(false).unwrap().is_clean());
+
+        // Remove a node behind the store's back and add an undecodable one
+        store.nodes_tree().unwrap().remove(b.id.to_bytes()).unwrap();
+        store
+            .nodes_tree()
+            .unwrap()
+            .insert(ulid::Ulid::new().to_bytes(), b"not a node".to_vec())
+            .unwrap();
+
+        let report = store.check(false).unwrap();
+        let kinds: Vec<_> = report.issues.iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&CheckIssueKind::DanglingEdge));
+        assert!(kinds.contains(&CheckIssueKind::Corrupt));
+        assert!(kinds.contains(&CheckIssueKind::StaleIndexEntry));
+        assert_eq!(report.unfixed(), report.issues.len());
+
+        let report = store.check(true).unwrap();
+        assert!(report.issues.iter().filter(|i| i.fixed).count() >= 3);
+        assert!(store.edges_tree().unwrap().get(edge.id.to_bytes()).unwrap().is_none());
+        assert!(store.edges_from(a.id).unwrap().is_empty());
+
+        // Only the unexplained disappearance of `b` remains
+        let report = store.check(false).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, CheckIssueKind::UnresolvableEvent);
+    }
+
+    #[test]
+    fn test_event_logging() {
+        let store = SledStore::open_temporary().unwrap();
+
+        let node = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "hello"}));
+        store.create_node(node, AgentId::Claude).unwrap();
+
+        let events = store.get_events(None, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].agent, AgentId::Claude);
+        assert_eq!(events[0].operation, Operation::Create);
+    }
+
+    #[test]
+    fn test_dedupe_by_content_hash() {
+        let store = SledStore::open_temporary().unwrap();
+        let doc = || StateNode::new(NodeKind::Context, serde_json::json!({"text": "same", "n": 1}));
+        let first = store
+            .create_node_deduped(doc(), AgentId::User, DedupeMode::Reuse)
+            .unwrap()
+            .into_node();
+
+        // Key order does not change the hash
+        let reordered = StateNode::new(NodeKind::Context, serde_json::json!({"n": 1, "text": "same"}));
+        let reused = store.create_node_deduped(reordered, AgentId::User, DedupeMode::Reuse).unwrap();
+        assert!(reused.is_reused());
+        assert_eq!(reused.node().id, first.id);
+        assert_eq!(store.count_nodes(None).unwrap(), 1);
+
+        // Other kinds are not duplicates
+        let insight = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "same", "n": 1}));
+        assert!(!store.create_node_deduped(insight, AgentId::User, DedupeMode::Reuse).unwrap().is_reused());
+
+        match store.create_node_deduped(doc(), AgentId::User, DedupeMode::Link).unwrap() {
+            DedupeOutcome::Linked { node, original, edge } => {
+                assert_eq!(original, first.id);
+                assert_eq!((edge.from, edge.to, edge.kind), (node.id, first.id, EdgeKind::DerivedFrom));
             }
+            other => panic!("expected a link, got {:?}", other),
         }
-        Ok(())
-    }
-}
 
-impl Store for SledStore {
-    fn create_node(&self, node: StateNode, agent: AgentId) -> Result<StateNode> {
-        let nodes = self.nodes_tree()?;
-        let nodes_by_kind = self.nodes_by_kind_tree()?;
+        // Updates move the node to its new hash
+        store.update_node(first.id, serde_json::json!({"text": "changed"}), None, AgentId::User).unwrap();
+        let changed = StateNode::new(NodeKind::Context, serde_json::json!({"text": "changed"}));
+        assert_eq!(store.find_duplicate(&changed).unwrap().map(|n| n.id), Some(first.id));
+        assert_ne!(store.find_duplicate(&doc()).unwrap().map(|n| n.id), Some(first.id));
 
-        let key = node.id.to_bytes();
-        let value = Self::serialize(&node)?;
+        store.delete_node(first.id, AgentId::User).unwrap();
+        assert!(store.find_duplicate(&changed).unwrap().is_none());
+        assert!(store.check(false).unwrap().is_clean());
+    }
 
-        nodes.insert(&key, value)?;
+    #[test]
+    fn test_schema_migration() {
+        let store = SledStore::open_temporary().unwrap();
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        assert!(store.pending_migrations().unwrap().is_empty());
 
-        // Index by kind
-        let kind_key = node.kind.to_string();
-        self.add_to_index(&nodes_by_kind, kind_key.as_bytes(), &key)?;
+        // Write records in the version 1 layout into an unstamped database
+        let node = StateNode::new(NodeKind::Task, serde_json::json!({"title": "old"}));
+        let legacy_node = migrate::v1::StateNode {
+            id: node.id,
+            kind: node.kind.clone(),
+            content: node.content.clone(),
+            metadata: node.metadata.clone(),
+            created_at: node.created_at,
+            updated_at: node.updated_at,
+            expires_at: None,
+        };
+        let event = StateEvent::new(AgentId::User, Operation::Create, Target::Node(node.id));
+        let legacy_event = migrate::v1::StateEvent {
+            id: event.id,
+            timestamp: event.timestamp,
+            agent: event.agent.clone(),
+            operation: event.operation.clone(),
+            target: event.target.clone(),
+            before: None,
+            after: Some(node.content.clone()),
+        };
+        let ns = store.namespaced("old").unwrap();
+        ns.register_namespace().unwrap();
+        ns.nodes_tree()
+            .unwrap()
+            .insert(node.id.to_bytes(), SledStore::serialize(&legacy_node).unwrap())
+            .unwrap();
+        ns.events_tree()
+            .unwrap()
+            .insert(event.id.to_bytes(), SledStore::serialize(&legacy_event).unwrap())
+            .unwrap();
+        store.db.remove(SCHEMA_VERSION_KEY).unwrap();
 
-        // Log event
-        let event = StateEvent::new(agent, Operation::Create, Target::Node(node.id))
-            .with_after(serde_json::to_value(&node).unwrap());
-        self.log_event(event)?;
+        assert_eq!(store.schema_version().unwrap(), migrate::UNSTAMPED_VERSION);
+        assert_eq!(store.pending_migrations().unwrap().len(), 8);
+        assert!(ns.get_node(node.id).is_err());
 
-        Ok(node)
-    }
+        // Stepwise: only the node migration
+        let reports = store.migrate(Some(2)).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].rewritten, 1);
+        assert_eq!(ns.get_node(node.id).unwrap().unwrap().version, 1);
+        // Undecodable events are skipped
+        assert!(ns.get_events(None, 10).unwrap().is_empty());
 
-    fn get_node(&self, id: NodeId) -> Result<Option<StateNode>> {
-        let nodes = self.nodes_tree()?;
-        let key = id.to_bytes();
+        let reports = store.migrate(Some(3)).unwrap();
+        assert_eq!(reports.len(), 1);
+        let events = ns.get_events(None, 10).unwrap();
+        assert_eq!(events[0].capture, CaptureMode::Full);
 
-        match nodes.get(key)? {
-            Some(bytes) => Ok(Some(Self::deserialize(&bytes)?)),
-            None => Ok(None),
-        }
-    }
+        // An event in the version 3 layout, written before groups existed
+        let hashed = StateEvent::new(AgentId::Claude, Operation::Update, Target::Node(node.id));
+        let v3_event = migrate::v3::StateEvent {
+            id: hashed.id,
+            timestamp: hashed.timestamp,
+            agent: hashed.agent.clone(),
+            operation: hashed.operation.clone(),
+            target: hashed.target.clone(),
+            before: None,
+            after: Some(serde_json::json!("digest")),
+            capture: CaptureMode::Hash,
+        };
+        ns.events_tree()
+            .unwrap()
+            .insert(hashed.id.to_bytes(), SledStore::serialize(&v3_event).unwrap())
+            .unwrap();
 
-    fn update_node(&self, id: NodeId, content: Value, agent: AgentId) -> Result<StateNode> {
-        let nodes = self.nodes_tree()?;
-        let key = id.to_bytes();
+        // And a node in the version 5 layout, from before tags
+        let untagged = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "old"}));
+        let v5_node = migrate::v5::StateNode {
+            id: untagged.id,
+            kind: untagged.kind.clone(),
+            content: untagged.content.clone(),
+            metadata: untagged.metadata.clone(),
+            created_at: untagged.created_at,
+            updated_at: untagged.updated_at,
+            expires_at: None,
+            version: 4,
+        };
+        ns.nodes_tree()
+            .unwrap()
+            .insert(untagged.id.to_bytes(), SledStore::serialize(&v5_node).unwrap())
+            .unwrap();
 
-        let old_node: StateNode = nodes
-            .get(&key)?
-            .map(|bytes| Self::deserialize(&bytes))
-            .transpose()?
-            .ok_or(StoreError::NodeNotFound(id))?;
+        // And one in the version 7 layout, from before pins
+        let unpinned = StateNode::new(NodeKind::Context, serde_json::json!({})).with_tags(["kept"]);
+        let v7_node = migrate::v7::StateNode {
+            id: unpinned.id,
+            kind: unpinned.kind.clone(),
+            content: unpinned.content.clone(),
+            metadata: unpinned.metadata.clone(),
+            created_at: unpinned.created_at,
+            updated_at: unpinned.updated_at,
+            expires_at: None,
+            version: 2,
+            tags: unpinned.tags.clone(),
+        };
+        ns.nodes_tree()
+            .unwrap()
+            .insert(unpinned.id.to_bytes(), SledStore::serialize(&v7_node).unwrap())
+            .unwrap();
 
-        let mut new_node = old_node.clone();
-        new_node.content = content;
-        new_node.updated_at = chrono::Utc::now();
+        // And a pinned one in the version 8 layout, from before retention classes
+        let pinned = StateNode::new(NodeKind::Context, serde_json::json!({})).with_pinned(true);
+        let v8_node = migrate::v8::StateNode {
+            id: pinned.id,
+            kind: pinned.kind.clone(),
+            content: pinned.content.clone(),
+            metadata: pinned.metadata.clone(),
+            created_at: pinned.created_at,
+            updated_at: pinned.updated_at,
+            expires_at: None,
+            version: 1,
+            tags: Vec::new(),
+            pinned: true,
+        };
+        ns.nodes_tree()
+            .unwrap()
+            .insert(pinned.id.to_bytes(), SledStore::serialize(&v8_node).unwrap())
+            .unwrap();
 
-        nodes.insert(&key, Self::serialize(&new_node)?)?;
+        let reports = store.migrate(None).unwrap();
+        assert_eq!(reports.len(), 6);
+        assert_eq!(reports[0].rewritten, 1);
+        assert_eq!(reports[1].rewritten, 0);
+        assert_eq!(reports[2].rewritten, 1);
+        // Both nodes were written behind the indexes' back
+        assert_eq!(reports[3].rewritten, 2);
+        assert_eq!(reports[4].rewritten, 1);
+        assert_eq!(reports[5].rewritten, 1);
+        let upgraded = ns.get_node(untagged.id).unwrap().unwrap();
+        assert_eq!((upgraded.version, upgraded.tags.len()), (4, 0));
+        let upgraded = ns.get_node(unpinned.id).unwrap().unwrap();
+        assert_eq!((upgraded.tags, upgraded.pinned), (vec!["kept".to_string()], false));
+        let upgraded = ns.get_node(pinned.id).unwrap().unwrap();
+        assert_eq!((upgraded.pinned, upgraded.retention), (true, None));
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        let events = ns.get_events(None, 10).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().any(|e| e.capture == CaptureMode::Hash));
+        assert!(events.iter().all(|e| e.group.is_none() && e.origin.is_none()));
 
-        // Log event
-        let event = StateEvent::new(agent, Operation::Update, Target::Node(id))
-            .with_before(serde_json::to_value(&old_node).unwrap())
-            .with_after(serde_json::to_value(&new_node).unwrap());
-        self.log_event(event)?;
+        // Nothing left to do, and no going back
+        assert!(store.migrate(None).unwrap().is_empty());
+        assert!(store.migrate(Some(3)).is_err());
 
-        Ok(new_node)
+        // Databases from a newer build are refused
+        store.stamp_schema(SCHEMA_VERSION + 1).unwrap();
+        assert!(matches!(store.check_schema(), Err(StoreError::UnsupportedSchema(..))));
     }
 
-    fn delete_node(&self, id: NodeId, agent: AgentId) -> Result<()> {
-        let nodes = self.nodes_tree()?;
-        let nodes_by_kind = self.nodes_by_kind_tree()?;
-        let key = id.to_bytes();
-
-        let old_node: StateNode = nodes
-            .get(&key)?
-            .map(|bytes| Self::deserialize(&bytes))
-            .transpose()?
-            .ok_or(StoreError::NodeNotFound(id))?;
+    #[test]
+    fn test_vector_clocks() {
+        let a = SledStore::open_temporary().unwrap();
+        let b = SledStore::open_temporary().unwrap();
+        let (a_id, b_id) = (a.instance_id().unwrap(), b.instance_id().unwrap());
 
-        // Remove from kind index
-        let kind_key = old_node.kind.to_string();
-        self.remove_from_index(&nodes_by_kind, kind_key.as_bytes(), &key)?;
+        let node = a
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"title": "shared"})), AgentId::User)
+            .unwrap();
+        let created = a.get_events(None, 10).unwrap().remove(0);
+        assert_eq!(created.origin.as_deref(), Some(a_id.as_str()));
+        assert_eq!(created.clock.get(&a_id), 1);
 
-        // Delete connected edges
-        for edge in self.edges_from(id)? {
-            self.delete_edge(edge.id, agent.clone())?;
-        }
-        for edge in self.edges_to(id)? {
-            self.delete_edge(edge.id, agent.clone())?;
-        }
+        // b replicates the create, then both edit without hearing from the other
+        b.apply_event(&created).unwrap();
+        assert_eq!(b.vector_clock().unwrap(), created.clock);
+        b.update_node(node.id, serde_json::json!({"title": "b"}), None, AgentId::Claude).unwrap();
+        a.update_node(node.id, serde_json::json!({"title": "a"}), None, AgentId::User).unwrap();
 
-        nodes.remove(&key)?;
+        let latest = |store: &SledStore, origin: &str| {
+            store
+                .get_events(None, 10)
+                .unwrap()
+                .into_iter()
+                .find(|e| e.operation == Operation::Update && e.origin.as_deref() == Some(origin))
+                .unwrap()
+        };
+        let from_b = latest(&b, &b_id);
+        let from_a = latest(&a, &a_id);
+        assert!(created.clock < from_b.clock);
+        assert!(created.clock < from_a.clock);
+        assert!(from_a.clock.concurrent_with(&from_b.clock));
 
-        // Log event
-        let event = StateEvent::new(agent, Operation::Delete, Target::Node(id))
-            .with_before(serde_json::to_value(&old_node).unwrap());
-        self.log_event(event)?;
+        // Zero counters, as merging leaves them, change nothing
+        let mut padded = created.clock.clone();
+        padded.merge(&VectorClock(BTreeMap::from([(b_id.clone(), 0)])));
+        assert_eq!(padded.0.len(), 2);
+        assert_eq!(padded, created.clock);
+        assert!(padded <= created.clock && padded >= created.clock);
 
-        Ok(())
+        // Applied events keep their origin; the clock takes in both histories
+        a.apply_event(&from_b).unwrap();
+        let clock = a.vector_clock().unwrap();
+        assert_eq!((clock.get(&a_id), clock.get(&b_id)), (2, 1));
+        assert_eq!(latest(&a, &b_id).clock, from_b.clock);
     }
 
-    fn list_nodes(&self, kind: Option<NodeKind>, limit: usize) -> Result<Vec<StateNode>> {
-        let nodes = self.nodes_tree()?;
+    #[test]
+    fn test_dump_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.dump");
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"title": "a"})), AgentId::User)
+            .unwrap();
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"title": "b"})), AgentId::User)
+            .unwrap();
+        store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.put_attachment(a.id, b"attached bytes", "text/plain").unwrap();
+        store.set_meta("proposals", &serde_json::json!([1, 2, 3])).unwrap();
+        let old = store
+            .create_node(StateNode::new(NodeKind::Context, serde_json::json!({"old": true})), AgentId::User)
+            .unwrap();
+        store.archive_nodes(&[old.id]).unwrap();
+        let ns = store.namespaced("team").unwrap();
+        let other = ns
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({"text": "ns"})), AgentId::User)
+            .unwrap();
 
-        match kind {
-            Some(k) => {
-                let nodes_by_kind = self.nodes_by_kind_tree()?;
-                let kind_key = k.to_string();
+        let dumped = store.dump_to(&path).unwrap();
+        assert_eq!(dumped.namespaces, 2);
+        assert_eq!((dumped.nodes, dumped.edges), (4, 1));
+        assert_eq!(dump::verify_dump(&path).unwrap().schema_version, SCHEMA_VERSION);
 
-                let ids: Vec<Vec<u8>> = nodes_by_kind
-                    .get(kind_key.as_bytes())?
-                    .map(|v| Self::deserialize(&v))
-                    .transpose()?
-                    .unwrap_or_default();
+        let target = SledStore::open_temporary().unwrap();
+        let loaded = target.load_from(&path).unwrap();
+        assert_eq!(loaded.records, dumped.records);
+        assert_eq!(target.get_node(a.id).unwrap().unwrap().content, a.content);
+        assert!(target.get_node(old.id).unwrap().is_none());
+        assert_eq!(target.get_archived(old.id).unwrap().unwrap().id, old.id);
+        assert_eq!(target.edges_from(a.id).unwrap().len(), 1);
+        assert_eq!(target.get_events(None, 10).unwrap().len(), store.get_events(None, 10).unwrap().len());
+        let (_, bytes) = target.get_attachment(a.id, &attachment::content_hash(b"attached bytes")).unwrap().unwrap();
+        assert_eq!(bytes, b"attached bytes");
+        assert_eq!(target.get_meta("proposals").unwrap(), Some(serde_json::json!([1, 2, 3])));
+        let target_ns = target.namespaced("team").unwrap();
+        assert_eq!(target_ns.get_node(other.id).unwrap().unwrap().kind, NodeKind::Insight);
+        assert!(target_ns.get_node(a.id).unwrap().is_none());
+        assert!(target.check(false).unwrap().is_clean());
 
-                ids.into_iter()
-                    .take(limit)
-                    .filter_map(|id| nodes.get(&id).ok().flatten())
-                    .map(|bytes| Self::deserialize(&bytes))
-                    .collect()
-            }
-            None => nodes
-                .iter()
-                .take(limit)
-                .filter_map(|r| r.ok())
-                .map(|(_, bytes)| Self::deserialize(&bytes))
-                .collect(),
-        }
+        // Non-empty targets are refused
+        assert!(target.load_from(&path).is_err());
+
+        // Any change to the body breaks the checksum
+        let raw = zstd::decode_all(std::fs::File::open(&path).unwrap()).unwrap();
+        let tampered = String::from_utf8(raw).unwrap().replacen("\"title\":\"a\"", "\"title\":\"z\"", 1);
+        std::fs::write(&path, zstd::encode_all(tampered.as_bytes(), 3).unwrap()).unwrap();
+        assert!(dump::verify_dump(&path).is_err());
+        assert!(SledStore::open_temporary().unwrap().load_from(&path).is_err());
     }
 
-    fn create_edge(&self, edge: StateEdge, agent: AgentId) -> Result<StateEdge> {
-        let edges = self.edges_tree()?;
-        let edges_by_from = self.edges_by_from_tree()?;
-        let edges_by_to = self.edges_by_to_tree()?;
+    #[test]
+    fn test_store_metrics() {
+        let store = SledStore::open_temporary().unwrap();
+        store.create_metadata_index("project").unwrap();
+        let mut metadata = Metadata::new();
+        metadata.insert("project".into(), serde_json::json!("elegant"));
+        let node = StateNode::new(NodeKind::Task, serde_json::json!({"title": "t"})).with_metadata(metadata);
+        let node = store.create_node(node, AgentId::User).unwrap();
+        store.namespaced("other").unwrap().get_node(node.id).unwrap();
+        store.get_node(node.id).unwrap();
 
-        let key = edge.id.to_bytes();
-        let value = Self::serialize(&edge)?;
+        let metrics = store.metrics();
+        let create = metrics.get("create_node").unwrap();
+        assert_eq!(create.calls, 1);
+        assert!(create.bytes_written > 0);
+        assert!(metrics.get("index:list").unwrap().calls >= 2);
+        assert_eq!(metrics.get("index:metadata").unwrap().calls, 1);
+        // Namespaced views share the counters
+        assert_eq!(metrics.get("get_node").unwrap().calls, 2);
+        assert!(create.total_micros >= metrics.get("index:metadata").unwrap().total_micros);
 
-        edges.insert(&key, value)?;
+        store.metrics_registry().reset();
+        assert!(store.metrics().ops.is_empty());
+    }
 
-        // Index by from/to
-        self.add_to_index(&edges_by_from, &edge.from.to_bytes(), &key)?;
-        self.add_to_index(&edges_by_to, &edge.to.to_bytes(), &key)?;
+    #[test]
+    fn test_shortest_path() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = |text: &str| {
+            store
+                .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({ "text": text })), AgentId::User)
+                .unwrap()
+                .id
+        };
+        let (a, b, c, d, lonely) = (node("a"), node("b"), node("c"), node("d"), node("lonely"));
+        let link = |from, to, kind, weight| {
+            store.create_edge(StateEdge::new(from, to, kind).with_weight(weight), AgentId::User).unwrap();
+        };
+        // a -> b -> d is two hops; a -> c <- d is cheaper by weight
+        link(a, b, EdgeKind::References, 1.0);
+        link(b, d, EdgeKind::References, 1.0);
+        link(a, c, EdgeKind::Enables, 0.25);
+        link(d, c, EdgeKind::Enables, 0.25);
 
-        // Log event
-        let event = StateEvent::new(agent, Operation::Link, Target::Edge(edge.id))
-            .with_after(serde_json::to_value(&edge).unwrap());
-        self.log_event(event)?;
+        let path = store.shortest_path(a, d, None, PathMode::MinCost).unwrap().unwrap();
+        assert_eq!(path.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, c, d]);
+        assert_eq!(path.hops(), 2);
+        assert_eq!(path.cost, 0.5);
+        assert!(path.steps[0].forward);
+        assert!(!path.steps[1].forward);
 
-        Ok(edge)
-    }
+        let path = store.shortest_path(a, d, Some(&[EdgeKind::References]), PathMode::MinCost).unwrap().unwrap();
+        assert_eq!(path.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, b, d]);
+        assert_eq!(path.cost, 2.0);
 
-    fn get_edge(&self, id: EdgeId) -> Result<Option<StateEdge>> {
-        let edges = self.edges_tree()?;
-        let key = id.to_bytes();
+        // The strongest chain is the unit-weight one, not the cheap one
+        let path = store.shortest_path(a, d, None, PathMode::MaxProduct).unwrap().unwrap();
+        assert_eq!(path.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, b, d]);
+        assert_eq!(path.strength, 1.0);
+        let e = node("e");
+        link(b, e, EdgeKind::References, 0.0);
+        assert!(store.shortest_path(a, e, None, PathMode::MaxProduct).unwrap().is_none());
+        assert_eq!(store.shortest_path(a, e, None, PathMode::MinCost).unwrap().unwrap().cost, 1.0);
 
-        match edges.get(key)? {
-            Some(bytes) => Ok(Some(Self::deserialize(&bytes)?)),
-            None => Ok(None),
-        }
+        assert_eq!(store.shortest_path(a, a, None, PathMode::MinCost).unwrap().unwrap().hops(), 0);
+        assert!(store.shortest_path(a, lonely, None, PathMode::MinCost).unwrap().is_none());
+        assert!(store.shortest_path(a, d, Some(&[EdgeKind::Blocks]), PathMode::MinCost).unwrap().is_none());
+        assert!(matches!(
+            store.shortest_path(a, ulid::Ulid::new(), None, PathMode::MinCost),
+            Err(StoreError::NodeNotFound(_))
+        ));
     }
 
-    fn delete_edge(&self, id: EdgeId, agent: AgentId) -> Result<()> {
-        let edges = self.edges_tree()?;
-        let edges_by_from = self.edges_by_from_tree()?;
-        let edges_by_to = self.edges_by_to_tree()?;
-        let key = id.to_bytes();
-
-        let old_edge: StateEdge = edges
-            .get(&key)?
-            .map(|bytes| Self::deserialize(&bytes))
-            .transpose()?
-            .ok_or(StoreError::EdgeNotFound(id))?;
+    #[test]
+    fn test_traverse_filters() {
+        let store = SledStore::open_temporary().unwrap();
+        let node = |text: &str| {
+            store
+                .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({ "text": text })), AgentId::User)
+                .unwrap()
+                .id
+        };
+        let (root, child, grandchild, source, cousin) =
+            (node("root"), node("child"), node("grandchild"), node("source"), node("cousin"));
+        let link = |from, to, kind| {
+            store.create_edge(StateEdge::new(from, to, kind), AgentId::User).unwrap();
+        };
+        link(root, child, EdgeKind::References);
+        link(child, grandchild, EdgeKind::References);
+        link(source, root, EdgeKind::DerivedFrom);
+        link(root, cousin, EdgeKind::Enables);
+        // A second route to grandchild must not report it twice
+        link(cousin, grandchild, EdgeKind::Enables);
 
-        // Remove from indices
-        self.remove_from_index(&edges_by_from, &old_edge.from.to_bytes(), &key)?;
-        self.remove_from_index(&edges_by_to, &old_edge.to.to_bytes(), &key)?;
+        let ids = |reached: Vec<Reached>| reached.into_iter().map(|r| (r.node.id, r.depth)).collect::<Vec<_>>();
 
-        edges.remove(&key)?;
+        let out = store.traverse(root, Direction::Outgoing, Some(&[EdgeKind::References]), 3).unwrap();
+        assert_eq!(out[1].path.iter().map(|s| s.edge.to).collect::<Vec<_>>(), vec![child, grandchild]);
+        assert_eq!(ids(out), vec![(child, 1), (grandchild, 2)]);
 
-        // Log event
-        let event = StateEvent::new(agent, Operation::Unlink, Target::Edge(id))
-            .with_before(serde_json::to_value(&old_edge).unwrap());
-        self.log_event(event)?;
+        let incoming = store.traverse(root, Direction::Incoming, None, 3).unwrap();
+        assert!(!incoming[0].path[0].forward);
+        assert_eq!(ids(incoming), vec![(source, 1)]);
 
-        Ok(())
+        let both = store.traverse(root, Direction::Both, None, 2).unwrap();
+        assert_eq!(both.len(), 4);
+        assert_eq!(both.iter().filter(|r| r.node.id == grandchild).count(), 1);
+        assert_eq!(store.neighbors(root, 1).unwrap().len(), 3);
+        assert!(store.traverse(root, Direction::Both, None, 0).unwrap().is_empty());
     }
 
-    fn edges_from(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
-        let edges = self.edges_tree()?;
-        let edges_by_from = self.edges_by_from_tree()?;
+    #[test]
+    fn test_symmetric_kinds() {
+        let store = SledStore::open_temporary().unwrap();
+        let [a, b, c] = nodes(&store, NodeKind::Insight);
+        let related =
+            store.create_edge(StateEdge::new(a, b, EdgeKind::RelatedTo), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(a, c, EdgeKind::Blocks), AgentId::User).unwrap();
 
-        let ids: Vec<Vec<u8>> = edges_by_from
-            .get(node_id.to_bytes())?
-            .map(|v| Self::deserialize(&v))
-            .transpose()?
-            .unwrap_or_default();
+        let from_b = store.edges_from(b).unwrap();
+        assert_eq!(from_b.len(), 1);
+        assert_eq!((from_b[0].id, from_b[0].from, from_b[0].to), (related.id, b, a));
+        assert_eq!(store.edges_to(a).unwrap()[0].from, b);
+        assert!(store.edges_from(c).unwrap().is_empty());
+        assert_eq!(store.neighbors(b, 1).unwrap().len(), 1);
+        assert_eq!(store.traverse(b, Direction::Outgoing, None, 2).unwrap().len(), 2);
+        assert_eq!(store.subgraph(a, 1, None).unwrap().edges.len(), 2);
 
-        ids.into_iter()
-            .filter_map(|id| edges.get(&id).ok().flatten())
-            .map(|bytes| Self::deserialize(&bytes))
-            .collect()
+        assert!(matches!(store.set_acyclic_kinds(&[EdgeKind::RelatedTo]), Err(StoreError::InvalidOperation(_))));
+        store.set_symmetric_kinds(&[]).unwrap();
+        assert!(store.edges_from(b).unwrap().is_empty());
+        store.set_symmetric_kinds(&[EdgeKind::RelatedTo]).unwrap();
+
+        store.delete_node(b, AgentId::User).unwrap();
+        assert_eq!(store.edges_from(a).unwrap().len(), 1);
     }
 
-    fn edges_to(&self, node_id: NodeId) -> Result<Vec<StateEdge>> {
-        let edges = self.edges_tree()?;
-        let edges_by_to = self.edges_by_to_tree()?;
+    #[test]
+    fn test_subgraph() {
+        let store = SledStore::open_temporary().unwrap();
+        let [root, near, far, other] = nodes(&store, NodeKind::Task);
+        link(&store, root, near, EdgeKind::Blocks);
+        link(&store, far, near, EdgeKind::Blocks);
+        link(&store, near, root, EdgeKind::RelatedTo);
+        link(&store, root, other, EdgeKind::RelatedTo);
 
-        let ids: Vec<Vec<u8>> = edges_by_to
-            .get(node_id.to_bytes())?
-            .map(|v| Self::deserialize(&v))
-            .transpose()?
-            .unwrap_or_default();
+        let graph = store.subgraph(root, 1, None).unwrap();
+        assert_eq!(graph.nodes[0].id, root);
+        assert_eq!(graph.nodes.len(), 3);
+        // far is two hops out, so its edge is left behind
+        assert_eq!(graph.edges.len(), 3);
 
-        ids.into_iter()
-            .filter_map(|id| edges.get(&id).ok().flatten())
-            .map(|bytes| Self::deserialize(&bytes))
-            .collect()
+        let blocks = store.subgraph(root, 2, Some(&[EdgeKind::Blocks])).unwrap();
+        let ids: HashSet<NodeId> = blocks.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, HashSet::from([root, near, far]));
+        assert!(blocks.edges.iter().all(|e| e.kind == EdgeKind::Blocks));
+        assert_eq!(blocks.edges.len(), 2);
+        assert!(matches!(store.subgraph(NodeId::new(), 1, None), Err(StoreError::NodeNotFound(_))));
     }
 
-    fn get_events(
-        &self,
-        since: Option<chrono::DateTime<chrono::Utc>>,
-        limit: usize,
-    ) -> Result<Vec<StateEvent>> {
-        let events = self.events_tree()?;
+    #[test]
+    fn test_tags() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut node = StateNode::new(NodeKind::Task, serde_json::json!({"title": "ship"}));
+        node.tags = vec![" release".into(), "urgent".into(), "release".into(), "".into()];
+        let a = store.create_node(node, AgentId::User).unwrap();
+        assert_eq!(a.tags, ["release", "urgent"]);
+        let b = store
+            .create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})).with_tags(["release"]), AgentId::User)
+            .unwrap();
 
-        let iter = events.iter().rev(); // Newest first (ULID is time-sortable)
+        let ids = |nodes: Vec<StateNode>| nodes.into_iter().map(|n| n.id).collect::<HashSet<_>>();
+        assert_eq!(ids(store.nodes_by_tag("release", None, 10).unwrap()), HashSet::from([a.id, b.id]));
+        assert_eq!(ids(store.nodes_by_tag("release", Some(&NodeKind::Insight), 10).unwrap()), HashSet::from([b.id]));
+        assert_eq!(store.nodes_by_tag("release", None, 1).unwrap().len(), 1);
+        assert!(store.nodes_by_tag("releas", None, 10).unwrap().is_empty());
 
-        let events: Vec<StateEvent> = iter
-            .filter_map(|r| r.ok())
-            .map(|(_, bytes)| Self::deserialize(&bytes))
-            .filter_map(|r| r.ok())
-            .filter(|e: &StateEvent| {
-                since.map(|s| e.timestamp >= s).unwrap_or(true)
-            })
-            .take(limit)
-            .collect();
+        let a = store.update_tags(a.id, &["blocked".into()], &["urgent".into()], AgentId::User).unwrap();
+        assert_eq!(a.tags, ["blocked", "release"]);
+        assert_eq!(a.version, 2);
+        assert!(store.nodes_by_tag("urgent", None, 10).unwrap().is_empty());
+        // Nothing to change, nothing recorded
+        assert_eq!(store.update_tags(a.id, &["release".into()], &[], AgentId::User).unwrap().version, 2);
+        assert!(store.update_tags(a.id, &["\0".into()], &[], AgentId::User).is_err());
+        assert_eq!(
+            store.tag_counts().unwrap(),
+            BTreeMap::from([("blocked".to_string(), 1), ("release".to_string(), 2)])
+        );
 
-        Ok(events)
+        store.archive_nodes(&[b.id]).unwrap();
+        store.delete_node(a.id, AgentId::User).unwrap();
+        assert!(store.tag_counts().unwrap().is_empty());
     }
 
-    fn search(&self, query: &str, kinds: Option<Vec<NodeKind>>) -> Result<Vec<StateNode>> {
-        let nodes = self.nodes_tree()?;
-        let query_lower = query.to_lowercase();
+    #[test]
+    fn test_pinned_nodes() {
+        let store = SledStore::open_temporary().unwrap();
+        let context = |n: u64| StateNode::new(NodeKind::Context, serde_json::json!({})).with_id(ulid::Ulid::from_parts(n, 0));
+        let a = store.create_node(context(1).with_pinned(true), AgentId::User).unwrap();
+        let b = store.create_node(context(2), AgentId::User).unwrap();
+        let task = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({})).with_pinned(true), AgentId::User)
+            .unwrap();
 
-        let results: Vec<StateNode> = nodes
-            .iter()
-            .filter_map(|r| r.ok())
-            .map(|(_, bytes)| Self::deserialize::<StateNode>(&bytes))
-            .filter_map(|r| r.ok())
-            .filter(|node| {
-                // Filter by kind if specified
-                if let Some(ref ks) = kinds {
-                    if !ks.contains(&node.kind) {
-                        return false;
-                    }
-                }
-                // Simple text search in content
-                node.content.to_string().to_lowercase().contains(&query_lower)
-            })
-            .collect();
+        let b = store.set_pinned(b.id, true, AgentId::User).unwrap();
+        assert_eq!((b.pinned, b.version), (true, 2));
+        // Already pinned: nothing recorded
+        assert_eq!(store.set_pinned(b.id, true, AgentId::User).unwrap().version, 2);
+
+        let ids = |nodes: Vec<StateNode>| nodes.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(store.pinned_nodes(Some(&NodeKind::Context), 10).unwrap()), [a.id, b.id]);
+        assert_eq!(store.pinned_nodes(None, 10).unwrap().len(), 3);
 
-        Ok(results)
+        store.set_pinned(a.id, false, AgentId::User).unwrap();
+        store.delete_node(task.id, AgentId::User).unwrap();
+        assert_eq!(ids(store.pinned_nodes(None, 10).unwrap()), [b.id]);
     }
 
-    fn neighbors(&self, id: NodeId, depth: usize) -> Result<Vec<StateNode>> {
-        if depth == 0 {
-            return Ok(vec![]);
-        }
+    #[test]
+    fn test_retention_classes() {
+        let store = SledStore::open_temporary().unwrap();
+        let mut policy = store.retention_policy().unwrap();
+        policy.kinds.insert("context".into(), RetentionClass::Ephemeral);
+        policy.standard.archive_after_seconds = Some(3600);
+        store.set_retention_policy(&policy).unwrap();
+        let now = chrono::Utc::now();
+        let week = chrono::Duration::days(7);
 
-        let mut visited = std::collections::HashSet::new();
-        let mut result = Vec::new();
-        let mut current_level = vec![id];
+        // Ephemeral by kind: expires a week after creation, events hash-only
+        let scratch = store
+            .create_node(StateNode::new(NodeKind::Context, serde_json::json!({"n": 1})), AgentId::Claude)
+            .unwrap();
+        assert_eq!(scratch.expires_at, Some(scratch.created_at + week));
+        assert_eq!(store.get_events(None, 10).unwrap()[0].capture, CaptureMode::Hash);
 
-        for _ in 0..depth {
-            let mut next_level = Vec::new();
+        // A permanent node outlives its expiry and is never archived
+        let mut kept = StateNode::new(NodeKind::Task, serde_json::json!({}))
+            .with_retention(RetentionClass::Permanent)
+            .with_ttl(chrono::Duration::seconds(-1));
+        kept.updated_at = now - chrono::Duration::hours(2);
+        let kept = store.create_node(kept, AgentId::User).unwrap();
+        let mut stale = StateNode::new(NodeKind::Task, serde_json::json!({}));
+        stale.updated_at = now - chrono::Duration::hours(2);
+        let stale = store.create_node(stale, AgentId::User).unwrap();
+        assert!(store.expired_nodes(now).unwrap().is_empty());
+        assert!(store.purge_expired(now).unwrap().is_empty());
+        assert_eq!(store.archive_due(now).unwrap(), vec![stale.id]);
 
-            for node_id in current_level {
-                if visited.contains(&node_id) {
-                    continue;
-                }
-                visited.insert(node_id);
+        // Dumps leave out ephemeral nodes and their edges
+        store.create_edge(StateEdge::new(scratch.id, kept.id, EdgeKind::References), AgentId::Claude).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let summary = store.dump_to(&dir.path().join("state.dump")).unwrap();
+        assert_eq!((summary.nodes, summary.edges), (2, 0));
 
-                // Get outgoing edges
-                for edge in self.edges_from(node_id)? {
-                    if !visited.contains(&edge.to) {
-                        if let Some(node) = self.get_node(edge.to)? {
-                            result.push(node);
-                            next_level.push(edge.to);
-                        }
-                    }
-                }
+        // Overriding to ephemeral stamps the TTL; repeating it changes nothing
+        let task = store.set_retention(stale.id, Some(RetentionClass::Ephemeral), AgentId::User).unwrap();
+        assert_eq!(task.expires_at, Some(task.created_at + week));
+        let again = store.set_retention(stale.id, Some(RetentionClass::Ephemeral), AgentId::User).unwrap();
+        assert_eq!(again.version, task.version);
+        assert!(store.archive_due(now).unwrap().is_empty());
+    }
 
-                // Get incoming edges
-                for edge in self.edges_to(node_id)? {
-                    if !visited.contains(&edge.from) {
-                        if let Some(node) = self.get_node(edge.from)? {
-                            result.push(node);
-                            next_level.push(edge.from);
-                        }
-                    }
-                }
-            }
+    #[test]
+    fn test_latest_version() {
+        let store = SledStore::open_temporary().unwrap();
+        let insight = |n: u64| {
+            let node = StateNode::new(NodeKind::Insight, serde_json::json!({"v": n})).with_id(ulid::Ulid::from_parts(n, 0));
+            store.create_node(node, AgentId::Claude).unwrap()
+        };
+        let supersede = |new: &StateNode, old: &StateNode| {
+            store.create_edge(StateEdge::new(new.id, old.id, EdgeKind::Supersedes), AgentId::Claude).unwrap();
+        };
+        let (v1, v2, v3, other) = (insight(1), insight(2), insight(3), insight(4));
+        supersede(&v2, &v1);
+        supersede(&v3, &v2);
 
-            current_level = next_level;
-        }
+        let latest = |id| store.latest_version(id).unwrap().map(|n| n.id);
+        assert_eq!(latest(v1.id), Some(v3.id));
+        assert_eq!(latest(v3.id), Some(v3.id));
+        assert_eq!(latest(ulid::Ulid::from_parts(9, 0)), None);
 
-        Ok(result)
+        // Two successors: the later one wins; a cycle back doesn't loop
+        let mut fork = StateNode::new(NodeKind::Insight, serde_json::json!({"v": 5})).with_id(ulid::Ulid::from_parts(5, 0));
+        fork.created_at = v3.created_at + chrono::Duration::seconds(1);
+        let fork = store.create_node(fork, AgentId::Claude).unwrap();
+        supersede(&fork, &v2);
+        supersede(&v1, &fork);
+        assert_eq!(latest(v1.id), Some(fork.id));
+        assert_eq!(latest(v2.id), Some(fork.id));
+
+        let resolved = store.latest_versions(vec![v1.clone(), other.clone(), v2.clone()]).unwrap();
+        assert_eq!(resolved.iter().map(|n| n.id).collect::<Vec<_>>(), [fork.id, other.id]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_node_aliases() {
+        let store = SledStore::open_temporary().unwrap();
+        let project = |name: &str| {
+            store.create_node(StateNode::new(NodeKind::Project, serde_json::json!({"name": name})), AgentId::User).unwrap()
+        };
+        let (roadmap, other) = (project("roadmap"), project("other"));
+
+        store.set_alias(roadmap.id, "project-roadmap").unwrap();
+        assert_eq!(store.resolve_node("project-roadmap").unwrap(), roadmap.id);
+        assert_eq!(store.resolve_node(&other.id.to_string()).unwrap(), other.id);
+        assert!(matches!(store.resolve_node("nope"), Err(StoreError::UnknownNodeRef(_))));
+
+        // Unique, idempotent for the owner, and one per node
+        assert!(matches!(store.set_alias(other.id, "project-roadmap"), Err(StoreError::AliasTaken(_, id)) if id == roadmap.id));
+        store.set_alias(roadmap.id, "project-roadmap").unwrap();
+        store.set_alias(roadmap.id, "roadmap").unwrap();
+        assert_eq!(store.node_for_alias("project-roadmap").unwrap(), None);
+        assert_eq!(store.alias_of(roadmap.id).unwrap().as_deref(), Some("roadmap"));
+        store.set_alias(other.id, "project-roadmap").unwrap();
+        assert!(store.set_alias(ulid::Ulid::from_parts(1, 0), "ghost").is_err());
+        assert!(store.set_alias(other.id, "Not A Slug").is_err());
+
+        // Deleting a node frees its alias
+        store.delete_node(roadmap.id, AgentId::User).unwrap();
+        assert_eq!(store.node_for_alias("roadmap").unwrap(), None);
+        assert_eq!(store.aliases().unwrap(), vec![("project-roadmap".to_string(), other.id)]);
+        assert_eq!(store.remove_alias(other.id).unwrap().as_deref(), Some("project-roadmap"));
+        assert!(store.aliases().unwrap().is_empty());
+    }
 
     #[test]
-    fn test_node_crud() {
+    fn test_reciprocal_edges() {
         let store = SledStore::open_temporary().unwrap();
+        let [a, b, c, d] = nodes(&store, NodeKind::Task);
+        let blocked_by = EdgeKind::Custom("blocked_by".into());
+        let early = store.create_edge(StateEdge::new(c, d, EdgeKind::Blocks), AgentId::User).unwrap();
 
-        let node = StateNode::new(
-            NodeKind::Project,
-            serde_json::json!({"name": "test"}),
-        );
-        let id = node.id;
+        // Symmetric kinds already read both ways, and a kind pairs once
+        assert!(store.set_reciprocal_kinds(&[(EdgeKind::RelatedTo, EdgeKind::References)]).is_err());
+        let clash = [(EdgeKind::Blocks, blocked_by.clone()), (EdgeKind::Enables, EdgeKind::Blocks)];
+        assert!(store.set_reciprocal_kinds(&clash).is_err());
+        store.set_reciprocal_kinds(&[(EdgeKind::Blocks, blocked_by.clone())]).unwrap();
 
-        // Create
-        let created = store.create_node(node, AgentId::User).unwrap();
-        assert_eq!(created.id, id);
+        let edge = store.create_edge(StateEdge::new(a, b, EdgeKind::Blocks).with_weight(0.5), AgentId::User).unwrap();
+        let twin = store.reciprocal_of(edge.id).unwrap().unwrap();
+        assert_eq!(store.reciprocal_of(twin).unwrap(), Some(edge.id));
+        let twin_edge = store.get_edge(twin).unwrap().unwrap();
+        assert_eq!((twin_edge.from, twin_edge.to, &twin_edge.kind, twin_edge.weight), (b, a, &blocked_by, 0.5));
 
-        // Read
-        let retrieved = store.get_node(id).unwrap().unwrap();
-        assert_eq!(retrieved.id, id);
+        // Updates and deletes reach the twin
+        store.update_edge(edge.id, Some(0.9), Metadata::new(), AgentId::User).unwrap();
+        assert_eq!(store.get_edge(twin).unwrap().unwrap().weight, 0.9);
+        store.delete_edge(twin, AgentId::User).unwrap();
+        assert!(store.get_edge(edge.id).unwrap().is_none());
+        assert_eq!(store.reciprocal_of(edge.id).unwrap(), None);
 
-        // Update
-        let updated = store
-            .update_node(id, serde_json::json!({"name": "updated"}), AgentId::User)
-            .unwrap();
-        assert_eq!(updated.content["name"], "updated");
+        // Deleting a node removes both without tripping over the twin
+        store.create_edge(StateEdge::new(a, b, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.delete_node(a, AgentId::User).unwrap();
+        assert!(store.edges_from(b).unwrap().is_empty());
 
-        // Delete
-        store.delete_node(id, AgentId::User).unwrap();
-        assert!(store.get_node(id).unwrap().is_none());
+        // Edges from before the rule get twins on backfill
+        assert_eq!(store.reciprocal_of(early.id).unwrap(), None);
+        let created = store.backfill_reciprocals(AgentId::User).unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!((created[0].from, created[0].to), (d, c));
+        assert!(store.backfill_reciprocals(AgentId::User).unwrap().is_empty());
     }
 
     #[test]
-    fn test_edge_operations() {
+    fn test_sorted_listings() {
         let store = SledStore::open_temporary().unwrap();
+        let now = chrono::Utc::now();
+        // IDs fix creation order; update times are set days apart
+        let node = |n: u64, kind: NodeKind, age: i64| {
+            let mut node = StateNode::new(kind, serde_json::json!({})).with_id(ulid::Ulid::from_parts(n, 0));
+            node.updated_at = now - chrono::Duration::days(age);
+            store.create_node(node, AgentId::User).unwrap().id
+        };
+        let a = node(1, NodeKind::Task, 3);
+        let b = node(2, NodeKind::Insight, 1);
+        let c = node(3, NodeKind::Task, 2);
+        store.create_edge(StateEdge::new(c, a, EdgeKind::Blocks), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(c, b, EdgeKind::References), AgentId::User).unwrap();
 
-        let node1 = store
-            .create_node(
-                StateNode::new(NodeKind::Project, serde_json::json!({"name": "A"})),
-                AgentId::User,
-            )
+        let ids = |key: SortKey, descending: bool, kind: Option<NodeKind>| -> Vec<NodeId> {
+            let nodes = store.list_nodes_sorted(kind.as_ref(), Sort::new(key, descending), 10).unwrap();
+            nodes.iter().map(|n| n.id).collect()
+        };
+        assert_eq!(ids(SortKey::CreatedAt, false, None), [a, b, c]);
+        assert_eq!(ids(SortKey::CreatedAt, true, Some(NodeKind::Task)), [c, a]);
+        assert_eq!(ids(SortKey::UpdatedAt, true, None), [b, c, a]);
+        assert_eq!(ids(SortKey::Kind, false, None), [b, a, c]);
+        // Ties on degree fall back to ID, in the same direction
+        assert_eq!(ids(SortKey::Degree, true, None), [c, b, a]);
+        assert_eq!(store.list_nodes_sorted(None, Sort::new(SortKey::CreatedAt, true), 2).unwrap().len(), 2);
+
+        // An update moves the node to the front, leaving no stale entry behind
+        store.update_node(a, serde_json::json!({"done": true}), None, AgentId::User).unwrap();
+        assert_eq!(ids(SortKey::UpdatedAt, true, Some(NodeKind::Task)), [a, c]);
+        assert_eq!(ids(SortKey::UpdatedAt, false, None), [c, b, a]);
+
+        assert_eq!(store.list_events_sorted(Sort::new(SortKey::CreatedAt, false), 2).unwrap().len(), 2);
+        assert!(store.list_events_sorted(Sort::new(SortKey::Degree, false), 10).is_err());
+    }
+
+    #[test]
+    fn test_strict_mode_reports_degradations() {
+        let store = SledStore::open_temporary().unwrap();
+        let a = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"title": "ship"})), AgentId::User)
             .unwrap();
-        let node2 = store
-            .create_node(
-                StateNode::new(NodeKind::Task, serde_json::json!({"name": "B"})),
-                AgentId::User,
-            )
+        let b = store
+            .create_node(StateNode::new(NodeKind::Task, serde_json::json!({"title": "ship too"})), AgentId::User)
             .unwrap();
+        let garbage = ulid::Ulid::new();
+        store.nodes_tree().unwrap().insert(garbage.to_bytes(), b"not a node".to_vec()).unwrap();
 
-        let edge = StateEdge::new(node1.id, node2.id, EdgeKind::PartOf);
-        let created = store.create_edge(edge, AgentId::User).unwrap();
+        // Lenient: the undecodable record and the unindexed field are worked around
+        assert_eq!(store.search("ship", None).unwrap().len(), 2);
+        let query = MetaQuery::Equals("x".into());
+        assert!(store.find_by_metadata("project", &query, None).unwrap().is_empty());
 
-        let edges_from = store.edges_from(node1.id).unwrap();
-        assert_eq!(edges_from.len(), 1);
-        assert_eq!(edges_from[0].id, created.id);
+        let strict = store.clone().with_strict(true);
+        match strict.search("ship", None) {
+            Err(StoreError::Degraded(problem)) => assert!(problem.contains(&garbage.to_string())),
+            other => panic!("expected a degradation, got {:?}", other.map(|nodes| nodes.len())),
+        }
+        assert!(matches!(strict.find_by_metadata("project", &query, None), Err(StoreError::Degraded(_))));
+        store.nodes_tree().unwrap().remove(garbage.to_bytes()).unwrap();
+        strict.create_metadata_index("metadata.project").unwrap();
+        assert!(strict.find_by_metadata("project", &query, None).unwrap().is_empty());
 
-        let edges_to = store.edges_to(node2.id).unwrap();
-        assert_eq!(edges_to.len(), 1);
+        // A kind index entry whose node is gone
+        store.nodes_tree().unwrap().remove(b.id.to_bytes()).unwrap();
+        let ids: Vec<NodeId> = store.list_nodes(Some(NodeKind::Task), 10).unwrap().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![a.id]);
+        assert!(matches!(strict.list_nodes(Some(NodeKind::Task), 10), Err(StoreError::Degraded(_))));
     }
 
     #[test]
-    fn test_event_logging() {
+    fn test_undo_and_redo() {
         let store = SledStore::open_temporary().unwrap();
+        let a = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 1})), AgentId::User).unwrap();
+        let b = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({"n": 2})), AgentId::Claude).unwrap();
+        store.update_node(b.id, serde_json::json!({"n": 3}), None, AgentId::Claude).unwrap();
+        store.create_edge(StateEdge::new(a.id, b.id, EdgeKind::Blocks), AgentId::Claude).unwrap();
+        store.delete_node(a.id, AgentId::Claude).unwrap();
+        assert!(store.undo_last(&AgentId::Llama).unwrap().is_none());
 
-        let node = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "hello"}));
-        store.create_node(node, AgentId::Claude).unwrap();
+        // The delete and the edge it cascaded to come back together
+        let reversal = store.undo_last(&AgentId::Claude).unwrap().unwrap();
+        assert_eq!((reversal.reverted.len(), reversal.recorded.len()), (2, 2));
+        assert!(reversal.recorded.iter().all(|e| e.group.is_some() && e.group == reversal.recorded[0].group));
+        assert_eq!(store.get_node(a.id).unwrap().unwrap().content, a.content);
+        assert_eq!(store.edges_from(a.id).unwrap().len(), 1);
 
-        let events = store.get_events(None, 10).unwrap();
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].agent, AgentId::Claude);
-        assert_eq!(events[0].operation, Operation::Create);
+        // Then the link, then the update, restored as a new version
+        store.undo_last(&AgentId::Claude).unwrap().unwrap();
+        assert!(store.edges_from(a.id).unwrap().is_empty());
+        store.undo_last(&AgentId::Claude).unwrap().unwrap();
+        let restored = store.get_node(b.id).unwrap().unwrap();
+        assert_eq!((restored.content, restored.version), (serde_json::json!({"n": 2}), 3));
+
+        // Redo goes forward again, most recent undo first
+        store.redo_last(&AgentId::Claude).unwrap().unwrap();
+        assert_eq!(store.get_node(b.id).unwrap().unwrap().content, serde_json::json!({"n": 3}));
+        store.redo_last(&AgentId::Claude).unwrap().unwrap();
+        assert_eq!(store.edges_from(a.id).unwrap().len(), 1);
+
+        // Another agent's later change blocks the undo, which changes nothing
+        store.create_edge(StateEdge::new(b.id, a.id, EdgeKind::References), AgentId::Claude).unwrap();
+        assert!(store.redo_last(&AgentId::Claude).unwrap().is_none());
+        store.update_node(b.id, serde_json::json!({"n": 4}), None, AgentId::User).unwrap();
+        store.undo_last(&AgentId::Claude).unwrap().unwrap();
+        store.undo_last(&AgentId::Claude).unwrap().unwrap();
+        let events = store.count_events().unwrap();
+        assert!(matches!(store.undo_last(&AgentId::Claude), Err(StoreError::Conflict(..))));
+        assert_eq!(store.count_events().unwrap(), events);
+        assert_eq!(store.get_node(b.id).unwrap().unwrap().content, serde_json::json!({"n": 4}));
     }
 }
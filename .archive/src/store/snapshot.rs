@@ -0,0 +1,134 @@
+//! Named database snapshots
+//!
+//! A snapshot is a full copy of every sled tree (all namespaces included)
+//! written to `<dir>/<name>/db`, with a `snapshot.json` manifest beside it.
+//! Restoring replaces the live trees with the snapshot's contents, after
+//! saving the live database as a `pre-restore-<time>` snapshot of its own:
+//! the trees are cleared before the copy, so an interrupted restore can only
+//! be undone from there.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{Result, StoreError};
+
+const MANIFEST_FILE: &str = "snapshot.json";
+const DB_DIR: &str = "db";
+
+/// Manifest describing a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub trees: usize,
+    pub entries: u64,
+}
+
+/// Prefix of the snapshot a restore saves the live database to first
+pub const PRE_RESTORE_PREFIX: &str = "pre-restore-";
+
+/// A restored snapshot and the one holding the database it replaced
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub restored: SnapshotInfo,
+    pub previous: SnapshotInfo,
+}
+
+/// Name for the snapshot taken before restoring at `at`
+pub(crate) fn pre_restore_name(at: DateTime<Utc>) -> String {
+    format!("{}{}", PRE_RESTORE_PREFIX, at.format("%Y%m%dT%H%M%S%3fZ"))
+}
+
+/// Snapshot names double as directory names
+pub(crate) fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(StoreError::InvalidOperation(format!("Invalid snapshot name: {:?}", name)))
+    }
+}
+
+pub(crate) fn snapshot_db_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(name).join(DB_DIR)
+}
+
+pub(crate) fn write_manifest(dir: &Path, info: &SnapshotInfo) -> Result<()> {
+    let path = dir.join(&info.name).join(MANIFEST_FILE);
+    let json = serde_json::to_vec_pretty(info).map_err(|e| StoreError::Serialization(e.to_string()))?;
+    std::fs::write(&path, json)
+        .map_err(|e| StoreError::InvalidOperation(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+pub(crate) fn read_manifest(dir: &Path, name: &str) -> Result<SnapshotInfo> {
+    let path = dir.join(name).join(MANIFEST_FILE);
+    let json = std::fs::read(&path)
+        .map_err(|_| StoreError::InvalidOperation(format!("No snapshot named {:?}", name)))?;
+    serde_json::from_slice(&json).map_err(|e| StoreError::Serialization(e.to_string()))
+}
+
+/// List snapshots in `dir`, oldest first
+pub fn list_snapshots(dir: &Path) -> Result<Vec<SnapshotInfo>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(StoreError::InvalidOperation(format!(
+                "Failed to read {}: {}",
+                dir.display(),
+                e
+            )))
+        }
+    };
+
+    let mut snapshots: Vec<SnapshotInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| read_manifest(dir, &entry.file_name().to_string_lossy()).ok())
+        .collect();
+    snapshots.sort_by_key(|s| s.created_at);
+    Ok(snapshots)
+}
+
+/// Replace the contents of `to` with every tree in `from`
+///
+/// Returns the number of trees and entries copied.
+pub(crate) fn copy_db(from: &sled::Db, to: &sled::Db) -> Result<(usize, u64)> {
+    from.flush()?;
+    let source_names = from.tree_names();
+
+    for name in to.tree_names() {
+        if name == to.name() || source_names.contains(&name) {
+            tree(to, &name)?.clear()?;
+        } else {
+            to.drop_tree(&name)?;
+        }
+    }
+
+    let mut entries = 0u64;
+    for name in &source_names {
+        let source = tree(from, name)?;
+        let target = tree(to, name)?;
+        for entry in source.iter() {
+            let (key, value) = entry?;
+            target.insert(key, value)?;
+            entries += 1;
+        }
+    }
+
+    to.flush()?;
+    Ok((source_names.len(), entries))
+}
+
+/// Open a tree by name; the default tree cannot be opened with `open_tree`
+pub(crate) fn tree(db: &sled::Db, name: &[u8]) -> Result<sled::Tree> {
+    if name == &*db.name() {
+        Ok((**db).clone())
+    } else {
+        Ok(db.open_tree(name)?)
+    }
+}
@@ -0,0 +1,63 @@
+//! Sort orders for listings
+//!
+//! Node listings are served from indexes rather than by sorting a full
+//! scan: creation order is ID order (node IDs are ULIDs), kind order walks
+//! the kind index, and update order walks a time index kept on every write.
+//! Degree has no index of its own, but is read from the lengths of the edge
+//! index lists without decoding any node. Events sort by their time-ordered
+//! keys; proposals live in one metadata record and are sorted in memory.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    CreatedAt,
+    UpdatedAt,
+    Kind,
+    /// Number of edges at a node, either direction
+    Degree,
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            SortKey::CreatedAt => "created_at",
+            SortKey::UpdatedAt => "updated_at",
+            SortKey::Kind => "kind",
+            SortKey::Degree => "degree",
+        })
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "created_at" | "created" => Ok(SortKey::CreatedAt),
+            "updated_at" | "updated" => Ok(SortKey::UpdatedAt),
+            "kind" => Ok(SortKey::Kind),
+            "degree" => Ok(SortKey::Degree),
+            _ => Err(format!("Unknown sort key: {} (expected created_at, updated_at, kind or degree)", s)),
+        }
+    }
+}
+
+/// A sort key and direction; ties are broken by ID in the same direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    pub fn new(key: SortKey, descending: bool) -> Self {
+        Self { key, descending }
+    }
+
+    /// Put `items`, already in ascending order, in this sort's direction
+    pub(crate) fn apply<T>(&self, mut items: Vec<T>) -> Vec<T> {
+        if self.descending {
+            items.reverse();
+        }
+        items
+    }
+}
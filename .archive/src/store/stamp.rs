@@ -0,0 +1,117 @@
+//! Per-agent default metadata
+//!
+//! An agent can be configured with fields the store stamps onto every node
+//! it creates (`module:scraper` always gets `source=web`), so output stays
+//! consistently tagged even when the agent forgets. Defaults only fill
+//! fields the node leaves unset. They count as system metadata: field names
+//! are checked when configured, may not shadow fields the store's own tools
+//! write, and values must be scalars so they can be indexed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::chunks::CHUNK_INDEX_KEY;
+use super::cluster::CLUSTER_KEY;
+use super::rank::{BETWEENNESS_KEY, PAGERANK_KEY};
+use super::xref::{LINKS_KEY, OUTLINE_KEY};
+use super::{Result, StoreError};
+use crate::connector::PROVENANCE_KEY;
+use crate::render::CONTENT_TYPE_KEY;
+use crate::schema::{AgentId, StateNode};
+
+/// Metadata fields managed by the store and its tools; defaults can't set them
+pub const SYSTEM_FIELDS: &[&str] = &[
+    CONTENT_TYPE_KEY,
+    PROVENANCE_KEY,
+    OUTLINE_KEY,
+    LINKS_KEY,
+    CHUNK_INDEX_KEY,
+    PAGERANK_KEY,
+    BETWEENNESS_KEY,
+    CLUSTER_KEY,
+];
+
+/// Default metadata per agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentDefaults {
+    /// Fields keyed by agent name (`module:scraper`)
+    #[serde(default)]
+    pub agents: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl AgentDefaults {
+    pub fn for_agent(&self, agent: &AgentId) -> Option<&BTreeMap<String, Value>> {
+        self.agents.get(&agent.to_string())
+    }
+
+    /// Stamp `field` on nodes `agent` creates
+    pub fn set(&mut self, agent: &AgentId, field: &str, value: Value) -> Result<()> {
+        validate_field(field, &value)?;
+        self.agents.entry(agent.to_string()).or_default().insert(field.to_string(), value);
+        Ok(())
+    }
+
+    /// Stop stamping `field`; returns whether it was set
+    pub fn remove(&mut self, agent: &AgentId, field: &str) -> bool {
+        let key = agent.to_string();
+        let Some(fields) = self.agents.get_mut(&key) else {
+            return false;
+        };
+        let removed = fields.remove(field).is_some();
+        if fields.is_empty() {
+            self.agents.remove(&key);
+        }
+        removed
+    }
+
+    /// Drop all of `agent`'s defaults; returns whether it had any
+    pub fn clear(&mut self, agent: &AgentId) -> bool {
+        self.agents.remove(&agent.to_string()).is_some()
+    }
+
+    /// Check every configured field, as when loaded from a file
+    pub fn validate(&self) -> Result<()> {
+        for (agent, fields) in &self.agents {
+            agent.parse::<AgentId>().map_err(StoreError::InvalidOperation)?;
+            for (field, value) in fields {
+                validate_field(field, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill the fields `node` leaves unset with `agent`'s defaults
+    pub fn apply(&self, agent: &AgentId, node: &mut StateNode) {
+        if let Some(fields) = self.for_agent(agent) {
+            for (field, value) in fields {
+                node.metadata.entry(field.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+/// Check that `field` may be stamped with `value`
+pub fn validate_field(field: &str, value: &Value) -> Result<()> {
+    let valid_name = !field.is_empty()
+        && field.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid_name {
+        return Err(StoreError::InvalidOperation(format!(
+            "Invalid metadata field name: {:?}",
+            field
+        )));
+    }
+    if SYSTEM_FIELDS.contains(&field) {
+        return Err(StoreError::InvalidOperation(format!(
+            "Metadata field {} is managed by the store",
+            field
+        )));
+    }
+    match value {
+        Value::String(_) | Value::Number(_) | Value::Bool(_) => Ok(()),
+        _ => Err(StoreError::InvalidOperation(format!(
+            "Default for {} must be a string, number or boolean",
+            field
+        ))),
+    }
+}
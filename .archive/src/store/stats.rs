@@ -0,0 +1,127 @@
+//! Incrementally maintained graph counters
+//!
+//! Nodes per kind, edges per kind, and events per agent and per UTC day are
+//! kept as counters in their own tree, bumped by every write, so stats
+//! reads don't scan the graph. The tree carries a validity marker: paths
+//! that bypass the counters (repairs, dump loads, migrations) clear it, and
+//! the next read recounts from scratch.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::Result;
+use crate::schema::{AgentId, EdgeKind, NodeKind};
+
+pub(crate) const STATS_TREE: &str = "stats";
+
+/// Present while the counters match the data
+pub(crate) const VALID_KEY: &[u8] = b"valid";
+
+/// One counter in the stats tree
+pub(crate) enum Counter<'a> {
+    NodeKind(&'a NodeKind),
+    EdgeKind(&'a EdgeKind),
+    Agent(&'a AgentId),
+    Day(DateTime<Utc>),
+}
+
+impl Counter<'_> {
+    fn key(&self) -> Vec<u8> {
+        match self {
+            Counter::NodeKind(kind) => format!("node_kind/{}", kind),
+            Counter::EdgeKind(kind) => format!("edge_kind/{}", kind),
+            Counter::Agent(agent) => format!("agent/{}", agent),
+            Counter::Day(at) => format!("day/{}", at.format("%Y-%m-%d")),
+        }
+        .into_bytes()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphStats {
+    /// Live nodes per kind; archived nodes aren't counted
+    pub nodes_by_kind: BTreeMap<String, u64>,
+    pub edges_by_kind: BTreeMap<String, u64>,
+    pub events_by_agent: BTreeMap<String, u64>,
+    /// Events per UTC day, `YYYY-MM-DD`
+    pub events_by_day: BTreeMap<String, u64>,
+}
+
+impl GraphStats {
+    pub fn nodes(&self) -> u64 {
+        self.nodes_by_kind.values().sum()
+    }
+
+    pub fn edges(&self) -> u64 {
+        self.edges_by_kind.values().sum()
+    }
+
+    pub fn events(&self) -> u64 {
+        self.events_by_agent.values().sum()
+    }
+
+    pub(crate) fn count(&mut self, counter: Counter<'_>) {
+        let key = String::from_utf8(counter.key()).expect("counter keys are UTF-8");
+        let (section, name) = key.split_once('/').expect("counter keys have a section");
+        *self.section(section).entry(name.to_string()).or_default() += 1;
+    }
+
+    fn section(&mut self, section: &str) -> &mut BTreeMap<String, u64> {
+        match section {
+            "node_kind" => &mut self.nodes_by_kind,
+            "edge_kind" => &mut self.edges_by_kind,
+            "agent" => &mut self.events_by_agent,
+            _ => &mut self.events_by_day,
+        }
+    }
+}
+
+/// Add `delta` to each counter, if the counters are valid
+pub(crate) fn bump(tree: &sled::Tree, counters: &[Counter<'_>], delta: i64) -> Result<()> {
+    if !tree.contains_key(VALID_KEY)? {
+        return Ok(());
+    }
+    for counter in counters {
+        tree.update_and_fetch(counter.key(), |old| {
+            let value = old.map_or(0, decode) + delta;
+            (value != 0).then(|| value.to_be_bytes().to_vec())
+        })?;
+    }
+    Ok(())
+}
+
+/// Read valid counters back
+pub(crate) fn read(tree: &sled::Tree) -> Result<GraphStats> {
+    let mut stats = GraphStats::default();
+    for entry in tree.iter() {
+        let (key, value) = entry?;
+        let Some((section, name)) = std::str::from_utf8(&key).ok().and_then(|k| k.split_once('/')) else {
+            continue;
+        };
+        stats.section(section).insert(name.to_string(), decode(&value).max(0) as u64);
+    }
+    Ok(stats)
+}
+
+/// Replace the counters with `stats` and mark them valid
+pub(crate) fn write(tree: &sled::Tree, stats: &GraphStats) -> Result<()> {
+    tree.clear()?;
+    let sections = [
+        ("node_kind", &stats.nodes_by_kind),
+        ("edge_kind", &stats.edges_by_kind),
+        ("agent", &stats.events_by_agent),
+        ("day", &stats.events_by_day),
+    ];
+    for (section, counts) in sections {
+        for (name, count) in counts {
+            tree.insert(format!("{}/{}", section, name), (*count as i64).to_be_bytes().to_vec())?;
+        }
+    }
+    tree.insert(VALID_KEY, Vec::<u8>::new())?;
+    Ok(())
+}
+
+fn decode(bytes: &[u8]) -> i64 {
+    bytes.try_into().map(i64::from_be_bytes).unwrap_or(0)
+}
@@ -0,0 +1,189 @@
+//! Shape of the graph: degrees, connected components and path lengths
+//!
+//! Where `GraphStats` counts records, `analyze` looks at how they hang
+//! together. Edges are treated as undirected and only edges between live
+//! nodes count. An ingestion agent creating disconnected junk shows up as a
+//! growing number of isolated nodes and small components; average path
+//! length is estimated by breadth-first searches from a sample of nodes
+//! spread evenly over the ID space, so older and newer nodes both count.
+
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use super::{Result, SledStore, Store};
+use crate::schema::NodeId;
+
+/// Sources `graph stats` samples path lengths from by default
+pub const DEFAULT_PATH_SAMPLES: usize = 32;
+
+/// How many edges meet the nodes
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DegreeStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: usize,
+    /// Number of nodes with each degree
+    pub histogram: BTreeMap<usize, u64>,
+}
+
+/// Connected components, ignoring edge direction
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Components {
+    pub count: usize,
+    /// Nodes in the largest component
+    pub largest: usize,
+    /// Number of components of each size
+    pub sizes: BTreeMap<usize, u64>,
+}
+
+/// Hop counts between sampled nodes and everything they reach
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PathSample {
+    /// Nodes searched from
+    pub sources: usize,
+    /// Connected pairs measured
+    pub pairs: u64,
+    /// `None` when no sampled node reaches any other
+    pub mean_hops: Option<f64>,
+    /// Longest shortest path seen; a lower bound on the diameter
+    pub max_hops: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StructureReport {
+    pub nodes: usize,
+    pub edges: usize,
+    pub nodes_by_kind: BTreeMap<String, u64>,
+    pub edges_by_kind: BTreeMap<String, u64>,
+    pub degree: DegreeStats,
+    pub components: Components,
+    /// Nodes with no edges at all
+    pub isolated: usize,
+    pub isolated_by_kind: BTreeMap<String, u64>,
+    pub paths: PathSample,
+}
+
+/// Report the structure of the live graph, sampling path lengths from up
+/// to `samples` nodes
+pub fn analyze(store: &SledStore, samples: usize) -> Result<StructureReport> {
+    let nodes = store.list_nodes(None, usize::MAX)?;
+    let mut report = StructureReport { nodes: nodes.len(), ..Default::default() };
+
+    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = nodes.iter().map(|node| (node.id, Vec::new())).collect();
+    for node in &nodes {
+        *report.nodes_by_kind.entry(node.kind.to_string()).or_default() += 1;
+    }
+    for edge in store.list_edges()? {
+        if !adjacency.contains_key(&edge.from) || !adjacency.contains_key(&edge.to) {
+            continue;
+        }
+        report.edges += 1;
+        *report.edges_by_kind.entry(edge.kind.to_string()).or_default() += 1;
+        adjacency.get_mut(&edge.from).expect("checked above").push(edge.to);
+        adjacency.get_mut(&edge.to).expect("checked above").push(edge.from);
+    }
+
+    let mut degrees: Vec<usize> = Vec::with_capacity(nodes.len());
+    for node in &nodes {
+        let degree = adjacency[&node.id].len();
+        degrees.push(degree);
+        *report.degree.histogram.entry(degree).or_default() += 1;
+        if degree == 0 {
+            report.isolated += 1;
+            *report.isolated_by_kind.entry(node.kind.to_string()).or_default() += 1;
+        }
+    }
+    degrees.sort_unstable();
+    if let (Some(&min), Some(&max)) = (degrees.first(), degrees.last()) {
+        report.degree.min = min;
+        report.degree.max = max;
+        report.degree.median = degrees[degrees.len() / 2];
+        report.degree.mean = degrees.iter().sum::<usize>() as f64 / degrees.len() as f64;
+    }
+
+    let mut seen: HashSet<NodeId> = HashSet::new();
+    for node in &nodes {
+        if seen.contains(&node.id) {
+            continue;
+        }
+        let members = distances(&adjacency, node.id);
+        let size = members.len();
+        seen.extend(members.into_keys());
+        report.components.count += 1;
+        report.components.largest = report.components.largest.max(size);
+        *report.components.sizes.entry(size).or_default() += 1;
+    }
+
+    let mut ids: Vec<NodeId> = nodes.iter().map(|node| node.id).collect();
+    ids.sort();
+    let sources = samples.min(ids.len());
+    let mut total = 0u64;
+    for i in 0..sources {
+        let source = ids[i * ids.len() / sources];
+        for (_, hops) in distances(&adjacency, source).into_iter().filter(|&(id, _)| id != source) {
+            report.paths.pairs += 1;
+            total += hops as u64;
+            report.paths.max_hops = report.paths.max_hops.max(hops);
+        }
+    }
+    report.paths.sources = sources;
+    report.paths.mean_hops = (report.paths.pairs > 0).then(|| total as f64 / report.paths.pairs as f64);
+    Ok(report)
+}
+
+/// Hops from `start` to every node it reaches, itself included
+fn distances(adjacency: &HashMap<NodeId, Vec<NodeId>>, start: NodeId) -> HashMap<NodeId, usize> {
+    let mut reached = HashMap::from([(start, 0)]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        let hops = reached[&node] + 1;
+        for &next in adjacency.get(&node).into_iter().flatten() {
+            if let Entry::Vacant(entry) = reached.entry(next) {
+                entry.insert(hops);
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{EdgeKind, NodeKind};
+    use crate::store::test_util::{link, node, nodes};
+
+    #[test]
+    fn test_analyze_structure() {
+        let store = SledStore::open_temporary().unwrap();
+        let [a, b] = nodes(&store, NodeKind::Task);
+        let [c, d, e] = nodes(&store, NodeKind::Insight);
+        node(&store, NodeKind::Context);
+        // a - b - c in a chain, d - e apart, the context node alone
+        link(&store, a, b, EdgeKind::Blocks);
+        link(&store, c, b, EdgeKind::References);
+        link(&store, d, e, EdgeKind::References);
+
+        let report = analyze(&store, 100).unwrap();
+        assert_eq!((report.nodes, report.edges), (6, 3));
+        assert_eq!(report.edges_by_kind["references"], 2);
+        assert_eq!(report.degree.histogram, BTreeMap::from([(0, 1), (1, 4), (2, 1)]));
+        assert_eq!((report.degree.min, report.degree.max), (0, 2));
+        assert_eq!(report.degree.mean, 1.0);
+        assert_eq!(report.components.count, 3);
+        assert_eq!(report.components.largest, 3);
+        assert_eq!(report.components.sizes, BTreeMap::from([(1, 1), (2, 1), (3, 1)]));
+        assert_eq!(report.isolated, 1);
+        assert_eq!(report.isolated_by_kind["context"], 1);
+
+        // Ordered pairs: 6 in the chain (hops 1, 1, 2 each way) and 2 between d and e
+        assert_eq!(report.paths.sources, 6);
+        assert_eq!(report.paths.pairs, 8);
+        assert_eq!(report.paths.mean_hops, Some(10.0 / 8.0));
+        assert_eq!(report.paths.max_hops, 2);
+
+        assert_eq!(analyze(&store, 0).unwrap().paths.mean_hops, None);
+    }
+}
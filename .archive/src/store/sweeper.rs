@@ -0,0 +1,26 @@
+//! Background removal of expired nodes
+//!
+//! Nodes created with an `expires_at` are deleted by the sweeper once that
+//! time has passed. Deletions are logged as `AgentId::System`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::SledStore;
+
+/// Spawn a tokio task that purges expired nodes every `interval`
+pub fn spawn_expiry_sweeper(store: Arc<SledStore>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match store.purge_expired(chrono::Utc::now()) {
+                Ok(purged) if !purged.is_empty() => {
+                    tracing::info!("expiry sweeper removed {} node(s)", purged.len());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("expiry sweeper failed: {}", e),
+            }
+        }
+    })
+}
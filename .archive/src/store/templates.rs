@@ -0,0 +1,220 @@
+//! Node templates
+//!
+//! A template is a node skeleton, and the edges to link it with, saved
+//! under a name. Strings in its content, metadata and edge ends may hold
+//! `{{variable}}` placeholders, filled in when it is applied. A string
+//! that is nothing but one placeholder takes the variable's JSON value, so
+//! `"{{estimate}}"` can become a number; elsewhere the value is spliced in
+//! as text. Applying with a variable missing fails before anything is
+//! written.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Result, StoreError};
+use crate::schema::{EdgeKind, Metadata, NodeId, NodeKind, StateEdge, StateNode};
+
+/// An edge made along with the template's node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateEdge {
+    pub kind: EdgeKind,
+    /// The other end: a node ID, or a placeholder for one
+    pub node: String,
+    /// Link from `node` to the new node rather than the other way round
+    #[serde(default)]
+    pub incoming: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeTemplate {
+    pub kind: NodeKind,
+    pub content: Value,
+    #[serde(default)]
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub edges: Vec<TemplateEdge>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl NodeTemplate {
+    pub fn new(kind: NodeKind, content: Value) -> Self {
+        Self { kind, content, metadata: Metadata::new(), edges: Vec::new(), description: None }
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Link the new node to `node` (an ID or placeholder)
+    pub fn with_edge(mut self, kind: EdgeKind, node: impl Into<String>) -> Self {
+        self.edges.push(TemplateEdge { kind, node: node.into(), incoming: false });
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Names of the variables the template needs
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.fill_all(&BTreeMap::new(), &mut names);
+        names
+    }
+
+    /// The node and edges the template makes with `variables`
+    pub fn instantiate(&self, variables: &BTreeMap<String, Value>) -> Result<(StateNode, Vec<StateEdge>)> {
+        let mut missing = BTreeSet::new();
+        let (content, metadata, ends) = self.fill_all(variables, &mut missing);
+        if !missing.is_empty() {
+            let names: Vec<String> = missing.into_iter().collect();
+            return Err(StoreError::InvalidOperation(format!(
+                "Missing template variables: {}",
+                names.join(", ")
+            )));
+        }
+
+        let node = StateNode::new(self.kind.clone(), content).with_metadata(metadata);
+        let edges = self
+            .edges
+            .iter()
+            .zip(ends)
+            .map(|(edge, end)| {
+                let other: NodeId = end.as_str().and_then(|id| id.parse().ok()).ok_or_else(|| {
+                    StoreError::InvalidOperation(format!("Template edge end {} is not a node ID", end))
+                })?;
+                Ok(if edge.incoming {
+                    StateEdge::new(other, node.id, edge.kind.clone())
+                } else {
+                    StateEdge::new(node.id, other, edge.kind.clone())
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok((node, edges))
+    }
+
+    fn fill_all(
+        &self,
+        variables: &BTreeMap<String, Value>,
+        missing: &mut BTreeSet<String>,
+    ) -> (Value, Metadata, Vec<Value>) {
+        let content = fill(&self.content, variables, missing);
+        let metadata = self
+            .metadata
+            .iter()
+            .map(|(field, value)| (field.clone(), fill(value, variables, missing)))
+            .collect();
+        let ends = self.edges.iter().map(|edge| fill_text(&edge.node, variables, missing)).collect();
+        (content, metadata, ends)
+    }
+}
+
+/// Check a template name: non-empty, without whitespace
+pub(crate) fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(StoreError::InvalidOperation(format!("Invalid template name {:?}", name)));
+    }
+    Ok(())
+}
+
+fn fill(value: &Value, variables: &BTreeMap<String, Value>, missing: &mut BTreeSet<String>) -> Value {
+    match value {
+        Value::String(text) => fill_text(text, variables, missing),
+        Value::Array(items) => Value::Array(items.iter().map(|item| fill(item, variables, missing)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields.iter().map(|(field, value)| (field.clone(), fill(value, variables, missing))).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn fill_text(text: &str, variables: &BTreeMap<String, Value>, missing: &mut BTreeSet<String>) -> Value {
+    let mut lookup = |name: &str| {
+        let value = variables.get(name).cloned();
+        if value.is_none() {
+            missing.insert(name.to_string());
+        }
+        value
+    };
+
+    let whole = text
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .filter(|name| !name.contains("{{") && !name.contains("}}"));
+    if let Some(name) = whole {
+        return lookup(name.trim()).unwrap_or(Value::Null);
+    }
+
+    let mut filled = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        filled.push_str(&rest[..start]);
+        match lookup(rest[start + 2..start + 2 + len].trim()) {
+            Some(Value::String(value)) => filled.push_str(&value),
+            Some(value) => filled.push_str(&value.to_string()),
+            None => {}
+        }
+        rest = &rest[start + 4 + len..];
+    }
+    filled.push_str(rest);
+    Value::String(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::AgentId;
+    use crate::store::{SledStore, Store};
+    use serde_json::json;
+
+    #[test]
+    fn test_node_templates() {
+        let store = SledStore::open_temporary().unwrap();
+        let project =
+            store.create_node(StateNode::new(NodeKind::Project, json!({"name": "state"})), AgentId::User).unwrap();
+
+        let template = NodeTemplate::new(
+            NodeKind::Task,
+            json!({"title": "{{title}}", "status": "todo", "estimate": "{{estimate}}", "note": "for {{ who }}"}),
+        )
+        .with_edge(EdgeKind::PartOf, "{{project}}")
+        .with_description("A task in a project");
+        assert_eq!(
+            template.variables().into_iter().collect::<Vec<_>>(),
+            vec!["estimate", "project", "title", "who"]
+        );
+        store.save_template("task", &template).unwrap();
+        assert!(store.save_template("two words", &template).is_err());
+
+        let mut variables = BTreeMap::new();
+        variables.insert("title".to_string(), json!("Ship templates"));
+        let err = store.apply_template("task", &variables, AgentId::Claude).unwrap_err();
+        assert!(err.to_string().contains("estimate, project, who"));
+
+        variables.insert("estimate".to_string(), json!(3));
+        variables.insert("who".to_string(), json!("agents"));
+        variables.insert("project".to_string(), json!(project.id.to_string()));
+        let (node, edges) = store.apply_template("task", &variables, AgentId::Claude).unwrap();
+        assert_eq!(
+            node.content,
+            json!({"title": "Ship templates", "status": "todo", "estimate": 3, "note": "for agents"})
+        );
+        assert_eq!(edges.len(), 1);
+        assert_eq!((edges[0].from, edges[0].to), (node.id, project.id));
+        assert_eq!(store.edges_to(project.id).unwrap().len(), 1);
+
+        // A dangling edge end is caught before the node is written
+        let before = store.list_nodes(Some(NodeKind::Task), 10).unwrap().len();
+        variables.insert("project".to_string(), json!(ulid::Ulid::new().to_string()));
+        assert!(store.apply_template("task", &variables, AgentId::Claude).is_err());
+        assert_eq!(store.list_nodes(Some(NodeKind::Task), 10).unwrap().len(), before);
+
+        assert!(store.delete_template("task").unwrap());
+        assert!(store.templates().unwrap().is_empty());
+    }
+}
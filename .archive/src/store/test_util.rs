@@ -0,0 +1,26 @@
+//! Graph-building shorthands shared by the store's tests
+
+use crate::schema::{AgentId, EdgeKind, NodeId, NodeKind, StateEdge, StateNode};
+
+use super::Store;
+
+/// `N` new nodes of `kind` with empty content
+pub(crate) fn nodes<const N: usize>(store: &impl Store, kind: NodeKind) -> [NodeId; N] {
+    std::array::from_fn(|_| {
+        store
+            .create_node(StateNode::new(kind.clone(), serde_json::json!({})), AgentId::User)
+            .unwrap()
+            .id
+    })
+}
+
+/// One new node of `kind` with empty content
+pub(crate) fn node(store: &impl Store, kind: NodeKind) -> NodeId {
+    let [id] = nodes(store, kind);
+    id
+}
+
+/// A default edge of `kind` from `from` to `to`
+pub(crate) fn link(store: &impl Store, from: NodeId, to: NodeId, kind: EdgeKind) -> StateEdge {
+    store.create_edge(StateEdge::new(from, to, kind), AgentId::User).unwrap()
+}
@@ -0,0 +1,135 @@
+//! Dependency order for scheduling
+//!
+//! `a blocks b` and `a enables b` both mean `a` has to happen first. Nodes
+//! come out in an order that respects every such edge, each with a level:
+//! nodes on the same level don't depend on each other and can run in
+//! parallel. Nodes on a cycle, or depending on one, can never be scheduled
+//! and are reported separately with the cycles responsible.
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::cycles::{self, Cycle};
+use super::{Result, SledStore, Store};
+use crate::schema::{EdgeKind, NodeId, NodeKind};
+
+/// Edge kinds that order nodes by default
+pub fn default_order_kinds() -> Vec<EdgeKind> {
+    vec![EdgeKind::Blocks, EdgeKind::Enables]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledNode {
+    pub node: NodeId,
+    pub kind: NodeKind,
+    /// 0 for nodes nothing precedes, else one more than the deepest predecessor
+    pub level: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TopoOrder {
+    pub order: Vec<ScheduledNode>,
+    /// Nodes on or behind a cycle
+    pub unsatisfiable: Vec<NodeId>,
+    pub cycles: Vec<Cycle>,
+}
+
+impl TopoOrder {
+    pub fn is_satisfiable(&self) -> bool {
+        self.unsatisfiable.is_empty()
+    }
+}
+
+/// Order nodes of `node_kinds` (all kinds when `None`) by edges of
+/// `edge_kinds` between them; ties break by node ID, so oldest first
+pub fn toposort(
+    store: &SledStore,
+    node_kinds: Option<&[NodeKind]>,
+    edge_kinds: &[EdgeKind],
+) -> Result<TopoOrder> {
+    let kinds: HashMap<NodeId, NodeKind> = store
+        .list_nodes(None, usize::MAX)?
+        .into_iter()
+        .filter(|n| node_kinds.map_or(true, |ks| ks.contains(&n.kind)))
+        .map(|n| (n.id, n.kind))
+        .collect();
+
+    let mut successors: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let mut pending: HashMap<NodeId, usize> = kinds.keys().map(|&id| (id, 0)).collect();
+    let mut seen = HashSet::new();
+    for edge in store.list_edges()? {
+        if !edge_kinds.contains(&edge.kind) || !kinds.contains_key(&edge.from) || !kinds.contains_key(&edge.to) {
+            continue;
+        }
+        // Parallel edges of different kinds are one dependency
+        if seen.insert((edge.from, edge.to)) {
+            successors.entry(edge.from).or_default().push(edge.to);
+            *pending.get_mut(&edge.to).expect("filtered above") += 1;
+        }
+    }
+
+    let mut levels: HashMap<NodeId, usize> = HashMap::new();
+    let mut ready: BTreeSet<NodeId> = pending.iter().filter(|(_, &n)| n == 0).map(|(&id, _)| id).collect();
+    let mut result = TopoOrder::default();
+    while let Some(node) = ready.pop_first() {
+        let level = levels.get(&node).copied().unwrap_or(0);
+        result.order.push(ScheduledNode { node, kind: kinds[&node].clone(), level });
+        for &next in successors.get(&node).into_iter().flatten() {
+            let entry = levels.entry(next).or_insert(0);
+            *entry = (*entry).max(level + 1);
+            let count = pending.get_mut(&next).expect("filtered above");
+            *count -= 1;
+            if *count == 0 {
+                ready.insert(next);
+            }
+        }
+    }
+
+    let stuck: HashSet<NodeId> = pending.into_iter().filter(|&(_, n)| n > 0).map(|(id, _)| id).collect();
+    if !stuck.is_empty() {
+        result.cycles = cycles::find_cycles(store, edge_kinds)?
+            .into_iter()
+            .filter(|c| c.nodes.iter().all(|id| stuck.contains(id)))
+            .collect();
+        result.unsatisfiable = stuck.into_iter().collect();
+        result.unsatisfiable.sort();
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::test_util::{link, node, nodes};
+
+    #[test]
+    fn test_toposort_levels_and_cycles() {
+        let store = SledStore::open_temporary().unwrap();
+        let [design, build, docs, ship] = nodes(&store, NodeKind::Task);
+        let note = node(&store, NodeKind::Insight);
+        link(&store, ship, note, EdgeKind::Blocks);
+        link(&store, build, ship, EdgeKind::Blocks);
+        link(&store, design, build, EdgeKind::Enables);
+        link(&store, design, docs, EdgeKind::Blocks);
+        link(&store, docs, ship, EdgeKind::Enables);
+
+        let tasks = [NodeKind::Task];
+        let sorted = toposort(&store, Some(&tasks), &default_order_kinds()).unwrap();
+        assert!(sorted.is_satisfiable());
+        let levels: HashMap<NodeId, usize> = sorted.order.iter().map(|s| (s.node, s.level)).collect();
+        assert_eq!(levels, HashMap::from([(design, 0), (build, 1), (docs, 1), (ship, 2)]));
+        assert_eq!(sorted.order[0].node, design);
+        assert_eq!(sorted.order[3].node, ship);
+
+        let [a, b, after] = nodes(&store, NodeKind::Task);
+        link(&store, a, b, EdgeKind::Blocks);
+        link(&store, b, a, EdgeKind::Blocks);
+        link(&store, b, after, EdgeKind::Enables);
+        let sorted = toposort(&store, Some(&tasks), &default_order_kinds()).unwrap();
+        assert_eq!(sorted.order.len(), 4);
+        let mut stuck = vec![a, b, after];
+        stuck.sort();
+        assert_eq!(sorted.unsatisfiable, stuck);
+        assert_eq!(sorted.cycles.len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+//! Picking the operation an undo or redo reverses
+//!
+//! An operation is an event and every event sharing its group. Undoing one
+//! logs compensating events, each recorded as reverting its original; a
+//! redo is the undo of an undo. An agent's undos walk back through its own
+//! operations, newest first, and its redos walk forward through its undos
+//! until it makes a change of its own.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::schema::{AgentId, EventId, StateEvent};
+
+/// Which way to revert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Revert {
+    Undo,
+    Redo,
+}
+
+/// An operation that was reverted and the compensating events recorded
+#[derive(Debug, Clone, Serialize)]
+pub struct Reversal {
+    /// The reverted events, newest first
+    pub reverted: Vec<StateEvent>,
+    /// Compensating events, in the order they were applied
+    pub recorded: Vec<StateEvent>,
+}
+
+/// Whether `id` undoes a change, following `reverts` (compensating event ->
+/// the event it reverts): an undo reverts an ordinary change or a redo
+fn is_undo(id: EventId, reverts: &HashMap<EventId, EventId>) -> bool {
+    let mut undo = false;
+    let mut current = id;
+    while let Some(&original) = reverts.get(&current) {
+        undo = !undo;
+        current = original;
+    }
+    undo
+}
+
+/// `agent`'s most recent operation to revert in `direction`, newest event
+/// first; `events` must be in chronological order
+pub(crate) fn last_operation<'a>(
+    events: &'a [StateEvent],
+    agent: &AgentId,
+    reverts: &HashMap<EventId, EventId>,
+    direction: Revert,
+) -> Option<Vec<&'a StateEvent>> {
+    let reverted: HashSet<EventId> = reverts.values().copied().collect();
+    let mut candidates = events.iter().rev().filter(|e| &e.agent == agent && !reverted.contains(&e.id));
+    let last = match direction {
+        Revert::Undo => candidates.find(|e| !is_undo(e.id, reverts))?,
+        // Redos are skipped so several undos redo in turn; any other
+        // change of the agent's ends the redo chain
+        Revert::Redo => candidates
+            .find(|e| !reverts.contains_key(&e.id) || is_undo(e.id, reverts))
+            .filter(|e| is_undo(e.id, reverts))?,
+    };
+    Some(match last.group {
+        Some(group) => events.iter().rev().filter(|e| e.group == Some(group)).collect(),
+        None => vec![last],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{NodeId, Operation, Target};
+
+    #[test]
+    fn test_undo_and_redo_walk_the_agent_history() {
+        let event = |agent: AgentId| StateEvent::new(agent, Operation::Update, Target::Node(NodeId::new()));
+        let (a, b, other) = (event(AgentId::Claude), event(AgentId::Claude), event(AgentId::User));
+        let mut events = vec![a.clone(), b.clone(), other];
+        let mut reverts = HashMap::new();
+        let pick = |events: &[StateEvent], reverts: &HashMap<_, _>, direction| {
+            last_operation(events, &AgentId::Claude, reverts, direction).map(|op| op[0].id)
+        };
+        assert_eq!(pick(&events, &reverts, Revert::Undo), Some(b.id));
+        assert_eq!(pick(&events, &reverts, Revert::Redo), None);
+
+        // Undo b, then a; redo brings back a first
+        let record = |events: &mut Vec<StateEvent>, reverts: &mut HashMap<_, _>, original: EventId| {
+            let compensating = event(AgentId::Claude);
+            reverts.insert(compensating.id, original);
+            events.push(compensating.clone());
+            compensating.id
+        };
+        let undo_b = record(&mut events, &mut reverts, b.id);
+        assert_eq!(pick(&events, &reverts, Revert::Undo), Some(a.id));
+        record(&mut events, &mut reverts, a.id);
+        assert_eq!(pick(&events, &reverts, Revert::Undo), None);
+        let undo_a = pick(&events, &reverts, Revert::Redo).unwrap();
+        let redo_a = record(&mut events, &mut reverts, undo_a);
+        assert_eq!(pick(&events, &reverts, Revert::Redo), Some(undo_b));
+
+        // A redo can itself be undone, and a new change ends the redo chain
+        assert_eq!(pick(&events, &reverts, Revert::Undo), Some(redo_a));
+        events.push(event(AgentId::Claude));
+        assert_eq!(pick(&events, &reverts, Revert::Redo), None);
+    }
+}
@@ -0,0 +1,392 @@
+//! Cross-reference extraction from markdown
+//!
+//! Ingested documents are scanned for headings, links, footnotes and code
+//! blocks. Links are resolved against existing nodes by URL or alias and
+//! turned into `References` edges; the heading outline is kept in the node's
+//! metadata so a single section can be retrieved later.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{Result, Store};
+use crate::schema::{AgentId, EdgeKind, NodeId, StateEdge, StateNode};
+
+/// Metadata key holding the heading outline of an ingested document
+pub const OUTLINE_KEY: &str = "outline";
+
+/// Metadata key holding the raw link targets of an ingested document
+pub const LINKS_KEY: &str = "links";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heading {
+    pub level: u8,
+    pub title: String,
+    /// GitHub-style slug, unique within the document
+    pub anchor: String,
+    /// Zero-based line number of the heading
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub text: String,
+    pub target: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Footnote {
+    pub label: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeBlock {
+    pub lang: Option<String>,
+    pub line: usize,
+    pub code: String,
+}
+
+/// Everything extracted from one markdown document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrossReferences {
+    pub headings: Vec<Heading>,
+    pub links: Vec<Link>,
+    pub footnotes: Vec<Footnote>,
+    pub code_blocks: Vec<CodeBlock>,
+}
+
+/// Parse headings, links, footnotes and fenced code blocks
+pub fn extract(markdown: &str) -> CrossReferences {
+    let mut refs = CrossReferences::default();
+    let mut slugs: HashMap<String, usize> = HashMap::new();
+    let mut fence: Option<(String, CodeBlock)> = None;
+
+    for (line_no, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+
+        if let Some((marker, mut block)) = fence.take() {
+            if trimmed.starts_with(marker.as_str()) {
+                refs.code_blocks.push(block);
+            } else {
+                if !block.code.is_empty() {
+                    block.code.push('\n');
+                }
+                block.code.push_str(line);
+                fence = Some((marker, block));
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let marker = trimmed[..3].to_string();
+            let lang = trimmed[3..].trim();
+            fence = Some((
+                marker,
+                CodeBlock {
+                    lang: (!lang.is_empty()).then(|| lang.to_string()),
+                    line: line_no,
+                    code: String::new(),
+                },
+            ));
+            continue;
+        }
+
+        if let Some(heading) = parse_heading(trimmed, line_no, &mut slugs) {
+            refs.headings.push(heading);
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("[^") {
+            if let Some((label, text)) = rest.split_once("]:") {
+                refs.footnotes.push(Footnote {
+                    label: label.to_string(),
+                    text: text.trim().to_string(),
+                });
+                continue;
+            }
+        }
+
+        // Reference-style definition: [label]: https://...
+        if let Some(rest) = trimmed.strip_prefix('[') {
+            if let Some((label, target)) = rest.split_once("]:") {
+                if !label.contains('[') {
+                    refs.links.push(Link {
+                        text: label.to_string(),
+                        target: target.split_whitespace().next().unwrap_or("").to_string(),
+                        line: line_no,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        parse_inline_links(line, line_no, &mut refs.links);
+    }
+
+    // An unterminated fence runs to the end of the document
+    if let Some((_, block)) = fence {
+        refs.code_blocks.push(block);
+    }
+
+    refs.links.retain(|link| !link.target.is_empty());
+    refs
+}
+
+fn parse_heading(line: &str, line_no: usize, slugs: &mut HashMap<String, usize>) -> Option<Heading> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    let title = line[level..].trim().trim_end_matches('#').trim().to_string();
+
+    let base = slugify(&title);
+    let seen = slugs.entry(base.clone()).or_insert(0);
+    let anchor = if *seen == 0 {
+        base
+    } else {
+        format!("{}-{}", base, seen)
+    };
+    *seen += 1;
+
+    Some(Heading {
+        level: level as u8,
+        title,
+        anchor,
+        line: line_no,
+    })
+}
+
+/// Inline `[text](target)`, autolinks `<https://...>` and wiki `[[target|text]]`
+fn parse_inline_links(line: &str, line_no: usize, links: &mut Vec<Link>) {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' if line[i..].starts_with("[[") => {
+                if let Some(end) = line[i + 2..].find("]]") {
+                    let inner = &line[i + 2..i + 2 + end];
+                    let (target, text) = inner.split_once('|').unwrap_or((inner, inner));
+                    links.push(Link {
+                        text: text.trim().to_string(),
+                        target: target.trim().to_string(),
+                        line: line_no,
+                    });
+                    i += end + 4;
+                    continue;
+                }
+            }
+            b'[' if !line[i..].starts_with("[^") && (i == 0 || bytes[i - 1] != b'!') => {
+                if let Some(close) = line[i..].find("](") {
+                    let text = &line[i + 1..i + close];
+                    let rest = &line[i + close + 2..];
+                    if let Some(end) = rest.find(')') {
+                        let target = rest[..end].split_whitespace().next().unwrap_or("");
+                        links.push(Link {
+                            text: text.to_string(),
+                            target: target.to_string(),
+                            line: line_no,
+                        });
+                        i += close + 2 + end + 1;
+                        continue;
+                    }
+                }
+            }
+            b'<' => {
+                if let Some(end) = line[i + 1..].find('>') {
+                    let target = &line[i + 1..i + 1 + end];
+                    if target.starts_with("http://") || target.starts_with("https://") {
+                        links.push(Link {
+                            text: target.to_string(),
+                            target: target.to_string(),
+                            line: line_no,
+                        });
+                        i += end + 2;
+                        continue;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// GitHub-style heading slug
+pub fn slugify(title: &str) -> String {
+    title
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '_' || c == '-' => Some(c),
+            ' ' => Some('-'),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Return the section under `anchor`, up to the next heading of the same or
+/// higher level
+pub fn section(markdown: &str, anchor: &str) -> Option<String> {
+    let headings = extract(markdown).headings;
+    let index = headings.iter().position(|h| h.anchor == anchor)?;
+    let heading = &headings[index];
+    let end = headings[index + 1..]
+        .iter()
+        .find(|h| h.level <= heading.level)
+        .map(|h| h.line);
+
+    let lines: Vec<&str> = markdown.lines().collect();
+    let end = end.unwrap_or(lines.len());
+    Some(lines[heading.line..end].join("\n").trim_end().to_string())
+}
+
+/// Result of ingesting a document
+#[derive(Debug, Clone)]
+pub struct Ingested {
+    pub node: StateNode,
+    pub references: Vec<StateEdge>,
+    /// Link targets that did not match any existing node
+    pub unresolved: Vec<String>,
+}
+
+/// Create a node for a markdown document and link it to the nodes it cites
+///
+/// The markdown is read from `content.text`. Link targets are matched
+/// against each existing node's `url` or `source` (in content or metadata)
+/// and its `alias`/`aliases` metadata.
+pub fn ingest_markdown<S: Store>(store: &S, mut node: StateNode, agent: AgentId) -> Result<Ingested> {
+    let markdown = node
+        .content
+        .get("text")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let refs = extract(&markdown);
+
+    node.metadata.insert(
+        OUTLINE_KEY.to_string(),
+        serde_json::to_value(&refs.headings).unwrap_or_default(),
+    );
+    node.metadata.insert(
+        LINKS_KEY.to_string(),
+        serde_json::to_value(refs.links.iter().map(|l| &l.target).collect::<Vec<_>>())
+            .unwrap_or_default(),
+    );
+
+    let lookup = reference_lookup(&store.list_nodes(None, usize::MAX)?);
+    let node = store.create_node(node, agent.clone())?;
+
+    let mut references = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut linked = Vec::new();
+    for link in &refs.links {
+        match lookup.get(&normalize_target(&link.target)) {
+            Some(&target) if target != node.id && !linked.contains(&target) => {
+                linked.push(target);
+                let edge = StateEdge::new(node.id, target, EdgeKind::References);
+                references.push(store.create_edge(edge, agent.clone())?);
+            }
+            Some(_) => {}
+            None => unresolved.push(link.target.clone()),
+        }
+    }
+
+    Ok(Ingested {
+        node,
+        references,
+        unresolved,
+    })
+}
+
+/// Map every URL and alias of existing nodes to the node
+fn reference_lookup(nodes: &[StateNode]) -> HashMap<String, NodeId> {
+    let mut lookup = HashMap::new();
+    for node in nodes {
+        for key in ["url", "source"] {
+            let values = [node.content.get(key), node.metadata.get(key)];
+            for value in values.into_iter().flatten() {
+                if let Some(s) = value.as_str() {
+                    lookup.insert(normalize_target(s), node.id);
+                }
+            }
+        }
+        if let Some(alias) = node.metadata.get("alias").and_then(|a| a.as_str()) {
+            lookup.insert(normalize_target(alias), node.id);
+        }
+        if let Some(aliases) = node.metadata.get("aliases").and_then(|a| a.as_array()) {
+            for alias in aliases.iter().filter_map(|a| a.as_str()) {
+                lookup.insert(normalize_target(alias), node.id);
+            }
+        }
+    }
+    lookup
+}
+
+/// Compare targets without fragments, trailing slashes or case
+fn normalize_target(target: &str) -> String {
+    let target = target.split('#').next().unwrap_or(target);
+    target.trim().trim_end_matches('/').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::NodeKind;
+    use crate::store::SledStore;
+
+    const DOC: &str = "# Intro\n\
+See [sled](https://sled.rs/) and [[storage-notes|the notes]].[^1]\n\
+\n\
+## Setup\n\
+```rust\n\
+// # not a heading\n\
+let db = sled::open(\"x\");\n\
+```\n\
+## Setup\n\
+More <https://example.com/a>.\n\
+# Appendix\n\
+[^1]: A footnote.\n";
+
+    #[test]
+    fn test_extract() {
+        let refs = extract(DOC);
+
+        let anchors: Vec<_> = refs.headings.iter().map(|h| h.anchor.as_str()).collect();
+        assert_eq!(anchors, ["intro", "setup", "setup-1", "appendix"]);
+
+        let targets: Vec<_> = refs.links.iter().map(|l| l.target.as_str()).collect();
+        assert_eq!(targets, ["https://sled.rs/", "storage-notes", "https://example.com/a"]);
+
+        assert_eq!(refs.footnotes[0].label, "1");
+        assert_eq!(refs.code_blocks.len(), 1);
+        assert_eq!(refs.code_blocks[0].lang.as_deref(), Some("rust"));
+
+        let setup = section(DOC, "setup").unwrap();
+        assert!(setup.starts_with("## Setup"));
+        assert!(setup.contains("sled::open"));
+        assert!(!setup.contains("example.com"));
+    }
+
+    #[test]
+    fn test_ingest_links_existing_nodes() {
+        let store = SledStore::open_temporary().unwrap();
+        let sled_page = store
+            .create_node(
+                StateNode::new(NodeKind::Context, serde_json::json!({"url": "https://sled.rs"})),
+                AgentId::User,
+            )
+            .unwrap();
+        let mut notes = StateNode::new(NodeKind::Insight, serde_json::json!({"text": "notes"}));
+        notes.metadata.insert("aliases".into(), serde_json::json!(["storage-notes"]));
+        let notes = store.create_node(notes, AgentId::User).unwrap();
+
+        let doc = StateNode::new(NodeKind::Context, serde_json::json!({"text": DOC}));
+        let ingested = ingest_markdown(&store, doc, AgentId::User).unwrap();
+
+        let targets: Vec<_> = ingested.references.iter().map(|e| e.to).collect();
+        assert_eq!(targets, [sled_page.id, notes.id]);
+        assert_eq!(ingested.unresolved, ["https://example.com/a"]);
+        assert_eq!(ingested.node.metadata[OUTLINE_KEY].as_array().unwrap().len(), 4);
+    }
+}
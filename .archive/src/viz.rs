@@ -0,0 +1,433 @@
+//! Graph diagrams for standard tooling
+//!
+//! Renders nodes and the edges among them as Graphviz DOT or as a Mermaid
+//! flowchart, which GitHub and Obsidian draw inline in markdown. Node kinds
+//! get their own shape and colour and edge kinds become edge labels. A node
+//! is labelled with its `title`, `name` or `text` field when it has one,
+//! else with its kind and ID. GraphML is for analysis rather than drawing:
+//! it carries node metadata as attributes and edge weights, and loads
+//! directly into Gephi or Cytoscape. Custom kinds registered with a colour
+//! are filled with it when rendered through `render_with_kinds`.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::schema::{EdgeKind, NodeKind, StateEdge, StateNode};
+use crate::store::KindRegistry;
+
+/// Characters of content shown in a node label
+const LABEL_CHARS: usize = 40;
+
+/// Mermaid lays labels out on one line, so they're kept shorter
+const MERMAID_LABEL_CHARS: usize = 30;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagramFormat {
+    #[default]
+    Dot,
+    Mermaid,
+    GraphMl,
+}
+
+impl std::fmt::Display for DiagramFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            DiagramFormat::Dot => "dot",
+            DiagramFormat::Mermaid => "mermaid",
+            DiagramFormat::GraphMl => "graphml",
+        })
+    }
+}
+
+impl std::str::FromStr for DiagramFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(DiagramFormat::Dot),
+            "mermaid" | "mmd" => Ok(DiagramFormat::Mermaid),
+            "graphml" => Ok(DiagramFormat::GraphMl),
+            _ => Err(format!("Unknown diagram format: {}", s)),
+        }
+    }
+}
+
+/// Render `nodes` and the edges among them; edges to other nodes are left out
+pub fn render(format: DiagramFormat, nodes: &[StateNode], edges: &[StateEdge]) -> String {
+    render_with_kinds(format, nodes, edges, &KindRegistry::default())
+}
+
+/// `render`, colouring custom kinds as registered
+pub fn render_with_kinds(
+    format: DiagramFormat,
+    nodes: &[StateNode],
+    edges: &[StateEdge],
+    kinds: &KindRegistry,
+) -> String {
+    match format {
+        DiagramFormat::Dot => dot(nodes, edges, kinds),
+        DiagramFormat::Mermaid => mermaid(nodes, edges, kinds),
+        DiagramFormat::GraphMl => to_graphml(nodes, edges),
+    }
+}
+
+/// Short human-readable label for a node
+pub fn node_label(node: &StateNode) -> String {
+    truncated_label(node, LABEL_CHARS)
+}
+
+/// The node's text with whitespace runs collapsed, cut at `max` characters
+fn truncated_label(node: &StateNode, max: usize) -> String {
+    let text = ["title", "name", "text"]
+        .iter()
+        .find_map(|field| node.content.get(field).and_then(|v| v.as_str()))
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|text| !text.is_empty());
+    match text {
+        Some(text) if text.chars().count() > max => {
+            format!("{}…", text.chars().take(max).collect::<String>().trim_end())
+        }
+        Some(text) => text,
+        None => format!("{} {}", node.kind, node.id),
+    }
+}
+
+/// Graphviz shape and fill colour for a node kind
+fn dot_style<'a>(kind: &NodeKind, kinds: &'a KindRegistry) -> (&'static str, &'a str) {
+    if let Some(color) = kinds.node_info(kind).and_then(|info| info.color.as_deref()) {
+        return ("oval", color);
+    }
+    match kind {
+        NodeKind::Conversation => ("ellipse", "#cfe2ff"),
+        NodeKind::Project => ("folder", "#d1e7dd"),
+        NodeKind::Insight => ("note", "#fff3cd"),
+        NodeKind::Task => ("box", "#f8d7da"),
+        NodeKind::Context => ("component", "#e2e3e5"),
+        NodeKind::Module => ("box3d", "#e0cffc"),
+        NodeKind::Agent => ("hexagon", "#ffe5d0"),
+        NodeKind::Custom(_) => ("oval", "#ffffff"),
+    }
+}
+
+/// Graphviz line style for an edge kind
+fn dot_edge_style(kind: &EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Blocks => "bold",
+        EdgeKind::RelatedTo => "dashed",
+        EdgeKind::Supersedes => "dotted",
+        _ => "solid",
+    }
+}
+
+fn dot_quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn to_dot(nodes: &[StateNode], edges: &[StateEdge]) -> String {
+    dot(nodes, edges, &KindRegistry::default())
+}
+
+fn dot(nodes: &[StateNode], edges: &[StateEdge], kinds: &KindRegistry) -> String {
+    let ids: std::collections::HashSet<_> = nodes.iter().map(|n| n.id).collect();
+    let mut dot = String::from("digraph state {\n  rankdir=LR;\n  node [style=filled, fontname=\"Helvetica\"];\n  edge [fontname=\"Helvetica\", fontsize=10];\n");
+    for node in nodes {
+        let (shape, color) = dot_style(&node.kind, kinds);
+        let label = format!("{}\n({})", node_label(node), node.kind);
+        let _ = writeln!(
+            dot,
+            "  {} [label={}, shape={}, fillcolor={}];",
+            dot_quote(&node.id.to_string()),
+            dot_quote(&label),
+            shape,
+            dot_quote(color)
+        );
+    }
+    for edge in edges.iter().filter(|e| ids.contains(&e.from) && ids.contains(&e.to)) {
+        let _ = writeln!(
+            dot,
+            "  {} -> {} [label={}, style={}];",
+            dot_quote(&edge.from.to_string()),
+            dot_quote(&edge.to.to_string()),
+            dot_quote(&edge.kind.to_string()),
+            dot_edge_style(&edge.kind)
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Mermaid node shape around an already escaped label
+fn mermaid_shape(kind: &NodeKind, label: &str) -> String {
+    match kind {
+        NodeKind::Conversation => format!("([\"{}\"])", label),
+        NodeKind::Project => format!("[[\"{}\"]]", label),
+        NodeKind::Insight => format!(">\"{}\"]", label),
+        NodeKind::Task => format!("[\"{}\"]", label),
+        NodeKind::Context => format!("[(\"{}\")]", label),
+        NodeKind::Module => format!("[/\"{}\"/]", label),
+        NodeKind::Agent => format!("{{{{\"{}\"}}}}", label),
+        NodeKind::Custom(_) => format!("(\"{}\")", label),
+    }
+}
+
+/// Mermaid class name for a node kind's colour; registered custom kinds
+/// get a class of their own
+fn mermaid_class(kind: &NodeKind, kinds: &KindRegistry) -> String {
+    match kind {
+        NodeKind::Custom(name) if kinds.node_info(kind).is_some_and(|info| info.color.is_some()) => {
+            format!("custom_{}", name.replace('-', "_"))
+        }
+        NodeKind::Custom(_) => "custom".to_string(),
+        kind => kind.to_string(),
+    }
+}
+
+/// Escape text for a quoted Mermaid label or edge label
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '|' => escaped.push_str("#124;"),
+            '#' => escaped.push_str("#35;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn to_mermaid(nodes: &[StateNode], edges: &[StateEdge]) -> String {
+    mermaid(nodes, edges, &KindRegistry::default())
+}
+
+fn mermaid(nodes: &[StateNode], edges: &[StateEdge], kinds: &KindRegistry) -> String {
+    // ULIDs may start with a digit, which Mermaid won't take as an ID
+    let ids: std::collections::HashMap<_, _> = nodes.iter().map(|n| (n.id, format!("n{}", n.id))).collect();
+    let mut chart = String::from("flowchart LR\n");
+    let mut classes = std::collections::BTreeMap::new();
+    for node in nodes {
+        let label = mermaid_escape(&truncated_label(node, MERMAID_LABEL_CHARS));
+        let class = mermaid_class(&node.kind, kinds);
+        let _ = writeln!(chart, "  {}{}:::{}", ids[&node.id], mermaid_shape(&node.kind, &label), class);
+        classes.entry(class).or_insert_with(|| dot_style(&node.kind, kinds).1);
+    }
+    for edge in edges {
+        let (Some(from), Some(to)) = (ids.get(&edge.from), ids.get(&edge.to)) else {
+            continue;
+        };
+        let arrow = match edge.kind {
+            EdgeKind::RelatedTo | EdgeKind::Supersedes => "-.->",
+            EdgeKind::Blocks => "==>",
+            _ => "-->",
+        };
+        let _ = writeln!(chart, "  {} {}|{}| {}", from, arrow, mermaid_escape(&edge.kind.to_string()), to);
+    }
+    for (class, color) in classes {
+        let _ = writeln!(chart, "  classDef {} fill:{},stroke:#555", class, color);
+    }
+    chart
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// GraphML attribute type holding every value seen for a metadata field
+fn graphml_type<'a>(values: impl Iterator<Item = &'a Value>) -> &'static str {
+    let mut kind = None;
+    for value in values {
+        let this = match value {
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "double",
+            _ => return "string",
+        };
+        if kind.is_some_and(|k| k != this) {
+            return "string";
+        }
+        kind = Some(this);
+    }
+    kind.unwrap_or("string")
+}
+
+fn graphml_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => xml_escape(s),
+        value => xml_escape(&value.to_string()),
+    }
+}
+
+/// Declare one `<key>` per metadata field of `items`, returning field → key ID
+fn graphml_metadata_keys<'a>(
+    out: &mut String,
+    domain: &str,
+    prefix: &str,
+    metadata: impl Iterator<Item = &'a crate::schema::Metadata>,
+) -> BTreeMap<String, String> {
+    let mut fields: BTreeMap<&str, Vec<&Value>> = BTreeMap::new();
+    for meta in metadata {
+        for (field, value) in meta {
+            fields.entry(field).or_default().push(value);
+        }
+    }
+    let mut ids = BTreeMap::new();
+    for (i, (field, values)) in fields.into_iter().enumerate() {
+        let id = format!("{}{}", prefix, i);
+        let _ = writeln!(
+            out,
+            "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+            id,
+            domain,
+            xml_escape(field),
+            graphml_type(values.into_iter())
+        );
+        ids.insert(field.to_string(), id);
+    }
+    ids
+}
+
+pub fn to_graphml(nodes: &[StateNode], edges: &[StateEdge]) -> String {
+    let ids: std::collections::HashSet<_> = nodes.iter().map(|n| n.id).collect();
+    let edges: Vec<&StateEdge> = edges.iter().filter(|e| ids.contains(&e.from) && ids.contains(&e.to)).collect();
+
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <key id=\"created_at\" for=\"node\" attr.name=\"created_at\" attr.type=\"string\"/>\n",
+        "  <key id=\"updated_at\" for=\"node\" attr.name=\"updated_at\" attr.type=\"string\"/>\n",
+        "  <key id=\"edge_kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n",
+    ));
+    let node_keys = graphml_metadata_keys(&mut xml, "node", "nm", nodes.iter().map(|n| &n.metadata));
+    let edge_keys = graphml_metadata_keys(&mut xml, "edge", "em", edges.iter().map(|e| &e.metadata));
+    xml.push_str("  <graph id=\"state\" edgedefault=\"directed\">\n");
+
+    let data = |out: &mut String, key: &str, value: &str| {
+        let _ = writeln!(out, "      <data key=\"{}\">{}</data>", key, value);
+    };
+    for node in nodes {
+        let _ = writeln!(xml, "    <node id=\"{}\">", node.id);
+        data(&mut xml, "label", &xml_escape(&node_label(node)));
+        data(&mut xml, "kind", &xml_escape(&node.kind.to_string()));
+        data(&mut xml, "created_at", &node.created_at.to_rfc3339());
+        data(&mut xml, "updated_at", &node.updated_at.to_rfc3339());
+        for (field, value) in node.metadata.iter().collect::<BTreeMap<_, _>>() {
+            data(&mut xml, &node_keys[field], &graphml_value(value));
+        }
+        xml.push_str("    </node>\n");
+    }
+    for edge in edges {
+        let _ = writeln!(xml, "    <edge id=\"{}\" source=\"{}\" target=\"{}\">", edge.id, edge.from, edge.to);
+        data(&mut xml, "edge_kind", &xml_escape(&edge.kind.to_string()));
+        data(&mut xml, "weight", &edge.weight.to_string());
+        for (field, value) in edge.metadata.iter().collect::<BTreeMap<_, _>>() {
+            data(&mut xml, &edge_keys[field], &graphml_value(value));
+        }
+        xml.push_str("    </edge>\n");
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot_output() {
+        let task = StateNode::new(NodeKind::Task, serde_json::json!({"title": "Ship \"v2\""}));
+        let note = StateNode::new(NodeKind::Insight, serde_json::json!({"n": 1}));
+        let outside = StateNode::new(NodeKind::Task, serde_json::json!({}));
+        let edges = vec![
+            StateEdge::new(task.id, note.id, EdgeKind::Blocks),
+            StateEdge::new(task.id, outside.id, EdgeKind::References),
+        ];
+
+        let dot = render(DiagramFormat::Dot, &[task.clone(), note.clone()], &edges);
+        assert!(dot.starts_with("digraph state {"));
+        assert!(dot.contains(r#"label="Ship \"v2\"\n(task)", shape=box"#));
+        assert!(dot.contains(&format!("label=\"insight {}\\n(insight)\", shape=note", note.id)));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\" [label=\"blocks\", style=bold];", task.id, note.id)));
+        assert!(!dot.contains(&outside.id.to_string()));
+        assert_eq!(dot.matches(" -> ").count(), 1);
+    }
+
+    #[test]
+    fn test_mermaid_output() {
+        let long = "A rather long <title> that\nwill not fit on one line of the chart";
+        let task = StateNode::new(NodeKind::Task, serde_json::json!({"title": long}));
+        let agent = StateNode::new(NodeKind::Agent, serde_json::json!({"name": "claude"}));
+        let edges = vec![StateEdge::new(agent.id, task.id, EdgeKind::Blocks)];
+
+        let chart = render(DiagramFormat::Mermaid, &[task.clone(), agent.clone()], &edges);
+        assert!(chart.starts_with("flowchart LR\n"));
+        assert!(chart.contains(&format!(r#"n{}["A rather long #lt;title#gt; that wil…"]:::task"#, task.id)));
+        assert!(chart.contains(&format!(r#"n{}{{{{"claude"}}}}:::agent"#, agent.id)));
+        assert!(chart.contains(&format!("n{} ==>|blocks| n{}", agent.id, task.id)));
+        assert!(chart.contains("classDef task fill:#f8d7da"));
+    }
+
+    #[test]
+    fn test_registered_kind_colours() {
+        let mut kinds = KindRegistry::default();
+        let paper = kinds
+            .register_node("paper", crate::store::KindInfo::default().with_color("#a0c4ff"))
+            .unwrap();
+        let node = StateNode::new(paper, serde_json::json!({"title": "On Graphs"}));
+        let stray = StateNode::new(NodeKind::Custom("memo".into()), serde_json::json!({}));
+
+        let dot = render_with_kinds(DiagramFormat::Dot, &[node.clone(), stray.clone()], &[], &kinds);
+        assert!(dot.contains(r##"(custom:paper)", shape=oval, fillcolor="#a0c4ff"]"##));
+        assert!(dot.contains(r##"fillcolor="#ffffff""##));
+        let chart = render_with_kinds(DiagramFormat::Mermaid, &[node, stray], &[], &kinds);
+        assert!(chart.contains("classDef custom_paper fill:#a0c4ff"));
+        assert!(chart.contains("classDef custom fill:#ffffff"));
+    }
+
+    #[test]
+    fn test_graphml_output() {
+        let mut metadata = crate::schema::Metadata::new();
+        metadata.insert("score".into(), serde_json::json!(0.5));
+        metadata.insert("source".into(), serde_json::json!("R&D"));
+        let a = StateNode::new(NodeKind::Task, serde_json::json!({"title": "a"})).with_metadata(metadata);
+        let mut other = crate::schema::Metadata::new();
+        other.insert("score".into(), serde_json::json!(2));
+        let b = StateNode::new(NodeKind::Insight, serde_json::json!({})).with_metadata(other);
+        let mut edge = StateEdge::new(a.id, b.id, EdgeKind::DerivedFrom);
+        edge.weight = 2.5;
+
+        let xml = render(DiagramFormat::GraphMl, &[a.clone(), b.clone()], &[edge.clone()]);
+        assert!(xml.contains(r#"<key id="nm0" for="node" attr.name="score" attr.type="double"/>"#));
+        assert!(xml.contains(r#"<key id="nm1" for="node" attr.name="source" attr.type="string"/>"#));
+        assert!(xml.contains(r#"<data key="nm1">R&amp;D</data>"#));
+        assert!(xml.contains(&format!(r#"<edge id="{}" source="{}" target="{}">"#, edge.id, a.id, b.id)));
+        assert!(xml.contains(r#"<data key="weight">2.5</data>"#));
+        assert!(xml.trim_end().ends_with("</graphml>"));
+    }
+}
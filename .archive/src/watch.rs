@@ -0,0 +1,257 @@
+//! Watching a server's event log for events a human cares about
+//!
+//! `state-cli watch` polls a running server's `events` query and prints the
+//! events matching any of its filters. Each filter chooses how it gets
+//! attention: a terminal bell, a desktop notification (with the `notify`
+//! feature), both, or neither. Filters are given inline,
+//!
+//! ```text
+//! votes:op=create,text=proposal,alert=desktop+bell
+//! ```
+//!
+//! or as a JSON array of the same fields in a file.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::graphql::client::GraphqlClient;
+
+/// Events fetched per poll; more arriving between polls are missed
+pub const POLL_LIMIT: usize = 200;
+
+const EVENTS_QUERY: &str = "query Watch($limit: Int!) { events(limit: $limit) { id timestamp agent operation before after } }";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Alert {
+    #[default]
+    Bell,
+    Desktop,
+}
+
+impl std::fmt::Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Alert::Bell => "bell",
+            Alert::Desktop => "desktop",
+        })
+    }
+}
+
+impl std::str::FromStr for Alert {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bell" => Ok(Alert::Bell),
+            "desktop" | "notify" => Ok(Alert::Desktop),
+            _ => Err(format!("Unknown alert: {}", s)),
+        }
+    }
+}
+
+/// An event as the server's `events` query returns it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedEvent {
+    pub id: String,
+    pub timestamp: String,
+    pub agent: String,
+    pub operation: String,
+    #[serde(default)]
+    pub before: Option<Value>,
+    #[serde(default)]
+    pub after: Option<Value>,
+}
+
+impl WatchedEvent {
+    /// One-line summary, naming the node or edge when the payload has one
+    pub fn summary(&self) -> String {
+        let payload = self.after.as_ref().or(self.before.as_ref());
+        let target = payload
+            .and_then(|p| p.get("id"))
+            .and_then(|id| id.as_str())
+            .map(|id| format!(" {}", id))
+            .unwrap_or_default();
+        format!("{}{} by {}", self.operation, target, self.agent)
+    }
+}
+
+/// Which events to report, and how loudly; empty criteria match anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchFilter {
+    pub name: String,
+    /// Operations (`create`, `update`, `delete`, `link`, `unlink`)
+    pub operations: Vec<String>,
+    /// Agents (`claude`, `module:coordinator`)
+    pub agents: Vec<String>,
+    /// Text that must appear in the event's before or after payload
+    pub text: Option<String>,
+    pub alerts: BTreeSet<Alert>,
+}
+
+impl WatchFilter {
+    pub fn matches(&self, event: &WatchedEvent) -> bool {
+        let any_of = |wanted: &[String], actual: &str| {
+            wanted.is_empty() || wanted.iter().any(|w| w.eq_ignore_ascii_case(actual))
+        };
+        if !any_of(&self.operations, &event.operation) || !any_of(&self.agents, &event.agent) {
+            return false;
+        }
+        match &self.text {
+            None => true,
+            Some(text) => {
+                let text = text.to_lowercase();
+                [&event.before, &event.after]
+                    .into_iter()
+                    .flatten()
+                    .any(|payload| payload.to_string().to_lowercase().contains(&text))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for WatchFilter {
+    type Err = String;
+
+    /// `[name:]key=value,...` with keys `op`, `agent`, `text` and `alert`;
+    /// `op` and `agent` take `a|b` alternatives, `alert` takes `bell`,
+    /// `desktop`, `desktop+bell` or `none`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, spec) = match s.split_once(':') {
+            Some((name, spec)) if !name.contains('=') => (name.to_string(), spec),
+            _ => (String::new(), s),
+        };
+        let mut filter = WatchFilter { name, alerts: BTreeSet::from([Alert::Bell]), ..Default::default() };
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid filter part (expected key=value): {}", part))?;
+            let alternatives = || value.split('|').map(|v| v.trim().to_string()).collect();
+            match key.trim() {
+                "op" | "operation" => filter.operations = alternatives(),
+                "agent" => filter.agents = alternatives(),
+                "text" => filter.text = Some(value.to_string()),
+                "alert" if value == "none" => filter.alerts.clear(),
+                "alert" => {
+                    filter.alerts = value.split('+').map(str::parse).collect::<Result<_, _>>()?;
+                }
+                other => return Err(format!("Unknown filter key: {}", other)),
+            }
+        }
+        if filter.name.is_empty() {
+            filter.name = spec.to_string();
+        }
+        Ok(filter)
+    }
+}
+
+/// Polls a server for new events
+pub struct Watcher {
+    client: GraphqlClient,
+    /// Newest event already seen; `None` until the first poll
+    last_seen: Option<String>,
+}
+
+impl Watcher {
+    pub fn new(client: GraphqlClient) -> Self {
+        Self { client, last_seen: None }
+    }
+
+    /// Events since the previous poll, oldest first
+    ///
+    /// The first poll only notes where the log ends, so a new watcher
+    /// doesn't replay history.
+    pub fn poll(&mut self) -> Result<Vec<WatchedEvent>, String> {
+        let response = self.client.execute(EVENTS_QUERY, Some(serde_json::json!({"limit": POLL_LIMIT})), None)?;
+        if let Some(errors) = response.get("errors").filter(|e| !e.is_null()) {
+            return Err(errors.to_string());
+        }
+        let events: Vec<WatchedEvent> = serde_json::from_value(response["data"]["events"].clone())
+            .map_err(|e| format!("Unexpected events response: {}", e))?;
+        Ok(self.advance(events))
+    }
+
+    /// Keep the events after `last_seen`, given newest first
+    fn advance(&mut self, events: Vec<WatchedEvent>) -> Vec<WatchedEvent> {
+        let Some(newest) = events.first().map(|e| e.id.clone()) else {
+            return Vec::new();
+        };
+        let fresh: Vec<WatchedEvent> = match self.last_seen.replace(newest) {
+            // Event IDs are ULIDs, so they sort by time as strings
+            Some(last) => events.into_iter().take_while(|e| e.id > last).collect(),
+            None => Vec::new(),
+        };
+        fresh.into_iter().rev().collect()
+    }
+}
+
+/// Get the watcher's attention: ring the terminal bell and/or raise a
+/// desktop notification
+pub fn alert(alerts: &BTreeSet<Alert>, title: &str, body: &str) {
+    if alerts.contains(&Alert::Bell) {
+        use std::io::Write;
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+    if alerts.contains(&Alert::Desktop) {
+        desktop_notification(title, body);
+    }
+}
+
+#[cfg(feature = "notify")]
+fn desktop_notification(title: &str, body: &str) {
+    if let Err(e) = notify_rust::Notification::new().summary(title).body(body).appname("elegant-state").show() {
+        tracing::warn!("desktop notification failed: {}", e);
+    }
+}
+
+#[cfg(not(feature = "notify"))]
+fn desktop_notification(_title: &str, _body: &str) {
+    tracing::warn!("desktop notifications need the `notify` feature");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, operation: &str, agent: &str, after: Value) -> WatchedEvent {
+        WatchedEvent {
+            id: id.into(),
+            timestamp: "2024-05-01T00:00:00+00:00".into(),
+            agent: agent.into(),
+            operation: operation.into(),
+            before: None,
+            after: Some(after),
+        }
+    }
+
+    #[test]
+    fn test_filters_and_polling() {
+        let filter: WatchFilter = "votes:op=Create|Update,agent=module:coordinator,text=PROPOSAL,alert=desktop+bell"
+            .parse()
+            .unwrap();
+        assert_eq!(filter.name, "votes");
+        assert_eq!(filter.alerts, BTreeSet::from([Alert::Bell, Alert::Desktop]));
+        let proposal = serde_json::json!({"id": "01N", "content": {"type": "proposal"}});
+        assert!(filter.matches(&event("2", "Create", "module:coordinator", proposal.clone())));
+        assert!(!filter.matches(&event("2", "Delete", "module:coordinator", proposal.clone())));
+        assert!(!filter.matches(&event("2", "Create", "claude", proposal)));
+        assert!(!filter.matches(&event("2", "Create", "module:coordinator", serde_json::json!({}))));
+
+        let quiet: WatchFilter = "agent=claude,alert=none".parse().unwrap();
+        assert_eq!((quiet.name.as_str(), quiet.alerts.is_empty()), ("agent=claude,alert=none", true));
+        assert!("op=create,colour=red".parse::<WatchFilter>().is_err());
+        assert!("alert=siren".parse::<WatchFilter>().is_err());
+
+        let mut watcher = Watcher::new(GraphqlClient::new("http://127.0.0.1:1/graphql"));
+        let e = |id: &str| event(id, "Create", "user", serde_json::json!({"id": id}));
+        assert!(watcher.advance(vec![e("01B"), e("01A")]).is_empty());
+        let fresh = watcher.advance(vec![e("01D"), e("01C"), e("01B"), e("01A")]);
+        assert_eq!(fresh.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["01C", "01D"]);
+        assert_eq!(fresh[0].summary(), "Create 01C by user");
+        assert!(watcher.advance(vec![e("01D")]).is_empty());
+    }
+}
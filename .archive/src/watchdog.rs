@@ -0,0 +1,331 @@
+//! Automatic downgrades for misbehaving module agents
+//!
+//! While `serve http` runs, the watchdog looks back over a sliding window
+//! at what each `module:*` agent did: events it logged (bursts of writes or
+//! deletes), requests the rate limit refused and mutations its capability
+//! mode refused. An agent over any threshold of the `watchdog` config
+//! section is demoted, by default from Direct to Proposal, an alert goes to
+//! the log and to webhooks subscribed to `watchdog`, and the configuration
+//! is reloaded so the demotion applies at once. Demotions are kept in the
+//! database and outrank the capability policy until `agent reinstate`
+//! lifts them; the watchdog never promotes anyone.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::coordinator::{CapabilityConfig, CapabilityMode};
+use crate::schema::{AgentId, Operation};
+use crate::server_config::LiveConfig;
+use crate::store::{Result, SledStore, Store, StoreError};
+
+/// Metadata key holding the agents the watchdog has demoted
+pub const DEMOTIONS_KEY: &str = "watchdog_demotions";
+
+/// Thresholds per window; an unset threshold is never tripped
+///
+/// Each `max_*` is the most allowed: reaching it is fine, going one over
+/// trips the watchdog.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchdogPolicy {
+    /// Seconds of activity each check looks back over
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Most events an agent may log in a window
+    #[serde(default)]
+    pub max_writes: Option<u32>,
+    /// Most node deletes and unlinks in a window
+    #[serde(default)]
+    pub max_deletes: Option<u32>,
+    /// Most requests refused by the rate limit in a window
+    #[serde(default)]
+    pub max_rate_limited: Option<u32>,
+    /// Most mutations refused by capability policy in a window
+    #[serde(default)]
+    pub max_denied: Option<u32>,
+    /// Mode a tripped agent drops to
+    #[serde(default = "default_demote_to")]
+    pub demote_to: CapabilityMode,
+}
+
+fn default_window_secs() -> u64 {
+    60
+}
+
+fn default_demote_to() -> CapabilityMode {
+    CapabilityMode::Proposal
+}
+
+impl Default for WatchdogPolicy {
+    fn default() -> Self {
+        Self {
+            window_secs: default_window_secs(),
+            max_writes: None,
+            max_deletes: None,
+            max_rate_limited: None,
+            max_denied: None,
+            demote_to: default_demote_to(),
+        }
+    }
+}
+
+impl WatchdogPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_writes.is_some()
+            || self.max_deletes.is_some()
+            || self.max_rate_limited.is_some()
+            || self.max_denied.is_some()
+    }
+
+    /// The first threshold `activity` exceeds, described
+    fn tripped(&self, activity: &Activity) -> Option<String> {
+        [
+            (self.max_writes, activity.writes, "writes"),
+            (self.max_deletes, activity.deletes, "deletes"),
+            (self.max_rate_limited, activity.rate_limited, "rate-limited requests"),
+            (self.max_denied, activity.denied, "denied mutations"),
+        ]
+        .into_iter()
+        .find_map(|(limit, count, what)| {
+            let limit = limit?;
+            (count > limit).then(|| format!("{} {} in {}s (limit {})", count, what, self.window_secs, limit))
+        })
+    }
+}
+
+/// A request the server turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    RateLimited,
+    Denied,
+}
+
+/// An agent held below its configured mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Demotion {
+    pub agent: String,
+    pub from: CapabilityMode,
+    pub to: CapabilityMode,
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Demoted agents, by name
+pub fn demotions(store: &SledStore) -> Result<BTreeMap<String, Demotion>> {
+    match store.get_meta(DEMOTIONS_KEY)? {
+        Some(value) => serde_json::from_value(value).map_err(|e| StoreError::Serialization(e.to_string())),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+fn save_demotions(store: &SledStore, demotions: &BTreeMap<String, Demotion>) -> Result<()> {
+    let value = serde_json::to_value(demotions).map_err(|e| StoreError::Serialization(e.to_string()))?;
+    store.set_meta(DEMOTIONS_KEY, &value)
+}
+
+/// Lift `agent`'s demotion, returning it if there was one
+pub fn reinstate(store: &SledStore, agent: &str) -> Result<Option<Demotion>> {
+    let mut all = demotions(store)?;
+    let lifted = all.remove(agent);
+    if lifted.is_some() {
+        save_demotions(store, &all)?;
+    }
+    Ok(lifted)
+}
+
+/// How restrictive a mode is; demotions only ever move up this scale
+fn rank(mode: CapabilityMode) -> u8 {
+    match mode {
+        CapabilityMode::Direct => 0,
+        CapabilityMode::Proposal => 1,
+        CapabilityMode::Observer => 2,
+    }
+}
+
+/// Hold each demoted agent at its demotion's mode or below
+pub fn apply_demotions(capabilities: &mut CapabilityConfig, demotions: &BTreeMap<String, Demotion>) {
+    for demotion in demotions.values() {
+        let Ok(agent) = demotion.agent.parse::<AgentId>() else {
+            continue;
+        };
+        let mut agent_caps = capabilities.get_capabilities(&agent);
+        if rank(agent_caps.mode) < rank(demotion.to) {
+            agent_caps.mode = demotion.to;
+            // Demotions bypass `allow_runtime_changes`; they are the policy's own enforcement
+            capabilities.agent_overrides.insert(demotion.agent.clone(), agent_caps);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Activity {
+    writes: u32,
+    deletes: u32,
+    rate_limited: u32,
+    denied: u32,
+}
+
+/// Refusals per agent with when they happened, oldest first
+type RefusalLog = HashMap<String, Vec<(DateTime<Utc>, Refusal)>>;
+
+pub struct Watchdog {
+    store: Arc<SledStore>,
+    live: Arc<LiveConfig>,
+    /// Recent refusals
+    refusals: Mutex<RefusalLog>,
+}
+
+impl Watchdog {
+    pub fn new(store: Arc<SledStore>, live: Arc<LiveConfig>) -> Self {
+        Self { store, live, refusals: Mutex::new(HashMap::new()) }
+    }
+
+    /// Note a refused request; only module agents are tracked
+    pub fn record_refusal(&self, agent: &str, refusal: Refusal) {
+        if matches!(agent.parse::<AgentId>(), Ok(AgentId::Module(_))) {
+            self.refusals.lock().unwrap().entry(agent.to_string()).or_default().push((Utc::now(), refusal));
+        }
+    }
+
+    /// Demote every module agent over a threshold in the window ending at
+    /// `now`, returning the new demotions
+    pub fn check(&self, now: DateTime<Utc>) -> Result<Vec<Demotion>> {
+        let config = self.live.current();
+        let policy = &config.watchdog;
+        if !policy.is_enabled() {
+            return Ok(Vec::new());
+        }
+        let since = now - Duration::seconds(policy.window_secs as i64);
+
+        let mut activity: BTreeMap<String, Activity> = BTreeMap::new();
+        for event in self.store.get_events(Some(since), usize::MAX)? {
+            if !matches!(event.agent, AgentId::Module(_)) {
+                continue;
+            }
+            let counts = activity.entry(event.agent.to_string()).or_default();
+            counts.writes += 1;
+            if matches!(event.operation, Operation::Delete | Operation::Unlink) {
+                counts.deletes += 1;
+            }
+        }
+        {
+            let mut refusals = self.refusals.lock().unwrap();
+            refusals.retain(|_, recent| {
+                recent.retain(|(at, _)| *at >= since);
+                !recent.is_empty()
+            });
+            for (agent, recent) in refusals.iter() {
+                let counts = activity.entry(agent.clone()).or_default();
+                for (_, refusal) in recent {
+                    match refusal {
+                        Refusal::RateLimited => counts.rate_limited += 1,
+                        Refusal::Denied => counts.denied += 1,
+                    }
+                }
+            }
+        }
+
+        let mut all = demotions(&self.store)?;
+        let mut demoted = Vec::new();
+        for (agent, counts) in &activity {
+            let Some(reason) = policy.tripped(counts) else {
+                continue;
+            };
+            let Ok(agent_id) = agent.parse::<AgentId>() else {
+                continue;
+            };
+            let from = config.capabilities.get_capabilities(&agent_id).mode;
+            if all.contains_key(agent) || rank(from) >= rank(policy.demote_to) {
+                continue;
+            }
+            let demotion =
+                Demotion { agent: agent.clone(), from, to: policy.demote_to, reason, at: now };
+            all.insert(agent.clone(), demotion.clone());
+            demoted.push(demotion);
+        }
+        if demoted.is_empty() {
+            return Ok(demoted);
+        }
+
+        save_demotions(&self.store, &all)?;
+        for demotion in &demoted {
+            tracing::warn!(
+                "watchdog demoted {} from {} to {}: {}",
+                demotion.agent,
+                demotion.from,
+                demotion.to,
+                demotion.reason
+            );
+            config.alert("watchdog", serde_json::to_value(demotion).unwrap_or_default());
+        }
+        self.live.reload("watchdog")?;
+        Ok(demoted)
+    }
+}
+
+/// Spawn a tokio task that runs the watchdog's checks every `interval`
+pub fn spawn_watchdog(watchdog: Arc<Watchdog>, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = watchdog.check(Utc::now()) {
+                tracing::warn!("watchdog check failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{NodeKind, StateNode};
+
+    #[test]
+    fn test_watchdog_demotes_until_reinstated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "capabilities": {"default_mode": "direct", "agent_overrides": {}, "allow_runtime_changes": false},
+                "watchdog": {"max_writes": 3, "max_denied": 1}
+            }"#,
+        )
+        .unwrap();
+        let store = Arc::new(SledStore::open_temporary().unwrap());
+        let live = Arc::new(LiveConfig::load(store.clone(), Some(path)).unwrap());
+        let watchdog = Watchdog::new(store.clone(), live.clone());
+
+        let scraper = AgentId::Module("scraper".into());
+        // At the write threshold and one denial: both still allowed
+        for _ in 0..3 {
+            store.create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), scraper.clone()).unwrap();
+            store.create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), AgentId::Claude).unwrap();
+        }
+        watchdog.record_refusal("module:crawler", Refusal::Denied);
+        assert!(watchdog.check(Utc::now()).unwrap().is_empty());
+
+        // Over the write threshold; claude isn't a module agent
+        store.create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), scraper.clone()).unwrap();
+        watchdog.record_refusal("module:crawler", Refusal::Denied);
+        let demoted = watchdog.check(Utc::now()).unwrap();
+        assert_eq!(demoted.iter().map(|d| d.agent.as_str()).collect::<Vec<_>>(), ["module:crawler", "module:scraper"]);
+        assert_eq!(demoted[1].from, CapabilityMode::Direct);
+        assert!(demoted[1].reason.contains("4 writes"));
+        let config = live.current();
+        assert!(!config.capabilities.can_write_directly(&scraper));
+        assert!(config.capabilities.can_write_directly(&AgentId::Claude));
+
+        // Still demoted after a reload, and not demoted twice
+        live.reload("sighup").unwrap();
+        assert!(!live.current().capabilities.can_write_directly(&scraper));
+        assert!(watchdog.check(Utc::now()).unwrap().is_empty());
+
+        assert!(reinstate(&store, "module:scraper").unwrap().is_some());
+        assert!(reinstate(&store, "module:scraper").unwrap().is_none());
+        live.reload("endpoint").unwrap();
+        assert!(live.current().capabilities.can_write_directly(&scraper));
+    }
+}
@@ -0,0 +1,172 @@
+//! Project context from the working directory
+//!
+//! Run inside a directory tree holding a `.elegant-state` marker file, or
+//! inside a git repository, the CLI scopes its work to that tree's Project
+//! node: new nodes are linked `part_of` it and listings only show what
+//! belongs to it. The nearest marker or `.git` above the working directory
+//! wins. The Project node is found by its `workspace` metadata (the tree's
+//! root path) and created on first use.
+//!
+//! The marker may be empty, or JSON naming the project and optionally
+//! pinning an existing node:
+//!
+//! ```json
+//! {"name": "elegant-state", "project": "01J..."}
+//! ```
+
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::schema::{AgentId, EdgeKind, Metadata, NodeId, NodeKind, StateEdge, StateNode};
+use crate::store::{MetaQuery, Result, SledStore, Store, StoreError};
+
+/// File marking a directory tree as a workspace
+pub const MARKER_FILE: &str = ".elegant-state";
+
+/// Metadata field holding a Project node's workspace root
+pub const WORKSPACE_KEY: &str = "workspace";
+
+/// Optional contents of a marker file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Marker {
+    pub name: Option<String>,
+    /// Use this node rather than looking the project up by path
+    pub project: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkspaceSource {
+    Marker,
+    Git,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub name: String,
+    pub source: WorkspaceSource,
+    pub marker: Marker,
+}
+
+impl Workspace {
+    /// The workspace containing `dir`, if any
+    pub fn detect(dir: &Path) -> std::io::Result<Option<Self>> {
+        for root in dir.ancestors() {
+            let marker = root.join(MARKER_FILE);
+            let (source, marker) = if marker.is_file() {
+                (WorkspaceSource::Marker, read_marker(&marker)?)
+            } else if root.join(".git").exists() {
+                (WorkspaceSource::Git, Marker::default())
+            } else {
+                continue;
+            };
+            let name = marker.name.clone().unwrap_or_else(|| {
+                root.file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| root.display().to_string())
+            });
+            return Ok(Some(Workspace { root: root.to_path_buf(), name, source, marker }));
+        }
+        Ok(None)
+    }
+
+    fn root_key(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    /// The workspace's Project node, if it exists yet
+    pub fn find_project(&self, store: &SledStore) -> Result<Option<StateNode>> {
+        if let Some(id) = self.marker.project {
+            return store.get_node(id)?.ok_or(StoreError::NodeNotFound(id)).map(Some);
+        }
+        let query = MetaQuery::Equals(self.root_key());
+        let mut found = store.find_by_metadata(WORKSPACE_KEY, &query, Some(vec![NodeKind::Project]))?;
+        found.sort_by_key(|n| n.id);
+        Ok(found.into_iter().next())
+    }
+
+    /// The workspace's Project node, created if missing
+    pub fn project(&self, store: &SledStore, agent: AgentId) -> Result<StateNode> {
+        if let Some(project) = self.find_project(store)? {
+            return Ok(project);
+        }
+        let mut metadata = Metadata::new();
+        metadata.insert(WORKSPACE_KEY.into(), self.root_key().into());
+        let project = StateNode::new(
+            NodeKind::Project,
+            serde_json::json!({"name": self.name, "path": self.root_key()}),
+        )
+        .with_metadata(metadata);
+        store.create_node(project, agent)
+    }
+}
+
+fn read_marker(path: &Path) -> std::io::Result<Marker> {
+    let text = std::fs::read_to_string(path)?;
+    if text.trim().is_empty() {
+        return Ok(Marker::default());
+    }
+    serde_json::from_str(&text).map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+    })
+}
+
+/// Link `node` into `project`
+pub fn attach(store: &SledStore, node: NodeId, project: NodeId, agent: AgentId) -> Result<StateEdge> {
+    store.create_edge(StateEdge::new(node, project, EdgeKind::PartOf), agent)
+}
+
+/// The project and every node that is, directly or transitively, part of it
+pub fn project_scope(store: &SledStore, project: NodeId) -> Result<HashSet<NodeId>> {
+    let mut scope = HashSet::from([project]);
+    let mut queue = VecDeque::from([project]);
+    while let Some(node) = queue.pop_front() {
+        for edge in store.edges_to(node)? {
+            if edge.kind == EdgeKind::PartOf && scope.insert(edge.from) {
+                queue.push_back(edge.from);
+            }
+        }
+    }
+    Ok(scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_detection_and_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let nested = repo.join("src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert!(Workspace::detect(&nested).unwrap().is_none());
+
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        let workspace = Workspace::detect(&nested).unwrap().unwrap();
+        assert_eq!(workspace.source, WorkspaceSource::Git);
+        assert_eq!((workspace.root.as_path(), workspace.name.as_str()), (repo.as_path(), "repo"));
+
+        std::fs::write(repo.join("src").join(MARKER_FILE), r#"{"name": "core"}"#).unwrap();
+        let inner = Workspace::detect(&nested).unwrap().unwrap();
+        assert_eq!((inner.source, inner.name.as_str()), (WorkspaceSource::Marker, "core"));
+
+        let store = SledStore::open_temporary().unwrap();
+        assert!(workspace.find_project(&store).unwrap().is_none());
+        let project = workspace.project(&store, AgentId::User).unwrap();
+        assert_eq!(workspace.project(&store, AgentId::User).unwrap().id, project.id);
+        assert_ne!(inner.project(&store, AgentId::User).unwrap().id, project.id);
+
+        let note = store.create_node(StateNode::new(NodeKind::Insight, serde_json::json!({})), AgentId::User).unwrap();
+        let part = store.create_node(StateNode::new(NodeKind::Context, serde_json::json!({})), AgentId::User).unwrap();
+        let stray = store.create_node(StateNode::new(NodeKind::Task, serde_json::json!({})), AgentId::User).unwrap();
+        attach(&store, note.id, project.id, AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(part.id, note.id, EdgeKind::PartOf), AgentId::User).unwrap();
+        store.create_edge(StateEdge::new(stray.id, note.id, EdgeKind::RelatedTo), AgentId::User).unwrap();
+        assert_eq!(
+            project_scope(&store, project.id).unwrap(),
+            HashSet::from([project.id, note.id, part.id])
+        );
+    }
+}